@@ -0,0 +1,47 @@
+/*
+ * C interface to the Rustic compiler, built by the `capi` crate as
+ * librustic_capi (shared or static).
+ *
+ * Strings are NUL-terminated UTF-8. Every string returned by these functions
+ * belongs to the caller and must be released with rustic_free.
+ */
+#ifndef RUSTIC_H
+#define RUSTIC_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* rustic_check results. */
+#define RUSTIC_OK 0
+#define RUSTIC_ERROR 1
+#define RUSTIC_INVALID_ARGUMENT (-1)
+
+/*
+ * Compiles `source` to Rust code. `module_name` names the generated module
+ * and the file in diagnostic spans. Returns NULL on failure; the reasons are
+ * in rustic_last_diagnostics_json().
+ */
+char *rustic_compile(const char *source, const char *module_name);
+
+/*
+ * Lexes, parses and type checks `source` without generating code. Returns
+ * RUSTIC_OK, RUSTIC_ERROR or RUSTIC_INVALID_ARGUMENT.
+ */
+int rustic_check(const char *source, const char *file_name);
+
+/*
+ * Diagnostics of the last rustic_compile or rustic_check call on the calling
+ * thread, as a JSON array of objects with `level`, `message` and `span`, plus
+ * `code`, `labels` and `suggestions` when present.
+ */
+char *rustic_last_diagnostics_json(void);
+
+/* Frees a string returned by this library. NULL is ignored. */
+void rustic_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RUSTIC_H */
@@ -0,0 +1,153 @@
+//! C interface for embedding the Rustic compiler; `include/rustic.h` declares
+//! it. Strings cross the boundary as NUL-terminated UTF-8, and every string
+//! returned to the host is owned by it until passed to `rustic_free`.
+//!
+//! Diagnostics from the most recent `rustic_compile` or `rustic_check` call on
+//! a thread are kept for `rustic_last_diagnostics_json`, so the result of each
+//! call stays a plain pointer or status code.
+
+use rustic::compiler::lexer::Lexer;
+use rustic::compiler::parser::Parser;
+use rustic::compiler::semantic::SemanticAnalyzer;
+use rustic::compiler::RusticCompiler;
+use rustic::diagnostics::DiagnosticEngine;
+use rustic::{Diagnostic, Error, Level, Result, Span};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// `rustic_check` status: the source is valid.
+pub const RUSTIC_OK: c_int = 0;
+/// `rustic_check` status: the source has errors, described by the diagnostics.
+pub const RUSTIC_ERROR: c_int = 1;
+/// `rustic_check` status: an argument was null or not UTF-8.
+pub const RUSTIC_INVALID_ARGUMENT: c_int = -1;
+
+thread_local! {
+    static LAST_DIAGNOSTICS: RefCell<Vec<Diagnostic>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Compiles `source` to Rust code. `module_name` names the generated module
+/// and the file in diagnostic spans. Returns null if compilation failed or an
+/// argument was invalid.
+///
+/// # Safety
+///
+/// Both arguments must be null or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn rustic_compile(
+    source: *const c_char,
+    module_name: *const c_char,
+) -> *mut c_char {
+    let (Some(source), Some(module_name)) = (read_str(source), read_str(module_name)) else {
+        record::<()>(Vec::new(), Err(invalid_argument()));
+        return ptr::null_mut();
+    };
+
+    let mut diagnostics = DiagnosticEngine::new();
+    let result = guard(|| RusticCompiler::new(&mut diagnostics).compile_str(source, module_name));
+    match record(diagnostics.take(), result) {
+        Some(code) => into_c_string(code),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Lexes, parses and checks `source` without generating code. `file_name`
+/// names the file in diagnostic spans.
+///
+/// # Safety
+///
+/// Both arguments must be null or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn rustic_check(source: *const c_char, file_name: *const c_char) -> c_int {
+    let (Some(source), Some(file_name)) = (read_str(source), read_str(file_name)) else {
+        record::<()>(Vec::new(), Err(invalid_argument()));
+        return RUSTIC_INVALID_ARGUMENT;
+    };
+
+    let mut diagnostics = DiagnosticEngine::new();
+    let result = guard(|| {
+        let tokens = Lexer::new(source, file_name).tokenize_reporting(&mut diagnostics);
+        let program = Parser::new(tokens, &mut diagnostics).parse()?;
+        SemanticAnalyzer::new(&mut diagnostics).analyze(&program)?;
+        Ok(())
+    });
+    match record(diagnostics.take(), result) {
+        Some(()) => RUSTIC_OK,
+        None => RUSTIC_ERROR,
+    }
+}
+
+/// The diagnostics of the last `rustic_compile` or `rustic_check` call on
+/// this thread, as a JSON array; `[]` before the first call.
+#[no_mangle]
+pub extern "C" fn rustic_last_diagnostics_json() -> *mut c_char {
+    let json = LAST_DIAGNOSTICS.with(|last| {
+        serde_json::to_string(&*last.borrow()).expect("diagnostics are always serializable")
+    });
+    into_c_string(json)
+}
+
+/// Frees a string returned by this library. Null is ignored.
+///
+/// # Safety
+///
+/// `string` must be null or a pointer returned by this library that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn rustic_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+unsafe fn read_str<'a>(string: *const c_char) -> Option<&'a str> {
+    if string.is_null() {
+        return None;
+    }
+    CStr::from_ptr(string).to_str().ok()
+}
+
+/// Runs `compile`, turning a panic into an error so it never unwinds into the
+/// host.
+fn guard<T>(compile: impl FnOnce() -> Result<T>) -> Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(compile)).unwrap_or_else(|_| {
+        Err(Error::CompilationError(
+            "internal compiler error".to_string(),
+        ))
+    })
+}
+
+/// Keeps `diagnostics` for `rustic_last_diagnostics_json`. A failure that
+/// reported no error of its own is recorded as one, so a failed call never
+/// leaves the host without an explanation.
+fn record<T>(mut diagnostics: Vec<Diagnostic>, result: Result<T>) -> Option<T> {
+    let value = match result {
+        Ok(value) => Some(value),
+        Err(error) => {
+            if !diagnostics.iter().any(|d| d.level == Level::Error) {
+                diagnostics.push(Diagnostic::error(error.to_string(), Span::default()));
+            }
+            None
+        }
+    };
+    LAST_DIAGNOSTICS.with(|last| *last.borrow_mut() = diagnostics);
+    value
+}
+
+fn invalid_argument() -> Error {
+    Error::CompilationError("arguments must be non-null UTF-8 strings".to_string())
+}
+
+/// Hands `string` to the host. Interior NULs cannot occur in generated code or
+/// JSON, but are cut off rather than trusted.
+fn into_c_string(string: String) -> *mut c_char {
+    let string = match string.find('\0') {
+        Some(end) => string[..end].to_string(),
+        None => string,
+    };
+    CString::new(string)
+        .expect("interior NULs were removed")
+        .into_raw()
+}
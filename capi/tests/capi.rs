@@ -0,0 +1,78 @@
+use rustic_capi::{
+    rustic_check, rustic_compile, rustic_free, rustic_last_diagnostics_json, RUSTIC_ERROR,
+    RUSTIC_INVALID_ARGUMENT, RUSTIC_OK,
+};
+use serde_json::Value;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+/// Copies a string returned by the library and frees it.
+fn take(string: *mut c_char) -> String {
+    assert!(!string.is_null());
+    let copy = unsafe { CStr::from_ptr(string) }
+        .to_str()
+        .unwrap()
+        .to_string();
+    unsafe { rustic_free(string) };
+    copy
+}
+
+fn last_diagnostics() -> Vec<Value> {
+    serde_json::from_str(&take(rustic_last_diagnostics_json())).unwrap()
+}
+
+#[test]
+fn compiling_returns_rust_code_owned_by_the_caller() {
+    let source = CString::new("pub fn square(x: int) -> int {\n  return x * x\n}\n").unwrap();
+    let module = CString::new("shapes").unwrap();
+
+    let code = take(unsafe { rustic_compile(source.as_ptr(), module.as_ptr()) });
+    assert!(code.contains("pub fn square(x: i64) -> i64"), "{}", code);
+    assert!(last_diagnostics().is_empty());
+}
+
+#[test]
+fn failures_leave_their_diagnostics_for_the_caller() {
+    let source = CString::new("fn main() {\n  let x: int = \"a\"\n}\n").unwrap();
+    let file = CString::new("broken.rsc").unwrap();
+
+    let code = unsafe { rustic_compile(source.as_ptr(), file.as_ptr()) };
+    assert!(code.is_null());
+    let diagnostics = last_diagnostics();
+    assert_eq!(diagnostics[0]["code"], "E0300");
+    assert_eq!(diagnostics[0]["span"]["file"], "broken.rsc");
+    assert_eq!(diagnostics[0]["span"]["start_line"], 2);
+
+    assert_eq!(
+        unsafe { rustic_check(source.as_ptr(), file.as_ptr()) },
+        RUSTIC_ERROR
+    );
+    let valid = CString::new("fn main() {\n}\n").unwrap();
+    assert_eq!(
+        unsafe { rustic_check(valid.as_ptr(), file.as_ptr()) },
+        RUSTIC_OK
+    );
+    assert!(last_diagnostics().is_empty());
+}
+
+#[test]
+fn invalid_arguments_are_reported_rather_than_trusted() {
+    let file = CString::new("file.rsc").unwrap();
+    assert_eq!(
+        unsafe { rustic_check(ptr::null(), file.as_ptr()) },
+        RUSTIC_INVALID_ARGUMENT
+    );
+    assert!(unsafe { rustic_compile(file.as_ptr(), ptr::null()) }.is_null());
+    let diagnostics = last_diagnostics();
+    assert_eq!(
+        diagnostics[0]["message"],
+        "Compilation error: arguments must be non-null UTF-8 strings"
+    );
+
+    let invalid_utf8 = [0xffu8, 0];
+    assert_eq!(
+        unsafe { rustic_check(invalid_utf8.as_ptr().cast(), file.as_ptr()) },
+        RUSTIC_INVALID_ARGUMENT
+    );
+    unsafe { rustic_free(ptr::null_mut()) };
+}
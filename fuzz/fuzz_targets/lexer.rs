@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rustic::compiler::lexer::Lexer;
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let _ = Lexer::new(&source, "fuzz.rsc").tokenize();
+});
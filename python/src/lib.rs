@@ -0,0 +1,183 @@
+//! Python bindings for the Rustic compiler, built as the `rustic` extension
+//! module with maturin:
+//!
+//! ```python
+//! import rustic
+//!
+//! rust_code = rustic.compile(source)
+//! for diagnostic in rustic.check(source, "main.rsc"):
+//!     print(diagnostic.line, diagnostic.message)
+//! program = rustic.parse(source)  # the AST as dicts and lists
+//! ```
+//!
+//! The AST has the same shape as the `program` of an `--emit ast-json`
+//! document. Failures raise `rustic.CompileError`, whose `diagnostics`
+//! attribute lists everything reported before the failure.
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyList};
+use rustic_lang::compiler::ast::Program;
+use rustic_lang::compiler::lexer::Lexer;
+use rustic_lang::compiler::parser::Parser;
+use rustic_lang::compiler::semantic::SemanticAnalyzer;
+use rustic_lang::compiler::RusticCompiler;
+use rustic_lang::diagnostics::DiagnosticEngine;
+use rustic_lang::{Diagnostic, Error};
+use serde_json::Value;
+
+create_exception!(
+    rustic,
+    CompileError,
+    PyException,
+    "Compilation failed; `diagnostics` explains why."
+);
+
+/// A message reported by the compiler.
+#[pyclass(name = "Diagnostic", module = "rustic", frozen)]
+struct PyDiagnostic(Diagnostic);
+
+#[pymethods]
+impl PyDiagnostic {
+    /// `"error"`, `"warning"` or `"note"`.
+    #[getter]
+    fn level(&self) -> String {
+        self.0.level.to_string()
+    }
+
+    #[getter]
+    fn code(&self) -> Option<String> {
+        self.0.code.clone()
+    }
+
+    #[getter]
+    fn message(&self) -> String {
+        self.0.message.clone()
+    }
+
+    #[getter]
+    fn file(&self) -> String {
+        self.0.span.file.clone()
+    }
+
+    /// 1-based line the diagnostic starts on.
+    #[getter]
+    fn line(&self) -> usize {
+        self.0.span.start_line
+    }
+
+    /// 1-based column the diagnostic starts at.
+    #[getter]
+    fn column(&self) -> usize {
+        self.0.span.start_column
+    }
+
+    /// Every field, including the full span, labels and suggestions.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(
+            py,
+            &serde_json::to_value(&self.0).expect("diagnostics serialize"),
+        )
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "<Diagnostic {} {}:{}:{}: {}>",
+            self.0.level,
+            self.0.span.file,
+            self.0.span.start_line,
+            self.0.span.start_column,
+            self.0.message
+        )
+    }
+}
+
+/// Compiles `source` to Rust code; `module_name` names the generated module
+/// and the file in diagnostics.
+#[pyfunction]
+#[pyo3(signature = (source, module_name = "main"))]
+fn compile(py: Python<'_>, source: &str, module_name: &str) -> PyResult<String> {
+    let mut diagnostics = DiagnosticEngine::new();
+    let result = RusticCompiler::new(&mut diagnostics).compile_str(source, module_name);
+    result.map_err(|error| compile_error(py, error, diagnostics.take()))
+}
+
+/// Checks `source` without generating code and returns every diagnostic; the
+/// source is valid when none of them is an error.
+#[pyfunction]
+#[pyo3(signature = (source, file_name = "<string>"))]
+fn check(source: &str, file_name: &str) -> Vec<PyDiagnostic> {
+    let mut diagnostics = DiagnosticEngine::new();
+    let tokens = Lexer::new(source, file_name).tokenize_reporting(&mut diagnostics);
+    if let Ok(program) = Parser::new(tokens, &mut diagnostics).parse() {
+        // Any error is already among the diagnostics.
+        let _ = SemanticAnalyzer::new(&mut diagnostics).analyze(&program);
+    }
+    diagnostics.take().into_iter().map(PyDiagnostic).collect()
+}
+
+/// Parses `source` and returns its AST as dicts and lists, without checking it.
+#[pyfunction]
+#[pyo3(signature = (source, file_name = "<string>"))]
+fn parse<'py>(py: Python<'py>, source: &str, file_name: &str) -> PyResult<Bound<'py, PyAny>> {
+    let mut diagnostics = DiagnosticEngine::new();
+    let tokens = Lexer::new(source, file_name).tokenize_reporting(&mut diagnostics);
+    let program: Program = Parser::new(tokens, &mut diagnostics)
+        .parse()
+        .map_err(|error| compile_error(py, error, diagnostics.take()))?;
+    to_python(py, &serde_json::to_value(&program).expect("AST serializes"))
+}
+
+fn compile_error(py: Python<'_>, error: Error, diagnostics: Vec<Diagnostic>) -> PyErr {
+    let exception = CompileError::new_err(error.to_string());
+    let attached = diagnostics
+        .into_iter()
+        .map(|diagnostic| Bound::new(py, PyDiagnostic(diagnostic)))
+        .collect::<PyResult<Vec<_>>>()
+        .and_then(|diagnostics| exception.value(py).setattr("diagnostics", diagnostics));
+    match attached {
+        Ok(()) => exception,
+        Err(error) => error,
+    }
+}
+
+fn to_python<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(value) => PyBool::new(py, *value).to_owned().into_any(),
+        Value::Number(number) => match number.as_i64() {
+            Some(value) => value.into_pyobject(py)?.into_any(),
+            None => number.as_f64().into_pyobject(py)?.into_any(),
+        },
+        Value::String(value) => value.into_pyobject(py)?.into_any(),
+        Value::Array(values) => {
+            let list = PyList::empty(py);
+            for value in values {
+                list.append(to_python(py, value)?)?;
+            }
+            list.into_any()
+        }
+        Value::Object(fields) => {
+            let dict = PyDict::new(py);
+            for (key, value) in fields {
+                dict.set_item(key, to_python(py, value)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+#[pymodule]
+fn rustic(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(compile, m)?)?;
+    m.add_function(wrap_pyfunction!(check, m)?)?;
+    m.add_function(wrap_pyfunction!(parse, m)?)?;
+    m.add_class::<PyDiagnostic>()?;
+    m.add("CompileError", m.py().get_type::<CompileError>())?;
+    Ok(())
+}
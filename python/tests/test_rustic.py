@@ -0,0 +1,55 @@
+"""Tests of the `rustic` extension module; run them after `maturin develop`
+with `python -m unittest discover python/tests`."""
+
+import unittest
+
+import rustic
+
+SQUARE = "pub fn square(x: int) -> int {\n  return x * x\n}\n"
+MISTYPED = 'fn main() {\n  let x: int = "a"\n}\n'
+
+
+class CompileTest(unittest.TestCase):
+    def test_returns_rust_code(self):
+        code = rustic.compile(SQUARE, "shapes")
+        self.assertIn("pub fn square(x: i64) -> i64", code)
+
+    def test_failures_raise_with_their_diagnostics(self):
+        with self.assertRaises(rustic.CompileError) as raised:
+            rustic.compile(MISTYPED, "broken")
+        diagnostic = raised.exception.diagnostics[0]
+        self.assertEqual(diagnostic.level, "error")
+        self.assertEqual(diagnostic.code, "E0300")
+        self.assertEqual((diagnostic.file, diagnostic.line), ("broken", 2))
+
+
+class CheckTest(unittest.TestCase):
+    def test_valid_source_has_no_diagnostics(self):
+        self.assertEqual(rustic.check(SQUARE), [])
+
+    def test_reports_every_diagnostic(self):
+        [error, warning] = rustic.check(MISTYPED, "main.rsc")
+        self.assertEqual((error.level, error.line, error.column), ("error", 2, 3))
+        self.assertEqual(warning.level, "warning")
+        self.assertEqual(warning.message, "Unused variable `x`")
+        fields = warning.to_dict()
+        self.assertEqual(fields["lint"], "unused-variable")
+        self.assertEqual(fields["span"]["file"], "main.rsc")
+
+
+class ParseTest(unittest.TestCase):
+    def test_returns_the_ast_as_dicts_and_lists(self):
+        program = rustic.parse(SQUARE)
+        [item] = program["items"]
+        function = item["Function"]
+        self.assertEqual(function["name"], "square")
+        self.assertEqual(function["parameters"][0]["param_type"], "Int")
+
+    def test_syntax_errors_raise(self):
+        with self.assertRaises(rustic.CompileError) as raised:
+            rustic.parse("fn main( {\n}\n")
+        self.assertEqual(raised.exception.diagnostics[0].code, "E0100")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,337 @@
+//! The embedding API: `rustic::Compiler` runs the whole pipeline for a file or
+//! directory and hands back the generated files and diagnostics, so other
+//! programs can drive the compiler the same way the `rustic` binary does.
+//! `Compiler::watch` keeps compiling as the sources change. `rustic::parse`
+//! stops after parsing, for tools that only need the AST.
+
+use crate::compiler::ast::Program;
+use crate::compiler::callbacks::CompilerCallbacks;
+use crate::compiler::graph::ProjectGraph;
+use crate::compiler::lexer::Lexer;
+use crate::compiler::parser::Parser;
+use crate::compiler::{Emit, FileOutcome, InputFormat, RusticCompiler};
+use crate::diagnostics::{
+    Diagnostic, DiagnosticCounts, DiagnosticEngine, Error, LintLevel, LintName,
+};
+use crate::profile;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Lexes and parses `source` without checking it. The program is `None` when
+/// parsing failed; the diagnostics explain why, covering every lexical and
+/// syntax error rather than just the first.
+pub fn parse(source: &str, file_name: &str) -> (Option<Program>, Vec<Diagnostic>) {
+    let mut diagnostics = DiagnosticEngine::new();
+    let tokens = Lexer::new(source, file_name).tokenize_reporting(&mut diagnostics);
+    let program = Parser::new(tokens, &mut diagnostics).parse().ok();
+    (program, diagnostics.diagnostics().to_vec())
+}
+
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    /// Directory the generated Rust files (and native build) are written to.
+    pub output_dir: PathBuf,
+    /// Also build the generated code into a native binary with cargo.
+    pub native: bool,
+    pub emit: Emit,
+    pub input_format: InputFormat,
+    /// Levels of warning categories and single lints, applied in order so
+    /// later ones win. A lint's level takes precedence over its category's.
+    pub lint_levels: Vec<(LintName, LintLevel)>,
+    /// When compiling a directory, skip the modules that did not change
+    /// since the last build into `output_dir`, nor did any module they
+    /// import. Their files are kept and their warnings reported again, but
+    /// they are missing from `CompileOutput::modules` and `graph`. Ignored by
+    /// `compile_with`, since callbacks would not run for them.
+    pub incremental: bool,
+    /// Leave the functions, methods, structs, enums and constants that `main`
+    /// never reaches out of the generated code. Directory builds are then never
+    /// incremental, since what one module keeps depends on the others.
+    pub eliminate_dead_code: bool,
+    /// Lay the generated Rust out the way rustfmt would. On by default.
+    pub format: bool,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            output_dir: PathBuf::from("target/generated"),
+            native: false,
+            emit: Emit::default(),
+            input_format: InputFormat::default(),
+            lint_levels: Vec::new(),
+            incremental: false,
+            eliminate_dead_code: false,
+            format: true,
+        }
+    }
+}
+
+impl CompileOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn output_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = dir.into();
+        self
+    }
+
+    pub fn native(mut self, native: bool) -> Self {
+        self.native = native;
+        self
+    }
+
+    pub fn emit(mut self, emit: Emit) -> Self {
+        self.emit = emit;
+        self
+    }
+
+    pub fn input_format(mut self, input_format: InputFormat) -> Self {
+        self.input_format = input_format;
+        self
+    }
+
+    /// Allows, warns about or denies the warnings of a category or lint.
+    pub fn lint_level(mut self, name: impl Into<LintName>, level: LintLevel) -> Self {
+        self.lint_levels.push((name.into(), level));
+        self
+    }
+
+    pub fn incremental(mut self, incremental: bool) -> Self {
+        self.incremental = incremental;
+        self
+    }
+
+    pub fn eliminate_dead_code(mut self, eliminate_dead_code: bool) -> Self {
+        self.eliminate_dead_code = eliminate_dead_code;
+        self
+    }
+
+    pub fn format(mut self, format: bool) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompileOutput {
+    /// Files written to the output directory, one per module: Rust code, or
+    /// AST JSON with `Emit::AstJson`. None with `Emit::Check`.
+    pub files: Vec<PathBuf>,
+    /// The native binary, when `CompileOptions::native` was set.
+    pub binary: Option<PathBuf>,
+    /// Warnings and notes reported along the way.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Import and call graph of the compiled modules.
+    pub graph: ProjectGraph,
+    /// How each file went, when compiling a directory.
+    pub outcomes: Vec<FileOutcome>,
+    /// The AST of each module compiled to Rust, by module name.
+    pub modules: BTreeMap<String, Arc<Program>>,
+}
+
+/// A failed compilation: the error that stopped it plus every diagnostic
+/// reported before that point.
+#[derive(Debug, Clone)]
+pub struct CompileError {
+    pub error: Error,
+    pub diagnostics: Vec<Diagnostic>,
+    /// How each file went, when compiling a directory; the files that
+    /// compiled were written even though others failed.
+    pub outcomes: Vec<FileOutcome>,
+}
+
+impl CompileOutput {
+    /// The AST of the module called `name`, if it was compiled to Rust.
+    pub fn module(&self, name: &str) -> Option<&Program> {
+        self.modules.get(name).map(|ast| &**ast)
+    }
+
+    /// How many warnings and notes were reported, by level and category.
+    pub fn counts(&self) -> DiagnosticCounts {
+        DiagnosticCounts::of(&self.diagnostics)
+    }
+}
+
+impl CompileError {
+    /// How many errors, warnings and notes were reported before the failure.
+    pub fn counts(&self) -> DiagnosticCounts {
+        DiagnosticCounts::of(&self.diagnostics)
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl std::error::Error for CompileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Compiler {
+    options: CompileOptions,
+}
+
+impl Compiler {
+    pub fn new(options: CompileOptions) -> Self {
+        Self { options }
+    }
+
+    pub fn options(&self) -> &CompileOptions {
+        &self.options
+    }
+
+    /// Compiles a single source file, or every source file under a directory.
+    pub fn compile(&self, input: impl AsRef<Path>) -> Result<CompileOutput, CompileError> {
+        self.run(input.as_ref(), None::<()>)
+    }
+
+    /// Like `compile`, running `callbacks` at each phase of every module.
+    pub fn compile_with(
+        &self,
+        input: impl AsRef<Path>,
+        callbacks: impl CompilerCallbacks,
+    ) -> Result<CompileOutput, CompileError> {
+        self.run(input.as_ref(), Some(callbacks))
+    }
+
+    /// Compiles `input`, incrementally if asked to unless there are
+    /// `callbacks` to run.
+    fn run(
+        &self,
+        input: &Path,
+        callbacks: Option<impl CompilerCallbacks>,
+    ) -> Result<CompileOutput, CompileError> {
+        let input_path = input.to_string_lossy();
+        let _timing = profile::start("driver", "compile").arg("input", input_path.as_ref());
+        let output_dir = self.options.output_dir.to_string_lossy();
+
+        if let Err(error) = self.check_options() {
+            return Err(CompileError {
+                error,
+                diagnostics: Vec::new(),
+                outcomes: Vec::new(),
+            });
+        }
+
+        let mut diagnostic_engine = self.diagnostic_engine();
+        let mut compiler = self.compiler(&mut diagnostic_engine);
+        // Nothing is meant to change the sources during one build.
+        compiler.set_map_sources(true);
+        match callbacks {
+            Some(callbacks) => compiler.add_callbacks(callbacks),
+            None => compiler.set_incremental(self.options.incremental),
+        }
+
+        let result = if input.is_file() {
+            compiler.compile_file(&input_path, &output_dir)
+        } else {
+            compiler.compile_directory(&input_path, &output_dir)
+        };
+        let outcomes = compiler.outcomes().to_vec();
+        self.finish(&mut compiler, result, outcomes)
+    }
+
+    /// Compiles like `compile`, then again each time a source file under
+    /// `input` changes, passing the result of every build to `on_build` until
+    /// it breaks. Only the changed modules of a directory and the modules
+    /// importing them are compiled again, and each result has the
+    /// diagnostics and outcomes of its own build. Fails if the files cannot
+    /// be watched.
+    pub fn watch(
+        &self,
+        input: impl AsRef<Path>,
+        mut on_build: impl FnMut(Result<CompileOutput, CompileError>) -> ControlFlow<()>,
+    ) -> Result<(), Error> {
+        let input_path = input.as_ref().to_string_lossy();
+        let output_dir = self.options.output_dir.to_string_lossy();
+        self.check_options()?;
+
+        let mut diagnostic_engine = self.diagnostic_engine();
+        let mut compiler = self.compiler(&mut diagnostic_engine);
+        compiler.set_incremental(self.options.incremental);
+        let mut seen = 0;
+        compiler.watch(&input_path, &output_dir, |compiler, result| {
+            let outcomes = compiler.outcomes()[seen..].to_vec();
+            seen = compiler.outcomes().len();
+            on_build(self.finish(compiler, result, outcomes))
+        })
+    }
+
+    fn check_options(&self) -> Result<(), Error> {
+        if self.options.native && self.options.emit != Emit::Rust {
+            return Err(Error::CompilationError(
+                "a native binary can only be built from generated Rust".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn diagnostic_engine(&self) -> DiagnosticEngine {
+        let mut diagnostic_engine = DiagnosticEngine::new();
+        for &(name, level) in &self.options.lint_levels {
+            diagnostic_engine.set_lint_level(name, level);
+        }
+        diagnostic_engine
+    }
+
+    fn compiler<'a>(&self, diagnostic_engine: &'a mut DiagnosticEngine) -> RusticCompiler<'a> {
+        let mut compiler = RusticCompiler::new(diagnostic_engine);
+        compiler.set_emit(self.options.emit);
+        compiler.set_input_format(self.options.input_format);
+        compiler.set_eliminate_dead_code(self.options.eliminate_dead_code);
+        compiler.set_format(self.options.format);
+        compiler.set_native(self.options.native);
+        compiler
+    }
+
+    /// Builds the native binary if asked to, and collects what a build that
+    /// ended with `result` produced and reported.
+    fn finish(
+        &self,
+        compiler: &mut RusticCompiler,
+        result: Result<Vec<String>, Error>,
+        outcomes: Vec<FileOutcome>,
+    ) -> Result<CompileOutput, CompileError> {
+        let output_dir = self.options.output_dir.to_string_lossy();
+        let result = result.and_then(|files| {
+            let binary = if self.options.native {
+                Some(compiler.compile_to_native(&files, &output_dir)?)
+            } else {
+                None
+            };
+            Ok((files, binary))
+        });
+        let graph = compiler.graph().clone();
+        let modules = compiler
+            .modules()
+            .map(|(name, ast)| (name.to_string(), Arc::clone(ast)))
+            .collect();
+
+        let diagnostics = compiler.take_diagnostics();
+        match result {
+            Ok((files, binary)) => Ok(CompileOutput {
+                files: files.into_iter().map(PathBuf::from).collect(),
+                binary: binary.map(PathBuf::from),
+                diagnostics,
+                graph,
+                outcomes,
+                modules,
+            }),
+            Err(error) => Err(CompileError {
+                error,
+                diagnostics,
+                outcomes,
+            }),
+        }
+    }
+}
@@ -1,5 +1,10 @@
+use crate::compiler::intern::Symbol;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+
+/// Bumped whenever the serialized shape of the AST changes.
+pub const AST_SCHEMA_VERSION: u32 = 27;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Program {
@@ -10,6 +15,22 @@ pub struct Program {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Import {
     pub module_path: String,
+    /// `import path as alias`: the name the module is referred to by instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<Symbol>,
+    /// `import path.{a, b as c}`: the items brought into scope, when only
+    /// some of them are.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<ImportedItem>>,
+    pub span: crate::diagnostics::Span,
+}
+
+/// An item named in a selective import.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportedItem {
+    pub name: Symbol,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<Symbol>,
     pub span: crate::diagnostics::Span,
 }
 
@@ -17,45 +38,133 @@ pub struct Import {
 pub enum Item {
     Function(Function),
     Struct(Struct),
+    Enum(Enum),
+    Impl(Impl),
     Variable(Variable),
     Constant(Constant),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Function {
-    pub name: String,
+    pub name: Symbol,
+    /// `T` and `U` in `fn map[T, U](...)`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub type_params: Vec<Symbol>,
     pub parameters: Vec<Parameter>,
     pub return_type: Type,
     pub body: Block,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
+    /// The lines of its `///` doc comment, without the slashes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub docs: Vec<String>,
+    /// Declared `pub`, so modules that import this one can use it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub public: bool,
     pub span: crate::diagnostics::Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Struct {
-    pub name: String,
+    pub name: Symbol,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub type_params: Vec<Symbol>,
     pub fields: Vec<Field>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<Deprecation>,
+    /// The lines of its `///` doc comment, without the slashes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub docs: Vec<String>,
+    /// Declared with `exception` rather than `struct`, so it can be thrown.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exception: bool,
+    /// Declared `pub`, so modules that import this one can use it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub public: bool,
+    pub span: crate::diagnostics::Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Enum {
+    pub name: Symbol,
+    pub variants: Vec<Variant>,
+    /// Declared `pub`, so modules that import this one can use it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub public: bool,
+    pub span: crate::diagnostics::Span,
+}
+
+/// Methods attached to a struct: `impl Point { fn norm(self) -> float { ... } }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Impl {
+    pub struct_name: Symbol,
+    /// Names for the type parameters of a generic struct: `impl Pair[A, B]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub type_params: Vec<Symbol>,
+    pub methods: Vec<Method>,
+    pub span: crate::diagnostics::Span,
+}
+
+/// A function in an `impl` block, called on a value as `value.name(...)`. The
+/// `self` receiver is not among the function's parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Method {
+    /// Whether the receiver is `var self`, which lets the method assign to it.
+    pub mutable: bool,
+    pub function: Function,
+}
+
+/// An enum variant; unit variants have an empty payload.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Variant {
+    pub name: Symbol,
+    pub payload: Vec<Type>,
+    pub span: crate::diagnostics::Span,
+}
+
+/// A `@deprecated` or `@deprecated("note")` attribute on an item.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deprecation {
+    /// What to use instead, or why the item is going away.
+    pub note: Option<String>,
     pub span: crate::diagnostics::Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Field {
-    pub name: String,
+    pub name: Symbol,
     pub field_type: Type,
     pub span: crate::diagnostics::Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Parameter {
-    pub name: String,
+    pub name: Symbol,
     pub param_type: Type,
     pub default_value: Option<Expression>,
     pub span: crate::diagnostics::Span,
 }
 
+/// `let name: type = initializer`. Without a type, a local variable takes the
+/// type of its initializer. A local `var` with a type may leave out the
+/// initializer, as long as it is assigned before every use.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Variable {
-    pub name: String,
-    pub var_type: Type,
+    pub name: Symbol,
+    pub var_type: Option<Type>,
+    pub initializer: Option<Expression>,
+    pub mutable: bool,
+    /// Declared `pub`, so modules that import this one can use it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub public: bool,
+    pub span: crate::diagnostics::Span,
+}
+
+/// `let (x, y) = pair`: a tuple unpacked into one variable per element. A name
+/// of `_` drops its element.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Destructure {
+    pub names: Vec<Identifier>,
     pub initializer: Expression,
     pub mutable: bool,
     pub span: crate::diagnostics::Span,
@@ -63,9 +172,12 @@ pub struct Variable {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Constant {
-    pub name: String,
+    pub name: Symbol,
     pub const_type: Type,
     pub value: Expression,
+    /// Declared `pub`, so modules that import this one can use it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub public: bool,
     pub span: crate::diagnostics::Span,
 }
 
@@ -76,7 +188,20 @@ pub enum Type {
     Str,
     Bool,
     List(Box<Type>),
-    Struct(String),
+    /// `map[K, V]`, from keys to values.
+    Map(Box<Type>, Box<Type>),
+    /// A struct or enum, by name.
+    Struct(Symbol),
+    /// A generic struct with its type arguments, as in `Pair[int, str]`.
+    Generic(Symbol, Vec<Type>),
+    /// A type parameter of the enclosing function, struct or `impl` block.
+    Param(Symbol),
+    /// `T?`: a `T` or `none`.
+    Optional(Box<Type>),
+    /// `fn(int, str) -> bool`: a function with these parameter and return types.
+    Function(Vec<Type>, Box<Type>),
+    /// `(int, str)`: a fixed number of values of these types.
+    Tuple(Vec<Type>),
     Void,
 }
 
@@ -90,16 +215,24 @@ pub struct Block {
 pub enum Statement {
     Expression(Expression),
     Variable(Variable),
+    Destructure(Destructure),
     Assignment(Assignment),
     If(IfStatement),
     For(ForLoop),
+    While(WhileLoop),
     Try(TryStatement),
+    Throw(ThrowStatement),
     Return(ReturnStatement),
+    Break(BreakStatement),
+    Continue(ContinueStatement),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Assignment {
     pub target: Expression,
+    /// The operator of a compound assignment such as `+=`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<BinaryOperator>,
     pub value: Expression,
     pub span: crate::diagnostics::Span,
 }
@@ -115,32 +248,63 @@ pub struct IfStatement {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ForLoop {
-    pub variable: String,
+    pub variable: Symbol,
     pub iterable: Expression,
     pub body: Block,
     pub span: crate::diagnostics::Span,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WhileLoop {
+    pub condition: Expression,
+    pub body: Block,
+    pub span: crate::diagnostics::Span,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TryStatement {
     pub try_block: Block,
     pub catch_clauses: Vec<CatchClause>,
+    /// Runs after the `try` block and any handler, however they finish.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finally_block: Option<Block>,
     pub span: crate::diagnostics::Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CatchClause {
-    pub exception_type: String,
+    /// A declared exception, or `Error` for any error.
+    pub exception_type: Symbol,
+    /// The name after `as` that holds the caught exception.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binding: Option<Symbol>,
     pub handler_block: Block,
     pub span: crate::diagnostics::Span,
 }
 
+/// `throw NotFound { path: "a.txt" }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThrowStatement {
+    pub value: Expression,
+    pub span: crate::diagnostics::Span,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ReturnStatement {
     pub value: Option<Expression>,
     pub span: crate::diagnostics::Span,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BreakStatement {
+    pub span: crate::diagnostics::Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContinueStatement {
+    pub span: crate::diagnostics::Span,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Expression {
     Literal(Literal),
@@ -150,7 +314,18 @@ pub enum Expression {
     Call(FunctionCall),
     MemberAccess(MemberAccess),
     List(ListLiteral),
+    Comprehension(Comprehension),
+    Tuple(TupleLiteral),
+    Map(MapLiteral),
+    Index(IndexExpression),
+    Slice(SliceExpression),
     StructInit(StructInitializer),
+    Match(MatchExpression),
+    Interpolation(Interpolation),
+    Range(RangeExpression),
+    Lambda(Lambda),
+    Cast(CastExpression),
+    If(IfExpression),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -159,11 +334,12 @@ pub enum Literal {
     Float(f64),
     String(String),
     Boolean(bool),
+    None,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Identifier {
-    pub name: String,
+    pub name: Symbol,
     pub span: crate::diagnostics::Span,
 }
 
@@ -180,6 +356,11 @@ pub enum BinaryOperator {
     Add, Sub, Mul, Div, Mod,
     Eq, Ne, Lt, Le, Gt, Ge,
     And, Or,
+    /// `a ?? b`: `a` unless it is `none`, else `b`.
+    Coalesce,
+    BitAnd, BitOr, BitXor, Shl, Shr,
+    /// `a ** b`, `a` to the power of `b`.
+    Pow,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -191,20 +372,33 @@ pub struct UnaryOp {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum UnaryOperator {
-    Neg, Not
+    Neg, Not,
+    /// `~x`, the bits of an `int` flipped.
+    BitNot,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FunctionCall {
     pub function: Box<Expression>,
     pub arguments: Vec<Expression>,
+    /// `name: value` arguments, which follow the positional ones.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub named_arguments: Vec<NamedArgument>,
+    pub span: crate::diagnostics::Span,
+}
+
+/// `f(x, scale: 2)`: an argument passed to the parameter `scale`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NamedArgument {
+    pub name: Symbol,
+    pub value: Expression,
     pub span: crate::diagnostics::Span,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MemberAccess {
     pub object: Box<Expression>,
-    pub member: String,
+    pub member: Symbol,
     pub span: crate::diagnostics::Span,
 }
 
@@ -214,9 +408,1287 @@ pub struct ListLiteral {
     pub span: crate::diagnostics::Span,
 }
 
+/// `[x * 2 for x in xs if x > 0]`: `element` for each value of `iterable`,
+/// bound to `variable`, that passes the optional `condition`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comprehension {
+    pub element: Box<Expression>,
+    pub variable: Symbol,
+    pub iterable: Box<Expression>,
+    pub condition: Option<Box<Expression>>,
+    pub span: crate::diagnostics::Span,
+}
+
+/// `(a, b)`, a tuple of two or more values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TupleLiteral {
+    pub elements: Vec<Expression>,
+    pub span: crate::diagnostics::Span,
+}
+
+/// `{key: value, ...}`, a map literal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapLiteral {
+    pub entries: Vec<(Expression, Expression)>,
+    pub span: crate::diagnostics::Span,
+}
+
+/// `object[index]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexExpression {
+    pub object: Box<Expression>,
+    pub index: Box<Expression>,
+    pub span: crate::diagnostics::Span,
+}
+
+/// `object[start:end]`, the elements from `start` up to `end`. Either bound
+/// may be left out, and negative bounds count from the end.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SliceExpression {
+    pub object: Box<Expression>,
+    pub start: Option<Box<Expression>>,
+    pub end: Option<Box<Expression>>,
+    pub span: crate::diagnostics::Span,
+}
+
+/// `start..end`, or `start..=end` with `inclusive`, the integers from `start`
+/// up to `end`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeExpression {
+    pub start: Box<Expression>,
+    pub end: Box<Expression>,
+    pub inclusive: bool,
+    pub span: crate::diagnostics::Span,
+}
+
+/// `value as float`: a conversion between numeric types, or to `str`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CastExpression {
+    pub value: Box<Expression>,
+    pub target: Type,
+    pub span: crate::diagnostics::Span,
+}
+
+/// `if ready { 1 } else { 0 }`: the value of one branch or the other. An
+/// `else if` is an `if` expression in `else_value`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IfExpression {
+    pub condition: Box<Expression>,
+    pub then_value: Box<Expression>,
+    pub else_value: Box<Expression>,
+    pub span: crate::diagnostics::Span,
+}
+
+/// An anonymous function: `fn(x: int) -> int { return x * 2 }`, or `|x| x * 2`
+/// whose type is that of its body. Parameter types may be left out where the
+/// lambda is stored or passed as a function type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lambda {
+    pub parameters: Vec<LambdaParameter>,
+    pub body: Box<LambdaBody>,
+    pub span: crate::diagnostics::Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LambdaParameter {
+    pub name: Symbol,
+    pub param_type: Option<Type>,
+    pub span: crate::diagnostics::Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum LambdaBody {
+    /// `fn(...) -> T { ... }`, `void` without the arrow.
+    Block(Type, Block),
+    /// `|...| expression`.
+    Expression(Expression),
+}
+
+/// `"hello {name}"`, a string literal with expressions in braces.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interpolation {
+    pub parts: Vec<InterpolationPart>,
+    pub span: crate::diagnostics::Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InterpolationPart {
+    /// Text with escapes and doubled braces already resolved.
+    Text(String),
+    Expression(Expression),
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StructInitializer {
-    pub struct_name: String,
-    pub fields: HashMap<String, Expression>,
+    pub struct_name: Symbol,
+    pub fields: HashMap<Symbol, Expression>,
+    pub span: crate::diagnostics::Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchExpression {
+    pub scrutinee: Box<Expression>,
+    pub arms: Vec<MatchArm>,
+    pub span: crate::diagnostics::Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub pattern_span: crate::diagnostics::Span,
+    pub body: Expression,
     pub span: crate::diagnostics::Span,
-}
\ No newline at end of file
+}
+
+/// What a `match` arm compares the value against.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Pattern {
+    /// `_`, which matches anything.
+    Wildcard,
+    Literal(Literal),
+    /// A name, which matches anything and binds the value to it.
+    Binding(Symbol),
+    /// `Enum.Variant` or `Enum.Variant(a, b)`, binding the payload to names.
+    Variant(VariantPattern),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantPattern {
+    pub enum_name: Symbol,
+    pub variant: Symbol,
+    /// One name per payload value; `_` ignores the value.
+    pub bindings: Vec<Symbol>,
+}
+
+impl Pattern {
+    /// Whether the pattern matches every value.
+    pub fn is_catch_all(&self) -> bool {
+        matches!(self, Pattern::Wildcard | Pattern::Binding(_))
+    }
+}
+impl Import {
+    /// The name code qualifies the module's items with, as in `name.item`:
+    /// the alias, or else the last segment of the path, so `import net.http`
+    /// is referred to as `http`.
+    pub fn name(&self) -> Symbol {
+        self.alias.unwrap_or_else(|| {
+            let path = &self.module_path;
+            Symbol::from(path.rsplit('.').next().unwrap_or(path))
+        })
+    }
+}
+
+impl ImportedItem {
+    /// The name the item is known by in the importing module.
+    pub fn local_name(&self) -> Symbol {
+        self.alias.unwrap_or(self.name)
+    }
+}
+
+impl Item {
+    pub fn span(&self) -> &crate::diagnostics::Span {
+        match self {
+            Item::Function(function) => &function.span,
+            Item::Struct(structure) => &structure.span,
+            Item::Enum(enumeration) => &enumeration.span,
+            Item::Impl(implementation) => &implementation.span,
+            Item::Variable(variable) => &variable.span,
+            Item::Constant(constant) => &constant.span,
+        }
+    }
+
+    /// The name the item declares; `impl` blocks declare none.
+    pub fn name(&self) -> Option<Symbol> {
+        match self {
+            Item::Function(function) => Some(function.name),
+            Item::Struct(structure) => Some(structure.name),
+            Item::Enum(enumeration) => Some(enumeration.name),
+            Item::Impl(_) => None,
+            Item::Variable(variable) => Some(variable.name),
+            Item::Constant(constant) => Some(constant.name),
+        }
+    }
+
+    /// Whether the item is declared `pub`. `impl` blocks never are, though
+    /// their methods may be.
+    pub fn is_public(&self) -> bool {
+        match self {
+            Item::Function(function) => function.public,
+            Item::Struct(structure) => structure.public,
+            Item::Enum(enumeration) => enumeration.public,
+            Item::Impl(_) => false,
+            Item::Variable(variable) => variable.public,
+            Item::Constant(constant) => constant.public,
+        }
+    }
+}
+
+impl Expression {
+    /// Source location of the expression. Literals carry no span of their own.
+    pub fn span(&self) -> Option<&crate::diagnostics::Span> {
+        match self {
+            Expression::Literal(_) => None,
+            Expression::Identifier(identifier) => Some(&identifier.span),
+            Expression::Binary(binary) => Some(&binary.span),
+            Expression::Unary(unary) => Some(&unary.span),
+            Expression::Call(call) => Some(&call.span),
+            Expression::MemberAccess(access) => Some(&access.span),
+            Expression::List(list) => Some(&list.span),
+            Expression::Comprehension(comprehension) => Some(&comprehension.span),
+            Expression::Tuple(tuple) => Some(&tuple.span),
+            Expression::Map(map) => Some(&map.span),
+            Expression::Index(index) => Some(&index.span),
+            Expression::Slice(slice) => Some(&slice.span),
+            Expression::StructInit(init) => Some(&init.span),
+            Expression::Interpolation(interpolation) => Some(&interpolation.span),
+            Expression::Range(range) => Some(&range.span),
+            Expression::Cast(cast) => Some(&cast.span),
+            Expression::If(if_expression) => Some(&if_expression.span),
+            Expression::Lambda(lambda) => Some(&lambda.span),
+            Expression::Match(match_expression) => Some(&match_expression.span),
+        }
+    }
+}
+
+impl Statement {
+    /// Source location of the statement; an expression statement that is just
+    /// a literal has none.
+    pub fn span(&self) -> Option<&crate::diagnostics::Span> {
+        match self {
+            Statement::Expression(expression) => expression.span(),
+            Statement::Variable(variable) => Some(&variable.span),
+            Statement::Destructure(destructure) => Some(&destructure.span),
+            Statement::Assignment(assignment) => Some(&assignment.span),
+            Statement::If(if_statement) => Some(&if_statement.span),
+            Statement::For(for_loop) => Some(&for_loop.span),
+            Statement::While(while_loop) => Some(&while_loop.span),
+            Statement::Try(try_statement) => Some(&try_statement.span),
+            Statement::Throw(throw_statement) => Some(&throw_statement.span),
+            Statement::Return(return_statement) => Some(&return_statement.span),
+            Statement::Break(break_statement) => Some(&break_statement.span),
+            Statement::Continue(continue_statement) => Some(&continue_statement.span),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Str => write!(f, "str"),
+            Type::Bool => write!(f, "bool"),
+            Type::List(element) => write!(f, "list[{}]", element),
+            Type::Map(key, value) => write!(f, "map[{}, {}]", key, value),
+            Type::Struct(name) | Type::Param(name) => write!(f, "{}", name),
+            Type::Generic(name, arguments) => {
+                let arguments: Vec<String> = arguments.iter().map(Type::to_string).collect();
+                write!(f, "{}[{}]", name, arguments.join(", "))
+            }
+            // `none` on its own, before anything says what it stands for.
+            Type::Optional(inner) if **inner == Type::Void => write!(f, "none"),
+            // Parenthesized, or the `?` would make the return type optional.
+            Type::Optional(inner) if matches!(**inner, Type::Function(..)) => {
+                write!(f, "({})?", inner)
+            }
+            Type::Optional(inner) => write!(f, "{}?", inner),
+            Type::Function(parameters, return_type) => {
+                let parameters: Vec<String> = parameters.iter().map(Type::to_string).collect();
+                write!(f, "fn({})", parameters.join(", "))?;
+                match **return_type {
+                    Type::Void => Ok(()),
+                    _ => write!(f, " -> {}", return_type),
+                }
+            }
+            Type::Tuple(elements) => {
+                let elements: Vec<String> = elements.iter().map(Type::to_string).collect();
+                write!(f, "({})", elements.join(", "))
+            }
+            Type::Void => write!(f, "void"),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Sub => "-",
+            BinaryOperator::Mul => "*",
+            BinaryOperator::Div => "/",
+            BinaryOperator::Mod => "%",
+            BinaryOperator::Eq => "==",
+            BinaryOperator::Ne => "!=",
+            BinaryOperator::Lt => "<",
+            BinaryOperator::Le => "<=",
+            BinaryOperator::Gt => ">",
+            BinaryOperator::Ge => ">=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+            BinaryOperator::Coalesce => "??",
+            BinaryOperator::BitAnd => "&",
+            BinaryOperator::BitOr => "|",
+            BinaryOperator::BitXor => "^",
+            BinaryOperator::Shl => "<<",
+            BinaryOperator::Shr => ">>",
+            BinaryOperator::Pow => "**",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnaryOperator::Neg => write!(f, "-"),
+            UnaryOperator::Not => write!(f, "!"),
+            UnaryOperator::BitNot => write!(f, "~"),
+        }
+    }
+}
+
+/// Read-only traversal of the AST. Every method defaults to the matching
+/// `walk_*` function, which visits the node's children in source order, so an
+/// implementation only overrides the nodes it cares about and calls `walk_*`
+/// itself to keep descending.
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program)
+    }
+
+    fn visit_import(&mut self, _import: &Import) {}
+
+    fn visit_item(&mut self, item: &Item) {
+        walk_item(self, item)
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function)
+    }
+
+    fn visit_parameter(&mut self, parameter: &Parameter) {
+        walk_parameter(self, parameter)
+    }
+
+    fn visit_struct(&mut self, structure: &Struct) {
+        walk_struct(self, structure)
+    }
+
+    fn visit_field(&mut self, field: &Field) {
+        self.visit_type(&field.field_type)
+    }
+
+    fn visit_enum(&mut self, enumeration: &Enum) {
+        walk_enum(self, enumeration)
+    }
+
+    fn visit_variant(&mut self, variant: &Variant) {
+        walk_variant(self, variant)
+    }
+
+    fn visit_impl(&mut self, implementation: &Impl) {
+        walk_impl(self, implementation)
+    }
+
+    fn visit_method(&mut self, method: &Method) {
+        self.visit_function(&method.function)
+    }
+
+    fn visit_variable(&mut self, variable: &Variable) {
+        walk_variable(self, variable)
+    }
+
+    fn visit_destructure(&mut self, destructure: &Destructure) {
+        self.visit_expression(&destructure.initializer)
+    }
+
+    fn visit_constant(&mut self, constant: &Constant) {
+        walk_constant(self, constant)
+    }
+
+    fn visit_type(&mut self, _ty: &Type) {}
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_assignment(&mut self, assignment: &Assignment) {
+        walk_assignment(self, assignment)
+    }
+
+    fn visit_if(&mut self, if_statement: &IfStatement) {
+        walk_if(self, if_statement)
+    }
+
+    fn visit_for(&mut self, for_loop: &ForLoop) {
+        walk_for(self, for_loop)
+    }
+
+    fn visit_while(&mut self, while_loop: &WhileLoop) {
+        walk_while(self, while_loop)
+    }
+
+    fn visit_try(&mut self, try_statement: &TryStatement) {
+        walk_try(self, try_statement)
+    }
+
+    fn visit_catch_clause(&mut self, clause: &CatchClause) {
+        self.visit_block(&clause.handler_block)
+    }
+
+    fn visit_throw(&mut self, throw_statement: &ThrowStatement) {
+        self.visit_expression(&throw_statement.value)
+    }
+
+    fn visit_return(&mut self, return_statement: &ReturnStatement) {
+        walk_return(self, return_statement)
+    }
+
+    fn visit_break(&mut self, _break_statement: &BreakStatement) {}
+
+    fn visit_continue(&mut self, _continue_statement: &ContinueStatement) {}
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression)
+    }
+
+    fn visit_literal(&mut self, _literal: &Literal) {}
+
+    fn visit_identifier(&mut self, _identifier: &Identifier) {}
+
+    fn visit_binary(&mut self, binary: &BinaryOp) {
+        walk_binary(self, binary)
+    }
+
+    fn visit_unary(&mut self, unary: &UnaryOp) {
+        self.visit_expression(&unary.operand)
+    }
+
+    fn visit_call(&mut self, call: &FunctionCall) {
+        walk_call(self, call)
+    }
+
+    fn visit_member_access(&mut self, access: &MemberAccess) {
+        self.visit_expression(&access.object)
+    }
+
+    fn visit_list(&mut self, list: &ListLiteral) {
+        walk_list(self, list)
+    }
+
+    fn visit_comprehension(&mut self, comprehension: &Comprehension) {
+        walk_comprehension(self, comprehension)
+    }
+
+    fn visit_tuple(&mut self, tuple: &TupleLiteral) {
+        walk_tuple(self, tuple)
+    }
+
+    fn visit_map(&mut self, map: &MapLiteral) {
+        walk_map(self, map)
+    }
+
+    fn visit_index(&mut self, index: &IndexExpression) {
+        walk_index(self, index)
+    }
+
+    fn visit_slice(&mut self, slice: &SliceExpression) {
+        walk_slice(self, slice)
+    }
+
+    fn visit_struct_init(&mut self, init: &StructInitializer) {
+        walk_struct_init(self, init)
+    }
+
+    fn visit_interpolation(&mut self, interpolation: &Interpolation) {
+        walk_interpolation(self, interpolation)
+    }
+
+    fn visit_range(&mut self, range: &RangeExpression) {
+        walk_range(self, range)
+    }
+
+    fn visit_cast(&mut self, cast: &CastExpression) {
+        walk_cast(self, cast)
+    }
+
+    fn visit_if_expression(&mut self, if_expression: &IfExpression) {
+        walk_if_expression(self, if_expression)
+    }
+
+    fn visit_lambda(&mut self, lambda: &Lambda) {
+        walk_lambda(self, lambda)
+    }
+
+    fn visit_match(&mut self, match_expression: &MatchExpression) {
+        walk_match(self, match_expression)
+    }
+
+    fn visit_match_arm(&mut self, arm: &MatchArm) {
+        walk_match_arm(self, arm)
+    }
+
+    fn visit_pattern(&mut self, _pattern: &Pattern) {}
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for import in &program.imports {
+        visitor.visit_import(import);
+    }
+    for item in &program.items {
+        visitor.visit_item(item);
+    }
+}
+
+pub fn walk_item<V: Visitor + ?Sized>(visitor: &mut V, item: &Item) {
+    match item {
+        Item::Function(function) => visitor.visit_function(function),
+        Item::Struct(structure) => visitor.visit_struct(structure),
+        Item::Enum(enumeration) => visitor.visit_enum(enumeration),
+        Item::Impl(implementation) => visitor.visit_impl(implementation),
+        Item::Variable(variable) => visitor.visit_variable(variable),
+        Item::Constant(constant) => visitor.visit_constant(constant),
+    }
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, function: &Function) {
+    for parameter in &function.parameters {
+        visitor.visit_parameter(parameter);
+    }
+    visitor.visit_type(&function.return_type);
+    visitor.visit_block(&function.body);
+}
+
+pub fn walk_parameter<V: Visitor + ?Sized>(visitor: &mut V, parameter: &Parameter) {
+    visitor.visit_type(&parameter.param_type);
+    if let Some(default) = &parameter.default_value {
+        visitor.visit_expression(default);
+    }
+}
+
+pub fn walk_struct<V: Visitor + ?Sized>(visitor: &mut V, structure: &Struct) {
+    for field in &structure.fields {
+        visitor.visit_field(field);
+    }
+}
+
+pub fn walk_enum<V: Visitor + ?Sized>(visitor: &mut V, enumeration: &Enum) {
+    for variant in &enumeration.variants {
+        visitor.visit_variant(variant);
+    }
+}
+
+pub fn walk_variant<V: Visitor + ?Sized>(visitor: &mut V, variant: &Variant) {
+    for ty in &variant.payload {
+        visitor.visit_type(ty);
+    }
+}
+
+pub fn walk_impl<V: Visitor + ?Sized>(visitor: &mut V, implementation: &Impl) {
+    for method in &implementation.methods {
+        visitor.visit_method(method);
+    }
+}
+
+pub fn walk_variable<V: Visitor + ?Sized>(visitor: &mut V, variable: &Variable) {
+    if let Some(ty) = &variable.var_type {
+        visitor.visit_type(ty);
+    }
+    if let Some(initializer) = &variable.initializer {
+        visitor.visit_expression(initializer);
+    }
+}
+
+pub fn walk_constant<V: Visitor + ?Sized>(visitor: &mut V, constant: &Constant) {
+    visitor.visit_type(&constant.const_type);
+    visitor.visit_expression(&constant.value);
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    for statement in &block.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Statement) {
+    match statement {
+        Statement::Expression(expression) => visitor.visit_expression(expression),
+        Statement::Variable(variable) => visitor.visit_variable(variable),
+        Statement::Destructure(destructure) => visitor.visit_destructure(destructure),
+        Statement::Assignment(assignment) => visitor.visit_assignment(assignment),
+        Statement::If(if_statement) => visitor.visit_if(if_statement),
+        Statement::For(for_loop) => visitor.visit_for(for_loop),
+        Statement::While(while_loop) => visitor.visit_while(while_loop),
+        Statement::Try(try_statement) => visitor.visit_try(try_statement),
+        Statement::Throw(throw_statement) => visitor.visit_throw(throw_statement),
+        Statement::Return(return_statement) => visitor.visit_return(return_statement),
+        Statement::Break(break_statement) => visitor.visit_break(break_statement),
+        Statement::Continue(continue_statement) => visitor.visit_continue(continue_statement),
+    }
+}
+
+pub fn walk_assignment<V: Visitor + ?Sized>(visitor: &mut V, assignment: &Assignment) {
+    visitor.visit_expression(&assignment.target);
+    visitor.visit_expression(&assignment.value);
+}
+
+pub fn walk_if<V: Visitor + ?Sized>(visitor: &mut V, if_statement: &IfStatement) {
+    visitor.visit_expression(&if_statement.condition);
+    visitor.visit_block(&if_statement.then_block);
+    for (condition, block) in &if_statement.else_ifs {
+        visitor.visit_expression(condition);
+        visitor.visit_block(block);
+    }
+    if let Some(else_block) = &if_statement.else_block {
+        visitor.visit_block(else_block);
+    }
+}
+
+pub fn walk_for<V: Visitor + ?Sized>(visitor: &mut V, for_loop: &ForLoop) {
+    visitor.visit_expression(&for_loop.iterable);
+    visitor.visit_block(&for_loop.body);
+}
+
+pub fn walk_while<V: Visitor + ?Sized>(visitor: &mut V, while_loop: &WhileLoop) {
+    visitor.visit_expression(&while_loop.condition);
+    visitor.visit_block(&while_loop.body);
+}
+
+pub fn walk_try<V: Visitor + ?Sized>(visitor: &mut V, try_statement: &TryStatement) {
+    visitor.visit_block(&try_statement.try_block);
+    for clause in &try_statement.catch_clauses {
+        visitor.visit_catch_clause(clause);
+    }
+    if let Some(finally_block) = &try_statement.finally_block {
+        visitor.visit_block(finally_block);
+    }
+}
+
+pub fn walk_return<V: Visitor + ?Sized>(visitor: &mut V, return_statement: &ReturnStatement) {
+    if let Some(value) = &return_statement.value {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &Expression) {
+    match expression {
+        Expression::Literal(literal) => visitor.visit_literal(literal),
+        Expression::Identifier(identifier) => visitor.visit_identifier(identifier),
+        Expression::Binary(binary) => visitor.visit_binary(binary),
+        Expression::Unary(unary) => visitor.visit_unary(unary),
+        Expression::Call(call) => visitor.visit_call(call),
+        Expression::MemberAccess(access) => visitor.visit_member_access(access),
+        Expression::List(list) => visitor.visit_list(list),
+        Expression::Comprehension(comprehension) => visitor.visit_comprehension(comprehension),
+        Expression::Tuple(tuple) => visitor.visit_tuple(tuple),
+        Expression::Map(map) => visitor.visit_map(map),
+        Expression::Index(index) => visitor.visit_index(index),
+        Expression::Slice(slice) => visitor.visit_slice(slice),
+        Expression::StructInit(init) => visitor.visit_struct_init(init),
+        Expression::Match(match_expression) => visitor.visit_match(match_expression),
+        Expression::Interpolation(interpolation) => visitor.visit_interpolation(interpolation),
+        Expression::Range(range) => visitor.visit_range(range),
+        Expression::Cast(cast) => visitor.visit_cast(cast),
+        Expression::If(if_expression) => visitor.visit_if_expression(if_expression),
+        Expression::Lambda(lambda) => visitor.visit_lambda(lambda),
+    }
+}
+
+pub fn walk_binary<V: Visitor + ?Sized>(visitor: &mut V, binary: &BinaryOp) {
+    visitor.visit_expression(&binary.left);
+    visitor.visit_expression(&binary.right);
+}
+
+pub fn walk_call<V: Visitor + ?Sized>(visitor: &mut V, call: &FunctionCall) {
+    visitor.visit_expression(&call.function);
+    for argument in &call.arguments {
+        visitor.visit_expression(argument);
+    }
+    for argument in &call.named_arguments {
+        visitor.visit_expression(&argument.value);
+    }
+}
+
+pub fn walk_list<V: Visitor + ?Sized>(visitor: &mut V, list: &ListLiteral) {
+    for element in &list.elements {
+        visitor.visit_expression(element);
+    }
+}
+
+pub fn walk_comprehension<V: Visitor + ?Sized>(visitor: &mut V, comprehension: &Comprehension) {
+    visitor.visit_expression(&comprehension.iterable);
+    if let Some(condition) = &comprehension.condition {
+        visitor.visit_expression(condition);
+    }
+    visitor.visit_expression(&comprehension.element);
+}
+
+pub fn walk_tuple<V: Visitor + ?Sized>(visitor: &mut V, tuple: &TupleLiteral) {
+    for element in &tuple.elements {
+        visitor.visit_expression(element);
+    }
+}
+
+pub fn walk_map<V: Visitor + ?Sized>(visitor: &mut V, map: &MapLiteral) {
+    for (key, value) in &map.entries {
+        visitor.visit_expression(key);
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_interpolation<V: Visitor + ?Sized>(visitor: &mut V, interpolation: &Interpolation) {
+    for part in &interpolation.parts {
+        if let InterpolationPart::Expression(expression) = part {
+            visitor.visit_expression(expression);
+        }
+    }
+}
+
+pub fn walk_range<V: Visitor + ?Sized>(visitor: &mut V, range: &RangeExpression) {
+    visitor.visit_expression(&range.start);
+    visitor.visit_expression(&range.end);
+}
+
+pub fn walk_cast<V: Visitor + ?Sized>(visitor: &mut V, cast: &CastExpression) {
+    visitor.visit_expression(&cast.value);
+    visitor.visit_type(&cast.target);
+}
+
+pub fn walk_if_expression<V: Visitor + ?Sized>(visitor: &mut V, if_expression: &IfExpression) {
+    visitor.visit_expression(&if_expression.condition);
+    visitor.visit_expression(&if_expression.then_value);
+    visitor.visit_expression(&if_expression.else_value);
+}
+
+pub fn walk_lambda<V: Visitor + ?Sized>(visitor: &mut V, lambda: &Lambda) {
+    for ty in lambda.parameters.iter().filter_map(|p| p.param_type.as_ref()) {
+        visitor.visit_type(ty);
+    }
+    match lambda.body.as_ref() {
+        LambdaBody::Block(return_type, block) => {
+            visitor.visit_type(return_type);
+            visitor.visit_block(block);
+        }
+        LambdaBody::Expression(expression) => visitor.visit_expression(expression),
+    }
+}
+
+pub fn walk_index<V: Visitor + ?Sized>(visitor: &mut V, index: &IndexExpression) {
+    visitor.visit_expression(&index.object);
+    visitor.visit_expression(&index.index);
+}
+
+pub fn walk_slice<V: Visitor + ?Sized>(visitor: &mut V, slice: &SliceExpression) {
+    visitor.visit_expression(&slice.object);
+    for bound in [&slice.start, &slice.end].into_iter().flatten() {
+        visitor.visit_expression(bound);
+    }
+}
+
+/// Visits field initializers sorted by field name, so traversal order does
+/// not depend on `HashMap` iteration order.
+pub fn walk_struct_init<V: Visitor + ?Sized>(visitor: &mut V, init: &StructInitializer) {
+    let mut fields: Vec<_> = init.fields.iter().collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    for (_, value) in fields {
+        visitor.visit_expression(value);
+    }
+}
+
+pub fn walk_match<V: Visitor + ?Sized>(visitor: &mut V, match_expression: &MatchExpression) {
+    visitor.visit_expression(&match_expression.scrutinee);
+    for arm in &match_expression.arms {
+        visitor.visit_match_arm(arm);
+    }
+}
+
+pub fn walk_match_arm<V: Visitor + ?Sized>(visitor: &mut V, arm: &MatchArm) {
+    visitor.visit_pattern(&arm.pattern);
+    visitor.visit_expression(&arm.body);
+}
+
+/// In-place rewriting counterpart of [`Visitor`]: same nodes, same order,
+/// with mutable access so a pass can replace or remove what it visits.
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_import_mut(&mut self, _import: &mut Import) {}
+
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        walk_item_mut(self, item)
+    }
+
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        walk_function_mut(self, function)
+    }
+
+    fn visit_parameter_mut(&mut self, parameter: &mut Parameter) {
+        walk_parameter_mut(self, parameter)
+    }
+
+    fn visit_struct_mut(&mut self, structure: &mut Struct) {
+        walk_struct_mut(self, structure)
+    }
+
+    fn visit_field_mut(&mut self, field: &mut Field) {
+        self.visit_type_mut(&mut field.field_type)
+    }
+
+    fn visit_enum_mut(&mut self, enumeration: &mut Enum) {
+        walk_enum_mut(self, enumeration)
+    }
+
+    fn visit_variant_mut(&mut self, variant: &mut Variant) {
+        walk_variant_mut(self, variant)
+    }
+
+    fn visit_impl_mut(&mut self, implementation: &mut Impl) {
+        walk_impl_mut(self, implementation)
+    }
+
+    fn visit_method_mut(&mut self, method: &mut Method) {
+        self.visit_function_mut(&mut method.function)
+    }
+
+    fn visit_variable_mut(&mut self, variable: &mut Variable) {
+        walk_variable_mut(self, variable)
+    }
+
+    fn visit_destructure_mut(&mut self, destructure: &mut Destructure) {
+        self.visit_expression_mut(&mut destructure.initializer)
+    }
+
+    fn visit_constant_mut(&mut self, constant: &mut Constant) {
+        walk_constant_mut(self, constant)
+    }
+
+    fn visit_type_mut(&mut self, _ty: &mut Type) {}
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_assignment_mut(&mut self, assignment: &mut Assignment) {
+        walk_assignment_mut(self, assignment)
+    }
+
+    fn visit_if_mut(&mut self, if_statement: &mut IfStatement) {
+        walk_if_mut(self, if_statement)
+    }
+
+    fn visit_for_mut(&mut self, for_loop: &mut ForLoop) {
+        walk_for_mut(self, for_loop)
+    }
+
+    fn visit_while_mut(&mut self, while_loop: &mut WhileLoop) {
+        walk_while_mut(self, while_loop)
+    }
+
+    fn visit_try_mut(&mut self, try_statement: &mut TryStatement) {
+        walk_try_mut(self, try_statement)
+    }
+
+    fn visit_catch_clause_mut(&mut self, clause: &mut CatchClause) {
+        self.visit_block_mut(&mut clause.handler_block)
+    }
+
+    fn visit_throw_mut(&mut self, throw_statement: &mut ThrowStatement) {
+        self.visit_expression_mut(&mut throw_statement.value)
+    }
+
+    fn visit_return_mut(&mut self, return_statement: &mut ReturnStatement) {
+        walk_return_mut(self, return_statement)
+    }
+
+    fn visit_break_mut(&mut self, _break_statement: &mut BreakStatement) {}
+
+    fn visit_continue_mut(&mut self, _continue_statement: &mut ContinueStatement) {}
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression)
+    }
+
+    fn visit_literal_mut(&mut self, _literal: &mut Literal) {}
+
+    fn visit_identifier_mut(&mut self, _identifier: &mut Identifier) {}
+
+    fn visit_binary_mut(&mut self, binary: &mut BinaryOp) {
+        walk_binary_mut(self, binary)
+    }
+
+    fn visit_unary_mut(&mut self, unary: &mut UnaryOp) {
+        self.visit_expression_mut(&mut unary.operand)
+    }
+
+    fn visit_call_mut(&mut self, call: &mut FunctionCall) {
+        walk_call_mut(self, call)
+    }
+
+    fn visit_member_access_mut(&mut self, access: &mut MemberAccess) {
+        self.visit_expression_mut(&mut access.object)
+    }
+
+    fn visit_list_mut(&mut self, list: &mut ListLiteral) {
+        walk_list_mut(self, list)
+    }
+
+    fn visit_comprehension_mut(&mut self, comprehension: &mut Comprehension) {
+        walk_comprehension_mut(self, comprehension)
+    }
+
+    fn visit_tuple_mut(&mut self, tuple: &mut TupleLiteral) {
+        walk_tuple_mut(self, tuple)
+    }
+
+    fn visit_map_mut(&mut self, map: &mut MapLiteral) {
+        walk_map_mut(self, map)
+    }
+
+    fn visit_index_mut(&mut self, index: &mut IndexExpression) {
+        walk_index_mut(self, index)
+    }
+
+    fn visit_slice_mut(&mut self, slice: &mut SliceExpression) {
+        walk_slice_mut(self, slice)
+    }
+
+    fn visit_interpolation_mut(&mut self, interpolation: &mut Interpolation) {
+        walk_interpolation_mut(self, interpolation)
+    }
+
+    fn visit_range_mut(&mut self, range: &mut RangeExpression) {
+        walk_range_mut(self, range)
+    }
+
+    fn visit_cast_mut(&mut self, cast: &mut CastExpression) {
+        walk_cast_mut(self, cast)
+    }
+
+    fn visit_if_expression_mut(&mut self, if_expression: &mut IfExpression) {
+        walk_if_expression_mut(self, if_expression)
+    }
+
+    fn visit_lambda_mut(&mut self, lambda: &mut Lambda) {
+        walk_lambda_mut(self, lambda)
+    }
+
+    fn visit_struct_init_mut(&mut self, init: &mut StructInitializer) {
+        walk_struct_init_mut(self, init)
+    }
+
+    fn visit_match_mut(&mut self, match_expression: &mut MatchExpression) {
+        walk_match_mut(self, match_expression)
+    }
+
+    fn visit_match_arm_mut(&mut self, arm: &mut MatchArm) {
+        walk_match_arm_mut(self, arm)
+    }
+
+    fn visit_pattern_mut(&mut self, _pattern: &mut Pattern) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    for import in &mut program.imports {
+        visitor.visit_import_mut(import);
+    }
+    for item in &mut program.items {
+        visitor.visit_item_mut(item);
+    }
+}
+
+pub fn walk_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut Item) {
+    match item {
+        Item::Function(function) => visitor.visit_function_mut(function),
+        Item::Struct(structure) => visitor.visit_struct_mut(structure),
+        Item::Enum(enumeration) => visitor.visit_enum_mut(enumeration),
+        Item::Impl(implementation) => visitor.visit_impl_mut(implementation),
+        Item::Variable(variable) => visitor.visit_variable_mut(variable),
+        Item::Constant(constant) => visitor.visit_constant_mut(constant),
+    }
+}
+
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(visitor: &mut V, function: &mut Function) {
+    for parameter in &mut function.parameters {
+        visitor.visit_parameter_mut(parameter);
+    }
+    visitor.visit_type_mut(&mut function.return_type);
+    visitor.visit_block_mut(&mut function.body);
+}
+
+pub fn walk_parameter_mut<V: VisitorMut + ?Sized>(visitor: &mut V, parameter: &mut Parameter) {
+    visitor.visit_type_mut(&mut parameter.param_type);
+    if let Some(default) = &mut parameter.default_value {
+        visitor.visit_expression_mut(default);
+    }
+}
+
+pub fn walk_struct_mut<V: VisitorMut + ?Sized>(visitor: &mut V, structure: &mut Struct) {
+    for field in &mut structure.fields {
+        visitor.visit_field_mut(field);
+    }
+}
+
+pub fn walk_enum_mut<V: VisitorMut + ?Sized>(visitor: &mut V, enumeration: &mut Enum) {
+    for variant in &mut enumeration.variants {
+        visitor.visit_variant_mut(variant);
+    }
+}
+
+pub fn walk_variant_mut<V: VisitorMut + ?Sized>(visitor: &mut V, variant: &mut Variant) {
+    for ty in &mut variant.payload {
+        visitor.visit_type_mut(ty);
+    }
+}
+
+pub fn walk_impl_mut<V: VisitorMut + ?Sized>(visitor: &mut V, implementation: &mut Impl) {
+    for method in &mut implementation.methods {
+        visitor.visit_method_mut(method);
+    }
+}
+
+pub fn walk_variable_mut<V: VisitorMut + ?Sized>(visitor: &mut V, variable: &mut Variable) {
+    if let Some(ty) = &mut variable.var_type {
+        visitor.visit_type_mut(ty);
+    }
+    if let Some(initializer) = &mut variable.initializer {
+        visitor.visit_expression_mut(initializer);
+    }
+}
+
+pub fn walk_constant_mut<V: VisitorMut + ?Sized>(visitor: &mut V, constant: &mut Constant) {
+    visitor.visit_type_mut(&mut constant.const_type);
+    visitor.visit_expression_mut(&mut constant.value);
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    for statement in &mut block.statements {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(visitor: &mut V, statement: &mut Statement) {
+    match statement {
+        Statement::Expression(expression) => visitor.visit_expression_mut(expression),
+        Statement::Variable(variable) => visitor.visit_variable_mut(variable),
+        Statement::Destructure(destructure) => visitor.visit_destructure_mut(destructure),
+        Statement::Assignment(assignment) => visitor.visit_assignment_mut(assignment),
+        Statement::If(if_statement) => visitor.visit_if_mut(if_statement),
+        Statement::For(for_loop) => visitor.visit_for_mut(for_loop),
+        Statement::While(while_loop) => visitor.visit_while_mut(while_loop),
+        Statement::Try(try_statement) => visitor.visit_try_mut(try_statement),
+        Statement::Throw(throw_statement) => visitor.visit_throw_mut(throw_statement),
+        Statement::Return(return_statement) => visitor.visit_return_mut(return_statement),
+        Statement::Break(break_statement) => visitor.visit_break_mut(break_statement),
+        Statement::Continue(continue_statement) => visitor.visit_continue_mut(continue_statement),
+    }
+}
+
+pub fn walk_assignment_mut<V: VisitorMut + ?Sized>(visitor: &mut V, assignment: &mut Assignment) {
+    visitor.visit_expression_mut(&mut assignment.target);
+    visitor.visit_expression_mut(&mut assignment.value);
+}
+
+pub fn walk_if_mut<V: VisitorMut + ?Sized>(visitor: &mut V, if_statement: &mut IfStatement) {
+    visitor.visit_expression_mut(&mut if_statement.condition);
+    visitor.visit_block_mut(&mut if_statement.then_block);
+    for (condition, block) in &mut if_statement.else_ifs {
+        visitor.visit_expression_mut(condition);
+        visitor.visit_block_mut(block);
+    }
+    if let Some(else_block) = &mut if_statement.else_block {
+        visitor.visit_block_mut(else_block);
+    }
+}
+
+pub fn walk_for_mut<V: VisitorMut + ?Sized>(visitor: &mut V, for_loop: &mut ForLoop) {
+    visitor.visit_expression_mut(&mut for_loop.iterable);
+    visitor.visit_block_mut(&mut for_loop.body);
+}
+
+pub fn walk_while_mut<V: VisitorMut + ?Sized>(visitor: &mut V, while_loop: &mut WhileLoop) {
+    visitor.visit_expression_mut(&mut while_loop.condition);
+    visitor.visit_block_mut(&mut while_loop.body);
+}
+
+pub fn walk_try_mut<V: VisitorMut + ?Sized>(visitor: &mut V, try_statement: &mut TryStatement) {
+    visitor.visit_block_mut(&mut try_statement.try_block);
+    for clause in &mut try_statement.catch_clauses {
+        visitor.visit_catch_clause_mut(clause);
+    }
+    if let Some(finally_block) = &mut try_statement.finally_block {
+        visitor.visit_block_mut(finally_block);
+    }
+}
+
+pub fn walk_return_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    return_statement: &mut ReturnStatement,
+) {
+    if let Some(value) = &mut return_statement.value {
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expression: &mut Expression) {
+    match expression {
+        Expression::Literal(literal) => visitor.visit_literal_mut(literal),
+        Expression::Identifier(identifier) => visitor.visit_identifier_mut(identifier),
+        Expression::Binary(binary) => visitor.visit_binary_mut(binary),
+        Expression::Unary(unary) => visitor.visit_unary_mut(unary),
+        Expression::Call(call) => visitor.visit_call_mut(call),
+        Expression::MemberAccess(access) => visitor.visit_member_access_mut(access),
+        Expression::List(list) => visitor.visit_list_mut(list),
+        Expression::Comprehension(comprehension) => {
+            visitor.visit_comprehension_mut(comprehension)
+        }
+        Expression::Tuple(tuple) => visitor.visit_tuple_mut(tuple),
+        Expression::Map(map) => visitor.visit_map_mut(map),
+        Expression::Index(index) => visitor.visit_index_mut(index),
+        Expression::Slice(slice) => visitor.visit_slice_mut(slice),
+        Expression::StructInit(init) => visitor.visit_struct_init_mut(init),
+        Expression::Match(match_expression) => visitor.visit_match_mut(match_expression),
+        Expression::Interpolation(interpolation) => {
+            visitor.visit_interpolation_mut(interpolation)
+        }
+        Expression::Range(range) => visitor.visit_range_mut(range),
+        Expression::Cast(cast) => visitor.visit_cast_mut(cast),
+        Expression::If(if_expression) => visitor.visit_if_expression_mut(if_expression),
+        Expression::Lambda(lambda) => visitor.visit_lambda_mut(lambda),
+    }
+}
+
+pub fn walk_binary_mut<V: VisitorMut + ?Sized>(visitor: &mut V, binary: &mut BinaryOp) {
+    visitor.visit_expression_mut(&mut binary.left);
+    visitor.visit_expression_mut(&mut binary.right);
+}
+
+pub fn walk_call_mut<V: VisitorMut + ?Sized>(visitor: &mut V, call: &mut FunctionCall) {
+    visitor.visit_expression_mut(&mut call.function);
+    for argument in &mut call.arguments {
+        visitor.visit_expression_mut(argument);
+    }
+    for argument in &mut call.named_arguments {
+        visitor.visit_expression_mut(&mut argument.value);
+    }
+}
+
+pub fn walk_list_mut<V: VisitorMut + ?Sized>(visitor: &mut V, list: &mut ListLiteral) {
+    for element in &mut list.elements {
+        visitor.visit_expression_mut(element);
+    }
+}
+
+pub fn walk_comprehension_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    comprehension: &mut Comprehension,
+) {
+    visitor.visit_expression_mut(&mut comprehension.iterable);
+    if let Some(condition) = &mut comprehension.condition {
+        visitor.visit_expression_mut(condition);
+    }
+    visitor.visit_expression_mut(&mut comprehension.element);
+}
+
+pub fn walk_tuple_mut<V: VisitorMut + ?Sized>(visitor: &mut V, tuple: &mut TupleLiteral) {
+    for element in &mut tuple.elements {
+        visitor.visit_expression_mut(element);
+    }
+}
+
+pub fn walk_interpolation_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    interpolation: &mut Interpolation,
+) {
+    for part in &mut interpolation.parts {
+        if let InterpolationPart::Expression(expression) = part {
+            visitor.visit_expression_mut(expression);
+        }
+    }
+}
+
+pub fn walk_range_mut<V: VisitorMut + ?Sized>(visitor: &mut V, range: &mut RangeExpression) {
+    visitor.visit_expression_mut(&mut range.start);
+    visitor.visit_expression_mut(&mut range.end);
+}
+
+pub fn walk_cast_mut<V: VisitorMut + ?Sized>(visitor: &mut V, cast: &mut CastExpression) {
+    visitor.visit_expression_mut(&mut cast.value);
+    visitor.visit_type_mut(&mut cast.target);
+}
+
+pub fn walk_if_expression_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    if_expression: &mut IfExpression,
+) {
+    visitor.visit_expression_mut(&mut if_expression.condition);
+    visitor.visit_expression_mut(&mut if_expression.then_value);
+    visitor.visit_expression_mut(&mut if_expression.else_value);
+}
+
+pub fn walk_lambda_mut<V: VisitorMut + ?Sized>(visitor: &mut V, lambda: &mut Lambda) {
+    for ty in lambda.parameters.iter_mut().filter_map(|p| p.param_type.as_mut()) {
+        visitor.visit_type_mut(ty);
+    }
+    match lambda.body.as_mut() {
+        LambdaBody::Block(return_type, block) => {
+            visitor.visit_type_mut(return_type);
+            visitor.visit_block_mut(block);
+        }
+        LambdaBody::Expression(expression) => visitor.visit_expression_mut(expression),
+    }
+}
+
+pub fn walk_map_mut<V: VisitorMut + ?Sized>(visitor: &mut V, map: &mut MapLiteral) {
+    for (key, value) in &mut map.entries {
+        visitor.visit_expression_mut(key);
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_index_mut<V: VisitorMut + ?Sized>(visitor: &mut V, index: &mut IndexExpression) {
+    visitor.visit_expression_mut(&mut index.object);
+    visitor.visit_expression_mut(&mut index.index);
+}
+
+pub fn walk_slice_mut<V: VisitorMut + ?Sized>(visitor: &mut V, slice: &mut SliceExpression) {
+    visitor.visit_expression_mut(&mut slice.object);
+    for bound in [&mut slice.start, &mut slice.end].into_iter().flatten() {
+        visitor.visit_expression_mut(bound);
+    }
+}
+
+pub fn walk_struct_init_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    init: &mut StructInitializer,
+) {
+    let mut fields: Vec<_> = init.fields.iter_mut().collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+    for (_, value) in fields {
+        visitor.visit_expression_mut(value);
+    }
+}
+
+pub fn walk_match_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    match_expression: &mut MatchExpression,
+) {
+    visitor.visit_expression_mut(&mut match_expression.scrutinee);
+    for arm in &mut match_expression.arms {
+        visitor.visit_match_arm_mut(arm);
+    }
+}
+
+pub fn walk_match_arm_mut<V: VisitorMut + ?Sized>(visitor: &mut V, arm: &mut MatchArm) {
+    visitor.visit_pattern_mut(&mut arm.pattern);
+    visitor.visit_expression_mut(&mut arm.body);
+}
@@ -0,0 +1,274 @@
+//! AST JSON as an interchange format: `--emit ast-json` writes it and
+//! `--from ast-json` reads it back, so other frontends and code generators can
+//! hand programs straight to semantic analysis and codegen.
+//!
+//! A document is `{"schema_version": N, "program": {...}}`, where the program
+//! is the serde form of `ast::Program`. Documents from another schema version
+//! are rejected rather than guessed at.
+
+use crate::compiler::ast::*;
+use crate::compiler::intern::Symbol;
+use crate::compiler::lexer::KEYWORDS;
+use crate::diagnostics::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Document<P> {
+    schema_version: u32,
+    program: P,
+}
+
+pub fn to_json(program: &Program) -> String {
+    let document = Document {
+        schema_version: AST_SCHEMA_VERSION,
+        program,
+    };
+    let mut json = serde_json::to_string_pretty(&document).expect("AST is always serializable");
+    json.push('\n');
+    json
+}
+
+/// Reads and validates a document written by `to_json`; `file` names it in errors.
+pub fn from_json(text: &str, file: &str) -> Result<Program> {
+    #[derive(Deserialize)]
+    struct Version {
+        schema_version: Option<u32>,
+    }
+
+    let invalid = |message: String| Error::ParseError(format!("{}: {}", file, message));
+
+    // Check the version first, so a document from another version gets a clear
+    // error rather than whatever shape mismatch it happens to hit.
+    let version: Version =
+        serde_json::from_str(text).map_err(|e| invalid(format!("invalid AST JSON: {}", e)))?;
+    match version.schema_version {
+        Some(AST_SCHEMA_VERSION) => {}
+        Some(other) => {
+            return Err(invalid(format!(
+                "AST schema version {} is not supported (expected {})",
+                other, AST_SCHEMA_VERSION
+            )))
+        }
+        None => return Err(invalid("missing `schema_version`".to_string())),
+    }
+
+    let document: Document<Program> =
+        serde_json::from_str(text).map_err(|e| invalid(format!("invalid AST JSON: {}", e)))?;
+
+    let mut validator = Validator::default();
+    validator.visit_program(&document.program);
+    match validator.errors.first() {
+        Some(message) => Err(invalid(message.clone())),
+        None => Ok(document.program),
+    }
+}
+
+/// Checks the invariants the parser guarantees and later stages rely on,
+/// which a hand-built AST may not uphold.
+#[derive(Default)]
+struct Validator {
+    errors: Vec<String>,
+}
+
+impl Validator {
+    fn name(&mut self, what: &str, name: &str) {
+        let mut chars = name.chars();
+        let valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || c == '_')
+            && !KEYWORDS.contains(&name);
+        if !valid {
+            self.errors
+                .push(format!("`{}` is not a valid {} name", name, what));
+        }
+    }
+}
+
+impl Visitor for Validator {
+    fn visit_import(&mut self, import: &Import) {
+        for part in import.module_path.split('.') {
+            self.name("module", part);
+        }
+        if let Some(alias) = import.alias {
+            self.name("module", &alias);
+        }
+        for item in import.items.iter().flatten() {
+            self.name("item", &item.name);
+            if let Some(alias) = item.alias {
+                self.name("item", &alias);
+            }
+        }
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        self.name("function", &function.name);
+        for param in &function.type_params {
+            self.name("type parameter", param);
+        }
+        walk_function(self, function);
+    }
+
+    fn visit_parameter(&mut self, parameter: &Parameter) {
+        self.name("parameter", &parameter.name);
+        walk_parameter(self, parameter);
+    }
+
+    fn visit_lambda(&mut self, lambda: &Lambda) {
+        for parameter in &lambda.parameters {
+            self.name("parameter", &parameter.name);
+        }
+        walk_lambda(self, lambda);
+    }
+
+    fn visit_struct(&mut self, structure: &Struct) {
+        self.name("struct", &structure.name);
+        for param in &structure.type_params {
+            self.name("type parameter", param);
+        }
+        walk_struct(self, structure);
+    }
+
+    fn visit_enum(&mut self, enumeration: &Enum) {
+        self.name("enum", &enumeration.name);
+        walk_enum(self, enumeration);
+    }
+
+    fn visit_variant(&mut self, variant: &Variant) {
+        self.name("variant", &variant.name);
+        walk_variant(self, variant);
+    }
+
+    fn visit_impl(&mut self, implementation: &Impl) {
+        self.name("struct", &implementation.struct_name);
+        for param in &implementation.type_params {
+            self.name("type parameter", param);
+        }
+        walk_impl(self, implementation);
+    }
+
+    fn visit_method(&mut self, method: &Method) {
+        self.name("method", &method.function.name);
+        for param in &method.function.type_params {
+            self.name("type parameter", param);
+        }
+        walk_function(self, &method.function);
+    }
+
+    fn visit_field(&mut self, field: &Field) {
+        self.name("field", &field.name);
+        self.visit_type(&field.field_type);
+    }
+
+    fn visit_variable(&mut self, variable: &Variable) {
+        self.name("variable", &variable.name);
+        walk_variable(self, variable);
+    }
+
+    fn visit_destructure(&mut self, destructure: &Destructure) {
+        for identifier in &destructure.names {
+            self.name("variable", &identifier.name);
+        }
+        self.visit_expression(&destructure.initializer);
+    }
+
+    fn visit_constant(&mut self, constant: &Constant) {
+        self.name("constant", &constant.name);
+        walk_constant(self, constant);
+    }
+
+    fn visit_type(&mut self, ty: &Type) {
+        match ty {
+            Type::Struct(name) | Type::Param(name) => self.name("type", name),
+            Type::Generic(name, arguments) => {
+                self.name("type", name);
+                for argument in arguments {
+                    self.visit_type(argument);
+                }
+            }
+            Type::List(element) | Type::Optional(element) => self.visit_type(element),
+            Type::Tuple(elements) => {
+                for element in elements {
+                    self.visit_type(element);
+                }
+            }
+            Type::Function(parameters, return_type) => {
+                for parameter in parameters {
+                    self.visit_type(parameter);
+                }
+                self.visit_type(return_type);
+            }
+            Type::Map(key, value) => {
+                self.visit_type(key);
+                self.visit_type(value);
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_assignment(&mut self, assignment: &Assignment) {
+        if !matches!(
+            assignment.target,
+            Expression::Identifier(_) | Expression::MemberAccess(_) | Expression::Index(_)
+        ) {
+            self.errors
+                .push(format!("invalid assignment target at {}", assignment.span));
+        }
+        walk_assignment(self, assignment);
+    }
+
+    fn visit_for(&mut self, for_loop: &ForLoop) {
+        self.name("variable", &for_loop.variable);
+        walk_for(self, for_loop);
+    }
+
+    fn visit_try(&mut self, try_statement: &TryStatement) {
+        if try_statement.catch_clauses.is_empty() && try_statement.finally_block.is_none() {
+            self.errors.push(format!(
+                "`try` without a `catch` or `finally` clause at {}",
+                try_statement.span
+            ));
+        }
+        walk_try(self, try_statement);
+    }
+
+    fn visit_catch_clause(&mut self, clause: &CatchClause) {
+        self.name("exception type", &clause.exception_type);
+        self.visit_block(&clause.handler_block);
+    }
+
+    fn visit_identifier(&mut self, identifier: &Identifier) {
+        // `self` is a keyword, but also the receiver of a method.
+        if identifier.name != "self" {
+            self.name("variable", &identifier.name);
+        }
+    }
+
+    fn visit_member_access(&mut self, access: &MemberAccess) {
+        self.name("member", &access.member);
+        self.visit_expression(&access.object);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Binding(name) => self.name("variable", name),
+            Pattern::Variant(variant) => {
+                self.name("enum", &variant.enum_name);
+                self.name("variant", &variant.variant);
+                for binding in &variant.bindings {
+                    self.name("variable", binding);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+        }
+    }
+
+    fn visit_struct_init(&mut self, init: &StructInitializer) {
+        self.name("struct", &init.struct_name);
+        let mut fields: Vec<&Symbol> = init.fields.keys().collect();
+        fields.sort();
+        for field in fields {
+            self.name("field", field);
+        }
+        walk_struct_init(self, init);
+    }
+}
@@ -0,0 +1,231 @@
+//! Signatures and Rust lowerings for the built-in modules (`math`, `io`) and
+//! the methods available on built-in types.
+//!
+//! Lowerings are small Rust templates where `$self` stands for the receiver and
+//! `$0`, `$1`, ... for the already-generated argument expressions.
+
+use crate::compiler::ast::Type;
+
+pub const MODULES: &[&str] = &["math", "io"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamKind {
+    Exact(Type),
+    /// Accepts a value of any type; the code generator formats it for display.
+    Printable,
+}
+
+#[derive(Debug, Clone)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub params: Vec<ParamKind>,
+    pub return_type: Type,
+    /// Methods that modify their receiver require it to be a mutable place.
+    pub mutates_receiver: bool,
+    template: &'static str,
+}
+
+impl Builtin {
+    fn new(
+        name: &'static str,
+        params: Vec<ParamKind>,
+        return_type: Type,
+        template: &'static str,
+    ) -> Self {
+        Self {
+            name,
+            params,
+            return_type,
+            mutates_receiver: false,
+            template,
+        }
+    }
+
+    fn mutating(mut self) -> Self {
+        self.mutates_receiver = true;
+        self
+    }
+
+    pub fn lower(&self, receiver: Option<&str>, args: &[String]) -> String {
+        let mut output = String::new();
+        let mut chars = self.template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                output.push(c);
+                continue;
+            }
+
+            if chars.peek() == Some(&'s') {
+                for _ in 0.."self".len() {
+                    chars.next();
+                }
+                output.push_str(receiver.unwrap_or_default());
+                continue;
+            }
+
+            let mut index = String::new();
+            while let Some(digit) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                index.push(*digit);
+                chars.next();
+            }
+            let index: usize = index.parse().unwrap_or_default();
+            output.push_str(args.get(index).map(String::as_str).unwrap_or_default());
+        }
+
+        output
+    }
+}
+
+pub fn is_module(name: &str) -> bool {
+    MODULES.contains(&name)
+}
+
+pub fn functions(module: &str) -> Vec<Builtin> {
+    use ParamKind::{Exact, Printable};
+
+    match module {
+        "math" => {
+            let unary = |name, template| {
+                Builtin::new(name, vec![Exact(Type::Float)], Type::Float, template)
+            };
+            let binary = |name, template| {
+                Builtin::new(
+                    name,
+                    vec![Exact(Type::Float), Exact(Type::Float)],
+                    Type::Float,
+                    template,
+                )
+            };
+            vec![
+                unary("sqrt", "f64::sqrt($0)"),
+                unary("abs", "f64::abs($0)"),
+                unary("floor", "f64::floor($0)"),
+                unary("ceil", "f64::ceil($0)"),
+                unary("round", "f64::round($0)"),
+                unary("sin", "f64::sin($0)"),
+                unary("cos", "f64::cos($0)"),
+                unary("tan", "f64::tan($0)"),
+                unary("exp", "f64::exp($0)"),
+                unary("ln", "f64::ln($0)"),
+                binary("pow", "f64::powf($0, $1)"),
+                binary("min", "f64::min($0, $1)"),
+                binary("max", "f64::max($0, $1)"),
+            ]
+        }
+        "io" => vec![
+            Builtin::new("print", vec![Printable], Type::Void, "println!(\"{}\", $0)"),
+            Builtin::new(
+                "input",
+                vec![Exact(Type::Str)],
+                Type::Str,
+                "{ use std::io::Write; print!(\"{}\", $0); std::io::stdout().flush().ok(); \
+                 let mut line = String::new(); std::io::stdin().read_line(&mut line).ok(); \
+                 line.trim_end_matches(['\\r', '\\n']).to_string() }",
+            ),
+            Builtin::new(
+                "args",
+                vec![],
+                Type::List(Box::new(Type::Str)),
+                "std::env::args().skip(1).collect::<Vec<String>>()",
+            ),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+pub fn function(module: &str, name: &str) -> Option<Builtin> {
+    functions(module).into_iter().find(|f| f.name == name)
+}
+
+pub fn constants(module: &str) -> Vec<(&'static str, Type, &'static str)> {
+    match module {
+        "math" => vec![
+            ("pi", Type::Float, "std::f64::consts::PI"),
+            ("e", Type::Float, "std::f64::consts::E"),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+pub fn constant(module: &str, name: &str) -> Option<(Type, &'static str)> {
+    constants(module)
+        .into_iter()
+        .find(|(constant, _, _)| *constant == name)
+        .map(|(_, ty, rust)| (ty, rust))
+}
+
+pub fn methods(receiver: &Type) -> Vec<Builtin> {
+    use ParamKind::Exact;
+
+    match receiver {
+        Type::List(element) => {
+            let element = element.as_ref().clone();
+            vec![
+                Builtin::new("len", vec![], Type::Int, "($self.len() as i64)"),
+                Builtin::new(
+                    "push",
+                    vec![Exact(element.clone())],
+                    Type::Void,
+                    "$self.push($0)",
+                )
+                .mutating(),
+                Builtin::new(
+                    "pop",
+                    vec![],
+                    element.clone(),
+                    "$self.pop().expect(\"pop from empty list\")",
+                )
+                .mutating(),
+                Builtin::new(
+                    "contains",
+                    vec![Exact(element)],
+                    Type::Bool,
+                    "$self.contains(&$0)",
+                ),
+            ]
+        }
+        Type::Map(key, value) => {
+            let (key, value) = (key.as_ref().clone(), value.as_ref().clone());
+            vec![
+                Builtin::new("len", vec![], Type::Int, "($self.len() as i64)"),
+                Builtin::new(
+                    "contains",
+                    vec![Exact(key.clone())],
+                    Type::Bool,
+                    "$self.contains_key(&$0)",
+                ),
+                Builtin::new(
+                    "remove",
+                    vec![Exact(key.clone())],
+                    value,
+                    "$self.remove(&$0).expect(\"remove of missing key\")",
+                )
+                .mutating(),
+                Builtin::new(
+                    "keys",
+                    vec![],
+                    Type::List(Box::new(key)),
+                    "{ let mut keys: Vec<_> = $self.keys().cloned().collect(); keys.sort(); keys }",
+                ),
+            ]
+        }
+        Type::Str => vec![
+            Builtin::new("len", vec![], Type::Int, "($self.chars().count() as i64)"),
+            Builtin::new("upper", vec![], Type::Str, "$self.to_uppercase()"),
+            Builtin::new("lower", vec![], Type::Str, "$self.to_lowercase()"),
+            Builtin::new("trim", vec![], Type::Str, "$self.trim().to_string()"),
+            Builtin::new(
+                "contains",
+                vec![Exact(Type::Str)],
+                Type::Bool,
+                "$self.contains($0.as_str())",
+            ),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+pub fn method(receiver: &Type, name: &str) -> Option<Builtin> {
+    methods(receiver).into_iter().find(|m| m.name == name)
+}
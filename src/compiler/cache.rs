@@ -0,0 +1,142 @@
+//! The incremental compilation cache. After building a directory, the
+//! compiler saves its query `Database`, which tells which modules are up to
+//! date, and for each module that compiled the file written for it and the
+//! warnings it had, in `.rustic-cache.json` under the output directory. The
+//! next build into that directory skips a module the database says is up to
+//! date and whose output is still there, replaying its warnings instead of
+//! compiling it again.
+
+use crate::compiler::query::Database;
+use crate::diagnostics::{Diagnostic, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// The name of the cache file in the output directory.
+pub const CACHE_FILE: &str = ".rustic-cache.json";
+
+/// What the cache knows about one module from the last time it compiled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedModule {
+    /// The file written for it, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Whether it defines `main`, for finding the entry of a native build.
+    #[serde(default)]
+    pub main: bool,
+    /// The warnings and notes reported compiling it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheFile {
+    /// Hash of the build settings the modules were compiled with.
+    settings: u64,
+    database: Database,
+    modules: BTreeMap<String, CachedModule>,
+}
+
+/// The cache of one output directory.
+#[derive(Debug)]
+pub struct BuildCache {
+    path: PathBuf,
+    file: CacheFile,
+}
+
+impl BuildCache {
+    /// Loads the cache of `output_dir` for a build with `settings`: whatever
+    /// besides the sources decides what is written and reported. The cache
+    /// starts out empty when there is none yet, it cannot be read, or it was
+    /// written by a build with other settings.
+    pub fn load(output_dir: &Path, settings: impl Hash) -> Self {
+        let mut hasher = StableHasher::default();
+        settings.hash(&mut hasher);
+        let settings = hasher.finish();
+
+        let path = output_dir.join(CACHE_FILE);
+        let file = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<CacheFile>(&text).ok())
+            .filter(|file| file.settings == settings)
+            .unwrap_or(CacheFile {
+                settings,
+                ..CacheFile::default()
+            });
+        Self { path, file }
+    }
+
+    /// Removes the cache of `output_dir`, if there is one.
+    pub fn remove(output_dir: &Path) {
+        let _ = fs::remove_file(output_dir.join(CACHE_FILE));
+    }
+
+    /// Takes the database the last build saved, leaving an empty one.
+    pub fn take_database(&mut self) -> Database {
+        std::mem::take(&mut self.file.database)
+    }
+
+    pub fn get(&self, module_name: &str) -> Option<&CachedModule> {
+        self.file.modules.get(module_name)
+    }
+
+    pub fn insert(&mut self, module_name: String, module: CachedModule) {
+        self.file.modules.insert(module_name, module);
+    }
+
+    /// Forgets every module for which `keep` returns false.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        self.file.modules.retain(|name, _| keep(name));
+    }
+
+    /// Every module in the cache, by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &CachedModule)> {
+        self.file.modules.iter()
+    }
+
+    /// Writes the cache, with `database`, to the output directory for the
+    /// next build.
+    pub fn save(&mut self, database: &Database) -> Result<()> {
+        self.file.database = database.clone();
+        let json = serde_json::to_string(&self.file)
+            .map_err(|e| Error::IoError(format!("Failed to serialize the build cache: {}", e)))?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| Error::IoError(format!("Failed to create output directory: {}", e)))?;
+        }
+        fs::write(&self.path, json)
+            .map_err(|e| Error::IoError(format!("Failed to write {}: {}", self.path.display(), e)))
+    }
+}
+
+/// Hash of a module's source, for telling whether it changed since it was
+/// cached. The compiler's version is part of it, so a new compiler compiles
+/// every module again.
+pub fn source_hash(source: &str) -> u64 {
+    let mut hasher = StableHasher::default();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A `Hasher` for keys that outlive the process, such as those in cache
+/// files: the first eight bytes of the SHA-256 of what it is fed. Unlike
+/// `DefaultHasher`, whose algorithm may change between Rust releases, it
+/// gives the same hash for the same input on every build of the compiler.
+#[derive(Debug, Default)]
+pub struct StableHasher {
+    bytes: Vec<u8>,
+}
+
+impl Hasher for StableHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        let digest = hmac_sha256::Hash::hash(&self.bytes);
+        u64::from_le_bytes(digest[..8].try_into().expect("a digest has 32 bytes"))
+    }
+}
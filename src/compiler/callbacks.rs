@@ -0,0 +1,76 @@
+//! Hooks into each phase of compilation. Register a `CompilerCallbacks`
+//! implementation with `RusticCompiler::add_callbacks` to add lints, collect
+//! metrics or rewrite a module between phases without patching the compiler.
+//!
+//! Every hook has an empty default, so an implementation only overrides the
+//! phases it cares about. Hooks run in registration order and receive the name
+//! of the module being compiled.
+
+use crate::compiler::ast::Program;
+use crate::compiler::lexer::Token;
+use crate::compiler::semantic::TypeTable;
+use crate::diagnostics::{Diagnostic, DiagnosticEngine};
+
+pub trait CompilerCallbacks {
+    /// After lexing, before parsing; the tokens end with `Eof`.
+    fn on_tokens(&mut self, _module: &str, _tokens: &mut Vec<Token>) {}
+
+    /// After parsing (or reading AST JSON), before semantic analysis. Errors
+    /// reported to `diagnostics` stop the module from compiling.
+    fn on_ast(
+        &mut self,
+        _module: &str,
+        _program: &mut Program,
+        _diagnostics: &mut DiagnosticEngine,
+    ) {
+    }
+
+    /// After semantic analysis, with the type of every expression keyed by
+    /// span. Errors reported to `diagnostics` stop code generation.
+    fn on_typed_ast(
+        &mut self,
+        _module: &str,
+        _program: &Program,
+        _types: &TypeTable,
+        _diagnostics: &mut DiagnosticEngine,
+    ) {
+    }
+
+    /// After code generation, before the Rust code is written out.
+    fn on_generated_code(&mut self, _module: &str, _code: &mut String) {}
+
+    /// Each diagnostic, in the order it was reported, including those
+    /// reported by other callbacks.
+    fn on_diagnostic(&mut self, _diagnostic: &Diagnostic) {}
+}
+
+/// No callbacks at all.
+impl CompilerCallbacks for () {}
+
+impl<T: CompilerCallbacks + ?Sized> CompilerCallbacks for &mut T {
+    fn on_tokens(&mut self, module: &str, tokens: &mut Vec<Token>) {
+        (**self).on_tokens(module, tokens)
+    }
+
+    fn on_ast(&mut self, module: &str, program: &mut Program, diagnostics: &mut DiagnosticEngine) {
+        (**self).on_ast(module, program, diagnostics)
+    }
+
+    fn on_typed_ast(
+        &mut self,
+        module: &str,
+        program: &Program,
+        types: &TypeTable,
+        diagnostics: &mut DiagnosticEngine,
+    ) {
+        (**self).on_typed_ast(module, program, types, diagnostics)
+    }
+
+    fn on_generated_code(&mut self, module: &str, code: &mut String) {
+        (**self).on_generated_code(module, code)
+    }
+
+    fn on_diagnostic(&mut self, diagnostic: &Diagnostic) {
+        (**self).on_diagnostic(diagnostic)
+    }
+}
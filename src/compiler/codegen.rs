@@ -0,0 +1,2011 @@
+use crate::compiler::ast::*;
+use crate::compiler::builtins::{self, Builtin, ParamKind};
+use crate::compiler::intern::Symbol;
+use crate::compiler::runtime::Helper;
+use crate::compiler::semantic::{
+    ambiguous_imports, imported_name, literal_type, narrowed_variable, qualifiers, qualify,
+    TypeTable,
+};
+use crate::compiler::sourcemap;
+use crate::diagnostics::{Error, Result, Span};
+use crate::ice;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::Arc;
+
+pub struct CodeGenerator<'a> {
+    types: &'a TypeTable,
+    output: String,
+    indent: usize,
+    /// Fields of each struct in declaration order, with their types.
+    structs: HashMap<Symbol, Vec<(Symbol, Type)>>,
+    /// Payload types of each enum variant.
+    enums: HashMap<Symbol, HashMap<Symbol, Vec<Type>>>,
+    /// Parameters of functions, and of methods by struct and name.
+    functions: HashMap<Symbol, Vec<Parameter>>,
+    methods: HashMap<(Symbol, Symbol), Vec<Parameter>>,
+    /// The path of the functions giving the default values of each function
+    /// in scope, which end in the number of the parameter.
+    defaults: HashMap<Symbol, String>,
+    globals: HashSet<Symbol>,
+    /// Names the module qualifies imported items with, with the module each
+    /// one names.
+    qualifiers: HashMap<Symbol, String>,
+    /// Names more than one module imported whole declares, which stay
+    /// qualified.
+    ambiguous: HashMap<Symbol, Vec<Import>>,
+    /// Other modules of the program by name, as added with `add_module`.
+    modules: HashMap<String, Arc<Program>>,
+    scopes: Vec<HashSet<Symbol>>,
+    return_type: Type,
+    /// Support functions the module calls, emitted after its items.
+    runtime: BTreeSet<Helper>,
+    /// The module's exceptions in declaration order, then those it imports.
+    exceptions: Vec<Symbol>,
+    /// `try` closures around the code being generated, which a `return` has
+    /// to leave first.
+    try_closures: usize,
+    /// The last source comment written, which the next one does not repeat.
+    source_comment: Option<String>,
+}
+
+impl<'a> CodeGenerator<'a> {
+    pub fn new(types: &'a TypeTable) -> Self {
+        Self {
+            types,
+            output: String::new(),
+            indent: 0,
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            functions: HashMap::new(),
+            methods: HashMap::new(),
+            defaults: HashMap::new(),
+            globals: HashSet::new(),
+            qualifiers: HashMap::new(),
+            ambiguous: HashMap::new(),
+            modules: HashMap::new(),
+            scopes: Vec::new(),
+            return_type: Type::Void,
+            runtime: BTreeSet::new(),
+            exceptions: Vec::new(),
+            try_closures: 0,
+            source_comment: None,
+        }
+    }
+
+    /// Makes the items of another module of the program known, so the
+    /// module being generated can import it.
+    pub fn add_module(&mut self, name: &str, program: Arc<Program>) {
+        self.modules.insert(name.to_string(), program);
+    }
+
+    /// The Rust for `program`, a module as `SemanticAnalyzer::take_folded`
+    /// gives it: checked, with its constants folded.
+    pub fn generate(&mut self, program: &Program, module_name: &str) -> Result<String> {
+        self.output.clear();
+        self.runtime.clear();
+        self.exceptions.clear();
+        self.source_comment = None;
+        self.collect_declarations(program);
+
+        self.line(&format!(
+            "// Generated by rustic from `{}.rsc`. Do not edit.",
+            module_name.replace('.', "/")
+        ));
+        self.line("#![allow(unused, arithmetic_overflow, unconditional_panic)]");
+        let imported: Vec<&Import> = program
+            .imports
+            .iter()
+            .filter(|import| self.modules.contains_key(&import.module_path))
+            .collect();
+        if !imported.is_empty() {
+            self.output.push('\n');
+        }
+        for import in imported {
+            self.generate_use(import, program);
+        }
+
+        for item in &program.items {
+            ice::set_span(item.span());
+            self.output.push('\n');
+            self.source(item.span());
+            match item {
+                Item::Struct(structure) => self.generate_struct(structure),
+                Item::Enum(enumeration) => self.generate_enum(enumeration),
+                Item::Function(function) => self.generate_function(function, None)?,
+                Item::Impl(implementation) => self.generate_impl(implementation)?,
+                Item::Variable(variable) => {
+                    let ty = self.variable_type(variable);
+                    let Some(value) = &variable.initializer else {
+                        return Err(Error::CodegenError(format!(
+                            "Missing value for global `{}`",
+                            variable.name
+                        )));
+                    };
+                    self.generate_global(&variable.name, &ty, value, variable.public)?
+                }
+                Item::Constant(constant) => {
+                    let (name, value) = (&constant.name, &constant.value);
+                    self.generate_global(name, &constant.const_type, value, constant.public)?
+                }
+            }
+        }
+
+        for helper in std::mem::take(&mut self.runtime) {
+            self.output.push('\n');
+            if helper == Helper::Error {
+                self.generate_error_enum();
+                self.output.push('\n');
+            }
+            self.line(helper.source());
+        }
+
+        Ok(std::mem::take(&mut self.output))
+    }
+
+    /// `use` declarations for an imported module of the program. Its structs
+    /// and enums are used by name however it is imported, as Rustic code
+    /// names them unqualified.
+    fn generate_use(&mut self, import: &Import, program: &Program) {
+        let path = format!("crate::{}", rust_module_path(&import.module_path));
+        if import.alias.is_none() && import.items.is_none() {
+            // Items whose names another import declares too are referred to
+            // through the module.
+            let qualified = self
+                .ambiguous
+                .values()
+                .flatten()
+                .any(|other| other.module_path == import.module_path);
+            match qualified {
+                true => self.line(&format!("use {}::{{self, *}};", path)),
+                false => self.line(&format!("use {}::*;", path)),
+            }
+            return;
+        }
+        if let Some(alias) = import.alias {
+            self.line(&format!("use {} as {};", path, rust_name(&alias)));
+        }
+        let declared = |name: Symbol| program.items.iter().any(|item| item.name() == Some(name));
+        let mut names = Vec::new();
+        for item in &self.modules[&import.module_path].items {
+            let Some(name) = item.name().filter(|_| item.is_public()) else {
+                continue;
+            };
+            let local = match imported_name(import, item, &self.ambiguous) {
+                Some(local) if declared(local) || (local != name && import.alias.is_some()) => {
+                    continue
+                }
+                Some(local) if local != name => {
+                    names.push(format!("{} as {}", rust_name(&name), rust_name(&local)));
+                    local
+                }
+                Some(_) => {
+                    names.push(rust_name(&name).into_owned());
+                    name
+                }
+                None => continue,
+            };
+            // A function's defaults come along under its local name.
+            let Item::Function(function) = item else {
+                continue;
+            };
+            for (index, parameter) in function.parameters.iter().enumerate() {
+                if parameter.default_value.is_none() {
+                    continue;
+                }
+                let default = format!("{}{}", default_function(name), index);
+                names.push(match local == name {
+                    true => default,
+                    false => format!("{} as {}{}", default, default_function(local), index),
+                });
+            }
+        }
+        match names.as_slice() {
+            [] => {}
+            [name] => self.line(&format!("use {}::{};", path, name)),
+            names => self.line(&format!("use {}::{{{}}};", path, names.join(", "))),
+        }
+    }
+
+    fn collect_declarations(&mut self, program: &Program) {
+        self.qualifiers = qualifiers(program, &self.modules);
+        self.ambiguous = ambiguous_imports(program, &self.modules);
+        self.collect_items(&program.items, None);
+        for import in &program.imports {
+            if let Some(module) = self.modules.get(&import.module_path).cloned() {
+                self.collect_items(&module.items, Some(import));
+            }
+        }
+    }
+
+    /// Collects the module's own items, or the `pub` ones a module it imports
+    /// brings into scope, under the names the import gives them. Items
+    /// already collected shadow imported ones.
+    fn collect_items(&mut self, items: &[Item], import: Option<&Import>) {
+        for item in items {
+            let mut name = item.name();
+            if let (Some(import), Some(_)) = (import, name) {
+                name = imported_name(import, item, &self.ambiguous)
+                    .filter(|&name| item.is_public() && !self.declares(name));
+                if name.is_none() {
+                    continue;
+                }
+            }
+            match item {
+                Item::Struct(structure) => {
+                    let fields = structure
+                        .fields
+                        .iter()
+                        .map(|field| (field.name, field.field_type.clone()))
+                        .collect();
+                    self.structs.insert(structure.name, fields);
+                    if structure.exception {
+                        self.exceptions.push(structure.name);
+                    }
+                }
+                Item::Enum(enumeration) => {
+                    let variants = enumeration
+                        .variants
+                        .iter()
+                        .map(|variant| (variant.name, variant.payload.clone()))
+                        .collect();
+                    self.enums.insert(enumeration.name, variants);
+                }
+                Item::Function(function) => {
+                    let name = name.unwrap_or(function.name);
+                    self.functions.insert(name, function.parameters.clone());
+                    // `generate_use` brings them in under the function's
+                    // local name, except when the function stays qualified.
+                    let path = match import {
+                        Some(import) if import.items.is_none() && name != function.name => {
+                            format!(
+                                "{}::{}",
+                                rust_name(&import.name()),
+                                default_function(function.name)
+                            )
+                        }
+                        _ => default_function(name),
+                    };
+                    self.defaults.insert(name, path);
+                }
+                Item::Impl(implementation) => {
+                    for method in &implementation.methods {
+                        if import.is_some() && !method.function.public {
+                            continue;
+                        }
+                        let key = (implementation.struct_name, method.function.name);
+                        let parameters = method.function.parameters.clone();
+                        self.methods.entry(key).or_insert(parameters);
+                    }
+                }
+                Item::Variable(variable) => {
+                    self.globals.insert(name.unwrap_or(variable.name));
+                }
+                Item::Constant(constant) => {
+                    self.globals.insert(name.unwrap_or(constant.name));
+                }
+            }
+        }
+    }
+
+    fn declares(&self, name: Symbol) -> bool {
+        self.structs.contains_key(&name)
+            || self.enums.contains_key(&name)
+            || self.functions.contains_key(&name)
+            || self.globals.contains(&name)
+    }
+
+    fn generate_struct(&mut self, structure: &Struct) {
+        self.generate_docs(&structure.docs);
+        self.line("#[derive(Debug, Clone, PartialEq)]");
+        self.line(&format!(
+            "{}struct {}{} {{",
+            visibility(structure.public),
+            rust_name(&structure.name),
+            generics(&structure.type_params, false)
+        ));
+        self.indent += 1;
+        for field in &structure.fields {
+            self.line(&format!(
+                "{}{}: {},",
+                visibility(structure.public),
+                rust_name(&field.name),
+                rust_type(&field.field_type)
+            ));
+        }
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// `RusticError`, what a `try` block failed with: one variant per
+    /// exception the module declares or imports, and `Runtime` for other
+    /// panics. Exceptions unwind as themselves, so a module can catch those
+    /// thrown by the modules it imports.
+    fn generate_error_enum(&mut self) {
+        self.line("#[derive(Debug)]");
+        self.line("enum RusticError {");
+        self.indent += 1;
+        for exception in self.exceptions.clone() {
+            self.line(&format!("{0}({0}),", rust_name(&exception)));
+        }
+        self.line("Runtime(String),");
+        self.indent -= 1;
+        self.line("}");
+        self.output.push('\n');
+        self.line("impl RusticError {");
+        self.indent += 1;
+        self.line("fn exception(payload: RusticPayload) -> Result<Self, RusticPayload> {");
+        self.indent += 1;
+        for exception in self.exceptions.clone() {
+            let exception = rust_name(&exception);
+            self.line(&format!(
+                "let payload = match payload.downcast::<{}>() {{",
+                exception
+            ));
+            self.indent += 1;
+            self.line(&format!(
+                "Ok(error) => return Ok(RusticError::{}(*error)),",
+                exception
+            ));
+            self.line("Err(payload) => payload,");
+            self.indent -= 1;
+            self.line("};");
+        }
+        self.line("Err(payload)");
+        self.indent -= 1;
+        self.line("}");
+        self.output.push('\n');
+        self.line("fn is_error(payload: &(dyn std::any::Any + Send)) -> bool {");
+        self.indent += 1;
+        let mut types = vec!["&str".to_string(), "String".to_string()];
+        types.extend(
+            self.exceptions
+                .iter()
+                .map(|exception| rust_name(exception).into_owned()),
+        );
+        let checks: Vec<String> = types
+            .iter()
+            .map(|ty| format!("payload.is::<{}>()", ty))
+            .collect();
+        self.line(&checks.join(" || "));
+        self.indent -= 1;
+        self.line("}");
+        self.output.push('\n');
+        self.line("fn into_panic(self) -> RusticPayload {");
+        self.indent += 1;
+        self.line("match self {");
+        self.indent += 1;
+        for exception in self.exceptions.clone() {
+            let exception = rust_name(&exception);
+            self.line(&format!(
+                "RusticError::{}(error) => Box::new(error),",
+                exception
+            ));
+        }
+        self.line("RusticError::Runtime(message) => Box::new(message),");
+        self.indent -= 1;
+        self.line("}");
+        self.indent -= 1;
+        self.line("}");
+        self.output.push('\n');
+        self.line("fn message(&self) -> String {");
+        self.indent += 1;
+        self.line("match self {");
+        self.indent += 1;
+        for exception in self.exceptions.clone() {
+            self.line(&format!(
+                "RusticError::{}(error) => format!(\"{{:?}}\", error),",
+                rust_name(&exception)
+            ));
+        }
+        self.line("RusticError::Runtime(message) => message.clone(),");
+        self.indent -= 1;
+        self.line("}");
+        self.indent -= 1;
+        self.line("}");
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// A doc comment carried over for `cargo doc`.
+    fn generate_docs(&mut self, docs: &[String]) {
+        for line in docs {
+            self.line(&format!("///{}", line));
+        }
+    }
+
+    fn generate_enum(&mut self, enumeration: &Enum) {
+        self.line("#[derive(Debug, Clone, PartialEq)]");
+        let public = visibility(enumeration.public);
+        self.line(&format!(
+            "{}enum {} {{",
+            public,
+            rust_name(&enumeration.name)
+        ));
+        self.indent += 1;
+        for variant in &enumeration.variants {
+            let name = rust_name(&variant.name);
+            if variant.payload.is_empty() {
+                self.line(&format!("{},", name));
+            } else {
+                let payload: Vec<String> = variant.payload.iter().map(rust_type).collect();
+                self.line(&format!("{}({}),", name, payload.join(", ")));
+            }
+        }
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    /// Globals are initialized lazily so any expression can be used as an initializer.
+    fn generate_global(
+        &mut self,
+        name: &str,
+        ty: &Type,
+        value: &Expression,
+        public: bool,
+    ) -> Result<()> {
+        let code = self.generate_expression(value)?;
+        let value = self.coerce(ty, value, code);
+        self.line(&format!(
+            "{}static {}: std::sync::LazyLock<{}> = std::sync::LazyLock::new(|| {});",
+            visibility(public),
+            rust_name(name),
+            rust_type(ty),
+            value
+        ));
+        Ok(())
+    }
+
+    fn generate_impl(&mut self, implementation: &Impl) -> Result<()> {
+        self.line(&format!(
+            "impl{} {}{} {{",
+            generics(&implementation.type_params, true),
+            rust_name(&implementation.struct_name),
+            generics(&implementation.type_params, false)
+        ));
+        self.indent += 1;
+        for (index, method) in implementation.methods.iter().enumerate() {
+            if index > 0 {
+                self.output.push('\n');
+            }
+            self.source(&method.function.span);
+            let receiver = if method.mutable { "&mut self" } else { "&self" };
+            self.generate_function(&method.function, Some(receiver))?;
+        }
+        self.indent -= 1;
+        self.line("}");
+        Ok(())
+    }
+
+    /// `receiver` is the `self` parameter of a method.
+    fn generate_function(&mut self, function: &Function, receiver: Option<&str>) -> Result<()> {
+        let parameters = receiver
+            .map(str::to_string)
+            .into_iter()
+            .chain(function.parameters.iter().map(|parameter| {
+                format!(
+                    "{}: {}",
+                    rust_name(&parameter.name),
+                    rust_type(&parameter.param_type)
+                )
+            }))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // `main` is the entry point, which a native build calls from its
+        // binary.
+        let entry = receiver.is_none() && function.name == "main";
+        let mut signature = format!(
+            "{}fn {}{}({})",
+            visibility(function.public || entry),
+            rust_name(&function.name),
+            generics(&function.type_params, true),
+            parameters
+        );
+        if function.return_type != Type::Void {
+            write!(signature, " -> {}", rust_type(&function.return_type)).unwrap();
+        }
+
+        self.generate_docs(&function.docs);
+        self.line(&format!("{} {{", signature));
+        self.return_type = function.return_type.clone();
+        let mut parameters: HashSet<Symbol> = function.parameters.iter().map(|p| p.name).collect();
+        if receiver.is_some() {
+            parameters.insert(Symbol::intern("self"));
+        }
+        self.scopes.push(parameters);
+        if entry && function.return_type == Type::Void {
+            self.runtime.extend([Helper::Error, Helper::Main]);
+            self.indent += 1;
+            self.line("rustic_main(|| {");
+            self.generate_statements(&function.body.statements)?;
+            self.line("});");
+            self.indent -= 1;
+        } else {
+            self.generate_statements(&function.body.statements)?;
+        }
+        self.scopes.pop();
+        self.line("}");
+        self.generate_defaults(function)
+    }
+
+    /// A function for each default value of `function`'s parameters, which
+    /// calls leaving the parameter out call. It is evaluated where `function`
+    /// is declared, so it sees the module's items whichever module calls.
+    fn generate_defaults(&mut self, function: &Function) -> Result<()> {
+        for (index, parameter) in function.parameters.iter().enumerate() {
+            let Some(default) = &parameter.default_value else {
+                continue;
+            };
+            self.output.push('\n');
+            self.line(&format!(
+                "{}fn {}{}{}() -> {} {{",
+                visibility(function.public),
+                default_function(function.name),
+                index,
+                generics(&function.type_params, true),
+                rust_type(&parameter.param_type)
+            ));
+            self.indent += 1;
+            let value = self.generate_expression(default)?;
+            let value = self.coerce(&parameter.param_type, default, value);
+            self.line(&value);
+            self.indent -= 1;
+            self.line("}");
+        }
+        Ok(())
+    }
+
+    fn generate_block(&mut self, block: &Block) -> Result<()> {
+        self.scopes.push(HashSet::new());
+        self.generate_statements(&block.statements)?;
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn generate_statements(&mut self, statements: &[Statement]) -> Result<()> {
+        self.indent += 1;
+        for statement in statements {
+            if let Some(span) = statement.span() {
+                self.source(span);
+            }
+            self.generate_statement(statement)?;
+        }
+        self.indent -= 1;
+        Ok(())
+    }
+
+    fn generate_statement(&mut self, statement: &Statement) -> Result<()> {
+        match statement {
+            Statement::Expression(expression) => {
+                let code = self.generate_expression(expression)?;
+                self.line(&format!("{};", code));
+            }
+            Statement::Variable(variable) => {
+                let ty = self.variable_type(variable);
+                let keyword = if variable.mutable { "let mut" } else { "let" };
+                let name = rust_name(&variable.name);
+                let declaration = format!("{} {}: {}", keyword, name, rust_type(&ty));
+                match &variable.initializer {
+                    Some(initializer) => {
+                        let value = self.generate_expression(initializer)?;
+                        let value = self.coerce(&ty, initializer, value);
+                        self.line(&format!("{} = {};", declaration, value));
+                    }
+                    // Semantic analysis checked that it is assigned before
+                    // every use, which rustc checks again.
+                    None => self.line(&format!("{};", declaration)),
+                }
+                self.declare(variable.name);
+            }
+            Statement::Destructure(destructure) => {
+                let value = self.generate_expression(&destructure.initializer)?;
+                let keyword = if destructure.mutable { "mut " } else { "" };
+                let names: Vec<String> = destructure
+                    .names
+                    .iter()
+                    .map(|identifier| match identifier.name.as_str() {
+                        "_" => "_".to_string(),
+                        name => format!("{}{}", keyword, rust_name(name)),
+                    })
+                    .collect();
+                self.line(&format!("let ({}) = {};", names.join(", "), value));
+                for identifier in &destructure.names {
+                    self.declare(identifier.name);
+                }
+            }
+            Statement::Assignment(assignment) => {
+                if let Some(index) = self.map_index(&assignment.target) {
+                    let map = self.generate_receiver(&index.object)?;
+                    let key = self.generate_expression(&index.index)?;
+                    let value = self.generate_assigned(assignment)?;
+                    self.line(&format!("{}.insert({}, {});", map, key, value));
+                    return Ok(());
+                }
+                let mut target = self.generate_place(&assignment.target)?;
+                if target == "self" {
+                    // `var self` is a `&mut self` receiver.
+                    target = "*self".to_string();
+                }
+                let value = self.generate_assigned(assignment)?;
+                match &assignment.operator {
+                    Some(operator) if !self.expands_compound(assignment) => {
+                        self.line(&format!("{} {}= {};", target, operator, value))
+                    }
+                    _ => self.line(&format!("{} = {};", target, value)),
+                }
+            }
+            Statement::If(if_statement) => {
+                let condition = self.generate_condition(&if_statement.condition)?;
+                self.line(&format!("if {} {{", condition));
+                self.generate_block(&if_statement.then_block)?;
+                for (condition, block) in &if_statement.else_ifs {
+                    let condition = self.generate_condition(condition)?;
+                    self.line(&format!("}} else if {} {{", condition));
+                    self.generate_block(block)?;
+                }
+                if let Some(else_block) = &if_statement.else_block {
+                    self.line("} else {");
+                    self.generate_block(else_block)?;
+                }
+                self.line("}");
+            }
+            Statement::For(for_loop) => {
+                let iterable = self.generate_expression(&for_loop.iterable)?;
+                let variable = rust_name(&for_loop.variable);
+                self.line(&format!("for {} in {} {{", variable, iterable));
+                self.scopes.push(HashSet::from([for_loop.variable]));
+                self.generate_statements(&for_loop.body.statements)?;
+                self.scopes.pop();
+                self.line("}");
+            }
+            Statement::While(while_loop) => {
+                let condition = self.generate_expression(&while_loop.condition)?;
+                self.line(&format!("while {} {{", condition));
+                self.generate_block(&while_loop.body)?;
+                self.line("}");
+            }
+            Statement::Try(try_statement) => self.generate_try(try_statement)?,
+            Statement::Throw(throw_statement) => {
+                let Some(Type::Struct(exception)) = self.type_of(&throw_statement.value) else {
+                    return Err(Error::CodegenError(
+                        "Missing type for thrown value".to_string(),
+                    ));
+                };
+                let value = self.generate_expression(&throw_statement.value)?;
+                let exception = rust_name(&exception);
+                self.line(&format!(
+                    "std::panic::panic_any::<{}>({});",
+                    exception, value
+                ));
+            }
+            Statement::Return(return_statement) => match &return_statement.value {
+                Some(value) => {
+                    let code = self.generate_expression(value)?;
+                    let return_type = self.return_type.clone();
+                    let value = self.coerce(&return_type, value, code);
+                    let exit = self.return_exit(&value);
+                    self.line(&format!("{};", exit));
+                }
+                None if self.try_closures > 0 => self.line("return Some(());"),
+                None => self.line("return;"),
+            },
+            Statement::Break(_) => self.line("break;"),
+            Statement::Continue(_) => self.line("continue;"),
+        }
+        Ok(())
+    }
+
+    /// Errors surface as panics: `throw` panics with the exception, and a
+    /// `try` block is a closure whose panic is matched against the clauses.
+    ///
+    /// A `return` leaves the closure with `Some(value)`, returned once the
+    /// `try` has finished. With a `finally`, the handlers run in a closure
+    /// too, and whatever they end with is resumed after the `finally` block.
+    fn generate_try(&mut self, try_statement: &TryStatement) -> Result<()> {
+        let clauses = &try_statement.catch_clauses;
+        let finally_block = try_statement.finally_block.as_ref();
+        let returns = contains_return(&try_statement.try_block)
+            || finally_block.is_some()
+                && clauses
+                    .iter()
+                    .any(|clause| contains_return(&clause.handler_block));
+
+        if finally_block.is_none() && !returns && catches_any(clauses) {
+            self.line("if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {");
+            self.generate_block(&try_statement.try_block)?;
+            self.line("}))");
+            self.line(".is_err()");
+            self.line("{");
+            if let Some(clause) = clauses.first() {
+                self.generate_block(&clause.handler_block)?;
+            }
+            self.line("}");
+            return Ok(());
+        }
+
+        self.line("let caught = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {");
+        self.try_closures += 1;
+        self.generate_block(&try_statement.try_block)?;
+        self.try_closures -= 1;
+        if returns {
+            self.indent += 1;
+            self.line("None");
+            self.indent -= 1;
+        }
+        self.line("}));");
+
+        let Some(finally_block) = finally_block else {
+            if !returns {
+                return self.generate_handlers(clauses, false, self.indent);
+            }
+            self.line("let returned = {");
+            self.generate_handlers(clauses, true, self.indent + 1)?;
+            self.line("};");
+            self.line("if let Some(value) = returned {");
+            self.indent += 1;
+            let exit = self.return_exit("value");
+            self.line(&format!("{};", exit));
+            self.indent -= 1;
+            self.line("}");
+            return Ok(());
+        };
+
+        self.line("let handled = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {");
+        self.try_closures += 1;
+        self.generate_handlers(clauses, returns, self.indent + 1)?;
+        self.try_closures -= 1;
+        self.line("}));");
+        self.indent -= 1;
+        self.generate_block(finally_block)?;
+        self.indent += 1;
+        if returns {
+            self.line("match handled {");
+            self.indent += 1;
+            let exit = self.return_exit("value");
+            self.line(&format!("Ok(Some(value)) => {},", exit));
+            self.line("Ok(None) => {}");
+            self.line("Err(payload) => std::panic::resume_unwind(payload),");
+            self.indent -= 1;
+            self.line("}");
+        } else {
+            self.line("if let Err(payload) = handled {");
+            self.indent += 1;
+            self.line("std::panic::resume_unwind(payload);");
+            self.indent -= 1;
+            self.line("}");
+        }
+        Ok(())
+    }
+
+    /// Handles `caught`, the outcome of a `try` block, at indentation
+    /// `indent`. When the block can return, this is an expression for the
+    /// value returned.
+    fn generate_handlers(
+        &mut self,
+        clauses: &[CatchClause],
+        returns: bool,
+        indent: usize,
+    ) -> Result<()> {
+        let outer = std::mem::replace(&mut self.indent, indent);
+        if returns {
+            self.line("match caught {");
+            self.indent += 1;
+            self.line("Ok(returned) => returned,");
+            self.line("Err(payload) => {");
+        } else {
+            self.line("if let Err(payload) = caught {");
+        }
+
+        if clauses.is_empty() {
+            self.indent += 1;
+            self.line("std::panic::resume_unwind(payload);");
+            self.indent -= 1;
+        } else if catches_any(clauses) {
+            self.generate_block(&clauses[0].handler_block)?;
+        } else {
+            self.generate_catch_clauses(clauses)?;
+        }
+
+        if returns {
+            self.indent += 1;
+            self.line("None");
+            self.indent -= 1;
+            self.line("}");
+            self.indent -= 1;
+        }
+        self.line("}");
+        self.indent = outer;
+        Ok(())
+    }
+
+    /// Matches the error in `payload` against each clause in turn, one level
+    /// in. Errors no clause catches keep unwinding.
+    fn generate_catch_clauses(&mut self, clauses: &[CatchClause]) -> Result<()> {
+        self.runtime.insert(Helper::Error);
+        self.indent += 1;
+        self.line("match RusticError::from_panic(payload) {");
+        self.indent += 1;
+        let mut catch_all = false;
+        for clause in clauses {
+            let binding = clause
+                .binding
+                .map_or("_".into(), |binding| rust_name(binding.as_str()));
+            if clause.exception_type == "Error" {
+                catch_all = true;
+                self.line(&format!("{} => {{", binding));
+                if clause.binding.is_some() {
+                    self.indent += 1;
+                    self.line(&format!("let {0} = {0}.message();", binding));
+                    self.indent -= 1;
+                }
+            } else {
+                self.line(&format!(
+                    "RusticError::{}({}) => {{",
+                    rust_name(&clause.exception_type),
+                    binding
+                ));
+            }
+            self.scopes.push(clause.binding.into_iter().collect());
+            self.generate_statements(&clause.handler_block.statements)?;
+            self.scopes.pop();
+            self.line("}");
+            if catch_all {
+                break;
+            }
+        }
+        if !catch_all {
+            self.line("error => std::panic::resume_unwind(error.into_panic()),");
+        }
+        self.indent -= 1;
+        self.line("}");
+        self.indent -= 1;
+        Ok(())
+    }
+
+    /// `return value`, or `return Some(value)` from inside a `try` closure.
+    fn return_exit(&self, value: &str) -> String {
+        if self.try_closures > 0 {
+            format!("return Some({})", value)
+        } else {
+            format!("return {}", value)
+        }
+    }
+
+    /// The value `assignment` stores; for a compound assignment Rust has no
+    /// operator for, the result of the operation.
+    fn generate_assigned(&mut self, assignment: &Assignment) -> Result<String> {
+        let value = match &assignment.operator {
+            Some(operator) if self.expands_compound(assignment) => {
+                self.generate_expression(&Expression::Binary(BinaryOp {
+                    left: Box::new(assignment.target.clone()),
+                    operator: operator.clone(),
+                    right: Box::new(assignment.value.clone()),
+                    span: assignment.span.clone(),
+                }))?
+            }
+            _ => self.generate_expression(&assignment.value)?,
+        };
+        Ok(match self.type_of(&assignment.target) {
+            Some(target) => self.coerce(&target, &assignment.value, value),
+            None => value,
+        })
+    }
+
+    /// Whether a compound assignment is spelled out as `x = x + y`: strings
+    /// are concatenated with `format!`, map entries are stored with
+    /// `insert`, and `int` arithmetic calls a function.
+    fn expands_compound(&self, assignment: &Assignment) -> bool {
+        let Some(operator) = &assignment.operator else {
+            return false;
+        };
+        self.map_index(&assignment.target).is_some()
+            || match self.type_of(&assignment.target) {
+                Some(Type::Str) => true,
+                Some(Type::Int) => int_function(operator).is_some(),
+                _ => false,
+            }
+    }
+
+    /// The function `operator` calls on `int`s, which wraps on overflow or
+    /// fails as the interpreter does, noting the helper it needs.
+    fn int_operator(&mut self, operator: &BinaryOperator) -> Option<&'static str> {
+        let function = int_function(operator)?;
+        match operator {
+            BinaryOperator::Div | BinaryOperator::Mod => {
+                self.runtime.insert(Helper::Divide);
+            }
+            BinaryOperator::Shl | BinaryOperator::Shr => {
+                self.runtime.insert(Helper::Shift);
+            }
+            _ => {}
+        }
+        Some(function)
+    }
+
+    /// `expression` if it indexes into a map, whose entries are stored with
+    /// `insert` rather than assigned in place.
+    fn map_index<'e>(&self, expression: &'e Expression) -> Option<&'e IndexExpression> {
+        match expression {
+            Expression::Index(index) => {
+                matches!(self.type_of(&index.object), Some(Type::Map(..))).then_some(index)
+            }
+            _ => None,
+        }
+    }
+
+    /// The condition of an `if` or `else if`. One of the form `x != none`
+    /// unwraps `x` for the block, which sees the value under the same name.
+    fn generate_condition(&mut self, condition: &Expression) -> Result<String> {
+        if let Some(identifier) = narrowed_variable(condition) {
+            let expression = Expression::Identifier(identifier.clone());
+            if let Some(Type::Optional(_)) = self.type_of(&expression) {
+                let value = self.generate_expression(&expression)?;
+                return Ok(format!(
+                    "let Some({}) = {}",
+                    rust_name(&identifier.name),
+                    value
+                ));
+            }
+        }
+        self.generate_expression(condition)
+    }
+
+    fn generate_expression(&mut self, expression: &Expression) -> Result<String> {
+        match expression {
+            Expression::Literal(literal) => Ok(generate_literal(literal)),
+            Expression::Identifier(identifier) => {
+                if !self.is_local(identifier.name) && self.globals.contains(&identifier.name) {
+                    return Ok(format!("(*{}).clone()", rust_path(identifier.name)));
+                }
+                if !self.is_local(identifier.name) && self.functions.contains_key(&identifier.name)
+                {
+                    // A function used as a value, such as `apply(double, 5)`.
+                    let ty = self.type_of(expression).unwrap_or(Type::Void);
+                    return Ok(format!(
+                        "std::rc::Rc::new({}) as {}",
+                        rust_path(identifier.name),
+                        rust_type(&ty)
+                    ));
+                }
+                Ok(self.clone_if_needed(rust_name(&identifier.name).into_owned(), expression))
+            }
+            Expression::Binary(binary) if binary.operator == BinaryOperator::Coalesce => {
+                self.generate_coalesce(binary)
+            }
+            Expression::Binary(binary) => {
+                let mut left = self.generate_expression(&binary.left)?;
+                let mut right = self.generate_expression(&binary.right)?;
+                if matches!(binary.operator, BinaryOperator::Eq | BinaryOperator::Ne) {
+                    // Comparing with an optional compares the other side as one.
+                    if let Some(ty @ Type::Optional(_)) = self.type_of(&binary.left) {
+                        right = self.coerce(&ty, &binary.right, right);
+                    }
+                    if let Some(ty @ Type::Optional(_)) = self.type_of(&binary.right) {
+                        left = self.coerce(&ty, &binary.left, left);
+                    }
+                }
+                if binary.operator == BinaryOperator::Pow {
+                    let is_int = self.type_of(expression) == Some(Type::Int);
+                    return Ok(if is_int {
+                        self.runtime.insert(Helper::Pow);
+                        format!("rustic_pow({}, {})", left, right)
+                    } else {
+                        let as_float =
+                            |code: String, operand: &Expression| match self.type_of(operand) {
+                                Some(Type::Int) => format!("({} as f64)", code),
+                                _ => code,
+                            };
+                        let left = as_float(left, &binary.left);
+                        let right = as_float(right, &binary.right);
+                        format!("f64::powf({}, {})", left, right)
+                    });
+                }
+                let is_concat = binary.operator == BinaryOperator::Add
+                    && (self.type_of(&binary.left) == Some(Type::Str)
+                        || self.type_of(&binary.right) == Some(Type::Str));
+                if is_concat {
+                    return Ok(format!("format!(\"{{}}{{}}\", {}, {})", left, right));
+                }
+                let is_int = self.type_of(&binary.left) == Some(Type::Int)
+                    && self.type_of(&binary.right) == Some(Type::Int);
+                if is_int {
+                    if let Some(function) = self.int_operator(&binary.operator) {
+                        return Ok(format!("{}({}, {})", function, left, right));
+                    }
+                }
+                Ok(format!("({} {} {})", left, binary.operator, right))
+            }
+            Expression::Unary(unary) => {
+                let operand = self.generate_expression(&unary.operand)?;
+                if unary.operator == UnaryOperator::Neg
+                    && self.type_of(&unary.operand) == Some(Type::Int)
+                {
+                    return Ok(format!("i64::wrapping_neg({})", operand));
+                }
+                // Rust flips the bits of an integer with `!`.
+                let operator = match unary.operator {
+                    UnaryOperator::BitNot => "!".to_string(),
+                    ref operator => operator.to_string(),
+                };
+                Ok(format!("({}{})", operator, operand))
+            }
+            Expression::Call(call) => self.generate_call(call),
+            Expression::MemberAccess(access) => {
+                if let Some(identifier) = self.qualified(access) {
+                    return self.generate_expression(&Expression::Identifier(identifier));
+                }
+                if let Some(module) = self.module_name(&access.object) {
+                    let (_, rust) =
+                        builtins::constant(&module, &access.member).ok_or_else(|| {
+                            Error::CodegenError(format!(
+                                "Unknown member `{}.{}`",
+                                module, access.member
+                            ))
+                        })?;
+                    return Ok(rust.to_string());
+                }
+                if let Some(path) = self.variant_path(access) {
+                    return Ok(path);
+                }
+                let place = self.generate_place(expression)?;
+                Ok(self.clone_if_needed(place, expression))
+            }
+            Expression::List(list) => {
+                if list.elements.is_empty() {
+                    return Ok("Vec::new()".to_string());
+                }
+                let elements = list
+                    .elements
+                    .iter()
+                    .map(|element| self.generate_expression(element))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(format!("vec![{}]", elements.join(", ")))
+            }
+            Expression::Comprehension(comprehension) => {
+                let iterable = self.generate_expression(&comprehension.iterable)?;
+                self.scopes.push(HashSet::from([comprehension.variable]));
+                let condition = comprehension
+                    .condition
+                    .as_ref()
+                    .map(|condition| self.generate_expression(condition))
+                    .transpose();
+                let element = self.generate_expression(&comprehension.element);
+                self.scopes.pop();
+                let variable = rust_name(&comprehension.variable);
+                let mut code = format!("{}.into_iter()", iterable);
+                if let Some(condition) = condition? {
+                    // `filter` sees each value by reference; a copied value
+                    // is taken out of it, and any other is only ever cloned.
+                    let copied = match &*comprehension.iterable {
+                        Expression::Range(_) => true,
+                        iterable => {
+                            matches!(self.type_of(iterable), Some(Type::List(ty)) if is_copy(&ty))
+                        }
+                    };
+                    let pattern = if copied { "&" } else { "" };
+                    let _ = write!(code, ".filter(|{}{}| {})", pattern, variable, condition);
+                }
+                let _ = write!(
+                    code,
+                    ".map(|{}| {}).collect::<Vec<_>>()",
+                    variable, element?
+                );
+                Ok(code)
+            }
+            Expression::Cast(cast) => {
+                let value = self.generate_expression(&cast.value)?;
+                Ok(match (&cast.target, self.type_of(&cast.value)) {
+                    (Type::Str, Some(Type::Str)) => value,
+                    (Type::Str, _) => format!("{}.to_string()", value),
+                    (target, _) => format!("({} as {})", value, rust_type(target)),
+                })
+            }
+            Expression::If(if_expression) => {
+                let condition = self.generate_condition(&if_expression.condition)?;
+                let mut branches = Vec::new();
+                for value in [&if_expression.then_value, &if_expression.else_value] {
+                    let code = self.generate_expression(value)?;
+                    branches.push(match self.type_of(expression) {
+                        Some(ty) => self.coerce(&ty, value, code),
+                        None => code,
+                    });
+                }
+                let [then_value, else_value] = [&branches[0], &branches[1]];
+                Ok(match *if_expression.else_value {
+                    Expression::If(_) => {
+                        format!("if {} {{ {} }} else {}", condition, then_value, else_value)
+                    }
+                    _ => format!(
+                        "if {} {{ {} }} else {{ {} }}",
+                        condition, then_value, else_value
+                    ),
+                })
+            }
+            Expression::Tuple(tuple) => {
+                let elements = tuple
+                    .elements
+                    .iter()
+                    .map(|element| self.generate_expression(element))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(format!("({})", elements.join(", ")))
+            }
+            Expression::Map(map) => {
+                if map.entries.is_empty() {
+                    return Ok("std::collections::BTreeMap::new()".to_string());
+                }
+                let mut entries = Vec::new();
+                for (key, value) in &map.entries {
+                    let key = self.generate_expression(key)?;
+                    let value = self.generate_expression(value)?;
+                    entries.push(format!("({}, {})", key, value));
+                }
+                Ok(format!(
+                    "std::collections::BTreeMap::from([{}])",
+                    entries.join(", ")
+                ))
+            }
+            Expression::Index(_) => {
+                let place = self.generate_place(expression)?;
+                Ok(self.clone_if_needed(place, expression))
+            }
+            Expression::Slice(slice) => {
+                self.runtime.insert(Helper::Slice);
+                let object = self.generate_receiver(&slice.object)?;
+                let start = match &slice.start {
+                    Some(start) => self.generate_expression(start)?,
+                    None => "0i64".to_string(),
+                };
+                let end = match &slice.end {
+                    Some(end) => self.generate_expression(end)?,
+                    None => "i64::MAX".to_string(),
+                };
+                Ok(format!("rustic_slice(&{}, {}, {})", object, start, end))
+            }
+            Expression::StructInit(init) => {
+                let mut names: Vec<Symbol> = init.fields.keys().copied().collect();
+                match self.structs.get(&init.struct_name) {
+                    Some(order) => {
+                        names.sort_by_key(|name| order.iter().position(|(field, _)| field == name))
+                    }
+                    None => names.sort(),
+                }
+
+                let mut fields = Vec::new();
+                for name in names {
+                    let value = &init.fields[&name];
+                    let code = self.generate_expression(value)?;
+                    let code = match self.field_type(init.struct_name, name) {
+                        Some(ty) => self.coerce(&ty, value, code),
+                        None => code,
+                    };
+                    fields.push(format!("{}: {}", rust_name(&name), code));
+                }
+                let name = rust_name(&init.struct_name);
+                Ok(format!("{} {{ {} }}", name, fields.join(", ")))
+            }
+            Expression::Match(match_expression) => self.generate_match(match_expression),
+            Expression::Lambda(lambda) => self.generate_lambda(lambda),
+            Expression::Range(range) => {
+                let start = self.generate_expression(&range.start)?;
+                let end = self.generate_expression(&range.end)?;
+                let operator = if range.inclusive { "..=" } else { ".." };
+                Ok(format!("({}{}{})", start, operator, end))
+            }
+            Expression::Interpolation(interpolation) => {
+                let mut format = String::new();
+                let mut arguments = Vec::new();
+                for part in &interpolation.parts {
+                    match part {
+                        InterpolationPart::Text(text) => {
+                            format.push_str(&text.replace('{', "{{").replace('}', "}}"))
+                        }
+                        InterpolationPart::Expression(expression) => {
+                            let ty = self.type_of(expression);
+                            let displayed = ty.as_ref().is_some_and(is_display);
+                            format.push_str(if displayed { "{}" } else { "{:?}" });
+                            arguments.push(self.generate_expression(expression)?);
+                        }
+                    }
+                }
+                Ok(format!("format!({:?}, {})", format, arguments.join(", ")))
+            }
+        }
+    }
+
+    /// `a ?? b` as `a.unwrap_or(b)`, evaluating `b` only when it is needed
+    /// unless it is a literal or a variable; `a.or(b)` when `b` is optional too.
+    fn generate_coalesce(&mut self, binary: &BinaryOp) -> Result<String> {
+        let left = self.generate_expression(&binary.left)?;
+        let right = self.generate_expression(&binary.right)?;
+        Ok(match (self.type_of(&binary.right), binary.right.as_ref()) {
+            (Some(Type::Optional(_)), Expression::Literal(_) | Expression::Identifier(_)) => {
+                format!("{}.or({})", left, right)
+            }
+            (Some(Type::Optional(_)), _) => format!("{}.or_else(|| {})", left, right),
+            (_, Expression::Literal(_) | Expression::Identifier(_)) => {
+                format!("{}.unwrap_or({})", left, right)
+            }
+            _ => format!("{}.unwrap_or_else(|| {})", left, right),
+        })
+    }
+
+    /// Strings are matched as `&str` so literal patterns apply; arms that
+    /// bind the value turn it back into a `String`.
+    fn generate_match(&mut self, match_expression: &MatchExpression) -> Result<String> {
+        let mut scrutinee = self.generate_expression(&match_expression.scrutinee)?;
+        let is_str = self.type_of(&match_expression.scrutinee) == Some(Type::Str);
+        if is_str {
+            scrutinee = format!("{}.as_str()", scrutinee);
+        }
+
+        let mut arms = Vec::new();
+        for arm in &match_expression.arms {
+            let binding = match arm.pattern {
+                Pattern::Binding(name) => Some(name),
+                _ => None,
+            };
+            let bound = match &arm.pattern {
+                Pattern::Variant(variant) => variant.bindings.iter().copied().collect(),
+                _ => binding.into_iter().collect(),
+            };
+            self.scopes.push(bound);
+            let body = self.generate_expression(&arm.body);
+            self.scopes.pop();
+            let body = body?;
+
+            arms.push(
+                match (&arm.pattern, binding.map(|name| rust_name(name.as_str()))) {
+                    (_, Some(name)) if is_str => {
+                        format!(
+                            "{} => {{ let {} = {}.to_string(); {} }}",
+                            name, name, name, body
+                        )
+                    }
+                    (_, Some(name)) => format!("{} => {}", name, body),
+                    (Pattern::Literal(Literal::String(value)), _) => {
+                        format!("{:?} => {}", value, body)
+                    }
+                    (Pattern::Literal(literal), _) => {
+                        format!("{} => {}", generate_literal(literal), body)
+                    }
+                    (Pattern::Variant(variant), _) if variant.bindings.is_empty() => {
+                        let enum_name = rust_name(&variant.enum_name);
+                        format!("{}::{} => {}", enum_name, rust_name(&variant.variant), body)
+                    }
+                    (Pattern::Variant(variant), _) => {
+                        let bindings: Vec<Cow<str>> = variant
+                            .bindings
+                            .iter()
+                            .map(|name| rust_name(name))
+                            .collect();
+                        format!(
+                            "{}::{}({}) => {}",
+                            rust_name(&variant.enum_name),
+                            rust_name(&variant.variant),
+                            bindings.join(", "),
+                            body
+                        )
+                    }
+                    _ => format!("_ => {}", body),
+                },
+            );
+        }
+        Ok(format!("match {} {{ {} }}", scrutinee, arms.join(", ")))
+    }
+
+    /// A lambda as an `Rc<dyn Fn>`, which can be cloned like any other value.
+    /// It moves in copies of the variables it uses, made when it is created,
+    /// so they stay usable after it.
+    fn generate_lambda(&mut self, lambda: &Lambda) -> Result<String> {
+        let types = self.types;
+        let Some(ty @ Type::Function(parameter_types, return_type)) = types.get(&lambda.span)
+        else {
+            return Err(Error::CodegenError("Missing type for lambda".to_string()));
+        };
+
+        let mut uses = IdentifierUses::default();
+        uses.visit_lambda(lambda);
+        let mut captures = Vec::new();
+        let mut captured: HashSet<Symbol> = lambda.parameters.iter().map(|p| p.name).collect();
+        for (name, span) in uses.identifiers {
+            if self.is_local(name) && captured.insert(name) {
+                let copy = self.types.get(&span).is_some_and(is_copy);
+                if !copy {
+                    captures.push(format!("let {0} = {0}.clone(); ", rust_name(&name)));
+                }
+            }
+        }
+
+        let parameters: Vec<String> = lambda
+            .parameters
+            .iter()
+            .zip(parameter_types)
+            .map(|(parameter, ty)| format!("{}: {}", rust_name(&parameter.name), rust_type(ty)))
+            .collect();
+        let mut closure = format!("move |{}|", parameters.join(", "));
+        if **return_type != Type::Void {
+            write!(closure, " -> {}", rust_type(return_type)).unwrap();
+        }
+
+        self.scopes.push(
+            lambda
+                .parameters
+                .iter()
+                .map(|parameter| parameter.name)
+                .collect(),
+        );
+        let body = match lambda.body.as_ref() {
+            LambdaBody::Block(_, block) => {
+                let outer_output = std::mem::take(&mut self.output);
+                let return_type = (**return_type).clone();
+                let outer_return = std::mem::replace(&mut self.return_type, return_type);
+                let try_closures = std::mem::take(&mut self.try_closures);
+                let result = self.generate_statements(&block.statements);
+                self.try_closures = try_closures;
+                self.return_type = outer_return;
+                let statements = std::mem::replace(&mut self.output, outer_output);
+                result.map(|()| format!("{{\n{}{}}}", statements, "    ".repeat(self.indent)))
+            }
+            LambdaBody::Expression(body) => {
+                self.generate_expression(body)
+                    .map(|code| match **return_type {
+                        Type::Void => format!("{{ {}; }}", code),
+                        _ => format!("{{ {} }}", self.coerce(return_type, body, code)),
+                    })
+            }
+        };
+        self.scopes.pop();
+
+        let lambda = format!(
+            "std::rc::Rc::new({} {}) as {}",
+            closure,
+            body?,
+            rust_type(ty)
+        );
+        if captures.is_empty() {
+            return Ok(lambda);
+        }
+        Ok(format!("{{ {}{} }}", captures.concat(), lambda))
+    }
+
+    fn generate_call(&mut self, call: &FunctionCall) -> Result<String> {
+        let expressions = self.call_arguments(call);
+        let mut arguments = Vec::new();
+        for argument in &expressions {
+            arguments.push(match argument {
+                Argument::Passed(expression) => self.generate_expression(expression)?,
+                Argument::Default(function) => format!("{}()", function),
+            });
+        }
+        let passed = || expressions.iter().map(Argument::passed);
+
+        let qualified = self.qualified_callee(call);
+        match qualified.as_ref().unwrap_or(&call.function) {
+            Expression::Identifier(identifier) if !self.is_local(identifier.name) => {
+                let params = self
+                    .functions
+                    .get(&identifier.name)
+                    .map(|p| parameter_types(p));
+                let params = params.unwrap_or_default();
+                let arguments = self.coerce_arguments(&params, passed(), arguments);
+                Ok(format!(
+                    "{}({})",
+                    rust_path(identifier.name),
+                    arguments.join(", ")
+                ))
+            }
+            Expression::MemberAccess(access) => {
+                if let Some(module) = self.module_name(&access.object) {
+                    let builtin = builtins::function(&module, &access.member).ok_or_else(|| {
+                        Error::CodegenError(format!(
+                            "Unknown function `{}.{}`",
+                            module, access.member
+                        ))
+                    })?;
+                    let arguments = self.display_arguments(&builtin, &call.arguments, arguments);
+                    return Ok(builtin.lower(None, &arguments));
+                }
+                if let Some(path) = self.variant_path(access) {
+                    let payload = match access.object.as_ref() {
+                        Expression::Identifier(identifier) => self
+                            .enums
+                            .get(&identifier.name)
+                            .and_then(|variants| variants.get(&access.member))
+                            .cloned()
+                            .unwrap_or_default(),
+                        _ => Vec::new(),
+                    };
+                    let arguments =
+                        self.coerce_arguments(&payload, call.arguments.iter().map(Some), arguments);
+                    return Ok(format!("{}({})", path, arguments.join(", ")));
+                }
+
+                let receiver_type = self.type_of(&access.object).ok_or_else(|| {
+                    Error::CodegenError(format!("Missing type for receiver of `{}`", access.member))
+                })?;
+                if let Type::Struct(name) | Type::Generic(name, _) = receiver_type {
+                    let params = self.methods.get(&(name, access.member));
+                    let params = params.map(|p| parameter_types(p)).unwrap_or_default();
+                    let arguments = self.coerce_arguments(&params, passed(), arguments);
+                    let receiver = self.generate_receiver(&access.object)?;
+                    let method = rust_name(&access.member);
+                    return Ok(format!("{}.{}({})", receiver, method, arguments.join(", ")));
+                }
+                let method = builtins::method(&receiver_type, &access.member).ok_or_else(|| {
+                    Error::CodegenError(format!(
+                        "Type `{}` has no method `{}`",
+                        receiver_type, access.member
+                    ))
+                })?;
+                let params: Vec<Type> = method
+                    .params
+                    .iter()
+                    .map(|param| match param {
+                        ParamKind::Exact(ty) => ty.clone(),
+                        ParamKind::Printable => Type::Void,
+                    })
+                    .collect();
+                let arguments =
+                    self.coerce_arguments(&params, call.arguments.iter().map(Some), arguments);
+                let receiver = self.generate_receiver(&access.object)?;
+                Ok(method.lower(Some(&receiver), &arguments))
+            }
+            callee => {
+                let params = match self.type_of(callee) {
+                    Some(Type::Function(params, _)) => params,
+                    _ => {
+                        return Err(Error::CodegenError(
+                            "Expression is not callable".to_string(),
+                        ))
+                    }
+                };
+                let arguments =
+                    self.coerce_arguments(&params, call.arguments.iter().map(Some), arguments);
+                let function = match callee {
+                    Expression::Identifier(identifier) => rust_name(&identifier.name).into_owned(),
+                    _ => format!("({})", self.generate_expression(callee)?),
+                };
+                Ok(format!("{}({})", function, arguments.join(", ")))
+            }
+        }
+    }
+
+    /// The arguments of `call` in the order of the parameters of the function
+    /// or method it calls, with named arguments moved into place and the
+    /// defaults of parameters left out.
+    fn call_arguments<'c>(&self, call: &'c FunctionCall) -> Vec<Argument<'c>> {
+        let qualified = self.qualified_callee(call);
+        let callee = match qualified.as_ref().unwrap_or(&call.function) {
+            Expression::Identifier(identifier) if !self.is_local(identifier.name) => self
+                .functions
+                .get(&identifier.name)
+                .zip(self.defaults.get(&identifier.name).cloned()),
+            Expression::MemberAccess(access) => match self.type_of(&access.object) {
+                Some(ref receiver @ (Type::Struct(name) | Type::Generic(name, _))) => {
+                    let path = format!(
+                        "<{}>::{}",
+                        rust_type(receiver),
+                        default_function(access.member)
+                    );
+                    self.methods.get(&(name, access.member)).zip(Some(path))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        let mut arguments: Vec<Argument> = call.arguments.iter().map(Argument::Passed).collect();
+        let Some((parameters, defaults)) = callee else {
+            return arguments;
+        };
+        for (index, parameter) in parameters.iter().enumerate().skip(call.arguments.len()) {
+            let named = call
+                .named_arguments
+                .iter()
+                .find(|argument| argument.name == parameter.name);
+            match named {
+                Some(argument) => arguments.push(Argument::Passed(&argument.value)),
+                None => arguments.push(Argument::Default(format!("{}{}", defaults, index))),
+            }
+        }
+        arguments
+    }
+
+    /// `arguments` converted to the types of `params`. An argument without
+    /// its expression has the type of its parameter already.
+    fn coerce_arguments<'e>(
+        &self,
+        params: &[Type],
+        expressions: impl IntoIterator<Item = Option<&'e Expression>>,
+        arguments: Vec<String>,
+    ) -> Vec<String> {
+        arguments
+            .into_iter()
+            .zip(expressions)
+            .enumerate()
+            .map(
+                |(index, (argument, expression))| match (params.get(index), expression) {
+                    (Some(param), Some(expression)) => self.coerce(param, expression, argument),
+                    _ => argument,
+                },
+            )
+            .collect()
+    }
+
+    /// Wraps arguments passed to `Printable` parameters so non-scalar values print via `Debug`.
+    fn display_arguments(
+        &self,
+        builtin: &Builtin,
+        expressions: &[Expression],
+        arguments: Vec<String>,
+    ) -> Vec<String> {
+        arguments
+            .into_iter()
+            .zip(expressions)
+            .enumerate()
+            .map(
+                |(index, (argument, expression))| match builtin.params.get(index) {
+                    Some(ParamKind::Printable)
+                        if !self.type_of(expression).as_ref().is_some_and(is_display) =>
+                    {
+                        format!("format!(\"{{:?}}\", {})", argument)
+                    }
+                    _ => argument,
+                },
+            )
+            .collect()
+    }
+
+    /// Renders an assignable place (`x`, `p.x`) without cloning.
+    fn generate_place(&mut self, expression: &Expression) -> Result<String> {
+        match expression {
+            Expression::Identifier(identifier) => Ok(rust_name(&identifier.name).into_owned()),
+            Expression::MemberAccess(access) => {
+                let object = self.generate_receiver(&access.object)?;
+                Ok(format!("{}.{}", object, rust_name(&access.member)))
+            }
+            Expression::Index(index) => {
+                let object = self.generate_receiver(&index.object)?;
+                let key = self.generate_expression(&index.index)?;
+                Ok(match self.type_of(&index.object) {
+                    // Out of bounds panics, with the interpreter's message,
+                    // which a `try` catches like a `throw`.
+                    Some(Type::List(_)) => {
+                        self.runtime.insert(Helper::Index);
+                        format!("{}[RusticIndex({})]", object, key)
+                    }
+                    // So does a missing key.
+                    _ => {
+                        self.runtime.insert(Helper::Key);
+                        format!("{}[RusticKey(&{})]", object, key)
+                    }
+                })
+            }
+            other => self.generate_expression(other),
+        }
+    }
+
+    /// `expression` as the left of `.` or `[`: places are used in place rather
+    /// than cloned.
+    fn generate_receiver(&mut self, expression: &Expression) -> Result<String> {
+        match expression {
+            Expression::Identifier(_) | Expression::MemberAccess(_) | Expression::Index(_) => {
+                self.generate_place(expression)
+            }
+            other => self.generate_expression(other),
+        }
+    }
+
+    /// `code` for `expression` stored where a `expected` is declared: a plain
+    /// value stored where an optional is expected becomes `Some(..)`.
+    fn coerce(&self, expected: &Type, expression: &Expression, code: String) -> String {
+        match (expected, self.type_of(expression)) {
+            (Type::Optional(_), Some(Type::Optional(_)) | None) => code,
+            (Type::Optional(_), Some(_)) => format!("Some({})", code),
+            _ => code,
+        }
+    }
+
+    /// The declared type of `variable`, or the one inferred from its initializer.
+    fn variable_type(&self, variable: &Variable) -> Type {
+        variable
+            .var_type
+            .clone()
+            .or_else(|| self.type_of(variable.initializer.as_ref()?))
+            .unwrap_or(Type::Void)
+    }
+
+    fn field_type(&self, struct_name: Symbol, field: Symbol) -> Option<Type> {
+        let fields = self.structs.get(&struct_name)?;
+        fields
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, ty)| ty.clone())
+    }
+
+    fn clone_if_needed(&self, code: String, expression: &Expression) -> String {
+        match self.type_of(expression) {
+            Some(ty) if is_copy(&ty) => code,
+            _ => format!("{}.clone()", code),
+        }
+    }
+
+    fn type_of(&self, expression: &Expression) -> Option<Type> {
+        match expression {
+            Expression::Literal(literal) => Some(literal_type(literal)),
+            other => other.span().and_then(|span| self.types.get(span)).cloned(),
+        }
+    }
+
+    fn module_name(&self, expression: &Expression) -> Option<String> {
+        match expression {
+            Expression::Identifier(identifier)
+                if !self.is_local(identifier.name) && !self.globals.contains(&identifier.name) =>
+            {
+                let module = self.qualifiers.get(&identifier.name)?;
+                builtins::is_module(module).then(|| module.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// `module.item` for an item of a module of the program imported whole,
+    /// as an identifier naming the item the way `collect_items` collected it:
+    /// unqualified unless the module has an alias or another module declares
+    /// the item too.
+    fn qualified(&self, access: &MemberAccess) -> Option<Identifier> {
+        let Expression::Identifier(module) = access.object.as_ref() else {
+            return None;
+        };
+        let path = self.qualifiers.get(&module.name)?;
+        let shadowed = self.is_local(module.name) || self.globals.contains(&module.name);
+        if shadowed || builtins::is_module(path) {
+            return None;
+        }
+        let qualified = qualify(module.name, access.member);
+        let name = match self.declares(qualified) {
+            true => qualified,
+            false => access.member,
+        };
+        Some(Identifier {
+            name,
+            span: access.span.clone(),
+        })
+    }
+
+    /// The function a call of `module.item` calls, as `qualified` names it.
+    fn qualified_callee(&self, call: &FunctionCall) -> Option<Expression> {
+        match call.function.as_ref() {
+            Expression::MemberAccess(access) => self.qualified(access).map(Expression::Identifier),
+            _ => None,
+        }
+    }
+
+    /// `Enum::Variant` for an `Enum.Variant` member access.
+    fn variant_path(&self, access: &MemberAccess) -> Option<String> {
+        match access.object.as_ref() {
+            Expression::Identifier(identifier)
+                if !self.is_local(identifier.name) && self.enums.contains_key(&identifier.name) =>
+            {
+                Some(format!(
+                    "{}::{}",
+                    rust_name(&identifier.name),
+                    rust_name(&access.member)
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// A comment naming the line of Rustic source the code after it comes
+    /// from, for the module's source map, unless it names the same line as
+    /// the last one.
+    fn source(&mut self, span: &Span) {
+        let comment = sourcemap::comment(span);
+        if self.source_comment.as_ref() != Some(&comment) {
+            self.line(&comment);
+            self.source_comment = Some(comment);
+        }
+    }
+
+    fn declare(&mut self, name: Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name);
+        }
+    }
+
+    fn is_local(&self, name: Symbol) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(&name))
+    }
+
+    fn line(&mut self, code: &str) {
+        for _ in 0..self.indent {
+            self.output.push_str("    ");
+        }
+        self.output.push_str(code);
+        self.output.push('\n');
+    }
+}
+
+/// Every variable an AST node refers to, with the span of each use.
+#[derive(Default)]
+struct IdentifierUses {
+    identifiers: Vec<(Symbol, Span)>,
+}
+
+impl Visitor for IdentifierUses {
+    fn visit_identifier(&mut self, identifier: &Identifier) {
+        self.identifiers
+            .push((identifier.name, identifier.span.clone()));
+    }
+}
+
+/// Whether a `return` in `block` leaves the enclosing function, rather
+/// than a lambda inside it.
+fn contains_return(block: &Block) -> bool {
+    #[derive(Default)]
+    struct Returns(bool);
+
+    impl Visitor for Returns {
+        fn visit_return(&mut self, _return_statement: &ReturnStatement) {
+            self.0 = true;
+        }
+
+        fn visit_lambda(&mut self, _lambda: &Lambda) {}
+    }
+
+    let mut returns = Returns::default();
+    returns.visit_block(block);
+    returns.0
+}
+
+/// Whether every clause is a plain `catch Error`, so the first one handles
+/// any error without looking at it.
+fn catches_any(clauses: &[CatchClause]) -> bool {
+    clauses
+        .iter()
+        .all(|clause| clause.exception_type == "Error" && clause.binding.is_none())
+}
+
+pub fn rust_type(ty: &Type) -> String {
+    match ty {
+        Type::Int => "i64".to_string(),
+        Type::Float => "f64".to_string(),
+        Type::Str => "String".to_string(),
+        Type::Bool => "bool".to_string(),
+        Type::List(element) => format!("Vec<{}>", rust_type(element)),
+        Type::Map(key, value) => {
+            format!(
+                "std::collections::BTreeMap<{}, {}>",
+                rust_type(key),
+                rust_type(value)
+            )
+        }
+        Type::Struct(name) | Type::Param(name) => rust_name(name).into_owned(),
+        Type::Generic(name, arguments) => {
+            let arguments: Vec<String> = arguments.iter().map(rust_type).collect();
+            format!("{}<{}>", rust_name(name), arguments.join(", "))
+        }
+        Type::Optional(inner) => format!("Option<{}>", rust_type(inner)),
+        Type::Function(parameters, return_type) => {
+            let parameters: Vec<String> = parameters.iter().map(rust_type).collect();
+            let mut function = format!("std::rc::Rc<dyn Fn({})", parameters.join(", "));
+            if **return_type != Type::Void {
+                write!(function, " -> {}", rust_type(return_type)).unwrap();
+            }
+            function.push('>');
+            function
+        }
+        Type::Tuple(elements) => {
+            let elements: Vec<String> = elements.iter().map(rust_type).collect();
+            format!("({})", elements.join(", "))
+        }
+        Type::Void => "()".to_string(),
+    }
+}
+
+/// An argument of a call, in the place of its parameter.
+enum Argument<'c> {
+    Passed(&'c Expression),
+    /// The parameter is left to its default: the function giving it.
+    Default(String),
+}
+
+impl<'c> Argument<'c> {
+    fn passed(&self) -> Option<&'c Expression> {
+        match self {
+            Argument::Passed(expression) => Some(expression),
+            Argument::Default(_) => None,
+        }
+    }
+}
+
+/// The name, but for the parameter's number, of the functions giving the
+/// default values of the parameters of the function or method `name`.
+fn default_function(name: Symbol) -> String {
+    format!("rustic_default_{}_", name)
+}
+
+fn parameter_types(parameters: &[Parameter]) -> Vec<Type> {
+    parameters
+        .iter()
+        .map(|parameter| parameter.param_type.clone())
+        .collect()
+}
+
+/// The Rust path of a function or global, which for an item of a module
+/// imported with `as` is qualified by the alias.
+fn rust_path(name: Symbol) -> String {
+    rust_module_path(&name)
+}
+
+/// Every keyword of Rust, strict or reserved, but `self`, which means the
+/// same in Rustic. Rustic reserves fewer words, so a Rustic name can be one
+/// of these.
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "Self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// `name` as a Rust identifier. A Rust keyword is written as a raw
+/// identifier, as in `r#loop`, except for those Rust does not allow raw,
+/// which get a trailing underscore instead: `crate_`.
+pub fn rust_name(name: &str) -> Cow<'_, str> {
+    if !RUST_KEYWORDS.contains(&name) {
+        return Cow::Borrowed(name);
+    }
+    match name {
+        "crate" | "Self" | "super" => Cow::Owned(format!("{}_", name)),
+        _ => Cow::Owned(format!("r#{}", name)),
+    }
+}
+
+/// The Rust path of a dotted Rustic path, such as the module `net.http`.
+pub fn rust_module_path(path: &str) -> String {
+    path.split('.')
+        .map(rust_name)
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// `code`, a module as `CodeGenerator::generate` writes it, laid out the way
+/// rustfmt lays out Rust, with a blank line before each item but the `use`
+/// declarations after the first. The comment lines it starts with are kept
+/// as they are, and so are its source comments, which stand in for the
+/// statements they precede as `rustic_source!(n);` while it is formatted.
+/// Code that does not parse is returned unchanged, for rustc to report.
+pub fn format(code: &str) -> String {
+    let header: usize = code
+        .lines()
+        .take_while(|line| line.starts_with("// "))
+        .map(|line| line.len() + 1)
+        .sum();
+    let (header, items) = code.split_at(header.min(code.len()));
+    let mut comments = Vec::new();
+    let items: String = items
+        .lines()
+        .map(|line| match sourcemap::parse_comment(line) {
+            Some(_) => {
+                comments.push(line.trim_start());
+                format!("rustic_source!({});\n", comments.len() - 1)
+            }
+            None => format!("{}\n", line),
+        })
+        .collect();
+    let Ok(mut file) = syn::parse_file(&items) else {
+        return code.to_string();
+    };
+
+    let items = std::mem::take(&mut file.items);
+    let mut formatted = format!("{}{}", header, prettyplease::unparse(&file));
+    if !formatted.ends_with('\n') {
+        formatted.push('\n');
+    }
+    let (mut uses, mut commented) = (false, false);
+    for item in items {
+        let is_use = matches!(item, syn::Item::Use(_));
+        if !(commented || uses && is_use) {
+            formatted.push('\n');
+        }
+        uses = is_use;
+        commented = match &item {
+            syn::Item::Macro(item) => item.mac.path.is_ident("rustic_source"),
+            _ => false,
+        };
+        let item = syn::File {
+            shebang: None,
+            attrs: Vec::new(),
+            items: vec![item],
+        };
+        formatted.push_str(&prettyplease::unparse(&item));
+    }
+
+    let mut restored = String::with_capacity(formatted.len());
+    for line in formatted.lines() {
+        let statement = line.trim_start();
+        let index = statement
+            .strip_prefix("rustic_source!(")
+            .and_then(|rest| rest.strip_suffix(");"))
+            .and_then(|index| index.parse::<usize>().ok());
+        match index.and_then(|index| comments.get(index)) {
+            Some(comment) => {
+                restored.push_str(&line[..line.len() - statement.len()]);
+                restored.push_str(comment);
+            }
+            None => restored.push_str(line),
+        }
+        restored.push('\n');
+    }
+    restored
+}
+
+/// `pub ` for items declared `pub`, which other modules may use.
+fn visibility(public: bool) -> &'static str {
+    if public {
+        "pub "
+    } else {
+        ""
+    }
+}
+
+/// `<T, U>`, or nothing without type parameters. With `bounds`, each
+/// parameter allows what generated code does with values: cloning,
+/// comparing and printing them.
+fn generics(type_params: &[Symbol], bounds: bool) -> String {
+    if type_params.is_empty() {
+        return String::new();
+    }
+    let type_params: Vec<String> = type_params
+        .iter()
+        .map(|name| match bounds {
+            true => format!("{}: Clone + PartialEq + std::fmt::Debug", rust_name(name)),
+            false => rust_name(name).into_owned(),
+        })
+        .collect();
+    format!("<{}>", type_params.join(", "))
+}
+
+fn generate_literal(literal: &Literal) -> String {
+    match literal {
+        Literal::Integer(value) => format!("{}i64", value),
+        Literal::Float(value) if value.is_infinite() => "f64::INFINITY".to_string(),
+        Literal::Float(value) => format!("{:?}f64", value),
+        Literal::String(value) => format!("String::from({:?})", value),
+        Literal::Boolean(value) => value.to_string(),
+        Literal::None => "None".to_string(),
+    }
+}
+
+fn is_copy(ty: &Type) -> bool {
+    match ty {
+        Type::Optional(inner) => is_copy(inner),
+        Type::Tuple(elements) => elements.iter().all(is_copy),
+        _ => matches!(ty, Type::Int | Type::Float | Type::Bool | Type::Void),
+    }
+}
+
+pub fn is_display(ty: &Type) -> bool {
+    matches!(ty, Type::Int | Type::Float | Type::Bool | Type::Str)
+}
+
+/// Whether `index` is a literal that indexes a list as it is, without
+/// counting from the end.
+/// The function that applies `operator` to two `int`s, for the operators
+/// whose Rust counterpart panics on overflow or that rustc rejects outright
+/// when it can tell they fail.
+fn int_function(operator: &BinaryOperator) -> Option<&'static str> {
+    Some(match operator {
+        BinaryOperator::Add => "i64::wrapping_add",
+        BinaryOperator::Sub => "i64::wrapping_sub",
+        BinaryOperator::Mul => "i64::wrapping_mul",
+        BinaryOperator::Div => "rustic_div",
+        BinaryOperator::Mod => "rustic_rem",
+        BinaryOperator::Shl => "rustic_shl",
+        BinaryOperator::Shr => "rustic_shr",
+        _ => return None,
+    })
+}
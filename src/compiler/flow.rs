@@ -0,0 +1,436 @@
+//! Control-flow checks run after type checking: statements
+//! that can never run because one before them always leaves the block,
+//! branches of an `if` or `while` whose condition is a constant that never
+//! lets them run, functions that can end without returning a value, and
+//! variables used before they are assigned one.
+
+use crate::compiler::ast::*;
+use crate::compiler::xref::{SymbolKind, XrefIndex};
+use crate::diagnostics::{codes, Diagnostic, Lint, Span};
+use std::collections::HashMap;
+
+/// Warnings about the code of `program` that can never run, in the order it
+/// appears.
+pub fn unreachable_code(program: &Program) -> Vec<Diagnostic> {
+    let mut reachability = Reachability::default();
+    reachability.visit_program(program);
+    reachability.diagnostics
+}
+
+/// Errors for the uses of local variables declared without a value, such as
+/// `var x: int`, that some path reaches before assigning them one. `xref` is
+/// the index semantic analysis built for `program`, which tells what each
+/// name refers to.
+pub fn unassigned_uses(program: &Program, xref: &XrefIndex) -> Vec<Diagnostic> {
+    let mut assignments = Assignments {
+        xref,
+        declarations: xref
+            .definitions
+            .iter()
+            .filter(|definition| definition.kind == SymbolKind::Variable)
+            .map(|definition| (&definition.span, definition.id))
+            .collect(),
+        references: xref
+            .references
+            .iter()
+            .map(|reference| (&reference.span, reference.definition))
+            .collect(),
+        unassigned: HashMap::new(),
+        closures: 0,
+        diagnostics: Vec::new(),
+    };
+    assignments.visit_program(program);
+    assignments.diagnostics
+}
+
+/// An error when a function returning `return_type` has a path through
+/// `body` that ends without a `return` or `throw`, pointing at where that
+/// path leaves the body: the closing brace of the block it ends in, or an
+/// `if` without an `else`. Which paths return is decided the way rustc does
+/// for the generated code, so conditions are not evaluated and loops and
+/// `try` statements never count as returning.
+pub fn missing_return(body: &Block, return_type: &Type) -> Option<Diagnostic> {
+    let message = format!(
+        "Not every path returns a value; function returns `{}`",
+        return_type
+    );
+    let mut block = body;
+    while !block_returns(block) {
+        let Some(Statement::If(if_statement)) = block.statements.last() else {
+            return Some(
+                Diagnostic::error(message, closing_brace(&block.span))
+                    .with_code(codes::RETURN)
+                    .with_help("end this block with a `return`"),
+            );
+        };
+        let Some(else_block) = &if_statement.else_block else {
+            return Some(
+                Diagnostic::error(message, if_statement.span.clone())
+                    .with_code(codes::RETURN)
+                    .with_help("add an `else` that returns a value, or a `return` after the `if`"),
+            );
+        };
+        block = std::iter::once(&if_statement.then_block)
+            .chain(if_statement.else_ifs.iter().map(|(_, block)| block))
+            .chain(std::iter::once(else_block))
+            .find(|block| !block_returns(block))
+            .expect("an `if` that does not return has a branch that does not");
+    }
+    None
+}
+
+#[derive(Default)]
+struct Reachability {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Reachability {
+    fn unreachable_branch(&mut self, block: &Block, help: &str) {
+        self.diagnostics.push(
+            Diagnostic::lint(
+                Lint::UnreachableCode,
+                "Unreachable branch",
+                block.span.clone(),
+            )
+            .with_help(help),
+        );
+    }
+}
+
+impl Visitor for Reachability {
+    /// Reports the statements after the first one that always leaves the
+    /// block, and checks only the ones before, so code that cannot run is not
+    /// reported twice.
+    fn visit_block(&mut self, block: &Block) {
+        let exit = block.statements.iter().position(diverges);
+        let reachable = exit.map_or(block.statements.len(), |exit| exit + 1);
+        for statement in &block.statements[..reachable] {
+            self.visit_statement(statement);
+        }
+
+        let rest = &block.statements[reachable..];
+        let first = rest.iter().find_map(Statement::span);
+        let last = rest.iter().rev().find_map(Statement::span);
+        if let (Some(exit), Some(first), Some(last)) = (exit, first, last) {
+            let exit = &block.statements[exit];
+            let mut diagnostic =
+                Diagnostic::lint(Lint::UnreachableCode, "Unreachable code", first.to(last));
+            if let Some(span) = exit.span() {
+                let label = format!("any code after this {} never runs", exit_kind(exit));
+                diagnostic = diagnostic.with_label(span.clone(), label);
+            }
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn visit_if(&mut self, if_statement: &IfStatement) {
+        let branches = std::iter::once((Some(&if_statement.condition), &if_statement.then_block))
+            .chain(
+                if_statement
+                    .else_ifs
+                    .iter()
+                    .map(|(condition, block)| (Some(condition), block)),
+            )
+            .chain(if_statement.else_block.iter().map(|block| (None, block)));
+        let mut always_taken = false;
+        for (condition, block) in branches {
+            if always_taken {
+                self.unreachable_branch(block, "an earlier condition is always `true`");
+                continue;
+            }
+            if let Some(condition) = condition {
+                self.visit_expression(condition);
+            }
+            match condition.and_then(constant) {
+                Some(false) => self.unreachable_branch(block, "its condition is always `false`"),
+                Some(true) => {
+                    always_taken = true;
+                    self.visit_block(block);
+                }
+                None => self.visit_block(block),
+            }
+        }
+    }
+
+    fn visit_while(&mut self, while_loop: &WhileLoop) {
+        self.visit_expression(&while_loop.condition);
+        if constant(&while_loop.condition) == Some(false) {
+            self.diagnostics.push(
+                Diagnostic::lint(
+                    Lint::UnreachableCode,
+                    "Unreachable loop body",
+                    while_loop.body.span.clone(),
+                )
+                .with_help("its condition is always `false`"),
+            );
+        } else {
+            self.visit_block(&while_loop.body);
+        }
+    }
+}
+
+/// Follows which variables declared without a value have been assigned one
+/// through every path of each function. Conditions are not evaluated and
+/// loops may run no times at all, as rustc assumes for the generated code.
+struct Assignments<'a> {
+    xref: &'a XrefIndex,
+    declarations: HashMap<&'a Span, usize>,
+    references: HashMap<&'a Span, usize>,
+    /// The variables some path to the current statement declared without
+    /// assigning them, by definition, with how many closures enclose their
+    /// declaration. Empty where no path reaches.
+    unassigned: HashMap<usize, usize>,
+    /// How many `try` blocks, `catch` clauses and lambdas enclose the current
+    /// statement, all of which are closures in the generated Rust.
+    closures: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Assignments<'_> {
+    /// Visits each of `blocks` from the current state and goes on with the
+    /// variables any of them may have left unassigned.
+    fn branches<'b>(&mut self, blocks: impl IntoIterator<Item = &'b Block>) {
+        let before = self.unassigned.clone();
+        let mut after = HashMap::new();
+        for block in blocks {
+            self.unassigned = before.clone();
+            self.visit_block(block);
+            after.extend(self.unassigned.drain());
+        }
+        self.unassigned = after;
+    }
+
+    /// Runs `visit` as code that may run any number of times, or never, so
+    /// whatever it assigns still counts as unassigned after it. `closure`
+    /// is set when it is a closure in the generated Rust.
+    fn maybe(&mut self, closure: bool, visit: impl FnOnce(&mut Self)) {
+        let before = self.unassigned.clone();
+        self.closures += usize::from(closure);
+        visit(self);
+        self.closures -= usize::from(closure);
+        self.unassigned = before;
+    }
+
+    /// The variable `identifier` refers to, if it is still unassigned, with
+    /// how many closures enclose its declaration.
+    fn unassigned(&self, identifier: &Identifier) -> Option<(usize, usize)> {
+        let definition = *self.references.get(&identifier.span)?;
+        let closures = *self.unassigned.get(&definition)?;
+        Some((definition, closures))
+    }
+
+    /// Reports `message` at `identifier`, which refers to the unassigned
+    /// `definition`, and counts it as assigned from then on so it is
+    /// reported once.
+    fn unassigned_error(
+        &mut self,
+        definition: usize,
+        identifier: &Identifier,
+        message: String,
+        help: String,
+    ) {
+        let declaration = self.xref.definitions[definition].span.clone();
+        self.diagnostics.push(
+            Diagnostic::error(message, identifier.span.clone())
+                .with_code(codes::UNASSIGNED)
+                .with_label(declaration, "declared here without a value")
+                .with_help(help),
+        );
+        self.unassigned.remove(&definition);
+    }
+}
+
+impl Visitor for Assignments<'_> {
+    fn visit_variable(&mut self, variable: &Variable) {
+        walk_variable(self, variable);
+        if variable.initializer.is_none() {
+            if let Some(&definition) = self.declarations.get(&variable.span) {
+                self.unassigned.insert(definition, self.closures);
+            }
+        }
+    }
+
+    fn visit_assignment(&mut self, assignment: &Assignment) {
+        self.visit_expression(&assignment.value);
+        let Expression::Identifier(identifier) = &assignment.target else {
+            self.visit_expression(&assignment.target);
+            return;
+        };
+        if assignment.operator.is_some() {
+            self.visit_identifier(identifier);
+            return;
+        }
+        let Some((definition, closures)) = self.unassigned(identifier) else {
+            return;
+        };
+        // A closure cannot capture a variable without a value, even to
+        // assign one.
+        if closures < self.closures {
+            let message = format!(
+                "Cannot assign `{}` inside a `try`, `catch` or lambda before it has a value",
+                identifier.name
+            );
+            let help = format!(
+                "give `{}` a value before the `try` or lambda",
+                identifier.name
+            );
+            self.unassigned_error(definition, identifier, message, help);
+        } else {
+            self.unassigned.remove(&definition);
+        }
+    }
+
+    fn visit_identifier(&mut self, identifier: &Identifier) {
+        if let Some((definition, _)) = self.unassigned(identifier) {
+            let message = format!("Use of `{}` before it is assigned a value", identifier.name);
+            let help = format!(
+                "assign `{}` on every path that reaches this, or give it a value where it is \
+                 declared",
+                identifier.name
+            );
+            self.unassigned_error(definition, identifier, message, help);
+        }
+    }
+
+    fn visit_if(&mut self, if_statement: &IfStatement) {
+        // Conditions cannot assign, so each one sees what the first does.
+        self.visit_expression(&if_statement.condition);
+        for (condition, _) in &if_statement.else_ifs {
+            self.visit_expression(condition);
+        }
+        let skipped = if_statement
+            .else_block
+            .is_none()
+            .then(|| self.unassigned.clone());
+        self.branches(
+            std::iter::once(&if_statement.then_block)
+                .chain(if_statement.else_ifs.iter().map(|(_, block)| block))
+                .chain(&if_statement.else_block),
+        );
+        self.unassigned.extend(skipped.into_iter().flatten());
+    }
+
+    fn visit_for(&mut self, for_loop: &ForLoop) {
+        self.visit_expression(&for_loop.iterable);
+        self.maybe(false, |this| this.visit_block(&for_loop.body));
+    }
+
+    fn visit_while(&mut self, while_loop: &WhileLoop) {
+        self.visit_expression(&while_loop.condition);
+        self.maybe(false, |this| this.visit_block(&while_loop.body));
+    }
+
+    /// The `try` block and its handlers may stop anywhere, so only the
+    /// `finally` block assigns for the code after it.
+    fn visit_try(&mut self, try_statement: &TryStatement) {
+        self.maybe(true, |this| {
+            this.visit_block(&try_statement.try_block);
+            for clause in &try_statement.catch_clauses {
+                this.visit_catch_clause(clause);
+            }
+        });
+        if let Some(finally_block) = &try_statement.finally_block {
+            self.visit_block(finally_block);
+        }
+    }
+
+    fn visit_lambda(&mut self, lambda: &Lambda) {
+        self.maybe(true, |this| walk_lambda(this, lambda));
+    }
+
+    fn visit_return(&mut self, return_statement: &ReturnStatement) {
+        walk_return(self, return_statement);
+        self.unassigned.clear();
+    }
+
+    fn visit_throw(&mut self, throw_statement: &ThrowStatement) {
+        self.visit_expression(&throw_statement.value);
+        self.unassigned.clear();
+    }
+
+    fn visit_break(&mut self, _break_statement: &BreakStatement) {
+        self.unassigned.clear();
+    }
+
+    fn visit_continue(&mut self, _continue_statement: &ContinueStatement) {
+        self.unassigned.clear();
+    }
+}
+
+/// Whether `statement` always leaves the block it is in: it returns, throws,
+/// breaks or continues, or every branch of an `if` does. A `try` never
+/// counts, as the Rust generated for a function needs a `return` after it
+/// even when the `try` always returns.
+fn diverges(statement: &Statement) -> bool {
+    match statement {
+        Statement::Return(_)
+        | Statement::Throw(_)
+        | Statement::Break(_)
+        | Statement::Continue(_) => true,
+        Statement::If(if_statement) => {
+            if_statement.else_block.as_ref().is_some_and(block_diverges)
+                && block_diverges(&if_statement.then_block)
+                && if_statement
+                    .else_ifs
+                    .iter()
+                    .all(|(_, block)| block_diverges(block))
+        }
+        _ => false,
+    }
+}
+
+fn block_diverges(block: &Block) -> bool {
+    block.statements.iter().any(diverges)
+}
+
+/// Whether `statement` always leaves the function it is in: it returns or
+/// throws, or every branch of an `if` does.
+fn returns(statement: &Statement) -> bool {
+    match statement {
+        Statement::Return(_) | Statement::Throw(_) => true,
+        Statement::If(if_statement) => {
+            if_statement.else_block.as_ref().is_some_and(block_returns)
+                && block_returns(&if_statement.then_block)
+                && if_statement
+                    .else_ifs
+                    .iter()
+                    .all(|(_, block)| block_returns(block))
+        }
+        _ => false,
+    }
+}
+
+fn block_returns(block: &Block) -> bool {
+    block.statements.iter().any(returns)
+}
+
+/// The `}` the span of a block ends with.
+fn closing_brace(span: &Span) -> Span {
+    Span {
+        start_line: span.end_line,
+        start_column: span.end_column.saturating_sub(1),
+        start_offset: span.end_offset.saturating_sub(1),
+        ..span.clone()
+    }
+}
+
+/// What `statement`, which always leaves its block, does, for the label
+/// pointing at it.
+fn exit_kind(statement: &Statement) -> &'static str {
+    match statement {
+        Statement::Return(_) => "`return`",
+        Statement::Throw(_) => "`throw`",
+        Statement::Break(_) => "`break`",
+        Statement::Continue(_) => "`continue`",
+        Statement::If(_) => "`if`",
+        _ => "statement",
+    }
+}
+
+/// The value of a condition that is the literal `true` or `false`.
+fn constant(condition: &Expression) -> Option<bool> {
+    match condition {
+        Expression::Literal(Literal::Boolean(value)) => Some(*value),
+        _ => None,
+    }
+}
@@ -0,0 +1,510 @@
+//! Constant folding, run by semantic analysis on a copy of each module it
+//! checks, which code is then generated from: arithmetic, comparisons, logic
+//! and string concatenation on literals become the literal they evaluate to,
+//! uses of the module's constants become their values, and `if` and `while`
+//! statements whose condition is a literal keep only the code that can run.
+//! An operation that would always fail, such as a division by zero, is left
+//! as it is and reported, since rustc would reject it in the generated code.
+
+use crate::compiler::ast::*;
+use crate::compiler::intern::Symbol;
+use crate::compiler::semantic::{literal_type, TypeTable};
+use crate::diagnostics::{codes, Diagnostic, Span};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// Folds `program` in place, returning errors for the operations on
+/// constants that always fail. `types` are the types semantic analysis
+/// found for its expressions.
+pub fn fold(program: &mut Program, types: &TypeTable) -> Vec<Diagnostic> {
+    let mut folder = Folder {
+        types,
+        constants: constant_values(program),
+        scopes: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    folder.visit_program_mut(program);
+    folder.diagnostics
+}
+
+/// The values of the module's constants that evaluate to a literal of their
+/// type. Constants may use others declared after them, so they are evaluated
+/// again until no more are found.
+fn constant_values(program: &Program) -> HashMap<Symbol, Literal> {
+    let mut values = HashMap::new();
+    loop {
+        let known = values.len();
+        for item in &program.items {
+            let Item::Constant(constant) = item else {
+                continue;
+            };
+            if values.contains_key(&constant.name) {
+                continue;
+            }
+            if let Some(value) = evaluate(&constant.value, &values)
+                .and_then(|value| typed(value, &constant.const_type))
+            {
+                values.insert(constant.name, value);
+            }
+        }
+        if values.len() == known {
+            return values;
+        }
+    }
+}
+
+/// The value of `expression` if it is made of literals, constants and the
+/// operators folding evaluates.
+fn evaluate(expression: &Expression, constants: &HashMap<Symbol, Literal>) -> Option<Literal> {
+    match expression {
+        Expression::Identifier(identifier) => constants.get(&identifier.name).cloned(),
+        Expression::Binary(binary) => {
+            let left = evaluate(&binary.left, constants)?;
+            let right = evaluate(&binary.right, constants)?;
+            binary_value(&binary.operator, &left, &right).ok()?
+        }
+        Expression::Unary(unary) => {
+            let operand = evaluate(&unary.operand, constants)?;
+            unary_value(&unary.operator, &operand).ok()?
+        }
+        expression => literal(expression),
+    }
+}
+
+struct Folder<'a> {
+    types: &'a TypeTable,
+    constants: HashMap<Symbol, Literal>,
+    /// Names declared by the enclosing functions, blocks and bindings, which
+    /// hide the constants of the same name.
+    scopes: Vec<HashSet<Symbol>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Why an operation on constants always fails.
+enum Failure {
+    DivideByZero,
+    RemainderByZero,
+    Overflow,
+    /// A shift by a negative number or 64 or more bits.
+    Shift,
+    /// An `int` raised to a negative power.
+    NegativeExponent,
+}
+
+impl Folder<'_> {
+    fn scoped(&mut self, names: impl IntoIterator<Item = Symbol>, visit: impl FnOnce(&mut Self)) {
+        self.scopes.push(names.into_iter().collect());
+        visit(self);
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: Symbol) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name);
+        }
+    }
+
+    fn is_local(&self, name: Symbol) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(&name))
+    }
+
+    /// What `expression`, whose operands are folded already, folds to.
+    fn fold_expression(&mut self, expression: &Expression) -> Option<Expression> {
+        let value = match expression {
+            Expression::Identifier(identifier) if !self.is_local(identifier.name) => {
+                self.constants.get(&identifier.name)?.clone()
+            }
+            Expression::Binary(binary) => {
+                // `false && x` and `true || x` never evaluate `x`.
+                match (&binary.operator, literal(&binary.left)) {
+                    (BinaryOperator::And, Some(Literal::Boolean(true)))
+                    | (BinaryOperator::Or, Some(Literal::Boolean(false))) => {
+                        return Some((*binary.right).clone());
+                    }
+                    (BinaryOperator::And, Some(Literal::Boolean(false)))
+                    | (BinaryOperator::Or, Some(Literal::Boolean(true))) => {
+                        return Some((*binary.left).clone());
+                    }
+                    _ => {}
+                }
+                // `x && true` and `x || false` are `x`.
+                match (&binary.operator, literal(&binary.right)) {
+                    (BinaryOperator::And, Some(Literal::Boolean(true)))
+                    | (BinaryOperator::Or, Some(Literal::Boolean(false))) => {
+                        return Some((*binary.left).clone());
+                    }
+                    _ => {}
+                }
+                let right = literal(&binary.right)?;
+                let result = match literal(&binary.left) {
+                    Some(left) => binary_value(&binary.operator, &left, &right),
+                    // rustc rejects a division by a constant zero whatever
+                    // is divided.
+                    None if right == Literal::Integer(0)
+                        && self.type_of(&binary.left) == Some(Type::Int) =>
+                    {
+                        match binary.operator {
+                            BinaryOperator::Div => Err(Failure::DivideByZero),
+                            BinaryOperator::Mod => Err(Failure::RemainderByZero),
+                            _ => return None,
+                        }
+                    }
+                    // Nor can any `int` be raised to a negative power.
+                    None if matches!(right, Literal::Integer(exponent) if exponent < 0)
+                        && binary.operator == BinaryOperator::Pow
+                        && self.type_of(&binary.left) == Some(Type::Int) =>
+                    {
+                        Err(Failure::NegativeExponent)
+                    }
+                    None => return None,
+                };
+                match result {
+                    Ok(value) => value?,
+                    Err(failure) => {
+                        self.report(failure, &binary.span);
+                        return None;
+                    }
+                }
+            }
+            Expression::Unary(unary) => {
+                match unary_value(&unary.operator, &literal(&unary.operand)?) {
+                    Ok(value) => value?,
+                    Err(failure) => {
+                        self.report(failure, &unary.span);
+                        return None;
+                    }
+                }
+            }
+            _ => return None,
+        };
+        literal_expression(value, expression.span()?)
+    }
+
+    fn type_of(&self, expression: &Expression) -> Option<Type> {
+        match literal(expression) {
+            Some(literal) => Some(literal_type(&literal)),
+            None => self.types.get(expression.span()?).cloned(),
+        }
+    }
+
+    fn report(&mut self, failure: Failure, span: &Span) {
+        let message = match failure {
+            Failure::DivideByZero => "Cannot divide by zero",
+            Failure::RemainderByZero => "Cannot take the remainder of a division by zero",
+            Failure::Overflow => "Arithmetic overflow: the result does not fit in an `int`",
+            Failure::Shift => "Cannot shift an `int` by a negative number or 64 or more bits",
+            Failure::NegativeExponent => "Cannot raise an `int` to a negative power",
+        };
+        self.diagnostics
+            .push(Diagnostic::error(message, span.clone()).with_code(codes::ALWAYS_FAILS));
+    }
+
+    /// The statements an `if` folds to: the branches whose condition is
+    /// `false` are dropped, and one whose condition is `true` becomes the
+    /// `else`. Its statements replace the `if` when no branch is left
+    /// before it, unless they declare variables that would then outlive it.
+    fn fold_if(&mut self, if_statement: IfStatement) -> Vec<Statement> {
+        let IfStatement {
+            condition,
+            then_block,
+            else_ifs,
+            mut else_block,
+            span,
+        } = if_statement;
+        let mut branches = Vec::new();
+        for (mut condition, block) in std::iter::once((condition, then_block)).chain(else_ifs) {
+            self.visit_expression_mut(&mut condition);
+            match literal(&condition) {
+                Some(Literal::Boolean(false)) => {}
+                Some(Literal::Boolean(true)) => {
+                    else_block = Some(block);
+                    break;
+                }
+                _ => branches.push((condition, block)),
+            }
+        }
+        for (_, block) in &mut branches {
+            self.visit_block_mut(block);
+        }
+        if let Some(else_block) = &mut else_block {
+            self.visit_block_mut(else_block);
+        }
+
+        let mut branches = branches.into_iter();
+        let Some((condition, then_block)) = branches.next() else {
+            return match else_block {
+                Some(block) if !block.statements.iter().any(declares) => block.statements,
+                Some(block) => vec![Statement::If(IfStatement {
+                    condition: Expression::Literal(Literal::Boolean(true)),
+                    then_block: block,
+                    else_ifs: Vec::new(),
+                    else_block: None,
+                    span,
+                })],
+                None => Vec::new(),
+            };
+        };
+        vec![Statement::If(IfStatement {
+            condition,
+            then_block,
+            else_ifs: branches.collect(),
+            else_block,
+            span,
+        })]
+    }
+}
+
+impl VisitorMut for Folder<'_> {
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        let parameters: Vec<_> = function.parameters.iter().map(|p| p.name).collect();
+        self.scoped(parameters, |this| walk_function_mut(this, function));
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        self.scopes.push(HashSet::new());
+        for mut statement in std::mem::take(&mut block.statements) {
+            match statement {
+                Statement::If(if_statement) => {
+                    let statements = self.fold_if(if_statement);
+                    block.statements.extend(statements);
+                }
+                Statement::While(mut while_loop) => {
+                    self.visit_expression_mut(&mut while_loop.condition);
+                    if literal(&while_loop.condition) != Some(Literal::Boolean(false)) {
+                        self.visit_block_mut(&mut while_loop.body);
+                        block.statements.push(Statement::While(while_loop));
+                    }
+                }
+                _ => {
+                    self.visit_statement_mut(&mut statement);
+                    block.statements.push(statement);
+                }
+            }
+        }
+        self.scopes.pop();
+    }
+
+    fn visit_variable_mut(&mut self, variable: &mut Variable) {
+        walk_variable_mut(self, variable);
+        self.declare(variable.name);
+    }
+
+    fn visit_destructure_mut(&mut self, destructure: &mut Destructure) {
+        self.visit_expression_mut(&mut destructure.initializer);
+        for identifier in &destructure.names {
+            self.declare(identifier.name);
+        }
+    }
+
+    fn visit_for_mut(&mut self, for_loop: &mut ForLoop) {
+        self.visit_expression_mut(&mut for_loop.iterable);
+        let body = &mut for_loop.body;
+        self.scoped([for_loop.variable], |this| this.visit_block_mut(body));
+    }
+
+    fn visit_catch_clause_mut(&mut self, clause: &mut CatchClause) {
+        let block = &mut clause.handler_block;
+        self.scoped(clause.binding, |this| this.visit_block_mut(block));
+    }
+
+    fn visit_lambda_mut(&mut self, lambda: &mut Lambda) {
+        let parameters: Vec<_> = lambda.parameters.iter().map(|p| p.name).collect();
+        self.scoped(parameters, |this| walk_lambda_mut(this, lambda));
+    }
+
+    fn visit_comprehension_mut(&mut self, comprehension: &mut Comprehension) {
+        let variable = comprehension.variable;
+        self.scoped([variable], |this| {
+            walk_comprehension_mut(this, comprehension)
+        });
+    }
+
+    fn visit_match_arm_mut(&mut self, arm: &mut MatchArm) {
+        let bound = match &arm.pattern {
+            Pattern::Binding(name) => vec![*name],
+            Pattern::Variant(pattern) => pattern.bindings.clone(),
+            Pattern::Wildcard | Pattern::Literal(_) => Vec::new(),
+        };
+        self.scoped(bound, |this| walk_match_arm_mut(this, arm));
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+        if let Some(folded) = self.fold_expression(expression) {
+            *expression = folded;
+        }
+    }
+}
+
+/// Whether `statement` declares a variable in the block it is in.
+fn declares(statement: &Statement) -> bool {
+    matches!(
+        statement,
+        Statement::Variable(_) | Statement::Destructure(_)
+    )
+}
+
+/// The value of a literal, counting a negated number as one; `none` is not
+/// folded.
+fn literal(expression: &Expression) -> Option<Literal> {
+    match expression {
+        Expression::Literal(Literal::None) => None,
+        Expression::Literal(literal) => Some(literal.clone()),
+        Expression::Unary(unary) if unary.operator == UnaryOperator::Neg => {
+            match literal(&unary.operand)? {
+                Literal::Integer(value) => Some(Literal::Integer(value.checked_neg()?)),
+                Literal::Float(value) => Some(Literal::Float(-value)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// An expression for `value` in place of one at `span`. Negative numbers
+/// are negated literals, as the parser reads them.
+fn literal_expression(value: Literal, span: &Span) -> Option<Expression> {
+    let negated = match value {
+        Literal::Integer(value) if value < 0 => Literal::Integer(value.checked_neg()?),
+        Literal::Float(value) if value.is_sign_negative() => Literal::Float(-value),
+        value => return Some(Expression::Literal(value)),
+    };
+    Some(Expression::Unary(UnaryOp {
+        operator: UnaryOperator::Neg,
+        operand: Box::new(Expression::Literal(negated)),
+        span: span.clone(),
+    }))
+}
+
+/// `value` as a value of `ty`, if it can be one without a conversion the
+/// generated code would have to make.
+fn typed(value: Literal, ty: &Type) -> Option<Literal> {
+    match (value, ty) {
+        (Literal::Integer(value), Type::Float) => Some(Literal::Float(value as f64)),
+        (value, ty) => (literal_type(&value) == *ty).then_some(value),
+    }
+}
+
+/// `left operator right`, or `None` for the operations not folded, such as
+/// ones mixing `int` and `float` or giving a float that is not finite.
+fn binary_value(
+    operator: &BinaryOperator,
+    left: &Literal,
+    right: &Literal,
+) -> Result<Option<Literal>, Failure> {
+    use BinaryOperator::*;
+    use Literal::{Boolean, Float, Integer};
+
+    let value = match (operator, left, right) {
+        (Add, Literal::String(_), _) | (Add, _, Literal::String(_)) => {
+            Literal::String(format!("{}{}", text(left), text(right)))
+        }
+        (Eq | Ne | Lt | Le | Gt | Ge, left, right) => {
+            let Some(ordering) = compare(left, right) else {
+                return Ok(None);
+            };
+            Boolean(match operator {
+                Eq => ordering == Ordering::Equal,
+                Ne => ordering != Ordering::Equal,
+                Lt => ordering == Ordering::Less,
+                Le => ordering != Ordering::Greater,
+                Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            })
+        }
+        (And, Boolean(left), Boolean(right)) => Boolean(*left && *right),
+        (Or, Boolean(left), Boolean(right)) => Boolean(*left || *right),
+        (Div, Integer(_), Integer(0)) => return Err(Failure::DivideByZero),
+        (Mod, Integer(_), Integer(0)) => return Err(Failure::RemainderByZero),
+        (Pow, Integer(_), Integer(right)) if *right < 0 => return Err(Failure::NegativeExponent),
+        (Pow, Integer(left), Integer(right)) => {
+            // Only 0, 1 and -1 have powers that fit for exponents past `u32`.
+            let value = match left {
+                0 => Some(if *right == 0 { 1 } else { 0 }),
+                1 => Some(1),
+                -1 => Some(if right % 2 == 0 { 1 } else { -1 }),
+                _ => u32::try_from(*right)
+                    .ok()
+                    .and_then(|right| left.checked_pow(right)),
+            };
+            Integer(value.ok_or(Failure::Overflow)?)
+        }
+        (Shl | Shr, Integer(left), Integer(right)) => {
+            let shift = u32::try_from(*right)
+                .ok()
+                .filter(|shift| *shift < i64::BITS)
+                .ok_or(Failure::Shift)?;
+            Integer(if *operator == Shl {
+                left << shift
+            } else {
+                left >> shift
+            })
+        }
+        (operator, Integer(left), Integer(right)) => {
+            let value = match operator {
+                Add => left.checked_add(*right),
+                Sub => left.checked_sub(*right),
+                Mul => left.checked_mul(*right),
+                Div => left.checked_div(*right),
+                Mod => left.checked_rem(*right),
+                BitAnd => Some(left & right),
+                BitOr => Some(left | right),
+                BitXor => Some(left ^ right),
+                _ => return Ok(None),
+            };
+            Integer(value.ok_or(Failure::Overflow)?)
+        }
+        (operator, Float(left), Float(right)) => {
+            let value = match operator {
+                Add => left + right,
+                Sub => left - right,
+                Mul => left * right,
+                Div => left / right,
+                Mod => left % right,
+                Pow => left.powf(*right),
+                _ => return Ok(None),
+            };
+            if !value.is_finite() {
+                return Ok(None);
+            }
+            Float(value)
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+fn unary_value(operator: &UnaryOperator, operand: &Literal) -> Result<Option<Literal>, Failure> {
+    let value = match (operator, operand) {
+        (UnaryOperator::Neg, Literal::Integer(value)) => {
+            Literal::Integer(value.checked_neg().ok_or(Failure::Overflow)?)
+        }
+        (UnaryOperator::Neg, Literal::Float(value)) => Literal::Float(-value),
+        (UnaryOperator::Not, Literal::Boolean(value)) => Literal::Boolean(!value),
+        (UnaryOperator::BitNot, Literal::Integer(value)) => Literal::Integer(!value),
+        _ => return Ok(None),
+    };
+    Ok(Some(value))
+}
+
+/// How two literals of the same type order, if they can be compared.
+fn compare(left: &Literal, right: &Literal) -> Option<Ordering> {
+    match (left, right) {
+        (Literal::Integer(left), Literal::Integer(right)) => Some(left.cmp(right)),
+        (Literal::Float(left), Literal::Float(right)) => left.partial_cmp(right),
+        (Literal::String(left), Literal::String(right)) => Some(left.cmp(right)),
+        (Literal::Boolean(left), Literal::Boolean(right)) => Some(left.cmp(right)),
+        _ => None,
+    }
+}
+
+/// A literal as string concatenation formats it.
+fn text(literal: &Literal) -> String {
+    match literal {
+        Literal::Integer(value) => value.to_string(),
+        Literal::Float(value) => value.to_string(),
+        Literal::String(value) => value.clone(),
+        Literal::Boolean(value) => value.to_string(),
+        Literal::None => "none".to_string(),
+    }
+}
@@ -0,0 +1,354 @@
+//! Module import graph and function call graph for `--report graph`, rendered
+//! as Graphviz DOT or JSON.
+
+use crate::compiler::ast::{Item, Program};
+use crate::compiler::builtins;
+use crate::compiler::semantic::CallGraph;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Default)]
+struct ModuleGraph {
+    imports: BTreeSet<String>,
+    functions: BTreeSet<String>,
+    calls: CallGraph,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProjectGraph {
+    modules: BTreeMap<String, ModuleGraph>,
+}
+
+#[derive(Serialize)]
+struct ModuleNode {
+    name: String,
+    builtin: bool,
+    imports: Vec<String>,
+    imported_by: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct FunctionNode {
+    name: String,
+    module: String,
+    calls: Vec<String>,
+    callers: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct GraphReport {
+    modules: Vec<ModuleNode>,
+    functions: Vec<FunctionNode>,
+    import_cycles: Vec<Vec<String>>,
+}
+
+impl ProjectGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the modules recorded in `other`, as when they were checked on
+    /// another thread.
+    pub fn merge(&mut self, other: ProjectGraph) {
+        self.modules.extend(other.modules);
+    }
+
+    /// Forgets a module, as when its file is deleted.
+    pub fn remove_module(&mut self, module: &str) {
+        self.modules.remove(module);
+    }
+
+    /// Records a checked module; `calls` is the analyzer's call graph for it.
+    pub fn add_module(&mut self, module: &str, program: &Program, calls: &CallGraph) {
+        let functions = program
+            .items
+            .iter()
+            .flat_map(|item| match item {
+                Item::Function(function) => vec![function.name.to_string()],
+                Item::Impl(implementation) => implementation
+                    .methods
+                    .iter()
+                    .map(|method| {
+                        format!("{}.{}", implementation.struct_name, method.function.name)
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+
+        self.modules.insert(
+            module.to_string(),
+            ModuleGraph {
+                imports: program
+                    .imports
+                    .iter()
+                    .map(|import| import.module_path.clone())
+                    .collect(),
+                functions,
+                calls: calls.clone(),
+            },
+        );
+    }
+
+    /// Every module seen, either compiled or imported.
+    fn module_names(&self) -> BTreeSet<String> {
+        let mut names: BTreeSet<String> = self.modules.keys().cloned().collect();
+        for graph in self.modules.values() {
+            names.extend(graph.imports.iter().cloned());
+        }
+        names
+    }
+
+    /// Qualified caller -> qualified callees across all modules.
+    fn call_edges(&self) -> BTreeMap<String, BTreeSet<String>> {
+        let mut edges = BTreeMap::new();
+        for (module, graph) in &self.modules {
+            for function in &graph.functions {
+                edges.insert(qualify(module, function), BTreeSet::new());
+            }
+            for (caller, callees) in &graph.calls {
+                edges
+                    .entry(qualify(module, caller))
+                    .or_insert_with(BTreeSet::new)
+                    .extend(callees.iter().map(|callee| {
+                        let imported = graph.imports.iter().any(|import| {
+                            callee
+                                .strip_prefix(import.as_str())
+                                .is_some_and(|rest| rest.starts_with('.'))
+                        });
+                        if imported {
+                            callee.clone()
+                        } else {
+                            qualify(module, callee)
+                        }
+                    }));
+            }
+        }
+        edges
+    }
+
+    /// Groups of modules that import each other, directly or transitively.
+    pub fn import_cycles(&self) -> Vec<Vec<String>> {
+        let names: Vec<String> = self.module_names().into_iter().collect();
+        let index: BTreeMap<&str, usize> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i))
+            .collect();
+        let edges: Vec<Vec<usize>> = names
+            .iter()
+            .map(|name| {
+                self.modules
+                    .get(name)
+                    .map(|graph| graph.imports.iter().map(|i| index[i.as_str()]).collect())
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        strongly_connected(&edges)
+            .into_iter()
+            .filter(|component| component.len() > 1 || edges[component[0]].contains(&component[0]))
+            .map(|component| {
+                let mut cycle: Vec<String> =
+                    component.into_iter().map(|i| names[i].clone()).collect();
+                cycle.sort();
+                cycle
+            })
+            .collect()
+    }
+
+    pub fn to_dot(&self) -> String {
+        let cycles: BTreeSet<String> = self.import_cycles().into_iter().flatten().collect();
+        let mut output = String::new();
+
+        writeln!(output, "digraph rustic {{").unwrap();
+        writeln!(output, "    rankdir=LR;").unwrap();
+        writeln!(output, "    node [shape=box];").unwrap();
+
+        writeln!(output, "    subgraph cluster_imports {{").unwrap();
+        writeln!(output, "        label=\"imports\";").unwrap();
+        for name in self.module_names() {
+            let mut attributes = vec![format!("label={}", quote(&name))];
+            if builtins::is_module(&name) {
+                attributes.push("style=dashed".to_string());
+            }
+            if cycles.contains(&name) {
+                attributes.push("color=red".to_string());
+            }
+            writeln!(
+                output,
+                "        {} [{}];",
+                quote(&format!("module:{}", name)),
+                attributes.join(", ")
+            )
+            .unwrap();
+        }
+        for (module, graph) in &self.modules {
+            for import in &graph.imports {
+                writeln!(
+                    output,
+                    "        {} -> {};",
+                    quote(&format!("module:{}", module)),
+                    quote(&format!("module:{}", import))
+                )
+                .unwrap();
+            }
+        }
+        writeln!(output, "    }}").unwrap();
+
+        let edges = self.call_edges();
+        let mut builtin_callees = BTreeSet::new();
+        writeln!(output, "    subgraph cluster_calls {{").unwrap();
+        writeln!(output, "        label=\"calls\";").unwrap();
+        for (caller, callees) in &edges {
+            writeln!(output, "        {};", quote(caller)).unwrap();
+            for callee in callees {
+                if !edges.contains_key(callee) {
+                    builtin_callees.insert(callee.clone());
+                }
+                writeln!(output, "        {} -> {};", quote(caller), quote(callee)).unwrap();
+            }
+        }
+        for callee in builtin_callees {
+            writeln!(output, "        {} [style=dashed];", quote(&callee)).unwrap();
+        }
+        writeln!(output, "    }}").unwrap();
+
+        writeln!(output, "}}").unwrap();
+        output
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut imported_by: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (module, graph) in &self.modules {
+            for import in &graph.imports {
+                imported_by
+                    .entry(import.clone())
+                    .or_default()
+                    .push(module.clone());
+            }
+        }
+
+        let modules = self
+            .module_names()
+            .into_iter()
+            .map(|name| ModuleNode {
+                builtin: builtins::is_module(&name),
+                imports: self
+                    .modules
+                    .get(&name)
+                    .map(|graph| graph.imports.iter().cloned().collect())
+                    .unwrap_or_default(),
+                imported_by: imported_by.remove(&name).unwrap_or_default(),
+                name,
+            })
+            .collect();
+
+        let edges = self.call_edges();
+        let mut callers: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for (caller, callees) in &edges {
+            for callee in callees {
+                callers.entry(callee).or_default().push(caller.clone());
+            }
+        }
+
+        let functions = edges
+            .iter()
+            .map(|(name, callees)| FunctionNode {
+                name: name.clone(),
+                module: name
+                    .rsplit_once('.')
+                    .map(|(module, _)| module.to_string())
+                    .unwrap_or_default(),
+                calls: callees.iter().cloned().collect(),
+                callers: callers.remove(name.as_str()).unwrap_or_default(),
+            })
+            .collect();
+
+        let report = GraphReport {
+            modules,
+            functions,
+            import_cycles: self.import_cycles(),
+        };
+        serde_json::to_string_pretty(&report).expect("graph report serializes")
+    }
+}
+
+/// Builtin callees are already `module.function`; local ones get the caller's module.
+fn qualify(module: &str, name: &str) -> String {
+    let builtin = name
+        .split_once('.')
+        .is_some_and(|(module, _)| builtins::is_module(module));
+    if builtin {
+        name.to_string()
+    } else {
+        format!("{}.{}", module, name)
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Tarjan's algorithm; components come out in reverse topological order.
+fn strongly_connected(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct State<'e> {
+        edges: &'e [Vec<usize>],
+        next: usize,
+        index: Vec<Option<usize>>,
+        lowlink: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        components: Vec<Vec<usize>>,
+    }
+
+    fn visit(state: &mut State, node: usize) {
+        state.index[node] = Some(state.next);
+        state.lowlink[node] = state.next;
+        state.next += 1;
+        state.stack.push(node);
+        state.on_stack[node] = true;
+
+        for &target in &state.edges[node] {
+            match state.index[target] {
+                None => {
+                    visit(state, target);
+                    state.lowlink[node] = state.lowlink[node].min(state.lowlink[target]);
+                }
+                Some(index) if state.on_stack[target] => {
+                    state.lowlink[node] = state.lowlink[node].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(state.lowlink[node]) == state.index[node] {
+            let mut component = Vec::new();
+            while let Some(member) = state.stack.pop() {
+                state.on_stack[member] = false;
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            state.components.push(component);
+        }
+    }
+
+    let mut state = State {
+        edges,
+        next: 0,
+        index: vec![None; edges.len()],
+        lowlink: vec![0; edges.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; edges.len()],
+        components: Vec::new(),
+    };
+    for node in 0..edges.len() {
+        if state.index[node].is_none() {
+            visit(&mut state, node);
+        }
+    }
+    state.components
+}
@@ -0,0 +1,196 @@
+//! Interned identifiers. A `Symbol` is a `u32` standing for a name, so names
+//! are copied and compared without touching their text. The interner is shared
+//! by every thread and lives as long as the process; names are never freed.
+//!
+//! There is no reset: `as_str` hands out `&'static str`, and nothing can tell
+//! when the last of those is gone. Instead each distinct name is stored once,
+//! so a long-lived process such as `watch`, the kernel or a program using the
+//! library holds one copy of every name it has seen, however many times it
+//! rebuilds. `Symbol::count` reports how many that is.
+//!
+//! Only interning takes a lock. A symbol's name sits in a slot that is written
+//! once, before the symbol is handed out, so reading it takes none.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Mutex, OnceLock};
+
+/// An interned name. Symbols are equal exactly when their names are; they
+/// order by name, so sorted output does not depend on interning order.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    len: u32,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+/// The names of all symbols, by number, in chunks that double in size: chunk
+/// `k` holds the `2^k` names numbered from `2^k - 1`. Chunks are never moved,
+/// so a name read from one stays valid while others are added.
+static NAMES: [OnceLock<Box<[OnceLock<&'static str>]>>; 32] = [const { OnceLock::new() }; 32];
+
+/// The slot for the name of symbol number `index`.
+fn slot(index: u32) -> &'static OnceLock<&'static str> {
+    let position = u64::from(index) + 1;
+    let chunk = position.ilog2();
+    let slots = NAMES[chunk as usize]
+        .get_or_init(|| (0..1usize << chunk).map(|_| OnceLock::new()).collect());
+    &slots[(position - (1 << chunk)) as usize]
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        let mut interner = interner().lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(&symbol) = interner.symbols.get(name) {
+            return symbol;
+        }
+        let index = interner.len;
+        interner.len = index.checked_add(1).expect("fewer than 2^32 names");
+        let symbol = Symbol(index);
+        let name: &'static str = Box::leak(name.into());
+        slot(index).get_or_init(|| name);
+        interner.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        slot(self.0)
+            .get()
+            .expect("symbols are interned before they are used")
+    }
+
+    /// The number of names interned so far in this process.
+    pub fn count() -> usize {
+        interner().lock().unwrap_or_else(|e| e.into_inner()).len as usize
+    }
+
+    /// The symbol's number, unique within this process.
+    pub fn as_u32(self) -> u32 {
+        self.0
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            Ordering::Equal
+        } else {
+            self.as_str().cmp(other.as_str())
+        }
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::intern(&name)
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(symbol: Symbol) -> Self {
+        symbol.as_str().to_string()
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Symbol {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<Symbol> for str {
+    fn eq(&self, other: &Symbol) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for &str {
+    fn eq(&self, other: &Symbol) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for String {
+    fn eq(&self, other: &Symbol) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Ok(Symbol::intern(&name))
+    }
+}
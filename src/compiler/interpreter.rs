@@ -0,0 +1,1571 @@
+//! A tree-walking interpreter for checked programs, which runs `main` without
+//! generating or building any Rust. It follows what the generated code does:
+//! values are copied rather than shared, integers wrap, and errors and
+//! exceptions unwind to the nearest `try` that catches them. Programs run as a
+//! single module, using only the built-in modules.
+
+use crate::compiler::ast::*;
+use crate::compiler::builtins;
+use crate::compiler::codegen::is_display;
+use crate::compiler::intern::Symbol;
+use crate::compiler::semantic::{literal_type, narrowed_variable, qualifiers, TypeTable};
+use crate::diagnostics::{Error, Result};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::sync::Arc;
+
+/// Most calls that may be in progress at once, so runaway recursion is
+/// reported rather than overflowing the interpreter's own stack.
+pub const MAX_CALL_DEPTH: usize = 1_000;
+
+/// Each interpreted call takes several frames of the interpreter's own, and
+/// debug builds make them large, so the main thread's stack is not enough.
+pub const STACK_SIZE: usize = 256 << 20;
+
+/// The runtime error of a program stopped through its interrupt flag.
+pub const INTERRUPTED: &str = "interrupted";
+
+/// Runs `f`, which interprets a program, on a thread with a stack large
+/// enough for `MAX_CALL_DEPTH` nested calls.
+pub fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .name("interpreter".to_string())
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .expect("failed to start the interpreter thread")
+            .join()
+            .unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+    })
+}
+
+#[derive(Clone, Default)]
+enum Value<'a> {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+    List(Vec<Value<'a>>),
+    Map(BTreeMap<Value<'a>, Value<'a>>),
+    Tuple(Vec<Value<'a>>),
+    /// A struct by name, with its fields in declaration order.
+    Struct(Symbol, Vec<(Symbol, Value<'a>)>),
+    /// An enum variant by enum and variant name, with its payload.
+    Variant(Symbol, Symbol, Vec<Value<'a>>),
+    Optional(Option<Box<Value<'a>>>),
+    Function(Rc<Callable<'a>>),
+    #[default]
+    Void,
+}
+
+enum Callable<'a> {
+    Function(&'a Function),
+    /// A lambda with copies of the variables it uses, made when it was
+    /// created.
+    Lambda(&'a Lambda, Vec<(Symbol, Value<'a>)>),
+}
+
+/// Why evaluation stopped before reaching the end of a block.
+enum Exit<'a> {
+    Return(Value<'a>),
+    Break,
+    Continue,
+    /// A thrown exception.
+    Throw(Value<'a>),
+    /// A runtime error, such as an index out of bounds, which only
+    /// `catch Error` catches.
+    Error(String),
+    /// Stopped through the interrupt flag, which nothing catches.
+    Interrupted,
+}
+
+type Flow<'a, T> = std::result::Result<T, Exit<'a>>;
+
+/// Where a variable, or a part of one, is stored.
+struct Place<'a> {
+    root: Root,
+    steps: Vec<Step<'a>>,
+}
+
+enum Root {
+    /// An index into the interpreter's locals.
+    Local(usize),
+    Global(Symbol),
+}
+
+enum Step<'a> {
+    Field(Symbol),
+    Index(Value<'a>),
+}
+
+pub struct Interpreter<'a> {
+    types: &'a TypeTable,
+    program: &'a Program,
+    functions: HashMap<Symbol, &'a Function>,
+    methods: HashMap<(Symbol, Symbol), &'a Method>,
+    /// Fields of each struct in declaration order, with their types.
+    structs: HashMap<Symbol, Vec<(Symbol, &'a Type)>>,
+    /// Payload types of each enum variant.
+    variants: HashMap<(Symbol, Symbol), &'a [Type]>,
+    /// Initializers of globals, with their types, evaluated when first used.
+    globals: HashMap<Symbol, (&'a Expression, Type)>,
+    global_values: HashMap<Symbol, Value<'a>>,
+    /// Globals being initialized, to report one that depends on itself.
+    initializing: HashSet<Symbol>,
+    /// Names the program qualifies items of built-in modules with.
+    qualifiers: HashMap<Symbol, String>,
+    /// The variables of every call in progress, innermost last.
+    locals: Vec<(Symbol, Value<'a>)>,
+    /// Where the variables of the innermost call start.
+    frame: usize,
+    depth: usize,
+    return_type: Type,
+    output: Box<dyn Write + 'a>,
+    input: Box<dyn BufRead + 'a>,
+    args: Vec<String>,
+    /// Set from another thread to stop the program.
+    interrupt: Option<Arc<AtomicBool>>,
+}
+
+/// What running part of `main` left for the part after it: the variables of
+/// `main` and the globals initialized so far. The kernel keeps one from cell
+/// to cell, so each cell runs only its own statements.
+#[derive(Default)]
+pub struct State<'a> {
+    locals: Vec<(Symbol, Value<'a>)>,
+    globals: HashMap<Symbol, Value<'a>>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// An interpreter for `program`, which has been checked into `types`.
+    pub fn new(program: &'a Program, types: &'a TypeTable) -> Self {
+        let mut interpreter = Self {
+            types,
+            program,
+            functions: HashMap::new(),
+            methods: HashMap::new(),
+            structs: HashMap::new(),
+            variants: HashMap::new(),
+            globals: HashMap::new(),
+            global_values: HashMap::new(),
+            initializing: HashSet::new(),
+            qualifiers: qualifiers(program, &HashMap::new()),
+            locals: Vec::new(),
+            frame: 0,
+            depth: 0,
+            return_type: Type::Void,
+            output: Box::new(io::stdout()),
+            input: Box::new(io::stdin().lock()),
+            args: Vec::new(),
+            interrupt: None,
+        };
+        interpreter.collect_items();
+        interpreter
+    }
+
+    /// Sends what the program prints to `output` rather than stdout.
+    pub fn set_output(&mut self, output: impl Write + 'a) {
+        self.output = Box::new(output);
+    }
+
+    /// Reads what `io.input` returns from `input` rather than stdin.
+    pub fn set_input(&mut self, input: impl BufRead + 'a) {
+        self.input = Box::new(input);
+    }
+
+    /// The arguments `io.args` returns.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    /// Stops the program, with a runtime error, at the first statement or
+    /// loop iteration after `interrupt` is set.
+    pub fn set_interrupt(&mut self, interrupt: Arc<AtomicBool>) {
+        self.interrupt = Some(interrupt);
+    }
+
+    /// Runs the program's `main`. An error or exception nothing catches
+    /// stops the program with a runtime error.
+    pub fn run(&mut self) -> Result<()> {
+        let main = self.main()?;
+        let result = self.invoke(main, None, Vec::new());
+        let _ = self.output.flush();
+        finish(result.map(|_| ()))
+    }
+
+    /// Runs the statements of `main` from the `start`th on, with the
+    /// variables and globals of `state` as the statements before them left
+    /// them, and returns the state they leave in turn. When they fail, the
+    /// variables they declared are dropped from it, but what they changed in
+    /// the earlier ones is kept.
+    pub fn resume(&mut self, start: usize, state: State<'a>) -> (Result<()>, State<'a>) {
+        let State { locals, globals } = state;
+        self.global_values = globals;
+        let main = match self.main() {
+            Ok(main) => main,
+            Err(error) => {
+                let globals = std::mem::take(&mut self.global_values);
+                return (Err(error), State { locals, globals });
+            }
+        };
+
+        let declared = locals.len();
+        let frame = std::mem::replace(&mut self.frame, self.locals.len());
+        self.depth += 1;
+        self.locals.extend(locals);
+        let return_type = std::mem::replace(&mut self.return_type, main.return_type.clone());
+        let statements = main.body.statements.get(start..).unwrap_or_default();
+        let result = self.execute_statements(statements);
+        self.return_type = return_type;
+        let _ = self.output.flush();
+
+        let mut locals = self.locals.split_off(self.frame);
+        self.leave(frame);
+        let result = finish(result);
+        if result.is_err() {
+            locals.truncate(declared);
+        }
+        let globals = std::mem::take(&mut self.global_values);
+        (result, State { locals, globals })
+    }
+
+    fn main(&self) -> Result<&'a Function> {
+        self.functions
+            .get(&Symbol::intern("main"))
+            .copied()
+            .ok_or_else(|| {
+                Error::RuntimeError("the program has no `main` function to run".to_string())
+            })
+    }
+
+    fn collect_items(&mut self) {
+        let program = self.program;
+        for item in &program.items {
+            match item {
+                Item::Function(function) => {
+                    self.functions.insert(function.name, function);
+                }
+                Item::Struct(structure) => {
+                    let fields = structure
+                        .fields
+                        .iter()
+                        .map(|field| (field.name, &field.field_type))
+                        .collect();
+                    self.structs.insert(structure.name, fields);
+                }
+                Item::Enum(enumeration) => {
+                    for variant in &enumeration.variants {
+                        let key = (enumeration.name, variant.name);
+                        self.variants.insert(key, &variant.payload);
+                    }
+                }
+                Item::Impl(implementation) => {
+                    for method in &implementation.methods {
+                        let key = (implementation.struct_name, method.function.name);
+                        self.methods.insert(key, method);
+                    }
+                }
+                Item::Variable(variable) => {
+                    let ty = self.variable_type(variable);
+                    if let Some(initializer) = &variable.initializer {
+                        self.globals.insert(variable.name, (initializer, ty));
+                    }
+                }
+                Item::Constant(constant) => {
+                    let value = (&constant.value, constant.const_type.clone());
+                    self.globals.insert(constant.name, value);
+                }
+            }
+        }
+    }
+
+    /// Calls `function` with its arguments in the order of its parameters,
+    /// returning its result and, for a method, its receiver afterwards.
+    fn invoke(
+        &mut self,
+        function: &'a Function,
+        receiver: Option<Value<'a>>,
+        arguments: Vec<Value<'a>>,
+    ) -> Flow<'a, (Value<'a>, Option<Value<'a>>)> {
+        let has_receiver = receiver.is_some();
+        let frame = self.enter()?;
+        if let Some(receiver) = receiver {
+            self.locals.push((Symbol::intern("self"), receiver));
+        }
+        let parameters = function.parameters.iter().map(|parameter| parameter.name);
+        self.locals.extend(parameters.zip(arguments));
+
+        let return_type = std::mem::replace(&mut self.return_type, function.return_type.clone());
+        let result = self.execute_statements(&function.body.statements);
+        self.return_type = return_type;
+        let receiver = has_receiver.then(|| std::mem::take(&mut self.locals[self.frame].1));
+        self.leave(frame);
+        match result {
+            Ok(()) => Ok((Value::Void, receiver)),
+            Err(Exit::Return(value)) => Ok((value, receiver)),
+            Err(exit) => Err(exit),
+        }
+    }
+
+    fn call_value(
+        &mut self,
+        callee: &Callable<'a>,
+        arguments: Vec<Value<'a>>,
+    ) -> Flow<'a, Value<'a>> {
+        let (lambda, captured) = match callee {
+            Callable::Function(function) => return Ok(self.invoke(function, None, arguments)?.0),
+            Callable::Lambda(lambda, captured) => (*lambda, captured),
+        };
+        let Some(Type::Function(_, return_type)) = self.types.get(&lambda.span) else {
+            return Err(Exit::Error("missing type for lambda".to_string()));
+        };
+        let return_type = (**return_type).clone();
+        let frame = self.enter()?;
+        self.locals.extend(captured.iter().cloned());
+        let parameters = lambda.parameters.iter().map(|parameter| parameter.name);
+        self.locals.extend(parameters.zip(arguments));
+
+        let result = match lambda.body.as_ref() {
+            LambdaBody::Block(_, block) => {
+                let outer = std::mem::replace(&mut self.return_type, return_type);
+                let result = match self.execute_statements(&block.statements) {
+                    Ok(()) => Ok(Value::Void),
+                    Err(Exit::Return(value)) => Ok(value),
+                    Err(exit) => Err(exit),
+                };
+                self.return_type = outer;
+                result
+            }
+            LambdaBody::Expression(body) => self.evaluate(body).map(|value| match return_type {
+                Type::Void => Value::Void,
+                ref ty => self.coerce(ty, body, value),
+            }),
+        };
+        self.leave(frame);
+        result
+    }
+
+    /// Starts the variables of a new call, returning where they start.
+    fn enter(&mut self) -> Flow<'a, usize> {
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(Exit::Error(format!(
+                "stack overflow: more than {} calls in progress",
+                MAX_CALL_DEPTH
+            )));
+        }
+        self.depth += 1;
+        Ok(std::mem::replace(&mut self.frame, self.locals.len()))
+    }
+
+    fn leave(&mut self, frame: usize) {
+        self.locals.truncate(self.frame);
+        self.frame = frame;
+        self.depth -= 1;
+    }
+
+    fn execute_block(&mut self, block: &'a Block) -> Flow<'a, ()> {
+        self.execute_scoped(&block.statements, Vec::new())
+    }
+
+    /// Runs `statements` in a scope of their own, which starts with `bound`.
+    fn execute_scoped(
+        &mut self,
+        statements: &'a [Statement],
+        bound: Vec<(Symbol, Value<'a>)>,
+    ) -> Flow<'a, ()> {
+        let scope = self.locals.len();
+        self.locals.extend(bound);
+        let result = self.execute_statements(statements);
+        self.locals.truncate(scope);
+        result
+    }
+
+    fn execute_statements(&mut self, statements: &'a [Statement]) -> Flow<'a, ()> {
+        for statement in statements {
+            self.execute(statement)?;
+        }
+        Ok(())
+    }
+
+    /// Stops with `Exit::Interrupted` once the interrupt flag is set.
+    fn check_interrupt(&self) -> Flow<'a, ()> {
+        match &self.interrupt {
+            Some(interrupt) if interrupt.load(AtomicOrdering::Relaxed) => Err(Exit::Interrupted),
+            _ => Ok(()),
+        }
+    }
+
+    fn execute(&mut self, statement: &'a Statement) -> Flow<'a, ()> {
+        self.check_interrupt()?;
+        match statement {
+            Statement::Expression(expression) => {
+                self.evaluate(expression)?;
+            }
+            Statement::Variable(variable) => {
+                // Until it is assigned, which semantic analysis checked
+                // happens before it is used.
+                let mut value = Value::Void;
+                if let Some(initializer) = &variable.initializer {
+                    let ty = self.variable_type(variable);
+                    value = self.evaluate(initializer)?;
+                    value = self.coerce(&ty, initializer, value);
+                }
+                self.locals.push((variable.name, value));
+            }
+            Statement::Destructure(destructure) => {
+                let Value::Tuple(values) = self.evaluate(&destructure.initializer)? else {
+                    return Err(Exit::Error("only tuples can be unpacked".to_string()));
+                };
+                for (identifier, value) in destructure.names.iter().zip(values) {
+                    if identifier.name != "_" {
+                        self.locals.push((identifier.name, value));
+                    }
+                }
+            }
+            Statement::Assignment(assignment) => {
+                let mut value = self.evaluate(&assignment.value)?;
+                if let Some(operator) = &assignment.operator {
+                    let current = self.evaluate(&assignment.target)?;
+                    value = apply(operator, current, value)?;
+                }
+                if let Some(target) = self.type_of(&assignment.target) {
+                    value = self.coerce(&target, &assignment.value, value);
+                }
+                let Some(place) = self.place(&assignment.target)? else {
+                    return Err(Exit::Error("cannot assign to this expression".to_string()));
+                };
+                *self.place_mut(&place, true)? = value;
+            }
+            Statement::If(if_statement) => {
+                let branches = std::iter::once((&if_statement.condition, &if_statement.then_block))
+                    .chain(
+                        if_statement
+                            .else_ifs
+                            .iter()
+                            .map(|(condition, block)| (condition, block)),
+                    );
+                for (condition, block) in branches {
+                    if let Some(bound) = self.condition(condition)? {
+                        return self.execute_scoped(&block.statements, bound);
+                    }
+                }
+                if let Some(else_block) = &if_statement.else_block {
+                    self.execute_block(else_block)?;
+                }
+            }
+            Statement::For(for_loop) => {
+                let values = self.iterate(&for_loop.iterable)?;
+                for value in values {
+                    self.check_interrupt()?;
+                    let bound = vec![(for_loop.variable, value?)];
+                    match self.execute_scoped(&for_loop.body.statements, bound) {
+                        Ok(()) | Err(Exit::Continue) => {}
+                        Err(Exit::Break) => break,
+                        Err(exit) => return Err(exit),
+                    }
+                }
+            }
+            Statement::While(while_loop) => {
+                while self.evaluate(&while_loop.condition)?.is_true() {
+                    self.check_interrupt()?;
+                    match self.execute_block(&while_loop.body) {
+                        Ok(()) | Err(Exit::Continue) => {}
+                        Err(Exit::Break) => break,
+                        Err(exit) => return Err(exit),
+                    }
+                }
+            }
+            Statement::Try(try_statement) => {
+                let result = match self.execute_block(&try_statement.try_block) {
+                    Err(exit @ (Exit::Throw(_) | Exit::Error(_))) => {
+                        self.catch(&try_statement.catch_clauses, exit)
+                    }
+                    result => result,
+                };
+                // Leaving the `finally` block early overrides how the rest
+                // finished.
+                if let Some(finally_block) = &try_statement.finally_block {
+                    self.execute_block(finally_block)?;
+                }
+                return result;
+            }
+            Statement::Throw(throw_statement) => {
+                return Err(Exit::Throw(self.evaluate(&throw_statement.value)?));
+            }
+            Statement::Return(return_statement) => {
+                let value = match &return_statement.value {
+                    Some(value) => {
+                        let returned = self.evaluate(value)?;
+                        let return_type = self.return_type.clone();
+                        self.coerce(&return_type, value, returned)
+                    }
+                    None => Value::Void,
+                };
+                return Err(Exit::Return(value));
+            }
+            Statement::Break(_) => return Err(Exit::Break),
+            Statement::Continue(_) => return Err(Exit::Continue),
+        }
+        Ok(())
+    }
+
+    /// Runs the first clause that catches the error `exit` is, or keeps
+    /// unwinding when none does.
+    fn catch(&mut self, clauses: &'a [CatchClause], exit: Exit<'a>) -> Flow<'a, ()> {
+        for clause in clauses {
+            let caught = match (&exit, clause.exception_type.as_str()) {
+                (Exit::Throw(exception), "Error") => Value::Str(format!("{:?}", exception)),
+                (Exit::Error(message), "Error") => Value::Str(message.clone()),
+                (Exit::Throw(exception @ Value::Struct(name, _)), expected)
+                    if *name == expected =>
+                {
+                    exception.clone()
+                }
+                _ => continue,
+            };
+            let bound = clause.binding.map(|binding| (binding, caught));
+            return self.execute_scoped(
+                &clause.handler_block.statements,
+                bound.into_iter().collect(),
+            );
+        }
+        Err(exit)
+    }
+
+    /// Whether an `if` condition holds, with the variable a `x != none`
+    /// condition unwraps for its block.
+    fn condition(
+        &mut self,
+        condition: &'a Expression,
+    ) -> Flow<'a, Option<Vec<(Symbol, Value<'a>)>>> {
+        if let Some(identifier) = narrowed_variable(condition) {
+            if let Some(Type::Optional(_)) = self.types.get(&identifier.span) {
+                let place = self.variable(identifier.name)?;
+                let value = match place {
+                    Some(place) => self.place_ref(&place)?.clone(),
+                    None => Value::Void,
+                };
+                return Ok(match value {
+                    Value::Optional(Some(value)) => Some(vec![(identifier.name, *value)]),
+                    _ => None,
+                });
+            }
+        }
+        Ok(self.evaluate(condition)?.is_true().then(Vec::new))
+    }
+
+    /// The values a `for` loop or comprehension goes through. A range yields
+    /// its values one at a time rather than being made into a list.
+    fn iterate(
+        &mut self,
+        iterable: &'a Expression,
+    ) -> Flow<'a, Box<dyn Iterator<Item = Flow<'a, Value<'a>>> + 'a>> {
+        if let Expression::Range(range) = iterable {
+            let start = self.evaluate(&range.start)?.as_int()?;
+            let end = self.evaluate(&range.end)?.as_int()?;
+            return Ok(match range.inclusive {
+                true => Box::new((start..=end).map(|value| Ok(Value::Int(value)))),
+                false => Box::new((start..end).map(|value| Ok(Value::Int(value)))),
+            });
+        }
+        match self.evaluate(iterable)? {
+            Value::List(values) => Ok(Box::new(values.into_iter().map(Ok))),
+            _ => Err(Exit::Error(
+                "only lists and ranges can be iterated over".to_string(),
+            )),
+        }
+    }
+
+    fn evaluate(&mut self, expression: &'a Expression) -> Flow<'a, Value<'a>> {
+        match expression {
+            Expression::Literal(literal) => Ok(literal_value(literal)),
+            Expression::Identifier(identifier) => {
+                if let Some(place) = self.place(expression)? {
+                    return Ok(self.place_ref(&place)?.clone());
+                }
+                match self.functions.get(&identifier.name) {
+                    Some(function) => Ok(Value::Function(Rc::new(Callable::Function(function)))),
+                    None => Err(Exit::Error(format!(
+                        "undefined variable `{}`",
+                        identifier.name
+                    ))),
+                }
+            }
+            Expression::Binary(binary) => match binary.operator {
+                BinaryOperator::Coalesce => match self.evaluate(&binary.left)? {
+                    Value::Optional(Some(value)) => Ok(match self.type_of(&binary.right) {
+                        Some(Type::Optional(_)) => Value::Optional(Some(value)),
+                        _ => *value,
+                    }),
+                    _ => self.evaluate(&binary.right),
+                },
+                BinaryOperator::And | BinaryOperator::Or => {
+                    let left = self.evaluate(&binary.left)?.is_true();
+                    if left == (binary.operator == BinaryOperator::Or) {
+                        return Ok(Value::Bool(left));
+                    }
+                    self.evaluate(&binary.right)
+                }
+                ref operator => {
+                    let left = self.evaluate(&binary.left)?;
+                    let right = self.evaluate(&binary.right)?;
+                    apply(operator, left, right)
+                }
+            },
+            Expression::Unary(unary) => match (&unary.operator, self.evaluate(&unary.operand)?) {
+                (UnaryOperator::Neg, Value::Int(value)) => Ok(Value::Int(value.wrapping_neg())),
+                (UnaryOperator::Neg, Value::Float(value)) => Ok(Value::Float(-value)),
+                (UnaryOperator::Not, Value::Bool(value)) => Ok(Value::Bool(!value)),
+                (UnaryOperator::BitNot, Value::Int(value)) => Ok(Value::Int(!value)),
+                (operator, value) => Err(Exit::Error(format!(
+                    "cannot apply `{}` to {:?}",
+                    operator, value
+                ))),
+            },
+            Expression::Call(call) => self.call(call),
+            Expression::MemberAccess(access) => {
+                if let Some(module) = self.module_name(&access.object) {
+                    return match (module.as_str(), access.member.as_str()) {
+                        ("math", "pi") => Ok(Value::Float(std::f64::consts::PI)),
+                        ("math", "e") => Ok(Value::Float(std::f64::consts::E)),
+                        (module, member) => Err(Exit::Error(format!(
+                            "unknown member `{}.{}`",
+                            module, member
+                        ))),
+                    };
+                }
+                if let Some((enum_name, variant)) = self.variant(access) {
+                    return Ok(Value::Variant(enum_name, variant, Vec::new()));
+                }
+                if let Some(place) = self.place(expression)? {
+                    return Ok(self.place_ref(&place)?.clone());
+                }
+                let object = self.evaluate(&access.object)?;
+                field(&object, access.member).cloned()
+            }
+            Expression::List(list) => {
+                let elements = list.elements.iter().map(|element| self.evaluate(element));
+                Ok(Value::List(elements.collect::<Flow<_>>()?))
+            }
+            Expression::Comprehension(comprehension) => {
+                let mut elements = Vec::new();
+                for value in self.iterate(&comprehension.iterable)? {
+                    let scope = self.locals.len();
+                    self.locals.push((comprehension.variable, value?));
+                    let included = match &comprehension.condition {
+                        Some(condition) => self.evaluate(condition).map(|value| value.is_true()),
+                        None => Ok(true),
+                    };
+                    let element = match included {
+                        Ok(true) => self.evaluate(&comprehension.element).map(Some),
+                        Ok(false) => Ok(None),
+                        Err(exit) => Err(exit),
+                    };
+                    self.locals.truncate(scope);
+                    elements.extend(element?);
+                }
+                Ok(Value::List(elements))
+            }
+            Expression::Cast(cast) => {
+                let value = self.evaluate(&cast.value)?;
+                Ok(match (&cast.target, value) {
+                    (Type::Str, value) => Value::Str(value.to_string()),
+                    (Type::Int, Value::Float(value)) => Value::Int(value as i64),
+                    (Type::Float, Value::Int(value)) => Value::Float(value as f64),
+                    (_, value) => value,
+                })
+            }
+            Expression::If(if_expression) => {
+                let value = match self.condition(&if_expression.condition)? {
+                    Some(bound) => {
+                        let scope = self.locals.len();
+                        self.locals.extend(bound);
+                        let value = self.evaluate(&if_expression.then_value);
+                        self.locals.truncate(scope);
+                        (value?, &if_expression.then_value)
+                    }
+                    None => (
+                        self.evaluate(&if_expression.else_value)?,
+                        &if_expression.else_value,
+                    ),
+                };
+                Ok(match self.type_of(expression) {
+                    Some(ty) => self.coerce(&ty, value.1, value.0),
+                    None => value.0,
+                })
+            }
+            Expression::Tuple(tuple) => {
+                let elements = tuple.elements.iter().map(|element| self.evaluate(element));
+                Ok(Value::Tuple(elements.collect::<Flow<_>>()?))
+            }
+            Expression::Map(map) => {
+                let mut entries = BTreeMap::new();
+                for (key, value) in &map.entries {
+                    let key = self.evaluate(key)?;
+                    let value = self.evaluate(value)?;
+                    entries.insert(key, value);
+                }
+                Ok(Value::Map(entries))
+            }
+            Expression::Index(index) => {
+                if let Some(place) = self.place(expression)? {
+                    return Ok(self.place_ref(&place)?.clone());
+                }
+                let object = self.evaluate(&index.object)?;
+                let key = self.evaluate(&index.index)?;
+                element(&object, &key).cloned()
+            }
+            Expression::Slice(slice) => {
+                let start = match &slice.start {
+                    Some(start) => self.evaluate(start)?.as_int()?,
+                    None => 0,
+                };
+                let end = match &slice.end {
+                    Some(end) => self.evaluate(end)?.as_int()?,
+                    None => i64::MAX,
+                };
+                let object = self.evaluate(&slice.object)?;
+                let Value::List(list) = object else {
+                    return Err(Exit::Error("only lists can be sliced".to_string()));
+                };
+                // Like Python, bounds past either end are clamped rather than
+                // reported.
+                let len = list.len() as i64;
+                let bound =
+                    |index: i64| (if index < 0 { index + len } else { index }).clamp(0, len);
+                let (start, end) = (bound(start) as usize, bound(end) as usize);
+                Ok(Value::List(list[start..end.max(start)].to_vec()))
+            }
+            Expression::StructInit(init) => {
+                let Some(declared) = self.structs.get(&init.struct_name).cloned() else {
+                    return Err(Exit::Error(format!(
+                        "unknown struct `{}`",
+                        init.struct_name
+                    )));
+                };
+                let mut fields = Vec::new();
+                for (name, ty) in declared {
+                    let Some(value) = init.fields.get(&name) else {
+                        continue;
+                    };
+                    let evaluated = self.evaluate(value)?;
+                    fields.push((name, self.coerce(ty, value, evaluated)));
+                }
+                Ok(Value::Struct(init.struct_name, fields))
+            }
+            Expression::Match(match_expression) => {
+                let scrutinee = self.evaluate(&match_expression.scrutinee)?;
+                for arm in &match_expression.arms {
+                    let Some(bound) = matches(&arm.pattern, &scrutinee) else {
+                        continue;
+                    };
+                    let scope = self.locals.len();
+                    self.locals.extend(bound);
+                    let value = self.evaluate(&arm.body);
+                    self.locals.truncate(scope);
+                    return value;
+                }
+                Err(Exit::Error(format!(
+                    "no arm of the `match` matches {:?}",
+                    scrutinee
+                )))
+            }
+            Expression::Lambda(lambda) => {
+                let mut uses = Uses::default();
+                uses.visit_lambda(lambda);
+                let mut captured: Vec<(Symbol, Value<'a>)> = Vec::new();
+                for name in uses.0 {
+                    let parameter = lambda.parameters.iter().any(|p| p.name == name);
+                    if parameter || captured.iter().any(|(captured, _)| *captured == name) {
+                        continue;
+                    }
+                    if let Some(index) = self.local(name) {
+                        captured.push((name, self.locals[index].1.clone()));
+                    }
+                }
+                Ok(Value::Function(Rc::new(Callable::Lambda(lambda, captured))))
+            }
+            Expression::Range(_) => Err(Exit::Error(
+                "ranges can only be used in `for` loops".to_string(),
+            )),
+            Expression::Interpolation(interpolation) => {
+                let mut text = String::new();
+                for part in &interpolation.parts {
+                    match part {
+                        InterpolationPart::Text(part) => text.push_str(part),
+                        InterpolationPart::Expression(expression) => {
+                            let value = self.evaluate(expression)?;
+                            text.push_str(&self.show(expression, &value));
+                        }
+                    }
+                }
+                Ok(Value::Str(text))
+            }
+        }
+    }
+
+    fn call(&mut self, call: &'a FunctionCall) -> Flow<'a, Value<'a>> {
+        let mut expressions = Vec::new();
+        let mut arguments = Vec::new();
+        for (argument, is_default) in self.call_arguments(call) {
+            expressions.push(argument);
+            arguments.push(match is_default {
+                true => self.evaluate_default(argument)?,
+                false => self.evaluate(argument)?,
+            });
+        }
+
+        match call.function.as_ref() {
+            Expression::Identifier(identifier) if self.local(identifier.name).is_none() => {
+                let Some(function) = self.functions.get(&identifier.name).copied() else {
+                    return Err(Exit::Error(format!(
+                        "undefined function `{}`",
+                        identifier.name
+                    )));
+                };
+                let parameters = function.parameters.iter().map(|p| &p.param_type);
+                let arguments = self.coerce_all(parameters, expressions.iter().copied(), arguments);
+                Ok(self.invoke(function, None, arguments)?.0)
+            }
+            Expression::MemberAccess(access) => {
+                if let Some(module) = self.module_name(&access.object) {
+                    return self.call_builtin(&module, access.member, &call.arguments, arguments);
+                }
+                if let Some(key) = self.variant(access) {
+                    let payload = self.variants.get(&key).copied().unwrap_or_default();
+                    let arguments = self.coerce_all(payload.iter(), &call.arguments, arguments);
+                    return Ok(Value::Variant(key.0, key.1, arguments));
+                }
+                let method = match self.type_of(&access.object) {
+                    Some(Type::Struct(name) | Type::Generic(name, _)) => {
+                        self.methods.get(&(name, access.member)).copied()
+                    }
+                    _ => None,
+                };
+                if let Some(method) = method {
+                    let parameters = method.function.parameters.iter().map(|p| &p.param_type);
+                    let arguments =
+                        self.coerce_all(parameters, expressions.iter().copied(), arguments);
+                    return self.call_method(method, &access.object, arguments);
+                }
+                self.call_builtin_method(access, &call.arguments, arguments)
+            }
+            callee => {
+                let parameters = match self.type_of(callee) {
+                    Some(Type::Function(parameters, _)) => parameters,
+                    _ => return Err(Exit::Error("expression is not callable".to_string())),
+                };
+                let arguments = self.coerce_all(parameters.iter(), &call.arguments, arguments);
+                match self.evaluate(callee)? {
+                    Value::Function(function) => self.call_value(&function, arguments),
+                    _ => Err(Exit::Error("expression is not callable".to_string())),
+                }
+            }
+        }
+    }
+
+    /// The arguments of `call` in the order of the parameters of the function
+    /// or method it calls, with named arguments moved into place and the
+    /// defaults of parameters left out filled in, each with whether it is a
+    /// default.
+    fn call_arguments(&self, call: &'a FunctionCall) -> Vec<(&'a Expression, bool)> {
+        let parameters = match call.function.as_ref() {
+            Expression::Identifier(identifier) if self.local(identifier.name).is_none() => {
+                self.functions.get(&identifier.name).map(|f| &f.parameters)
+            }
+            Expression::MemberAccess(access) => match self.type_of(&access.object) {
+                Some(Type::Struct(name) | Type::Generic(name, _)) => self
+                    .methods
+                    .get(&(name, access.member))
+                    .map(|method| &method.function.parameters),
+                _ => None,
+            },
+            _ => None,
+        };
+        let mut arguments: Vec<_> = call
+            .arguments
+            .iter()
+            .map(|argument| (argument, false))
+            .collect();
+        for parameter in parameters.into_iter().flatten().skip(call.arguments.len()) {
+            let named = call
+                .named_arguments
+                .iter()
+                .find(|argument| argument.name == parameter.name);
+            match named {
+                Some(argument) => arguments.push((&argument.value, false)),
+                None => arguments.extend(parameter.default_value.as_ref().zip(Some(true))),
+            }
+        }
+        arguments
+    }
+
+    /// The default value of a parameter a call leaves out, evaluated where
+    /// its function is declared: it sees the program's globals but none of
+    /// the caller's variables.
+    fn evaluate_default(&mut self, default: &'a Expression) -> Flow<'a, Value<'a>> {
+        let frame = self.enter()?;
+        let value = self.evaluate(default);
+        self.leave(frame);
+        value
+    }
+
+    /// Calls a method declared in an `impl` block. A receiver that is a
+    /// variable is moved into the call and back, so a `var self` method
+    /// changes it in place.
+    fn call_method(
+        &mut self,
+        method: &'a Method,
+        object: &'a Expression,
+        arguments: Vec<Value<'a>>,
+    ) -> Flow<'a, Value<'a>> {
+        let place = self
+            .place(object)?
+            .filter(|place| matches!(place.root, Root::Local(_)));
+        let Some(place) = place else {
+            let receiver = self.evaluate(object)?;
+            return Ok(self.invoke(&method.function, Some(receiver), arguments)?.0);
+        };
+        let receiver = std::mem::take(self.place_mut(&place, false)?);
+        let (value, receiver) =
+            match self.invoke(&method.function, Some(receiver.clone()), arguments) {
+                Ok((value, after)) => (Ok(value), after.unwrap_or(receiver)),
+                Err(exit) => (Err(exit), receiver),
+            };
+        *self.place_mut(&place, false)? = receiver;
+        value
+    }
+
+    fn call_builtin(
+        &mut self,
+        module: &str,
+        name: Symbol,
+        expressions: &'a [Expression],
+        arguments: Vec<Value<'a>>,
+    ) -> Flow<'a, Value<'a>> {
+        let float = |index: usize| match arguments.get(index) {
+            Some(Value::Float(value)) => Ok(*value),
+            _ => Err(Exit::Error(format!(
+                "`{}.{}` takes `float` arguments",
+                module, name
+            ))),
+        };
+        let value = match (module, name.as_str()) {
+            ("math", "sqrt") => Value::Float(float(0)?.sqrt()),
+            ("math", "abs") => Value::Float(float(0)?.abs()),
+            ("math", "floor") => Value::Float(float(0)?.floor()),
+            ("math", "ceil") => Value::Float(float(0)?.ceil()),
+            ("math", "round") => Value::Float(float(0)?.round()),
+            ("math", "sin") => Value::Float(float(0)?.sin()),
+            ("math", "cos") => Value::Float(float(0)?.cos()),
+            ("math", "tan") => Value::Float(float(0)?.tan()),
+            ("math", "exp") => Value::Float(float(0)?.exp()),
+            ("math", "ln") => Value::Float(float(0)?.ln()),
+            ("math", "pow") => Value::Float(float(0)?.powf(float(1)?)),
+            ("math", "min") => Value::Float(float(0)?.min(float(1)?)),
+            ("math", "max") => Value::Float(float(0)?.max(float(1)?)),
+            ("io", "print") => {
+                let text = match (expressions.first(), arguments.first()) {
+                    (Some(expression), Some(value)) => self.show(expression, value),
+                    _ => String::new(),
+                };
+                self.write(&format!("{}\n", text))?;
+                Value::Void
+            }
+            ("io", "input") => {
+                let prompt = arguments.first().map(Value::to_string).unwrap_or_default();
+                self.write(&prompt)?;
+                let _ = self.output.flush();
+                let mut line = String::new();
+                let _ = self.input.read_line(&mut line);
+                Value::Str(line.trim_end_matches(['\r', '\n']).to_string())
+            }
+            ("io", "args") => Value::List(self.args.iter().cloned().map(Value::Str).collect()),
+            (module, name) => {
+                return Err(Exit::Error(format!(
+                    "unknown function `{}.{}`",
+                    module, name
+                )))
+            }
+        };
+        Ok(value)
+    }
+
+    /// Calls a method of a built-in type, changing the receiver in place
+    /// when it is a variable and the method modifies it.
+    fn call_builtin_method(
+        &mut self,
+        access: &'a MemberAccess,
+        expressions: &'a [Expression],
+        arguments: Vec<Value<'a>>,
+    ) -> Flow<'a, Value<'a>> {
+        let receiver_type = self.type_of(&access.object).unwrap_or(Type::Void);
+        let arguments = match builtins::method(&receiver_type, &access.member) {
+            Some(method) => {
+                let parameters = method.params.iter().filter_map(|param| match param {
+                    builtins::ParamKind::Exact(ty) => Some(ty),
+                    builtins::ParamKind::Printable => None,
+                });
+                let parameters: Vec<Type> = parameters.cloned().collect();
+                self.coerce_all(parameters.iter(), expressions, arguments)
+            }
+            None => arguments,
+        };
+        let member = access.member.as_str();
+        let mut temporary;
+        let receiver = match self.place(&access.object)? {
+            Some(place) if matches!(place.root, Root::Local(_)) => self.place_mut(&place, false)?,
+            _ => {
+                temporary = self.evaluate(&access.object)?;
+                &mut temporary
+            }
+        };
+        let argument = arguments.into_iter().next();
+        let value = match (receiver, member, argument) {
+            (Value::List(list), "len", _) => Value::Int(list.len() as i64),
+            (Value::List(list), "push", Some(value)) => {
+                list.push(value);
+                Value::Void
+            }
+            (Value::List(list), "pop", _) => list
+                .pop()
+                .ok_or_else(|| Exit::Error("pop from empty list".to_string()))?,
+            (Value::List(list), "contains", Some(value)) => Value::Bool(list.contains(&value)),
+            (Value::Map(map), "len", _) => Value::Int(map.len() as i64),
+            (Value::Map(map), "contains", Some(key)) => Value::Bool(map.contains_key(&key)),
+            (Value::Map(map), "remove", Some(key)) => map
+                .remove(&key)
+                .ok_or_else(|| Exit::Error("remove of missing key".to_string()))?,
+            (Value::Map(map), "keys", _) => Value::List(map.keys().cloned().collect()),
+            (Value::Str(text), "len", _) => Value::Int(text.chars().count() as i64),
+            (Value::Str(text), "upper", _) => Value::Str(text.to_uppercase()),
+            (Value::Str(text), "lower", _) => Value::Str(text.to_lowercase()),
+            (Value::Str(text), "trim", _) => Value::Str(text.trim().to_string()),
+            (Value::Str(text), "contains", Some(Value::Str(part))) => {
+                Value::Bool(text.contains(part.as_str()))
+            }
+            (_, member, _) => {
+                return Err(Exit::Error(format!(
+                    "type `{}` has no method `{}`",
+                    receiver_type, member
+                )))
+            }
+        };
+        Ok(value)
+    }
+
+    /// Where `expression` is stored, if it names a variable or part of one.
+    fn place(&mut self, expression: &'a Expression) -> Flow<'a, Option<Place<'a>>> {
+        match expression {
+            Expression::Identifier(identifier) => self.variable(identifier.name),
+            Expression::MemberAccess(access) => {
+                if self.module_name(&access.object).is_some() || self.variant(access).is_some() {
+                    return Ok(None);
+                }
+                let Some(mut place) = self.place(&access.object)? else {
+                    return Ok(None);
+                };
+                place.steps.push(Step::Field(access.member));
+                Ok(Some(place))
+            }
+            Expression::Index(index) => {
+                let Some(mut place) = self.place(&index.object)? else {
+                    return Ok(None);
+                };
+                place.steps.push(Step::Index(self.evaluate(&index.index)?));
+                Ok(Some(place))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Where the variable `name` is stored, if there is one.
+    fn variable(&mut self, name: Symbol) -> Flow<'a, Option<Place<'a>>> {
+        let root = match self.local(name) {
+            Some(index) => Root::Local(index),
+            None if self.globals.contains_key(&name) => {
+                self.initialize(name)?;
+                Root::Global(name)
+            }
+            None => return Ok(None),
+        };
+        Ok(Some(Place {
+            root,
+            steps: Vec::new(),
+        }))
+    }
+
+    fn place_ref(&self, place: &Place<'a>) -> Flow<'a, &Value<'a>> {
+        let mut value = match place.root {
+            Root::Local(index) => &self.locals[index].1,
+            Root::Global(name) => &self.global_values[&name],
+        };
+        for step in &place.steps {
+            value = match step {
+                Step::Field(name) => field(value, *name)?,
+                Step::Index(key) => element(value, key)?,
+            };
+        }
+        Ok(value)
+    }
+
+    /// The value stored at `place`, for changing it. With `insert`, a map
+    /// entry that is missing is added.
+    fn place_mut(&mut self, place: &Place<'a>, insert: bool) -> Flow<'a, &mut Value<'a>> {
+        let Root::Local(index) = place.root else {
+            return Err(Exit::Error("globals cannot be changed".to_string()));
+        };
+        let mut value = &mut self.locals[index].1;
+        for step in &place.steps {
+            value = match (step, value) {
+                (Step::Field(name), Value::Struct(_, fields)) => fields
+                    .iter_mut()
+                    .find(|(field, _)| field == name)
+                    .map(|(_, value)| value)
+                    .ok_or_else(|| Exit::Error(format!("no field `{}`", name)))?,
+                (Step::Index(Value::Int(index)), Value::List(list)) => {
+                    let position = position(*index, list.len())?;
+                    &mut list[position]
+                }
+                (Step::Index(key), Value::Map(map)) => match insert {
+                    true => map.entry(key.clone()).or_insert(Value::Void),
+                    false => map
+                        .get_mut(key)
+                        .ok_or_else(|| Exit::Error("key not found".to_string()))?,
+                },
+                _ => return Err(Exit::Error("cannot assign to this expression".to_string())),
+            };
+        }
+        Ok(value)
+    }
+
+    /// Evaluates the initializer of the global `name` the first time it is
+    /// used.
+    fn initialize(&mut self, name: Symbol) -> Flow<'a, ()> {
+        if self.global_values.contains_key(&name) {
+            return Ok(());
+        }
+        if !self.initializing.insert(name) {
+            return Err(Exit::Error(format!("global `{}` depends on itself", name)));
+        }
+        let (initializer, ty) = self.globals[&name].clone();
+        let frame = self.enter()?;
+        let value = self.evaluate(initializer);
+        self.leave(frame);
+        self.initializing.remove(&name);
+        let value = self.coerce(&ty, initializer, value?);
+        self.global_values.insert(name, value);
+        Ok(())
+    }
+
+    fn write(&mut self, text: &str) -> Flow<'a, ()> {
+        self.output
+            .write_all(text.as_bytes())
+            .map_err(|e| Exit::Error(format!("failed to write output: {}", e)))
+    }
+
+    /// `value` as `io.print` and interpolation show it: plainly for scalars,
+    /// and as Rust's `Debug` would write it otherwise.
+    fn show(&self, expression: &Expression, value: &Value<'a>) -> String {
+        match self.type_of(expression) {
+            Some(ty) if is_display(&ty) => value.to_string(),
+            _ => format!("{:?}", value),
+        }
+    }
+
+    /// `value` of `expression` stored where a `expected` is declared: a
+    /// plain value stored where an optional is expected becomes one.
+    fn coerce(&self, expected: &Type, expression: &Expression, value: Value<'a>) -> Value<'a> {
+        match (expected, self.type_of(expression)) {
+            (Type::Optional(_), Some(Type::Optional(_)) | None) => value,
+            (Type::Optional(_), Some(_)) => Value::Optional(Some(Box::new(value))),
+            _ => value,
+        }
+    }
+
+    fn coerce_all<'t, 'e>(
+        &self,
+        parameters: impl Iterator<Item = &'t Type>,
+        expressions: impl IntoIterator<Item = &'e Expression>,
+        arguments: Vec<Value<'a>>,
+    ) -> Vec<Value<'a>> {
+        let mut parameters = parameters;
+        arguments
+            .into_iter()
+            .zip(expressions)
+            .map(|(argument, expression)| match parameters.next() {
+                Some(parameter) => self.coerce(parameter, expression, argument),
+                None => argument,
+            })
+            .collect()
+    }
+
+    /// The declared type of `variable`, or the one inferred from its initializer.
+    fn variable_type(&self, variable: &Variable) -> Type {
+        variable
+            .var_type
+            .clone()
+            .or_else(|| self.type_of(variable.initializer.as_ref()?))
+            .unwrap_or(Type::Void)
+    }
+
+    fn type_of(&self, expression: &Expression) -> Option<Type> {
+        match expression {
+            Expression::Literal(literal) => Some(literal_type(literal)),
+            other => other.span().and_then(|span| self.types.get(span)).cloned(),
+        }
+    }
+
+    /// The innermost variable of the running call called `name`.
+    fn local(&self, name: Symbol) -> Option<usize> {
+        let mut variables = self.locals[self.frame..].iter();
+        let offset = variables.rposition(|(variable, _)| *variable == name)?;
+        Some(self.frame + offset)
+    }
+
+    fn module_name(&self, expression: &Expression) -> Option<String> {
+        match expression {
+            Expression::Identifier(identifier)
+                if self.local(identifier.name).is_none()
+                    && !self.globals.contains_key(&identifier.name) =>
+            {
+                let module = self.qualifiers.get(&identifier.name)?;
+                builtins::is_module(module).then(|| module.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// The enum and variant an `Enum.Variant` member access names.
+    fn variant(&self, access: &MemberAccess) -> Option<(Symbol, Symbol)> {
+        match access.object.as_ref() {
+            Expression::Identifier(identifier) if self.local(identifier.name).is_none() => {
+                let key = (identifier.name, access.member);
+                self.variants.contains_key(&key).then_some(key)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// What running `main`, or part of it, ended with: an error or exception
+/// nothing caught, or an interrupt, is a runtime error.
+fn finish(result: Flow<'_, ()>) -> Result<()> {
+    match result {
+        Ok(()) | Err(Exit::Return(_) | Exit::Break | Exit::Continue) => Ok(()),
+        Err(Exit::Throw(exception)) => Err(Error::RuntimeError(format!(
+            "uncaught exception {:?}",
+            exception
+        ))),
+        Err(Exit::Error(message)) => Err(Error::RuntimeError(message)),
+        Err(Exit::Interrupted) => Err(Error::RuntimeError(INTERRUPTED.to_string())),
+    }
+}
+
+/// Every name an AST node refers to.
+#[derive(Default)]
+struct Uses(Vec<Symbol>);
+
+impl Visitor for Uses {
+    fn visit_identifier(&mut self, identifier: &Identifier) {
+        self.0.push(identifier.name);
+    }
+}
+
+fn literal_value<'a>(literal: &Literal) -> Value<'a> {
+    match literal {
+        Literal::Integer(value) => Value::Int(*value),
+        Literal::Float(value) => Value::Float(*value),
+        Literal::String(value) => Value::Str(value.clone()),
+        Literal::Boolean(value) => Value::Bool(*value),
+        Literal::None => Value::Optional(None),
+    }
+}
+
+/// `base ** exponent` for a non-negative `exponent`, wrapping on overflow.
+/// Squaring takes any exponent, where `i64::wrapping_pow` only takes a `u32`.
+fn wrapping_pow(mut base: i64, mut exponent: i64) -> i64 {
+    let mut power = 1i64;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            power = power.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exponent >>= 1;
+    }
+    power
+}
+
+/// `left operator right` for the operators that evaluate both sides.
+fn apply<'a>(operator: &BinaryOperator, left: Value<'a>, right: Value<'a>) -> Flow<'a, Value<'a>> {
+    use BinaryOperator::*;
+    let value = match (operator, left, right) {
+        (Add, left @ Value::Str(_), right) | (Add, left, right @ Value::Str(_)) => {
+            Value::Str(format!("{}{}", left, right))
+        }
+        (Eq, left, right) => Value::Bool(equal(left, right)),
+        (Ne, left, right) => Value::Bool(!equal(left, right)),
+        (Lt, left, right) => Value::Bool(left < right),
+        (Le, left, right) => Value::Bool(left <= right),
+        (Gt, left, right) => Value::Bool(left > right),
+        (Ge, left, right) => Value::Bool(left >= right),
+        (operator, Value::Int(left), Value::Int(right)) => Value::Int(match operator {
+            Add => left.wrapping_add(right),
+            Sub => left.wrapping_sub(right),
+            Mul => left.wrapping_mul(right),
+            Div if right == 0 => return Err(Exit::Error("attempt to divide by zero".to_string())),
+            Div => left
+                .checked_div(right)
+                .ok_or_else(|| Exit::Error("attempt to divide with overflow".to_string()))?,
+            Mod if right == 0 => {
+                return Err(Exit::Error(
+                    "attempt to calculate the remainder with a divisor of zero".to_string(),
+                ))
+            }
+            Mod => left.checked_rem(right).ok_or_else(|| {
+                Exit::Error("attempt to calculate the remainder with overflow".to_string())
+            })?,
+            Pow if right < 0 => {
+                return Err(Exit::Error(
+                    "attempt to raise an `int` to a negative power".to_string(),
+                ))
+            }
+            Pow => wrapping_pow(left, right),
+            BitAnd => left & right,
+            BitOr => left | right,
+            BitXor => left ^ right,
+            Shl => u32::try_from(right)
+                .ok()
+                .and_then(|shift| left.checked_shl(shift))
+                .ok_or_else(|| Exit::Error("attempt to shift left with overflow".to_string()))?,
+            Shr => u32::try_from(right)
+                .ok()
+                .and_then(|shift| left.checked_shr(shift))
+                .ok_or_else(|| Exit::Error("attempt to shift right with overflow".to_string()))?,
+            operator => return Err(Exit::Error(format!("cannot apply `{}` to `int`", operator))),
+        }),
+        (operator, left, right) => {
+            let (left, right) = (left.as_float()?, right.as_float()?);
+            Value::Float(match operator {
+                Add => left + right,
+                Sub => left - right,
+                Mul => left * right,
+                Div => left / right,
+                Mod => left % right,
+                Pow => left.powf(right),
+                operator => {
+                    return Err(Exit::Error(format!(
+                        "cannot apply `{}` to `float`",
+                        operator
+                    )))
+                }
+            })
+        }
+    };
+    Ok(value)
+}
+
+/// Whether `left == right`, where comparing with an optional compares the
+/// other side as one.
+fn equal<'a>(left: Value<'a>, right: Value<'a>) -> bool {
+    match (left, right) {
+        (Value::Optional(left), right @ (Value::Optional(_) | Value::Void)) => {
+            Value::Optional(left) == right
+        }
+        (Value::Optional(left), right) => left.is_some_and(|left| *left == right),
+        (left, Value::Optional(right)) => right.is_some_and(|right| left == *right),
+        (left, right) => left == right,
+    }
+}
+
+/// The bindings `pattern` makes when it matches `value`.
+fn matches<'a>(pattern: &Pattern, value: &Value<'a>) -> Option<Vec<(Symbol, Value<'a>)>> {
+    match (pattern, value) {
+        (Pattern::Wildcard, _) => Some(Vec::new()),
+        (Pattern::Binding(name), value) => Some(vec![(*name, value.clone())]),
+        (Pattern::Literal(literal), value) => (literal_value(literal) == *value).then(Vec::new),
+        (Pattern::Variant(pattern), Value::Variant(enum_name, variant, payload)) => {
+            if pattern.enum_name != *enum_name || pattern.variant != *variant {
+                return None;
+            }
+            let bound = pattern.bindings.iter().zip(payload);
+            let bound = bound.filter(|(name, _)| **name != "_");
+            Some(bound.map(|(name, value)| (*name, value.clone())).collect())
+        }
+        _ => None,
+    }
+}
+
+fn field<'v, 'a>(value: &'v Value<'a>, name: Symbol) -> Flow<'a, &'v Value<'a>> {
+    let Value::Struct(_, fields) = value else {
+        return Err(Exit::Error(format!("no field `{}` on {:?}", name, value)));
+    };
+    fields
+        .iter()
+        .find(|(field, _)| *field == name)
+        .map(|(_, value)| value)
+        .ok_or_else(|| Exit::Error(format!("no field `{}` on {:?}", name, value)))
+}
+
+fn element<'v, 'a>(value: &'v Value<'a>, key: &Value<'a>) -> Flow<'a, &'v Value<'a>> {
+    match (value, key) {
+        (Value::List(list), Value::Int(index)) => Ok(&list[position(*index, list.len())?]),
+        (Value::Map(map), key) => map
+            .get(key)
+            .ok_or_else(|| Exit::Error("key not found".to_string())),
+        _ => Err(Exit::Error(format!("cannot index into {:?}", value))),
+    }
+}
+
+/// The position `index` stands for in a list of `len` elements, counting from
+/// the end when it is negative.
+fn position<'a>(index: i64, len: usize) -> Flow<'a, usize> {
+    let position = if index < 0 { index + len as i64 } else { index };
+    if position < 0 || position >= len as i64 {
+        return Err(Exit::Error(format!(
+            "index {} is out of bounds for a list of length {}",
+            index, len
+        )));
+    }
+    Ok(position as usize)
+}
+
+impl<'a> Value<'a> {
+    fn is_true(&self) -> bool {
+        matches!(self, Value::Bool(true))
+    }
+
+    fn as_int(&self) -> Flow<'a, i64> {
+        match self {
+            Value::Int(value) => Ok(*value),
+            other => Err(Exit::Error(format!("expected an `int`, found {:?}", other))),
+        }
+    }
+
+    fn as_float(&self) -> Flow<'a, f64> {
+        match self {
+            Value::Float(value) => Ok(*value),
+            Value::Int(value) => Ok(*value as f64),
+            other => Err(Exit::Error(format!("expected a number, found {:?}", other))),
+        }
+    }
+
+    /// Orders values of different kinds, which no program compares, so
+    /// values of any kind can be map keys.
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Int(_) => 0,
+            Value::Float(_) => 1,
+            Value::Str(_) => 2,
+            Value::Bool(_) => 3,
+            Value::List(_) => 4,
+            Value::Map(_) => 5,
+            Value::Tuple(_) => 6,
+            Value::Struct(..) => 7,
+            Value::Variant(..) => 8,
+            Value::Optional(_) => 9,
+            Value::Function(_) => 10,
+            Value::Void => 11,
+        }
+    }
+}
+
+/// Equality as derived in generated code: structural, with floats compared
+/// as floats. Functions are never equal.
+impl PartialEq for Value<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Float(left), Value::Float(right)) => left == right,
+            (Value::Function(_), _) | (_, Value::Function(_)) => false,
+            (left, right) => left.cmp(right) == Ordering::Equal,
+        }
+    }
+}
+
+impl Eq for Value<'_> {}
+
+impl PartialOrd for Value<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Value<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Value::Int(left), Value::Int(right)) => left.cmp(right),
+            (Value::Float(left), Value::Float(right)) => left.total_cmp(right),
+            (Value::Str(left), Value::Str(right)) => left.cmp(right),
+            (Value::Bool(left), Value::Bool(right)) => left.cmp(right),
+            (Value::List(left), Value::List(right)) | (Value::Tuple(left), Value::Tuple(right)) => {
+                left.cmp(right)
+            }
+            (Value::Map(left), Value::Map(right)) => left.iter().cmp(right.iter()),
+            (Value::Struct(left, left_fields), Value::Struct(right, right_fields)) => left
+                .cmp(right)
+                .then_with(|| left_fields.iter().cmp(right_fields.iter())),
+            (
+                Value::Variant(left_enum, left, left_payload),
+                Value::Variant(right_enum, right, right_payload),
+            ) => (left_enum, left, left_payload).cmp(&(right_enum, right, right_payload)),
+            (Value::Optional(left), Value::Optional(right)) => left.cmp(right),
+            (left, right) => left.rank().cmp(&right.rank()),
+        }
+    }
+}
+
+/// How `io.print` and interpolation show scalars.
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Str(value) => f.write_str(value),
+            Value::Bool(value) => write!(f, "{}", value),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Values as the derived `Debug` of generated code writes them.
+impl fmt::Debug for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{:?}", value),
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Str(value) => write!(f, "{:?}", value),
+            Value::Bool(value) => write!(f, "{:?}", value),
+            Value::List(values) => f.debug_list().entries(values).finish(),
+            Value::Map(entries) => f.debug_map().entries(entries).finish(),
+            Value::Tuple(values) => {
+                let mut tuple = f.debug_tuple("");
+                for value in values {
+                    tuple.field(value);
+                }
+                tuple.finish()
+            }
+            Value::Struct(name, fields) => {
+                let mut structure = f.debug_struct(name);
+                for (name, value) in fields {
+                    structure.field(name, value);
+                }
+                structure.finish()
+            }
+            Value::Variant(_, variant, payload) => {
+                let mut tuple = f.debug_tuple(variant);
+                for value in payload {
+                    tuple.field(value);
+                }
+                tuple.finish()
+            }
+            Value::Optional(Some(value)) => f.debug_tuple("Some").field(value).finish(),
+            Value::Optional(None) => f.write_str("None"),
+            Value::Function(_) => f.write_str("<function>"),
+            Value::Void => f.write_str("()"),
+        }
+    }
+}
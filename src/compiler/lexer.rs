@@ -1,96 +1,191 @@
-use crate::diagnostics::{Error, Result, Span};
+use crate::diagnostics::{
+    codes, Diagnostic, DiagnosticEngine, Error, LintLevel, LintName, Result, Span, Suppression,
+};
+use crate::compiler::intern::Symbol;
+use std::cell::Cell;
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     Integer(i64),
     Float(f64),
     String(String),
+    /// A string literal with `{expression}`s in it.
+    InterpolatedString(Vec<StringPart>),
     Boolean(bool),
 
-    Identifier(String),
+    Identifier(Symbol),
+    /// A `///` comment documenting the item after it, without the slashes.
+    DocComment(String),
 
-    Let, Var, Fn, If, Else, For, In, Try, Catch, Return,
-    Import, Struct, Throw,
+    Let, Var, Fn, If, Else, For, While, Break, Continue, In, Match, Try, Catch, Finally, Return,
+    Import, Pub, Struct, Enum, Impl, SelfValue, Throw, Exception, Const, NoneValue, As,
 
-    IntType, FloatType, StrType, BoolType, ListType, VoidType,
+    IntType, FloatType, StrType, BoolType, ListType, MapType, VoidType,
 
-    Plus, Minus, Star, Slash, Percent,
+    Plus, Minus, Star, StarStar, Slash, Percent,
     Equal, NotEqual, Less, LessEqual, Greater, GreaterEqual,
-    And, Or, Not,
-    Assign,
+    And, Or, Not, Pipe, Ampersand, Caret, Tilde, ShiftLeft, ShiftRight,
+    Question, Coalesce,
+    Assign, PlusAssign, MinusAssign, StarAssign, SlashAssign, PercentAssign,
 
     LeftParen, RightParen,
     LeftBrace, RightBrace,
     LeftBracket, RightBracket,
-    Comma, Dot, Colon, Semicolon, Arrow,
+    Comma, Dot, DotDot, DotDotEqual, Colon, Semicolon, Arrow, FatArrow, At,
 
     Newline, Eof,
+
+    /// Input the lexer could not make sense of, already reported; only
+    /// `tokenize_reporting` produces these.
+    Error(String),
 }
 
-#[derive(Debug, Clone)]
+/// A piece of an interpolated string: text, or the tokens of an expression
+/// between braces.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    Code(Vec<Token>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub span: Span,
 }
 
-pub struct Lexer {
-    input: Vec<char>,
+/// Turns source text into tokens, either all at once with `tokenize` or lazily
+/// as an iterator. Iteration yields every token through the final `Eof`, or
+/// stops after the first error; use `.peekable()` for lookahead.
+///
+/// The lexer borrows the source and walks it with a byte cursor that always
+/// sits on a character boundary; columns still count characters.
+pub struct Lexer<'a> {
+    input: &'a str,
+    /// Byte offset of the next character.
     position: usize,
     line: usize,
     column: usize,
     file_path: String,
+    finished: bool,
+    /// Line the last token other than `Newline` ended on, to tell trailing
+    /// comments from those on a line of their own.
+    last_token_line: usize,
+    suppressions: Vec<Suppression>,
+    /// Problems with `rustic:` comments, reported by `tokenize_reporting`.
+    comment_warnings: Vec<Diagnostic>,
+    /// The code of the last lexical error, for the diagnostic reporting it.
+    error_code: Cell<&'static str>,
 }
 
-impl Lexer {
-    pub fn new(input: &str, file_path: &str) -> Self {
+impl<'a> Lexer<'a> {
+    pub fn new(input: &'a str, file_path: &str) -> Self {
         Self {
-            input: input.chars().collect(),
+            input,
             position: 0,
             line: 1,
             column: 1,
-            file_path: file_path.to_string()
+            file_path: file_path.to_string(),
+            finished: false,
+            last_token_line: 0,
+            suppressions: Vec::new(),
+            comment_warnings: Vec::new(),
+            error_code: Cell::new(codes::SYNTAX),
         }
     }
 
-    pub fn tokenize(&mut self) -> Result<Vec<Token>> {
-        let mut tokens = Vec::new();
+    /// The `// rustic:allow(...)` and `// rustic:deny(...)` comments scanned
+    /// so far.
+    pub fn suppressions(&self) -> &[Suppression] {
+        &self.suppressions
+    }
 
-        while !self.is_at_end() {
-            self.skip_whitespace();
+    /// Zero-width span at the lexer's current position; after `tokenize`
+    /// fails, this is where the offending input was found.
+    pub fn current_span(&self) -> Span {
+        Span {
+            file: self.file_path.clone(),
+            start_line: self.line,
+            start_column: self.column,
+            end_line: self.line,
+            end_column: self.column,
+            start_offset: self.position,
+            end_offset: self.position,
+        }
+    }
 
-            if self.is_at_end() {
-                break;
+    /// Like `tokenize`, but never gives up: each lexical error is recorded in
+    /// `diagnostics` and becomes an `Error` token covering the bad input, and
+    /// scanning carries on after it. The module's suppression comments are
+    /// put in effect in `diagnostics`.
+    pub fn tokenize_reporting(&mut self, diagnostics: &mut DiagnosticEngine) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let start = self.current_span();
+            match self.next_token() {
+                Ok(token) => {
+                    let end = token.token_type == TokenType::Eof;
+                    tokens.push(token);
+                    if end {
+                        diagnostics.set_suppressions(self.suppressions.clone());
+                        for warning in self.comment_warnings.drain(..) {
+                            diagnostics.emit(warning);
+                        }
+                        return tokens;
+                    }
+                }
+                Err(error) => {
+                    let message = match error {
+                        Error::LexError(message) => message,
+                        other => other.to_string(),
+                    };
+                    let span = start.to(&self.current_span());
+                    let diagnostic = Diagnostic::error(message.clone(), span.clone())
+                        .with_code(self.error_code.get());
+                    diagnostics.emit(diagnostic);
+                    tokens.push(Token {
+                        token_type: TokenType::Error(message),
+                        span,
+                    });
+                }
             }
+        }
+    }
+
+    pub fn tokenize(&mut self) -> Result<Vec<Token>> {
+        self.collect()
+    }
 
-            let start_pos = self.position;
-            let start_line = self.line;
-            let start_column = self.column;
+    /// Scans the next token; at the end of input this is `Eof`, every time.
+    pub fn next_token(&mut self) -> Result<Token> {
+        self.skip_whitespace();
 
-            let token_type = self.scan_token()?;
+        let start_line = self.line;
+        let start_column = self.column;
+        let start_offset = self.position;
 
-            let span = Span {
-                file: self.file_path.clone(),
-                start_line,
-                start_column,
-                end_line: self.line,
-                end_column: self.column,
-            };
+        let token_type = if self.is_at_end() {
+            TokenType::Eof
+        } else {
+            self.scan_token()?
+        };
 
-            tokens.push(Token { token_type, span });
+        let span = Span {
+            file: self.file_path.clone(),
+            start_line,
+            start_column,
+            end_line: self.line,
+            end_column: self.column,
+            start_offset,
+            end_offset: self.position,
+        };
+        if token_type != TokenType::Newline {
+            self.last_token_line = span.end_line;
         }
 
-        tokens.push(Token {
-            token_type: TokenType::Eof,
-            span: Span {
-                file: self.file_path.clone(),
-                start_line: self.line,
-                start_column: self.column,
-                end_ine: self.line,
-                end_column: self.column,
-            },
-        });
-
-        Ok(tokens)
+        Ok(Token { token_type, span })
     }
 
     fn scan_token(&mut self) -> Result<TokenType> {
@@ -104,37 +199,57 @@ impl Lexer {
             '[' => Ok(TokenType::LeftBracket),
             ']' => Ok(TokenType::RightBracket),
             ',' => Ok(TokenType::Comma),
+            '.' if self.match_char('.') => {
+                if self.match_char('=') {
+                    Ok(TokenType::DotDotEqual)
+                } else {
+                    Ok(TokenType::DotDot)
+                }
+            }
             '.' => Ok(TokenType::Dot),
             ':' => Ok(TokenType::Colon),
             ';' => Ok(TokenType::Semicolon),
+            '@' => Ok(TokenType::At),
+            '+' if self.match_char('=') => Ok(TokenType::PlusAssign),
             '+' => Ok(TokenType::Plus),
-            '(' => Ok(TokenType::Percent),
-            '\n' => {
-                self.line += 1;
-                self.column = 1;
-                Ok(TokenType::Newline)
-            }
+            '%' if self.match_char('=') => Ok(TokenType::PercentAssign),
+            '%' => Ok(TokenType::Percent),
+            '\n' => Ok(TokenType::Newline),
             '-' => {
                 if self.match_char('>') {
                     Ok(TokenType::Arrow)
+                } else if self.match_char('=') {
+                    Ok(TokenType::MinusAssign)
                 } else {
                     Ok(TokenType::Minus)
                 }
             }
+            '*' if self.match_char('*') => Ok(TokenType::StarStar),
+            '*' if self.match_char('=') => Ok(TokenType::StarAssign),
             '*' => Ok(TokenType::Star),
-            '/' => {
-                if self.match_char('/') {
-                    while self.peek() != '\n' && !self.is_at_end() {
-                        self.advance();
-                    }
-                    self.scan_token()
-                } else {
-                    Ok(TokenType::Slash)
+            // `skip_whitespace` leaves a block comment alone only when it
+            // never ends; the rest of the file is part of it.
+            '/' if self.peek() == '*' => {
+                while !self.is_at_end() {
+                    self.advance();
+                }
+                Err(self.error(codes::UNTERMINATED, "Unterminated block comment"))
+            }
+            '/' if self.match_char('/') => {
+                self.advance(); // the third `/`
+                let start = self.position;
+                while self.peek() != '\n' && !self.is_at_end() {
+                    self.advance();
                 }
+                Ok(TokenType::DocComment(self.input[start..self.position].to_string()))
             }
+            '/' if self.match_char('=') => Ok(TokenType::SlashAssign),
+            '/' => Ok(TokenType::Slash),
             '=' => {
                 if self.match_char('=') {
                     Ok(TokenType::Equal)
+                } else if self.match_char('>') {
+                    Ok(TokenType::FatArrow)
                 } else {
                     Ok(TokenType::Assign)
                 }
@@ -146,6 +261,10 @@ impl Lexer {
                     Ok(TokenType::Not)
                 }
             }
+            '<' if self.match_char('<') => Ok(TokenType::ShiftLeft),
+            '>' if self.match_char('>') => Ok(TokenType::ShiftRight),
+            '^' => Ok(TokenType::Caret),
+            '~' => Ok(TokenType::Tilde),
             '<' => {
                 if self.match_char('=') {
                     Ok(TokenType::LessEqual)
@@ -164,54 +283,580 @@ impl Lexer {
                 if self.match_char('&') {
                     Ok(TokenType::And)
                 } else {
-                    Err(Error::LexError(format!("Unexpected character: {}", c)))
+                    Ok(TokenType::Ampersand)
                 }
             }
             '|' => {
                 if self.match_char('|') {
                     Ok(TokenType::Or)
                 } else {
-                    Err(Error::LexError(format!("Unexpected character: {}", c)))
+                    Ok(TokenType::Pipe)
+                }
+            }
+            '?' => {
+                if self.match_char('?') {
+                    Ok(TokenType::Coalesce)
+                } else {
+                    Ok(TokenType::Question)
                 }
             }
             '"' => self.scan_string(),
             _ if c.is_ascii_digit() => self.scan_number(),
             _ if c.is_ascii_alphabetic() || c == '_' => self.scan_identifier(),
-            _ => Err(Error::LexError(format!("Unexpected character: {}", c))),
+            _ => {
+                let message = format!("Unexpected character: {}", c);
+                Err(self.error(codes::UNEXPECTED_CHARACTER, message))
+            }
         }
     }
 
     fn scan_string(&mut self) -> Result<TokenType> {
         let mut value = String::new();
+        let mut parts = Vec::new();
+        let mut invalid_escape = None;
+        // Where the opening line ends, so an unterminated string only swallows
+        // the rest of its own line.
+        let mut line_end = None;
 
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
-                self.column = 1;
+            if self.peek() == '\n' && line_end.is_none() {
+                line_end = Some(self.mark());
             }
-
             let c = self.advance();
             if c == '\\' {
-                match self.advance() {
-                    'n' => value.push('\n'),
-                    't' => value.push('\t'),
-                    'r' => value.push('\r'),
-                    '\\' => value.push('\\'),
-                    '"' => value.push('"'),
-                    c => {
-                        return Err(Error::LexError(format!("Invalid escape sequence: \\{}", c)));
+                let c = self.advance();
+                match ESCAPES.iter().find(|(escape, _)| *escape == c) {
+                    Some((_, escaped)) => value.push(*escaped),
+                    None => {
+                        invalid_escape.get_or_insert(c);
                     }
                 }
+            } else if c == '{' && !self.match_char('{') {
+                if !value.is_empty() {
+                    parts.push(StringPart::Text(std::mem::take(&mut value)));
+                }
+                parts.push(StringPart::Code(self.scan_interpolation()?));
             } else {
+                // `{{` and `}}` stand for one brace; a `}` on its own is kept.
+                if c == '}' {
+                    self.match_char('}');
+                }
                 value.push(c);
             }
         }
 
         if self.is_at_end() {
-            return Err(Error::LexError("Unterminated string".to_string()));
+            if let Some(mark) = line_end {
+                self.reset(mark);
+            }
+            return Err(self.error(codes::UNTERMINATED, "Unterminated string"));
         }
 
         self.advance(); // close
-        Ok(TokenType::String(value))
+        if let Some(c) = invalid_escape {
+            let message = format!("Invalid escape sequence: \\{}", c);
+            return Err(self.error(codes::INVALID_ESCAPE, message));
+        }
+        if parts.is_empty() {
+            return Ok(TokenType::String(value));
+        }
+        if !value.is_empty() {
+            parts.push(StringPart::Text(value));
+        }
+        Ok(TokenType::InterpolatedString(parts))
+    }
+
+    /// Scans the expression after a `{` in a string, through the matching `}`,
+    /// and returns its tokens. On failure the rest of the string is skipped,
+    /// so it is not lexed as code.
+    fn scan_interpolation(&mut self) -> Result<Vec<Token>> {
+        let result = self.scan_interpolated_tokens();
+        if result.is_err() {
+            while !matches!(self.peek(), '"' | '\n') && !self.is_at_end() {
+                self.advance();
+            }
+            self.match_char('"');
+        }
+        result
     }
-}
\ No newline at end of file
+
+    fn scan_interpolated_tokens(&mut self) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        let mut depth = 0;
+        loop {
+            let mark = self.mark();
+            let token = self.next_token()?;
+            match token.token_type {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace if depth == 0 && tokens.is_empty() => {
+                    return Err(self.error(
+                        codes::SYNTAX,
+                        "Expected an expression between `{` and `}`; write `{{` for a brace",
+                    ));
+                }
+                TokenType::RightBrace if depth == 0 => return Ok(tokens),
+                TokenType::RightBrace => depth -= 1,
+                TokenType::Newline | TokenType::Eof => {
+                    self.reset(mark);
+                    return Err(self.error(
+                        codes::UNTERMINATED,
+                        "Expected `}` to end the interpolation",
+                    ));
+                }
+                _ => {}
+            }
+            tokens.push(token);
+        }
+    }
+
+    fn scan_number(&mut self) -> Result<TokenType> {
+        let start = self.position - 1;
+
+        if self.input[start..].starts_with('0') {
+            let radix = match self.peek() {
+                'x' => Some((16, "hexadecimal")),
+                'o' => Some((8, "octal")),
+                'b' => Some((2, "binary")),
+                _ => None,
+            };
+            if let Some((radix, name)) = radix {
+                self.advance();
+                return self.scan_radix_integer(start, radix, name);
+            }
+        }
+
+        self.skip_digits();
+        let mut is_float = self.peek() == '.' && self.peek_next().is_ascii_digit();
+        if is_float {
+            self.advance();
+            self.skip_digits();
+        }
+        if matches!(self.peek(), 'e' | 'E') {
+            is_float = true;
+            self.advance();
+            if matches!(self.peek(), '+' | '-') {
+                self.advance();
+            }
+            if !self.peek().is_ascii_digit() {
+                return Err(self.error(
+                    codes::INVALID_NUMBER,
+                    format!(
+                        "Expected digits in the exponent of `{}`",
+                        &self.input[start..self.position]
+                    ),
+                ));
+            }
+            self.skip_digits();
+        }
+
+        let text = &self.input[start..self.position];
+        let digits = text.replace('_', "");
+        if is_float {
+            digits
+                .parse::<f64>()
+                .map(TokenType::Float)
+                .map_err(|_| {
+                    self.error(codes::INVALID_NUMBER, format!("Invalid float literal: {}", text))
+                })
+        } else {
+            digits
+                .parse::<i64>()
+                .map(TokenType::Integer)
+                .map_err(|_| self.out_of_range(text))
+        }
+    }
+
+    /// Decimal digits, which `_` may separate, as in `1_000_000`.
+    fn skip_digits(&mut self) {
+        while self.peek().is_ascii_digit() || self.peek() == '_' {
+            self.advance();
+        }
+    }
+
+    /// The rest of a `0x`, `0o` or `0b` literal, after the prefix. Letters
+    /// and digits after the prefix all belong to the literal, so `0b12` is
+    /// one bad literal rather than `0b1` followed by `2`.
+    fn scan_radix_integer(&mut self, start: usize, radix: u32, name: &str) -> Result<TokenType> {
+        let digits_start = self.position;
+        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
+            self.advance();
+        }
+        let text = &self.input[start..self.position];
+        let digits = self.input[digits_start..self.position].replace('_', "");
+        if digits.is_empty() {
+            return Err(self.error(
+                codes::INVALID_NUMBER,
+                format!("Missing digits after `{}`", &self.input[start..digits_start]),
+            ));
+        }
+        if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+            return Err(self.error(
+                codes::INVALID_NUMBER,
+                format!("Invalid digit `{}` in {} literal", digit, name),
+            ));
+        }
+        i64::from_str_radix(&digits, radix)
+            .map(TokenType::Integer)
+            .map_err(|_| self.out_of_range(text))
+    }
+
+    fn out_of_range(&self, text: &str) -> Error {
+        self.error(codes::INVALID_NUMBER, format!("Integer literal out of range: {}", text))
+    }
+
+    /// A lexical error, whose code `tokenize_reporting` gives its diagnostic.
+    fn error(&self, code: &'static str, message: impl Into<String>) -> Error {
+        self.error_code.set(code);
+        Error::LexError(message.into())
+    }
+
+    fn scan_identifier(&mut self) -> Result<TokenType> {
+        let start = self.position - 1;
+
+        while self.peek().is_ascii_alphanumeric() || self.peek() == '_' {
+            self.advance();
+        }
+
+        let text = &self.input[start..self.position];
+        Ok(keyword(text).unwrap_or_else(|| TokenType::Identifier(Symbol::intern(text))))
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                ' ' | '\t' | '\r' => {
+                    self.advance();
+                }
+                '/' if self.peek_next() == '/' && !self.at_doc_comment() => {
+                    let start = self.current_span();
+                    let text_start = self.position + 2;
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        self.advance();
+                    }
+                    let text = &self.input[text_start..self.position];
+                    if let Some(directive) = text.trim().strip_prefix("rustic:") {
+                        let span = start.to(&self.current_span());
+                        self.suppression(directive, span);
+                    }
+                }
+                '/' if self.peek_next() == '*' => {
+                    if !self.skip_block_comment() {
+                        break;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Whether a `///` doc comment starts here. Four or more slashes make an
+    /// ordinary comment, as in Rust.
+    fn at_doc_comment(&self) -> bool {
+        self.input[self.position..].starts_with("///")
+            && !self.input[self.position..].starts_with("////")
+    }
+
+    /// Skips a `/* ... */` comment, in which other block comments nest.
+    /// Returns false, having skipped nothing, if the comment never ends.
+    fn skip_block_comment(&mut self) -> bool {
+        let start = self.mark();
+        let mut depth = 0;
+        while !self.is_at_end() {
+            if self.peek() == '/' && self.peek_next() == '*' {
+                self.advance();
+                self.advance();
+                depth += 1;
+            } else if self.peek() == '*' && self.peek_next() == '/' {
+                self.advance();
+                self.advance();
+                depth -= 1;
+                if depth == 0 {
+                    return true;
+                }
+            } else {
+                self.advance();
+            }
+        }
+        self.reset(start);
+        false
+    }
+
+    /// Records the `allow(...)` or `deny(...)` after `rustic:` in a comment.
+    fn suppression(&mut self, directive: &str, span: Span) {
+        let parsed = directive
+            .strip_suffix(')')
+            .and_then(|rest| rest.split_once('('))
+            .and_then(|(level, categories)| match level.trim() {
+                "allow" => Some((LintLevel::Allow, categories)),
+                "deny" => Some((LintLevel::Deny, categories)),
+                _ => None,
+            });
+        let Some((level, listed)) = parsed else {
+            let message = format!("Malformed `rustic:{}` comment", directive);
+            self.comment_warnings.push(
+                Diagnostic::warning(message, span)
+                    .with_help("write `rustic:allow(NAME, ...)` or `rustic:deny(NAME, ...)`"),
+            );
+            return;
+        };
+
+        // The names `-W`, `-A` and `-D` take: categories and single lints.
+        let mut names = Vec::new();
+        for name in listed.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            match LintName::from_name(name) {
+                Some(lint_name) => names.push(lint_name),
+                None => {
+                    let known: Vec<&str> = LintName::names().collect();
+                    let message = format!("Unknown warning category or lint `{}`", name);
+                    self.comment_warnings.push(
+                        Diagnostic::warning(message, span.clone())
+                            .with_note(format!("the names are {}", known.join(", "))),
+                    );
+                }
+            }
+        }
+        // A comment on a line of its own is about the line below it.
+        let line = if self.last_token_line == span.start_line {
+            span.start_line
+        } else {
+            span.start_line + 1
+        };
+        self.suppressions.push(Suppression {
+            level,
+            names,
+            line,
+            span,
+        });
+    }
+
+    fn mark(&self) -> (usize, usize, usize) {
+        (self.position, self.line, self.column)
+    }
+
+    fn reset(&mut self, (position, line, column): (usize, usize, usize)) {
+        self.position = position;
+        self.line = line;
+        self.column = column;
+    }
+
+    fn advance(&mut self) -> char {
+        let c = self.peek();
+        if !self.is_at_end() {
+            self.position += c.len_utf8();
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        c
+    }
+
+    fn match_char(&mut self, expected: char) -> bool {
+        if self.peek() != expected {
+            return false;
+        }
+        self.advance();
+        true
+    }
+
+    fn peek(&self) -> char {
+        self.char_at(self.position)
+    }
+
+    fn peek_next(&self) -> char {
+        self.char_at(self.position + self.peek().len_utf8())
+    }
+
+    /// The character starting at byte `index`, or `'\0'` past the end. Source
+    /// is mostly ASCII, which is read without decoding.
+    fn char_at(&self, index: usize) -> char {
+        match self.input.as_bytes().get(index) {
+            None => '\0',
+            Some(&byte) if byte.is_ascii() => byte as char,
+            Some(_) => self.input[index..].chars().next().unwrap_or('\0'),
+        }
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.position >= self.input.len()
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let token = self.next_token();
+        self.finished = match &token {
+            Ok(token) => token.token_type == TokenType::Eof,
+            Err(_) => true,
+        };
+        Some(token)
+    }
+}
+
+/// Escape sequences allowed in string literals: the character after `\` and
+/// the character it stands for.
+pub const ESCAPES: &[(char, char)] = &[
+    ('n', '\n'), ('t', '\t'), ('r', '\r'), ('\\', '\\'), ('"', '"'),
+];
+
+/// Every operator and punctuation lexeme recognized by the lexer.
+pub const SYMBOLS: &[&str] = &[
+    "(", ")", "{", "}", "[", "]", ",", ".", "..", "..=", ":", ";", "->", "=>", "@",
+    "+", "-", "*", "/", "%", "+=", "-=", "*=", "/=", "%=",
+    "==", "!=", "<", "<=", ">", ">=", "&&", "||", "|", "!", "?", "??", "=",
+    "&", "^", "~", "<<", ">>", "**",
+];
+
+/// Every reserved word recognized by [`keyword`].
+pub const KEYWORDS: &[&str] = &[
+    "let", "var", "fn", "if", "else", "for", "while", "break", "continue", "in", "match", "try",
+    "catch", "finally", "return",
+    "import", "pub", "struct", "enum", "impl", "self", "throw", "exception", "const", "none",
+    "as",
+    "int", "float", "str", "bool", "list", "map", "void",
+    "true", "false",
+];
+
+pub fn keyword(text: &str) -> Option<TokenType> {
+    let token_type = match text {
+        "let" => TokenType::Let,
+        "var" => TokenType::Var,
+        "fn" => TokenType::Fn,
+        "if" => TokenType::If,
+        "else" => TokenType::Else,
+        "for" => TokenType::For,
+        "while" => TokenType::While,
+        "break" => TokenType::Break,
+        "continue" => TokenType::Continue,
+        "in" => TokenType::In,
+        "match" => TokenType::Match,
+        "try" => TokenType::Try,
+        "catch" => TokenType::Catch,
+        "finally" => TokenType::Finally,
+        "return" => TokenType::Return,
+        "import" => TokenType::Import,
+        "pub" => TokenType::Pub,
+        "struct" => TokenType::Struct,
+        "enum" => TokenType::Enum,
+        "impl" => TokenType::Impl,
+        "self" => TokenType::SelfValue,
+        "throw" => TokenType::Throw,
+        "exception" => TokenType::Exception,
+        "const" => TokenType::Const,
+        "none" => TokenType::NoneValue,
+        "as" => TokenType::As,
+        "int" => TokenType::IntType,
+        "float" => TokenType::FloatType,
+        "str" => TokenType::StrType,
+        "bool" => TokenType::BoolType,
+        "list" => TokenType::ListType,
+        "map" => TokenType::MapType,
+        "void" => TokenType::VoidType,
+        "true" => TokenType::Boolean(true),
+        "false" => TokenType::Boolean(false),
+        _ => return None,
+    };
+    Some(token_type)
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            TokenType::Integer(value) => return write!(f, "integer `{}`", value),
+            TokenType::Float(value) => return write!(f, "float `{}`", value),
+            TokenType::String(value) => return write!(f, "string {:?}", value),
+            TokenType::InterpolatedString(_) => return write!(f, "interpolated string"),
+            TokenType::Boolean(value) => return write!(f, "`{}`", value),
+            TokenType::Identifier(name) => return write!(f, "identifier `{}`", name),
+            TokenType::DocComment(_) => return write!(f, "doc comment"),
+            TokenType::Error(_) => "invalid input",
+            TokenType::Let => "let",
+            TokenType::Var => "var",
+            TokenType::Fn => "fn",
+            TokenType::If => "if",
+            TokenType::Else => "else",
+            TokenType::For => "for",
+            TokenType::While => "while",
+            TokenType::Break => "break",
+            TokenType::Continue => "continue",
+            TokenType::In => "in",
+            TokenType::Match => "match",
+            TokenType::Try => "try",
+            TokenType::Catch => "catch",
+            TokenType::Finally => "finally",
+            TokenType::Return => "return",
+            TokenType::Import => "import",
+            TokenType::Pub => "pub",
+            TokenType::Struct => "struct",
+            TokenType::Enum => "enum",
+            TokenType::Impl => "impl",
+            TokenType::SelfValue => "self",
+            TokenType::Throw => "throw",
+            TokenType::Exception => "exception",
+            TokenType::Const => "const",
+            TokenType::NoneValue => "none",
+            TokenType::As => "as",
+            TokenType::IntType => "int",
+            TokenType::FloatType => "float",
+            TokenType::StrType => "str",
+            TokenType::BoolType => "bool",
+            TokenType::ListType => "list",
+            TokenType::MapType => "map",
+            TokenType::VoidType => "void",
+            TokenType::Plus => "+",
+            TokenType::Minus => "-",
+            TokenType::Star => "*",
+            TokenType::StarStar => "**",
+            TokenType::Slash => "/",
+            TokenType::Percent => "%",
+            TokenType::Equal => "==",
+            TokenType::NotEqual => "!=",
+            TokenType::Less => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::Greater => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::And => "&&",
+            TokenType::Or => "||",
+            TokenType::Pipe => "|",
+            TokenType::Ampersand => "&",
+            TokenType::Caret => "^",
+            TokenType::Tilde => "~",
+            TokenType::ShiftLeft => "<<",
+            TokenType::ShiftRight => ">>",
+            TokenType::Not => "!",
+            TokenType::Question => "?",
+            TokenType::Coalesce => "??",
+            TokenType::Assign => "=",
+            TokenType::PlusAssign => "+=",
+            TokenType::MinusAssign => "-=",
+            TokenType::StarAssign => "*=",
+            TokenType::SlashAssign => "/=",
+            TokenType::PercentAssign => "%=",
+            TokenType::LeftParen => "(",
+            TokenType::RightParen => ")",
+            TokenType::LeftBrace => "{",
+            TokenType::RightBrace => "}",
+            TokenType::LeftBracket => "[",
+            TokenType::RightBracket => "]",
+            TokenType::Comma => ",",
+            TokenType::Dot => ".",
+            TokenType::DotDot => "..",
+            TokenType::DotDotEqual => "..=",
+            TokenType::Colon => ":",
+            TokenType::Semicolon => ";",
+            TokenType::Arrow => "->",
+            TokenType::FatArrow => "=>",
+            TokenType::At => "@",
+            TokenType::Newline => return write!(f, "newline"),
+            TokenType::Eof => return write!(f, "end of file"),
+        };
+        write!(f, "`{}`", text)
+    }
+}
@@ -1,77 +1,805 @@
-use crate::diagnostics::{self, DiagnosticEngine, Error, Result};
-use std::collections::HashMap;
+use crate::diagnostics::{
+    codes, Applicability, Diagnostic, DiagnosticEngine, Error, Level, Lint, Result, Span,
+};
+use crate::ice::{self, Phase};
+use crate::profile;
+use crate::utils;
+use notify::{RecursiveMode, Watcher};
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs;
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use assert_cmd::output;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
 use walkdir::WalkDir;
 
 pub mod ast;
+pub mod ast_json;
+pub mod builtins;
+pub mod cache;
+pub mod callbacks;
 pub mod codegen;
+pub mod flow;
+pub mod fold;
+pub mod graph;
+pub mod intern;
+pub mod interpreter;
 pub mod lexer;
 pub mod parser;
+pub mod printer;
+pub mod prune;
+pub mod query;
+pub mod runtime;
+pub mod rustc;
 pub mod semantic;
+pub mod source;
+pub mod sourcemap;
+pub mod xref;
 
-use ast::Program;
+use ast::{Item, Program};
+use cache::{BuildCache, CachedModule};
+use callbacks::CompilerCallbacks;
 use codegen::CodeGenerator;
+use graph::ProjectGraph;
 use lexer::Lexer;
 use parser::Parser;
-use semantic::SemanticAnalyzer;
+use query::Database;
+use semantic::{SemanticAnalyzer, TypeTable};
+use source::SourceFile;
+use sourcemap::SourceMap;
+use xref::XrefIndex;
+
+/// How long `watch` waits for more changes after one, so that saving several
+/// files at once, or an editor writing one in steps, starts a single build.
+const WATCH_DELAY: Duration = Duration::from_millis(100);
+
+/// A file of a directory build as read: its path, module name, AST or what
+/// stopped it from being read, and the errors and warnings reported reading it.
+/// There is no AST for a module the cache says is up to date.
+type ReadFile = (String, String, Result<Option<Arc<Program>>>, (usize, usize));
+
+/// What the compiler writes to the output directory for each module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Emit {
+    #[default]
+    Rust,
+    /// The parsed program as an `ast_json` document, without checking it.
+    AstJson,
+    /// Nothing: each module is lexed, parsed and checked, but no code is
+    /// generated and nothing is written. What `rustic check` does.
+    Check,
+}
+
+/// What the compiler reads from input files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum InputFormat {
+    #[default]
+    Rustic,
+    /// `ast_json` documents, named `<module>.ast.json`.
+    AstJson,
+}
+
+/// How one file of a directory build went.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileOutcome {
+    pub file: String,
+    /// The file written for it, if any, or what stopped it from compiling.
+    pub result: Result<Option<String>>,
+    pub errors: usize,
+    pub warnings: usize,
+    /// Whether the file was unchanged since the last build, so its output
+    /// was kept rather than compiled again.
+    pub cached: bool,
+}
 
 pub struct RusticCompiler<'a> {
     diagnostics: &'a mut DiagnosticEngine,
-    modules: HashMap<String, Program>,
+    /// Every module compiled to Rust so far, shared rather than copied.
+    modules: HashMap<String, Arc<Program>>,
+    /// The modules of the directory being built, by name, as read. Their
+    /// imports are the edges; each module is checked against the modules it
+    /// imports. Kept after a build, so `rebuild` can find what depends on a
+    /// changed file. Shared with the compilers checking modules in parallel.
+    module_graph: Arc<HashMap<String, Arc<Program>>>,
+    /// The source file of each module of the directory, by module name.
+    sources: BTreeMap<String, String>,
+    /// The file written for each module of the directory that compiled.
+    written: BTreeMap<String, String>,
+    /// What each module of the directory was last read as, and which are
+    /// up to date; kept after a build for `rebuild`.
+    database: Database,
+    /// Whether directory builds skip the modules the cache says are up to
+    /// date.
+    incremental: bool,
+    /// The cache of the output directory, during and after an incremental
+    /// directory build.
+    cache: Option<BuildCache>,
+    /// Whether the generated code leaves out the items `main` never reaches.
+    eliminate_dead_code: bool,
+    /// Whether generated Rust is laid out the way rustfmt would.
+    format: bool,
+    /// Whether large sources are memory-mapped rather than read.
+    map_sources: bool,
+    /// Whether the generated Rust is written under `src/` of the output
+    /// directory, as the crate `compile_to_native` builds.
+    native: bool,
+    /// The hash of each module's source as last read, for the cache.
+    hashes: HashMap<String, u64>,
+    graph: ProjectGraph,
+    emit: Emit,
+    input_format: InputFormat,
+    callbacks: Vec<Box<dyn CompilerCallbacks + 'a>>,
+    /// How many diagnostics have been passed to `on_diagnostic` so far.
+    forwarded: usize,
+    outcomes: Vec<FileOutcome>,
 }
 
 impl <'a> RusticCompiler<'a> {
     pub fn new(diagnostics: &'a mut DiagnosticEngine) -> Self {
         Self {
+            modules: HashMap::new(),
+            module_graph: Arc::default(),
+            sources: BTreeMap::new(),
+            written: BTreeMap::new(),
+            database: Database::new(),
+            incremental: false,
+            cache: None,
+            eliminate_dead_code: false,
+            format: true,
+            map_sources: false,
+            native: false,
+            hashes: HashMap::new(),
+            graph: ProjectGraph::new(),
+            emit: Emit::default(),
+            input_format: InputFormat::default(),
+            callbacks: Vec::new(),
+            forwarded: diagnostics.diagnostics().len(),
+            outcomes: Vec::new(),
             diagnostics,
-            modules: HashMap::new()
         }
     }
 
+    /// Registers hooks to run during every later compilation, after any
+    /// registered before them.
+    pub fn add_callbacks(&mut self, callbacks: impl CompilerCallbacks + 'a) {
+        self.callbacks.push(Box::new(callbacks));
+    }
+
+    pub fn set_emit(&mut self, emit: Emit) {
+        self.emit = emit;
+    }
+
+    pub fn set_input_format(&mut self, input_format: InputFormat) {
+        self.input_format = input_format;
+    }
+
+    /// Makes directory builds that write output incremental: a module is not
+    /// compiled again when neither it nor any module it imports changed since
+    /// the last build into the same output directory with the same settings.
+    /// Its output is kept and its warnings reported again, but its AST is
+    /// not among `modules`, it is not in `graph`, and callbacks do not run
+    /// for it.
+    pub fn set_incremental(&mut self, incremental: bool) {
+        self.incremental = incremental;
+    }
+
+    /// Leaves the functions, methods, structs, enums and constants that
+    /// `main` can never reach, in any module of the program, out of the
+    /// generated Rust. What a module keeps then depends on every other
+    /// module, so directory builds are not incremental and `rebuild`
+    /// compiles every module again.
+    /// Does nothing for a program without `main`.
+    pub fn set_eliminate_dead_code(&mut self, eliminate_dead_code: bool) {
+        self.eliminate_dead_code = eliminate_dead_code;
+    }
+
+    /// Whether generated Rust is laid out the way rustfmt would, which it is
+    /// by default, or left as code generation writes it, which is faster.
+    pub fn set_format(&mut self, format: bool) {
+        self.format = format;
+    }
+
+    /// Memory-maps large sources rather than reading them, which saves
+    /// copying them but requires that nothing writes to them during the
+    /// build. Off by default; not for watching files that are being edited.
+    pub fn set_map_sources(&mut self, map_sources: bool) {
+        self.map_sources = map_sources;
+    }
+
+    /// Writes the generated Rust where `compile_to_native` builds it from:
+    /// under `src/` of the output directory rather than at its top.
+    pub fn set_native(&mut self, native: bool) {
+        self.native = native;
+    }
+
+    /// Import and call graph of every module compiled so far.
+    pub fn graph(&self) -> &ProjectGraph {
+        &self.graph
+    }
+
+    /// The AST of a module compiled to Rust so far, after callbacks ran.
+    pub fn module(&self, name: &str) -> Option<Arc<Program>> {
+        self.modules.get(name).cloned()
+    }
+
+    /// Every module compiled to Rust so far with its AST, in no particular
+    /// order.
+    pub fn modules(&self) -> impl Iterator<Item = (&str, &Arc<Program>)> {
+        self.modules.iter().map(|(name, ast)| (name.as_str(), ast))
+    }
+
+    /// Each file of the directory builds so far, in the order compiled.
+    pub fn outcomes(&self) -> &[FileOutcome] {
+        &self.outcomes
+    }
+
+    /// Takes the diagnostics reported so far out of the engine, after passing
+    /// them to `on_diagnostic`, so a later build reports the same problems
+    /// again rather than treating them as already reported.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.forward_diagnostics();
+        self.forwarded = 0;
+        self.diagnostics.take()
+    }
+
     pub fn compile_file(&mut self, input_path: &str, output_dir: &str) -> Result<Vec<String>> {
-        let source = fs::read_to_string(input_path)
+        let source = self.open_source(input_path)
             .map_err(|e| Error::IoError(format!("Failed to read file {}: {}", input_path, e)))?;
+        self.add_source(input_path, &source);
 
-        let module_name = Path::new(input_path)
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("main")
-            .to_string();
+        let module_name = self.module_name(Path::new(input_path), "main");
 
         self.compile_source(&source, &module_name, input_path, output_dir)
-            .map(|rust_file| vec![rust_file])
+            .map(|rust_file| rust_file.into_iter().collect())
     }
 
+    /// Compiles every input file under `input_dir`. Every file is read
+    /// before any is checked, so each module can import any other. A file
+    /// that fails does not stop the others; the build fails at the end if any
+    /// did, and `outcomes` tells how each file went. Files are read, and then
+    /// compiled, in parallel unless callbacks are registered; diagnostics are
+    /// reported in the same order either way.
+    ///
+    /// Files in subdirectories are nested modules: `net/http.rsc` is imported
+    /// as `net.http` and compiled to `net/http.rs`, declared in `net.rs`.
     pub fn compile_directory(&mut self, input_dir: &str, output_dir: &str) -> Result<Vec<String>> {
-        let mut generated_files = Vec::new();
+        self.module_graph = Arc::default();
+        self.sources.clear();
+        self.written.clear();
+        self.hashes.clear();
+        self.cache = self.load_cache(output_dir);
+        self.database = self.cache.as_mut().map(BuildCache::take_database).unwrap_or_default();
+        let root = Path::new(input_dir);
+        let entries = WalkDir::new(input_dir).sort_by_file_name().into_iter();
+        let files: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .map(|entry| entry.into_path())
+            .filter(|path| self.is_input(path))
+            .collect();
+
+        let outdated = self.outdated_files(root, &files);
+        let (changed, reused): (Vec<&PathBuf>, Vec<&PathBuf>) = files
+            .iter()
+            .partition(|path| outdated.contains(&self.nested_module_name(root, path)));
+        let mut read = self.map_modules(&changed, |compiler, path| compiler.parse_file(root, path));
+        for file in &read {
+            self.record_file(file);
+        }
+        for path in reused {
+            let module_name = self.nested_module_name(root, path);
+            read.push(self.reuse_module(path, module_name));
+        }
+        read.sort_by(|a, b| a.0.cmp(&b.0));
+        self.read_imports(root, &read);
+        self.write_modules(read, output_dir)
+    }
+
+    /// Compiles again after `compile_directory` built `input_dir`, when the
+    /// files in `changed` were added, edited or deleted since. Only those
+    /// files and the modules the query `Database` says are out of date are
+    /// recompiled: the ones that failed to compile last time and those
+    /// importing a changed module, directly or through other modules. The
+    /// output of a deleted file is removed. Returns every file the
+    /// directory's modules are written to, as `compile_directory` does.
+    pub fn rebuild(
+        &mut self,
+        input_dir: &str,
+        output_dir: &str,
+        changed: &[PathBuf],
+    ) -> Result<Vec<String>> {
+        let root = Path::new(input_dir);
+        let mut read = Vec::new();
+        for path in changed {
+            let module_name = self.nested_module_name(root, path);
+            if path.is_file() {
+                read.push(self.read_file(root, path));
+            } else if self.sources.remove(&module_name).is_some() {
+                self.database.remove(&module_name);
+                Arc::make_mut(&mut self.module_graph).remove(&module_name);
+                self.modules.remove(&module_name);
+                self.graph.remove_module(&module_name);
+                if let Some(output) = self.written.remove(&module_name) {
+                    let _ = fs::remove_file(sourcemap::map_path(&output));
+                    let _ = fs::remove_file(output);
+                }
+            }
+        }
+
+        let outdated = if self.eliminate_dead_code {
+            self.sources.keys().cloned().collect()
+        } else {
+            self.database.outdated()
+        };
+        for module_name in &outdated {
+            if read.iter().any(|(_, name, _, _)| name == module_name) {
+                continue;
+            }
+            let Some(file) = self.sources.get(module_name).cloned() else {
+                continue;
+            };
+            // A module the cache let the last build skip has to be read.
+            match self.module_graph.get(module_name) {
+                Some(ast) => {
+                    read.push((file, module_name.clone(), Ok(Some(Arc::clone(ast))), (0, 0)))
+                }
+                None => read.push(self.read_file(root, Path::new(&file))),
+            }
+        }
+        read.sort_by(|a, b| a.0.cmp(&b.0));
+        self.read_imports(root, &read);
+        self.write_modules(read, output_dir)
+    }
+
+    /// Compiles `input_path`, a file or a directory, and again each time an
+    /// input file in it changes, passing the result of each build to
+    /// `on_build` until it breaks. A directory is compiled again with
+    /// `rebuild`. Fails if the files cannot be watched.
+    pub fn watch(
+        &mut self,
+        input_path: &str,
+        output_dir: &str,
+        mut on_build: impl FnMut(&mut Self, Result<Vec<String>>) -> ControlFlow<()>,
+    ) -> Result<()> {
+        let input = Path::new(input_path);
+        let watch_error =
+            |e: notify::Error| Error::IoError(format!("Failed to watch {}: {}", input_path, e));
+        let is_file = input.is_file();
+        let result = match is_file {
+            true => self.compile_file(input_path, output_dir),
+            false => self.compile_directory(input_path, output_dir),
+        };
+        if on_build(self, result).is_break() {
+            return Ok(());
+        }
+
+        // Editors often save by replacing a file, so a single file is
+        // watched through its directory.
+        let (root, mode) = match is_file {
+            true => (
+                input.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")),
+                RecursiveMode::NonRecursive,
+            ),
+            false => (input, RecursiveMode::Recursive),
+        };
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+        watcher.watch(root, mode).map_err(watch_error)?;
+
+        loop {
+            let mut changed = self.wait_for_changes(root, &events).map_err(watch_error)?;
+            if is_file {
+                changed.retain(|path| path == input);
+                if changed.is_empty() {
+                    continue;
+                }
+            }
+            let result = match is_file {
+                true => self.compile_file(input_path, output_dir),
+                false => self.rebuild(input_path, output_dir, &changed),
+            };
+            if on_build(self, result).is_break() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Blocks until input files under `root` change, returning their paths
+    /// under `root`.
+    fn wait_for_changes(
+        &self,
+        root: &Path,
+        events: &Receiver<notify::Result<notify::Event>>,
+    ) -> notify::Result<Vec<PathBuf>> {
+        let stopped = || notify::Error::generic("the watcher stopped");
+        // Events may name files by their canonical path.
+        let canonical_root = fs::canonicalize(root).ok();
+        let mut changed = BTreeSet::new();
+        loop {
+            let event = if changed.is_empty() {
+                events.recv().map_err(|_| stopped())?
+            } else {
+                match events.recv_timeout(WATCH_DELAY) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return Err(stopped()),
+                }
+            }?;
+            if event.kind.is_access() {
+                continue;
+            }
+            for path in event.paths {
+                let path = match (&canonical_root, path.strip_prefix(root).is_ok()) {
+                    (Some(canonical), false) => match path.strip_prefix(canonical) {
+                        Ok(relative) => root.join(relative),
+                        Err(_) => continue,
+                    },
+                    _ => path,
+                };
+                if self.is_input(&path) {
+                    changed.insert(path);
+                }
+            }
+        }
+        Ok(changed.into_iter().collect())
+    }
+
+    /// Checks a file or every source file under a directory and returns the
+    /// combined cross-reference index, without generating any code.
+    pub fn index(&mut self, input_path: &str) -> Result<XrefIndex> {
+        let path = Path::new(input_path);
+        let files: Vec<_> = if path.is_file() {
+            vec![path.to_path_buf()]
+        } else {
+            WalkDir::new(path)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .map(|entry| entry.into_path())
+                .filter(|path| utils::is_rustic_source(path))
+                .collect()
+        };
+
+        let mut modules = Vec::new();
+        for file in files {
+            let source = self.open_source(&file).map_err(|e| {
+                Error::IoError(format!("Failed to read file {:?}: {}", file, e))
+            })?;
+
+            let tokens = Lexer::new(&source, file.to_str().unwrap_or(""))
+                .tokenize_reporting(self.diagnostics);
+            let ast = Parser::new(tokens, self.diagnostics).parse()?;
+            modules.push((self.nested_module_name(path, &file), Arc::new(ast)));
+        }
+
+        let mut index = XrefIndex::new();
+        for (module_name, ast) in &modules {
+            let mut analyzer = SemanticAnalyzer::new(self.diagnostics);
+            for (name, module) in modules.iter().filter(|(name, _)| name != module_name) {
+                analyzer.add_module(name, Arc::clone(module));
+            }
+            analyzer.analyze(ast)?;
+            index.merge(module_name, analyzer.xref());
+        }
+
+        Ok(index)
+    }
+
+    /// Reads the file at `path` under `root` as a module of the directory
+    /// being built.
+    fn read_file(&mut self, root: &Path, path: &Path) -> ReadFile {
+        let file = self.parse_file(root, path);
+        self.record_file(&file);
+        file
+    }
+
+    /// Reads the file at `path` under `root`, without adding its module to
+    /// those of the directory.
+    fn parse_file(&mut self, root: &Path, path: &Path) -> ReadFile {
+        let file = path.to_str().unwrap_or("").to_string();
+        let module_name = self.nested_module_name(root, path);
+        let reported = self.diagnostics.diagnostics().len();
+
+        let result = self.open_source(path)
+            .map_err(|e| Error::IoError(format!("Failed to read file {:?}: {}", path, e)))
+            .and_then(|source| {
+                self.add_source(&file, &source);
+                self.hashes.insert(module_name.clone(), cache::source_hash(&source));
+                let _timing = profile::start("file", &file);
+                self.read_module(&source, &module_name, &file).map(Arc::new)
+            });
+        self.forward_diagnostics();
+        let counts = self.count_since(reported);
+        (file, module_name, result.map(Some), counts)
+    }
+
+    /// Adds a module read by `parse_file` to those of the directory, and
+    /// tells the database what it was read as.
+    fn record_file(&mut self, (file, module_name, result, _): &ReadFile) {
+        if let Some(&hash) = self.hashes.get(module_name) {
+            self.database.set_source(module_name, hash);
+        }
+        let module_graph = Arc::make_mut(&mut self.module_graph);
+        match result {
+            Ok(Some(ast)) => {
+                let imports = ast.imports.iter().map(|import| import.module_path.as_str());
+                self.database.set_imports(module_name, imports);
+                module_graph.insert(module_name.clone(), Arc::clone(ast))
+            }
+            _ => {
+                self.database.invalidate(module_name);
+                module_graph.remove(module_name)
+            }
+        };
+        self.sources.insert(module_name.clone(), file.clone());
+    }
+
+    /// Runs `job` on each of `items` and returns the results in order. Each
+    /// job runs in parallel with a compiler of its own, which shares this
+    /// one's settings and the modules read so far; what the job reports and
+    /// compiles is merged into this compiler in the order of `items`, so
+    /// diagnostics come out as if the jobs ran one after another. Callbacks
+    /// need not be thread-safe, so with any registered the jobs run one
+    /// after another on this compiler.
+    fn map_modules<T: Sync, R: Send>(
+        &mut self,
+        items: &[T],
+        job: impl Fn(&mut RusticCompiler, &T) -> R + Sync,
+    ) -> Vec<R> {
+        if !self.callbacks.is_empty() || items.len() < 2 {
+            return items.iter().map(|item| job(self, item)).collect();
+        }
+        let diagnostics = self.diagnostics.fork();
+        let module_graph = &self.module_graph;
+        let (emit, input_format, incremental) = (self.emit, self.input_format, self.incremental);
+        let (eliminate_dead_code, format, native, map_sources) =
+            (self.eliminate_dead_code, self.format, self.native, self.map_sources);
+        let results: Vec<_> = items
+            .par_iter()
+            .map(|item| {
+                let mut diagnostics = diagnostics.fork();
+                let (result, modules, hashes, graph) = {
+                    let mut compiler = RusticCompiler::new(&mut diagnostics);
+                    compiler.module_graph = Arc::clone(module_graph);
+                    compiler.emit = emit;
+                    compiler.input_format = input_format;
+                    compiler.incremental = incremental;
+                    compiler.eliminate_dead_code = eliminate_dead_code;
+                    compiler.format = format;
+                    compiler.native = native;
+                    compiler.map_sources = map_sources;
+                    let result = job(&mut compiler, item);
+                    let RusticCompiler { modules, hashes, graph, .. } = compiler;
+                    (result, modules, hashes, graph)
+                };
+                (result, modules, hashes, graph, diagnostics)
+            })
+            .collect();
 
-        for entry in WalkDir::new(input_dir).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path();
-            if path.extension().and_then(|s| s.to_str()) == Some("rsc") {
-                let source = fs::read_to_string(path).map_err(|e| {
-                    Error::IoError(format!("Failed to read file {:?}: {}", path, e))
-                })?;
+        let mut merged = Vec::with_capacity(results.len());
+        for (result, modules, hashes, graph, diagnostics) in results {
+            self.diagnostics.absorb(diagnostics);
+            self.forward_diagnostics();
+            self.modules.extend(modules);
+            self.hashes.extend(hashes);
+            self.graph.merge(graph);
+            merged.push(result);
+        }
+        merged
+    }
 
-                let module_name = path
-                     .file_stem()
-                     .and_then(|s| s.to_str())
-                     .unwrap_or("unnamed")
-                     .to_string();
+    /// Takes the module in `path` as the last build left it, reporting the
+    /// warnings it had again, when the cache says it is up to date.
+    fn reuse_module(&mut self, path: &Path, module_name: String) -> ReadFile {
+        let file = path.to_str().unwrap_or("").to_string();
+        let reported = self.diagnostics.diagnostics().len();
+        let cached = self.cache.as_ref().and_then(|cache| cache.get(&module_name)).cloned();
+        if let Some(cached) = cached {
+            // They were reported under the module's own suppression comments.
+            self.diagnostics.set_suppressions(Vec::new());
+            for diagnostic in cached.diagnostics {
+                self.diagnostics.emit(diagnostic);
+            }
+            if let Some(output) = cached.output {
+                self.written.insert(module_name.clone(), output);
+            }
+        }
+        self.forward_diagnostics();
+        self.sources.insert(module_name.clone(), file.clone());
+        let counts = self.count_since(reported);
+        (file, module_name, Ok(None), counts)
+    }
 
-                let rust_file = self.compile_source(
-                    &source,
-                    &module_name,
-                    path.to_str().unwrap_or(""),
-                    output_dir,
-                )?;
-                generated_files.push(rust_file);
+    /// Reads the modules imported, directly or through other modules, by the
+    /// modules in `read` that are compiled, when the cache let them be
+    /// skipped: those are checked against the modules they import.
+    fn read_imports(&mut self, root: &Path, read: &[ReadFile]) {
+        let mut queue: Vec<String> = read
+            .iter()
+            .filter_map(|(_, _, result, _)| result.as_ref().ok()?.as_ref())
+            .flat_map(|ast| ast.imports.iter().map(|import| import.module_path.clone()))
+            .collect();
+        while let Some(module_name) = queue.pop() {
+            if self.module_graph.contains_key(&module_name) {
+                continue;
+            }
+            let Some(file) = self.sources.get(&module_name).cloned() else {
+                continue;
+            };
+            if let (_, _, Ok(Some(ast)), _) = self.read_file(root, Path::new(&file)) {
+                queue.extend(ast.imports.iter().map(|import| import.module_path.clone()));
             }
         }
+    }
 
-        Ok(generated_files)
+    /// The cache of `output_dir`, when directory builds are incremental,
+    /// write output and keep dead code. A build that is not incremental removes the cache, which
+    /// would no longer match what is in `output_dir` after it.
+    fn load_cache(&self, output_dir: &str) -> Option<BuildCache> {
+        if self.emit == Emit::Check {
+            return None;
+        }
+        if !self.incremental || self.eliminate_dead_code {
+            BuildCache::remove(Path::new(output_dir));
+            return None;
+        }
+        let lint_levels = Lint::ALL.map(|lint| self.diagnostics.lint_level(lint));
+        let settings = (self.emit, self.input_format, lint_levels, self.format, self.native);
+        Some(BuildCache::load(Path::new(output_dir), settings))
+    }
+
+    /// The modules of `files` under `root` that have to be compiled: all
+    /// of them when the cache kept no database, or else the ones the
+    /// database says are out of date once it is told what the files hold
+    /// now, and those whose output is gone.
+    fn outdated_files(&mut self, root: &Path, files: &[PathBuf]) -> BTreeSet<String> {
+        let names: BTreeSet<String> =
+            files.iter().map(|path| self.nested_module_name(root, path)).collect();
+        if self.database.is_empty() {
+            return names;
+        }
+        for path in files {
+            let module_name = self.nested_module_name(root, path);
+            if let Ok(source) = fs::read_to_string(path) {
+                let hash = cache::source_hash(&source);
+                self.hashes.insert(module_name.clone(), hash);
+                self.database.set_source(&module_name, hash);
+            }
+        }
+        let gone: Vec<String> = self
+            .database
+            .modules()
+            .filter(|name| !names.contains(*name))
+            .map(String::from)
+            .collect();
+        for module_name in gone {
+            self.database.remove(&module_name);
+        }
+        // The cache has the output and warnings of the modules it kept.
+        for module_name in &names {
+            let cached = self.cache.as_ref().and_then(|cache| cache.get(module_name));
+            let kept = cached.is_some_and(|cached| {
+                cached.output.as_ref().is_none_or(|output| Path::new(output).is_file())
+            });
+            if !kept {
+                self.database.invalidate(module_name);
+            }
+        }
+        let outdated = self.database.outdated();
+        names
+            .into_iter()
+            .filter(|name| outdated.contains(name) || !self.database.contains(name))
+            .collect()
+    }
+
+    /// Records each module of `compiled` in the cache, forgets the modules
+    /// that failed or are gone, and saves it.
+    fn update_cache(&mut self, compiled: &BTreeSet<String>) -> Result<()> {
+        let Some(mut cache) = self.cache.take() else {
+            return Ok(());
+        };
+        for module_name in compiled {
+            let file = self.sources.get(module_name);
+            let ast = self.module_graph.get(module_name);
+            let (Some(file), Some(ast)) = (file, ast) else {
+                continue;
+            };
+            let diagnostics = self.diagnostics.iter().filter(|d| d.span.file == *file);
+            cache.insert(
+                module_name.clone(),
+                CachedModule {
+                    output: self.written.get(module_name).cloned(),
+                    main: defines_main(ast),
+                    diagnostics: diagnostics.cloned().collect(),
+                },
+            );
+        }
+        let outdated = self.database.outdated();
+        cache.retain(|name| self.sources.contains_key(name) && !outdated.contains(name));
+        let saved = cache.save(&self.database);
+        self.cache = Some(cache);
+        saved
+    }
+
+    /// Writes the output for each module of the directory in `read`, and
+    /// returns every file the directory's modules are written to.
+    fn write_modules(&mut self, read: Vec<ReadFile>, output_dir: &str) -> Result<Vec<String>> {
+        let mut rewritten = BTreeSet::new();
+        let mut failed = 0;
+        let total = read.len();
+        let compiled = self.map_modules(&read, |compiler, (file, module_name, result, _)| {
+            let Ok(Some(ast)) = result else {
+                return None;
+            };
+            let reported = compiler.diagnostics.diagnostics().len();
+            let _timing = profile::start("file", file).arg("module", module_name.as_str());
+            let result = compiler.write_module(Arc::clone(ast), module_name, file, output_dir);
+            compiler.forward_diagnostics();
+            Some((result, compiler.count_since(reported)))
+        });
+
+        for (read, compiled) in read.into_iter().zip(compiled) {
+            let (file, module_name, result, (errors, warnings)) = read;
+            let cached = matches!(result, Ok(None));
+            let (result, (more_errors, more_warnings)) = match (result, compiled) {
+                (Ok(Some(_)), Some(compiled)) => compiled,
+                (Ok(_), _) => (Ok(self.written.get(&module_name).cloned()), (0, 0)),
+                (Err(e), _) => (Err(e), (0, 0)),
+            };
+            match &result {
+                Ok(output_file) => {
+                    if let Some(output_file) = output_file {
+                        self.written.insert(module_name.clone(), output_file.clone());
+                    }
+                    if !cached {
+                        self.database.compiled(&module_name);
+                        rewritten.insert(module_name);
+                    }
+                }
+                Err(_) => {
+                    self.written.remove(&module_name);
+                    self.modules.remove(&module_name);
+                    self.database.invalidate(&module_name);
+                    failed += 1;
+                }
+            }
+            self.outcomes.push(FileOutcome {
+                file,
+                result,
+                errors: errors + more_errors,
+                warnings: warnings + more_warnings,
+                cached,
+            });
+        }
+
+        let mut generated_files: Vec<String> = self.written.values().cloned().collect();
+        if self.emit == Emit::Rust {
+            let written: Vec<&String> = self.written.keys().collect();
+            let rust_dir = self.rust_dir(output_dir);
+            generated_files.extend(declare_submodules(&written, &rewritten, &rust_dir)?);
+        }
+        self.update_cache(&rewritten)?;
+        match failed {
+            0 => Ok(generated_files),
+            failed => Err(Error::CompilationError(format!(
+                "{} of {} files failed to compile",
+                failed, total
+            ))),
+        }
+    }
+
+    /// Compiles `source` in memory and returns the generated Rust code; spans
+    /// in diagnostics use `module_name` as the file name.
+    pub fn compile_str(&mut self, source: &str, module_name: &str) -> Result<String> {
+        self.diagnostics.add_source(module_name, source);
+        let result = self
+            .parse_source(source, module_name, module_name)
+            .and_then(|ast| self.generate(Arc::new(ast), module_name, module_name));
+        self.forward_diagnostics();
+        result
     }
 
     fn compile_source(
@@ -80,79 +808,508 @@ impl <'a> RusticCompiler<'a> {
         module_name: &str,
         file_path: &str,
         output_dir: &str,
-    ) -> Result<String> {
+    ) -> Result<Option<String>> {
+        let _timing = profile::start("file", file_path).arg("module", module_name);
+        let result = self
+            .read_module(source, module_name, file_path)
+            .map(Arc::new)
+            .and_then(|ast| self.write_module(ast, module_name, file_path, output_dir));
+        self.forward_diagnostics();
+        result
+    }
+
+    /// Reads the AST of one module from its source.
+    fn read_module(&mut self, source: &str, module_name: &str, file_path: &str) -> Result<Program> {
+        match self.input_format {
+            InputFormat::Rustic => self.parse_source(source, module_name, file_path),
+            InputFormat::AstJson => {
+                let mut ast = ast_json::from_json(source, file_path)?;
+                self.run_ast_callbacks(module_name, &mut ast)?;
+                Ok(ast)
+            }
+        }
+    }
+
+    /// Produces the output for one module and writes it to `output_dir`,
+    /// returning the path written, if any.
+    fn write_module(
+        &mut self,
+        ast: Arc<Program>,
+        module_name: &str,
+        file_path: &str,
+        output_dir: &str,
+    ) -> Result<Option<String>> {
+        let (output, extension) = match self.emit {
+            Emit::Rust => (self.generate(ast, module_name, file_path)?, "rs"),
+            Emit::AstJson => (ast_json::to_json(&ast), "ast.json"),
+            Emit::Check => {
+                self.check(&ast, module_name, file_path)?;
+                return Ok(None);
+            }
+        };
+
+        // Nested modules go in the directories named after their parents.
+        let rust_dir = self.rust_dir(output_dir);
+        let output_path = match module_name {
+            // `src/main.rs` is the binary, so a module named `main` is kept
+            // in another file.
+            "main" if self.native => format!("{}/main_module.rs", rust_dir),
+            _ => format!("{}/{}.{}", rust_dir, module_path(module_name), extension),
+        };
+        if let Some(parent) = Path::new(&output_path).parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| Error::IoError(format!("Failed to create output directory: {}", e)))?;
+        }
+        if self.emit == Emit::Rust {
+            let map_path = sourcemap::map_path(&output_path);
+            fs::write(&map_path, SourceMap::of(&output).to_json())
+                .map_err(|e| Error::IoError(format!("Failed to write {}: {}", map_path, e)))?;
+        }
+        fs::write(&output_path, output)
+            .map_err(|e| Error::IoError(format!("Failed to write {}: {}", output_path, e)))?;
+
+        Ok(Some(output_path))
+    }
+
+    /// Where the modules of a build into `output_dir` are written.
+    fn rust_dir(&self, output_dir: &str) -> String {
+        match self.native {
+            true => format!("{}/src", output_dir),
+            false => output_dir.to_string(),
+        }
+    }
+
+    fn parse_source(
+        &mut self,
+        source: &str,
+        module_name: &str,
+        file_path: &str,
+    ) -> Result<Program> {
+        let phase = ice::enter(Phase::Lexing, file_path);
         let mut lexer = Lexer::new(source, file_path);
-        let tokens = lexer.tokenize()?;
+        let mut tokens = lexer.tokenize_reporting(self.diagnostics);
+        for callbacks in &mut self.callbacks {
+            callbacks.on_tokens(module_name, &mut tokens);
+        }
+        drop(phase);
 
+        let _phase = ice::enter(Phase::Parsing, file_path);
         let mut parser = Parser::new(tokens, self.diagnostics);
-        let ast = parser.parse()?;
+        let mut ast = parser.parse()?;
+        self.run_ast_callbacks(module_name, &mut ast)?;
+        Ok(ast)
+    }
+
+    fn generate(
+        &mut self,
+        ast: Arc<Program>,
+        module_name: &str,
+        file_path: &str,
+    ) -> Result<String> {
+        let (types, folded) = self.check(&ast, module_name, file_path)?;
 
+        let _phase = ice::enter(Phase::CodeGeneration, file_path);
+        let live = if self.eliminate_dead_code {
+            let modules = self.other_modules(module_name).map(|(_, module)| &**module);
+            prune::live_items(modules.chain([&*ast]))
+        } else {
+            None
+        };
+        let mut codegen = CodeGenerator::new(&types);
+        for (name, module) in self.other_modules(module_name) {
+            // The generated `use` declarations name only what the other
+            // modules keep.
+            match &live {
+                Some(live) => codegen.add_module(name, Arc::new(prune::prune(module, live))),
+                None => codegen.add_module(name, Arc::clone(module)),
+            }
+        }
+        let mut rust_code = match &live {
+            Some(live) => codegen.generate(&prune::prune(&folded, live), module_name)?,
+            None => codegen.generate(&folded, module_name)?,
+        };
+        if self.format {
+            rust_code = codegen::format(&rust_code);
+        }
+        for callbacks in &mut self.callbacks {
+            callbacks.on_generated_code(module_name, &mut rust_code);
+        }
+
+        self.modules.insert(module_name.to_string(), ast);
+        Ok(rust_code)
+    }
+
+    /// Checks one module against the others of the program and runs the
+    /// typed-AST callbacks, returning the types of its expressions and the
+    /// module with its constants folded.
+    fn check(
+        &mut self,
+        ast: &Program,
+        module_name: &str,
+        file_path: &str,
+    ) -> Result<(TypeTable, Program)> {
+        let _phase = ice::enter(Phase::SemanticAnalysis, file_path);
+        self.check_import_cycle(ast, module_name)?;
         let mut analyzer = SemanticAnalyzer::new(self.diagnostics);
-        analyzer.analyze(&ast)?;
+        for (name, module) in self.module_graph.iter() {
+            if name != module_name {
+                analyzer.add_module(name, Arc::clone(module));
+            }
+        }
+        let types = analyzer.analyze(ast)?;
+        self.graph.add_module(module_name, ast, analyzer.call_graph());
+        let folded = analyzer.take_folded().expect("analyze folds the program it checks");
 
-        self.modules.insert(module_name.to_string(), ast.clone());
+        let errors = self.diagnostics.error_count();
+        for callbacks in &mut self.callbacks {
+            callbacks.on_typed_ast(module_name, ast, &types, self.diagnostics);
+        }
+        self.check_callback_errors(errors)?;
+        Ok((types, folded))
+    }
 
-        let mut codegen = CodeGenerator::new();
-        let rust_code = codegen.generate(&ast, module_name)?;
+    /// The modules of the program being built other than `module_name`.
+    fn other_modules<'m>(
+        &'m self,
+        module_name: &'m str,
+    ) -> impl Iterator<Item = (&'m String, &'m Arc<Program>)> {
+        self.module_graph.iter().filter(move |(name, _)| name.as_str() != module_name)
+    }
 
-        fs::create_dir_all(output_dir)
-            .map_err(|e| Error::IoError(format!("Failed to create output directory: {}", e)))?;
+    /// Fails with the chain of imports that leads from `module_name` back to
+    /// itself, when the modules of the program import each other in a loop.
+    fn check_import_cycle(&mut self, ast: &Program, module_name: &str) -> Result<()> {
+        let Some((span, cycle)) = self.import_cycle(ast, module_name) else {
+            return Ok(());
+        };
+        if cycle.len() == 2 {
+            let message = format!("Module `{}` imports itself", module_name);
+            let diagnostic = Diagnostic::error(message.clone(), span.clone())
+                .with_code(codes::IMPORT)
+                .with_suggestion("remove the import", span, "", Applicability::MachineApplicable);
+            self.diagnostics.emit(diagnostic);
+            return Err(Error::SemanticError(message));
+        }
+        let path: Vec<String> = cycle.iter().map(|module| format!("`{}`", module)).collect();
+        let message = format!("Circular import: {}", path.join(" -> "));
+        self.diagnostics.emit(
+            Diagnostic::error(message.clone(), span)
+                .with_code(codes::IMPORT)
+                .with_note("modules cannot import each other, directly or through other modules")
+                .with_help("move what they share into a module that imports none of them"),
+        );
+        Err(Error::SemanticError(message))
+    }
 
-        let rust_file_path = format!("{}/{}.rs", output_dir, module_name);
-        fs::write(&rust_file_path, rust_code)
-            .map_err(|e| Error::IoError(format!("Failed to write Rust file: {}", e)))?;
+    /// The shortest chain of imports from `module_name` back to itself, with
+    /// the import in `ast` it starts with.
+    fn import_cycle(&self, ast: &Program, module_name: &str) -> Option<(Span, Vec<String>)> {
+        let mut queue: VecDeque<(&Span, Vec<&str>)> = ast
+            .imports
+            .iter()
+            .map(|import| (&import.span, vec![module_name, import.module_path.as_str()]))
+            .collect();
+        let mut visited = HashSet::new();
+        while let Some((span, path)) = queue.pop_front() {
+            let last = path[path.len() - 1];
+            if last == module_name {
+                let path = path.into_iter().map(String::from).collect();
+                return Some((span.clone(), path));
+            }
+            let Some(module) = self.module_graph.get(last) else {
+                continue;
+            };
+            if !visited.insert(last) {
+                continue;
+            }
+            for import in &module.imports {
+                let mut path = path.clone();
+                path.push(&import.module_path);
+                queue.push_back((span, path));
+            }
+        }
+        None
+    }
+
+    /// Errors and warnings among the diagnostics reported since there were
+    /// `reported`. Callbacks may have taken diagnostics out of the engine.
+    fn count_since(&self, reported: usize) -> (usize, usize) {
+        let diagnostics = self.diagnostics.diagnostics();
+        let new = &diagnostics[reported.min(diagnostics.len())..];
+        let count = |level| new.iter().filter(|d| d.level == level).count();
+        (count(Level::Error), count(Level::Warning))
+    }
+
+    fn open_source(&self, path: impl AsRef<Path>) -> std::io::Result<SourceFile> {
+        match self.map_sources {
+            true => SourceFile::map(path),
+            false => SourceFile::read(path),
+        }
+    }
 
-        Ok(rust_file_path)
+    /// Keeps the text of `file` for rendering diagnostics. Mapped files are
+    /// too big to copy; the renderer reads them again if it needs them.
+    fn add_source(&mut self, file: &str, source: &SourceFile) {
+        if !source.is_mapped() {
+            self.diagnostics.add_source(file, source);
+        }
     }
 
+    fn run_ast_callbacks(&mut self, module_name: &str, ast: &mut Program) -> Result<()> {
+        let errors = self.diagnostics.error_count();
+        for callbacks in &mut self.callbacks {
+            callbacks.on_ast(module_name, ast, self.diagnostics);
+        }
+        self.check_callback_errors(errors)
+    }
+
+    /// Fails if callbacks reported errors since there were `before` of them.
+    fn check_callback_errors(&self, before: usize) -> Result<()> {
+        match self.diagnostics.error_count().saturating_sub(before) {
+            0 => Ok(()),
+            count => Err(Error::CompilationError(format!(
+                "compiler callbacks reported {} error(s)",
+                count
+            ))),
+        }
+    }
+
+    /// Passes diagnostics reported since the last call to `on_diagnostic`.
+    fn forward_diagnostics(&mut self) {
+        let diagnostics = self.diagnostics.diagnostics();
+        // Someone may have taken diagnostics out of the engine in between.
+        let start = self.forwarded.min(diagnostics.len());
+        for diagnostic in &diagnostics[start..] {
+            for callbacks in &mut self.callbacks {
+                callbacks.on_diagnostic(diagnostic);
+            }
+        }
+        self.forwarded = diagnostics.len();
+    }
+
+    fn is_input(&self, path: &Path) -> bool {
+        match self.input_format {
+            InputFormat::Rustic => utils::is_rustic_source(path),
+            InputFormat::AstJson => utils::is_ast_json(path),
+        }
+    }
+
+    /// The name of the module in `path` under `root`: the directories
+    /// between them and the file's own name, separated by `.` as in
+    /// `import net.http`.
+    fn nested_module_name(&self, root: &Path, path: &Path) -> String {
+        let name = self.module_name(path, "unnamed");
+        let directories = path.strip_prefix(root).ok().and_then(Path::parent);
+        let mut parts: Vec<&str> = directories
+            .into_iter()
+            .flat_map(Path::components)
+            .filter_map(|component| component.as_os_str().to_str())
+            .collect();
+        parts.push(&name);
+        parts.join(".")
+    }
+
+    fn module_name(&self, path: &Path, default: &str) -> String {
+        let name = utils::module_name(path, default);
+        match self.input_format {
+            InputFormat::Rustic => name,
+            InputFormat::AstJson => name.strip_suffix(".ast").unwrap_or(&name).to_string(),
+        }
+    }
+
+    /// Makes a Cargo crate in `output_dir` of the generated modules, which
+    /// `set_native` has them written under `src/` for, builds it and returns
+    /// the path of what it built: the program, when one of the modules defines `main`, or else
+    /// the library. `cargo check` runs first, so a crate that does not build
+    /// fails fast.
     pub fn compile_to_native(
-        &self,
+        &mut self,
         rust_files: &[String],
         output_dir: &str,
     ) -> Result<String> {
+        let _timing = profile::start("driver", "native build").arg("files", rust_files.len());
         let cargo_toml = r#"[package]
 name = "rustic-generated"
 version = "0.1.0"
 edition = "2021"
-
-[dependencies]
-rustic-runtime = { path = "../../../stdlib-runtime" }
 "#;
         let cargo_path = format!("{}/Cargo.toml", output_dir);
         fs::write(&cargo_path, cargo_toml)
             .map_err(|e| Error::IoError(format!("Failed to write Cargo.toml: {}", e)))?;
 
-        let lib_content = rust_files
-            .iter()
-            .map(|path|{
-                let module_name = Path::new(path)
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("unnamed");
-                format!("pub mod: {};", module_name)
-            })
-            .collect::<Vec<_>>()
-            .join("\n");
+        let src_dir = Path::new(output_dir).join("src");
+        fs::create_dir_all(&src_dir)
+            .map_err(|e| Error::IoError(format!("Failed to create src directory: {}", e)))?;
+        let mut lib_content = String::from("// Generated by rustic. Do not edit.\n\n");
+        for file in rust_files {
+            let relative = Path::new(file).strip_prefix(&src_dir).map_err(|_| {
+                Error::IoError(format!("{} is not in the crate's src directory", file))
+            })?;
+            // Nested modules are declared by the modules containing them.
+            if relative.parent() != Some(Path::new("")) {
+                continue;
+            }
+            let module_name = relative
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("unnamed");
+            if self.written.get("main") == Some(file) {
+                lib_content.push_str("#[path = \"main_module.rs\"]\npub mod main;\n");
+            } else {
+                lib_content.push_str(&declare_module(module_name, ""));
+            }
+        }
 
-        let lib_path = format!("{}/lib.rs", output_dir);
-        fs::write(&lib_path, lib_content)
+        fs::write(src_dir.join("lib.rs"), lib_content)
             .map_err(|e| Error::IoError(format!("Failed to write lib.rs: {}", e)))?;
 
+        // Cargo builds `src/main.rs` into a binary named after the package.
+        let entry = self.entry_module()?;
+        let main_path = src_dir.join("main.rs");
+        match &entry {
+            Some(module_name) => {
+                let main_content = format!(
+                    "// Generated by rustic. Do not edit.\n\nfn main() {{\n    \
+                     rustic_generated::{}::main();\n}}\n",
+                    codegen::rust_module_path(module_name)
+                );
+                fs::write(&main_path, main_content)
+                    .map_err(|e| Error::IoError(format!("Failed to write main.rs: {}", e)))?;
+            }
+            // A library, which a binary left from an earlier build must not
+            // be taken for.
+            None if main_path.exists() => {
+                fs::remove_file(&main_path)
+                    .map_err(|e| Error::IoError(format!("Failed to remove main.rs: {}", e)))?;
+            }
+            None => {}
+        }
+
+        self.cargo(&["check", "--quiet"], output_dir, "Generated crate does not build")?;
+        self.cargo(&["build", "--release"], output_dir, "Cargo compilation failed")?;
+
+        let built = match entry {
+            Some(_) => format!("rustic-generated{}", std::env::consts::EXE_SUFFIX),
+            None => "librustic_generated.rlib".to_string(),
+        };
+        Ok(format!("{}/target/release/{}", output_dir, built))
+    }
+
+    /// Runs `cargo` with `args` in `dir`. When it fails, the errors rustc
+    /// found in generated code are reported at the Rustic lines they came
+    /// from, and the error says `failure` and gives what else Cargo printed.
+    fn cargo(&mut self, args: &[&str], dir: &str, failure: &str) -> Result<()> {
         let output = Command::new("cargo")
-            .args(&["build", "--release"])
-            .current_dir(output_dir)
+            .args(args)
+            .arg("--message-format=json")
+            .current_dir(dir)
             .output()
             .map_err(|e| Error::CompilationError(format!("Failed to run cargo: {}", e)))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let details = rustc::report(
+            Path::new(dir),
+            rustc::errors(&String::from_utf8_lossy(&output.stdout)),
+            &String::from_utf8_lossy(&output.stderr),
+            self.diagnostics,
+        );
+        Err(Error::CompilationError(format!("{}:\n{}", failure, details)))
+    }
+
+    /// The module whose `main` a native build runs: the one named `main` if
+    /// it defines one, or else the only module that does.
+    fn entry_module(&self) -> Result<Option<String>> {
+        // Modules the cache let the build skip are not among `modules`.
+        let cached = self.cache.iter().flat_map(BuildCache::iter).filter(|(name, cached)| {
+            cached.main && self.written.contains_key(*name) && !self.modules.contains_key(*name)
+        });
+        let mut entries: Vec<&str> = self
+            .modules()
+            .filter(|(_, ast)| defines_main(ast))
+            .map(|(name, _)| name)
+            .chain(cached.map(|(name, _)| name.as_str()))
+            .collect();
+        entries.sort_unstable();
+        match entries.as_slice() {
+            [] => Ok(None),
+            [entry] => Ok(Some(entry.to_string())),
+            _ if entries.contains(&"main") => Ok(Some("main".to_string())),
+            _ => Err(Error::CompilationError(format!(
+                "Modules {} all define `main`; name the one to run `main.rsc`",
+                entries
+                    .iter()
+                    .map(|name| format!("`{}`", name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))),
+        }
+    }
+}
+
+fn defines_main(ast: &Program) -> bool {
+    ast.items
+        .iter()
+        .any(|item| matches!(item, Item::Function(function) if function.name == "main"))
+}
+
+/// Where the output for `module_name` goes under the output directory,
+/// without the extension: `net/http` for `net.http`.
+fn module_path(module_name: &str) -> String {
+    module_name.replace('.', "/")
+}
 
-        if !output.status.success() {
-            let stderr = String::from_utf16_lossy(&output.stderr);
-            return Err(Error::CompilationError(format!(
-                "Cargo compilation failed: \n{}",
-                stderr
-            )));
+/// `pub mod name;` for the module in `name.rs` under `directory`, relative
+/// to the file declaring it. A name that is a Rust keyword is declared as a
+/// raw identifier, or else under the name `codegen::rust_name` gives it,
+/// with a `#[path]` to its file.
+fn declare_module(name: &str, directory: &str) -> String {
+    match codegen::rust_name(name) {
+        Cow::Owned(rust) if !rust.starts_with("r#") => {
+            format!("#[path = \"{}{}.rs\"]\npub mod {};\n", directory, name, rust)
         }
+        rust => format!("pub mod {};\n", rust),
+    }
+}
 
-        let binary_path = format!("{}/target/release/rustic-generated", output_dir);
-        Ok(binary_path)
+/// Declares each nested module of `written` in the Rust file of the module
+/// containing it, when that file was just written and is in `rewritten`. A
+/// directory no module is named after gets a file of its own, whose path is
+/// returned.
+fn declare_submodules(
+    written: &[&String],
+    rewritten: &BTreeSet<String>,
+    output_dir: &str,
+) -> Result<Vec<String>> {
+    let mut children: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for module_name in written {
+        let mut parent = module_name.as_str();
+        while let Some((outer, child)) = parent.rsplit_once('.') {
+            children.entry(outer).or_default().insert(child);
+            parent = outer;
         }
+    }
+
+    let mut created = Vec::new();
+    for (parent, children) in children {
+        let path = format!("{}/{}.rs", output_dir, module_path(parent));
+        let mut content = if written.iter().any(|name| *name == parent) {
+            if !rewritten.contains(parent) {
+                continue;
+            }
+            fs::read_to_string(&path)
+                .map_err(|e| Error::IoError(format!("Failed to read {}: {}", path, e)))?
+        } else {
+            created.push(path.clone());
+            format!("// Generated by rustic for the `{}` directory. Do not edit.\n", parent)
+        };
+        content.push('\n');
+        let directory = parent.rsplit('.').next().unwrap_or(parent);
+        for child in children {
+            content.push_str(&declare_module(child, &format!("{}/", directory)));
+        }
+        fs::write(&path, content)
+            .map_err(|e| Error::IoError(format!("Failed to write {}: {}", path, e)))?;
+    }
+    Ok(created)
 }
@@ -0,0 +1,1902 @@
+use crate::compiler::ast::*;
+use crate::compiler::intern::Symbol;
+use crate::compiler::lexer::{StringPart, Token, TokenType};
+use crate::diagnostics::{codes, Applicability, Diagnostic, DiagnosticEngine, Error, Result, Span};
+use std::collections::HashMap;
+
+/// Deepest nesting of blocks, expressions and types accepted before the parser
+/// reports an error, keeping pathological input from overflowing the stack.
+pub const MAX_NESTING_DEPTH: usize = 256;
+
+pub struct Parser<'a> {
+    tokens: Vec<Token>,
+    current: usize,
+    depth: usize,
+    diagnostics: &'a mut DiagnosticEngine,
+    /// Syntax errors recovered from so far; `parse` fails if there are any.
+    errors: Vec<Error>,
+    /// Diagnostics reported by the parser itself, leaving out errors at
+    /// `Error` tokens, which the lexer already reported.
+    reported: usize,
+    /// Lexical errors carried in by `Error` tokens.
+    lex_errors: Vec<Error>,
+    /// Type parameters of the items being parsed, which types may name.
+    type_params: Vec<Symbol>,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: Vec<Token>, diagnostics: &'a mut DiagnosticEngine) -> Self {
+        let lex_errors = tokens
+            .iter()
+            .filter_map(|token| match &token.token_type {
+                TokenType::Error(message) => {
+                    Some(Error::LexError(format!("{} at {}", message, token.span)))
+                }
+                _ => None,
+            })
+            .collect();
+        Self {
+            tokens,
+            current: 0,
+            depth: 0,
+            diagnostics,
+            errors: Vec::new(),
+            reported: 0,
+            lex_errors,
+            type_params: Vec::new(),
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<Program> {
+        let mut items = Vec::new();
+        let mut imports = Vec::new();
+
+        self.skip_newlines();
+        while !self.is_at_end() {
+            let result = if self.check(&TokenType::Import) {
+                self.parse_import().map(|import| imports.push(import))
+            } else {
+                self.parse_item().map(|item| items.push(item))
+            };
+            if let Err(error) = result.and_then(|()| self.expect_terminator()) {
+                self.errors.push(error);
+                self.synchronize_item();
+            }
+            self.skip_newlines();
+        }
+
+        match self.reported + self.lex_errors.len() {
+            0 => Ok(Program { items, imports }),
+            1 if self.reported == 0 => Err(self.lex_errors.remove(0)),
+            1 => Err(self.errors.remove(0)),
+            count => Err(Error::ParseError(format!("found {} syntax errors", count))),
+        }
+    }
+
+    fn parse_import(&mut self) -> Result<Import> {
+        let start = self.expect(TokenType::Import)?;
+        let mut module_path = self.expect_identifier()?.to_string();
+        let mut items = None;
+        while self.match_token(&TokenType::Dot) {
+            if self.check(&TokenType::LeftBrace) {
+                items = Some(self.parse_imported_items()?);
+                break;
+            }
+            module_path.push('.');
+            module_path.push_str(&self.expect_identifier()?);
+        }
+        let alias = match items {
+            None if self.match_token(&TokenType::As) => Some(self.expect_identifier()?),
+            _ => None,
+        };
+
+        Ok(Import {
+            module_path,
+            alias,
+            items,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// `{a, b as c}` after the module path of a selective import.
+    fn parse_imported_items(&mut self) -> Result<Vec<ImportedItem>> {
+        let start = self.expect(TokenType::LeftBrace)?;
+        let mut items = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) {
+            let item_start = self.peek_span();
+            let name = self.expect_identifier()?;
+            let alias = if self.match_token(&TokenType::As) {
+                Some(self.expect_identifier()?)
+            } else {
+                None
+            };
+            items.push(ImportedItem {
+                name,
+                alias,
+                span: self.span_from(&item_start),
+            });
+            self.skip_newlines();
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+            self.skip_newlines();
+        }
+        self.expect_list_end(TokenType::RightBrace)?;
+        if items.is_empty() {
+            let span = self.span_from(&start);
+            return Err(self.error("Expected at least one item to import", span));
+        }
+        Ok(items)
+    }
+
+    /// An item with the doc comment before it, if any.
+    fn parse_item(&mut self) -> Result<Item> {
+        let docs_span = self.peek_span();
+        let docs = self.parse_docs();
+        let mut item = self.parse_undocumented_item()?;
+        match &mut item {
+            _ if docs.is_empty() => {}
+            Item::Function(function) => function.docs = docs,
+            Item::Struct(structure) => structure.docs = docs,
+            _ => {
+                self.error(
+                    "Doc comments can only document `fn` and `struct`",
+                    docs_span,
+                );
+            }
+        }
+        Ok(item)
+    }
+
+    /// The lines of the `///` comments before an item.
+    fn parse_docs(&mut self) -> Vec<String> {
+        let mut docs = Vec::new();
+        while let TokenType::DocComment(line) = self.peek_type() {
+            docs.push(line.clone());
+            self.advance();
+            self.skip_newlines();
+        }
+        docs
+    }
+
+    fn parse_undocumented_item(&mut self) -> Result<Item> {
+        if self.check(&TokenType::At) {
+            return self.parse_attributed_item();
+        }
+        if self.check(&TokenType::Pub) {
+            return self.parse_public_item();
+        }
+        match self.peek_type() {
+            TokenType::Fn => Ok(Item::Function(self.parse_function()?)),
+            TokenType::Struct => Ok(Item::Struct(self.parse_struct()?)),
+            TokenType::Exception => Ok(Item::Struct(self.parse_exception()?)),
+            TokenType::Enum => Ok(Item::Enum(self.parse_enum()?)),
+            TokenType::Impl => Ok(Item::Impl(self.parse_impl()?)),
+            TokenType::Let | TokenType::Var => Ok(Item::Variable(self.parse_variable()?)),
+            TokenType::Const => Ok(Item::Constant(self.parse_constant()?)),
+            TokenType::Identifier(word) if FUNCTION_KEYWORDS.contains(&word.as_str()) => {
+                let diagnostic = Diagnostic::error(
+                    format!("Expected an item, found `{}`", word),
+                    self.peek_span(),
+                )
+                .with_suggestion(
+                    "declare the function with",
+                    self.peek_span(),
+                    "fn",
+                    Applicability::MachineApplicable,
+                );
+                Err(self.report(diagnostic))
+            }
+            _ => Err(self.error_at_current(
+                "Expected an item (`fn`, `struct`, `exception`, `enum`, `impl`, `let`, `var` or \
+                 `const`)",
+            )),
+        }
+    }
+
+    /// `pub` and the item it exports. Attributes come before the `pub`.
+    fn parse_public_item(&mut self) -> Result<Item> {
+        let keyword = self.expect(TokenType::Pub)?;
+        match self.peek_type() {
+            TokenType::Impl => {
+                let diagnostic =
+                    Diagnostic::error("`impl` blocks cannot be `pub`", keyword.clone())
+                        .with_help("mark the methods other modules may call `pub` instead");
+                return Err(self.report(diagnostic));
+            }
+            TokenType::Pub | TokenType::At => {
+                return Err(self.error_at_current(format!(
+                    "Expected an item after `pub`, found {}",
+                    self.peek_type()
+                )))
+            }
+            _ => {}
+        }
+        let mut item = self.parse_undocumented_item()?;
+        match &mut item {
+            Item::Function(function) => function.public = true,
+            Item::Struct(structure) => structure.public = true,
+            Item::Enum(enumeration) => enumeration.public = true,
+            Item::Impl(_) => unreachable!("`pub impl` is rejected above"),
+            Item::Variable(variable) => variable.public = true,
+            Item::Constant(constant) => constant.public = true,
+        }
+        Ok(item)
+    }
+
+    /// An item preceded by an attribute. `@deprecated` is the only one, and
+    /// applies to functions and structs.
+    fn parse_attributed_item(&mut self) -> Result<Item> {
+        let start = self.expect(TokenType::At)?;
+        let name_span = self.peek_span();
+        let name = self.expect_identifier()?;
+        if name != "deprecated" {
+            return Err(self.error(format!("Unknown attribute `@{}`", name), name_span));
+        }
+        let note = if self.match_token(&TokenType::LeftParen) {
+            let note = match self.peek_type() {
+                TokenType::String(note) => note.clone(),
+                other => {
+                    return Err(self.error_at_current(format!(
+                        "Expected a string explaining the deprecation, found {}",
+                        other
+                    )))
+                }
+            };
+            self.advance();
+            self.expect(TokenType::RightParen)?;
+            Some(note)
+        } else {
+            None
+        };
+        let deprecation = Deprecation {
+            note,
+            span: self.span_from(&start),
+        };
+        self.skip_newlines();
+        let public = self.match_token(&TokenType::Pub);
+
+        match self.peek_type() {
+            TokenType::Fn => {
+                let mut function = self.parse_function()?;
+                function.public = public;
+                function.deprecated = Some(deprecation);
+                Ok(Item::Function(function))
+            }
+            TokenType::Struct => {
+                let mut structure = self.parse_struct()?;
+                structure.public = public;
+                structure.deprecated = Some(deprecation);
+                Ok(Item::Struct(structure))
+            }
+            _ => {
+                Err(self.error_at_current("`@deprecated` can only be applied to `fn` and `struct`"))
+            }
+        }
+    }
+
+    fn parse_function(&mut self) -> Result<Function> {
+        let start = self.expect(TokenType::Fn)?;
+        let name = self.expect_identifier()?;
+        let type_params = self.parse_type_params()?;
+        self.expect(TokenType::LeftParen)?;
+        self.parse_function_rest(name, type_params, start)
+    }
+
+    /// A method: a function whose first parameter is `self` or `var self`.
+    fn parse_method(&mut self) -> Result<Method> {
+        let start = self.expect(TokenType::Fn)?;
+        let name = self.expect_identifier()?;
+        let type_params = self.parse_type_params()?;
+        self.expect(TokenType::LeftParen)?;
+        self.skip_newlines();
+        let mutable = self.match_token(&TokenType::Var);
+        if !self.match_token(&TokenType::SelfValue) {
+            return Err(self.error_at_current(format!(
+                "Expected `self` as the first parameter of method `{}`",
+                name
+            )));
+        }
+        self.skip_newlines();
+        if !self.check(&TokenType::RightParen) {
+            self.expect(TokenType::Comma)?;
+        }
+        let function = self.parse_function_rest(name, type_params, start)?;
+        Ok(Method { mutable, function })
+    }
+
+    /// The rest of a function from its parameters, after the `(`.
+    fn parse_function_rest(
+        &mut self,
+        name: Symbol,
+        type_params: Vec<Symbol>,
+        start: Span,
+    ) -> Result<Function> {
+        let mut function = self.with_type_params(&type_params, |parser| {
+            parser.parse_parameters_and_body(name, start)
+        })?;
+        function.type_params = type_params;
+        Ok(function)
+    }
+
+    fn parse_parameters_and_body(&mut self, name: Symbol, start: Span) -> Result<Function> {
+        let mut parameters = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightParen) {
+            parameters.push(self.parse_parameter()?);
+            self.skip_newlines();
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+            self.skip_newlines();
+        }
+        self.expect_list_end(TokenType::RightParen)?;
+
+        let return_type = if self.match_token(&TokenType::Arrow) {
+            self.parse_type()?
+        } else {
+            Type::Void
+        };
+
+        let body = self.parse_block()?;
+
+        Ok(Function {
+            name,
+            type_params: Vec::new(),
+            parameters,
+            return_type,
+            body,
+            deprecated: None,
+            docs: Vec::new(),
+            public: false,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_parameter(&mut self) -> Result<Parameter> {
+        let start = self.peek_span();
+        let name = self.expect_identifier()?;
+        self.expect(TokenType::Colon)?;
+        let param_type = self.parse_type()?;
+        let default_value = if self.match_token(&TokenType::Assign) {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+
+        Ok(Parameter {
+            name,
+            param_type,
+            default_value,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_struct(&mut self) -> Result<Struct> {
+        let start = self.expect(TokenType::Struct)?;
+        let name = self.expect_identifier()?;
+        let type_params = self.parse_type_params()?;
+        let fields = self.with_type_params(&type_params, Self::parse_fields)?;
+
+        Ok(Struct {
+            name,
+            type_params,
+            fields,
+            deprecated: None,
+            docs: Vec::new(),
+            exception: false,
+            public: false,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// `exception NotFound { path: str }`: a struct that can be thrown.
+    fn parse_exception(&mut self) -> Result<Struct> {
+        let start = self.expect(TokenType::Exception)?;
+        let name = self.expect_identifier()?;
+        let fields = self.parse_fields()?;
+
+        Ok(Struct {
+            name,
+            type_params: Vec::new(),
+            fields,
+            deprecated: None,
+            docs: Vec::new(),
+            exception: true,
+            public: false,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_fields(&mut self) -> Result<Vec<Field>> {
+        self.expect(TokenType::LeftBrace)?;
+        let mut fields = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) {
+            let field_start = self.peek_span();
+            let field_name = self.expect_identifier()?;
+            self.expect(TokenType::Colon)?;
+            let field_type = self.parse_type()?;
+            fields.push(Field {
+                name: field_name,
+                field_type,
+                span: self.span_from(&field_start),
+            });
+
+            let separated = self.match_token(&TokenType::Comma);
+            if !separated && !self.check(&TokenType::Newline) && !self.check(&TokenType::RightBrace)
+            {
+                return Err(self.error_at_current("Expected `,` or newline after struct field"));
+            }
+            self.skip_newlines();
+        }
+        self.expect(TokenType::RightBrace)?;
+        Ok(fields)
+    }
+
+    fn parse_impl(&mut self) -> Result<Impl> {
+        let start = self.expect(TokenType::Impl)?;
+        let struct_name = self.expect_identifier()?;
+        let type_params = self.parse_type_params()?;
+        let methods = self.with_type_params(&type_params, Self::parse_methods)?;
+
+        Ok(Impl {
+            struct_name,
+            type_params,
+            methods,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_methods(&mut self) -> Result<Vec<Method>> {
+        self.expect(TokenType::LeftBrace)?;
+        let mut methods = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) {
+            let docs = self.parse_docs();
+            let public = self.match_token(&TokenType::Pub);
+            let mut method = self.parse_method()?;
+            method.function.docs = docs;
+            method.function.public = public;
+            methods.push(method);
+            self.skip_newlines();
+        }
+        self.expect(TokenType::RightBrace)?;
+        Ok(methods)
+    }
+
+    /// `[T, U]` after the name of a generic item, if present.
+    fn parse_type_params(&mut self) -> Result<Vec<Symbol>> {
+        let mut type_params = Vec::new();
+        if !self.match_token(&TokenType::LeftBracket) {
+            return Ok(type_params);
+        }
+        while !self.check(&TokenType::RightBracket) {
+            type_params.push(self.expect_identifier()?);
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+        }
+        self.expect_list_end(TokenType::RightBracket)?;
+        Ok(type_params)
+    }
+
+    /// Runs `parse` with `type_params` in scope for the types it parses.
+    fn with_type_params<T>(
+        &mut self,
+        type_params: &[Symbol],
+        parse: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let outer = self.type_params.len();
+        self.type_params.extend_from_slice(type_params);
+        let result = parse(self);
+        self.type_params.truncate(outer);
+        result
+    }
+
+    fn parse_enum(&mut self) -> Result<Enum> {
+        let start = self.expect(TokenType::Enum)?;
+        let name = self.expect_identifier()?;
+
+        self.expect(TokenType::LeftBrace)?;
+        let mut variants = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) {
+            let variant_start = self.peek_span();
+            let variant_name = self.expect_identifier()?;
+            let mut payload = Vec::new();
+            if self.match_token(&TokenType::LeftParen) {
+                self.skip_newlines();
+                while !self.check(&TokenType::RightParen) {
+                    payload.push(self.parse_type()?);
+                    self.skip_newlines();
+                    if !self.match_token(&TokenType::Comma) {
+                        break;
+                    }
+                    self.skip_newlines();
+                }
+                self.expect_list_end(TokenType::RightParen)?;
+            }
+            variants.push(Variant {
+                name: variant_name,
+                payload,
+                span: self.span_from(&variant_start),
+            });
+
+            let separated = self.match_token(&TokenType::Comma);
+            if !separated && !self.check(&TokenType::Newline) && !self.check(&TokenType::RightBrace)
+            {
+                return Err(self.error_at_current("Expected `,` or newline after enum variant"));
+            }
+            self.skip_newlines();
+        }
+        self.expect(TokenType::RightBrace)?;
+
+        Ok(Enum {
+            name,
+            variants,
+            public: false,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_variable(&mut self) -> Result<Variable> {
+        let start = self.peek_span();
+        let mutable = match self.peek_type() {
+            TokenType::Var => true,
+            TokenType::Let => false,
+            _ => return Err(self.error_at_current("Expected `let` or `var`")),
+        };
+        self.advance();
+
+        if !mutable && self.check(&TokenType::Identifier(Symbol::intern("mut"))) {
+            let mutability = start.to(&self.peek_span());
+            let diagnostic = Diagnostic::error(
+                "Mutable variables are declared with `var`, not `let mut`",
+                mutability.clone(),
+            )
+            .with_suggestion(
+                "declare the variable with",
+                mutability,
+                "var",
+                Applicability::MachineApplicable,
+            );
+            return Err(self.report(diagnostic));
+        }
+
+        let name = self.expect_identifier()?;
+        let var_type = if self.match_token(&TokenType::Colon) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        // `var x: int` is assigned later; without a type, the initializer
+        // is what gives the variable one.
+        let initializer = if var_type.is_some() && !self.check(&TokenType::Assign) {
+            if !mutable {
+                let keyword = start.clone();
+                let diagnostic = Diagnostic::error(
+                    format!("`{}` is declared with `let` but has no value", name),
+                    self.span_from(&start),
+                )
+                .with_suggestion(
+                    "declare it with `var` to assign it later",
+                    keyword,
+                    "var",
+                    Applicability::MaybeIncorrect,
+                );
+                return Err(self.report(diagnostic));
+            }
+            None
+        } else {
+            self.expect(TokenType::Assign)?;
+            Some(self.parse_expression()?)
+        };
+
+        Ok(Variable {
+            name,
+            var_type,
+            initializer,
+            mutable,
+            public: false,
+            span: self.span_from(&start),
+        })
+    }
+
+    /// `let (x, y) = pair`. The variables take their types from the tuple.
+    fn parse_destructure(&mut self) -> Result<Destructure> {
+        let start = self.peek_span();
+        let mutable = self.advance().token_type == TokenType::Var;
+        self.expect(TokenType::LeftParen)?;
+        let mut names = Vec::new();
+        while !self.check(&TokenType::RightParen) {
+            let span = self.peek_span();
+            let name = self.expect_identifier()?;
+            names.push(Identifier { name, span });
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+        }
+        self.expect_list_end(TokenType::RightParen)?;
+        if names.len() < 2 {
+            let span = self.span_from(&start);
+            return Err(self.error("Destructuring needs at least two names", span));
+        }
+        self.expect(TokenType::Assign)?;
+        let initializer = self.parse_expression()?;
+
+        Ok(Destructure {
+            names,
+            initializer,
+            mutable,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_constant(&mut self) -> Result<Constant> {
+        let start = self.expect(TokenType::Const)?;
+        let name = self.expect_identifier()?;
+        self.expect(TokenType::Colon)?;
+        let const_type = self.parse_type()?;
+        self.expect(TokenType::Assign)?;
+        let value = self.parse_expression()?;
+
+        Ok(Constant {
+            name,
+            const_type,
+            value,
+            public: false,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_type(&mut self) -> Result<Type> {
+        let start = self.peek_span();
+        let mut ty = self.parse_base_type()?;
+        while self.match_token(&TokenType::Question) {
+            // `void?` stands for the type of a bare `none`.
+            if ty == Type::Void {
+                return Err(self.error("`void` cannot be optional", self.span_from(&start)));
+            }
+            ty = Type::Optional(Box::new(ty));
+        }
+        Ok(ty)
+    }
+
+    fn parse_base_type(&mut self) -> Result<Type> {
+        let start = self.current;
+        let token = self.advance();
+        match token.token_type {
+            TokenType::IntType => Ok(Type::Int),
+            TokenType::FloatType => Ok(Type::Float),
+            TokenType::StrType => Ok(Type::Str),
+            TokenType::BoolType => Ok(Type::Bool),
+            TokenType::VoidType => Ok(Type::Void),
+            TokenType::ListType => {
+                self.expect(TokenType::LeftBracket)?;
+                let element = self.nested(Self::parse_type)?;
+                self.expect(TokenType::RightBracket)?;
+                Ok(Type::List(Box::new(element)))
+            }
+            TokenType::MapType => {
+                self.expect(TokenType::LeftBracket)?;
+                let key = self.nested(Self::parse_type)?;
+                self.expect(TokenType::Comma)?;
+                let value = self.nested(Self::parse_type)?;
+                self.expect(TokenType::RightBracket)?;
+                Ok(Type::Map(Box::new(key), Box::new(value)))
+            }
+            TokenType::Identifier(name) if self.type_params.contains(&name) => {
+                Ok(Type::Param(name))
+            }
+            TokenType::Identifier(name) if self.match_token(&TokenType::LeftBracket) => {
+                let mut arguments = Vec::new();
+                while !self.check(&TokenType::RightBracket) {
+                    arguments.push(self.nested(Self::parse_type)?);
+                    if !self.match_token(&TokenType::Comma) {
+                        break;
+                    }
+                }
+                self.expect_list_end(TokenType::RightBracket)?;
+                Ok(Type::Generic(name, arguments))
+            }
+            TokenType::Identifier(name) => Ok(Type::Struct(name)),
+            TokenType::Fn => {
+                self.expect(TokenType::LeftParen)?;
+                let mut parameters = Vec::new();
+                while !self.check(&TokenType::RightParen) {
+                    parameters.push(self.nested(Self::parse_type)?);
+                    if !self.match_token(&TokenType::Comma) {
+                        break;
+                    }
+                }
+                self.expect_list_end(TokenType::RightParen)?;
+                let return_type = if self.match_token(&TokenType::Arrow) {
+                    self.nested(Self::parse_type)?
+                } else {
+                    Type::Void
+                };
+                Ok(Type::Function(parameters, Box::new(return_type)))
+            }
+            // A tuple, or parentheses around a function type made optional:
+            // `(fn() -> int)?`.
+            TokenType::LeftParen => {
+                let ty = self.nested(Self::parse_type)?;
+                if !self.match_token(&TokenType::Comma) {
+                    self.expect(TokenType::RightParen)?;
+                    return Ok(ty);
+                }
+                let mut elements = vec![ty];
+                while !self.check(&TokenType::RightParen) {
+                    elements.push(self.nested(Self::parse_type)?);
+                    if !self.match_token(&TokenType::Comma) {
+                        break;
+                    }
+                }
+                self.expect_list_end(TokenType::RightParen)?;
+                if elements.len() < 2 {
+                    let span = self.span_from(&token.span);
+                    return Err(self.error("Tuples need at least two elements", span));
+                }
+                Ok(Type::Tuple(elements))
+            }
+            other => {
+                // Leave the offending token for error recovery to stop at.
+                self.current = start;
+                Err(self.error(format!("Expected a type, found {}", other), token.span))
+            }
+        }
+    }
+
+    fn parse_block(&mut self) -> Result<Block> {
+        self.nested(Self::parse_block_contents)
+    }
+
+    fn parse_block_contents(&mut self) -> Result<Block> {
+        let start = self.expect(TokenType::LeftBrace)?;
+        let mut statements = Vec::new();
+
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            let statement_start = self.current;
+            let result = self
+                .parse_statement()
+                .and_then(|statement| self.expect_terminator().map(|()| statement));
+            match result {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize_statement(statement_start);
+                }
+            }
+            self.skip_newlines();
+        }
+        self.expect(TokenType::RightBrace)?;
+
+        Ok(Block {
+            statements,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement> {
+        match self.peek_type() {
+            TokenType::DocComment(_) => {
+                let span = self.peek_span();
+                let slashes = Span {
+                    end_line: span.start_line,
+                    end_column: span.start_column + 3,
+                    end_offset: span.start_offset + 3,
+                    ..span.clone()
+                };
+                let diagnostic = Diagnostic::error("Doc comments can only document items", span)
+                    .with_suggestion(
+                        "use an ordinary comment",
+                        slashes,
+                        "//",
+                        Applicability::MachineApplicable,
+                    );
+                Err(self.report(diagnostic))
+            }
+            TokenType::Let | TokenType::Var
+                if self
+                    .tokens
+                    .get(self.current + 1)
+                    .map(|token| &token.token_type)
+                    == Some(&TokenType::LeftParen) =>
+            {
+                Ok(Statement::Destructure(self.parse_destructure()?))
+            }
+            TokenType::Let | TokenType::Var => Ok(Statement::Variable(self.parse_variable()?)),
+            TokenType::If => Ok(Statement::If(self.parse_if()?)),
+            TokenType::For => Ok(Statement::For(self.parse_for()?)),
+            TokenType::While => Ok(Statement::While(self.parse_while()?)),
+            TokenType::Try => Ok(Statement::Try(self.parse_try()?)),
+            TokenType::Throw => {
+                let start = self.expect(TokenType::Throw)?;
+                let value = self.parse_expression()?;
+                Ok(Statement::Throw(ThrowStatement {
+                    value,
+                    span: self.span_from(&start),
+                }))
+            }
+            TokenType::Return => Ok(Statement::Return(self.parse_return()?)),
+            TokenType::Break => {
+                let span = self.expect(TokenType::Break)?;
+                Ok(Statement::Break(BreakStatement { span }))
+            }
+            TokenType::Continue => {
+                let span = self.expect(TokenType::Continue)?;
+                Ok(Statement::Continue(ContinueStatement { span }))
+            }
+            _ => self.parse_expression_statement(),
+        }
+    }
+
+    fn parse_if(&mut self) -> Result<IfStatement> {
+        let start = self.expect(TokenType::If)?;
+        let condition = self.parse_expression_no_struct()?;
+        let then_block = self.parse_block()?;
+
+        let mut else_ifs = Vec::new();
+        let mut else_block = None;
+        while self.check_after_newlines(&TokenType::Else) {
+            self.skip_newlines();
+            self.expect(TokenType::Else)?;
+            if self.match_token(&TokenType::If) {
+                let condition = self.parse_expression_no_struct()?;
+                let block = self.parse_block()?;
+                else_ifs.push((condition, block));
+            } else {
+                else_block = Some(self.parse_block()?);
+                break;
+            }
+        }
+
+        Ok(IfStatement {
+            condition,
+            then_block,
+            else_ifs,
+            else_block,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_for(&mut self) -> Result<ForLoop> {
+        let start = self.expect(TokenType::For)?;
+        let variable = self.expect_identifier()?;
+        self.expect(TokenType::In)?;
+        let iterable = self.parse_expression_no_struct()?;
+        let body = self.parse_block()?;
+
+        Ok(ForLoop {
+            variable,
+            iterable,
+            body,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_while(&mut self) -> Result<WhileLoop> {
+        let start = self.expect(TokenType::While)?;
+        let condition = self.parse_expression_no_struct()?;
+        let body = self.parse_block()?;
+
+        Ok(WhileLoop {
+            condition,
+            body,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_try(&mut self) -> Result<TryStatement> {
+        let start = self.expect(TokenType::Try)?;
+        let try_block = self.parse_block()?;
+
+        let mut catch_clauses = Vec::new();
+        while self.check_after_newlines(&TokenType::Catch) {
+            self.skip_newlines();
+            let catch_start = self.expect(TokenType::Catch)?;
+            let exception_type = self.expect_identifier()?;
+            let binding = if self.match_token(&TokenType::As) {
+                Some(self.expect_identifier()?)
+            } else {
+                None
+            };
+            let handler_block = self.parse_block()?;
+            catch_clauses.push(CatchClause {
+                exception_type,
+                binding,
+                handler_block,
+                span: self.span_from(&catch_start),
+            });
+        }
+
+        let finally_block = if self.check_after_newlines(&TokenType::Finally) {
+            self.skip_newlines();
+            self.expect(TokenType::Finally)?;
+            Some(self.parse_block()?)
+        } else {
+            None
+        };
+
+        if catch_clauses.is_empty() && finally_block.is_none() {
+            return Err(
+                self.error_at_current("Expected a `catch` or `finally` clause after `try` block")
+            );
+        }
+
+        Ok(TryStatement {
+            try_block,
+            catch_clauses,
+            finally_block,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_return(&mut self) -> Result<ReturnStatement> {
+        let start = self.expect(TokenType::Return)?;
+        let value = if self.at_statement_end() {
+            None
+        } else {
+            Some(self.parse_expression()?)
+        };
+
+        Ok(ReturnStatement {
+            value,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn parse_expression_statement(&mut self) -> Result<Statement> {
+        let start = self.peek_span();
+        let expression = self.parse_expression()?;
+
+        let operator = match self.peek_type() {
+            TokenType::Assign => Some(None),
+            TokenType::PlusAssign => Some(Some(BinaryOperator::Add)),
+            TokenType::MinusAssign => Some(Some(BinaryOperator::Sub)),
+            TokenType::StarAssign => Some(Some(BinaryOperator::Mul)),
+            TokenType::SlashAssign => Some(Some(BinaryOperator::Div)),
+            TokenType::PercentAssign => Some(Some(BinaryOperator::Mod)),
+            _ => None,
+        };
+        if let Some(operator) = operator {
+            self.advance();
+            if !matches!(
+                expression,
+                Expression::Identifier(_) | Expression::MemberAccess(_) | Expression::Index(_)
+            ) {
+                return Err(self.error("Invalid assignment target", self.span_from(&start)));
+            }
+            let value = self.parse_expression()?;
+            return Ok(Statement::Assignment(Assignment {
+                target: expression,
+                operator,
+                value,
+                span: self.span_from(&start),
+            }));
+        }
+
+        Ok(Statement::Expression(expression))
+    }
+
+    pub fn parse_expression(&mut self) -> Result<Expression> {
+        self.nested(|parser| parser.parse_range(true))
+    }
+
+    /// Parses an expression in a position followed by a block (`if`, `for`),
+    /// where `Name {` must start the block rather than a struct initializer.
+    fn parse_expression_no_struct(&mut self) -> Result<Expression> {
+        self.nested(|parser| parser.parse_range(false))
+    }
+
+    /// Ranges bind more loosely than any binary operator and do not chain.
+    fn parse_range(&mut self, allow_struct: bool) -> Result<Expression> {
+        let start = self.peek_span();
+        let expression = self.parse_binary(0, allow_struct)?;
+        let inclusive = match self.peek_type() {
+            TokenType::DotDot => false,
+            TokenType::DotDotEqual => true,
+            _ => return Ok(expression),
+        };
+        self.advance();
+        let end = self.parse_binary(0, allow_struct)?;
+        Ok(Expression::Range(RangeExpression {
+            start: Box::new(expression),
+            end: Box::new(end),
+            inclusive,
+            span: self.span_from(&start),
+        }))
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8, allow_struct: bool) -> Result<Expression> {
+        let start = self.peek_span();
+        let mut left = self.parse_cast(allow_struct)?;
+
+        while let Some((operator, precedence)) = binary_operator(self.peek_type()) {
+            if precedence < min_precedence {
+                break;
+            }
+            self.advance();
+            self.skip_newlines();
+            let right = self.parse_binary(precedence + 1, allow_struct)?;
+            left = Expression::Binary(BinaryOp {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+                span: self.span_from(&start),
+            });
+        }
+
+        Ok(left)
+    }
+
+    /// A unary expression followed by any number of `as T` casts.
+    fn parse_cast(&mut self, allow_struct: bool) -> Result<Expression> {
+        let start = self.peek_span();
+        let mut value = self.parse_unary(allow_struct)?;
+        while self.match_token(&TokenType::As) {
+            let target = self.parse_type()?;
+            value = Expression::Cast(CastExpression {
+                value: Box::new(value),
+                target,
+                span: self.span_from(&start),
+            });
+        }
+        Ok(value)
+    }
+
+    fn parse_unary(&mut self, allow_struct: bool) -> Result<Expression> {
+        let start = self.peek_span();
+        let operator = match self.peek_type() {
+            TokenType::Minus => UnaryOperator::Neg,
+            TokenType::Not => UnaryOperator::Not,
+            TokenType::Tilde => UnaryOperator::BitNot,
+            _ => return self.parse_power(allow_struct),
+        };
+        self.advance();
+
+        let operand = self.nested(|parser| parser.parse_unary(allow_struct))?;
+        Ok(Expression::Unary(UnaryOp {
+            operator,
+            operand: Box::new(operand),
+            span: self.span_from(&start),
+        }))
+    }
+
+    /// `base ** exponent`. It binds tighter than a prefix operator before it,
+    /// so `-x ** 2` is `-(x ** 2)`, and associates to the right.
+    fn parse_power(&mut self, allow_struct: bool) -> Result<Expression> {
+        let start = self.peek_span();
+        let base = self.parse_postfix(allow_struct)?;
+        if !self.match_token(&TokenType::StarStar) {
+            return Ok(base);
+        }
+        self.skip_newlines();
+        let exponent = self.nested(|parser| parser.parse_unary(allow_struct))?;
+        Ok(Expression::Binary(BinaryOp {
+            left: Box::new(base),
+            operator: BinaryOperator::Pow,
+            right: Box::new(exponent),
+            span: self.span_from(&start),
+        }))
+    }
+
+    fn parse_postfix(&mut self, allow_struct: bool) -> Result<Expression> {
+        let start = self.peek_span();
+        let mut expression = self.parse_primary(allow_struct)?;
+
+        loop {
+            if self.match_token(&TokenType::LeftParen) {
+                let (arguments, named_arguments) = self.parse_arguments()?;
+                expression = Expression::Call(FunctionCall {
+                    function: Box::new(expression),
+                    arguments,
+                    named_arguments,
+                    span: self.span_from(&start),
+                });
+            } else if self.match_token(&TokenType::LeftBracket) {
+                expression = self.parse_index(expression, &start)?;
+            } else if self.match_token(&TokenType::Dot) {
+                let member = self.expect_identifier()?;
+                expression = Expression::MemberAccess(MemberAccess {
+                    object: Box::new(expression),
+                    member,
+                    span: self.span_from(&start),
+                });
+            } else {
+                break;
+            }
+        }
+
+        Ok(expression)
+    }
+
+    /// The rest of `object[index]` or `object[start:end]` after the `[`.
+    fn parse_index(&mut self, object: Expression, start: &Span) -> Result<Expression> {
+        self.skip_newlines();
+        let first = if self.check(&TokenType::Colon) {
+            None
+        } else {
+            Some(Box::new(self.parse_expression()?))
+        };
+        self.skip_newlines();
+        let slice_start = match first {
+            Some(index) if !self.check(&TokenType::Colon) => {
+                self.expect(TokenType::RightBracket)?;
+                return Ok(Expression::Index(IndexExpression {
+                    object: Box::new(object),
+                    index,
+                    span: self.span_from(start),
+                }));
+            }
+            slice_start => slice_start,
+        };
+        self.expect(TokenType::Colon)?;
+        self.skip_newlines();
+        let end = if self.check(&TokenType::RightBracket) {
+            None
+        } else {
+            Some(Box::new(self.parse_expression()?))
+        };
+        self.skip_newlines();
+        self.expect(TokenType::RightBracket)?;
+        Ok(Expression::Slice(SliceExpression {
+            object: Box::new(object),
+            start: slice_start,
+            end,
+            span: self.span_from(start),
+        }))
+    }
+
+    /// The positional and then the named arguments of a call.
+    fn parse_arguments(&mut self) -> Result<(Vec<Expression>, Vec<NamedArgument>)> {
+        let mut arguments = Vec::new();
+        let mut named_arguments = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightParen) {
+            let named = matches!(self.peek_type(), TokenType::Identifier(_))
+                && self
+                    .tokens
+                    .get(self.current + 1)
+                    .map(|token| &token.token_type)
+                    == Some(&TokenType::Colon);
+            if named {
+                let start = self.peek_span();
+                let name = self.expect_identifier()?;
+                self.expect(TokenType::Colon)?;
+                self.skip_newlines();
+                let value = self.parse_expression()?;
+                named_arguments.push(NamedArgument {
+                    name,
+                    value,
+                    span: self.span_from(&start),
+                });
+            } else if named_arguments.is_empty() {
+                arguments.push(self.parse_expression()?);
+            } else {
+                return Err(
+                    self.error_at_current("Positional arguments must come before named arguments")
+                );
+            }
+            self.skip_newlines();
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+            self.skip_newlines();
+        }
+        self.expect_list_end(TokenType::RightParen)?;
+        Ok((arguments, named_arguments))
+    }
+
+    fn parse_primary(&mut self, allow_struct: bool) -> Result<Expression> {
+        let start = self.current;
+        let token = self.advance();
+        match token.token_type {
+            TokenType::Integer(value) => Ok(Expression::Literal(Literal::Integer(value))),
+            TokenType::Float(value) => Ok(Expression::Literal(Literal::Float(value))),
+            TokenType::String(value) => Ok(Expression::Literal(Literal::String(value))),
+            TokenType::InterpolatedString(parts) => self.parse_interpolation(parts, token.span),
+            TokenType::Boolean(value) => Ok(Expression::Literal(Literal::Boolean(value))),
+            TokenType::NoneValue => Ok(Expression::Literal(Literal::None)),
+            // Already reported; stand in for the expression the input most
+            // likely meant so parsing can carry on.
+            TokenType::Error(_) => Ok(Expression::Identifier(Identifier {
+                name: Symbol::intern(""),
+                span: token.span,
+            })),
+            TokenType::Identifier(name) => {
+                if allow_struct && self.check(&TokenType::LeftBrace) {
+                    return self.parse_struct_init(name, token.span);
+                }
+                Ok(Expression::Identifier(Identifier {
+                    name,
+                    span: token.span,
+                }))
+            }
+            TokenType::SelfValue => Ok(Expression::Identifier(Identifier {
+                name: Symbol::intern("self"),
+                span: token.span,
+            })),
+            TokenType::Match => self.parse_match(token.span),
+            TokenType::If => self.parse_if_expression(token.span),
+            TokenType::Fn => self.parse_block_lambda(token.span),
+            TokenType::Pipe => self.parse_expression_lambda(token.span, allow_struct),
+            TokenType::Or => self.parse_lambda_body(Vec::new(), token.span, allow_struct),
+            TokenType::LeftParen => {
+                self.skip_newlines();
+                let expression = self.parse_expression()?;
+                self.skip_newlines();
+                if !self.match_token(&TokenType::Comma) {
+                    self.expect(TokenType::RightParen)?;
+                    return Ok(expression);
+                }
+                let mut elements = vec![expression];
+                self.skip_newlines();
+                while !self.check(&TokenType::RightParen) {
+                    elements.push(self.parse_expression()?);
+                    self.skip_newlines();
+                    if !self.match_token(&TokenType::Comma) {
+                        break;
+                    }
+                    self.skip_newlines();
+                }
+                self.expect_list_end(TokenType::RightParen)?;
+                if elements.len() < 2 {
+                    let span = self.span_from(&token.span);
+                    return Err(self.error("Tuples need at least two elements", span));
+                }
+                Ok(Expression::Tuple(TupleLiteral {
+                    elements,
+                    span: self.span_from(&token.span),
+                }))
+            }
+            TokenType::LeftBrace if allow_struct => self.parse_map(token.span),
+            TokenType::LeftBracket => {
+                let mut elements = Vec::new();
+                self.skip_newlines();
+                while !self.check(&TokenType::RightBracket) {
+                    elements.push(self.parse_expression()?);
+                    if elements.len() == 1 && self.check_after_newlines(&TokenType::For) {
+                        let element = elements.pop().expect("just pushed");
+                        return self.parse_comprehension(element, token.span);
+                    }
+                    self.skip_newlines();
+                    if !self.match_token(&TokenType::Comma) {
+                        break;
+                    }
+                    self.skip_newlines();
+                }
+                self.expect_list_end(TokenType::RightBracket)?;
+                Ok(Expression::List(ListLiteral {
+                    elements,
+                    span: self.span_from(&token.span),
+                }))
+            }
+            other => {
+                self.current = start;
+                Err(self.error(
+                    format!("Expected an expression, found {}", other),
+                    token.span,
+                ))
+            }
+        }
+    }
+
+    /// The rest of `[element for x in xs if condition]` after the element.
+    fn parse_comprehension(&mut self, element: Expression, start: Span) -> Result<Expression> {
+        self.skip_newlines();
+        self.expect(TokenType::For)?;
+        let variable = self.expect_identifier()?;
+        self.expect(TokenType::In)?;
+        let iterable = self.parse_expression()?;
+        let condition = if self.check_after_newlines(&TokenType::If) {
+            self.skip_newlines();
+            self.advance();
+            Some(Box::new(self.parse_expression()?))
+        } else {
+            None
+        };
+        self.skip_newlines();
+        self.expect(TokenType::RightBracket)?;
+        Ok(Expression::Comprehension(Comprehension {
+            element: Box::new(element),
+            variable,
+            iterable: Box::new(iterable),
+            condition,
+            span: self.span_from(&start),
+        }))
+    }
+
+    /// `fn(x: int) -> int { ... }`, after the `fn`.
+    fn parse_block_lambda(&mut self, start: Span) -> Result<Expression> {
+        self.expect(TokenType::LeftParen)?;
+        let parameters = self.parse_lambda_parameters(TokenType::RightParen)?;
+        let return_type = if self.match_token(&TokenType::Arrow) {
+            self.parse_type()?
+        } else {
+            Type::Void
+        };
+        let body = self.parse_block()?;
+        Ok(Expression::Lambda(Lambda {
+            parameters,
+            body: Box::new(LambdaBody::Block(return_type, body)),
+            span: self.span_from(&start),
+        }))
+    }
+
+    /// `|x, y: int| expression`, after the first `|`.
+    fn parse_expression_lambda(&mut self, start: Span, allow_struct: bool) -> Result<Expression> {
+        let parameters = self.parse_lambda_parameters(TokenType::Pipe)?;
+        self.parse_lambda_body(parameters, start, allow_struct)
+    }
+
+    /// The body of a `|...|` lambda, which extends as far as an expression can.
+    fn parse_lambda_body(
+        &mut self,
+        parameters: Vec<LambdaParameter>,
+        start: Span,
+        allow_struct: bool,
+    ) -> Result<Expression> {
+        let body = self.nested(|parser| parser.parse_range(allow_struct))?;
+        Ok(Expression::Lambda(Lambda {
+            parameters,
+            body: Box::new(LambdaBody::Expression(body)),
+            span: self.span_from(&start),
+        }))
+    }
+
+    /// Parameters with optional types, up to and including `end`.
+    fn parse_lambda_parameters(&mut self, end: TokenType) -> Result<Vec<LambdaParameter>> {
+        let mut parameters = Vec::new();
+        self.skip_newlines();
+        while !self.check(&end) {
+            let start = self.peek_span();
+            let name = self.expect_identifier()?;
+            let param_type = if self.match_token(&TokenType::Colon) {
+                Some(self.parse_type()?)
+            } else {
+                None
+            };
+            parameters.push(LambdaParameter {
+                name,
+                param_type,
+                span: self.span_from(&start),
+            });
+            self.skip_newlines();
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+            self.skip_newlines();
+        }
+        self.expect_list_end(end)?;
+        Ok(parameters)
+    }
+
+    /// Parses the expressions of an interpolated string, each from its own
+    /// tokens.
+    fn parse_interpolation(&mut self, parts: Vec<StringPart>, span: Span) -> Result<Expression> {
+        let mut parsed = Vec::new();
+        for part in parts {
+            let mut tokens = match part {
+                StringPart::Text(text) => {
+                    parsed.push(InterpolationPart::Text(text));
+                    continue;
+                }
+                StringPart::Code(tokens) => tokens,
+            };
+            let last = tokens.last().map_or(&span, |token| &token.span);
+            let end = Span {
+                start_line: last.end_line,
+                start_column: last.end_column,
+                start_offset: last.end_offset,
+                ..last.clone()
+            };
+            tokens.push(Token {
+                token_type: TokenType::Eof,
+                span: end,
+            });
+
+            let outer = std::mem::replace(&mut self.tokens, tokens);
+            let position = std::mem::replace(&mut self.current, 0);
+            let result = self.parse_expression().and_then(|expression| {
+                if self.is_at_end() {
+                    Ok(expression)
+                } else {
+                    Err(self.error_at_current("Expected `}` after the interpolated expression"))
+                }
+            });
+            self.tokens = outer;
+            self.current = position;
+            parsed.push(InterpolationPart::Expression(result?));
+        }
+        Ok(Expression::Interpolation(Interpolation {
+            parts: parsed,
+            span,
+        }))
+    }
+
+    fn parse_map(&mut self, start: Span) -> Result<Expression> {
+        let mut entries = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) {
+            let key = self.parse_expression()?;
+            self.expect(TokenType::Colon)?;
+            self.skip_newlines();
+            let value = self.parse_expression()?;
+            entries.push((key, value));
+            self.skip_newlines();
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+            self.skip_newlines();
+        }
+        self.expect_list_end(TokenType::RightBrace)?;
+
+        Ok(Expression::Map(MapLiteral {
+            entries,
+            span: self.span_from(&start),
+        }))
+    }
+
+    fn parse_struct_init(&mut self, struct_name: Symbol, start: Span) -> Result<Expression> {
+        self.expect(TokenType::LeftBrace)?;
+        let mut fields = HashMap::new();
+
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) {
+            let field_span = self.peek_span();
+            let field_name = self.expect_identifier()?;
+            self.expect(TokenType::Colon)?;
+            let value = self.parse_expression()?;
+            if fields.insert(field_name, value).is_some() {
+                return Err(self.error(
+                    format!("Field `{}` specified more than once", field_name),
+                    field_span,
+                ));
+            }
+            self.skip_newlines();
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+            self.skip_newlines();
+        }
+        self.expect_list_end(TokenType::RightBrace)?;
+
+        Ok(Expression::StructInit(StructInitializer {
+            struct_name,
+            fields,
+            span: self.span_from(&start),
+        }))
+    }
+
+    fn parse_match(&mut self, start: Span) -> Result<Expression> {
+        let scrutinee = self.parse_expression_no_struct()?;
+        self.expect(TokenType::LeftBrace)?;
+        let mut arms = Vec::new();
+        self.skip_newlines();
+        while !self.check(&TokenType::RightBrace) {
+            let arm_start = self.peek_span();
+            let pattern = self.parse_pattern()?;
+            let pattern_span = self.span_from(&arm_start);
+            self.expect(TokenType::FatArrow)?;
+            self.skip_newlines();
+            let body = self.parse_expression()?;
+            arms.push(MatchArm {
+                pattern,
+                pattern_span,
+                body,
+                span: self.span_from(&arm_start),
+            });
+
+            let separated = self.match_token(&TokenType::Comma);
+            if !separated && !self.check(&TokenType::Newline) && !self.check(&TokenType::RightBrace)
+            {
+                return Err(self.error_at_current("Expected `,` or newline after `match` arm"));
+            }
+            self.skip_newlines();
+        }
+        self.expect(TokenType::RightBrace)?;
+
+        Ok(Expression::Match(MatchExpression {
+            scrutinee: Box::new(scrutinee),
+            arms,
+            span: self.span_from(&start),
+        }))
+    }
+
+    /// The rest of an `if` expression after `if`. Each branch is a single
+    /// expression in braces, and the `else` is required.
+    fn parse_if_expression(&mut self, start: Span) -> Result<Expression> {
+        let condition = self.parse_expression_no_struct()?;
+        let then_value = self.parse_branch_value()?;
+        if !self.check_after_newlines(&TokenType::Else) {
+            let span = self.span_from(&start);
+            return Err(self.report(
+                Diagnostic::error("An `if` expression needs an `else` branch", span)
+                    .with_help("add `else { ... }` with the value to use otherwise"),
+            ));
+        }
+        self.skip_newlines();
+        self.expect(TokenType::Else)?;
+        let else_value = if self.check(&TokenType::If) {
+            let else_start = self.advance().span;
+            self.parse_if_expression(else_start)?
+        } else {
+            self.parse_branch_value()?
+        };
+        Ok(Expression::If(IfExpression {
+            condition: Box::new(condition),
+            then_value: Box::new(then_value),
+            else_value: Box::new(else_value),
+            span: self.span_from(&start),
+        }))
+    }
+
+    fn parse_branch_value(&mut self) -> Result<Expression> {
+        self.expect(TokenType::LeftBrace)?;
+        self.skip_newlines();
+        let value = self.parse_expression()?;
+        self.skip_newlines();
+        self.expect(TokenType::RightBrace)?;
+        Ok(value)
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern> {
+        let negative = self.match_token(&TokenType::Minus);
+        let start = self.current;
+        let token = self.advance();
+        let pattern = match token.token_type {
+            TokenType::Integer(value) if negative => Pattern::Literal(Literal::Integer(-value)),
+            TokenType::Float(value) if negative => Pattern::Literal(Literal::Float(-value)),
+            _ if negative => {
+                return Err(self.error(
+                    format!("Expected a number after `-`, found {}", token.token_type),
+                    token.span,
+                ))
+            }
+            TokenType::Integer(value) => Pattern::Literal(Literal::Integer(value)),
+            TokenType::Float(value) => Pattern::Literal(Literal::Float(value)),
+            TokenType::String(value) => Pattern::Literal(Literal::String(value)),
+            TokenType::Boolean(value) => Pattern::Literal(Literal::Boolean(value)),
+            TokenType::Identifier(name) if name == "_" => Pattern::Wildcard,
+            TokenType::Identifier(enum_name) if self.match_token(&TokenType::Dot) => {
+                let variant = self.expect_identifier()?;
+                let mut bindings = Vec::new();
+                if self.match_token(&TokenType::LeftParen) {
+                    while !self.check(&TokenType::RightParen) {
+                        bindings.push(self.expect_identifier()?);
+                        if !self.match_token(&TokenType::Comma) {
+                            break;
+                        }
+                    }
+                    self.expect_list_end(TokenType::RightParen)?;
+                }
+                Pattern::Variant(VariantPattern {
+                    enum_name,
+                    variant,
+                    bindings,
+                })
+            }
+            TokenType::Identifier(name) => Pattern::Binding(name),
+            other => {
+                self.current = start;
+                return Err(self.error(format!("Expected a pattern, found {}", other), token.span));
+            }
+        };
+        Ok(pattern)
+    }
+
+    fn expect_terminator(&mut self) -> Result<()> {
+        if self.match_token(&TokenType::Semicolon) || self.match_token(&TokenType::Newline) {
+            return Ok(());
+        }
+        if self.check(&TokenType::RightBrace) || self.is_at_end() {
+            return Ok(());
+        }
+        Err(self.error_at_current(format!(
+            "Expected newline or `;`, found {}",
+            self.peek_type()
+        )))
+    }
+
+    /// After a syntax error in the statement that began at token `start`, skips
+    /// to the end of that statement: the next newline or `;` outside the
+    /// brackets it opened, or the `}` closing the block. Brackets opened
+    /// before the error count too, so an error inside a `match` or a closure
+    /// skips the rest of it rather than parsing its lines as statements.
+    fn synchronize_statement(&mut self, start: usize) {
+        let mut open = Vec::new();
+        for token in &self.tokens[start..self.current] {
+            Self::track_bracket(&mut open, &token.token_type);
+        }
+        while !self.is_at_end() {
+            match self.peek_type() {
+                TokenType::Newline | TokenType::Semicolon if open.is_empty() => {
+                    self.advance();
+                    return;
+                }
+                TokenType::RightBrace if !open.contains(&TokenType::LeftBrace) => return,
+                token => Self::track_bracket(&mut open, token),
+            }
+            self.advance();
+        }
+    }
+
+    /// Keeps `open`, the brackets open so far, up to date with `token`. A `}`
+    /// also closes any `(` or `[` left open inside its braces.
+    fn track_bracket(open: &mut Vec<TokenType>, token: &TokenType) {
+        match token {
+            TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => {
+                open.push(token.clone())
+            }
+            TokenType::RightParen if open.last() == Some(&TokenType::LeftParen) => {
+                open.pop();
+            }
+            TokenType::RightBracket if open.last() == Some(&TokenType::LeftBracket) => {
+                open.pop();
+            }
+            TokenType::RightBrace => {
+                while let Some(bracket) = open.pop() {
+                    if bracket == TokenType::LeftBrace {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// After a syntax error at the top level, skips to the next line that
+    /// starts an item: a declaration keyword at column 1, or outside brackets.
+    fn synchronize_item(&mut self) {
+        let mut depth = 0usize;
+        let mut line_start = false;
+        while !self.is_at_end() {
+            let token = &self.tokens[self.current];
+            let starts_item = starts_item(&token.token_type)
+                || matches!(token.token_type, TokenType::Let | TokenType::Var);
+            if line_start && starts_item && (depth == 0 || token.span.start_column == 1) {
+                return;
+            }
+            line_start = token.token_type == TokenType::Newline;
+            match token.token_type {
+                TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => depth += 1,
+                TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    fn at_statement_end(&self) -> bool {
+        matches!(
+            self.peek_type(),
+            TokenType::Newline | TokenType::Semicolon | TokenType::RightBrace | TokenType::Eof
+        )
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.check(&TokenType::Newline) {
+            self.advance();
+        }
+    }
+
+    fn check_after_newlines(&self, token_type: &TokenType) -> bool {
+        self.tokens[self.current..]
+            .iter()
+            .find(|token| token.token_type != TokenType::Newline)
+            .is_some_and(|token| &token.token_type == token_type)
+    }
+
+    fn expect(&mut self, token_type: TokenType) -> Result<Span> {
+        if self.check(&token_type) {
+            return Ok(self.advance().span);
+        }
+        Err(self.error_at_current(format!(
+            "Expected {}, found {}",
+            token_type,
+            self.peek_type()
+        )))
+    }
+
+    /// Expects the token closing a comma-separated list. When the next token
+    /// could start another element instead, the comma before it is missing.
+    fn expect_list_end(&mut self, close: TokenType) -> Result<Span> {
+        if self.check(&close) {
+            return Ok(self.advance().span);
+        }
+        let found = self.peek_type();
+        let mut diagnostic = Diagnostic::error(
+            format!("Expected `,` or {}, found {}", close, found),
+            self.peek_span(),
+        );
+        if starts_element(found) {
+            diagnostic = diagnostic.with_suggestion(
+                "add a comma",
+                self.end_of_previous_token(),
+                ",",
+                Applicability::MachineApplicable,
+            );
+        }
+        Err(self.report(diagnostic))
+    }
+
+    /// Zero-width span just after the last token before the current one,
+    /// ignoring newlines.
+    fn end_of_previous_token(&self) -> Span {
+        let previous = self.tokens[..self.current]
+            .iter()
+            .rev()
+            .find(|token| token.token_type != TokenType::Newline)
+            .map_or_else(|| self.peek_span(), |token| token.span.clone());
+        Span {
+            start_line: previous.end_line,
+            start_column: previous.end_column,
+            start_offset: previous.end_offset,
+            ..previous
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<Symbol> {
+        if let TokenType::Identifier(name) = self.peek_type() {
+            let name = *name;
+            self.advance();
+            return Ok(name);
+        }
+        Err(self.error_at_current(format!("Expected identifier, found {}", self.peek_type())))
+    }
+
+    fn match_token(&mut self, token_type: &TokenType) -> bool {
+        if self.check(token_type) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn check(&self, token_type: &TokenType) -> bool {
+        self.peek_type() == token_type
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.current].clone();
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        token
+    }
+
+    fn peek_type(&self) -> &TokenType {
+        &self.tokens[self.current].token_type
+    }
+
+    fn peek_span(&self) -> Span {
+        self.tokens[self.current].span.clone()
+    }
+
+    fn previous_span(&self) -> Span {
+        self.tokens[self.current.saturating_sub(1)].span.clone()
+    }
+
+    fn span_from(&self, start: &Span) -> Span {
+        start.to(&self.previous_span())
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.tokens[self.current].token_type == TokenType::Eof
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            let message = format!("Nesting exceeds the maximum depth of {}", MAX_NESTING_DEPTH);
+            let diagnostic =
+                Diagnostic::error(message, self.peek_span()).with_code(codes::TOO_DEEP);
+            return Err(self.report(diagnostic));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn error_at_current(&mut self, message: impl Into<String>) -> Error {
+        let span = self.peek_span();
+        self.error(message, span)
+    }
+
+    fn error(&mut self, message: impl Into<String>, span: Span) -> Error {
+        self.report(Diagnostic::error(message, span))
+    }
+
+    /// Records a syntax error, unless it is at a token the lexer already
+    /// reported. Errors without a code of their own are `codes::SYNTAX`.
+    fn report(&mut self, mut diagnostic: Diagnostic) -> Error {
+        diagnostic
+            .code
+            .get_or_insert_with(|| codes::SYNTAX.to_string());
+        let error = Error::ParseError(format!("{} at {}", diagnostic.message, diagnostic.span));
+        if !matches!(self.peek_type(), TokenType::Error(_)) {
+            self.diagnostics.emit(diagnostic);
+            self.reported += 1;
+        }
+        error
+    }
+}
+
+/// Words other languages declare functions with.
+const FUNCTION_KEYWORDS: &[&str] = &["def", "fun", "func", "function"];
+
+/// Whether `token_type` begins an item or import and cannot begin a
+/// statement. `let` and `var` begin both, so they are left out.
+pub fn starts_item(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Fn
+            | TokenType::Struct
+            | TokenType::Exception
+            | TokenType::Enum
+            | TokenType::Impl
+            | TokenType::Const
+            | TokenType::Import
+            | TokenType::Pub
+            | TokenType::At
+            | TokenType::DocComment(_)
+    )
+}
+
+/// Whether `token_type` can begin a list element: an expression, a parameter
+/// or a field.
+fn starts_element(token_type: &TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Integer(_)
+            | TokenType::Float(_)
+            | TokenType::String(_)
+            | TokenType::InterpolatedString(_)
+            | TokenType::Boolean(_)
+            | TokenType::Identifier(_)
+            | TokenType::SelfValue
+            | TokenType::NoneValue
+            | TokenType::Match
+            | TokenType::If
+            | TokenType::Fn
+            | TokenType::Pipe
+            | TokenType::LeftParen
+            | TokenType::LeftBracket
+            | TokenType::Minus
+            | TokenType::Not
+            | TokenType::Tilde
+    )
+}
+
+fn binary_operator(token_type: &TokenType) -> Option<(BinaryOperator, u8)> {
+    let operator = match token_type {
+        TokenType::Or => (BinaryOperator::Or, 1),
+        TokenType::And => (BinaryOperator::And, 2),
+        TokenType::Equal => (BinaryOperator::Eq, 3),
+        TokenType::NotEqual => (BinaryOperator::Ne, 3),
+        TokenType::Less => (BinaryOperator::Lt, 4),
+        TokenType::LessEqual => (BinaryOperator::Le, 4),
+        TokenType::Greater => (BinaryOperator::Gt, 4),
+        TokenType::GreaterEqual => (BinaryOperator::Ge, 4),
+        TokenType::Coalesce => (BinaryOperator::Coalesce, 5),
+        TokenType::Pipe => (BinaryOperator::BitOr, 6),
+        TokenType::Caret => (BinaryOperator::BitXor, 7),
+        TokenType::Ampersand => (BinaryOperator::BitAnd, 8),
+        TokenType::ShiftLeft => (BinaryOperator::Shl, 9),
+        TokenType::ShiftRight => (BinaryOperator::Shr, 9),
+        TokenType::Plus => (BinaryOperator::Add, 10),
+        TokenType::Minus => (BinaryOperator::Sub, 10),
+        TokenType::Star => (BinaryOperator::Mul, 11),
+        TokenType::Slash => (BinaryOperator::Div, 11),
+        TokenType::Percent => (BinaryOperator::Mod, 11),
+        _ => return None,
+    };
+    Some(operator)
+}
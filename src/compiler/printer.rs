@@ -0,0 +1,747 @@
+//! Renders an AST back to Rustic source. The output is canonical rather than
+//! faithful: comments and blank lines are not in the AST, imports come before
+//! items, struct initializer fields are sorted by name, and parentheses appear
+//! only where precedence requires them. Parsing the output gives back the same
+//! program, spans aside.
+
+use crate::compiler::ast::*;
+use crate::compiler::intern::Symbol;
+use crate::compiler::lexer::ESCAPES;
+
+const INDENT: &str = "    ";
+
+pub fn to_source(program: &Program) -> String {
+    let mut printer = Printer::default();
+    printer.program(program);
+    printer.output
+}
+
+/// Renders a single expression, as it would appear in a statement.
+pub fn expression_to_source(expression: &Expression) -> String {
+    let mut printer = Printer::default();
+    printer.expression(expression, Context::default());
+    printer.output
+}
+
+#[derive(Default)]
+struct Printer {
+    output: String,
+    indent: usize,
+}
+
+/// Where an expression is printed, which decides whether it needs parentheses.
+#[derive(Debug, Clone, Copy, Default)]
+struct Context {
+    /// Binding strength the position requires; weaker expressions are wrapped.
+    precedence: u8,
+    /// Inside an `if` condition or `for` iterable, where `Name {` would start
+    /// the block.
+    no_struct: bool,
+}
+
+const CAST: u8 = 12;
+const PREFIX: u8 = 13;
+const POWER: u8 = 14;
+const POSTFIX: u8 = 15;
+
+impl Printer {
+    fn program(&mut self, program: &Program) {
+        for import in &program.imports {
+            self.output.push_str("import ");
+            self.output.push_str(&import.module_path);
+            if let Some(items) = &import.items {
+                let items: Vec<String> = items
+                    .iter()
+                    .map(|item| match item.alias {
+                        Some(alias) => format!("{} as {}", item.name, alias),
+                        None => item.name.to_string(),
+                    })
+                    .collect();
+                self.output.push_str(&format!(".{{{}}}", items.join(", ")));
+            }
+            if let Some(alias) = import.alias {
+                self.output.push_str(" as ");
+                self.output.push_str(&alias);
+            }
+            self.output.push('\n');
+        }
+
+        for (index, item) in program.items.iter().enumerate() {
+            if index > 0 || !program.imports.is_empty() {
+                self.output.push('\n');
+            }
+            self.item(item);
+            self.output.push('\n');
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        match item {
+            Item::Function(function) => self.function(function, None),
+            Item::Struct(structure) => self.structure(structure),
+            Item::Enum(enumeration) => self.enumeration(enumeration),
+            Item::Impl(implementation) => self.implementation(implementation),
+            Item::Variable(variable) => self.variable(variable),
+            Item::Constant(constant) => {
+                self.visibility(constant.public);
+                self.output.push_str(&format!(
+                    "const {}: {} = ",
+                    constant.name, constant.const_type
+                ));
+                self.expression(&constant.value, Context::default());
+            }
+        }
+    }
+
+    /// `receiver` is the `self` parameter of a method.
+    fn function(&mut self, function: &Function, receiver: Option<&str>) {
+        self.docs(&function.docs);
+        self.deprecation(function.deprecated.as_ref());
+        self.visibility(function.public);
+        self.output.push_str("fn ");
+        self.output.push_str(&function.name);
+        self.type_params(&function.type_params);
+        self.output.push('(');
+        if let Some(receiver) = receiver {
+            self.output.push_str(receiver);
+        }
+        for (index, parameter) in function.parameters.iter().enumerate() {
+            if index > 0 || receiver.is_some() {
+                self.output.push_str(", ");
+            }
+            self.output
+                .push_str(&format!("{}: {}", parameter.name, parameter.param_type));
+            if let Some(default) = &parameter.default_value {
+                self.output.push_str(" = ");
+                self.expression(default, Context::default());
+            }
+        }
+        self.output.push(')');
+        if function.return_type != Type::Void {
+            self.output.push_str(" -> ");
+            self.output.push_str(&function.return_type.to_string());
+        }
+        self.output.push(' ');
+        self.block(&function.body);
+    }
+
+    fn structure(&mut self, structure: &Struct) {
+        self.docs(&structure.docs);
+        self.deprecation(structure.deprecated.as_ref());
+        self.visibility(structure.public);
+        self.output.push_str(if structure.exception {
+            "exception "
+        } else {
+            "struct "
+        });
+        self.output.push_str(&structure.name);
+        self.type_params(&structure.type_params);
+        if structure.fields.is_empty() {
+            self.output.push_str(" {}");
+            return;
+        }
+        self.output.push_str(" {\n");
+        for field in &structure.fields {
+            self.output.push_str(INDENT);
+            self.output
+                .push_str(&format!("{}: {},\n", field.name, field.field_type));
+        }
+        self.output.push('}');
+    }
+
+    fn implementation(&mut self, implementation: &Impl) {
+        self.output.push_str("impl ");
+        self.output.push_str(&implementation.struct_name);
+        self.type_params(&implementation.type_params);
+        if implementation.methods.is_empty() {
+            self.output.push_str(" {}");
+            return;
+        }
+        self.output.push_str(" {\n");
+        self.indent += 1;
+        for (index, method) in implementation.methods.iter().enumerate() {
+            if index > 0 {
+                self.output.push('\n');
+            }
+            self.write_indent();
+            let receiver = if method.mutable { "var self" } else { "self" };
+            self.function(&method.function, Some(receiver));
+            self.output.push('\n');
+        }
+        self.indent -= 1;
+        self.output.push('}');
+    }
+
+    fn type_params(&mut self, type_params: &[Symbol]) {
+        if !type_params.is_empty() {
+            let names: Vec<&str> = type_params.iter().map(|name| name.as_str()).collect();
+            self.output.push_str(&format!("[{}]", names.join(", ")));
+        }
+    }
+
+    fn enumeration(&mut self, enumeration: &Enum) {
+        self.visibility(enumeration.public);
+        self.output.push_str("enum ");
+        self.output.push_str(&enumeration.name);
+        if enumeration.variants.is_empty() {
+            self.output.push_str(" {}");
+            return;
+        }
+        self.output.push_str(" {\n");
+        for variant in &enumeration.variants {
+            self.output.push_str(INDENT);
+            self.output.push_str(&variant.name);
+            if !variant.payload.is_empty() {
+                let payload: Vec<String> = variant.payload.iter().map(Type::to_string).collect();
+                self.output.push_str(&format!("({})", payload.join(", ")));
+            }
+            self.output.push_str(",\n");
+        }
+        self.output.push('}');
+    }
+
+    /// Doc comment lines, each followed by the indentation of the item.
+    fn docs(&mut self, docs: &[String]) {
+        for line in docs {
+            self.output.push_str(&format!("///{}\n", line));
+            self.write_indent();
+        }
+    }
+
+    fn deprecation(&mut self, deprecation: Option<&Deprecation>) {
+        let Some(deprecation) = deprecation else {
+            return;
+        };
+        self.output.push_str("@deprecated");
+        if let Some(note) = &deprecation.note {
+            self.output.push('(');
+            self.literal(&Literal::String(note.clone()));
+            self.output.push(')');
+        }
+        self.output.push('\n');
+    }
+
+    fn visibility(&mut self, public: bool) {
+        if public {
+            self.output.push_str("pub ");
+        }
+    }
+
+    fn variable(&mut self, variable: &Variable) {
+        self.visibility(variable.public);
+        self.output
+            .push_str(if variable.mutable { "var " } else { "let " });
+        self.output.push_str(&variable.name);
+        if let Some(ty) = &variable.var_type {
+            self.output.push_str(&format!(": {}", ty));
+        }
+        if let Some(initializer) = &variable.initializer {
+            self.output.push_str(" = ");
+            self.expression(initializer, Context::default());
+        }
+    }
+
+    fn block(&mut self, block: &Block) {
+        if block.statements.is_empty() {
+            self.output.push_str("{}");
+            return;
+        }
+        self.output.push_str("{\n");
+        self.indent += 1;
+        for statement in &block.statements {
+            self.write_indent();
+            self.statement(statement);
+            self.output.push('\n');
+        }
+        self.indent -= 1;
+        self.write_indent();
+        self.output.push('}');
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        let condition = Context {
+            precedence: 0,
+            no_struct: true,
+        };
+        match statement {
+            Statement::Expression(expression) => {
+                self.expression(expression, Context::default());
+            }
+            Statement::Variable(variable) => self.variable(variable),
+            Statement::Destructure(destructure) => {
+                let names: Vec<&str> = destructure.names.iter().map(|n| n.name.as_str()).collect();
+                self.output.push_str(&format!(
+                    "{} ({}) = ",
+                    if destructure.mutable { "var" } else { "let" },
+                    names.join(", ")
+                ));
+                self.expression(&destructure.initializer, Context::default());
+            }
+            Statement::Assignment(assignment) => {
+                self.expression(&assignment.target, Context::default());
+                match &assignment.operator {
+                    Some(operator) => self.output.push_str(&format!(" {}= ", operator)),
+                    None => self.output.push_str(" = "),
+                }
+                self.expression(&assignment.value, Context::default());
+            }
+            Statement::If(if_statement) => {
+                self.output.push_str("if ");
+                self.expression(&if_statement.condition, condition);
+                self.output.push(' ');
+                self.block(&if_statement.then_block);
+                for (else_condition, block) in &if_statement.else_ifs {
+                    self.output.push_str(" else if ");
+                    self.expression(else_condition, condition);
+                    self.output.push(' ');
+                    self.block(block);
+                }
+                if let Some(else_block) = &if_statement.else_block {
+                    self.output.push_str(" else ");
+                    self.block(else_block);
+                }
+            }
+            Statement::For(for_loop) => {
+                self.output
+                    .push_str(&format!("for {} in ", for_loop.variable));
+                self.expression(&for_loop.iterable, condition);
+                self.output.push(' ');
+                self.block(&for_loop.body);
+            }
+            Statement::While(while_loop) => {
+                self.output.push_str("while ");
+                self.expression(&while_loop.condition, condition);
+                self.output.push(' ');
+                self.block(&while_loop.body);
+            }
+            Statement::Try(try_statement) => {
+                self.output.push_str("try ");
+                self.block(&try_statement.try_block);
+                for clause in &try_statement.catch_clauses {
+                    self.output
+                        .push_str(&format!(" catch {} ", clause.exception_type));
+                    if let Some(binding) = &clause.binding {
+                        self.output.push_str(&format!("as {} ", binding));
+                    }
+                    self.block(&clause.handler_block);
+                }
+                if let Some(finally_block) = &try_statement.finally_block {
+                    self.output.push_str(" finally ");
+                    self.block(finally_block);
+                }
+            }
+            Statement::Throw(throw_statement) => {
+                self.output.push_str("throw ");
+                self.expression(&throw_statement.value, Context::default());
+            }
+            Statement::Break(_) => self.output.push_str("break"),
+            Statement::Continue(_) => self.output.push_str("continue"),
+            Statement::Return(return_statement) => {
+                self.output.push_str("return");
+                if let Some(value) = &return_statement.value {
+                    self.output.push(' ');
+                    self.expression(value, Context::default());
+                }
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression, context: Context) {
+        let precedence = expression_precedence(expression);
+        let wrap = precedence < context.precedence
+            || (context.no_struct
+                && matches!(expression, Expression::StructInit(_) | Expression::Map(_)));
+        if wrap {
+            self.output.push('(');
+        }
+        // Parentheses end any restriction from the surrounding position.
+        let inner = Context {
+            precedence: 0,
+            no_struct: context.no_struct && !wrap,
+        };
+
+        match expression {
+            Expression::Literal(literal) => self.literal(literal),
+            Expression::Identifier(identifier) => self.output.push_str(&identifier.name),
+            Expression::Binary(binary) => {
+                let operator = binary_precedence(&binary.operator);
+                // Binary operators associate to the left, so an equally strong
+                // right operand needs parentheses; `**` is the other way
+                // around, and takes a prefix operator on its right.
+                let (left, right) = match binary.operator {
+                    BinaryOperator::Pow => (POSTFIX, PREFIX),
+                    _ => (operator, operator + 1),
+                };
+                self.expression(
+                    &binary.left,
+                    Context {
+                        precedence: left,
+                        ..inner
+                    },
+                );
+                self.output.push_str(&format!(" {} ", binary.operator));
+                self.expression(
+                    &binary.right,
+                    Context {
+                        precedence: right,
+                        ..inner
+                    },
+                );
+            }
+            Expression::Unary(unary) => {
+                // Keep `-(-x)` from printing as `--x`.
+                let doubled_minus = unary.operator == UnaryOperator::Neg
+                    && expression_precedence(&unary.operand) == PREFIX
+                    && !matches!(
+                        &*unary.operand,
+                        Expression::Unary(UnaryOp {
+                            operator: UnaryOperator::Not,
+                            ..
+                        })
+                    );
+                self.output.push_str(&unary.operator.to_string());
+                self.expression(
+                    &unary.operand,
+                    Context {
+                        precedence: if doubled_minus { POSTFIX } else { PREFIX },
+                        ..inner
+                    },
+                );
+            }
+            Expression::Call(call) => {
+                self.expression(
+                    &call.function,
+                    Context {
+                        precedence: POSTFIX,
+                        ..inner
+                    },
+                );
+                self.output.push('(');
+                for (index, argument) in call.arguments.iter().enumerate() {
+                    if index > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.expression(argument, Context::default());
+                }
+                for (index, argument) in call.named_arguments.iter().enumerate() {
+                    if index > 0 || !call.arguments.is_empty() {
+                        self.output.push_str(", ");
+                    }
+                    self.output.push_str(&format!("{}: ", argument.name));
+                    self.expression(&argument.value, Context::default());
+                }
+                self.output.push(')');
+            }
+            Expression::MemberAccess(access) => {
+                self.expression(
+                    &access.object,
+                    Context {
+                        precedence: POSTFIX,
+                        ..inner
+                    },
+                );
+                self.output.push('.');
+                self.output.push_str(&access.member);
+            }
+            Expression::Cast(cast) => {
+                self.expression(
+                    &cast.value,
+                    Context {
+                        precedence: CAST,
+                        ..inner
+                    },
+                );
+                self.output.push_str(&format!(" as {}", cast.target));
+            }
+            Expression::Range(range) => {
+                let operand = Context {
+                    precedence: 1,
+                    ..inner
+                };
+                self.expression(&range.start, operand);
+                self.output
+                    .push_str(if range.inclusive { "..=" } else { ".." });
+                self.expression(&range.end, operand);
+            }
+            Expression::Interpolation(interpolation) => {
+                self.output.push('"');
+                for part in &interpolation.parts {
+                    match part {
+                        InterpolationPart::Text(text) => self.string_text(text),
+                        InterpolationPart::Expression(expression) => {
+                            self.output.push('{');
+                            self.expression(expression, Context::default());
+                            self.output.push('}');
+                        }
+                    }
+                }
+                self.output.push('"');
+            }
+            Expression::List(list) => {
+                self.output.push('[');
+                for (index, element) in list.elements.iter().enumerate() {
+                    if index > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.expression(element, Context::default());
+                }
+                self.output.push(']');
+            }
+            Expression::Comprehension(comprehension) => {
+                self.output.push('[');
+                self.expression(&comprehension.element, Context::default());
+                self.output.push_str(" for ");
+                self.output.push_str(&comprehension.variable);
+                self.output.push_str(" in ");
+                self.expression(&comprehension.iterable, Context::default());
+                if let Some(condition) = &comprehension.condition {
+                    self.output.push_str(" if ");
+                    self.expression(condition, Context::default());
+                }
+                self.output.push(']');
+            }
+            Expression::Tuple(tuple) => {
+                self.output.push('(');
+                for (index, element) in tuple.elements.iter().enumerate() {
+                    if index > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.expression(element, Context::default());
+                }
+                self.output.push(')');
+            }
+            Expression::Map(map) => {
+                self.output.push('{');
+                for (index, (key, value)) in map.entries.iter().enumerate() {
+                    if index > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.expression(key, Context::default());
+                    self.output.push_str(": ");
+                    self.expression(value, Context::default());
+                }
+                self.output.push('}');
+            }
+            Expression::Slice(slice) => {
+                self.expression(
+                    &slice.object,
+                    Context {
+                        precedence: POSTFIX,
+                        ..inner
+                    },
+                );
+                self.output.push('[');
+                if let Some(start) = &slice.start {
+                    self.expression(start, Context::default());
+                }
+                self.output.push(':');
+                if let Some(end) = &slice.end {
+                    self.expression(end, Context::default());
+                }
+                self.output.push(']');
+            }
+            Expression::Index(index) => {
+                self.expression(
+                    &index.object,
+                    Context {
+                        precedence: POSTFIX,
+                        ..inner
+                    },
+                );
+                self.output.push('[');
+                self.expression(&index.index, Context::default());
+                self.output.push(']');
+            }
+            Expression::StructInit(init) => {
+                self.output.push_str(&init.struct_name);
+                if init.fields.is_empty() {
+                    self.output.push_str(" {}");
+                } else {
+                    let mut fields: Vec<_> = init.fields.iter().collect();
+                    fields.sort_by(|a, b| a.0.cmp(b.0));
+                    self.output.push_str(" { ");
+                    for (index, (name, value)) in fields.into_iter().enumerate() {
+                        if index > 0 {
+                            self.output.push_str(", ");
+                        }
+                        self.output.push_str(name);
+                        self.output.push_str(": ");
+                        self.expression(value, Context::default());
+                    }
+                    self.output.push_str(" }");
+                }
+            }
+            Expression::Lambda(lambda) => self.lambda(lambda, inner),
+            Expression::If(if_expression) => {
+                self.output.push_str("if ");
+                self.expression(
+                    &if_expression.condition,
+                    Context {
+                        precedence: 0,
+                        no_struct: true,
+                    },
+                );
+                self.output.push_str(" { ");
+                self.expression(&if_expression.then_value, Context::default());
+                self.output.push_str(" } else ");
+                if let Expression::If(_) = &*if_expression.else_value {
+                    self.expression(&if_expression.else_value, Context::default());
+                } else {
+                    self.output.push_str("{ ");
+                    self.expression(&if_expression.else_value, Context::default());
+                    self.output.push_str(" }");
+                }
+            }
+            Expression::Match(match_expression) => {
+                self.output.push_str("match ");
+                self.expression(
+                    &match_expression.scrutinee,
+                    Context {
+                        precedence: 0,
+                        no_struct: true,
+                    },
+                );
+                self.output.push_str(" {\n");
+                self.indent += 1;
+                for arm in &match_expression.arms {
+                    self.write_indent();
+                    self.pattern(&arm.pattern);
+                    self.output.push_str(" => ");
+                    self.expression(&arm.body, Context::default());
+                    self.output.push_str(",\n");
+                }
+                self.indent -= 1;
+                self.write_indent();
+                self.output.push('}');
+            }
+        }
+
+        if wrap {
+            self.output.push(')');
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        match literal {
+            Literal::Integer(value) => self.output.push_str(&value.to_string()),
+            Literal::Float(value) => {
+                // `Display` never uses an exponent, but drops `.0` from whole numbers.
+                let text = value.to_string();
+                self.output.push_str(&text);
+                if value.is_finite() && !text.contains('.') {
+                    self.output.push_str(".0");
+                }
+            }
+            Literal::String(value) => {
+                self.output.push('"');
+                self.string_text(value);
+                self.output.push('"');
+            }
+            Literal::Boolean(value) => self.output.push_str(&value.to_string()),
+            Literal::None => self.output.push_str("none"),
+        }
+    }
+
+    /// The text of a string literal, escaped, with braces doubled.
+    fn string_text(&mut self, text: &str) {
+        for c in text.chars() {
+            match ESCAPES.iter().find(|(_, escaped)| *escaped == c) {
+                Some((escape, _)) => {
+                    self.output.push('\\');
+                    self.output.push(*escape);
+                }
+                None if c == '{' || c == '}' => {
+                    self.output.push(c);
+                    self.output.push(c);
+                }
+                None => self.output.push(c),
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard => self.output.push('_'),
+            Pattern::Literal(literal) => self.literal(literal),
+            Pattern::Binding(name) => self.output.push_str(name),
+            Pattern::Variant(variant) => {
+                self.output.push_str(&variant.enum_name);
+                self.output.push('.');
+                self.output.push_str(&variant.variant);
+                if !variant.bindings.is_empty() {
+                    let bindings: Vec<&str> =
+                        variant.bindings.iter().map(|name| name.as_str()).collect();
+                    self.output.push_str(&format!("({})", bindings.join(", ")));
+                }
+            }
+        }
+    }
+
+    fn lambda(&mut self, lambda: &Lambda, context: Context) {
+        let parameters: Vec<String> = lambda
+            .parameters
+            .iter()
+            .map(|parameter| match &parameter.param_type {
+                Some(ty) => format!("{}: {}", parameter.name, ty),
+                None => parameter.name.to_string(),
+            })
+            .collect();
+        match lambda.body.as_ref() {
+            LambdaBody::Block(return_type, block) => {
+                self.output
+                    .push_str(&format!("fn({})", parameters.join(", ")));
+                if *return_type != Type::Void {
+                    self.output.push_str(&format!(" -> {}", return_type));
+                }
+                self.output.push(' ');
+                self.block(block);
+            }
+            LambdaBody::Expression(body) => {
+                self.output
+                    .push_str(&format!("|{}| ", parameters.join(", ")));
+                self.expression(body, context);
+            }
+        }
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.output.push_str(INDENT);
+        }
+    }
+}
+
+/// Mirrors the parser's binding strengths: binary operators from 1 (`||`)
+/// to 7 (`*`), then prefix operators, then postfix calls and member access.
+fn binary_precedence(operator: &BinaryOperator) -> u8 {
+    match operator {
+        BinaryOperator::Or => 1,
+        BinaryOperator::And => 2,
+        BinaryOperator::Eq | BinaryOperator::Ne => 3,
+        BinaryOperator::Lt | BinaryOperator::Le | BinaryOperator::Gt | BinaryOperator::Ge => 4,
+        BinaryOperator::Coalesce => 5,
+        BinaryOperator::BitOr => 6,
+        BinaryOperator::BitXor => 7,
+        BinaryOperator::BitAnd => 8,
+        BinaryOperator::Shl | BinaryOperator::Shr => 9,
+        BinaryOperator::Add | BinaryOperator::Sub => 10,
+        BinaryOperator::Mul | BinaryOperator::Div | BinaryOperator::Mod => 11,
+        BinaryOperator::Pow => POWER,
+    }
+}
+
+fn expression_precedence(expression: &Expression) -> u8 {
+    match expression {
+        Expression::Binary(binary) => binary_precedence(&binary.operator),
+        Expression::Range(_) => 0,
+        Expression::Cast(_) => CAST,
+        // The body of `|x| ...` takes in everything after it.
+        Expression::Lambda(lambda) if matches!(*lambda.body, LambdaBody::Expression(_)) => 0,
+        Expression::Unary(_) => PREFIX,
+        // A negative literal prints with a leading `-`, which reads as a prefix
+        // operator when it is the object of a call or member access.
+        Expression::Literal(Literal::Integer(value)) if *value < 0 => PREFIX,
+        Expression::Literal(Literal::Float(value)) if value.is_sign_negative() => PREFIX,
+        _ => POSTFIX + 1,
+    }
+}
@@ -0,0 +1,210 @@
+//! Dead code elimination for release builds: the functions, methods,
+//! structs, enums and constants that `main` can never reach are left out of
+//! the generated Rust, so there is less for rustc to compile.
+//!
+//! Reachability goes by name across every module of the program. An item is
+//! kept when any kept item mentions its name, whichever module the mention
+//! refers to, so an item is only dropped when nothing that runs could refer
+//! to it. A method is kept when its struct is and a kept item mentions its
+//! name. Global variables and exceptions are always kept, as are the items
+//! named in a selective import, which the generated `use` needs.
+
+use crate::compiler::ast::*;
+use crate::compiler::intern::Symbol;
+use std::collections::{HashMap, HashSet};
+
+/// The items and methods reachable from `main`.
+#[derive(Debug, Default)]
+pub struct Live {
+    items: HashSet<Symbol>,
+    /// By struct and method name.
+    methods: HashSet<(Symbol, Symbol)>,
+}
+
+/// The items and methods of `modules` reachable from `main`, or `None` when
+/// no module defines `main`, so there is nothing to prune from.
+pub fn live_items<'p>(modules: impl IntoIterator<Item = &'p Program>) -> Option<Live> {
+    let modules: Vec<&Program> = modules.into_iter().collect();
+    let mut items: HashMap<Symbol, Vec<&Item>> = HashMap::new();
+    let mut methods: HashMap<Symbol, Vec<(Symbol, &Method)>> = HashMap::new();
+    let mut references = References::default();
+    for program in &modules {
+        for import in &program.imports {
+            for item in import.items.iter().flatten() {
+                references.names.insert(item.name);
+            }
+        }
+        for item in &program.items {
+            match item {
+                Item::Function(function) => items.entry(function.name).or_default().push(item),
+                Item::Struct(structure) if !structure.exception => {
+                    items.entry(structure.name).or_default().push(item)
+                }
+                Item::Enum(enumeration) => items.entry(enumeration.name).or_default().push(item),
+                Item::Impl(implementation) => {
+                    for method in &implementation.methods {
+                        methods
+                            .entry(method.function.name)
+                            .or_default()
+                            .push((implementation.struct_name, method));
+                    }
+                }
+                Item::Constant(constant) => items.entry(constant.name).or_default().push(item),
+                _ => references.visit_item(item),
+            }
+        }
+    }
+    let defines_main = modules
+        .iter()
+        .flat_map(|program| &program.items)
+        .any(|item| matches!(item, Item::Function(function) if function.name == "main"));
+    if !defines_main {
+        return None;
+    }
+    references.names.insert(Symbol::intern("main"));
+
+    let mut live = Live::default();
+    loop {
+        let found: Vec<Symbol> = references
+            .names
+            .iter()
+            .filter(|name| items.contains_key(name) && !live.items.contains(*name))
+            .copied()
+            .collect();
+        let found_methods: Vec<(Symbol, &Method)> = references
+            .names
+            .iter()
+            .filter_map(|name| methods.get(name))
+            .flatten()
+            .filter(|(struct_name, method)| {
+                let key = (*struct_name, method.function.name);
+                live.items.contains(struct_name) && !live.methods.contains(&key)
+            })
+            .copied()
+            .collect();
+        if found.is_empty() && found_methods.is_empty() {
+            break;
+        }
+        for name in found {
+            live.items.insert(name);
+            for item in &items[&name] {
+                references.visit_item(item);
+            }
+        }
+        for (struct_name, method) in found_methods {
+            live.methods.insert((struct_name, method.function.name));
+            references.visit_method(method);
+        }
+    }
+    Some(live)
+}
+
+/// `program` without the functions, structs, enums, constants and methods
+/// not in `live`, and without the `impl` blocks left with no methods.
+pub fn prune(program: &Program, live: &Live) -> Program {
+    let items = program
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Function(function) => live.items.contains(&function.name).then(|| item.clone()),
+            Item::Struct(structure) => {
+                (structure.exception || live.items.contains(&structure.name)).then(|| item.clone())
+            }
+            Item::Enum(enumeration) => live.items.contains(&enumeration.name).then(|| item.clone()),
+            Item::Impl(implementation) => {
+                let methods: Vec<Method> = implementation
+                    .methods
+                    .iter()
+                    .filter(|method| {
+                        let key = (implementation.struct_name, method.function.name);
+                        live.methods.contains(&key)
+                    })
+                    .cloned()
+                    .collect();
+                (!methods.is_empty()).then(|| {
+                    Item::Impl(Impl {
+                        methods,
+                        ..implementation.clone()
+                    })
+                })
+            }
+            Item::Constant(constant) => live.items.contains(&constant.name).then(|| item.clone()),
+            Item::Variable(_) => Some(item.clone()),
+        })
+        .collect();
+    Program {
+        items,
+        imports: program.imports.clone(),
+    }
+}
+
+/// Every name the items it visits mention: in expressions, types and
+/// `catch` clauses.
+#[derive(Default)]
+struct References {
+    names: HashSet<Symbol>,
+}
+
+impl References {
+    fn type_names(&mut self, ty: &Type) {
+        match ty {
+            Type::Struct(name) => {
+                self.names.insert(*name);
+            }
+            Type::Generic(name, arguments) => {
+                self.names.insert(*name);
+                for argument in arguments {
+                    self.type_names(argument);
+                }
+            }
+            Type::List(element) | Type::Optional(element) => self.type_names(element),
+            Type::Map(key, value) => {
+                self.type_names(key);
+                self.type_names(value);
+            }
+            Type::Function(parameters, result) => {
+                for parameter in parameters {
+                    self.type_names(parameter);
+                }
+                self.type_names(result);
+            }
+            Type::Tuple(elements) => {
+                for element in elements {
+                    self.type_names(element);
+                }
+            }
+            Type::Int | Type::Float | Type::Str | Type::Bool | Type::Param(_) | Type::Void => {}
+        }
+    }
+}
+
+impl Visitor for References {
+    fn visit_type(&mut self, ty: &Type) {
+        self.type_names(ty);
+    }
+
+    fn visit_identifier(&mut self, identifier: &Identifier) {
+        self.names.insert(identifier.name);
+    }
+
+    fn visit_member_access(&mut self, access: &MemberAccess) {
+        self.names.insert(access.member);
+        self.visit_expression(&access.object);
+    }
+
+    fn visit_struct_init(&mut self, init: &StructInitializer) {
+        self.names.insert(init.struct_name);
+        walk_struct_init(self, init);
+    }
+
+    fn visit_catch_clause(&mut self, clause: &CatchClause) {
+        self.names.insert(clause.exception_type);
+        self.visit_block(&clause.handler_block);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        if let Pattern::Variant(variant) = pattern {
+            self.names.insert(variant.enum_name);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Which modules of a directory build have to be compiled again. The
+//! `Database` knows what each module was last read as, a hash of its source
+//! and the modules it imports, and the revision its output was last written
+//! in. A module is out of date when it or a module it imports, directly or
+//! through others, changed since then, or when it has never compiled.
+//!
+//! It is the one invalidation model of the compiler: watch mode keeps a
+//! `Database` between rebuilds and tells it which files changed, and
+//! incremental builds save it in the build cache for the next run.
+//!
+//! Every change to a module starts a new revision. A module nested in
+//! another is declared by it, so adding or removing one changes the module
+//! containing it too.
+
+use crate::compiler::builtins;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// Counts changes to modules; a module remembers the revisions it last
+/// changed and compiled in.
+pub type Revision = u64;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Database {
+    revision: Revision,
+    modules: BTreeMap<String, Module>,
+}
+
+/// What the database knows about one module.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Module {
+    /// The `cache::source_hash` of its source.
+    hash: u64,
+    /// The modules of the directory it imports, by name.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    imports: Vec<String>,
+    changed_at: Revision,
+    /// When its output was last written, or `None` if it has not compiled
+    /// since it was added or last failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compiled_at: Option<Revision>,
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn revision(&self) -> Revision {
+        self.revision
+    }
+
+    /// Whether the database knows of no module, so every module is out of
+    /// date.
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty()
+    }
+
+    pub fn contains(&self, module_name: &str) -> bool {
+        self.modules.contains_key(module_name)
+    }
+
+    /// Every module, by name.
+    pub fn modules(&self) -> impl Iterator<Item = &str> {
+        self.modules.keys().map(String::as_str)
+    }
+
+    /// Records that the source of `module_name` hashes to `hash`, starting a
+    /// new revision unless it is unchanged.
+    pub fn set_source(&mut self, module_name: &str, hash: u64) {
+        match self.modules.get(module_name) {
+            Some(module) if module.hash == hash => return,
+            Some(_) => {}
+            None => self.changed_nested(module_name),
+        }
+        self.revision += 1;
+        let module = self
+            .modules
+            .entry(module_name.to_string())
+            .or_insert(Module {
+                hash,
+                imports: Vec::new(),
+                changed_at: 0,
+                compiled_at: None,
+            });
+        module.hash = hash;
+        module.changed_at = self.revision;
+    }
+
+    /// Records the modules `module_name` imports, as read from its source.
+    /// Builtin modules are left out: they never change.
+    pub fn set_imports<'i>(
+        &mut self,
+        module_name: &str,
+        imports: impl IntoIterator<Item = &'i str>,
+    ) {
+        if let Some(module) = self.modules.get_mut(module_name) {
+            module.imports = imports
+                .into_iter()
+                .filter(|import| !builtins::is_module(import))
+                .map(str::to_string)
+                .collect();
+        }
+    }
+
+    /// Forgets `module_name`, whose file is gone. The modules importing it
+    /// are out of date from then on.
+    pub fn remove(&mut self, module_name: &str) {
+        if self.modules.remove(module_name).is_some() {
+            self.changed_nested(module_name);
+        }
+    }
+
+    /// Records that the output of `module_name` was written from its current
+    /// source.
+    pub fn compiled(&mut self, module_name: &str) {
+        let revision = self.revision;
+        if let Some(module) = self.modules.get_mut(module_name) {
+            module.compiled_at = Some(revision);
+        }
+    }
+
+    /// Records that `module_name` has no output to keep: it failed to
+    /// compile, or its output is gone.
+    pub fn invalidate(&mut self, module_name: &str) {
+        if let Some(module) = self.modules.get_mut(module_name) {
+            module.compiled_at = None;
+        }
+    }
+
+    /// The modules that have to be compiled again: those that have not
+    /// compiled, and those that did before they or a module they import
+    /// changed, or before a module they import was removed.
+    pub fn outdated(&self) -> BTreeSet<String> {
+        self.modules
+            .iter()
+            .filter(|(name, module)| match module.compiled_at {
+                Some(compiled_at) => self.changed_since(name, compiled_at),
+                None => true,
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Whether `module_name`, or a module it imports directly or through
+    /// others, changed after `revision` or is gone.
+    fn changed_since(&self, module_name: &str, revision: Revision) -> bool {
+        let mut queue = vec![module_name];
+        let mut visited = HashSet::new();
+        while let Some(name) = queue.pop() {
+            if !visited.insert(name) {
+                continue;
+            }
+            let Some(module) = self.modules.get(name) else {
+                return true;
+            };
+            if module.changed_at > revision {
+                return true;
+            }
+            queue.extend(module.imports.iter().map(String::as_str));
+        }
+        false
+    }
+
+    /// Changes the module containing `module_name`, which declares it, when
+    /// it is added or removed.
+    fn changed_nested(&mut self, module_name: &str) {
+        let Some((parent, _)) = module_name.rsplit_once('.') else {
+            return;
+        };
+        if let Some(module) = self.modules.get_mut(parent) {
+            self.revision += 1;
+            module.changed_at = self.revision;
+        }
+    }
+}
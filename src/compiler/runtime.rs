@@ -0,0 +1,193 @@
+//! Support code for generated modules. Generated modules build on their own,
+//! so each one carries a copy of the helpers it uses, appended after its
+//! items.
+
+/// A support item, named `Rustic*` or `rustic_*` in generated code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Helper {
+    /// `list[RusticIndex(index)]`: the element at `index`, counting from the
+    /// end when it is negative.
+    Index,
+    /// `map[RusticKey(&key)]`: the entry for `key`, which must be present.
+    Key,
+    /// `rustic_slice(&list, start, end)`: the elements between the bounds.
+    Slice,
+    /// `rustic_pow(base, exponent)`: `base ** exponent` for `int`s.
+    Pow,
+    /// `rustic_div(left, right)` and `rustic_rem(left, right)`: `/` and `%`
+    /// for `int`s.
+    Divide,
+    /// `rustic_shl(value, shift)` and `rustic_shr(value, shift)`: `<<` and
+    /// `>>` for `int`s.
+    Shift,
+    /// `RusticError::from_panic(payload)`: the error a `try` block failed
+    /// with. The `RusticError` enum itself lists the exceptions the module
+    /// knows, so code generation writes it along with `exception`, which
+    /// picks the one a payload is, and `is_error`, which tells a payload
+    /// that is a Rustic error from other panics.
+    Error,
+    /// `rustic_main(|| { ... })`: the body of the program's `main`, with
+    /// errors reported as the interpreter reports them.
+    Main,
+}
+
+impl Helper {
+    pub fn source(self) -> &'static str {
+        match self {
+            Helper::Index => {
+                "struct RusticIndex(i64);
+
+impl RusticIndex {
+    fn position(self, len: usize) -> usize {
+        let position = if self.0 < 0 { self.0 + len as i64 } else { self.0 };
+        if position < 0 || position >= len as i64 {
+            panic!(\"index {} is out of bounds for a list of length {}\", self.0, len);
+        }
+        position as usize
+    }
+}
+
+impl<T> std::ops::Index<RusticIndex> for Vec<T> {
+    type Output = T;
+
+    fn index(&self, index: RusticIndex) -> &T {
+        &self[index.position(self.len())]
+    }
+}
+
+impl<T> std::ops::IndexMut<RusticIndex> for Vec<T> {
+    fn index_mut(&mut self, index: RusticIndex) -> &mut T {
+        let position = index.position(self.len());
+        &mut self[position]
+    }
+}"
+            }
+            Helper::Key => {
+                "struct RusticKey<'k, K>(&'k K);
+
+impl<K: Ord, V> std::ops::Index<RusticKey<'_, K>> for std::collections::BTreeMap<K, V> {
+    type Output = V;
+
+    fn index(&self, key: RusticKey<'_, K>) -> &V {
+        self.get(key.0).unwrap_or_else(|| panic!(\"key not found\"))
+    }
+}
+
+impl<K: Ord, V> std::ops::IndexMut<RusticKey<'_, K>> for std::collections::BTreeMap<K, V> {
+    fn index_mut(&mut self, key: RusticKey<'_, K>) -> &mut V {
+        self.get_mut(key.0).unwrap_or_else(|| panic!(\"key not found\"))
+    }
+}"
+            }
+            // Like Python, bounds past either end are clamped rather than
+            // reported.
+            Helper::Slice => {
+                "fn rustic_slice<T: Clone>(list: &[T], start: i64, end: i64) -> Vec<T> {
+    let len = list.len() as i64;
+    let bound = |index: i64| (if index < 0 { index + len } else { index }).clamp(0, len) as usize;
+    let (start, end) = (bound(start), bound(end));
+    list[start..end.max(start)].to_vec()
+}"
+            }
+            // Like the other arithmetic, overflow wraps. Squaring takes any
+            // `int` exponent, where `wrapping_pow` only takes a `u32`.
+            Helper::Pow => {
+                "fn rustic_pow(base: i64, exponent: i64) -> i64 {
+    if exponent < 0 {
+        panic!(\"attempt to raise an `int` to a negative power\");
+    }
+    let (mut base, mut exponent, mut power) = (base, exponent, 1i64);
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            power = power.wrapping_mul(base);
+        }
+        base = base.wrapping_mul(base);
+        exponent >>= 1;
+    }
+    power
+}"
+            }
+            // Functions rather than operators, so that rustc cannot reject a
+            // division it can tell fails; it fails when run instead, with the
+            // interpreter's message.
+            Helper::Divide => {
+                "fn rustic_div(left: i64, right: i64) -> i64 {
+    if right == 0 {
+        panic!(\"attempt to divide by zero\");
+    }
+    left.checked_div(right)
+        .unwrap_or_else(|| panic!(\"attempt to divide with overflow\"))
+}
+
+fn rustic_rem(left: i64, right: i64) -> i64 {
+    if right == 0 {
+        panic!(\"attempt to calculate the remainder with a divisor of zero\");
+    }
+    left.checked_rem(right)
+        .unwrap_or_else(|| panic!(\"attempt to calculate the remainder with overflow\"))
+}"
+            }
+            // Shifting by a negative number or 64 or more bits is an error,
+            // as it is when the shift is a constant.
+            Helper::Shift => {
+                "fn rustic_shl(value: i64, shift: i64) -> i64 {
+    u32::try_from(shift)
+        .ok()
+        .and_then(|shift| value.checked_shl(shift))
+        .unwrap_or_else(|| panic!(\"attempt to shift left with overflow\"))
+}
+
+fn rustic_shr(value: i64, shift: i64) -> i64 {
+    u32::try_from(shift)
+        .ok()
+        .and_then(|shift| value.checked_shr(shift))
+        .unwrap_or_else(|| panic!(\"attempt to shift right with overflow\"))
+}"
+            }
+            // `throw` panics with the exception; any other panic is a runtime
+            // error with a message.
+            Helper::Error => {
+                "type RusticPayload = Box<dyn std::any::Any + Send>;
+
+impl RusticError {
+    fn from_panic(payload: RusticPayload) -> Self {
+        match RusticError::exception(payload) {
+            Ok(error) => error,
+            Err(payload) => RusticError::Runtime(
+                payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| \"unknown error\".to_string()),
+            ),
+        }
+    }
+}"
+            }
+            // The panic hook keeps quiet about Rustic errors, which may yet be
+            // caught; one that reaches `main` uncaught is reported there.
+            // Other panics are bugs and keep the default report.
+            Helper::Main => {
+                "fn rustic_main(main: impl FnOnce()) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if !RusticError::is_error(info.payload()) {
+            default_hook(info);
+        }
+    }));
+    let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(main)) else {
+        return;
+    };
+    if !RusticError::is_error(&*payload) {
+        std::panic::resume_unwind(payload);
+    }
+    match RusticError::from_panic(payload) {
+        RusticError::Runtime(message) => eprintln!(\"Runtime error: {}\", message),
+        error => eprintln!(\"Runtime error: uncaught exception {}\", error.message()),
+    }
+    std::process::exit(101);
+}"
+            }
+        }
+    }
+}
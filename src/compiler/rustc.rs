@@ -0,0 +1,213 @@
+//! Errors rustc finds in the generated Rust, read from what `cargo
+//! --message-format=json` or `rustc --error-format=json` prints and moved
+//! back, through the source maps of the generated files, onto the Rustic
+//! lines that generated the code.
+
+use crate::compiler::sourcemap::SourceMap;
+use crate::diagnostics::{codes, Diagnostic, DiagnosticEngine, Span};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// A line of Cargo's JSON output. Only the `compiler-message` lines carry a
+/// `message`.
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<CompilerMessage>,
+}
+
+/// A diagnostic as rustc gives it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompilerMessage {
+    pub message: String,
+    pub level: String,
+    pub code: Option<CompilerCode>,
+    pub spans: Vec<CompilerSpan>,
+    /// The message as rustc would have printed it.
+    pub rendered: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompilerCode {
+    pub code: String,
+}
+
+/// Where in the crate a message points, with lines and columns counted
+/// from 1 and `file_name` relative to the crate's directory.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompilerSpan {
+    pub file_name: String,
+    pub line_start: usize,
+    pub column_start: usize,
+    pub is_primary: bool,
+    pub label: Option<String>,
+    /// The macro call the span's code was expanded from, if any.
+    pub expansion: Option<Box<CompilerExpansion>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CompilerExpansion {
+    pub span: CompilerSpan,
+}
+
+/// The errors among the messages in Cargo's JSON `output`, without the
+/// closing "aborting due to" one, which only counts the others.
+pub fn errors(output: &str) -> Vec<CompilerMessage> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<CargoMessage>(line).ok())
+        .filter(|line| line.reason == "compiler-message")
+        .filter_map(|line| line.message)
+        .filter(is_error)
+        .collect()
+}
+
+/// The errors among the messages rustc's JSON `output` gives one per line,
+/// like `errors` for Cargo's.
+pub fn rustc_errors(output: &str) -> Vec<CompilerMessage> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<CompilerMessage>(line).ok())
+        .filter(is_error)
+        .collect()
+}
+
+fn is_error(message: &CompilerMessage) -> bool {
+    message.level == "error" && !message.message.starts_with("aborting")
+}
+
+/// Reports each of `messages` that maps onto Rustic source through the
+/// source maps of the crate in `crate_dir`, and returns what else to tell
+/// about the failure: the messages that do not map, or `output` when none
+/// were found at all.
+pub fn report(
+    crate_dir: &Path,
+    messages: Vec<CompilerMessage>,
+    output: &str,
+    diagnostics: &mut DiagnosticEngine,
+) -> String {
+    let mut translator = Translator::new(crate_dir);
+    let (mut reported, mut unmapped) = (false, String::new());
+    for message in messages {
+        match translator.diagnostic(&message, diagnostics) {
+            Some(diagnostic) => {
+                diagnostics.emit(diagnostic);
+                reported = true;
+            }
+            None => unmapped.push_str(message.rendered.as_deref().unwrap_or(&message.message)),
+        }
+    }
+    match (reported, unmapped.is_empty()) {
+        (false, true) => output.to_string(),
+        (true, true) => "the errors rustc found are reported above".to_string(),
+        (_, false) => unmapped,
+    }
+}
+
+/// Moves rustc's errors back onto Rustic source for the crate in one
+/// directory, reading the source map of each generated file once.
+pub struct Translator<'a> {
+    crate_dir: &'a Path,
+    maps: HashMap<String, SourceMap>,
+}
+
+impl<'a> Translator<'a> {
+    pub fn new(crate_dir: &'a Path) -> Self {
+        Self {
+            crate_dir,
+            maps: HashMap::new(),
+        }
+    }
+
+    /// `message` as an error on the Rustic line its primary span came from,
+    /// with its other spans that map as labels. `None` if the primary span is
+    /// not in generated code, such as `lib.rs`, which has no source map.
+    pub fn diagnostic(
+        &mut self,
+        message: &CompilerMessage,
+        diagnostics: &mut DiagnosticEngine,
+    ) -> Option<Diagnostic> {
+        let primary = message.spans.iter().find(|span| span.is_primary)?;
+        let label = primary
+            .label
+            .as_ref()
+            .map_or(String::new(), |label| format!(": {}", label));
+        let primary = call_site(primary);
+        let span = self.span(primary, diagnostics)?;
+        let code = message
+            .code
+            .as_ref()
+            .map_or(String::new(), |code| format!("[{}]", code.code));
+        let mut diagnostic = Diagnostic::error(
+            format!("the generated Rust does not compile: {}", message.message),
+            span,
+        )
+        .with_code(codes::GENERATED_RUST)
+        .with_note(format!(
+            "rustc error{} in the generated crate at {}:{}:{}{}",
+            code, primary.file_name, primary.line_start, primary.column_start, label
+        ));
+        for other in &message.spans {
+            let Some(label) = other.label.as_ref().filter(|_| !other.is_primary) else {
+                continue;
+            };
+            if let Some(span) = self.span(call_site(other), diagnostics) {
+                diagnostic = diagnostic.with_label(span, label.clone());
+            }
+        }
+        Some(diagnostic)
+    }
+
+    /// The Rustic line, without its indentation, that the generated line
+    /// `span` points at came from.
+    fn span(&mut self, span: &CompilerSpan, diagnostics: &mut DiagnosticEngine) -> Option<Span> {
+        if !self.maps.contains_key(&span.file_name) {
+            let code = fs::read_to_string(self.crate_dir.join(&span.file_name)).ok()?;
+            self.maps
+                .insert(span.file_name.clone(), SourceMap::of(&code));
+        }
+        let mapping = self.maps[&span.file_name].lookup(span.line_start)?;
+        let source = diagnostics.source(&mapping.file);
+        Some(line_span(&mapping.file, mapping.line, source.as_deref()))
+    }
+}
+
+/// Where the code `span` points at was written: for code a macro expanded,
+/// such as `println!`, the call of the outermost macro.
+fn call_site(span: &CompilerSpan) -> &CompilerSpan {
+    match &span.expansion {
+        Some(expansion) => call_site(&expansion.span),
+        None => span,
+    }
+}
+
+/// A span over line `line` of `file` whose text is `source`, from its first
+/// character after the indentation to its end. Without the text, only the
+/// line is known.
+fn line_span(file: &str, line: usize, source: Option<&str>) -> Span {
+    let mut span = Span {
+        file: file.to_string(),
+        start_line: line,
+        start_column: 1,
+        end_line: line,
+        end_column: 1,
+        start_offset: 0,
+        end_offset: 0,
+    };
+    let Some(source) = source else {
+        return span;
+    };
+    let mut offset = 0;
+    for text in source.split_inclusive('\n').take(line.saturating_sub(1)) {
+        offset += text.len();
+    }
+    let text = source[offset..].lines().next().unwrap_or("");
+    let indent = text.len() - text.trim_start().len();
+    span.start_column = text[..indent].chars().count() + 1;
+    span.end_column = text.trim_end().chars().count() + 1;
+    span.start_offset = offset + indent;
+    span.end_offset = offset + text.trim_end().len();
+    span
+}
@@ -0,0 +1,4090 @@
+use crate::compiler::ast::*;
+use crate::compiler::builtins::{self, Builtin, ParamKind};
+use crate::compiler::flow;
+use crate::compiler::fold;
+use crate::compiler::intern::Symbol;
+use crate::compiler::printer;
+use crate::compiler::xref::{SymbolKind, XrefIndex};
+use crate::diagnostics::{
+    codes, Applicability, Diagnostic, DiagnosticEngine, Error, Level, Lint, Result, Span,
+};
+use crate::ice;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+
+/// Types of every spanned expression in a checked program, consumed by codegen.
+pub type TypeTable = HashMap<Span, Type>;
+
+/// Caller -> callees for one module. Callers are functions, methods (named
+/// `Struct.method`) or, for calls in their initializers, globals; builtin
+/// and imported callees are named `module.function`.
+pub type CallGraph = BTreeMap<String, BTreeSet<String>>;
+
+/// Types bound to type parameters, inferred at a call or given in a type.
+type Substitution = HashMap<Symbol, Type>;
+
+#[derive(Debug, Clone)]
+struct FunctionSignature {
+    type_params: Vec<Symbol>,
+    parameters: Vec<Type>,
+    parameter_names: Vec<Symbol>,
+    /// Whether each parameter has a default value, which calls may leave it
+    /// to.
+    defaults: Vec<bool>,
+    return_type: Type,
+    /// Where the function and each of its parameters are declared.
+    span: Span,
+    parameter_spans: Vec<Span>,
+}
+
+impl FunctionSignature {
+    fn of(function: &Function) -> Self {
+        Self {
+            type_params: function.type_params.clone(),
+            parameters: function
+                .parameters
+                .iter()
+                .map(|p| p.param_type.clone())
+                .collect(),
+            parameter_names: function.parameters.iter().map(|p| p.name).collect(),
+            defaults: function
+                .parameters
+                .iter()
+                .map(|p| p.default_value.is_some())
+                .collect(),
+            return_type: function.return_type.clone(),
+            span: function.span.clone(),
+            parameter_spans: function.parameters.iter().map(|p| p.span.clone()).collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for FunctionSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let parameters: Vec<String> = self.parameters.iter().map(Type::to_string).collect();
+        write!(f, "fn")?;
+        if !self.type_params.is_empty() {
+            let type_params: Vec<&str> =
+                self.type_params.iter().map(|name| name.as_str()).collect();
+            write!(f, "[{}]", type_params.join(", "))?;
+        }
+        write!(f, "({}) -> {}", parameters.join(", "), self.return_type)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    ty: Type,
+    mutable: bool,
+    definition: usize,
+    /// Whether any expression has referred to the symbol.
+    used: bool,
+    /// A copy of an optional variable inside the `if` that checked it for
+    /// `none`, with the optional unwrapped.
+    narrowed: bool,
+}
+
+pub struct SemanticAnalyzer<'a> {
+    diagnostics: &'a mut DiagnosticEngine,
+    structs: HashMap<Symbol, Vec<(Symbol, Type)>>,
+    /// Type parameters of generic structs.
+    generic_structs: HashMap<Symbol, Vec<Symbol>>,
+    /// Structs declared with `exception`.
+    exceptions: HashSet<Symbol>,
+    /// Variants of each enum with their payload types.
+    enums: HashMap<Symbol, Vec<(Symbol, Vec<Type>)>>,
+    functions: HashMap<Symbol, FunctionSignature>,
+    /// Methods by struct and name, with whether they take `var self`.
+    methods: HashMap<(Symbol, Symbol), (FunctionSignature, bool)>,
+    scopes: Vec<HashMap<Symbol, Binding>>,
+    /// Imported modules, with the span of the first import of each.
+    imports: HashMap<String, Span>,
+    /// Names code qualifies the items of imported modules with, as given by
+    /// `qualifiers`.
+    qualifiers: HashMap<Symbol, String>,
+    /// Imported modules that have been referred to, directly or through
+    /// an item they declare.
+    used_imports: HashSet<String>,
+    /// Other modules of the program by name, as added with `add_module`.
+    modules: HashMap<String, Arc<Program>>,
+    /// `pub` items of imported modules, with the module declaring each.
+    imported: HashMap<Symbol, String>,
+    /// Imported items their import renames, with the name their module
+    /// declares each under.
+    renamed_imports: HashMap<Symbol, Symbol>,
+    /// Items that imported modules declare without `pub`, with the module
+    /// declaring each.
+    private_imports: HashMap<Symbol, String>,
+    /// `pub` items of imported modules that their import leaves out of
+    /// scope, with the module declaring each and how to refer to them.
+    unimported: HashMap<Symbol, (String, String)>,
+    /// Names more than one module imported whole declares, as given by
+    /// `ambiguous_imports`.
+    ambiguous: HashMap<Symbol, Vec<Import>>,
+    /// Methods of imported structs declared without `pub`, by struct and name.
+    private_methods: HashMap<(Symbol, Symbol), String>,
+    /// `@deprecated` functions and structs.
+    deprecated: HashMap<(SymbolKind, Symbol), Deprecation>,
+    return_type: Option<Type>,
+    /// Type parameters in scope: those of the function being checked and of
+    /// its `impl` block or struct.
+    type_params: Vec<Symbol>,
+    /// Loops around the statement being checked that `break` can reach.
+    loop_depth: usize,
+    /// Loops cut off by an enclosing `try` block, which is compiled to a
+    /// closure that `break` and `continue` cannot leave.
+    loops_outside_try: usize,
+    /// Declaration of the function being checked.
+    function_span: Option<Span>,
+    /// For each lambda being checked, innermost last, the index of its scope
+    /// in `scopes`; variables from the scopes before it are captured.
+    closures: Vec<usize>,
+    caller: Option<Symbol>,
+    calls: CallGraph,
+    xref: XrefIndex,
+    types: TypeTable,
+    /// The program the last `analyze` checked, with its constants folded.
+    folded: Option<Program>,
+    errors: usize,
+}
+
+impl<'a> SemanticAnalyzer<'a> {
+    pub fn new(diagnostics: &'a mut DiagnosticEngine) -> Self {
+        Self {
+            diagnostics,
+            structs: HashMap::new(),
+            generic_structs: HashMap::new(),
+            exceptions: HashSet::new(),
+            enums: HashMap::new(),
+            functions: HashMap::new(),
+            methods: HashMap::new(),
+            scopes: vec![HashMap::new()],
+            imports: HashMap::new(),
+            qualifiers: HashMap::new(),
+            used_imports: HashSet::new(),
+            modules: HashMap::new(),
+            imported: HashMap::new(),
+            renamed_imports: HashMap::new(),
+            private_imports: HashMap::new(),
+            unimported: HashMap::new(),
+            ambiguous: HashMap::new(),
+            private_methods: HashMap::new(),
+            deprecated: HashMap::new(),
+            return_type: None,
+            type_params: Vec::new(),
+            loop_depth: 0,
+            loops_outside_try: 0,
+            function_span: None,
+            closures: Vec::new(),
+            caller: None,
+            calls: CallGraph::new(),
+            xref: XrefIndex::new(),
+            types: TypeTable::new(),
+            folded: None,
+            errors: 0,
+        }
+    }
+
+    /// Makes the items of another module of the program known, so the
+    /// module being checked can import it.
+    pub fn add_module(&mut self, name: &str, program: Arc<Program>) {
+        self.modules.insert(name.to_string(), program);
+    }
+
+    pub fn analyze(&mut self, program: &Program) -> Result<TypeTable> {
+        for import in &program.imports {
+            if let Some(first) = self.imports.get(&import.module_path) {
+                let diagnostic = Diagnostic::error(
+                    format!("Module `{}` is imported more than once", import.module_path),
+                    import.span.clone(),
+                )
+                .with_code(codes::IMPORT)
+                .with_label(first.clone(), "first imported here");
+                self.report(diagnostic);
+            } else {
+                self.imports
+                    .insert(import.module_path.clone(), import.span.clone());
+            }
+            if !builtins::is_module(&import.module_path)
+                && !self.modules.contains_key(&import.module_path)
+            {
+                self.report(
+                    Diagnostic::error(
+                        format!("Unknown module `{}`", import.module_path),
+                        import.span.clone(),
+                    )
+                    .with_code(codes::UNKNOWN_MODULE)
+                    .with_note("modules are the other `.rsc` files compiled along with this one"),
+                );
+            }
+            if let Some(item) = import.items.iter().flatten().next() {
+                if builtins::is_module(&import.module_path) {
+                    let diagnostic = Diagnostic::error(
+                        format!(
+                            "Items of built-in module `{}` cannot be imported by name",
+                            import.module_path
+                        ),
+                        import.span.clone(),
+                    )
+                    .with_code(codes::IMPORT)
+                    .with_help(format!(
+                        "import the module and refer to them as `{}.{}`",
+                        import.module_path, item.name
+                    ));
+                    self.report(diagnostic);
+                }
+            }
+        }
+        self.qualifiers = qualifiers(program, &self.modules);
+        self.ambiguous = ambiguous_imports(program, &self.modules);
+        self.check_qualifiers(program);
+
+        self.collect_declarations(program);
+        for import in &program.imports {
+            // A second import of a module, or of one under a name another
+            // module took, only gets reported.
+            if self.imports.get(&import.module_path) != Some(&import.span) {
+                continue;
+            }
+            if import.items.is_none()
+                && self.qualifiers.get(&import.name()) != Some(&import.module_path)
+            {
+                continue;
+            }
+            if let Some(module) = self.modules.get(&import.module_path).cloned() {
+                self.import_module(import, &module);
+            }
+        }
+
+        for item in &program.items {
+            self.visit_item(item);
+        }
+        self.check_unused_functions(program);
+        self.check_unused_imports(program);
+        for diagnostic in flow::unreachable_code(program) {
+            self.report(diagnostic);
+        }
+        for diagnostic in flow::unassigned_uses(program, &self.xref) {
+            self.report(diagnostic);
+        }
+        let mut folded = program.clone();
+        for diagnostic in fold::fold(&mut folded, &self.types) {
+            self.report(diagnostic);
+        }
+        self.folded = Some(folded);
+
+        let errors = self.diagnostics.error_count();
+        self.diagnostics.report_unused_suppressions();
+        self.errors += self.diagnostics.error_count() - errors;
+
+        if self.errors > 0 {
+            return Err(Error::SemanticError(format!(
+                "found {} error{}",
+                self.errors,
+                if self.errors == 1 { "" } else { "s" }
+            )));
+        }
+
+        Ok(std::mem::take(&mut self.types))
+    }
+
+    /// The program the last `analyze` checked, with its constants folded,
+    /// which is what code is generated from. `None` before `analyze`, or
+    /// once taken.
+    pub fn take_folded(&mut self) -> Option<Program> {
+        self.folded.take()
+    }
+
+    /// Calls seen by the last `analyze`, including those in rejected programs.
+    pub fn call_graph(&self) -> &CallGraph {
+        &self.calls
+    }
+
+    /// Definitions and resolved references seen by the last `analyze`.
+    pub fn xref(&self) -> &XrefIndex {
+        &self.xref
+    }
+
+    fn collect_declarations(&mut self, program: &Program) {
+        for item in &program.items {
+            match item {
+                Item::Struct(structure) => {
+                    self.xref.define(
+                        &structure.name,
+                        SymbolKind::Struct,
+                        structure.name.to_string(),
+                        None,
+                        false,
+                        &structure.span,
+                    );
+                    self.check_name(SymbolKind::Struct, &structure.name, &structure.span);
+                    if let Some(deprecation) = &structure.deprecated {
+                        self.deprecated
+                            .insert((SymbolKind::Struct, structure.name), deprecation.clone());
+                    }
+                    for field in &structure.fields {
+                        self.xref.define(
+                            &field.name,
+                            SymbolKind::Field,
+                            field.field_type.to_string(),
+                            Some(&structure.name),
+                            false,
+                            &field.span,
+                        );
+                        self.check_name(SymbolKind::Field, &field.name, &field.span);
+                    }
+                    self.check_type_params(&structure.type_params, &structure.span);
+                    if structure.exception {
+                        if structure.name == "Error" {
+                            self.report(
+                                Diagnostic::error(
+                                    "`Error` cannot be declared as an exception",
+                                    structure.span.clone(),
+                                )
+                                .with_code(codes::NOT_AN_EXCEPTION)
+                                .with_help("`catch Error` already catches every error"),
+                            );
+                        }
+                        self.exceptions.insert(structure.name);
+                    }
+                    if !structure.type_params.is_empty() {
+                        self.generic_structs
+                            .insert(structure.name, structure.type_params.clone());
+                    }
+                    let fields = structure
+                        .fields
+                        .iter()
+                        .map(|field| (field.name, field.field_type.clone()))
+                        .collect();
+                    if self.enums.contains_key(&structure.name) {
+                        let first = self.item_span(SymbolKind::Enum, &structure.name);
+                        self.redefinition(
+                            format!("Type `{}` is defined more than once", structure.name),
+                            &structure.span,
+                            first,
+                        );
+                    } else if self.structs.insert(structure.name, fields).is_some() {
+                        let first = self.item_span(SymbolKind::Struct, &structure.name);
+                        self.redefinition(
+                            format!("Struct `{}` is defined more than once", structure.name),
+                            &structure.span,
+                            first,
+                        );
+                    }
+                }
+                Item::Enum(enumeration) => self.collect_enum(enumeration),
+                Item::Function(function) => {
+                    self.check_type_params(&function.type_params, &function.span);
+                    let signature = FunctionSignature::of(function);
+                    self.xref.define(
+                        &function.name,
+                        SymbolKind::Function,
+                        signature.to_string(),
+                        None,
+                        false,
+                        &function.span,
+                    );
+                    self.check_name(SymbolKind::Function, &function.name, &function.span);
+                    if let Some(deprecation) = &function.deprecated {
+                        self.deprecated
+                            .insert((SymbolKind::Function, function.name), deprecation.clone());
+                    }
+                    if let Some(first) = self.functions.insert(function.name, signature) {
+                        self.redefinition(
+                            format!("Function `{}` is defined more than once", function.name),
+                            &function.span,
+                            Some(first.span),
+                        );
+                    }
+                }
+                // Collected once every struct is known.
+                Item::Impl(_) => {}
+                // A global without a type is reported when it is checked.
+                Item::Variable(variable) => {
+                    if let Some(ty) = &variable.var_type {
+                        self.declare_global(variable.name, SymbolKind::Global, ty, &variable.span);
+                    }
+                }
+                Item::Constant(constant) => {
+                    self.declare_global(
+                        constant.name,
+                        SymbolKind::Constant,
+                        &constant.const_type,
+                        &constant.span,
+                    );
+                }
+            }
+        }
+
+        for item in &program.items {
+            if let Item::Impl(implementation) = item {
+                self.collect_impl(implementation);
+            }
+        }
+    }
+
+    /// Declares the `pub` items of an imported module, under the names its
+    /// import gives them. Items of the module being checked shadow them; the
+    /// module's other items and methods are remembered so uses of them are
+    /// reported as private or not imported.
+    fn import_module(&mut self, import: &Import, module: &Program) {
+        let (module_name, span) = (import.module_path.as_str(), &import.span);
+        if let Some(items) = &import.items {
+            self.check_imported_items(module_name, module, items);
+        }
+        for item in &module.items {
+            let Some(name) = item.name() else {
+                continue;
+            };
+            let local = imported_name(import, item, &self.ambiguous);
+            if !item.is_public() {
+                let name = local.unwrap_or(name);
+                self.private_imports.insert(name, module_name.to_string());
+                continue;
+            }
+            let help = match local {
+                Some(local) if local == name => None,
+                Some(local) => Some(format!("refer to it as `{}`", local)),
+                None => Some(format!(
+                    "add it to the items imported from `{}`",
+                    module_name
+                )),
+            };
+            if let Some(help) = help {
+                self.unimported
+                    .insert(name, (module_name.to_string(), help));
+            }
+            let Some(local) = local else {
+                continue;
+            };
+            if local != name {
+                self.renamed_imports.insert(local, name);
+            }
+            let name = local;
+            if let Some(other) = self.imported.get(&name) {
+                let message = format!(
+                    "`{}` is imported from both `{}` and `{}`",
+                    name, other, module_name
+                );
+                self.error(codes::IMPORT, message, span);
+                continue;
+            }
+            let declared = self.structs.contains_key(&name)
+                || self.enums.contains_key(&name)
+                || self.functions.contains_key(&name)
+                || self.scopes[0].contains_key(&name);
+            if declared {
+                continue;
+            }
+            self.imported.insert(name, module_name.to_string());
+            match item {
+                Item::Struct(structure) => {
+                    let fields = structure
+                        .fields
+                        .iter()
+                        .map(|field| (field.name, field.field_type.clone()))
+                        .collect();
+                    self.structs.insert(name, fields);
+                    if !structure.type_params.is_empty() {
+                        self.generic_structs
+                            .insert(name, structure.type_params.clone());
+                    }
+                    if structure.exception {
+                        self.exceptions.insert(name);
+                    }
+                    if let Some(deprecation) = &structure.deprecated {
+                        self.deprecated
+                            .insert((SymbolKind::Struct, name), deprecation.clone());
+                    }
+                }
+                Item::Enum(enumeration) => {
+                    let variants = enumeration
+                        .variants
+                        .iter()
+                        .map(|variant| (variant.name, variant.payload.clone()))
+                        .collect();
+                    self.enums.insert(name, variants);
+                }
+                Item::Function(function) => {
+                    self.functions.insert(name, FunctionSignature::of(function));
+                    if let Some(deprecation) = &function.deprecated {
+                        self.deprecated
+                            .insert((SymbolKind::Function, name), deprecation.clone());
+                    }
+                }
+                Item::Variable(variable) => {
+                    // The module declaring it reports a global without a type.
+                    if let Some(ty) = &variable.var_type {
+                        self.import_global(name, SymbolKind::Global, ty, &variable.span);
+                    }
+                }
+                Item::Constant(constant) => {
+                    let ty = &constant.const_type;
+                    self.import_global(name, SymbolKind::Constant, ty, &constant.span);
+                }
+                Item::Impl(_) => {}
+            }
+        }
+
+        for item in &module.items {
+            let Item::Impl(implementation) = item else {
+                continue;
+            };
+            let struct_name = implementation.struct_name;
+            if self.imported.get(&struct_name).map(String::as_str) != Some(module_name) {
+                continue;
+            }
+            for method in &implementation.methods {
+                let key = (struct_name, method.function.name);
+                if method.function.public {
+                    let signature = FunctionSignature::of(&method.function);
+                    self.methods.insert(key, (signature, method.mutable));
+                } else {
+                    self.private_methods.insert(key, module_name.to_string());
+                }
+            }
+        }
+    }
+
+    /// Reports items of a selective import that `module` does not let this
+    /// module import.
+    fn check_imported_items(
+        &mut self,
+        module_name: &str,
+        module: &Program,
+        items: &[ImportedItem],
+    ) {
+        let mut names = HashSet::new();
+        for selected in items {
+            if !names.insert(selected.local_name()) {
+                let message = format!("`{}` is imported more than once", selected.local_name());
+                self.error(codes::IMPORT, message, &selected.span);
+                continue;
+            }
+            let item = module
+                .items
+                .iter()
+                .find(|item| item.name() == Some(selected.name));
+            match item {
+                None => {
+                    let message =
+                        format!("Module `{}` has no item `{}`", module_name, selected.name);
+                    self.error(codes::NO_MEMBER, message, &selected.span);
+                }
+                Some(item) if !item.is_public() => {
+                    self.private(selected.name, module_name, &selected.span);
+                }
+                Some(Item::Struct(_) | Item::Enum(_)) if selected.alias.is_some() => {
+                    let diagnostic = Diagnostic::error(
+                        format!("Type `{}` cannot be renamed", selected.name),
+                        selected.span.clone(),
+                    )
+                    .with_code(codes::IMPORT)
+                    .with_help(format!(
+                        "types keep their names when imported, so import it as `{}`",
+                        selected.name
+                    ));
+                    self.report(diagnostic);
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Binds a global of an imported module. Its definition is recorded in
+    /// this module's index too, where diagnostics can point to it.
+    fn import_global(&mut self, name: Symbol, kind: SymbolKind, ty: &Type, span: &Span) {
+        let definition = self
+            .xref
+            .define(&name, kind, ty.to_string(), None, false, span);
+        let binding = Binding {
+            ty: ty.clone(),
+            mutable: false,
+            definition,
+            used: false,
+            narrowed: false,
+        };
+        self.scopes[0].insert(name, binding);
+    }
+
+    fn collect_impl(&mut self, implementation: &Impl) {
+        let struct_name = implementation.struct_name;
+        let Some(fields) = self.structs.get(&struct_name).cloned() else {
+            let message = if self.enums.contains_key(&struct_name) {
+                format!(
+                    "Methods can only be added to structs, and `{}` is an enum",
+                    struct_name
+                )
+            } else {
+                format!("Unknown struct `{}`", struct_name)
+            };
+            self.error(codes::UNKNOWN_NAME, message, &implementation.span);
+            return;
+        };
+        let expected = self
+            .generic_structs
+            .get(&struct_name)
+            .cloned()
+            .unwrap_or_default();
+        if implementation.type_params.len() != expected.len() {
+            let mut diagnostic = Diagnostic::error(
+                format!(
+                    "Struct `{}` has {} type parameter{}, but the `impl` block names {}",
+                    struct_name,
+                    expected.len(),
+                    if expected.len() == 1 { "" } else { "s" },
+                    implementation.type_params.len()
+                ),
+                implementation.span.clone(),
+            )
+            .with_code(codes::TYPE_ARGUMENTS);
+            if !expected.is_empty() {
+                let names: Vec<&str> = expected.iter().map(|name| name.as_str()).collect();
+                diagnostic = diagnostic.with_help(format!(
+                    "write `impl {}[{}]`",
+                    struct_name,
+                    names.join(", ")
+                ));
+            }
+            self.report(diagnostic);
+            return;
+        }
+
+        for method in &implementation.methods {
+            let function = &method.function;
+            let type_params: Vec<Symbol> = implementation
+                .type_params
+                .iter()
+                .chain(&function.type_params)
+                .copied()
+                .collect();
+            self.check_type_params(&type_params, &function.span);
+            let signature = FunctionSignature::of(function);
+            self.xref.define(
+                &function.name,
+                SymbolKind::Method,
+                signature.to_string(),
+                Some(&struct_name),
+                false,
+                &function.span,
+            );
+            self.check_name(SymbolKind::Method, &function.name, &function.span);
+            if fields.iter().any(|(field, _)| *field == function.name) {
+                let field = self
+                    .xref
+                    .item(SymbolKind::Field, Some(&struct_name), &function.name);
+                let mut diagnostic = Diagnostic::error(
+                    format!(
+                        "Struct `{}` already has a field named `{}`",
+                        struct_name, function.name
+                    ),
+                    function.span.clone(),
+                )
+                .with_code(codes::REDEFINED);
+                if let Some(field) = field {
+                    diagnostic =
+                        diagnostic.with_label(self.definition_span(field), "field declared here");
+                }
+                self.report(diagnostic);
+                continue;
+            }
+            let key = (struct_name, function.name);
+            if let Some((first, _)) = self.methods.get(&key) {
+                let first = Some(first.span.clone());
+                self.redefinition(
+                    format!(
+                        "Method `{}` of `{}` is defined more than once",
+                        function.name, struct_name
+                    ),
+                    &function.span,
+                    first,
+                );
+                continue;
+            }
+            self.methods.insert(key, (signature, method.mutable));
+        }
+    }
+
+    fn check_type_params(&mut self, type_params: &[Symbol], span: &Span) {
+        for (index, name) in type_params.iter().enumerate() {
+            if type_params[..index].contains(name) {
+                self.error(
+                    codes::REDEFINED,
+                    format!("Type parameter `{}` is declared more than once", name),
+                    span,
+                );
+            }
+        }
+    }
+
+    /// The type of `self` in the methods of `implementation`, or `None` when
+    /// the block does not fit its struct.
+    fn impl_self_type(&self, implementation: &Impl) -> Option<Type> {
+        let name = implementation.struct_name;
+        if !self.structs.contains_key(&name) {
+            return None;
+        }
+        let expected = self.generic_structs.get(&name).map_or(0, Vec::len);
+        if implementation.type_params.len() != expected {
+            return None;
+        }
+        Some(match expected {
+            0 => Type::Struct(name),
+            _ => Type::Generic(
+                name,
+                implementation
+                    .type_params
+                    .iter()
+                    .map(|param| Type::Param(*param))
+                    .collect(),
+            ),
+        })
+    }
+
+    fn collect_enum(&mut self, enumeration: &Enum) {
+        self.xref.define(
+            &enumeration.name,
+            SymbolKind::Enum,
+            enumeration.name.to_string(),
+            None,
+            false,
+            &enumeration.span,
+        );
+        self.check_name(SymbolKind::Enum, &enumeration.name, &enumeration.span);
+
+        let mut variants: Vec<(Symbol, Vec<Type>)> = Vec::new();
+        for variant in &enumeration.variants {
+            let payload: Vec<String> = variant.payload.iter().map(Type::to_string).collect();
+            let ty = if payload.is_empty() {
+                enumeration.name.to_string()
+            } else {
+                format!("fn({}) -> {}", payload.join(", "), enumeration.name)
+            };
+            let definition = self.xref.define(
+                &variant.name,
+                SymbolKind::Variant,
+                ty,
+                Some(&enumeration.name),
+                false,
+                &variant.span,
+            );
+            self.check_name(SymbolKind::Variant, &variant.name, &variant.span);
+            if variants.iter().any(|(name, _)| *name == variant.name) {
+                let first = self
+                    .xref
+                    .item(SymbolKind::Variant, Some(&enumeration.name), &variant.name)
+                    .filter(|first| *first != definition)
+                    .map(|first| self.definition_span(first));
+                self.redefinition(
+                    format!(
+                        "Variant `{}` of `{}` is defined more than once",
+                        variant.name, enumeration.name
+                    ),
+                    &variant.span,
+                    first,
+                );
+                continue;
+            }
+            variants.push((variant.name, variant.payload.clone()));
+        }
+
+        if self.structs.contains_key(&enumeration.name)
+            || self.enums.contains_key(&enumeration.name)
+        {
+            let first = self
+                .item_span(SymbolKind::Struct, &enumeration.name)
+                .or_else(|| self.item_span(SymbolKind::Enum, &enumeration.name));
+            self.redefinition(
+                format!("Type `{}` is defined more than once", enumeration.name),
+                &enumeration.span,
+                first,
+            );
+            return;
+        }
+        self.enums.insert(enumeration.name, variants);
+    }
+
+    fn declare_global(&mut self, name: Symbol, kind: SymbolKind, ty: &Type, span: &Span) {
+        let definition = self
+            .xref
+            .define(&name, kind, ty.to_string(), None, false, span);
+        self.check_name(kind, &name, span);
+        let symbol = Binding {
+            ty: ty.clone(),
+            mutable: false,
+            definition,
+            used: false,
+            narrowed: false,
+        };
+        if let Some(first) = self.scopes[0].insert(name, symbol) {
+            let first = self.definition_span(first.definition);
+            self.redefinition(
+                format!("Global `{}` is defined more than once", name),
+                span,
+                Some(first),
+            );
+        }
+    }
+
+    fn check_assignment(&mut self, assignment: &Assignment) {
+        let target_type = match &assignment.target {
+            Expression::Identifier(identifier) => match self.resolve_target(assignment, identifier)
+            {
+                Some(_) if self.is_captured(identifier.name) => {
+                    self.captured_mutation(identifier.name, &assignment.span);
+                    None
+                }
+                Some(symbol) if symbol.narrowed => {
+                    self.narrowed_mutation(identifier.name, &assignment.span);
+                    None
+                }
+                Some(symbol) if symbol.mutable => {
+                    self.types
+                        .insert(identifier.span.clone(), symbol.ty.clone());
+                    Some(symbol.ty)
+                }
+                Some(symbol) => {
+                    self.immutable(
+                        format!("Cannot assign to immutable variable `{}`", identifier.name),
+                        &assignment.span,
+                        &identifier.name,
+                        symbol.definition,
+                    );
+                    None
+                }
+                None => {
+                    let message = format!("Undefined variable `{}`", identifier.name);
+                    self.unresolved(identifier.name, message, &identifier.span);
+                    None
+                }
+            },
+            Expression::MemberAccess(access) => {
+                let ty = self.check_expression(&assignment.target);
+                self.check_mutable_place(&access.object, &assignment.span);
+                ty
+            }
+            Expression::Index(index) => {
+                let ty = self.check_expression(&assignment.target);
+                self.check_mutable_place(&index.object, &assignment.span);
+                ty
+            }
+            _ => {
+                self.error(
+                    codes::INVALID_EXPRESSION,
+                    "Invalid assignment target",
+                    &assignment.span,
+                );
+                None
+            }
+        };
+
+        let value_type = match (&target_type, &assignment.operator) {
+            (Some(expected), None) => self.check_expected(&assignment.value, expected),
+            _ => self.check_expression(&assignment.value),
+        };
+        if let (Some(expected), Some(actual), Some(operator)) =
+            (&target_type, &value_type, &assignment.operator)
+        {
+            let result = binary_type(operator, expected, actual);
+            if !result.is_some_and(|result| fits(expected, &result)) {
+                let operator = format!("{}=", operator);
+                self.operator_mismatch(&operator, expected, actual, &assignment.span);
+            }
+        } else if let (Some(expected), Some(actual)) = (target_type, value_type) {
+            if !compatible(&expected, &actual) {
+                self.error(
+                    codes::MISMATCHED_TYPES,
+                    format!(
+                        "Cannot assign a value of type `{}` to a target of type `{}`",
+                        actual, expected
+                    ),
+                    &assignment.span,
+                );
+            }
+        }
+    }
+
+    /// Reports an error unless `expression` is rooted at a `var` binding.
+    fn check_mutable_place(&mut self, expression: &Expression, span: &Span) {
+        match expression {
+            Expression::Identifier(identifier) => {
+                if self.is_captured(identifier.name) {
+                    self.captured_mutation(identifier.name, span);
+                } else if let Some(symbol) = self.lookup(identifier.name) {
+                    if symbol.narrowed {
+                        self.narrowed_mutation(identifier.name, span);
+                    } else if !symbol.mutable {
+                        let definition = symbol.definition;
+                        self.immutable(
+                            format!("Cannot mutate immutable variable `{}`", identifier.name),
+                            span,
+                            &identifier.name,
+                            definition,
+                        );
+                    }
+                }
+            }
+            Expression::MemberAccess(access) => self.check_mutable_place(&access.object, span),
+            // Map entries are replaced with `insert`, so only list elements
+            // can be changed in place.
+            Expression::Index(index)
+                if index
+                    .object
+                    .span()
+                    .and_then(|span| self.types.get(span))
+                    .is_some_and(|ty| matches!(ty, Type::List(_))) =>
+            {
+                self.check_mutable_place(&index.object, span)
+            }
+            _ => self.error(codes::IMMUTABLE, "Cannot mutate a temporary value", span),
+        }
+    }
+
+    /// Reports a change to a variable through its copy narrowed by an `if`,
+    /// which would be lost when the block ends.
+    fn narrowed_mutation(&mut self, name: Symbol, span: &Span) {
+        self.report(
+            Diagnostic::error(
+                format!(
+                    "Cannot change `{}` inside the `if` that checked it for `none`",
+                    name
+                ),
+                span.clone(),
+            )
+            .with_code(codes::IMMUTABLE)
+            .with_help("change it after the `if` block instead"),
+        );
+    }
+
+    /// Reports a change to a variable from inside a lambda, which has its own
+    /// copy of the variable.
+    fn captured_mutation(&mut self, name: Symbol, span: &Span) {
+        self.report(
+            Diagnostic::error(
+                format!("Cannot change `{}` inside a lambda that captures it", name),
+                span.clone(),
+            )
+            .with_code(codes::IMMUTABLE)
+            .with_help("lambdas capture a copy of each variable they use when they are created"),
+        );
+    }
+
+    /// Reports use of a value that may be `none` as if it were not.
+    fn possibly_none(&mut self, message: String, span: &Span) {
+        self.report(
+            Diagnostic::error(message, span.clone())
+                .with_code(codes::POSSIBLY_NONE)
+                .with_help(
+                    "check it with `if value != none { ... }` first, or give a default with `??`",
+                ),
+        );
+    }
+
+    fn check_return(&mut self, return_statement: &ReturnStatement) {
+        let expected = self.return_type.clone().unwrap_or(Type::Void);
+        match &return_statement.value {
+            Some(value) => {
+                if let Some(actual) = self.check_expected(value, &expected) {
+                    if expected == Type::Void {
+                        let diagnostic = Diagnostic::error(
+                            "Cannot return a value from a `void` function",
+                            return_statement.span.clone(),
+                        )
+                        .with_code(codes::RETURN)
+                        .with_help(format!(
+                            "declare the function with `-> {}` to return this value",
+                            actual
+                        ));
+                        self.report(
+                            self.with_function_label(diagnostic, "declared without a return type"),
+                        );
+                    } else if !compatible(&expected, &actual) {
+                        let diagnostic = Diagnostic::error(
+                            format!("Expected return type `{}`, found `{}`", expected, actual),
+                            return_statement.span.clone(),
+                        )
+                        .with_code(codes::RETURN);
+                        let label = format!("expected `{}` because of this return type", expected);
+                        self.report(self.with_function_label(diagnostic, &label));
+                    }
+                }
+            }
+            None => {
+                if expected != Type::Void {
+                    let diagnostic = Diagnostic::error(
+                        format!("Missing return value; function returns `{}`", expected),
+                        return_statement.span.clone(),
+                    )
+                    .with_code(codes::RETURN);
+                    let label = format!("declared to return `{}` here", expected);
+                    self.report(self.with_function_label(diagnostic, &label));
+                }
+            }
+        }
+    }
+
+    fn check_condition(&mut self, condition: &Expression, span: &Span) {
+        if let Some(ty) = self.check_expression(condition) {
+            if ty != Type::Bool {
+                let span = condition.span().unwrap_or(span).clone();
+                self.error(
+                    codes::MISMATCHED_TYPES,
+                    format!("Condition must be `bool`, found `{}`", ty),
+                    &span,
+                );
+            }
+        }
+    }
+
+    /// Checks that a `break` or `continue` has a loop to leave.
+    fn check_loop_exit(&mut self, keyword: &str, span: &Span) {
+        if self.loop_depth > 0 {
+            return;
+        }
+        if self.loops_outside_try > 0 {
+            self.error(
+                codes::CONTROL_FLOW,
+                format!("`{}` cannot leave a `try` block", keyword),
+                span,
+            );
+        } else {
+            self.error(
+                codes::CONTROL_FLOW,
+                format!("`{}` outside of a loop", keyword),
+                span,
+            );
+        }
+    }
+
+    /// The type of a variable declared without one, which is that of its
+    /// initializer unless the initializer leaves part of it open.
+    fn infer_variable_type(&mut self, variable: &Variable) -> Option<Type> {
+        let ty = self.check_expression(variable.initializer.as_ref()?)?;
+        if ty == Type::Void {
+            self.error(
+                codes::INVALID_TYPE,
+                format!("Cannot store a value of type `void` in `{}`", variable.name),
+                &variable.span,
+            );
+            return None;
+        }
+        if contains_void(&ty) {
+            let example = match &ty {
+                Type::List(_) => "list[int]",
+                Type::Map(..) => "map[str, int]",
+                Type::Optional(_) => "int?",
+                _ => "<type>",
+            };
+            self.report(
+                Diagnostic::error(
+                    format!("Cannot infer the type of `{}`", variable.name),
+                    variable.span.clone(),
+                )
+                .with_code(codes::CANNOT_INFER)
+                .with_help(format!(
+                    "write the type, such as `let {}: {} = ...`",
+                    variable.name, example
+                )),
+            );
+            return None;
+        }
+        Some(ty)
+    }
+
+    fn check_initializer(&mut self, expected: &Type, value: &Expression, span: &Span) {
+        if let Some(actual) = self.check_expected(value, expected) {
+            if !compatible(expected, &actual) {
+                self.error(
+                    codes::MISMATCHED_TYPES,
+                    format!("Expected `{}`, found `{}`", expected, actual),
+                    span,
+                );
+            }
+        }
+    }
+
+    fn check_type(&mut self, ty: &Type, span: &Span) {
+        if let Type::Struct(name) | Type::Generic(name, _) = ty {
+            self.use_import(*name);
+        }
+        match ty {
+            Type::List(element) => self.check_type(element, span),
+            Type::Map(key, value) => {
+                self.check_type(key, span);
+                self.check_type(value, span);
+                if !is_map_key(key) {
+                    self.error(
+                        codes::INVALID_TYPE,
+                        format!("Map keys must be `int`, `str` or `bool`, found `{}`", key),
+                        span,
+                    );
+                }
+            }
+            Type::Optional(inner) => self.check_type(inner, span),
+            Type::Tuple(elements) => {
+                for element in elements {
+                    self.check_type(element, span);
+                }
+            }
+            Type::Function(parameters, return_type) => {
+                for parameter in parameters {
+                    self.check_type(parameter, span);
+                }
+                self.check_type(return_type, span);
+            }
+            Type::Param(name) if !self.type_params.contains(name) => {
+                self.error(
+                    codes::UNKNOWN_NAME,
+                    format!("Unknown type parameter `{}`", name),
+                    span,
+                );
+            }
+            Type::Generic(name, arguments) => {
+                for argument in arguments {
+                    self.check_type(argument, span);
+                    if contains_function(argument) {
+                        self.error(
+                            codes::TYPE_ARGUMENTS,
+                            format!("Type arguments cannot be functions, found `{}`", argument),
+                            span,
+                        );
+                    }
+                }
+                match self.generic_structs.get(name).map(Vec::len) {
+                    Some(expected) if expected == arguments.len() => {
+                        self.check_deprecated(SymbolKind::Struct, *name, span, false)
+                    }
+                    Some(expected) => self.error(
+                        codes::TYPE_ARGUMENTS,
+                        format!(
+                            "Struct `{}` expects {} type argument{}, found {}",
+                            name,
+                            expected,
+                            if expected == 1 { "" } else { "s" },
+                            arguments.len()
+                        ),
+                        span,
+                    ),
+                    None if self.structs.contains_key(name) || self.enums.contains_key(name) => {
+                        self.error(
+                            codes::TYPE_ARGUMENTS,
+                            format!("Type `{}` takes no type arguments", name),
+                            span,
+                        )
+                    }
+                    None => self.unresolved(*name, format!("Unknown type `{}`", name), span),
+                }
+            }
+            Type::Struct(name)
+                if !self.structs.contains_key(name) && !self.enums.contains_key(name) =>
+            {
+                self.unresolved(*name, format!("Unknown type `{}`", name), span);
+            }
+            Type::Struct(name) if self.generic_structs.contains_key(name) => {
+                let expected = self.generic_structs[name].len();
+                self.error(
+                    codes::TYPE_ARGUMENTS,
+                    format!(
+                        "Struct `{}` expects {} type argument{}, found 0",
+                        name,
+                        expected,
+                        if expected == 1 { "" } else { "s" }
+                    ),
+                    span,
+                );
+            }
+            Type::Struct(name) => self.check_deprecated(SymbolKind::Struct, *name, span, false),
+            _ => {}
+        }
+    }
+
+    fn check_expression(&mut self, expression: &Expression) -> Option<Type> {
+        let ty = match expression {
+            Expression::Literal(literal) => Some(literal_type(literal)),
+            Expression::Identifier(identifier)
+                if identifier.name == "self" && self.is_captured(identifier.name) =>
+            {
+                self.report(
+                    Diagnostic::error("Lambdas cannot use `self`", identifier.span.clone())
+                        .with_code(codes::INVALID_EXPRESSION)
+                        .with_help("copy what the lambda needs from `self` into a variable first"),
+                );
+                None
+            }
+            Expression::Identifier(identifier) => match self.resolve(identifier) {
+                Some(symbol) => Some(symbol.ty.clone()),
+                None => self.check_function_value(identifier),
+            },
+            Expression::Binary(binary) => self.check_binary(binary),
+            Expression::Unary(unary) => {
+                let operand = self.check_expression(&unary.operand)?;
+                match (&unary.operator, &operand) {
+                    (UnaryOperator::Neg, Type::Int | Type::Float) => Some(operand),
+                    (UnaryOperator::Not, Type::Bool) => Some(Type::Bool),
+                    (UnaryOperator::BitNot, Type::Int) => Some(Type::Int),
+                    _ => {
+                        self.error(
+                            codes::UNSUPPORTED_OPERATION,
+                            format!("Cannot apply `{}` to `{}`", unary.operator, operand),
+                            &unary.span,
+                        );
+                        None
+                    }
+                }
+            }
+            Expression::Call(call) => self.check_call(call),
+            Expression::MemberAccess(access) => self.check_member_access(access),
+            Expression::List(list) => self.check_list(list),
+            Expression::Tuple(tuple) => self.check_tuple(tuple),
+            Expression::Cast(cast) => self.check_cast(cast),
+            Expression::If(if_expression) => self.check_if_expression(if_expression),
+            Expression::Map(map) => self.check_map(map),
+            Expression::Index(index) => self.check_index(index),
+            Expression::Comprehension(comprehension) => self.check_comprehension(comprehension),
+            Expression::Slice(slice) => self.check_slice(slice),
+            Expression::StructInit(init) => self.check_struct_init(init),
+            Expression::Match(match_expression) => self.check_match(match_expression),
+            Expression::Interpolation(interpolation) => self.check_interpolation(interpolation),
+            Expression::Range(range) => {
+                self.check_range_bounds(range);
+                self.error(
+                    codes::INVALID_EXPRESSION,
+                    "Ranges can only be used in `for` loops",
+                    &range.span,
+                );
+                None
+            }
+            Expression::Lambda(lambda) => self.check_lambda(lambda, None),
+        };
+
+        if let (Some(span), Some(ty)) = (expression.span(), &ty) {
+            self.types.insert(span.clone(), ty.clone());
+        }
+        ty
+    }
+
+    /// A function named as a value rather than called, as in `apply(double, 5)`.
+    fn check_function_value(&mut self, identifier: &Identifier) -> Option<Type> {
+        let Some(signature) = self.functions.get(&identifier.name).cloned() else {
+            let message = format!("Undefined variable `{}`", identifier.name);
+            self.unresolved(identifier.name, message, &identifier.span);
+            return None;
+        };
+        if !signature.type_params.is_empty() {
+            self.report(
+                Diagnostic::error(
+                    format!(
+                        "Generic function `{}` cannot be used as a value",
+                        identifier.name
+                    ),
+                    identifier.span.clone(),
+                )
+                .with_code(codes::INVALID_EXPRESSION)
+                .with_help("wrap it in a lambda whose parameters give the types to use"),
+            );
+            return None;
+        }
+
+        self.record_call(&identifier.name);
+        self.reference_item(
+            SymbolKind::Function,
+            None,
+            &identifier.name,
+            &identifier.span,
+        );
+        self.check_deprecated(
+            SymbolKind::Function,
+            identifier.name,
+            &identifier.span,
+            true,
+        );
+        Some(Type::Function(
+            signature.parameters,
+            Box::new(signature.return_type),
+        ))
+    }
+
+    /// Checks `expression` where a value of type `expected` is wanted, which
+    /// gives the parameters of a lambda their types.
+    fn check_expected(&mut self, expression: &Expression, expected: &Type) -> Option<Type> {
+        let Expression::Lambda(lambda) = expression else {
+            return self.check_expression(expression);
+        };
+        let ty = self.check_lambda(lambda, Some(expected));
+        if let Some(ty) = &ty {
+            self.types.insert(lambda.span.clone(), ty.clone());
+        }
+        ty
+    }
+
+    /// A lambda's parameters without a type take it from `expected`, and a
+    /// `|...|` lambda returns what its body does, or `expected`'s return type
+    /// when the body's value can be stored as one.
+    fn check_lambda(&mut self, lambda: &Lambda, expected: Option<&Type>) -> Option<Type> {
+        let (expected_parameters, expected_return) = match expected {
+            Some(Type::Function(parameters, return_type))
+                if parameters.len() == lambda.parameters.len() =>
+            {
+                (Some(parameters), Some(return_type.as_ref()))
+            }
+            _ => (None, None),
+        };
+        let mut parameters = Vec::new();
+        for (index, parameter) in lambda.parameters.iter().enumerate() {
+            if let Some(ty) = &parameter.param_type {
+                self.check_type(ty, &parameter.span);
+                parameters.push(ty.clone());
+                continue;
+            }
+            // Type parameters of a callee still unbound say nothing.
+            match expected_parameters.map(|expected| &expected[index]) {
+                Some(ty) if !contains_param(ty) => parameters.push(ty.clone()),
+                _ => {
+                    self.report(
+                        Diagnostic::error(
+                            format!("Cannot infer the type of parameter `{}`", parameter.name),
+                            parameter.span.clone(),
+                        )
+                        .with_code(codes::CANNOT_INFER)
+                        .with_help(format!(
+                            "give the parameter a type, as in `{}: int`",
+                            parameter.name
+                        )),
+                    );
+                    return None;
+                }
+            }
+        }
+
+        self.closures.push(self.scopes.len());
+        self.push_scope();
+        for (parameter, ty) in lambda.parameters.iter().zip(&parameters) {
+            self.declare(
+                parameter.name,
+                SymbolKind::Parameter,
+                ty.clone(),
+                false,
+                &parameter.span,
+            );
+        }
+        let return_type = match lambda.body.as_ref() {
+            LambdaBody::Block(return_type, block) => {
+                self.check_type(return_type, &lambda.span);
+                let outer_return = self.return_type.replace(return_type.clone());
+                let outer_span = self.function_span.replace(lambda.span.clone());
+                let loop_depth = std::mem::take(&mut self.loop_depth);
+                let loops_outside_try = std::mem::take(&mut self.loops_outside_try);
+                walk_block(self, block);
+                self.check_returns(return_type, block);
+                self.return_type = outer_return;
+                self.function_span = outer_span;
+                self.loop_depth = loop_depth;
+                self.loops_outside_try = loops_outside_try;
+                Some(return_type.clone())
+            }
+            LambdaBody::Expression(body) => {
+                match expected_return.filter(|ty| !contains_param(ty)) {
+                    Some(expected) => self.check_expected(body, expected).map(|actual| {
+                        if compatible(expected, &actual) {
+                            expected.clone()
+                        } else {
+                            actual
+                        }
+                    }),
+                    None => self.check_expression(body),
+                }
+            }
+        };
+        self.pop_scope();
+        self.closures.pop();
+        Some(Type::Function(parameters, Box::new(return_type?)))
+    }
+
+    /// Whether `name` is a local variable from outside the lambda being
+    /// checked.
+    fn is_captured(&self, name: Symbol) -> bool {
+        let Some(&closure) = self.closures.last() else {
+            return false;
+        };
+        self.scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(&name))
+            .is_some_and(|index| index > 0 && index < closure)
+    }
+
+    /// Globals are created on first use and shared by every thread, which the
+    /// values of lambdas cannot be.
+    fn check_global_type(&mut self, name: Symbol, ty: &Type, span: &Span) {
+        if contains_function(ty) {
+            self.error(
+                codes::INVALID_TYPE,
+                format!("Global `{}` cannot hold a function, found `{}`", name, ty),
+                span,
+            );
+        }
+    }
+
+    fn check_binary(&mut self, binary: &BinaryOp) -> Option<Type> {
+        let left = self.check_expression(&binary.left);
+        let right = self.check_expression(&binary.right);
+        let (left, right) = (left?, right?);
+
+        let result = binary_type(&binary.operator, &left, &right);
+        if result.is_none() {
+            let operator = binary.operator.to_string();
+            self.operator_mismatch(&operator, &left, &right, &binary.span);
+        }
+        result
+    }
+
+    /// Reports an operator applied to operands it does not take.
+    fn operator_mismatch(&mut self, operator: &str, left: &Type, right: &Type, span: &Span) {
+        let message = format!("Cannot apply `{}` to `{}` and `{}`", operator, left, right);
+        let optional = [left, right]
+            .into_iter()
+            .any(|ty| matches!(ty, Type::Optional(_)));
+        if operator != "??" && optional {
+            self.possibly_none(message, span);
+        } else {
+            self.error(codes::UNSUPPORTED_OPERATION, message, span);
+        }
+    }
+
+    fn check_call(&mut self, call: &FunctionCall) -> Option<Type> {
+        match call.function.as_ref() {
+            Expression::Identifier(identifier) if self.lookup(identifier.name).is_none() => {
+                self.check_function_call(identifier, call)
+            }
+            Expression::MemberAccess(access) => {
+                if let Some(identifier) = self.qualified(access) {
+                    return self.check_function_call(&identifier, call);
+                }
+                if let Some(module) = self.module_name(&access.object) {
+                    let Some(builtin) = builtins::function(&module, &access.member) else {
+                        self.error(
+                            codes::NO_MEMBER,
+                            format!("Module `{}` has no function `{}`", module, access.member),
+                            &access.span,
+                        );
+                        self.check_arguments_only(call);
+                        return None;
+                    };
+                    let name = format!("{}.{}", module, access.member);
+                    self.record_call(&name);
+                    if self.reject_named_arguments(&name, call) {
+                        return None;
+                    }
+                    self.check_arguments(
+                        &name,
+                        &builtin.params,
+                        &passed(&call.arguments),
+                        &call.span,
+                        None,
+                    );
+                    return Some(builtin.return_type);
+                }
+
+                if let Some((enum_name, payload)) = self.enum_variant(access) {
+                    return self.check_variant(enum_name, access, payload, Some(call));
+                }
+
+                let Some(receiver) = self.check_expression(&access.object) else {
+                    self.check_arguments_only(call);
+                    return None;
+                };
+                if let Type::Optional(_) = receiver {
+                    self.possibly_none(
+                        format!(
+                            "Cannot call `{}` on a value of type `{}`, which may be `none`",
+                            access.member, receiver
+                        ),
+                        &access.span,
+                    );
+                    self.check_arguments_only(call);
+                    return None;
+                }
+                if let Some(struct_name) = struct_name(&receiver) {
+                    if let Some((signature, mutable)) =
+                        self.methods.get(&(struct_name, access.member)).cloned()
+                    {
+                        return self
+                            .check_method_call(&receiver, access, call, &signature, mutable);
+                    }
+                }
+                let Some(method) = builtins::method(&receiver, &access.member) else {
+                    let private = struct_name(&receiver).and_then(|struct_name| {
+                        self.private_methods.get(&(struct_name, access.member))
+                    });
+                    let diagnostic = match private {
+                        Some(module) => Diagnostic::error(
+                            format!(
+                                "Method `{}` of `{}` is private to module `{}`",
+                                access.member, receiver, module
+                            ),
+                            access.span.clone(),
+                        )
+                        .with_code(codes::PRIVATE)
+                        .with_help(format!("declare it `pub` in `{}` to use it here", module)),
+                        None => Diagnostic::error(
+                            format!("Type `{}` has no method `{}`", receiver, access.member),
+                            access.span.clone(),
+                        )
+                        .with_code(codes::NO_MEMBER),
+                    };
+                    self.report(diagnostic);
+                    self.check_arguments_only(call);
+                    return None;
+                };
+                self.check_method_receiver(&method, &access.object, &call.span);
+                if self.reject_named_arguments(&access.member, call) {
+                    return None;
+                }
+                self.check_arguments(
+                    &access.member,
+                    &method.params,
+                    &passed(&call.arguments),
+                    &call.span,
+                    None,
+                );
+                Some(method.return_type)
+            }
+            callee => {
+                let Some(ty) = self.check_expression(callee) else {
+                    self.check_arguments_only(call);
+                    return None;
+                };
+                let Type::Function(parameters, return_type) = ty else {
+                    self.error(
+                        codes::UNSUPPORTED_OPERATION,
+                        format!("Cannot call a value of type `{}`", ty),
+                        &call.span,
+                    );
+                    self.check_arguments_only(call);
+                    return None;
+                };
+                let name = printer::expression_to_source(callee);
+                if self.reject_named_arguments(&name, call) {
+                    return None;
+                }
+                let params: Vec<ParamKind> = parameters.into_iter().map(ParamKind::Exact).collect();
+                self.check_arguments(&name, &params, &passed(&call.arguments), &call.span, None);
+                Some(*return_type)
+            }
+        }
+    }
+
+    /// A call of the function `identifier` names.
+    fn check_function_call(
+        &mut self,
+        identifier: &Identifier,
+        call: &FunctionCall,
+    ) -> Option<Type> {
+        let Some(signature) = self.functions.get(&identifier.name).cloned() else {
+            let message = if self.structs.contains_key(&identifier.name) {
+                format!(
+                    "Struct `{}` must be created with `{} {{ ... }}`",
+                    identifier.name, identifier.name
+                )
+            } else {
+                format!("Undefined function `{}`", identifier.name)
+            };
+            self.unresolved(identifier.name, message, &identifier.span);
+            self.check_arguments_only(call);
+            return None;
+        };
+
+        self.record_call(&identifier.name);
+        self.reference_item(
+            SymbolKind::Function,
+            None,
+            &identifier.name,
+            &identifier.span,
+        );
+        self.check_deprecated(
+            SymbolKind::Function,
+            identifier.name,
+            &identifier.span,
+            true,
+        );
+        let arguments = self.arrange_arguments(&identifier.name, &signature, call)?;
+        let params: Vec<ParamKind> = signature
+            .parameters
+            .iter()
+            .cloned()
+            .map(ParamKind::Exact)
+            .collect();
+        let inferred = self.check_arguments(
+            &identifier.name,
+            &params,
+            &arguments,
+            &call.span,
+            Some(&signature),
+        );
+        self.instantiate(&identifier.name, &signature, &inferred, &call.span)
+    }
+
+    fn check_method_call(
+        &mut self,
+        receiver: &Type,
+        access: &MemberAccess,
+        call: &FunctionCall,
+        signature: &FunctionSignature,
+        mutates_receiver: bool,
+    ) -> Option<Type> {
+        let struct_name = struct_name(receiver)?;
+        self.record_call(&format!("{}.{}", struct_name, access.member));
+        self.reference_item(
+            SymbolKind::Method,
+            Some(&struct_name),
+            &access.member,
+            &access.span,
+        );
+        if mutates_receiver {
+            self.check_mutable_place(&access.object, &call.span);
+        }
+        // The struct's type arguments are known from the receiver; the
+        // method's own are inferred from the arguments.
+        let values = self.arrange_arguments(&access.member, signature, call)?;
+        let mut arguments = self.type_arguments(receiver);
+        let params: Vec<ParamKind> = signature
+            .parameters
+            .iter()
+            .map(|ty| ParamKind::Exact(substitute(ty, &arguments)))
+            .collect();
+        let inferred = self.check_arguments(
+            &access.member,
+            &params,
+            &values,
+            &call.span,
+            Some(signature),
+        );
+        arguments.extend(inferred);
+        self.instantiate(&access.member, signature, &arguments, &call.span)
+    }
+
+    /// The type parameters of the struct `ty` bound to its type arguments.
+    fn type_arguments(&self, ty: &Type) -> Substitution {
+        match ty {
+            Type::Generic(name, arguments) => self
+                .generic_structs
+                .get(name)
+                .into_iter()
+                .flatten()
+                .copied()
+                .zip(arguments.iter().cloned())
+                .collect(),
+            _ => Substitution::new(),
+        }
+    }
+
+    /// The return type of a call to `signature`, once the arguments have
+    /// bound each of its type parameters.
+    fn instantiate(
+        &mut self,
+        name: &str,
+        signature: &FunctionSignature,
+        inferred: &Substitution,
+        span: &Span,
+    ) -> Option<Type> {
+        if let Some(param) = signature
+            .type_params
+            .iter()
+            .find(|param| !inferred.contains_key(param))
+        {
+            self.error(
+                codes::CANNOT_INFER,
+                format!("Cannot infer type parameter `{}` of `{}`", param, name),
+                span,
+            );
+            return None;
+        }
+        for param in &signature.type_params {
+            if contains_function(&inferred[param]) {
+                self.error(
+                    codes::TYPE_ARGUMENTS,
+                    format!(
+                        "Type parameter `{}` of `{}` cannot be a function, found `{}`",
+                        param, name, inferred[param]
+                    ),
+                    span,
+                );
+            }
+        }
+        Some(substitute(&signature.return_type, inferred))
+    }
+
+    fn check_method_receiver(&mut self, method: &Builtin, receiver: &Expression, span: &Span) {
+        if method.mutates_receiver {
+            self.check_mutable_place(receiver, span);
+        }
+    }
+
+    /// `declaration` is the signature of a user-defined function, whose
+    /// declarations are pointed out when the arguments do not fit. Returns
+    /// the types the arguments bind the type parameters in `params` to.
+    fn check_arguments(
+        &mut self,
+        name: &str,
+        params: &[ParamKind],
+        arguments: &[Option<&Expression>],
+        span: &Span,
+        declaration: Option<&FunctionSignature>,
+    ) -> Substitution {
+        let mut inferred = Substitution::new();
+        if params.len() != arguments.len() {
+            let mut diagnostic = Diagnostic::error(
+                format!(
+                    "`{}` expects {} argument{}, found {}",
+                    name,
+                    params.len(),
+                    if params.len() == 1 { "" } else { "s" },
+                    arguments.len()
+                ),
+                span.clone(),
+            )
+            .with_code(codes::ARGUMENTS);
+            if let Some(declaration) = declaration {
+                diagnostic = diagnostic
+                    .with_label(declaration.span.clone(), format!("`{}` defined here", name));
+            }
+            self.report(diagnostic);
+        }
+
+        // Lambdas go last, so the other arguments can bind the type
+        // parameters their parameters' types mention.
+        let mut order: Vec<usize> = (0..arguments.len()).collect();
+        order.sort_by_key(|&index| matches!(arguments[index], Some(Expression::Lambda(_))));
+        for index in order {
+            // A default was checked where its function is declared.
+            let Some(argument) = arguments[index] else {
+                continue;
+            };
+            let actual = match params.get(index) {
+                Some(ParamKind::Exact(expected)) => {
+                    self.check_expected(argument, &substitute(expected, &inferred))
+                }
+                _ => self.check_expression(argument),
+            };
+            let Some(actual) = actual else {
+                continue;
+            };
+            let param = params.get(index).map(|param| match param {
+                ParamKind::Exact(expected) => {
+                    infer(expected, &actual, &mut inferred);
+                    ParamKind::Exact(substitute(expected, &inferred))
+                }
+                other => other.clone(),
+            });
+            match &param {
+                Some(ParamKind::Exact(expected)) if !compatible(expected, &actual) => {
+                    let span = argument.span().unwrap_or(span).clone();
+                    let mut diagnostic = Diagnostic::error(
+                        format!(
+                            "Argument {} of `{}` expects `{}`, found `{}`",
+                            index + 1,
+                            name,
+                            expected,
+                            actual
+                        ),
+                        span,
+                    )
+                    .with_code(codes::MISMATCHED_TYPES);
+                    if let Some(parameter) =
+                        declaration.and_then(|declaration| declaration.parameter_spans.get(index))
+                    {
+                        diagnostic = diagnostic.with_label(
+                            parameter.clone(),
+                            format!(
+                                "expected `{}` because of this parameter declaration",
+                                expected
+                            ),
+                        );
+                    }
+                    self.report(diagnostic);
+                }
+                Some(ParamKind::Printable) if contains_function(&actual) => {
+                    let span = argument.span().unwrap_or(span).clone();
+                    self.error(
+                        codes::UNSUPPORTED_OPERATION,
+                        format!("Cannot print a value of type `{}`", actual),
+                        &span,
+                    );
+                }
+                Some(ParamKind::Printable) if actual == Type::Void => {
+                    let span = argument.span().unwrap_or(span).clone();
+                    self.error(
+                        codes::INVALID_TYPE,
+                        format!("Argument {} of `{}` has no value", index + 1, name),
+                        &span,
+                    );
+                }
+                _ => {}
+            }
+        }
+        inferred
+    }
+
+    /// Checks the arguments of a call that cannot be checked against the
+    /// callee's parameters.
+    fn check_arguments_only(&mut self, call: &FunctionCall) {
+        for argument in &call.arguments {
+            self.check_expression(argument);
+        }
+        for argument in &call.named_arguments {
+            self.check_expression(&argument.value);
+        }
+    }
+
+    /// The arguments of `call` in the order of the callee's parameters, with
+    /// named arguments moved into place and `None` for parameters left to
+    /// their defaults. `None` when they do not fit the parameters, which has
+    /// been reported and the arguments checked.
+    fn arrange_arguments<'c>(
+        &mut self,
+        name: &str,
+        signature: &FunctionSignature,
+        call: &'c FunctionCall,
+    ) -> Option<Vec<Option<&'c Expression>>> {
+        let mut slots: Vec<Option<&Expression>> = call.arguments.iter().map(Some).collect();
+        slots.resize(slots.len().max(signature.parameters.len()), None);
+        let mut fits = true;
+        for argument in &call.named_arguments {
+            let position = signature
+                .parameter_names
+                .iter()
+                .position(|parameter| *parameter == argument.name);
+            let message = match position {
+                Some(index) if slots[index].is_none() => {
+                    slots[index] = Some(&argument.value);
+                    continue;
+                }
+                Some(_) => format!("Argument `{}` is passed more than once", argument.name),
+                None => format!("`{}` has no parameter named `{}`", name, argument.name),
+            };
+            fits = false;
+            self.report(
+                Diagnostic::error(message, argument.span.clone())
+                    .with_code(codes::ARGUMENTS)
+                    .with_label(signature.span.clone(), format!("`{}` defined here", name)),
+            );
+        }
+        let missing: Vec<String> = signature
+            .parameter_names
+            .iter()
+            .zip(&slots)
+            .zip(&signature.defaults)
+            .filter(|((_, slot), &default)| slot.is_none() && !default)
+            .map(|((parameter, _), _)| format!("`{}`", parameter))
+            .collect();
+        if fits && !missing.is_empty() {
+            fits = false;
+            self.report(
+                Diagnostic::error(
+                    format!(
+                        "Missing argument{} {} in call to `{}`",
+                        if missing.len() == 1 { "" } else { "s" },
+                        missing.join(", "),
+                        name
+                    ),
+                    call.span.clone(),
+                )
+                .with_code(codes::ARGUMENTS)
+                .with_label(signature.span.clone(), format!("`{}` defined here", name)),
+            );
+        }
+        if !fits {
+            self.check_arguments_only(call);
+            return None;
+        }
+        Some(slots)
+    }
+
+    /// Reports named arguments to a callee without parameter names, and then
+    /// checks the arguments. Returns whether there were any.
+    fn reject_named_arguments(&mut self, name: &str, call: &FunctionCall) -> bool {
+        let Some(first) = call.named_arguments.first() else {
+            return false;
+        };
+        self.report(
+            Diagnostic::error(
+                format!("`{}` does not take named arguments", name),
+                first.span.clone(),
+            )
+            .with_code(codes::ARGUMENTS)
+            .with_help("pass the arguments in order"),
+        );
+        self.check_arguments_only(call);
+        true
+    }
+
+    /// Records that the function being checked calls `callee`. Imported
+    /// functions are recorded as `module.name`, under the name their module
+    /// declares them with.
+    fn record_call(&mut self, callee: &str) {
+        let Some(caller) = &self.caller else {
+            return;
+        };
+        let name = Symbol::intern(callee);
+        let callee = match self.imported.get(&name) {
+            Some(module) => {
+                let declared = self.renamed_imports.get(&name).unwrap_or(&name);
+                format!("{}.{}", module, declared)
+            }
+            None => callee.to_string(),
+        };
+        self.calls.entry(caller.to_string()).or_default().insert(callee);
+    }
+
+    fn check_member_access(&mut self, access: &MemberAccess) -> Option<Type> {
+        if let Some(identifier) = self.qualified(access) {
+            return self.check_expression(&Expression::Identifier(identifier));
+        }
+        if let Some(module) = self.module_name(&access.object) {
+            return match builtins::constant(&module, &access.member) {
+                Some((ty, _)) => Some(ty),
+                None => {
+                    self.error(
+                        codes::NO_MEMBER,
+                        format!("Module `{}` has no member `{}`", module, access.member),
+                        &access.span,
+                    );
+                    None
+                }
+            };
+        }
+
+        if let Some((enum_name, payload)) = self.enum_variant(access) {
+            return self.check_variant(enum_name, access, payload, None);
+        }
+
+        let object = self.check_expression(&access.object)?;
+        if let Type::Optional(_) = object {
+            self.possibly_none(
+                format!(
+                    "Cannot access `{}` on a value of type `{}`, which may be `none`",
+                    access.member, object
+                ),
+                &access.span,
+            );
+            return None;
+        }
+        let struct_name = struct_name(&object);
+        let field = match struct_name {
+            Some(name) => {
+                self.reference_item(SymbolKind::Field, Some(&name), &access.member, &access.span);
+                let arguments = self.type_arguments(&object);
+                self.structs
+                    .get(&name)
+                    .and_then(|fields| fields.iter().find(|(field, _)| *field == access.member))
+                    .map(|(_, ty)| substitute(ty, &arguments))
+            }
+            None => None,
+        };
+
+        match (&field, struct_name) {
+            (Some(_), _) => {}
+            (None, Some(name)) if self.methods.contains_key(&(name, access.member)) => {
+                self.report(
+                    Diagnostic::error(
+                        format!("Method `{}` of `{}` must be called", access.member, name),
+                        access.span.clone(),
+                    )
+                    .with_code(codes::INVALID_EXPRESSION)
+                    .with_help(format!("add parentheses: `{}()`", access.member)),
+                );
+            }
+            (None, _) => self.error(
+                codes::NO_MEMBER,
+                format!("Type `{}` has no field `{}`", object, access.member),
+                &access.span,
+            ),
+        }
+        field
+    }
+
+    fn check_interpolation(&mut self, interpolation: &Interpolation) -> Option<Type> {
+        for part in &interpolation.parts {
+            let InterpolationPart::Expression(expression) = part else {
+                continue;
+            };
+            let span = expression.span().unwrap_or(&interpolation.span);
+            match self.check_expression(expression) {
+                Some(Type::Void) => self.error(
+                    codes::UNSUPPORTED_OPERATION,
+                    "Cannot interpolate a value of type `void`",
+                    span,
+                ),
+                Some(ty) if contains_function(&ty) => self.error(
+                    codes::UNSUPPORTED_OPERATION,
+                    format!("Cannot interpolate a value of type `{}`", ty),
+                    span,
+                ),
+                Some(ty @ Type::Optional(_)) => self.possibly_none(
+                    format!(
+                        "Cannot interpolate a value of type `{}`, which may be `none`",
+                        ty
+                    ),
+                    span,
+                ),
+                _ => {}
+            }
+        }
+        Some(Type::Str)
+    }
+
+    /// Checks that both bounds of `range` are `int`.
+    fn check_range_bounds(&mut self, range: &RangeExpression) {
+        for bound in [&range.start, &range.end] {
+            match self.check_expression(bound) {
+                Some(Type::Int) | None => {}
+                Some(other) => {
+                    let span = bound.span().unwrap_or(&range.span);
+                    self.error(
+                        codes::MISMATCHED_TYPES,
+                        format!("Range bounds must be `int`, found `{}`", other),
+                        span,
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_list(&mut self, list: &ListLiteral) -> Option<Type> {
+        let mut element_type: Option<Type> = None;
+        let mut valid = true;
+
+        for element in &list.elements {
+            let Some(ty) = self.check_expression(element) else {
+                valid = false;
+                continue;
+            };
+            match &element_type {
+                None => element_type = Some(ty),
+                Some(expected) if !fits(expected, &ty) => {
+                    let span = element.span().unwrap_or(&list.span).clone();
+                    self.error(
+                        codes::MISMATCHED_TYPES,
+                        format!("List elements must all be `{}`, found `{}`", expected, ty),
+                        &span,
+                    );
+                    valid = false;
+                }
+                _ => {}
+            }
+        }
+
+        valid.then(|| Type::List(Box::new(element_type.unwrap_or(Type::Void))))
+    }
+
+    /// Numbers convert to each other, and numbers, `bool` and `str` to `str`.
+    fn check_cast(&mut self, cast: &CastExpression) -> Option<Type> {
+        self.check_type(&cast.target, &cast.span);
+        let value = self.check_expression(&cast.value)?;
+        let allowed = match &cast.target {
+            Type::Str => is_scalar(&value),
+            target => is_numeric(&value) && is_numeric(target),
+        };
+        if !allowed {
+            self.error(
+                codes::UNSUPPORTED_OPERATION,
+                format!("Cannot cast `{}` to `{}`", value, cast.target),
+                &cast.span,
+            );
+            return None;
+        }
+        Some(cast.target.clone())
+    }
+
+    fn check_tuple(&mut self, tuple: &TupleLiteral) -> Option<Type> {
+        let mut elements = Vec::new();
+        let mut valid = true;
+        for element in &tuple.elements {
+            match self.check_expression(element) {
+                Some(Type::Void) => {
+                    let span = element.span().unwrap_or(&tuple.span).clone();
+                    self.error(
+                        codes::INVALID_TYPE,
+                        "Tuple elements cannot be `void`",
+                        &span,
+                    );
+                    valid = false;
+                }
+                Some(ty) => elements.push(ty),
+                None => valid = false,
+            }
+        }
+        valid.then_some(Type::Tuple(elements))
+    }
+
+    fn check_map(&mut self, map: &MapLiteral) -> Option<Type> {
+        let mut key_type: Option<Type> = None;
+        let mut value_type: Option<Type> = None;
+        let mut valid = true;
+
+        for (key, value) in &map.entries {
+            for (expression, entry_type, what) in [
+                (key, &mut key_type, "keys"),
+                (value, &mut value_type, "values"),
+            ] {
+                let Some(ty) = self.check_expression(expression) else {
+                    valid = false;
+                    continue;
+                };
+                match entry_type {
+                    None => *entry_type = Some(ty),
+                    Some(expected) if !fits(expected, &ty) => {
+                        let span = expression.span().unwrap_or(&map.span).clone();
+                        self.error(
+                            codes::MISMATCHED_TYPES,
+                            format!("Map {} must all be `{}`, found `{}`", what, expected, ty),
+                            &span,
+                        );
+                        valid = false;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let key_type = key_type.unwrap_or(Type::Void);
+        if key_type != Type::Void && !is_map_key(&key_type) {
+            self.error(
+                codes::INVALID_TYPE,
+                format!(
+                    "Map keys must be `int`, `str` or `bool`, found `{}`",
+                    key_type
+                ),
+                &map.span,
+            );
+            valid = false;
+        }
+        valid.then(|| {
+            Type::Map(
+                Box::new(key_type),
+                Box::new(value_type.unwrap_or(Type::Void)),
+            )
+        })
+    }
+
+    fn check_index(&mut self, index: &IndexExpression) -> Option<Type> {
+        let object = self.check_expression(&index.object);
+        let key = self.check_expression(&index.index);
+        match object? {
+            Type::Map(expected, value) => {
+                if let Some(key) = key.filter(|key| !compatible(&expected, key)) {
+                    let span = index.index.span().unwrap_or(&index.span).clone();
+                    self.error(
+                        codes::MISMATCHED_TYPES,
+                        format!("Map keys are `{}`, found `{}`", expected, key),
+                        &span,
+                    );
+                }
+                Some(*value)
+            }
+            Type::List(element) => {
+                if let Some(key) = key.filter(|key| *key != Type::Int) {
+                    let span = index.index.span().unwrap_or(&index.span).clone();
+                    self.error(
+                        codes::MISMATCHED_TYPES,
+                        format!("List indices are `int`, found `{}`", key),
+                        &span,
+                    );
+                }
+                Some(*element)
+            }
+            other => {
+                self.error(
+                    codes::UNSUPPORTED_OPERATION,
+                    format!("Cannot index into a value of type `{}`", other),
+                    &index.span,
+                );
+                None
+            }
+        }
+    }
+
+    fn check_slice(&mut self, slice: &SliceExpression) -> Option<Type> {
+        let object = self.check_expression(&slice.object);
+        for bound in [&slice.start, &slice.end].into_iter().flatten() {
+            match self.check_expression(bound) {
+                Some(Type::Int) | None => {}
+                Some(other) => {
+                    let span = bound.span().unwrap_or(&slice.span).clone();
+                    self.error(
+                        codes::MISMATCHED_TYPES,
+                        format!("Slice bounds are `int`, found `{}`", other),
+                        &span,
+                    );
+                }
+            }
+        }
+        match object? {
+            list @ Type::List(_) => Some(list),
+            other => {
+                self.error(
+                    codes::UNSUPPORTED_OPERATION,
+                    format!("Cannot slice a value of type `{}`", other),
+                    &slice.span,
+                );
+                None
+            }
+        }
+    }
+
+    fn check_struct_init(&mut self, init: &StructInitializer) -> Option<Type> {
+        let Some(fields) = self.structs.get(&init.struct_name).cloned() else {
+            let message = format!("Unknown struct `{}`", init.struct_name);
+            self.unresolved(init.struct_name, message, &init.span);
+            for value in init.fields.values() {
+                self.check_expression(value);
+            }
+            return None;
+        };
+        self.reference_item(SymbolKind::Struct, None, &init.struct_name, &init.span);
+        let name_span = Span {
+            end_line: init.span.start_line,
+            end_column: init.span.start_column + init.struct_name.chars().count(),
+            end_offset: init.span.start_offset + init.struct_name.len(),
+            ..init.span.clone()
+        };
+        self.check_deprecated(SymbolKind::Struct, init.struct_name, &name_span, true);
+
+        let mut provided: Vec<_> = init.fields.iter().collect();
+        provided.sort_by(|a, b| a.0.cmp(b.0));
+
+        // The type arguments of a generic struct come from its fields.
+        let mut inferred = Substitution::new();
+        for (name, value) in provided {
+            let span = value.span().unwrap_or(&init.span).clone();
+            self.reference_item(SymbolKind::Field, Some(&init.struct_name), name, &span);
+            let actual = self.check_expression(value);
+            match fields.iter().find(|(field, _)| field == name) {
+                Some((_, expected)) => {
+                    if let Some(actual) = &actual {
+                        infer(expected, actual, &mut inferred);
+                    }
+                    let expected = substitute(expected, &inferred);
+                    // A parameter left uninferred is reported once, below.
+                    let actual = actual.filter(|_| !contains_param(&expected));
+                    if let Some(actual) = actual.filter(|actual| !compatible(&expected, actual)) {
+                        self.error(
+                            codes::MISMATCHED_TYPES,
+                            format!(
+                                "Field `{}` of `{}` expects `{}`, found `{}`",
+                                name, init.struct_name, expected, actual
+                            ),
+                            &init.span,
+                        );
+                    }
+                }
+                None => {
+                    self.error(
+                        codes::NO_MEMBER,
+                        format!("Struct `{}` has no field `{}`", init.struct_name, name),
+                        &init.span,
+                    );
+                }
+            }
+        }
+
+        for (name, _) in &fields {
+            if !init.fields.contains_key(name) {
+                self.error(
+                    codes::ARGUMENTS,
+                    format!(
+                        "Missing field `{}` in `{}` initializer",
+                        name, init.struct_name
+                    ),
+                    &init.span,
+                );
+            }
+        }
+
+        let Some(type_params) = self.generic_structs.get(&init.struct_name).cloned() else {
+            return Some(Type::Struct(init.struct_name));
+        };
+        let mut arguments = Vec::new();
+        for param in &type_params {
+            let Some(argument) = inferred.get(param) else {
+                self.error(
+                    codes::CANNOT_INFER,
+                    format!(
+                        "Cannot infer type parameter `{}` of `{}`",
+                        param, init.struct_name
+                    ),
+                    &init.span,
+                );
+                return None;
+            };
+            arguments.push(argument.clone());
+        }
+        Some(Type::Generic(init.struct_name, arguments))
+    }
+
+    /// Checks each arm against the scrutinee, warns about arms an earlier one
+    /// already covers, and requires the arms together to cover every value.
+    /// The type of an `if` expression is the one both branches fit. A branch
+    /// of `none` makes the other branch's value optional.
+    fn check_if_expression(&mut self, if_expression: &IfExpression) -> Option<Type> {
+        self.check_condition(&if_expression.condition, &if_expression.span);
+        let narrowed = self.narrowed_binding(&if_expression.condition);
+        self.push_scope();
+        if let Some((name, binding)) = narrowed {
+            self.scopes
+                .last_mut()
+                .expect("scope stack is never empty")
+                .insert(name, binding);
+        }
+        let then_type = self.check_expression(&if_expression.then_value);
+        self.pop_scope();
+        let else_type = self.check_expression(&if_expression.else_value);
+
+        let (then_type, else_type) = (then_type?, else_type?);
+        match (&then_type, &else_type) {
+            (expected, actual) if compatible(expected, actual) => Some(then_type),
+            (actual, expected) if compatible(expected, actual) => Some(else_type),
+            (Type::Optional(none), other) | (other, Type::Optional(none))
+                if **none == Type::Void && *other != Type::Void =>
+            {
+                Some(Type::Optional(Box::new(other.clone())))
+            }
+            _ => {
+                let span = if_expression
+                    .else_value
+                    .span()
+                    .unwrap_or(&if_expression.span)
+                    .clone();
+                self.error(
+                    codes::MISMATCHED_TYPES,
+                    format!(
+                        "`if` branches have incompatible types: expected `{}`, found `{}`",
+                        then_type, else_type
+                    ),
+                    &span,
+                );
+                None
+            }
+        }
+    }
+
+    /// Checks the exception a `catch` clause names, and returns the type of
+    /// what it catches: the exception, or the message for `Error`.
+    fn check_catch_type(&mut self, clause: &CatchClause) -> Option<Type> {
+        let name = clause.exception_type;
+        if name == "Error" {
+            return Some(Type::Str);
+        }
+        if self.exceptions.contains(&name) {
+            return Some(Type::Struct(name));
+        }
+        let diagnostic = if self.structs.contains_key(&name) {
+            Diagnostic::error(
+                format!("`{}` is a struct, not an exception", name),
+                clause.span.clone(),
+            )
+            .with_code(codes::NOT_AN_EXCEPTION)
+            .with_help(format!(
+                "declare it with `exception {} {{ ... }}` to throw and catch it",
+                name
+            ))
+        } else {
+            Diagnostic::error(
+                format!("Unknown exception type `{}`", name),
+                clause.span.clone(),
+            )
+            .with_code(codes::UNKNOWN_NAME)
+            .with_help("catch any error with `catch Error`")
+        };
+        self.report(diagnostic);
+        None
+    }
+
+    /// Checks what a `for` iterates over, and returns the type of its values.
+    fn check_iterable(&mut self, iterable: &Expression, span: &Span) -> Option<Type> {
+        let iterated = match iterable {
+            // The variable is an `int` even when a bound is not.
+            Expression::Range(range) => {
+                self.check_range_bounds(range);
+                Some(Type::List(Box::new(Type::Int)))
+            }
+            iterable => self.check_expression(iterable),
+        };
+        match iterated? {
+            Type::List(element) => Some(*element),
+            other => {
+                self.error(
+                    codes::UNSUPPORTED_OPERATION,
+                    format!("Cannot iterate over a value of type `{}`", other),
+                    span,
+                );
+                None
+            }
+        }
+    }
+
+    fn check_comprehension(&mut self, comprehension: &Comprehension) -> Option<Type> {
+        let variable = self.check_iterable(&comprehension.iterable, &comprehension.span);
+        self.push_scope();
+        if let Some(variable) = variable {
+            self.declare(
+                comprehension.variable,
+                SymbolKind::Variable,
+                variable,
+                false,
+                &comprehension.span,
+            );
+        }
+        if let Some(condition) = &comprehension.condition {
+            self.check_condition(condition, &comprehension.span);
+        }
+        let element = self.check_expression(&comprehension.element);
+        self.pop_scope();
+
+        match element? {
+            Type::Void => {
+                let span = comprehension
+                    .element
+                    .span()
+                    .unwrap_or(&comprehension.span)
+                    .clone();
+                self.error(codes::INVALID_TYPE, "List elements cannot be `void`", &span);
+                None
+            }
+            element => Some(Type::List(Box::new(element))),
+        }
+    }
+
+    fn check_match(&mut self, match_expression: &MatchExpression) -> Option<Type> {
+        let scrutinee = self.check_expression(&match_expression.scrutinee);
+        // Every value of a `bool` or enum scrutinee, which arms can list out.
+        let values: Option<Vec<String>> = match &scrutinee {
+            Some(Type::Bool) => Some(vec!["true".to_string(), "false".to_string()]),
+            Some(Type::Struct(name)) => self.enums.get(name).map(|variants| {
+                variants
+                    .iter()
+                    .map(|(variant, _)| format!("{}.{}", name, variant))
+                    .collect()
+            }),
+            _ => None,
+        };
+        let mut result: Option<Type> = None;
+        let mut covered: Vec<String> = Vec::new();
+        let mut catch_all = false;
+
+        for arm in &match_expression.arms {
+            let bindings = self.check_pattern(&arm.pattern, scrutinee.as_ref(), &arm.pattern_span);
+            let exhausted = values
+                .as_ref()
+                .is_some_and(|values| values.iter().all(|value| covered.contains(value)));
+            let key = pattern_key(&arm.pattern);
+            let unreachable = if catch_all || exhausted {
+                Some("an earlier arm matches every value")
+            } else if key.as_ref().is_some_and(|key| covered.contains(key)) {
+                Some("an earlier arm matches the same value")
+            } else {
+                None
+            };
+            if let Some(reason) = unreachable {
+                self.report(
+                    Diagnostic::lint(
+                        Lint::UnreachablePattern,
+                        "Unreachable `match` arm",
+                        arm.span.clone(),
+                    )
+                    .with_help(reason),
+                );
+            }
+            match key {
+                Some(key) => covered.push(key),
+                None => catch_all = true,
+            }
+
+            self.push_scope();
+            for (name, ty) in bindings {
+                self.declare(name, SymbolKind::Variable, ty, false, &arm.pattern_span);
+            }
+            let body = self.check_expression(&arm.body);
+            self.pop_scope();
+
+            match (&result, body) {
+                (None, body) => result = body,
+                (Some(expected), Some(actual)) if !fits(expected, &actual) => {
+                    let span = arm.body.span().unwrap_or(&arm.span).clone();
+                    self.error(
+                        codes::MISMATCHED_TYPES,
+                        format!(
+                            "`match` arms have incompatible types: expected `{}`, found `{}`",
+                            expected, actual
+                        ),
+                        &span,
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        if !catch_all {
+            let message = match (&scrutinee, &values) {
+                (_, Some(values)) => values
+                    .iter()
+                    .find(|value| !covered.contains(value))
+                    .map(|value| format!("Non-exhaustive `match`: `{}` is not covered", value)),
+                (Some(other), None) => Some(format!("Non-exhaustive `match` on `{}`", other)),
+                (None, None) => None,
+            };
+            if let Some(message) = message {
+                self.report(
+                    Diagnostic::error(message, match_expression.span.clone())
+                        .with_code(codes::NON_EXHAUSTIVE)
+                        .with_help("add a `_ => ...` arm to match the remaining values"),
+                );
+            }
+        }
+        result
+    }
+
+    /// Checks that `pattern` can match a value of type `scrutinee`, and
+    /// returns the names it binds with their types.
+    fn check_pattern(
+        &mut self,
+        pattern: &Pattern,
+        scrutinee: Option<&Type>,
+        span: &Span,
+    ) -> Vec<(Symbol, Type)> {
+        match pattern {
+            Pattern::Wildcard => Vec::new(),
+            Pattern::Binding(name) => scrutinee
+                .map(|ty| vec![(*name, ty.clone())])
+                .unwrap_or_default(),
+            Pattern::Literal(literal) => {
+                let ty = literal_type(literal);
+                if let Some(scrutinee) = scrutinee.filter(|scrutinee| **scrutinee != ty) {
+                    self.error(
+                        codes::MISMATCHED_TYPES,
+                        format!(
+                            "Pattern of type `{}` cannot match a value of type `{}`",
+                            ty, scrutinee
+                        ),
+                        span,
+                    );
+                }
+                Vec::new()
+            }
+            Pattern::Variant(variant) => self.check_variant_pattern(variant, scrutinee, span),
+        }
+    }
+
+    fn check_variant_pattern(
+        &mut self,
+        pattern: &VariantPattern,
+        scrutinee: Option<&Type>,
+        span: &Span,
+    ) -> Vec<(Symbol, Type)> {
+        let Some(variants) = self.enums.get(&pattern.enum_name) else {
+            self.error(
+                codes::UNKNOWN_NAME,
+                format!("Unknown enum `{}`", pattern.enum_name),
+                span,
+            );
+            return Vec::new();
+        };
+        let ty = Type::Struct(pattern.enum_name);
+        let payload = variants
+            .iter()
+            .find(|(variant, _)| *variant == pattern.variant)
+            .map(|(_, payload)| payload.clone());
+        if let Some(scrutinee) = scrutinee.filter(|scrutinee| **scrutinee != ty) {
+            self.error(
+                codes::MISMATCHED_TYPES,
+                format!(
+                    "Pattern of type `{}` cannot match a value of type `{}`",
+                    ty, scrutinee
+                ),
+                span,
+            );
+        }
+        let Some(payload) = payload else {
+            self.error(
+                codes::NO_MEMBER,
+                format!(
+                    "Enum `{}` has no variant `{}`",
+                    pattern.enum_name, pattern.variant
+                ),
+                span,
+            );
+            return Vec::new();
+        };
+        self.reference_item(
+            SymbolKind::Variant,
+            Some(&pattern.enum_name),
+            &pattern.variant,
+            span,
+        );
+        if payload.len() != pattern.bindings.len() {
+            self.error(
+                codes::ARGUMENTS,
+                format!(
+                    "`{}.{}` holds {} value{}, found {} binding{}",
+                    pattern.enum_name,
+                    pattern.variant,
+                    payload.len(),
+                    if payload.len() == 1 { "" } else { "s" },
+                    pattern.bindings.len(),
+                    if pattern.bindings.len() == 1 { "" } else { "s" }
+                ),
+                span,
+            );
+            return Vec::new();
+        }
+        pattern
+            .bindings
+            .iter()
+            .copied()
+            .zip(payload)
+            .filter(|(name, _)| *name != "_")
+            .collect()
+    }
+
+    /// When `access` is `Enum.Variant` for an enum no variable shadows,
+    /// returns the enum and the variant's payload, if it has that variant.
+    fn enum_variant(&self, access: &MemberAccess) -> Option<(Symbol, Option<Vec<Type>>)> {
+        let Expression::Identifier(identifier) = access.object.as_ref() else {
+            return None;
+        };
+        if self.lookup(identifier.name).is_some() {
+            return None;
+        }
+        let variants = self.enums.get(&identifier.name)?;
+        let payload = variants
+            .iter()
+            .find(|(variant, _)| *variant == access.member)
+            .map(|(_, payload)| payload.clone());
+        Some((identifier.name, payload))
+    }
+
+    /// Checks `Enum.Variant`, or `Enum.Variant(...)` when `call` is given.
+    fn check_variant(
+        &mut self,
+        enum_name: Symbol,
+        access: &MemberAccess,
+        payload: Option<Vec<Type>>,
+        call: Option<&FunctionCall>,
+    ) -> Option<Type> {
+        let Some(payload) = payload else {
+            self.error(
+                codes::NO_MEMBER,
+                format!("Enum `{}` has no variant `{}`", enum_name, access.member),
+                &access.span,
+            );
+            if let Some(call) = call {
+                self.check_arguments_only(call);
+            }
+            return None;
+        };
+        self.reference_item(
+            SymbolKind::Variant,
+            Some(&enum_name),
+            &access.member,
+            &access.span,
+        );
+
+        let name = format!("{}.{}", enum_name, access.member);
+        match call {
+            None if !payload.is_empty() => {
+                self.error(
+                    codes::ARGUMENTS,
+                    format!(
+                        "Variant `{}` holds {} value{}; create it with `{}(...)`",
+                        name,
+                        payload.len(),
+                        if payload.len() == 1 { "" } else { "s" },
+                        name
+                    ),
+                    &access.span,
+                );
+                return None;
+            }
+            Some(call) if payload.is_empty() => {
+                self.error(
+                    codes::ARGUMENTS,
+                    format!(
+                        "Variant `{}` holds no values; create it without parentheses",
+                        name
+                    ),
+                    &call.span,
+                );
+                self.check_arguments_only(call);
+                return None;
+            }
+            Some(call) => {
+                if self.reject_named_arguments(&name, call) {
+                    return None;
+                }
+                let params: Vec<ParamKind> = payload.into_iter().map(ParamKind::Exact).collect();
+                self.check_arguments(&name, &params, &passed(&call.arguments), &call.span, None);
+            }
+            None => {}
+        }
+        Some(Type::Struct(enum_name))
+    }
+
+    /// Returns the module name when `expression` refers to an imported built-in module
+    /// that is not shadowed by a variable, and marks the import used.
+    fn module_name(&mut self, expression: &Expression) -> Option<String> {
+        let Expression::Identifier(identifier) = expression else {
+            return None;
+        };
+        if self.lookup(identifier.name).is_some() {
+            return None;
+        }
+        let module = self.qualifiers.get(&identifier.name)?.clone();
+        if !builtins::is_module(&module) {
+            return None;
+        }
+        self.used_imports.insert(module.clone());
+        Some(module)
+    }
+
+    /// `module.item` for an item of a module of the program imported whole,
+    /// as an identifier naming the item the way `import_module` declared it,
+    /// and marks the import used. A module imported without `as` declared
+    /// its items unqualified, so a local variable of the same name hides them
+    /// from the generated code and is reported.
+    fn qualified(&mut self, access: &MemberAccess) -> Option<Identifier> {
+        let Expression::Identifier(module) = access.object.as_ref() else {
+            return None;
+        };
+        let path = self.qualifiers.get(&module.name)?.clone();
+        if self.lookup(module.name).is_some() || builtins::is_module(&path) {
+            return None;
+        }
+        self.used_imports.insert(path.clone());
+        let member = access.member;
+        let declared = self.imported.get(&member) == Some(&path)
+            || self.private_imports.get(&member) == Some(&path);
+        if !declared {
+            return Some(Identifier {
+                name: qualify(module.name, member),
+                span: access.span.clone(),
+            });
+        }
+        if self.scopes[1..]
+            .iter()
+            .any(|scope| scope.contains_key(&member))
+        {
+            let diagnostic = Diagnostic::error(
+                format!(
+                    "`{}.{}` is shadowed by the local variable `{}`",
+                    path, member, member
+                ),
+                access.span.clone(),
+            )
+            .with_code(codes::UNKNOWN_NAME)
+            .with_help(format!(
+                "rename the variable, or import `{}` with `as` to keep its items qualified",
+                path
+            ));
+            self.report(diagnostic);
+        }
+        Some(Identifier {
+            name: member,
+            span: access.span.clone(),
+        })
+    }
+
+    /// Warns about private functions other than `main` that nothing outside
+    /// their own body refers to.
+    fn check_unused_functions(&mut self, program: &Program) {
+        for item in &program.items {
+            let Item::Function(function) = item else {
+                continue;
+            };
+            if function.public || function.name == "main" || function.name.starts_with('_') {
+                continue;
+            }
+            let Some(definition) = self.xref.item(SymbolKind::Function, None, &function.name)
+            else {
+                continue;
+            };
+            let own_body = function.span.range();
+            let used = self.xref.references.iter().any(|reference| {
+                reference.definition == definition
+                    && !(reference.span.file == function.span.file
+                        && own_body.contains(&reference.span.start_offset))
+            });
+            if used {
+                continue;
+            }
+            let span = self
+                .name_span(&function.span, &function.name)
+                .unwrap_or_else(|| function.span.clone());
+            self.report(
+                Diagnostic::lint(
+                    Lint::UnusedFunction,
+                    format!("Unused function `{}`", function.name),
+                    span,
+                )
+                .with_help(format!(
+                    "remove it, or prefix its name with an underscore (`_{}`) if this is \
+                     intentional",
+                    function.name
+                )),
+            );
+        }
+    }
+
+    /// Where `name` is written in the declaration at `span`, when the source
+    /// is at hand.
+    fn name_span(&mut self, span: &Span, name: &str) -> Option<Span> {
+        let source = self.diagnostics.source(&span.file)?;
+        span.find_word(&source, name)
+    }
+
+    /// Reports modules imported whole under the same name, such as
+    /// `net.http` and `db.http`, whose items code could not tell apart.
+    fn check_qualifiers(&mut self, program: &Program) {
+        let mut named: HashMap<Symbol, &str> = HashMap::new();
+        for import in program
+            .imports
+            .iter()
+            .filter(|import| import.items.is_none())
+        {
+            let name = import.name();
+            match named.get(&name) {
+                Some(&path) if path != import.module_path => {
+                    let diagnostic = Diagnostic::error(
+                        format!(
+                            "`{}` refers to both `{}` and `{}`",
+                            name, path, import.module_path
+                        ),
+                        import.span.clone(),
+                    )
+                    .with_code(codes::IMPORT)
+                    .with_help(format!(
+                        "import `{}` with `as` to give it another name",
+                        import.module_path
+                    ));
+                    self.report(diagnostic);
+                }
+                Some(_) => {}
+                None => {
+                    named.insert(name, &import.module_path);
+                }
+            }
+        }
+    }
+
+    /// Warns about imported modules that nothing refers to, neither the
+    /// module itself nor any item imported from it.
+    fn check_unused_imports(&mut self, program: &Program) {
+        for import in &program.imports {
+            let module = &import.module_path;
+            // Items cannot be imported from built-in modules by name, which
+            // is reported already.
+            let known = match import.items {
+                Some(_) => !builtins::is_module(module) && self.modules.contains_key(module),
+                None => builtins::is_module(module) || self.modules.contains_key(module),
+            };
+            let first = self.imports.get(module) == Some(&import.span);
+            // So is a module imported under a name another one took.
+            let named =
+                import.items.is_some() || self.qualifiers.get(&import.name()) == Some(module);
+            if !known || !first || !named || self.used_imports.contains(module) {
+                continue;
+            }
+            self.report(
+                Diagnostic::lint(
+                    Lint::UnusedImport,
+                    format!("Unused import `{}`", module),
+                    import.span.clone(),
+                )
+                .with_suggestion(
+                    "remove the import",
+                    import.span.clone(),
+                    "",
+                    Applicability::MachineApplicable,
+                ),
+            );
+        }
+    }
+
+    fn declare(&mut self, name: Symbol, kind: SymbolKind, ty: Type, mutable: bool, span: &Span) {
+        let definition = self.xref.define(
+            &name,
+            kind,
+            ty.to_string(),
+            self.caller.as_deref(),
+            mutable,
+            span,
+        );
+        self.check_name(kind, &name, span);
+        // Locals may shadow globals freely; hiding another local is easy to
+        // do by accident.
+        let enclosing = self.scopes.len().saturating_sub(1);
+        if let Some(shadowed) = self.scopes[..enclosing]
+            .iter()
+            .skip(1)
+            .rev()
+            .find_map(|scope| scope.get(&name))
+        {
+            let shadowed = self.definition_span(shadowed.definition);
+            self.report(
+                Diagnostic::lint(
+                    Lint::ShadowedVariable,
+                    format!("`{}` shadows a variable from an enclosing scope", name),
+                    span.clone(),
+                )
+                .with_label(shadowed, "shadowed declaration here"),
+            );
+        }
+
+        let scope = self.scopes.last_mut().expect("scope stack is never empty");
+        let symbol = Binding {
+            ty,
+            mutable,
+            definition,
+            used: false,
+            narrowed: false,
+        };
+        if let Some(first) = scope.insert(name, symbol) {
+            if self.scopes.len() > 1 {
+                let first = self.definition_span(first.definition);
+                self.redefinition(
+                    format!("`{}` is already declared in this scope", name),
+                    span,
+                    Some(first),
+                );
+            }
+        }
+    }
+
+    /// Looks up a variable use and records it in the cross-reference index.
+    fn resolve(&mut self, identifier: &Identifier) -> Option<&Binding> {
+        let depth = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(&identifier.name))?;
+        if depth == 0 {
+            self.use_import(identifier.name);
+        }
+        let symbol = self.scopes[depth]
+            .get_mut(&identifier.name)
+            .expect("found in this scope");
+        symbol.used = true;
+        self.xref.reference(symbol.definition, &identifier.span);
+        self.lookup(identifier.name)
+    }
+
+    /// Looks up the variable `assignment` writes to. Unlike `resolve`, a
+    /// plain `=` does not count as a use, so a variable that is only ever
+    /// assigned is still unused; `+=` and the like read it first.
+    fn resolve_target(
+        &mut self,
+        assignment: &Assignment,
+        identifier: &Identifier,
+    ) -> Option<Binding> {
+        if assignment.operator.is_some() {
+            return self.resolve(identifier).cloned();
+        }
+        let definition = self.lookup(identifier.name)?.definition;
+        self.xref.reference(definition, &identifier.span);
+        self.lookup(identifier.name).cloned()
+    }
+
+    fn reference_item(
+        &mut self,
+        kind: SymbolKind,
+        container: Option<&str>,
+        name: &str,
+        span: &Span,
+    ) {
+        let local = Symbol::intern(container.unwrap_or(name));
+        self.use_import(local);
+        if let Some(definition) = self.xref.item(kind, container, name) {
+            self.xref.reference(definition, span);
+        } else if let Some(module) = self.imported.get(&local) {
+            let declared = self.renamed_imports.get(&local).unwrap_or(&local);
+            let (container, name) = match container {
+                Some(_) => (Some(declared.as_str()), name),
+                None => (None, declared.as_str()),
+            };
+            self.xref
+                .reference_imported(module, kind, container, name, span);
+        }
+    }
+
+    /// Marks the import that brought `name` into scope as used, if any.
+    fn use_import(&mut self, name: Symbol) {
+        if let Some(module) = self.imported.get(&name) {
+            self.used_imports.insert(module.clone());
+        }
+    }
+
+    /// Warns about a use of a deprecated function or struct, outside other
+    /// deprecated functions. A `span` wider than the name, such as the
+    /// declaration a type annotation is in, is narrowed to where the name is
+    /// written in it. When the deprecation note just names another item to
+    /// use instead and the span covers exactly the name, the warning
+    /// suggests the rename. Qualified names are left alone, as the
+    /// replacement is looked for in this module.
+    fn check_deprecated(&mut self, kind: SymbolKind, name: Symbol, span: &Span, is_name: bool) {
+        if !self.deprecated.contains_key(&(kind, name)) {
+            return;
+        }
+        let in_deprecated = self.caller.as_ref().is_some_and(|caller| {
+            self.deprecated
+                .contains_key(&(SymbolKind::Function, *caller))
+        });
+        if in_deprecated {
+            return;
+        }
+        let narrowed = if is_name {
+            None
+        } else {
+            self.name_span(span, &name)
+        };
+        let is_name = is_name || narrowed.is_some();
+        let span = narrowed.as_ref().unwrap_or(span);
+        let Some(deprecation) = self.deprecated.get(&(kind, name)) else {
+            return;
+        };
+
+        let what = if kind == SymbolKind::Struct {
+            "struct"
+        } else {
+            "function"
+        };
+        let mut diagnostic = Diagnostic::lint(
+            Lint::Deprecated,
+            format!("Use of deprecated {} `{}`", what, name),
+            span.clone(),
+        )
+        .with_label(deprecation.span.clone(), "deprecated here");
+        if let Some(note) = &deprecation.note {
+            diagnostic = diagnostic.with_note(note.clone());
+            let replacement = replacement_name(note).filter(|replacement| {
+                let replacement = Symbol::intern(replacement);
+                let defined = match kind {
+                    SymbolKind::Struct => self.structs.contains_key(&replacement),
+                    _ => self.functions.contains_key(&replacement),
+                };
+                defined && !self.deprecated.contains_key(&(kind, replacement))
+            });
+            let qualified = name.contains('.');
+            if let Some(replacement) = replacement.filter(|_| is_name && !qualified) {
+                diagnostic = diagnostic.with_suggestion(
+                    "replace it with",
+                    span.clone(),
+                    replacement,
+                    Applicability::MachineApplicable,
+                );
+            }
+        }
+        self.report(diagnostic);
+    }
+
+    /// Checks the block of an `if`, where a variable its condition checked
+    /// with `!= none` holds a value.
+    fn visit_narrowed_block(&mut self, condition: &Expression, block: &Block) {
+        let Some((name, binding)) = self.narrowed_binding(condition) else {
+            return self.visit_block(block);
+        };
+        self.push_scope();
+        self.scopes
+            .last_mut()
+            .expect("scope stack is never empty")
+            .insert(name, binding);
+        self.visit_block(block);
+        self.pop_scope();
+    }
+
+    /// The variable `condition` checks with `!= none`, bound to its value.
+    fn narrowed_binding(&self, condition: &Expression) -> Option<(Symbol, Binding)> {
+        let identifier = narrowed_variable(condition)?;
+        let binding = self.lookup(identifier.name)?;
+        let Type::Optional(inner) = &binding.ty else {
+            return None;
+        };
+        let binding = Binding {
+            ty: (**inner).clone(),
+            mutable: false,
+            used: true,
+            narrowed: true,
+            ..binding.clone()
+        };
+        Some((identifier.name, binding))
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(&name))
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Leaves a local scope, warning about the bindings in it that were never
+    /// used, in the order they were declared.
+    fn pop_scope(&mut self) {
+        let scope = self.scopes.pop().expect("scope stack is never empty");
+        let mut unused: Vec<(Symbol, usize)> = scope
+            .into_iter()
+            .filter(|(name, symbol)| {
+                !symbol.used && !name.is_empty() && !name.starts_with('_') && *name != "self"
+            })
+            .map(|(name, symbol)| (name, symbol.definition))
+            .collect();
+        unused.sort_by_key(|(_, definition)| *definition);
+        for (name, definition) in unused {
+            let kind = match self.xref.definitions[definition].kind {
+                SymbolKind::Parameter => "parameter",
+                _ => "variable",
+            };
+            // Pointing at the name rather than the whole declaration.
+            let span = self.definition_span(definition);
+            let name_span = self.name_span(&span, &name);
+            let diagnostic = Diagnostic::lint(
+                Lint::UnusedVariable,
+                format!("Unused {} `{}`", kind, name),
+                name_span.clone().unwrap_or(span),
+            );
+            let diagnostic = match name_span {
+                Some(name_span) => diagnostic.with_suggestion(
+                    "if this is intentional, prefix it with an underscore",
+                    name_span,
+                    format!("_{}", name),
+                    Applicability::MaybeIncorrect,
+                ),
+                None => diagnostic.with_help(format!(
+                    "prefix it with an underscore (`_{}`) if this is intentional",
+                    name
+                )),
+            };
+            self.report(diagnostic);
+        }
+    }
+
+    /// Reports `name` as private when an imported module declares it without
+    /// `pub`, as not imported when its import leaves it out, and with
+    /// `message` otherwise.
+    fn unresolved(&mut self, name: Symbol, message: String, span: &Span) {
+        if let Some(imports) = self.ambiguous.get(&name).cloned() {
+            let paths = imports.iter().map(|import| import.module_path.clone());
+            let names = imports
+                .iter()
+                .map(|import| qualify(import.name(), name).to_string());
+            self.used_imports
+                .extend(imports.iter().map(|import| import.module_path.clone()));
+            let diagnostic = Diagnostic::error(
+                format!("`{}` is imported from {}", name, listing(paths, "and")),
+                span.clone(),
+            )
+            .with_code(codes::IMPORT)
+            .with_help(format!("refer to it as {}", listing(names, "or")));
+            self.report(diagnostic);
+        } else if let Some(module) = self.private_imports.get(&name).cloned() {
+            self.used_imports.insert(module.clone());
+            self.private(name, &module, span);
+        } else if let Some((module, help)) = self.unimported.get(&name) {
+            self.used_imports.insert(module.clone());
+            let diagnostic = Diagnostic::error(
+                format!("`{}` is not imported from module `{}`", name, module),
+                span.clone(),
+            )
+            .with_code(codes::UNKNOWN_NAME)
+            .with_help(help.clone());
+            self.report(diagnostic);
+        } else {
+            self.error(codes::UNKNOWN_NAME, message, span);
+        }
+    }
+
+    fn private(&mut self, name: Symbol, module: &str, span: &Span) {
+        let diagnostic = Diagnostic::error(
+            format!("`{}` is private to module `{}`", name, module),
+            span.clone(),
+        )
+        .with_code(codes::PRIVATE)
+        .with_help(format!("declare it `pub` in `{}` to use it here", module));
+        self.report(diagnostic);
+    }
+
+    fn error(&mut self, code: &str, message: impl Into<String>, span: &Span) {
+        self.report(Diagnostic::error(message, span.clone()).with_code(code));
+    }
+
+    /// Records `diagnostic`; lints the engine is set to deny count as errors.
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if self.diagnostics.emit(diagnostic) == Some(Level::Error) {
+            self.errors += 1;
+        }
+    }
+
+    /// Warns when `name` does not follow the naming convention for `kind`:
+    /// `UpperCamelCase` for structs, `UPPER_SNAKE_CASE` for constants and
+    /// `snake_case` for everything else. Globals may use either snake case.
+    fn check_name(&mut self, kind: SymbolKind, name: &str, span: &Span) {
+        let stem = name.trim_start_matches('_');
+        if stem.is_empty() {
+            return;
+        }
+        let (expected, convention) = match kind {
+            SymbolKind::Struct | SymbolKind::Enum | SymbolKind::Variant => {
+                (upper_camel_case(stem), "an upper camel case")
+            }
+            SymbolKind::Constant => (snake_case(stem).to_uppercase(), "an upper snake case"),
+            SymbolKind::Global if stem == snake_case(stem).to_uppercase() => return,
+            _ => (snake_case(stem), "a snake case"),
+        };
+        if expected == stem {
+            return;
+        }
+        let expected = format!("{}{}", &name[..name.len() - stem.len()], expected);
+        let what = match kind {
+            SymbolKind::Function => "Function",
+            SymbolKind::Method => "Method",
+            SymbolKind::Struct => "Struct",
+            SymbolKind::Field => "Field",
+            SymbolKind::Enum => "Enum",
+            SymbolKind::Variant => "Variant",
+            SymbolKind::Global => "Global",
+            SymbolKind::Constant => "Constant",
+            SymbolKind::Parameter => "Parameter",
+            SymbolKind::Variable => "Variable",
+        };
+        self.report(
+            Diagnostic::lint(
+                Lint::NamingConvention,
+                format!("{} `{}` should have {} name", what, name, convention),
+                span.clone(),
+            )
+            .with_help(format!("rename it to `{}`", expected)),
+        );
+    }
+
+    /// Reports a second definition of a name, pointing at the first.
+    fn redefinition(&mut self, message: String, span: &Span, first: Option<Span>) {
+        let mut diagnostic = Diagnostic::error(message, span.clone()).with_code(codes::REDEFINED);
+        if let Some(first) = first {
+            diagnostic = diagnostic.with_label(first, "first defined here");
+        }
+        self.report(diagnostic);
+    }
+
+    fn immutable(&mut self, message: String, span: &Span, name: &str, definition: usize) {
+        let declared = self.definition_span(definition);
+        self.report(
+            Diagnostic::error(message, span.clone())
+                .with_code(codes::IMMUTABLE)
+                .with_label(declared, format!("`{}` declared here", name))
+                .with_help(format!("declare `{}` with `var` to make it mutable", name)),
+        );
+    }
+
+    /// Points `diagnostic` at the declaration of the function being checked.
+    fn with_function_label(&self, diagnostic: Diagnostic, message: &str) -> Diagnostic {
+        match &self.function_span {
+            Some(span) => diagnostic.with_label(span.clone(), message),
+            None => diagnostic,
+        }
+    }
+
+    fn definition_span(&self, definition: usize) -> Span {
+        self.xref.definitions[definition].span.clone()
+    }
+
+    /// Checks a function's body; `receiver` is the struct of a method, the
+    /// type of `self` and whether it is `var self`.
+    fn check_function(&mut self, function: &Function, receiver: Option<(Symbol, Type, bool)>) {
+        let outer_type_params = self.type_params.len();
+        self.type_params.extend_from_slice(&function.type_params);
+        self.check_type(&function.return_type, &function.span);
+        self.return_type = Some(function.return_type.clone());
+        self.function_span = Some(function.span.clone());
+        self.caller = Some(match &receiver {
+            Some((struct_name, _, _)) => Symbol::from(format!("{}.{}", struct_name, function.name)),
+            None => function.name,
+        });
+        self.push_scope();
+
+        if let Some((_, self_type, mutable)) = receiver {
+            self.declare(
+                Symbol::intern("self"),
+                SymbolKind::Parameter,
+                self_type,
+                mutable,
+                &function.span,
+            );
+        }
+        for parameter in &function.parameters {
+            self.visit_parameter(parameter);
+        }
+
+        // Parameters and the body's top-level bindings share one scope.
+        walk_block(self, &function.body);
+        self.check_returns(&function.return_type, &function.body);
+
+        self.pop_scope();
+        self.return_type = None;
+        self.function_span = None;
+        self.caller = None;
+        self.type_params.truncate(outer_type_params);
+    }
+
+    /// Reports a path through the body of a function returning
+    /// `return_type` that ends without returning a value.
+    fn check_returns(&mut self, return_type: &Type, body: &Block) {
+        if *return_type == Type::Void {
+            return;
+        }
+        if let Some(diagnostic) = flow::missing_return(body, return_type) {
+            let label = format!("declared to return `{}` here", return_type);
+            self.report(self.with_function_label(diagnostic, &label));
+        }
+    }
+
+    fn item_span(&self, kind: SymbolKind, name: &str) -> Option<Span> {
+        self.xref
+            .item(kind, None, name)
+            .map(|definition| self.definition_span(definition))
+    }
+}
+
+/// Items and statements are checked through the AST visitor; expressions go
+/// through `check_expression`, which also returns the expression's type.
+impl Visitor for SemanticAnalyzer<'_> {
+    fn visit_item(&mut self, item: &Item) {
+        ice::set_span(item.span());
+        match item {
+            Item::Variable(variable) => {
+                if variable.mutable {
+                    self.error(
+                        codes::IMMUTABLE,
+                        format!(
+                            "Global `{}` cannot be mutable; use `let` or `const`",
+                            variable.name
+                        ),
+                        &variable.span,
+                    );
+                }
+                self.caller = Some(variable.name);
+                match &variable.var_type {
+                    Some(ty) => {
+                        self.check_global_type(variable.name, ty, &variable.span);
+                        if let Some(initializer) = &variable.initializer {
+                            self.check_initializer(ty, initializer, &variable.span);
+                        }
+                    }
+                    None => {
+                        // Globals are declared before any initializer is
+                        // checked, so there is nothing to infer from yet.
+                        self.report(
+                            Diagnostic::error(
+                                format!("Global `{}` needs a type", variable.name),
+                                variable.span.clone(),
+                            )
+                            .with_code(codes::CANNOT_INFER)
+                            .with_help(format!(
+                                "only local variables take their type from their initializer; \
+                                 write `let {}: <type> = ...`",
+                                variable.name
+                            )),
+                        );
+                        if let Some(initializer) = &variable.initializer {
+                            self.check_expression(initializer);
+                        }
+                    }
+                }
+                self.caller = None;
+            }
+            Item::Constant(constant) => {
+                self.check_global_type(constant.name, &constant.const_type, &constant.span);
+                self.caller = Some(constant.name);
+                self.check_initializer(&constant.const_type, &constant.value, &constant.span);
+                self.caller = None;
+            }
+            Item::Function(_) | Item::Struct(_) | Item::Enum(_) | Item::Impl(_) => {
+                walk_item(self, item)
+            }
+        }
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        self.check_function(function, None);
+    }
+
+    fn visit_impl(&mut self, implementation: &Impl) {
+        let Some(self_type) = self.impl_self_type(implementation) else {
+            return;
+        };
+        self.type_params = implementation.type_params.clone();
+        for method in &implementation.methods {
+            let receiver = (
+                implementation.struct_name,
+                self_type.clone(),
+                method.mutable,
+            );
+            self.check_function(&method.function, Some(receiver));
+        }
+        self.type_params.clear();
+    }
+
+    fn visit_struct(&mut self, structure: &Struct) {
+        self.type_params = structure.type_params.clone();
+        walk_struct(self, structure);
+        self.type_params.clear();
+    }
+
+    fn visit_parameter(&mut self, parameter: &Parameter) {
+        self.check_type(&parameter.param_type, &parameter.span);
+        if let Some(default) = &parameter.default_value {
+            // A default is evaluated where the function is declared, for
+            // each call that leaves it out, and sees the module's items but
+            // not the other parameters.
+            let parameters = self.scopes.pop().unwrap_or_default();
+            self.check_initializer(&parameter.param_type, default, &parameter.span);
+            self.scopes.push(parameters);
+        }
+        self.declare(
+            parameter.name,
+            SymbolKind::Parameter,
+            parameter.param_type.clone(),
+            false,
+            &parameter.span,
+        );
+    }
+
+    // Structs and enums are compared and printed, which functions cannot be.
+    fn visit_field(&mut self, field: &Field) {
+        self.check_type(&field.field_type, &field.span);
+        if contains_function(&field.field_type) {
+            self.error(
+                codes::INVALID_TYPE,
+                format!("Fields cannot hold functions, found `{}`", field.field_type),
+                &field.span,
+            );
+        }
+    }
+
+    fn visit_variant(&mut self, variant: &Variant) {
+        for ty in &variant.payload {
+            self.check_type(ty, &variant.span);
+            if contains_function(ty) {
+                self.error(
+                    codes::INVALID_TYPE,
+                    format!("Variant payloads cannot hold functions, found `{}`", ty),
+                    &variant.span,
+                );
+            }
+        }
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.push_scope();
+        walk_block(self, block);
+        self.pop_scope();
+    }
+
+    fn visit_variable(&mut self, variable: &Variable) {
+        let ty = match &variable.var_type {
+            Some(ty) => {
+                self.check_type(ty, &variable.span);
+                if let Some(initializer) = &variable.initializer {
+                    self.check_initializer(ty, initializer, &variable.span);
+                }
+                Some(ty.clone())
+            }
+            None => self.infer_variable_type(variable),
+        };
+        if let Some(ty) = ty {
+            self.declare(
+                variable.name,
+                SymbolKind::Variable,
+                ty,
+                variable.mutable,
+                &variable.span,
+            );
+        }
+    }
+
+    fn visit_destructure(&mut self, destructure: &Destructure) {
+        let elements = match self.check_expression(&destructure.initializer) {
+            Some(Type::Tuple(elements)) if elements.len() == destructure.names.len() => elements,
+            Some(ty) => {
+                self.error(
+                    codes::MISMATCHED_TYPES,
+                    format!(
+                        "Expected a tuple of {} values, found `{}`",
+                        destructure.names.len(),
+                        ty
+                    ),
+                    &destructure.span,
+                );
+                return;
+            }
+            None => return,
+        };
+        for (identifier, ty) in destructure.names.iter().zip(elements) {
+            if identifier.name != "_" {
+                self.declare(
+                    identifier.name,
+                    SymbolKind::Variable,
+                    ty,
+                    destructure.mutable,
+                    &identifier.span,
+                );
+            }
+        }
+    }
+
+    fn visit_assignment(&mut self, assignment: &Assignment) {
+        self.check_assignment(assignment);
+    }
+
+    fn visit_if(&mut self, if_statement: &IfStatement) {
+        self.check_condition(&if_statement.condition, &if_statement.span);
+        self.visit_narrowed_block(&if_statement.condition, &if_statement.then_block);
+        for (condition, block) in &if_statement.else_ifs {
+            self.check_condition(condition, &block.span);
+            self.visit_narrowed_block(condition, block);
+        }
+        if let Some(else_block) = &if_statement.else_block {
+            self.visit_block(else_block);
+        }
+    }
+
+    fn visit_for(&mut self, for_loop: &ForLoop) {
+        let element = self.check_iterable(&for_loop.iterable, &for_loop.span);
+        self.push_scope();
+        if let Some(element) = element {
+            self.declare(
+                for_loop.variable,
+                SymbolKind::Variable,
+                element,
+                false,
+                &for_loop.span,
+            );
+        }
+        self.loop_depth += 1;
+        walk_block(self, &for_loop.body);
+        self.loop_depth -= 1;
+        self.pop_scope();
+    }
+
+    fn visit_while(&mut self, while_loop: &WhileLoop) {
+        self.check_condition(&while_loop.condition, &while_loop.span);
+        self.loop_depth += 1;
+        self.visit_block(&while_loop.body);
+        self.loop_depth -= 1;
+    }
+
+    fn visit_try(&mut self, try_statement: &TryStatement) {
+        let loop_depth = std::mem::take(&mut self.loop_depth);
+        self.loops_outside_try += loop_depth;
+        self.visit_block(&try_statement.try_block);
+        // With a `finally`, the handlers are compiled to a closure as well,
+        // so that it runs even when they fail.
+        if try_statement.finally_block.is_none() {
+            self.loops_outside_try -= loop_depth;
+            self.loop_depth = loop_depth;
+        }
+        let mut caught: Vec<Symbol> = Vec::new();
+        for clause in &try_statement.catch_clauses {
+            let unreachable = if caught.iter().any(|name| name == "Error") {
+                Some("the `catch Error` before it catches every error")
+            } else if caught.contains(&clause.exception_type) {
+                Some("an earlier clause catches the same exception")
+            } else {
+                None
+            };
+            if let Some(reason) = unreachable {
+                self.report(
+                    Diagnostic::lint(
+                        Lint::UnreachablePattern,
+                        "Unreachable `catch` clause",
+                        clause.span.clone(),
+                    )
+                    .with_help(reason),
+                );
+            }
+            caught.push(clause.exception_type);
+            self.visit_catch_clause(clause);
+        }
+        if let Some(finally_block) = &try_statement.finally_block {
+            self.loops_outside_try -= loop_depth;
+            self.loop_depth = loop_depth;
+            self.visit_block(finally_block);
+        }
+    }
+
+    fn visit_catch_clause(&mut self, clause: &CatchClause) {
+        let caught = self.check_catch_type(clause);
+        self.push_scope();
+        if let (Some(binding), Some(ty)) = (clause.binding, caught) {
+            self.declare(binding, SymbolKind::Variable, ty, false, &clause.span);
+        }
+        walk_block(self, &clause.handler_block);
+        self.pop_scope();
+    }
+
+    fn visit_throw(&mut self, throw_statement: &ThrowStatement) {
+        let Some(ty) = self.check_expression(&throw_statement.value) else {
+            return;
+        };
+        if matches!(&ty, Type::Struct(name) if self.exceptions.contains(name)) {
+            return;
+        }
+        let mut diagnostic = Diagnostic::error(
+            format!("Only exceptions can be thrown, found `{}`", ty),
+            throw_statement.span.clone(),
+        )
+        .with_code(codes::NOT_AN_EXCEPTION);
+        if let Type::Struct(name) = &ty {
+            if self.structs.contains_key(name) {
+                diagnostic = diagnostic.with_help(format!(
+                    "declare `{}` with `exception` instead of `struct`",
+                    name
+                ));
+            }
+        }
+        self.report(diagnostic);
+    }
+
+    fn visit_break(&mut self, break_statement: &BreakStatement) {
+        self.check_loop_exit("break", &break_statement.span);
+    }
+
+    fn visit_continue(&mut self, continue_statement: &ContinueStatement) {
+        self.check_loop_exit("continue", &continue_statement.span);
+    }
+
+    fn visit_return(&mut self, return_statement: &ReturnStatement) {
+        self.check_return(return_statement);
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        self.check_expression(expression);
+    }
+}
+
+/// The item a deprecation note says to use instead, when that is all it
+/// says: `use parse_int instead`, optionally with the name in backticks.
+fn replacement_name(note: &str) -> Option<&str> {
+    let rest = note.trim().trim_end_matches('.');
+    let rest = rest
+        .strip_prefix("use ")
+        .or_else(|| rest.strip_prefix("Use "))?;
+    let name = rest.strip_suffix(" instead").unwrap_or(rest).trim();
+    let name = name
+        .strip_prefix('`')
+        .and_then(|name| name.strip_suffix('`'))
+        .unwrap_or(name);
+    let mut chars = name.chars();
+    let starts_identifier = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_');
+    (starts_identifier && chars.all(|c| c.is_alphanumeric() || c == '_')).then_some(name)
+}
+
+/// What a pattern that does not match everything matches, as written.
+fn pattern_key(pattern: &Pattern) -> Option<String> {
+    match pattern {
+        Pattern::Wildcard | Pattern::Binding(_) => None,
+        Pattern::Literal(Literal::String(value)) => Some(format!("{:?}", value)),
+        Pattern::Literal(Literal::Integer(value)) => Some(value.to_string()),
+        Pattern::Literal(Literal::Float(value)) => Some(value.to_string()),
+        Pattern::Literal(Literal::Boolean(value)) => Some(value.to_string()),
+        Pattern::Literal(Literal::None) => Some("none".to_string()),
+        Pattern::Variant(variant) => Some(format!("{}.{}", variant.enum_name, variant.variant)),
+    }
+}
+
+pub fn literal_type(literal: &Literal) -> Type {
+    match literal {
+        Literal::Integer(_) => Type::Int,
+        Literal::Float(_) => Type::Float,
+        Literal::String(_) => Type::Str,
+        Literal::Boolean(_) => Type::Bool,
+        Literal::None => Type::Optional(Box::new(Type::Void)),
+    }
+}
+
+/// Whether a value of type `actual` may be stored where `expected` is declared.
+/// A `T` stored where a `T?` is declared is wrapped; inside lists, maps and
+/// generic structs types must fit exactly.
+pub fn compatible(expected: &Type, actual: &Type) -> bool {
+    match (expected, actual) {
+        (Type::Optional(_), Type::Optional(_)) => fits(expected, actual),
+        (Type::Optional(inner), actual) => fits(inner, actual),
+        _ => fits(expected, actual),
+    }
+}
+
+/// Whether a value of type `actual` is a value of type `expected` as it is.
+/// An empty list or map literal (`list[void]`, `map[void, void]`) fits any list
+/// or map type, and `none` any optional type.
+pub fn fits(expected: &Type, actual: &Type) -> bool {
+    match (expected, actual) {
+        (Type::List(_), Type::List(element)) if **element == Type::Void => true,
+        (Type::List(expected), Type::List(actual)) => fits(expected, actual),
+        (Type::Map(..), Type::Map(key, value)) if **key == Type::Void && **value == Type::Void => {
+            true
+        }
+        (Type::Map(expected_key, expected_value), Type::Map(key, value)) => {
+            fits(expected_key, key) && fits(expected_value, value)
+        }
+        (Type::Generic(expected_name, expected), Type::Generic(name, actual)) => {
+            expected_name == name
+                && expected.len() == actual.len()
+                && expected
+                    .iter()
+                    .zip(actual)
+                    .all(|(expected, actual)| fits(expected, actual))
+        }
+        (Type::Tuple(expected), Type::Tuple(actual)) => {
+            expected.len() == actual.len()
+                && expected
+                    .iter()
+                    .zip(actual)
+                    .all(|(expected, actual)| fits(expected, actual))
+        }
+        (Type::Optional(_), Type::Optional(inner)) if **inner == Type::Void => true,
+        (Type::Optional(expected), Type::Optional(actual)) => fits(expected, actual),
+        _ => expected == actual,
+    }
+}
+
+/// The variable an `if` condition of the form `name != none` checks.
+pub fn narrowed_variable(condition: &Expression) -> Option<&Identifier> {
+    let Expression::Binary(binary) = condition else {
+        return None;
+    };
+    match (&*binary.left, &binary.operator, &*binary.right) {
+        (Expression::Identifier(identifier), BinaryOperator::Ne, none)
+        | (none, BinaryOperator::Ne, Expression::Identifier(identifier))
+            if *none == Expression::Literal(Literal::None) =>
+        {
+            Some(identifier)
+        }
+        _ => None,
+    }
+}
+
+/// The names a program's imports let code qualify items with, as in
+/// `math.sqrt`, with the module each one names: the modules imported whole,
+/// under their alias if they have one. A name two modules are imported under
+/// names the first.
+pub fn qualifiers(
+    program: &Program,
+    modules: &HashMap<String, Arc<Program>>,
+) -> HashMap<Symbol, String> {
+    let mut qualifiers = HashMap::new();
+    for import in program
+        .imports
+        .iter()
+        .filter(|import| import.items.is_none())
+    {
+        if builtins::is_module(&import.module_path) || modules.contains_key(&import.module_path) {
+            qualifiers
+                .entry(import.name())
+                .or_insert_with(|| import.module_path.clone());
+        }
+    }
+    qualifiers
+}
+
+/// Names of `pub` functions, globals and constants that more than one of
+/// the modules imported whole and without `as` declare, with those imports.
+/// None of them brings such a name into scope unqualified, so code refers
+/// to each as `module.name`.
+pub fn ambiguous_imports(
+    program: &Program,
+    modules: &HashMap<String, Arc<Program>>,
+) -> HashMap<Symbol, Vec<Import>> {
+    let qualifiers = qualifiers(program, modules);
+    let mut declaring: HashMap<Symbol, Vec<Import>> = HashMap::new();
+    let mut seen = HashSet::new();
+    for import in &program.imports {
+        if import.alias.is_some() || import.items.is_some() {
+            continue;
+        }
+        let Some(module) = modules.get(&import.module_path) else {
+            continue;
+        };
+        // A module imported under a name another one took is only reported.
+        if qualifiers.get(&import.name()) != Some(&import.module_path)
+            || !seen.insert(&import.module_path)
+        {
+            continue;
+        }
+        for item in &module.items {
+            if matches!(item, Item::Struct(_) | Item::Enum(_)) || !item.is_public() {
+                continue;
+            }
+            if let Some(name) = item.name() {
+                declaring.entry(name).or_default().push(import.clone());
+            }
+        }
+    }
+    declaring.retain(|_, imports| imports.len() > 1);
+    declaring
+}
+
+/// The name `import` brings `item` into scope under, if any. Structs and
+/// enums keep their names even when the module is imported with `as` or only
+/// some of its items are, as types cannot be qualified. An item whose name
+/// is `ambiguous` stays qualified.
+pub fn imported_name(
+    import: &Import,
+    item: &Item,
+    ambiguous: &HashMap<Symbol, Vec<Import>>,
+) -> Option<Symbol> {
+    let name = item.name()?;
+    if matches!(item, Item::Struct(_) | Item::Enum(_)) {
+        return Some(name);
+    }
+    match (&import.items, import.alias) {
+        (Some(items), _) => items
+            .iter()
+            .find(|selected| selected.name == name)
+            .map(ImportedItem::local_name),
+        (None, Some(alias)) => Some(qualify(alias, name)),
+        (None, None) if ambiguous.contains_key(&name) => Some(qualify(import.name(), name)),
+        (None, None) => Some(name),
+    }
+}
+
+/// `items` quoted and listed in prose, as in "`a`, `b` and `c`".
+fn listing(items: impl Iterator<Item = String>, conjunction: &str) -> String {
+    let items: Vec<String> = items.map(|item| format!("`{}`", item)).collect();
+    match items.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} {} {}", rest.join(", "), conjunction, last),
+        None => String::new(),
+    }
+}
+
+/// The name an item of a module imported as `module` is declared under in
+/// the importing module, which code writes as `module.name`.
+pub fn qualify(module: Symbol, name: Symbol) -> Symbol {
+    Symbol::from(format!("{}.{}", module, name))
+}
+
+/// The struct a value of type `ty` is an instance of, if any.
+fn struct_name(ty: &Type) -> Option<Symbol> {
+    match ty {
+        Type::Struct(name) | Type::Generic(name, _) => Some(*name),
+        _ => None,
+    }
+}
+
+/// `ty` with the type parameters bound in `substitution` replaced.
+fn substitute(ty: &Type, substitution: &Substitution) -> Type {
+    match ty {
+        Type::Param(name) => substitution
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| ty.clone()),
+        Type::List(element) => Type::List(Box::new(substitute(element, substitution))),
+        Type::Map(key, value) => Type::Map(
+            Box::new(substitute(key, substitution)),
+            Box::new(substitute(value, substitution)),
+        ),
+        Type::Generic(name, arguments) => Type::Generic(
+            *name,
+            arguments
+                .iter()
+                .map(|argument| substitute(argument, substitution))
+                .collect(),
+        ),
+        Type::Optional(inner) => Type::Optional(Box::new(substitute(inner, substitution))),
+        Type::Tuple(elements) => Type::Tuple(
+            elements
+                .iter()
+                .map(|element| substitute(element, substitution))
+                .collect(),
+        ),
+        Type::Function(parameters, return_type) => Type::Function(
+            parameters
+                .iter()
+                .map(|parameter| substitute(parameter, substitution))
+                .collect(),
+            Box::new(substitute(return_type, substitution)),
+        ),
+        _ => ty.clone(),
+    }
+}
+
+/// Binds the type parameters in `expected` to the matching parts of
+/// `actual`. Parameters already bound keep their type, and the unknown
+/// element types of empty literals bind nothing.
+fn infer(expected: &Type, actual: &Type, substitution: &mut Substitution) {
+    match (expected, actual) {
+        (Type::Param(name), _) if !contains_void(actual) => {
+            substitution.entry(*name).or_insert_with(|| actual.clone());
+        }
+        (Type::List(expected), Type::List(actual))
+        | (Type::Optional(expected), Type::Optional(actual)) => {
+            infer(expected, actual, substitution)
+        }
+        (Type::Optional(expected), actual) => infer(expected, actual, substitution),
+        (Type::Map(expected_key, expected_value), Type::Map(key, value)) => {
+            infer(expected_key, key, substitution);
+            infer(expected_value, value, substitution);
+        }
+        (Type::Generic(expected_name, expected), Type::Generic(name, actual))
+            if expected_name == name =>
+        {
+            for (expected, actual) in expected.iter().zip(actual) {
+                infer(expected, actual, substitution);
+            }
+        }
+        (Type::Tuple(expected), Type::Tuple(actual)) => {
+            for (expected, actual) in expected.iter().zip(actual) {
+                infer(expected, actual, substitution);
+            }
+        }
+        (
+            Type::Function(expected_parameters, expected_return),
+            Type::Function(parameters, return_type),
+        ) => {
+            for (expected, actual) in expected_parameters.iter().zip(parameters) {
+                infer(expected, actual, substitution);
+            }
+            infer(expected_return, return_type, substitution);
+        }
+        _ => {}
+    }
+}
+
+fn contains_void(ty: &Type) -> bool {
+    match ty {
+        Type::Void => true,
+        Type::List(element) | Type::Optional(element) => contains_void(element),
+        Type::Map(key, value) => contains_void(key) || contains_void(value),
+        Type::Generic(_, arguments) | Type::Tuple(arguments) => arguments.iter().any(contains_void),
+        _ => false,
+    }
+}
+
+fn contains_param(ty: &Type) -> bool {
+    match ty {
+        Type::Param(_) => true,
+        Type::List(element) | Type::Optional(element) => contains_param(element),
+        Type::Map(key, value) => contains_param(key) || contains_param(value),
+        Type::Generic(_, arguments) | Type::Tuple(arguments) => {
+            arguments.iter().any(contains_param)
+        }
+        Type::Function(parameters, return_type) => {
+            parameters.iter().any(contains_param) || contains_param(return_type)
+        }
+        _ => false,
+    }
+}
+
+/// Whether values of `ty` are or contain functions, which cannot be compared
+/// or printed.
+fn contains_function(ty: &Type) -> bool {
+    match ty {
+        Type::Function(..) => true,
+        Type::List(element) | Type::Optional(element) => contains_function(element),
+        Type::Map(key, value) => contains_function(key) || contains_function(value),
+        Type::Generic(_, arguments) | Type::Tuple(arguments) => {
+            arguments.iter().any(contains_function)
+        }
+        _ => false,
+    }
+}
+
+/// `arguments`, all passed, as `check_arguments` takes them.
+fn passed(arguments: &[Expression]) -> Vec<Option<&Expression>> {
+    arguments.iter().map(Some).collect()
+}
+
+/// Whether values of `ty` can be map keys.
+fn is_map_key(ty: &Type) -> bool {
+    matches!(ty, Type::Int | Type::Str | Type::Bool)
+}
+
+/// The type of `left operator right`, or `None` if the operator does not
+/// apply to those operands.
+fn binary_type(operator: &BinaryOperator, left: &Type, right: &Type) -> Option<Type> {
+    match operator {
+        BinaryOperator::Add if *left == Type::Str || *right == Type::Str => {
+            (is_scalar(left) && is_scalar(right)).then_some(Type::Str)
+        }
+        BinaryOperator::Add
+        | BinaryOperator::Sub
+        | BinaryOperator::Mul
+        | BinaryOperator::Div
+        | BinaryOperator::Mod => (is_numeric(left) && left == right).then(|| left.clone()),
+        BinaryOperator::Eq | BinaryOperator::Ne => {
+            let comparable = !contains_function(left) && !contains_function(right);
+            (comparable && (compatible(left, right) || compatible(right, left)))
+                .then_some(Type::Bool)
+        }
+        BinaryOperator::Lt | BinaryOperator::Le | BinaryOperator::Gt | BinaryOperator::Ge => {
+            ((is_numeric(left) || *left == Type::Str) && left == right).then_some(Type::Bool)
+        }
+        BinaryOperator::Pow => match (left, right) {
+            (Type::Int, Type::Int) => Some(Type::Int),
+            _ => (is_numeric(left) && is_numeric(right)).then_some(Type::Float),
+        },
+        BinaryOperator::BitAnd
+        | BinaryOperator::BitOr
+        | BinaryOperator::BitXor
+        | BinaryOperator::Shl
+        | BinaryOperator::Shr => (*left == Type::Int && *right == Type::Int).then_some(Type::Int),
+        BinaryOperator::And | BinaryOperator::Or => {
+            (*left == Type::Bool && *right == Type::Bool).then_some(Type::Bool)
+        }
+        BinaryOperator::Coalesce => match left {
+            Type::Optional(inner) if **inner == Type::Void => Some(right.clone()),
+            Type::Optional(_) if matches!(right, Type::Optional(_)) => {
+                fits(left, right).then(|| left.clone())
+            }
+            Type::Optional(inner) => fits(inner, right).then(|| (**inner).clone()),
+            _ => None,
+        },
+    }
+}
+
+fn is_numeric(ty: &Type) -> bool {
+    matches!(ty, Type::Int | Type::Float)
+}
+
+fn is_scalar(ty: &Type) -> bool {
+    matches!(ty, Type::Int | Type::Float | Type::Str | Type::Bool)
+}
+
+/// `name` in `snake_case`: an underscore goes before each capital that starts
+/// a new word, and everything is lowercased.
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut result = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next_lower)
+            {
+                result.push('_');
+            }
+        }
+        result.extend(c.to_lowercase());
+    }
+    result
+}
+
+/// `name` in `UpperCamelCase`: underscores are dropped and the letter after
+/// each one is capitalized.
+fn upper_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().expect("words are not empty");
+            first.to_uppercase().chain(chars).collect::<String>()
+        })
+        .collect()
+}
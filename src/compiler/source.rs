@@ -0,0 +1,93 @@
+//! Reading source files. One-shot builds memory-map files of
+//! `MMAP_THRESHOLD` bytes or more and lex them in place, so a generated
+//! source of hundreds of megabytes is never copied onto the heap; smaller
+//! files, and files that cannot be mapped, are read into a string. Anything
+//! that runs while the sources are being edited, such as watch mode, reads
+//! every file, since a mapping shows what is written to the file after it
+//! was checked.
+
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::Path;
+
+/// Size from which source files are memory-mapped instead of read.
+pub const MMAP_THRESHOLD: u64 = 1 << 20;
+
+/// The UTF-8 text of a source file, mapped or read.
+pub struct SourceFile {
+    text: Text,
+}
+
+enum Text {
+    /// Checked to be UTF-8 once, when mapped.
+    Mapped(Mmap),
+    Read(String),
+}
+
+impl SourceFile {
+    /// Reads `path` into memory.
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::read_file(file)
+    }
+
+    /// Opens `path`, mapping it when it is large enough to be worth it. The
+    /// file must not be written to while the result is alive, so this is
+    /// only for one-shot builds.
+    pub fn map(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() < MMAP_THRESHOLD {
+            return Self::read_file(file);
+        }
+        let Some(map) = map(&file) else {
+            return Self::read_file(file);
+        };
+        std::str::from_utf8(&map).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self {
+            text: Text::Mapped(map),
+        })
+    }
+
+    fn read_file(mut file: File) -> io::Result<Self> {
+        let size = file.metadata()?.len();
+        let mut text = String::with_capacity(usize::try_from(size).unwrap_or(0));
+        file.read_to_string(&mut text)?;
+        Ok(Self {
+            text: Text::Read(text),
+        })
+    }
+
+    /// Whether the text is mapped rather than held in memory.
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.text, Text::Mapped(_))
+    }
+
+    pub fn as_str(&self) -> &str {
+        match &self.text {
+            // SAFETY: `SourceFile::map` checked with `from_utf8` that the
+            // mapped bytes are UTF-8, and they stay the bytes it checked as
+            // long as nothing writes to the file, which its callers promise:
+            // only one-shot builds map files, never watch mode.
+            Text::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map) },
+            Text::Read(text) => text,
+        }
+    }
+}
+
+impl Deref for SourceFile {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Maps `file` read-only, or `None` where that is not possible, such as for
+/// pipes and some network file systems.
+fn map(file: &File) -> Option<Mmap> {
+    // SAFETY: the mapping is read-only, and callers of `SourceFile::map`
+    // promise nothing writes to the file while it is mapped.
+    unsafe { Mmap::map(file) }.ok()
+}
@@ -0,0 +1,97 @@
+//! Source maps from generated Rust back to Rustic. Code generation writes a
+//! `// rsc: file:line` comment before the code of each item, method and
+//! statement, naming where in the Rustic source it came from. `SourceMap::of`
+//! reads those comments back as ranges of generated lines, which the
+//! compiler writes next to each generated file as `.map.json` for tools that
+//! need to find the Rustic code behind a line of Rust.
+
+use crate::diagnostics::Span;
+use serde::{Deserialize, Serialize};
+
+/// What a source comment starts with.
+const PREFIX: &str = "// rsc: ";
+
+/// The comment code generation writes before the code for `span`.
+pub fn comment(span: &Span) -> String {
+    format!("{}{}:{}", PREFIX, span.file, span.start_line)
+}
+
+/// Where `comment` says its code came from: a file and a line.
+pub fn parse_comment(comment: &str) -> Option<(&str, usize)> {
+    let (file, line) = comment
+        .trim_start()
+        .strip_prefix(PREFIX)?
+        .rsplit_once(':')?;
+    Some((file, line.parse().ok()?))
+}
+
+/// The source map written next to the generated file `rust_file`:
+/// `net/http.map.json` for `net/http.rs`.
+pub fn map_path(rust_file: &str) -> String {
+    let stem = rust_file.strip_suffix(".rs").unwrap_or(rust_file);
+    format!("{}.map.json", stem)
+}
+
+/// Generated lines and the Rustic lines they came from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SourceMap {
+    pub mappings: Vec<Mapping>,
+}
+
+/// The generated lines from `start_line` to `end_line`, counted from 1, came
+/// from `line` of the Rustic file `file`. The code of a statement nested in
+/// another has a mapping of its own within the outer one's.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mapping {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub file: String,
+    pub line: usize,
+}
+
+impl SourceMap {
+    /// The source map of generated `code`, from its source comments. The
+    /// code after a comment runs until a line indented less than it, the
+    /// next comment indented no more than it, or a blank line.
+    pub fn of(code: &str) -> Self {
+        let lines: Vec<&str> = code.lines().collect();
+        let indent = |line: &str| line.len() - line.trim_start().len();
+        let mut mappings = Vec::new();
+        for (index, comment) in lines.iter().enumerate() {
+            let Some((file, line)) = parse_comment(comment) else {
+                continue;
+            };
+            let depth = indent(comment);
+            let end = lines[index + 1..]
+                .iter()
+                .position(|next| {
+                    next.trim().is_empty()
+                        || indent(next) < depth
+                        || indent(next) == depth && parse_comment(next).is_some()
+                })
+                .map_or(lines.len(), |length| index + 1 + length);
+            if end > index + 1 {
+                mappings.push(Mapping {
+                    start_line: index + 2,
+                    end_line: end,
+                    file: file.to_string(),
+                    line,
+                });
+            }
+        }
+        Self { mappings }
+    }
+
+    /// Where the generated `line`, counted from 1, came from: the innermost
+    /// mapping that covers it.
+    pub fn lookup(&self, line: usize) -> Option<&Mapping> {
+        self.mappings
+            .iter()
+            .filter(|mapping| mapping.start_line <= line && line <= mapping.end_line)
+            .max_by_key(|mapping| mapping.start_line)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a source map is always serializable")
+    }
+}
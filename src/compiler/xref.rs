@@ -0,0 +1,184 @@
+//! Symbol cross-reference index: every definition the semantic analyzer sees
+//! and every resolved use of it, with types and spans, for `rustic xref`.
+//!
+//! Each module is analyzed on its own, so a use of an item another module
+//! declares is kept aside until `merge` brings in that module's index.
+
+use crate::diagnostics::Span;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymbolKind {
+    Function,
+    Method,
+    Struct,
+    Field,
+    Enum,
+    Variant,
+    Global,
+    Constant,
+    Parameter,
+    Variable,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Definition {
+    pub id: usize,
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The symbol's type as written in Rustic, e.g. `list[int]` or `fn(float) -> float`.
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// The enclosing function or struct, if any.
+    pub container: Option<String>,
+    pub mutable: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reference {
+    pub definition: usize,
+    pub span: Span,
+}
+
+/// A module-level item: (kind, container, name).
+type ItemKey = (SymbolKind, Option<String>, String);
+
+/// A use of an item declared by another module.
+#[derive(Debug, Clone, PartialEq)]
+struct ImportedReference {
+    module: String,
+    item: ItemKey,
+    span: Span,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct XrefIndex {
+    pub definitions: Vec<Definition>,
+    pub references: Vec<Reference>,
+    /// Module-level functions, structs, fields, methods, enums and variants by
+    /// (kind, container, name).
+    #[serde(skip)]
+    items: HashMap<ItemKey, usize>,
+    /// The items of merged modules, by module.
+    #[serde(skip)]
+    merged_items: HashMap<(String, ItemKey), usize>,
+    /// Uses of items whose module has not been merged yet.
+    #[serde(skip)]
+    imported: Vec<ImportedReference>,
+}
+
+impl XrefIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn define(
+        &mut self,
+        name: &str,
+        kind: SymbolKind,
+        ty: String,
+        container: Option<&str>,
+        mutable: bool,
+        span: &Span,
+    ) -> usize {
+        let id = self.definitions.len();
+        self.definitions.push(Definition {
+            id,
+            name: name.to_string(),
+            kind,
+            ty,
+            container: container.map(str::to_string),
+            mutable,
+            span: span.clone(),
+        });
+        if matches!(
+            kind,
+            SymbolKind::Function
+                | SymbolKind::Method
+                | SymbolKind::Struct
+                | SymbolKind::Field
+                | SymbolKind::Enum
+                | SymbolKind::Variant
+        ) {
+            self.items
+                .entry((kind, container.map(str::to_string), name.to_string()))
+                .or_insert(id);
+        }
+        id
+    }
+
+    pub fn reference(&mut self, definition: usize, span: &Span) {
+        self.references.push(Reference {
+            definition,
+            span: span.clone(),
+        });
+    }
+
+    /// Records a use of an item `module` declares, resolved once that
+    /// module's index is merged in.
+    pub fn reference_imported(
+        &mut self,
+        module: &str,
+        kind: SymbolKind,
+        container: Option<&str>,
+        name: &str,
+        span: &Span,
+    ) {
+        self.imported.push(ImportedReference {
+            module: module.to_string(),
+            item: (kind, container.map(str::to_string), name.to_string()),
+            span: span.clone(),
+        });
+    }
+
+    /// Looks up a function, struct, enum or (with `container` set to the struct
+    /// or enum) field, method or variant.
+    pub fn item(&self, kind: SymbolKind, container: Option<&str>, name: &str) -> Option<usize> {
+        self.items
+            .get(&(kind, container.map(str::to_string), name.to_string()))
+            .copied()
+    }
+
+    /// Appends the index of `module`, renumbering its definitions so ids stay
+    /// unique, and resolves the uses of items between the modules merged so
+    /// far. Item lookups keep referring to this module only.
+    pub fn merge(&mut self, module: &str, other: &XrefIndex) {
+        let offset = self.definitions.len();
+        self.definitions
+            .extend(other.definitions.iter().cloned().map(|mut definition| {
+                definition.id += offset;
+                definition
+            }));
+        self.references
+            .extend(other.references.iter().cloned().map(|mut reference| {
+                reference.definition += offset;
+                reference
+            }));
+        for (item, id) in &other.items {
+            self.merged_items
+                .insert((module.to_string(), item.clone()), id + offset);
+        }
+        self.imported.extend(other.imported.iter().cloned());
+
+        let merged_items = &self.merged_items;
+        let references = &mut self.references;
+        self.imported.retain(|imported| {
+            let key = (imported.module.clone(), imported.item.clone());
+            let Some(&definition) = merged_items.get(&key) else {
+                return true;
+            };
+            references.push(Reference {
+                definition,
+                span: imported.span.clone(),
+            });
+            false
+        });
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("xref index serializes")
+    }
+}
@@ -0,0 +1,943 @@
+use crate::render::{self, SourceMap};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Write as _};
+use std::ops::Range;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// A region of one source file. Lines and columns are 1-based and count
+/// characters, for display; the offsets are 0-based UTF-8 byte offsets into
+/// the file's text, for slicing it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub file: String,
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    #[serde(default)]
+    pub start_offset: usize,
+    #[serde(default)]
+    pub end_offset: usize,
+}
+
+impl Span {
+    /// Returns a span covering `self` through the end of `other`.
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            file: self.file.clone(),
+            start_line: self.start_line,
+            start_column: self.start_column,
+            end_line: other.end_line,
+            end_column: other.end_column,
+            start_offset: self.start_offset,
+            end_offset: other.end_offset,
+        }
+    }
+
+    /// Byte range of the span in its source text.
+    pub fn range(&self) -> Range<usize> {
+        self.start_offset..self.end_offset
+    }
+
+    /// The text the span covers in `source`, or `None` if `source` is not the
+    /// text the span was taken from.
+    pub fn slice<'a>(&self, source: &'a str) -> Option<&'a str> {
+        source.get(self.range())
+    }
+
+    /// The first occurrence of the identifier `word` within the span, as a
+    /// whole word, e.g. the name of the variable a `let` declares.
+    pub fn find_word(&self, source: &str, word: &str) -> Option<Span> {
+        let text = self.slice(source)?;
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let (index, _) = text.match_indices(word).find(|&(index, _)| {
+            let before = text[..index].chars().next_back();
+            let after = text[index + word.len()..].chars().next();
+            !before.is_some_and(is_word) && !after.is_some_and(is_word)
+        })?;
+
+        let before = &text[..index];
+        let (line, column) = match before.rfind('\n') {
+            Some(newline) => (
+                self.start_line + before.matches('\n').count(),
+                before[newline + 1..].chars().count() + 1,
+            ),
+            None => (self.start_line, self.start_column + before.chars().count()),
+        };
+        Some(Span {
+            file: self.file.clone(),
+            start_line: line,
+            start_column: column,
+            end_line: line,
+            end_column: column + word.chars().count(),
+            start_offset: self.start_offset + index,
+            end_offset: self.start_offset + index + word.len(),
+        })
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.start_line, self.start_column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Error {
+    #[error("Lexical error: {0}")]
+    LexError(String),
+    #[error("Parse error: {0}")]
+    ParseError(String),
+    #[error("Semantic error: {0}")]
+    SemanticError(String),
+    #[error("Code generation error: {0}")]
+    CodegenError(String),
+    #[error("I/O error: {0}")]
+    IoError(String),
+    #[error("Compilation error: {0}")]
+    CompilationError(String),
+    #[error("Runtime error: {0}")]
+    RuntimeError(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Error => write!(f, "error"),
+            Level::Warning => write!(f, "warning"),
+            Level::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A group of warnings that can be allowed, warned about or denied together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    /// Declarations that are never used.
+    Unused,
+    /// Declarations that hide another of the same name.
+    Shadowing,
+    /// Naming and layout conventions.
+    Style,
+    /// Uses of deprecated items.
+    Deprecation,
+    /// Code that is correct but needlessly slow.
+    Performance,
+}
+
+impl Category {
+    pub const ALL: [Category; 5] = [
+        Category::Unused,
+        Category::Shadowing,
+        Category::Style,
+        Category::Deprecation,
+        Category::Performance,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Unused => "unused",
+            Category::Shadowing => "shadowing",
+            Category::Style => "style",
+            Category::Deprecation => "deprecation",
+            Category::Performance => "performance",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Category> {
+        Category::ALL
+            .into_iter()
+            .find(|category| category.name() == name)
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A kind of warning, in one category, whose level can also be set on its
+/// own. A lint's level takes precedence over its category's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Lint {
+    /// A variable or parameter that is never read.
+    UnusedVariable,
+    /// A private function nothing calls.
+    UnusedFunction,
+    /// An import nothing refers to.
+    UnusedImport,
+    /// A `rustic:allow` comment that silences nothing.
+    UnusedSuppression,
+    /// Statements, branches or loop bodies that never run.
+    UnreachableCode,
+    /// A `match` arm or `catch` clause that the ones before it leave nothing
+    /// to.
+    UnreachablePattern,
+    /// A variable declared over one of an enclosing scope.
+    ShadowedVariable,
+    /// A use of a deprecated struct or function.
+    Deprecated,
+    /// A name that does not follow the naming conventions.
+    NamingConvention,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 9] = [
+        Lint::UnusedVariable,
+        Lint::UnusedFunction,
+        Lint::UnusedImport,
+        Lint::UnusedSuppression,
+        Lint::UnreachableCode,
+        Lint::UnreachablePattern,
+        Lint::ShadowedVariable,
+        Lint::Deprecated,
+        Lint::NamingConvention,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedFunction => "unused-function",
+            Lint::UnusedImport => "unused-import",
+            Lint::UnusedSuppression => "unused-suppression",
+            Lint::UnreachableCode => "unreachable-code",
+            Lint::UnreachablePattern => "unreachable-pattern",
+            Lint::ShadowedVariable => "shadowed-variable",
+            Lint::Deprecated => "deprecated",
+            Lint::NamingConvention => "naming-convention",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+
+    pub fn category(self) -> Category {
+        match self {
+            Lint::UnusedVariable
+            | Lint::UnusedFunction
+            | Lint::UnusedImport
+            | Lint::UnusedSuppression
+            | Lint::UnreachableCode
+            | Lint::UnreachablePattern => Category::Unused,
+            Lint::ShadowedVariable => Category::Shadowing,
+            Lint::Deprecated => Category::Deprecation,
+            Lint::NamingConvention => Category::Style,
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What a lint level is set for: a whole category, or a single lint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintName {
+    Category(Category),
+    Lint(Lint),
+}
+
+impl LintName {
+    /// Every category's name, then every lint's.
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        let categories = Category::ALL.into_iter().map(Category::name);
+        categories.chain(Lint::ALL.into_iter().map(Lint::name))
+    }
+
+    pub fn from_name(name: &str) -> Option<LintName> {
+        Category::from_name(name)
+            .map(LintName::Category)
+            .or_else(|| Lint::from_name(name).map(LintName::Lint))
+    }
+}
+
+impl fmt::Display for LintName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintName::Category(category) => f.write_str(category.name()),
+            LintName::Lint(lint) => f.write_str(lint.name()),
+        }
+    }
+}
+
+impl From<Category> for LintName {
+    fn from(category: Category) -> Self {
+        LintName::Category(category)
+    }
+}
+
+impl From<Lint> for LintName {
+    fn from(lint: Lint) -> Self {
+        LintName::Lint(lint)
+    }
+}
+
+/// What to do with the warnings of a category or lint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LintLevel {
+    /// Drop them.
+    Allow,
+    /// Report them as warnings.
+    #[default]
+    Warn,
+    /// Report them as errors.
+    Deny,
+}
+
+/// A `// rustic:allow(...)` or `// rustic:deny(...)` comment, setting the level
+/// of the listed categories and lints for diagnostics that start on `line`:
+/// the line the comment is on, or the next one when the comment is on a line
+/// of its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suppression {
+    pub level: LintLevel,
+    pub names: Vec<LintName>,
+    pub line: usize,
+    /// The comment itself.
+    pub span: Span,
+}
+
+impl Suppression {
+    /// The position of the name in the comment that `lint`, reported as
+    /// `diagnostic`, falls under, if the comment applies to it.
+    fn applies_to(&self, diagnostic: &Diagnostic, lint: Lint) -> Option<usize> {
+        if self.span.file != diagnostic.span.file || self.line != diagnostic.span.start_line {
+            return None;
+        }
+        self.names.iter().position(|name| match name {
+            LintName::Category(category) => *category == lint.category(),
+            LintName::Lint(named) => *named == lint,
+        })
+    }
+}
+
+/// A suppression in effect, with the names it has been applied to.
+#[derive(Debug, Clone)]
+struct ActiveSuppression {
+    suppression: Suppression,
+    used: Vec<bool>,
+}
+
+/// A secondary span with its own message, pointing at code related to the
+/// primary span (e.g. an earlier definition).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// How sure the compiler is that applying a suggestion gives the code the
+/// author meant; `--fix` and `rustic fix` apply only machine-applicable ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Applicability {
+    /// Certainly right; safe to apply without review.
+    MachineApplicable,
+    /// Probably right, but may change what the code means.
+    MaybeIncorrect,
+    /// The replacement contains placeholders to fill in by hand.
+    HasPlaceholders,
+    #[default]
+    Unspecified,
+}
+
+/// A proposed edit: replace the text at `span` with `replacement`. An empty
+/// span inserts, an empty replacement removes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub message: String,
+    pub span: Span,
+    pub replacement: String,
+    #[serde(default)]
+    pub applicability: Applicability,
+}
+
+impl Suggestion {
+    /// The suggestion as a `help:` line.
+    pub(crate) fn help(&self) -> String {
+        if self.replacement.is_empty() {
+            self.message.clone()
+        } else {
+            format!("{}: `{}`", self.message, self.replacement)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub level: Level,
+    /// Stable identifier for the kind of problem, e.g. `E0001`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+    /// For warnings, the group that decides whether they are reported and at
+    /// which level.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<Category>,
+    /// For warnings, the lint within `category`, whose own level takes
+    /// precedence over the category's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lint: Option<Lint>,
+    /// Primary location of the problem.
+    pub span: Span,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<Label>,
+    /// Background on the problem, shown as `note:` lines.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<String>,
+    /// Advice on fixing the problem, shown as `help:` lines.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub help: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(level: Level, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            level,
+            code: None,
+            message: message.into(),
+            category: None,
+            lint: None,
+            span,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Level::Error, message, span)
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Level::Warning, message, span)
+    }
+
+    pub fn note(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Level::Note, message, span)
+    }
+
+    /// A warning of `lint`, in its category.
+    pub fn lint(lint: Lint, message: impl Into<String>, span: Span) -> Self {
+        let mut diagnostic = Self::warning(message, span);
+        diagnostic.category = Some(lint.category());
+        diagnostic.lint = Some(lint);
+        diagnostic
+    }
+
+    /// For a categorized diagnostic, which level its category is at and how
+    /// to change it.
+    pub(crate) fn category_note(&self) -> Option<String> {
+        let category = self.category?;
+        Some(match self.level {
+            Level::Error => format!("`{}` warnings are denied", category),
+            _ => format!("`{}` warnings are on; `-A {0}` turns them off", category),
+        })
+    }
+
+    /// Whether both report the same thing at the same place, ignoring
+    /// labels, notes and suggestions.
+    pub fn same_problem(&self, other: &Diagnostic) -> bool {
+        self.level == other.level
+            && self.code == other.code
+            && self.span == other.span
+            && self.message == other.message
+    }
+
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    pub fn with_suggestion(
+        mut self,
+        message: impl Into<String>,
+        span: Span,
+        replacement: impl Into<String>,
+        applicability: Applicability,
+    ) -> Self {
+        self.suggestions.push(Suggestion {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+            applicability,
+        });
+        self
+    }
+}
+
+/// Puts diagnostics in reading order, by file, line and column, keeping the
+/// reported order otherwise, and drops repeats of the same problem.
+pub fn sort_diagnostics(diagnostics: &mut Vec<Diagnostic>) {
+    diagnostics.sort_by(|a, b| {
+        (&a.span.file, a.span.start_line, a.span.start_column).cmp(&(
+            &b.span.file,
+            b.span.start_line,
+            b.span.start_column,
+        ))
+    });
+    let mut kept: Vec<Diagnostic> = Vec::with_capacity(diagnostics.len());
+    for diagnostic in diagnostics.drain(..) {
+        if !kept.iter().any(|d| d.same_problem(&diagnostic)) {
+            kept.push(diagnostic);
+        }
+    }
+    *diagnostics = kept;
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.level)?;
+        if let Some(code) = &self.code {
+            write!(f, "[{}]", code)?;
+        }
+        write!(f, ": {}\n  --> {}", self.message, self.span)?;
+        for label in &self.labels {
+            write!(f, "\n  --> {}: {}", label.span, label.message)?;
+        }
+        for note in self.notes.iter().cloned().chain(self.category_note()) {
+            write!(f, "\n  = note: {}", note)?;
+        }
+        for help in &self.help {
+            write!(f, "\n  = help: {}", help)?;
+        }
+        for suggestion in &self.suggestions {
+            write!(f, "\n  = help: {}", suggestion.help())?;
+        }
+        Ok(())
+    }
+}
+
+/// How many diagnostics of each level, and warnings of each category, a run
+/// reported.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DiagnosticCounts {
+    pub errors: usize,
+    pub warnings: usize,
+    pub notes: usize,
+    /// Warnings by category; uncategorized warnings are not listed.
+    pub warnings_by_category: BTreeMap<Category, usize>,
+}
+
+impl DiagnosticCounts {
+    pub fn of(diagnostics: &[Diagnostic]) -> Self {
+        let mut counts = Self::default();
+        for diagnostic in diagnostics {
+            match diagnostic.level {
+                Level::Error => counts.errors += 1,
+                Level::Warning => {
+                    counts.warnings += 1;
+                    if let Some(category) = diagnostic.category {
+                        *counts.warnings_by_category.entry(category).or_default() += 1;
+                    }
+                }
+                Level::Note => counts.notes += 1,
+            }
+        }
+        counts
+    }
+
+    /// The level of the summary line, or `None` if there is nothing to sum up.
+    pub fn summary_level(&self) -> Option<Level> {
+        if self.errors > 0 {
+            Some(Level::Error)
+        } else if self.warnings > 0 {
+            Some(Level::Warning)
+        } else {
+            None
+        }
+    }
+
+    /// The end-of-run line without its level, e.g. `aborting due to 3
+    /// previous errors; 7 warnings emitted (4 unused, 3 style)`.
+    pub fn summary(&self) -> Option<String> {
+        let warnings = match self.warnings {
+            0 => None,
+            count => {
+                let mut text = format!("{} emitted", plural(count, "warning"));
+                if !self.warnings_by_category.is_empty() {
+                    let categories: Vec<String> = self
+                        .warnings_by_category
+                        .iter()
+                        .map(|(category, count)| format!("{} {}", count, category))
+                        .collect();
+                    let _ = write!(text, " ({})", categories.join(", "));
+                }
+                Some(text)
+            }
+        };
+        match (self.errors, warnings) {
+            (0, warnings) => warnings,
+            (errors, None) => Some(format!(
+                "aborting due to {}",
+                plural(errors, "previous error")
+            )),
+            (errors, Some(warnings)) => Some(format!(
+                "aborting due to {}; {}",
+                plural(errors, "previous error"),
+                warnings
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.summary_level(), self.summary()) {
+            (Some(level), Some(summary)) => write!(f, "{}: {}", level, summary),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+#[derive(Debug, Default)]
+pub struct DiagnosticEngine {
+    diagnostics: Vec<Diagnostic>,
+    sources: SourceMap,
+    /// Levels of categories and lints that differ from the default, `Warn`.
+    lint_levels: HashMap<LintName, LintLevel>,
+    /// Comments overriding `lint_levels` on single lines of the module being
+    /// compiled.
+    suppressions: Vec<ActiveSuppression>,
+}
+
+impl DiagnosticEngine {
+    pub fn new() -> Self {
+        Self {
+            diagnostics: Vec::new(),
+            sources: SourceMap::new(),
+            lint_levels: HashMap::new(),
+            suppressions: Vec::new(),
+        }
+    }
+
+    /// Remembers the text of `file` for source snippets in `emit_all`, for
+    /// sources that are not on disk or may have changed since.
+    pub fn add_source(&mut self, file: &str, text: &str) {
+        self.sources.add(file, text);
+    }
+
+    /// The text of `file` as given to `add_source`, or else as read from disk.
+    pub fn source(&mut self, file: &str) -> Option<Arc<str>> {
+        self.sources.load(file)
+    }
+
+    pub fn error(&mut self, message: impl Into<String>, span: Span) {
+        self.push(Level::Error, message.into(), span);
+    }
+
+    pub fn warning(&mut self, message: impl Into<String>, span: Span) {
+        self.push(Level::Warning, message.into(), span);
+    }
+
+    pub fn note(&mut self, message: impl Into<String>, span: Span) {
+        self.push(Level::Note, message.into(), span);
+    }
+
+    pub fn set_lint_level(&mut self, name: impl Into<LintName>, level: LintLevel) {
+        self.lint_levels.insert(name.into(), level);
+    }
+
+    /// The level of the warnings of `lint`: its own if it was given one, or
+    /// else its category's.
+    pub fn lint_level(&self, lint: Lint) -> LintLevel {
+        self.lint_levels
+            .get(&LintName::Lint(lint))
+            .or_else(|| self.lint_levels.get(&LintName::Category(lint.category())))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// A new, empty engine with the lint levels of this one, for checking
+    /// modules on another thread. `absorb` takes back what it reported.
+    pub fn fork(&self) -> Self {
+        Self {
+            lint_levels: self.lint_levels.clone(),
+            ..Self::new()
+        }
+    }
+
+    /// Takes the diagnostics and sources of `other`, an engine from `fork`,
+    /// after those recorded here. Its diagnostics already have their final
+    /// levels; problems already recorded here are dropped.
+    pub fn absorb(&mut self, other: DiagnosticEngine) {
+        for diagnostic in other.diagnostics {
+            if !self.diagnostics.iter().any(|d| d.same_problem(&diagnostic)) {
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        self.sources.extend(other.sources);
+    }
+
+    /// Puts the suppression comments of a module in effect, replacing those
+    /// of the module before it.
+    pub fn set_suppressions(&mut self, suppressions: Vec<Suppression>) {
+        self.suppressions = suppressions
+            .into_iter()
+            .map(|suppression| ActiveSuppression {
+                used: vec![false; suppression.names.len()],
+                suppression,
+            })
+            .collect();
+    }
+
+    /// Warns about every name in an allow comment that has not silenced
+    /// anything yet. Call once the module's diagnostics have all been emitted.
+    pub fn report_unused_suppressions(&mut self) {
+        let mut unused = Vec::new();
+        for active in &mut self.suppressions {
+            let suppression = &active.suppression;
+            if suppression.level != LintLevel::Allow {
+                continue;
+            }
+            for (name, used) in suppression.names.iter().zip(&mut active.used) {
+                if !*used {
+                    // Reported once, even if asked again.
+                    *used = true;
+                    unused.push(
+                        Diagnostic::lint(
+                            Lint::UnusedSuppression,
+                            format!("`rustic:allow({})` does not silence anything", name),
+                            suppression.span.clone(),
+                        )
+                        .with_help("remove it"),
+                    );
+                }
+            }
+        }
+        for diagnostic in unused {
+            self.emit(diagnostic);
+        }
+    }
+
+    /// Records a fully built diagnostic, with any code, labels and suggestions,
+    /// and returns the level it was recorded at: a denied lint turns a
+    /// warning into an error. Returns `None` if the diagnostic was dropped,
+    /// because its lint is allowed or the same problem was already recorded.
+    /// A suppression comment on the diagnostic's line takes precedence over
+    /// the lint's level.
+    pub fn emit(&mut self, mut diagnostic: Diagnostic) -> Option<Level> {
+        if let Some(lint) = diagnostic.lint {
+            let mut level = self.lint_level(lint);
+            for active in &mut self.suppressions {
+                if let Some(index) = active.suppression.applies_to(&diagnostic, lint) {
+                    active.used[index] = true;
+                    level = active.suppression.level;
+                }
+            }
+            match level {
+                LintLevel::Allow => return None,
+                LintLevel::Warn => {}
+                LintLevel::Deny => diagnostic.level = Level::Error,
+            }
+        }
+        if self.diagnostics.iter().any(|d| d.same_problem(&diagnostic)) {
+            return None;
+        }
+        let level = diagnostic.level;
+        self.diagnostics.push(diagnostic);
+        Some(level)
+    }
+
+    fn push(&mut self, level: Level, message: String, span: Span) {
+        self.emit(Diagnostic::new(level, message, span));
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.diagnostics.iter()
+    }
+
+    /// Takes every diagnostic collected so far, leaving the engine empty.
+    pub fn take(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.level == Level::Error)
+            .count()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    pub fn counts(&self) -> DiagnosticCounts {
+        DiagnosticCounts::of(&self.diagnostics)
+    }
+
+    pub fn clear(&mut self) {
+        self.diagnostics.clear();
+    }
+
+    /// Writes every diagnostic to stderr with annotated source snippets.
+    pub fn emit_all(&self) {
+        render::emit(&self.diagnostics, &mut self.sources.clone());
+    }
+
+    /// Writes every diagnostic to stderr as JSON, one object per line.
+    pub fn emit_all_json(&self) {
+        render::emit_json(&self.diagnostics);
+    }
+}
+
+impl<'a> IntoIterator for &'a DiagnosticEngine {
+    type Item = &'a Diagnostic;
+    type IntoIter = std::slice::Iter<'a, Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for DiagnosticEngine {
+    type Item = Diagnostic;
+    type IntoIter = std::vec::IntoIter<Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.diagnostics.into_iter()
+    }
+}
+
+/// The stable codes of errors, by kind of problem. A code is never reused for
+/// another kind of problem, so tools and documentation can rely on it.
+pub mod codes {
+    /// An error code, with the name of its constant and what its doc comment
+    /// says it is for, as `rustic introspect` lists them.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Code {
+        pub code: &'static str,
+        pub name: &'static str,
+        pub summary: &'static str,
+    }
+
+    /// Declares each code as a constant and lists them all in `ALL`, in
+    /// order.
+    macro_rules! codes {
+        ($($(#[doc = $doc:literal])+ $name:ident = $code:literal,)+) => {
+            $($(#[doc = $doc])+ pub const $name: &str = $code;)+
+
+            /// Every code, in order.
+            pub const ALL: &[Code] = &[$(Code {
+                code: $code,
+                name: stringify!($name),
+                summary: concat!($($doc),+),
+            }),+];
+        };
+    }
+
+    codes! {
+        /// A character that cannot start a token.
+        UNEXPECTED_CHARACTER = "E0001",
+        /// A string, interpolation or block comment that is never closed.
+        UNTERMINATED = "E0002",
+        /// An escape sequence strings do not have.
+        INVALID_ESCAPE = "E0003",
+        /// A number literal that is malformed or out of range.
+        INVALID_NUMBER = "E0004",
+
+        /// Input the grammar does not allow where it appears.
+        SYNTAX = "E0100",
+        /// Code nested deeper than the parser allows.
+        TOO_DEEP = "E0101",
+
+        /// A name that is neither defined nor imported.
+        UNKNOWN_NAME = "E0200",
+        /// An import of a module that does not exist.
+        UNKNOWN_MODULE = "E0201",
+        /// A field, method, variant or module member that does not exist.
+        NO_MEMBER = "E0202",
+        /// A name defined twice where it must be unique.
+        REDEFINED = "E0203",
+        /// A use of an item that is private to another module.
+        PRIVATE = "E0204",
+        /// An import the module system does not allow.
+        IMPORT = "E0205",
+
+        /// A value of another type than the one expected.
+        MISMATCHED_TYPES = "E0300",
+        /// An operation the type of the value does not support.
+        UNSUPPORTED_OPERATION = "E0301",
+        /// Too many or too few arguments, fields or values, or ones the callee
+        /// does not take.
+        ARGUMENTS = "E0302",
+        /// Type arguments that do not fit the generic type or function.
+        TYPE_ARGUMENTS = "E0303",
+        /// A type that cannot be inferred and must be written out.
+        CANNOT_INFER = "E0304",
+        /// A type that cannot be used where it appears, such as `void` in a list.
+        INVALID_TYPE = "E0305",
+        /// An optional used as if it could not be `none`.
+        POSSIBLY_NONE = "E0306",
+        /// An expression that cannot be used where it appears.
+        INVALID_EXPRESSION = "E0307",
+
+        /// A change to something that cannot be changed.
+        IMMUTABLE = "E0400",
+        /// `break`, `continue` or `return` where control cannot go that way.
+        CONTROL_FLOW = "E0401",
+        /// A `return` that does not fit the function's return type.
+        RETURN = "E0402",
+        /// A `match` that does not cover every value.
+        NON_EXHAUSTIVE = "E0403",
+        /// Something other than an exception thrown, caught or declared as one.
+        NOT_AN_EXCEPTION = "E0404",
+        /// A variable declared without a value used before it is assigned one.
+        UNASSIGNED = "E0405",
+        /// An operation on constants that always fails, such as a division by
+        /// zero.
+        ALWAYS_FAILS = "E0406",
+
+        /// Generated Rust that rustc rejects, reported at the Rustic code it was
+        /// generated from.
+        GENERATED_RUST = "E0500",
+    }
+}
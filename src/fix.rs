@@ -0,0 +1,99 @@
+//! Applies machine-applicable suggestions to source files, for `rustic --fix`
+//! and `rustic fix`.
+//! Other suggestions need a person to check them and are left alone.
+
+use crate::diagnostics::{Applicability, Diagnostic, Error, Result, Suggestion};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+
+/// A file rewritten by `fix_files` and the number of suggestions applied to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedFile {
+    pub file: String,
+    pub fixes: usize,
+}
+
+/// Applies the machine-applicable suggestions of `diagnostics` to the files
+/// they point into and writes the files back, in file name order.
+pub fn fix_files(diagnostics: &[Diagnostic]) -> Result<Vec<FixedFile>> {
+    let mut by_file: BTreeMap<&str, Vec<&Suggestion>> = BTreeMap::new();
+    for suggestion in diagnostics.iter().flat_map(|d| &d.suggestions) {
+        if suggestion.applicability == Applicability::MachineApplicable {
+            by_file
+                .entry(&suggestion.span.file)
+                .or_default()
+                .push(suggestion);
+        }
+    }
+
+    let mut fixed = Vec::new();
+    for (file, suggestions) in by_file {
+        let source = fs::read_to_string(file)
+            .map_err(|e| Error::IoError(format!("Failed to read {}: {}", file, e)))?;
+        let (text, fixes) = apply(&source, &suggestions);
+        if fixes == 0 {
+            continue;
+        }
+        fs::write(file, text)
+            .map_err(|e| Error::IoError(format!("Failed to write {}: {}", file, e)))?;
+        fixed.push(FixedFile {
+            file: file.to_string(),
+            fixes,
+        });
+    }
+    Ok(fixed)
+}
+
+/// Applies `suggestions` to `source` and returns the result with the number
+/// applied. A suggestion that overlaps one applied before it, or whose span
+/// does not fit `source`, is skipped. A removal that leaves its line blank
+/// removes the line.
+pub fn apply(source: &str, suggestions: &[&Suggestion]) -> (String, usize) {
+    let mut edits: Vec<(Range<usize>, &str)> = suggestions
+        .iter()
+        .filter(|suggestion| suggestion.span.slice(source).is_some())
+        .map(|suggestion| {
+            let range = suggestion.span.range();
+            let range = if suggestion.replacement.is_empty() {
+                whole_line(source, range)
+            } else {
+                range
+            };
+            (range, suggestion.replacement.as_str())
+        })
+        .collect();
+    edits.sort_by_key(|(range, _)| (range.start, range.end));
+    edits.dedup();
+
+    let mut output = String::with_capacity(source.len());
+    let mut copied = 0;
+    let mut applied = 0;
+    for (range, replacement) in edits {
+        if range.start < copied {
+            continue;
+        }
+        output.push_str(&source[copied..range.start]);
+        output.push_str(replacement);
+        copied = range.end;
+        applied += 1;
+    }
+    output.push_str(&source[copied..]);
+    (output, applied)
+}
+
+/// `range` widened to its whole line, line ending included, when nothing
+/// but whitespace surrounds it there.
+fn whole_line(source: &str, range: Range<usize>) -> Range<usize> {
+    let line_start = source[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[range.end..]
+        .find('\n')
+        .map_or(source.len(), |i| range.end + i + 1);
+    let before = &source[line_start..range.start];
+    let after = &source[range.end..line_end];
+    if before.trim().is_empty() && after.trim().is_empty() {
+        line_start..line_end
+    } else {
+        range
+    }
+}
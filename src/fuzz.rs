@@ -0,0 +1,412 @@
+//! Frontend fuzzing: random and grammar-derived inputs are pushed through the
+//! lexer and parser, which must reject bad input with diagnostics and never panic.
+//!
+//! Used by `rustic fuzz` and by the cargo-fuzz targets under `fuzz/`.
+
+use crate::compiler::lexer::{Lexer, KEYWORDS};
+use crate::compiler::parser::Parser;
+use crate::diagnostics::DiagnosticEngine;
+use crate::utils;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const PUNCTUATION: &[&str] = &[
+    "(", ")", "{", "}", "[", "]", ",", ".", "..", "..=", ":", ";", "->", "+", "-", "*", "/", "%",
+    "==", "!=", "<", "<=", ">", ">=", "&&", "||", "|", "!", "?", "??", "=", "&", "^", "~", "<<",
+    ">>", "**", "\n", "\"", "//",
+];
+
+#[derive(Debug, Clone)]
+pub struct FuzzOptions {
+    pub iterations: u64,
+    pub seed: u64,
+    pub max_len: usize,
+    /// Existing `.rsc` files used as mutation seeds.
+    pub corpus: Option<PathBuf>,
+    /// Where minimized crashing inputs are written.
+    pub artifacts: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct Crash {
+    pub message: String,
+    pub input: Vec<u8>,
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+pub struct FuzzReport {
+    pub executed: u64,
+    pub elapsed: Duration,
+    pub crashes: Vec<Crash>,
+}
+
+/// Runs `input` through the lexer and parser, returning a description of the
+/// failure if either panics or the parser fails without reporting a diagnostic.
+pub fn check_input(input: &[u8]) -> Option<String> {
+    let source = String::from_utf8_lossy(input);
+
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut diagnostics = DiagnosticEngine::new();
+        let tokens = Lexer::new(&source, "fuzz.rsc").tokenize_reporting(&mut diagnostics);
+        let result = Parser::new(tokens, &mut diagnostics).parse();
+        match result {
+            Err(error) if !diagnostics.has_errors() => {
+                Some(format!("parser failed without a diagnostic: {}", error))
+            }
+            _ => None,
+        }
+    }));
+
+    match outcome {
+        Ok(failure) => failure,
+        Err(payload) => Some(panic_message(payload.as_ref())),
+    }
+}
+
+pub fn run(options: &FuzzOptions) -> FuzzReport {
+    let seeds = options
+        .corpus
+        .as_deref()
+        .map(load_corpus)
+        .unwrap_or_default();
+    let mut rng = Rng::new(options.seed);
+    let mut report = FuzzReport::default();
+    let mut seen = Vec::new();
+    let start = Instant::now();
+
+    with_silent_panics(|| {
+        for _ in 0..options.iterations {
+            let input = generate_input(&mut rng, &seeds, options.max_len);
+            report.executed += 1;
+
+            let Some(message) = check_input(&input) else {
+                continue;
+            };
+            if seen.contains(&message) {
+                continue;
+            }
+            seen.push(message.clone());
+
+            let input = minimize(&input, |candidate| {
+                check_input(candidate).as_ref() == Some(&message)
+            });
+            let path = save_artifact(&options.artifacts, &input).ok();
+            report.crashes.push(Crash {
+                message,
+                input,
+                path,
+            });
+        }
+    });
+
+    report.elapsed = start.elapsed();
+    report
+}
+
+/// Shrinks `input` by deleting ever smaller chunks while `still_fails` holds.
+pub fn minimize(input: &[u8], mut still_fails: impl FnMut(&[u8]) -> bool) -> Vec<u8> {
+    let mut current = input.to_vec();
+    let mut chunk = current.len().div_ceil(2).max(1);
+
+    loop {
+        let mut reduced = false;
+        let mut start = 0;
+        while start < current.len() {
+            let end = (start + chunk).min(current.len());
+            let mut candidate = current[..start].to_vec();
+            candidate.extend_from_slice(&current[end..]);
+
+            if still_fails(&candidate) {
+                current = candidate;
+                reduced = true;
+            } else {
+                start += chunk;
+            }
+        }
+
+        if chunk == 1 && !reduced {
+            return current;
+        }
+        if !reduced {
+            chunk = chunk.div_ceil(2);
+        }
+    }
+}
+
+/// Minimizes a saved crashing input under the same failure, silencing panic output.
+pub fn minimize_file(path: &Path) -> std::io::Result<Option<Vec<u8>>> {
+    let input = fs::read(path)?;
+    Ok(with_silent_panics(|| {
+        let message = check_input(&input)?;
+        Some(minimize(&input, |candidate| {
+            check_input(candidate).as_ref() == Some(&message)
+        }))
+    }))
+}
+
+fn generate_input(rng: &mut Rng, seeds: &[Vec<u8>], max_len: usize) -> Vec<u8> {
+    let mut input = match rng.below(4) {
+        0 => random_bytes(rng, max_len),
+        1 => token_soup(rng, max_len),
+        2 if !seeds.is_empty() => seeds[rng.below(seeds.len())].clone(),
+        _ => grammar_program(rng).into_bytes(),
+    };
+
+    for _ in 0..rng.below(4) {
+        mutate(rng, &mut input);
+    }
+    input.truncate(max_len);
+    input
+}
+
+fn random_bytes(rng: &mut Rng, max_len: usize) -> Vec<u8> {
+    (0..rng.below(max_len.min(256) + 1))
+        .map(|_| rng.next() as u8)
+        .collect()
+}
+
+fn token_soup(rng: &mut Rng, max_len: usize) -> Vec<u8> {
+    let mut output = String::new();
+    while output.len() < max_len && rng.below(64) != 0 {
+        match rng.below(5) {
+            0 => output.push_str(KEYWORDS[rng.below(KEYWORDS.len())]),
+            1 => output.push_str(&identifier(rng)),
+            2 => output.push_str(&rng.next().to_string()),
+            _ => output.push_str(PUNCTUATION[rng.below(PUNCTUATION.len())]),
+        }
+        output.push(' ');
+    }
+    output.into_bytes()
+}
+
+fn mutate(rng: &mut Rng, input: &mut Vec<u8>) {
+    let position = rng.below(input.len() + 1);
+    match rng.below(3) {
+        0 if position < input.len() => {
+            input.remove(position);
+        }
+        1 => {
+            let fragment = PUNCTUATION[rng.below(PUNCTUATION.len())];
+            input.splice(position..position, fragment.bytes());
+        }
+        _ if !input.is_empty() => {
+            let start = rng.below(input.len());
+            let end = (start + rng.below(16)).min(input.len());
+            let chunk = input[start..end].to_vec();
+            input.splice(position..position, chunk);
+        }
+        _ => {}
+    }
+}
+
+/// Builds a mostly well-formed program so mutations reach deep parser states.
+fn grammar_program(rng: &mut Rng) -> String {
+    let mut output = String::new();
+    if rng.below(2) == 0 {
+        output.push_str("import math\nimport io\n");
+    }
+    for _ in 0..=rng.below(3) {
+        if rng.below(3) == 0 {
+            output.push_str(&format!(
+                "struct {} {{ x: int, y: {} }}\n",
+                identifier(rng),
+                type_name(rng)
+            ));
+        } else if rng.below(4) == 0 {
+            output.push_str(&format!(
+                "enum {} {{ A, B({}) }}\n",
+                identifier(rng),
+                type_name(rng)
+            ));
+        } else {
+            let method = rng.below(4) == 0;
+            if method {
+                output.push_str(&format!("impl {} {{\n", identifier(rng)));
+            }
+            output.push_str(&format!(
+                "fn {}({}a: int, b: {}) -> {} {{\n",
+                identifier(rng),
+                if method { "self, " } else { "" },
+                type_name(rng),
+                type_name(rng)
+            ));
+            for _ in 0..rng.below(5) {
+                output.push_str(&statement(rng, 2));
+            }
+            output.push_str("}\n");
+            if method {
+                output.push_str("}\n");
+            }
+        }
+    }
+    output
+}
+
+fn statement(rng: &mut Rng, depth: usize) -> String {
+    match rng.below(if depth == 0 { 3 } else { 7 }) {
+        0 => format!(
+            "let {}: {} = {}\n",
+            identifier(rng),
+            type_name(rng),
+            expression(rng, 3)
+        ),
+        1 => format!("{} = {}\n", identifier(rng), expression(rng, 3)),
+        2 => format!("return {}\n", expression(rng, 3)),
+        3 => format!(
+            "if {} {{\n{}}} else {{\n{}}}\n",
+            expression(rng, 2),
+            statement(rng, depth - 1),
+            statement(rng, depth - 1)
+        ),
+        4 => format!(
+            "for x in {} {{\n{}}}\n",
+            expression(rng, 2),
+            statement(rng, depth - 1)
+        ),
+        5 => format!(
+            "while {} {{\n{}}}\n",
+            expression(rng, 2),
+            statement(rng, depth - 1)
+        ),
+        _ => format!(
+            "try {{\n{}}} catch Error {{\n{}}}\n",
+            statement(rng, depth - 1),
+            statement(rng, depth - 1)
+        ),
+    }
+}
+
+fn expression(rng: &mut Rng, depth: usize) -> String {
+    let leaf = depth == 0 || rng.below(3) == 0;
+    if leaf {
+        return match rng.below(6) {
+            0 => rng.below(1000).to_string(),
+            1 => format!("{}.5", rng.below(100)),
+            2 => "\"text\"".to_string(),
+            3 => "true".to_string(),
+            4 => "none".to_string(),
+            _ => identifier(rng),
+        };
+    }
+
+    match rng.below(7) {
+        0 => format!("-{}", expression(rng, depth - 1)),
+        1 => format!("{}({})", identifier(rng), expression(rng, depth - 1)),
+        2 => format!(
+            "[{}, {}]",
+            expression(rng, depth - 1),
+            expression(rng, depth - 1)
+        ),
+        3 => format!("({})", expression(rng, depth - 1)),
+        4 => format!(
+            "match {} {{\n1 => {}\n_ => {}\n}}",
+            expression(rng, depth - 1),
+            expression(rng, depth - 1),
+            expression(rng, depth - 1)
+        ),
+        5 => format!(
+            "{{{}: {}}}[{}]",
+            expression(rng, depth - 1),
+            expression(rng, depth - 1),
+            expression(rng, depth - 1)
+        ),
+        _ => {
+            let operator = ["+", "-", "*", "/", "==", "<", "&&", "||", "??"][rng.below(9)];
+            format!(
+                "{} {} {}",
+                expression(rng, depth - 1),
+                operator,
+                expression(rng, depth - 1)
+            )
+        }
+    }
+}
+
+fn identifier(rng: &mut Rng) -> String {
+    ["a", "b", "x", "total", "Point", "io", "math", "_tmp"][rng.below(8)].to_string()
+}
+
+fn type_name(rng: &mut Rng) -> &'static str {
+    [
+        "int",
+        "float",
+        "str",
+        "bool",
+        "list[int]",
+        "map[str, int]",
+        "Point",
+        "void",
+    ][rng.below(8)]
+}
+
+fn load_corpus(dir: &Path) -> Vec<Vec<u8>> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| utils::is_rustic_source(path))
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    paths
+        .iter()
+        .filter_map(|path| fs::read(path).ok())
+        .collect()
+}
+
+fn save_artifact(dir: &Path, input: &[u8]) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let mut hasher = DefaultHasher::new();
+    input.hash(&mut hasher);
+    let path = dir.join(format!("crash-{:016x}.rsc", hasher.finish()));
+    fs::write(&path, input)?;
+    Ok(path)
+}
+
+fn with_silent_panics<T>(f: impl FnOnce() -> T) -> T {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = f();
+    panic::set_hook(previous);
+    result
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        format!("panic: {}", message)
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        format!("panic: {}", message)
+    } else {
+        "panic with non-string payload".to_string()
+    }
+}
+
+/// xorshift64*: deterministic for a given seed so failures can be replayed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        if bound == 0 {
+            0
+        } else {
+            (self.next() % bound as u64) as usize
+        }
+    }
+}
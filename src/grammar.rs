@@ -0,0 +1,132 @@
+//! Generates an editor grammar (TextMate, as used by VS Code, Sublime Text and
+//! most highlighters) from the lexer's own keyword, symbol and escape tables,
+//! so editor highlighting follows the language as it changes.
+//!
+//! Each table entry is classified by running it through the lexer, using the
+//! same classes as `rustic highlight`.
+
+use crate::compiler::lexer::{Lexer, ESCAPES, KEYWORDS, SYMBOLS};
+use crate::highlight::{self, Class};
+use crate::utils::SOURCE_EXTENSION;
+use serde_json::{json, Value};
+
+pub const SCOPE_NAME: &str = "source.rustic";
+
+pub fn textmate() -> Value {
+    let words = |class| alternation(&lexemes(KEYWORDS, class), true);
+    let symbols = |class| alternation(&lexemes(SYMBOLS, class), false);
+    let escapes: String = ESCAPES
+        .iter()
+        .map(|(escape, _)| regex_escape(&escape.to_string()))
+        .collect();
+
+    json!({
+        "$schema": "https://raw.githubusercontent.com/martinring/tmlanguage/master/tmlanguage.json",
+        "name": "Rustic",
+        "scopeName": SCOPE_NAME,
+        "fileTypes": [SOURCE_EXTENSION],
+        "patterns": [
+            { "include": "#comments" },
+            { "include": "#strings" },
+            { "include": "#numbers" },
+            { "include": "#keywords" },
+            { "include": "#functions" },
+            { "include": "#types" },
+            { "include": "#operators" },
+            { "include": "#punctuation" },
+        ],
+        "repository": {
+            "comments": {
+                "patterns": [
+                    { "name": "comment.line.double-slash.rustic", "match": "//.*$" },
+                    { "include": "#block-comments" },
+                ],
+            },
+            "block-comments": {
+                "name": "comment.block.rustic",
+                "begin": "/\\*",
+                "end": "\\*/",
+                "patterns": [{ "include": "#block-comments" }],
+            },
+            "strings": {
+                "name": "string.quoted.double.rustic",
+                "begin": "\"",
+                "end": "\"",
+                "patterns": [
+                    { "name": "constant.character.escape.rustic", "match": format!("\\\\[{}]", escapes) },
+                    { "name": "invalid.illegal.escape.rustic", "match": "\\\\." },
+                ],
+            },
+            "numbers": {
+                "patterns": [
+                    { "name": "constant.numeric.float.rustic", "match": "\\b[0-9][0-9_]*(\\.[0-9][0-9_]*([eE][+-]?[0-9][0-9_]*)?|[eE][+-]?[0-9][0-9_]*)\\b" },
+                    { "name": "constant.numeric.integer.rustic", "match": "\\b(0x[0-9a-fA-F_]+|0o[0-7_]+|0b[01_]+|[0-9][0-9_]*)\\b" },
+                ],
+            },
+            "keywords": {
+                "patterns": [
+                    { "name": "keyword.control.rustic", "match": words(Class::Keyword) },
+                    { "name": "storage.type.rustic", "match": words(Class::Type) },
+                    { "name": "constant.language.boolean.rustic", "match": words(Class::Boolean) },
+                ],
+            },
+            "functions": {
+                "name": "entity.name.function.rustic",
+                "match": "\\b[A-Za-z_][A-Za-z0-9_]*(?=\\s*\\()",
+            },
+            "types": {
+                "name": "entity.name.type.rustic",
+                "match": "\\b[A-Z][A-Za-z0-9_]*\\b",
+            },
+            "operators": {
+                "name": "keyword.operator.rustic",
+                "match": symbols(Class::Operator),
+            },
+            "punctuation": {
+                "name": "punctuation.separator.rustic",
+                "match": symbols(Class::Punctuation),
+            },
+        },
+    })
+}
+
+/// The entries of `table` the lexer reads as a single token of `class`.
+fn lexemes(table: &[&'static str], class: Class) -> Vec<&'static str> {
+    table
+        .iter()
+        .copied()
+        .filter(|lexeme| {
+            let Ok(tokens) = Lexer::new(lexeme, "<grammar>").tokenize() else {
+                return false;
+            };
+            matches!(tokens.as_slice(), [token, _eof] if highlight::classify(&token.token_type, None) == class)
+        })
+        .collect()
+}
+
+/// A regex matching any of `lexemes`, longest first so `==` wins over `=`.
+fn alternation(lexemes: &[&str], words: bool) -> String {
+    let mut lexemes = lexemes.to_vec();
+    lexemes.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    let body = lexemes
+        .iter()
+        .map(|lexeme| regex_escape(lexeme))
+        .collect::<Vec<_>>()
+        .join("|");
+    if words {
+        format!("\\b(?:{})\\b", body)
+    } else {
+        format!("(?:{})", body)
+    }
+}
+
+fn regex_escape(text: &str) -> String {
+    let mut output = String::new();
+    for c in text.chars() {
+        if "\\^$.|?*+()[]{}/".contains(c) {
+            output.push('\\');
+        }
+        output.push(c);
+    }
+    output
+}
@@ -0,0 +1,224 @@
+//! Syntax highlighting driven by the real lexer, rendered as standalone HTML
+//! (with an anchor per line) or as ANSI-colored terminal text.
+//!
+//! Text between tokens (whitespace and comments) is taken verbatim from the
+//! source, so the output always reproduces the input exactly. Input the lexer
+//! rejects is marked as an error rather than stopping the highlighting.
+
+use crate::compiler::lexer::{Lexer, TokenType};
+use crate::diagnostics::DiagnosticEngine;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Ansi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Keyword,
+    Type,
+    Number,
+    String,
+    Boolean,
+    Function,
+    Identifier,
+    Operator,
+    Punctuation,
+    Comment,
+    Error,
+    Plain,
+}
+
+impl Class {
+    fn css(self) -> &'static str {
+        match self {
+            Class::Keyword => "kw",
+            Class::Type => "ty",
+            Class::Number => "num",
+            Class::String => "str",
+            Class::Boolean => "bool",
+            Class::Function => "fn",
+            Class::Identifier => "id",
+            Class::Operator => "op",
+            Class::Punctuation => "punct",
+            Class::Comment => "comment",
+            Class::Error => "err",
+            Class::Plain => "",
+        }
+    }
+
+    fn ansi(self) -> &'static str {
+        match self {
+            Class::Keyword => "\x1b[35m",
+            Class::Type => "\x1b[33m",
+            Class::Number | Class::Boolean => "\x1b[36m",
+            Class::String => "\x1b[32m",
+            Class::Function => "\x1b[34m",
+            Class::Comment => "\x1b[90m",
+            Class::Error => "\x1b[4;31m",
+            Class::Identifier | Class::Operator | Class::Punctuation | Class::Plain => "",
+        }
+    }
+}
+
+const STYLE: &str = "body { margin: 0; background: #fafafa; color: #24292e; }
+pre { margin: 0; padding: 1em 0; font: 13px/1.5 ui-monospace, monospace; }
+.line { display: block; padding-right: 1em; }
+.line:target { background: #fff5b1; }
+.ln { display: inline-block; width: 4em; padding-right: 1em; text-align: right; color: #959da5; text-decoration: none; user-select: none; }
+.kw { color: #d73a49; font-weight: bold; }
+.ty { color: #6f42c1; }
+.num, .bool { color: #005cc5; }
+.str { color: #032f62; }
+.fn { color: #6f42c1; }
+.comment { color: #6a737d; font-style: italic; }
+.err { color: #b31d28; text-decoration: underline wavy #b31d28; }";
+
+/// Splits `source` into classified runs that concatenate back to `source`.
+pub fn segments(source: &str, file_path: &str) -> Vec<(Class, String)> {
+    let tokens = Lexer::new(source, file_path).tokenize_reporting(&mut DiagnosticEngine::new());
+
+    let mut segments = Vec::new();
+    let mut position = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        let range = token.span.range();
+        if range.start > position {
+            push_gap(&mut segments, &source[position..range.start]);
+        }
+        if range.end > range.start {
+            let next = tokens.get(i + 1).map(|t| &t.token_type);
+            segments.push((
+                classify(&token.token_type, next),
+                source[range.clone()].to_string(),
+            ));
+        }
+        position = position.max(range.end);
+    }
+    if position < source.len() {
+        push_gap(&mut segments, &source[position..]);
+    }
+
+    segments
+}
+
+pub fn highlight(source: &str, file_path: &str, format: Format) -> String {
+    let segments = segments(source, file_path);
+    match format {
+        Format::Html => render_html(&segments, file_path),
+        Format::Ansi => render_ansi(&segments),
+    }
+}
+
+pub(crate) fn classify(token: &TokenType, next: Option<&TokenType>) -> Class {
+    use TokenType::*;
+
+    match token {
+        Integer(_) | Float(_) => Class::Number,
+        String(_) | InterpolatedString(_) => Class::String,
+        DocComment(_) => Class::Comment,
+        Boolean(_) => Class::Boolean,
+        Identifier(_) if next == Some(&LeftParen) => Class::Function,
+        Identifier(name) if name.starts_with(char::is_uppercase) => Class::Type,
+        Identifier(_) => Class::Identifier,
+        Let | Var | Fn | If | Else | For | While | Break | Continue | In | Match | Try | Catch
+        | Finally | Return | Import | Pub | Struct | Enum | Impl | SelfValue | Throw
+        | Exception | Const | NoneValue | As => Class::Keyword,
+        IntType | FloatType | StrType | BoolType | ListType | MapType | VoidType => Class::Type,
+        Plus | Minus | Star | StarStar | Slash | Percent | Equal | NotEqual | Less | LessEqual
+        | Greater | GreaterEqual | And | Or | Pipe | Ampersand | Caret | Tilde | ShiftLeft
+        | ShiftRight | Not | Question | Coalesce | Assign | PlusAssign | MinusAssign
+        | StarAssign | SlashAssign | PercentAssign | Arrow | FatArrow | DotDot | DotDotEqual => {
+            Class::Operator
+        }
+        LeftParen | RightParen | LeftBrace | RightBrace | LeftBracket | RightBracket | Comma
+        | Dot | Colon | Semicolon | At => Class::Punctuation,
+        Error(_) => Class::Error,
+        Newline | Eof => Class::Plain,
+    }
+}
+
+/// Skipped text is whitespace, possibly followed by comments. Only comments
+/// contain a `/`, and everything from the first one on is taken as comment.
+fn push_gap(segments: &mut Vec<(Class, String)>, gap: &str) {
+    match gap.find('/') {
+        Some(index) => {
+            if index > 0 {
+                segments.push((Class::Plain, gap[..index].to_string()));
+            }
+            segments.push((Class::Comment, gap[index..].to_string()));
+        }
+        None => segments.push((Class::Plain, gap.to_string())),
+    }
+}
+
+fn render_html(segments: &[(Class, String)], title: &str) -> String {
+    let mut output = String::new();
+    writeln!(output, "<!DOCTYPE html>").unwrap();
+    writeln!(output, "<html>\n<head>\n<meta charset=\"utf-8\">").unwrap();
+    writeln!(output, "<title>{}</title>", escape_html(title)).unwrap();
+    writeln!(output, "<style>\n{}\n</style>\n</head>\n<body>", STYLE).unwrap();
+    output.push_str("<pre><code>");
+
+    // A trailing newline ends the last line rather than starting an empty one.
+    let last = segments.len().saturating_sub(1);
+    let mut line = 1;
+    open_line(&mut output, line);
+    for (index, (class, text)) in segments.iter().enumerate() {
+        let text = if index == last {
+            text.strip_suffix('\n').unwrap_or(text)
+        } else {
+            text
+        };
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                output.push_str("</span>\n");
+                line += 1;
+                open_line(&mut output, line);
+            }
+            if part.is_empty() {
+                continue;
+            }
+            match class {
+                Class::Plain => output.push_str(&escape_html(part)),
+                _ => write!(
+                    output,
+                    "<span class=\"{}\">{}</span>",
+                    class.css(),
+                    escape_html(part)
+                )
+                .unwrap(),
+            }
+        }
+    }
+    output.push_str("</span></code></pre>\n</body>\n</html>\n");
+    output
+}
+
+fn open_line(output: &mut String, line: usize) {
+    write!(
+        output,
+        "<span class=\"line\" id=\"L{0}\"><a class=\"ln\" href=\"#L{0}\">{0}</a>",
+        line
+    )
+    .unwrap();
+}
+
+fn render_ansi(segments: &[(Class, String)]) -> String {
+    let mut output = String::new();
+    for (class, text) in segments {
+        match class.ansi() {
+            "" => output.push_str(text),
+            color => write!(output, "{}{}\x1b[0m", color, text).unwrap(),
+        }
+    }
+    output
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
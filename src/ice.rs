@@ -0,0 +1,204 @@
+//! Internal compiler errors. `install_hook` replaces Rust's panic message with
+//! a report of what the compiler was doing when it panicked, and writes a bug
+//! report with the details to attach to an issue.
+//!
+//! The pipeline records its progress with `enter` and `set_span`; both are
+//! cheap, so they are called unconditionally. Phases entered also show up in
+//! `--self-profile` traces.
+
+use crate::diagnostics::Span;
+use crate::profile::{self, TimingGuard};
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A stage of compiling one module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Lexing,
+    Parsing,
+    SemanticAnalysis,
+    CodeGeneration,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Phase::Lexing => "lexing",
+            Phase::Parsing => "parsing",
+            Phase::SemanticAnalysis => "semantic analysis",
+            Phase::CodeGeneration => "code generation",
+        })
+    }
+}
+
+/// Where the compiler is, as far as it has said.
+#[derive(Debug, Clone, Default)]
+struct Context {
+    phase: Option<Phase>,
+    file: Option<String>,
+    span: Option<Span>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::default());
+}
+
+/// Restores the context from before `enter` when dropped.
+#[must_use = "the phase ends when the guard is dropped"]
+pub struct PhaseGuard {
+    previous: Context,
+    _timing: TimingGuard,
+}
+
+impl Drop for PhaseGuard {
+    fn drop(&mut self) {
+        let previous = std::mem::take(&mut self.previous);
+        CONTEXT.with(|context| *context.borrow_mut() = previous);
+    }
+}
+
+/// Records that `phase` of compiling `file` has started, until the returned
+/// guard is dropped.
+pub fn enter(phase: Phase, file: &str) -> PhaseGuard {
+    let context = Context {
+        phase: Some(phase),
+        file: Some(file.to_string()),
+        span: None,
+    };
+    let previous = CONTEXT.with(|current| current.replace(context));
+    let timing = profile::start("phase", &phase.to_string()).arg("file", file);
+    PhaseGuard {
+        previous,
+        _timing: timing,
+    }
+}
+
+/// Records the code the current phase is working on.
+pub fn set_span(span: &Span) {
+    CONTEXT.with(|context| context.borrow_mut().span = Some(span.clone()));
+}
+
+/// Reports panics as internal compiler errors from now on. Output to a
+/// closed pipe, as in `rustic introspect | head`, is not one: the compiler
+/// exits quietly, with the status of a process killed by `SIGPIPE`.
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info| {
+        let report = Report::capture(info);
+        if report.message.starts_with("failed printing to std")
+            && report.message.contains("Broken pipe")
+        {
+            process::exit(141);
+        }
+        eprintln!("{}", report.summary());
+        match report.write() {
+            Ok(path) => eprintln!(
+                "  = note: a bug report with the details was written to {}\n  \
+                 = help: please attach it to a new issue on the Rustic issue tracker",
+                path.display()
+            ),
+            Err(error) => eprintln!(
+                "  = note: could not write a bug report ({}); the details follow\n\n{}",
+                error,
+                report.details()
+            ),
+        }
+    }));
+}
+
+struct Report {
+    message: String,
+    location: Option<String>,
+    context: Context,
+    backtrace: Backtrace,
+}
+
+impl Report {
+    fn capture(info: &PanicHookInfo<'_>) -> Self {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Self {
+            message,
+            location: info.location().map(|location| location.to_string()),
+            // A panic while the context is borrowed must not panic again.
+            context: CONTEXT
+                .with(|context| context.try_borrow().map(|c| c.clone()))
+                .unwrap_or_default(),
+            backtrace: Backtrace::force_capture(),
+        }
+    }
+
+    /// What is printed to stderr, in the layout of other diagnostics.
+    fn summary(&self) -> String {
+        let mut summary = format!("error: internal compiler error: {}", self.message);
+        if let Some(span) = &self.context.span {
+            let _ = write!(summary, "\n --> {}", span);
+        }
+        summary.push_str("\n  = note: the compiler unexpectedly panicked; this is a bug");
+        let _ = write!(summary, "\n  = note: {}", self.doing());
+        if let Some(location) = &self.location {
+            let _ = write!(summary, "\n  = note: panicked at {}", location);
+        }
+        summary
+    }
+
+    fn doing(&self) -> String {
+        let version = env!("CARGO_PKG_VERSION");
+        match (&self.context.phase, &self.context.file) {
+            (Some(phase), Some(file)) => {
+                format!("rustic {}, during {} of `{}`", version, phase, file)
+            }
+            _ => format!("rustic {}, outside of compiling a file", version),
+        }
+    }
+
+    /// The bug report, as Markdown ready to paste into an issue.
+    fn details(&self) -> String {
+        let unknown = || "unknown".to_string();
+        format!(
+            "## Internal compiler error\n\n\
+             **Message:** {}\n\n\
+             | | |\n|---|---|\n\
+             | Version | rustic {} |\n\
+             | Phase | {} |\n\
+             | File | {} |\n\
+             | Span | {} |\n\
+             | Panicked at | {} |\n\n\
+             ## Steps to reproduce\n\n\
+             <!-- The command you ran and, if you can share it, the file being compiled. -->\n\n\
+             ## Backtrace\n\n```\n{}\n```\n",
+            self.message,
+            env!("CARGO_PKG_VERSION"),
+            self.context
+                .phase
+                .map_or_else(unknown, |phase| phase.to_string()),
+            self.context.file.clone().unwrap_or_else(unknown),
+            self.context
+                .span
+                .as_ref()
+                .map_or_else(unknown, Span::to_string),
+            self.location.clone().unwrap_or_else(unknown),
+            self.backtrace
+        )
+    }
+
+    /// Writes the bug report to the temporary directory and returns its path.
+    fn write(&self) -> std::io::Result<PathBuf> {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let path =
+            std::env::temp_dir().join(format!("rustic-ice-{}-{}.md", seconds, std::process::id()));
+        fs::write(&path, self.details())?;
+        Ok(path)
+    }
+}
@@ -0,0 +1,109 @@
+//! Machine-readable description of the language surface this build supports,
+//! for `rustic introspect`.
+
+use crate::compiler::ast::{Type, AST_SCHEMA_VERSION};
+use crate::compiler::builtins::{self, Builtin, ParamKind, MODULES};
+use crate::compiler::intern::Symbol;
+use crate::compiler::lexer::{keyword, TokenType, KEYWORDS, SYMBOLS};
+use crate::diagnostics::{codes, Level};
+use crate::utils::SOURCE_EXTENSION;
+use serde_json::{json, Value};
+
+pub fn describe() -> Value {
+    let (types, keywords): (Vec<&str>, Vec<&str>) =
+        KEYWORDS.iter().partition(|word| is_type_keyword(word));
+
+    let modules: Vec<Value> = MODULES
+        .iter()
+        .map(|module| {
+            json!({
+                "name": module,
+                "functions": builtins::functions(module).iter().map(signature).collect::<Vec<_>>(),
+                "constants": builtins::constants(module)
+                    .iter()
+                    .map(|(name, ty, _)| json!({ "name": name, "type": ty.to_string() }))
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    // Methods on `list[T]` and `map[K, V]` are described with placeholder types.
+    let placeholder = |name| Box::new(Type::Param(Symbol::intern(name)));
+    let receivers = [
+        Type::List(placeholder("T")),
+        Type::Map(placeholder("K"), placeholder("V")),
+        Type::Str,
+    ];
+    let methods: Vec<Value> = receivers
+        .iter()
+        .map(|receiver| {
+            json!({
+                "receiver": receiver.to_string(),
+                "methods": builtins::methods(receiver).iter().map(signature).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    let levels: Vec<String> = [Level::Error, Level::Warning, Level::Note]
+        .iter()
+        .map(Level::to_string)
+        .collect();
+    let codes: Vec<Value> = codes::ALL
+        .iter()
+        .map(|code| {
+            json!({
+                "code": code.code,
+                "name": code.name.to_lowercase().replace('_', "-"),
+                "summary": code.summary.trim(),
+            })
+        })
+        .collect();
+
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "ast_schema_version": AST_SCHEMA_VERSION,
+        "file_extension": SOURCE_EXTENSION,
+        "keywords": keywords,
+        "builtin_types": types,
+        "symbols": SYMBOLS,
+        "modules": modules,
+        "methods": methods,
+        "diagnostics": {
+            "levels": levels,
+            "codes": codes,
+        },
+    })
+}
+
+fn is_type_keyword(word: &str) -> bool {
+    matches!(
+        keyword(word),
+        Some(
+            TokenType::IntType
+                | TokenType::FloatType
+                | TokenType::StrType
+                | TokenType::BoolType
+                | TokenType::ListType
+                | TokenType::MapType
+                | TokenType::VoidType
+        )
+    )
+}
+
+fn signature(builtin: &Builtin) -> Value {
+    let params: Vec<String> = builtin
+        .params
+        .iter()
+        .map(|param| match param {
+            ParamKind::Exact(ty) => ty.to_string(),
+            ParamKind::Printable => "any".to_string(),
+        })
+        .collect();
+
+    json!({
+        "name": builtin.name,
+        "params": params,
+        "return_type": builtin.return_type.to_string(),
+        "mutates_receiver": builtin.mutates_receiver,
+    })
+}
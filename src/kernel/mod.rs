@@ -0,0 +1,594 @@
+//! Jupyter kernel: `rustic kernel install` registers a kernelspec, and the
+//! notebook server then starts `rustic kernel --connection-file FILE`, which
+//! speaks the Jupyter messaging protocol (5.3) over the sockets listed there.
+
+mod session;
+mod zmtp;
+
+pub use session::{is_complete, CellError, CellOutput, Running, Session};
+
+use hmac_sha256::HMAC;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zmtp::{Connection, Sender, SocketType};
+
+const PROTOCOL_VERSION: &str = "5.3";
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+/// How often a running cell stops waiting to look for interrupt requests.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+/// How long to wait before accepting connections again after failing to.
+const ACCEPT_RETRY: Duration = Duration::from_millis(100);
+
+/// The connection file Jupyter writes for each kernel it starts.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConnectionInfo {
+    pub transport: String,
+    pub ip: String,
+    pub shell_port: u16,
+    pub iopub_port: u16,
+    pub stdin_port: u16,
+    pub control_port: u16,
+    pub hb_port: u16,
+    pub key: String,
+    pub signature_scheme: String,
+}
+
+impl ConnectionInfo {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// `jupyter --data-dir`'s per-user location for kernelspecs.
+pub fn default_kernels_dir() -> PathBuf {
+    let home = || env::var_os("HOME").map(PathBuf::from).unwrap_or_default();
+    if cfg!(windows) {
+        env::var_os("APPDATA")
+            .map(PathBuf::from)
+            .unwrap_or_else(home)
+            .join("jupyter")
+            .join("kernels")
+    } else if cfg!(target_os = "macos") {
+        home().join("Library/Jupyter/kernels")
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home().join(".local/share"))
+            .join("jupyter")
+            .join("kernels")
+    }
+}
+
+/// Writes a kernelspec pointing at the running executable; returns its directory.
+pub fn install(kernels_dir: &Path) -> io::Result<PathBuf> {
+    let executable = env::current_exe()?;
+    let spec = json!({
+        "argv": [executable, "kernel", "--connection-file", "{connection_file}"],
+        "display_name": "Rustic",
+        "language": "rustic",
+    });
+
+    let dir = kernels_dir.join("rustic");
+    fs::create_dir_all(&dir)?;
+    fs::write(
+        dir.join("kernel.json"),
+        serde_json::to_string_pretty(&spec).expect("kernelspec serializes"),
+    )?;
+    Ok(dir)
+}
+
+enum Channel {
+    Shell,
+    Control,
+}
+
+struct Request {
+    channel: Channel,
+    reply: Sender,
+    message: Message,
+}
+
+struct Message {
+    identities: Vec<Vec<u8>>,
+    header: Value,
+    content: Value,
+}
+
+type Incoming = Receiver<(Channel, Sender, Vec<Vec<u8>>)>;
+
+struct Kernel {
+    key: Vec<u8>,
+    session_id: String,
+    iopub: Arc<Mutex<Vec<Sender>>>,
+    session: Session,
+    execution_count: u64,
+    incoming: Incoming,
+    /// Requests that arrived while a cell ran, to handle once it is done.
+    deferred: VecDeque<Request>,
+}
+
+/// Serves the kernel until a `shutdown_request` arrives.
+pub fn run(info: &ConnectionInfo) -> io::Result<()> {
+    if info.transport != "tcp" {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("unsupported transport `{}`", info.transport),
+        ));
+    }
+    if !info.key.is_empty() && info.signature_scheme != "hmac-sha256" {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("unsupported signature scheme `{}`", info.signature_scheme),
+        ));
+    }
+
+    let bind = |port: u16| TcpListener::bind((info.ip.as_str(), port));
+    let (requests, incoming) = mpsc::channel();
+    let iopub = Arc::new(Mutex::new(Vec::new()));
+
+    serve_router(bind(info.shell_port)?, requests.clone(), || Channel::Shell);
+    serve_router(bind(info.control_port)?, requests, || Channel::Control);
+    // stdin is only used for input requests, which cells never make.
+    serve_router(bind(info.stdin_port)?, mpsc::channel().0, || Channel::Shell);
+    serve_heartbeat(bind(info.hb_port)?);
+    serve_iopub(bind(info.iopub_port)?, iopub.clone());
+
+    let mut kernel = Kernel {
+        key: info.key.as_bytes().to_vec(),
+        session_id: new_id(),
+        iopub,
+        session: Session::new(),
+        execution_count: 0,
+        incoming,
+        deferred: VecDeque::new(),
+    };
+    kernel.serve()
+}
+
+/// Accepts peers on `listener` on a thread of its own, handshaking with and
+/// then serving each on another, so a slow peer holds up no other. When
+/// accepting fails, such as when out of file descriptors, it waits a little
+/// before trying again.
+fn accept(
+    listener: TcpListener,
+    socket_type: SocketType,
+    serve: impl Fn(Connection) + Send + Sync + 'static,
+) {
+    let serve = Arc::new(serve);
+    thread::spawn(move || loop {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(_) => {
+                thread::sleep(ACCEPT_RETRY);
+                continue;
+            }
+        };
+        let serve = Arc::clone(&serve);
+        thread::spawn(move || {
+            if let Ok(connection) = Connection::new(stream, socket_type) {
+                serve(connection);
+            }
+        });
+    });
+}
+
+fn serve_router(
+    listener: TcpListener,
+    requests: mpsc::Sender<(Channel, Sender, Vec<Vec<u8>>)>,
+    channel: fn() -> Channel,
+) {
+    accept(listener, SocketType::Router, move |mut connection| {
+        let reply = connection.sender();
+        while let Ok(parts) = connection.recv() {
+            if requests.send((channel(), reply.clone(), parts)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn serve_heartbeat(listener: TcpListener) {
+    accept(listener, SocketType::Rep, |mut connection| {
+        let reply = connection.sender();
+        while let Ok(parts) = connection.recv() {
+            if reply.send(&parts).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn serve_iopub(listener: TcpListener, subscribers: Arc<Mutex<Vec<Sender>>>) {
+    accept(listener, SocketType::Pub, move |mut connection| {
+        subscribers.lock().unwrap().push(connection.sender());
+        // Subscriptions are not filtered; draining them keeps the peer unblocked.
+        while connection.recv().is_ok() {}
+    });
+}
+
+impl Kernel {
+    fn serve(&mut self) -> io::Result<()> {
+        while let Some(request) = self.next_request() {
+            if !self.handle(&request)? {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    /// The first deferred request, or else the next to arrive with a valid
+    /// signature. `None` once no socket can send more.
+    fn next_request(&mut self) -> Option<Request> {
+        if let Some(request) = self.deferred.pop_front() {
+            return Some(request);
+        }
+        loop {
+            let (channel, reply, parts) = self.incoming.recv().ok()?;
+            if let Some(message) = self.decode(parts) {
+                return Some(Request {
+                    channel,
+                    reply,
+                    message,
+                });
+            }
+        }
+    }
+
+    /// Stops `running` for each `interrupt_request` that has arrived, and
+    /// defers the other requests until the cell is done.
+    fn take_interrupts(&mut self, running: &Running) -> io::Result<()> {
+        while let Ok((channel, reply, parts)) = self.incoming.try_recv() {
+            let Some(message) = self.decode(parts) else {
+                continue;
+            };
+            let request = Request {
+                channel,
+                reply,
+                message,
+            };
+            if request.message.header["msg_type"] != "interrupt_request" {
+                self.deferred.push_back(request);
+                continue;
+            }
+            running.interrupt();
+            self.reply(&request, "interrupt_reply", json!({ "status": "ok" }))?;
+        }
+        Ok(())
+    }
+
+    /// Returns `false` once the kernel should exit.
+    fn handle(&mut self, request: &Request) -> io::Result<bool> {
+        let msg_type = request.message.header["msg_type"]
+            .as_str()
+            .unwrap_or_default();
+        let parent = &request.message;
+        if let Channel::Shell = request.channel {
+            self.publish(parent, "status", json!({ "execution_state": "busy" }));
+        }
+
+        match msg_type {
+            "kernel_info_request" => self.reply(
+                request,
+                "kernel_info_reply",
+                json!({
+                    "status": "ok",
+                    "protocol_version": PROTOCOL_VERSION,
+                    "implementation": "rustic",
+                    "implementation_version": env!("CARGO_PKG_VERSION"),
+                    "language_info": {
+                        "name": "rustic",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "mimetype": "text/x-rustic",
+                        "file_extension": format!(".{}", crate::utils::SOURCE_EXTENSION),
+                    },
+                    "banner": format!("Rustic {}", env!("CARGO_PKG_VERSION")),
+                }),
+            )?,
+            "execute_request" => self.execute(request)?,
+            // Nothing is running between cells.
+            "interrupt_request" => {
+                self.reply(request, "interrupt_reply", json!({ "status": "ok" }))?
+            }
+            "is_complete_request" => {
+                let code = parent.content["code"].as_str().unwrap_or_default();
+                let status = is_complete(code);
+                let mut content = json!({ "status": status });
+                if status == "incomplete" {
+                    content["indent"] = json!("    ");
+                }
+                self.reply(request, "is_complete_reply", content)?
+            }
+            "complete_request" => {
+                let code = parent.content["code"].as_str().unwrap_or_default();
+                let cursor = parent.content["cursor_pos"].as_u64().unwrap_or(0) as usize;
+                let before: String = code.chars().take(cursor).collect();
+                let start = before
+                    .char_indices()
+                    .rev()
+                    .take_while(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+                    .last()
+                    .map(|(index, _)| before[..index].chars().count())
+                    .unwrap_or(cursor);
+                let prefix: String = before.chars().skip(start).collect();
+                self.reply(
+                    request,
+                    "complete_reply",
+                    json!({
+                        "status": "ok",
+                        "matches": self.session.completions(&prefix),
+                        "cursor_start": start,
+                        "cursor_end": cursor,
+                        "metadata": {},
+                    }),
+                )?
+            }
+            "comm_info_request" => self.reply(
+                request,
+                "comm_info_reply",
+                json!({ "status": "ok", "comms": {} }),
+            )?,
+            "history_request" => self.reply(
+                request,
+                "history_reply",
+                json!({ "status": "ok", "history": [] }),
+            )?,
+            "shutdown_request" => {
+                let restart = parent.content["restart"].as_bool().unwrap_or(false);
+                self.reply(
+                    request,
+                    "shutdown_reply",
+                    json!({ "status": "ok", "restart": restart }),
+                )?;
+                return Ok(false);
+            }
+            _ => {}
+        }
+
+        if let Channel::Shell = request.channel {
+            self.publish(parent, "status", json!({ "execution_state": "idle" }));
+        }
+        Ok(true)
+    }
+
+    fn execute(&mut self, request: &Request) -> io::Result<()> {
+        let parent = &request.message;
+        let code = parent.content["code"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let silent = parent.content["silent"].as_bool().unwrap_or(false);
+        if !silent {
+            self.execution_count += 1;
+        }
+        let count = self.execution_count;
+        self.publish(
+            parent,
+            "execute_input",
+            json!({ "code": code, "execution_count": count }),
+        );
+
+        let result = match self.session.start(&code) {
+            Ok(Some(mut running)) => {
+                while !running.wait(POLL_INTERVAL) {
+                    self.take_interrupts(&running)?;
+                }
+                self.session.finish(running)
+            }
+            Ok(None) => Ok(CellOutput::default()),
+            Err(error) => Err(error),
+        };
+        let stdout = match &result {
+            Ok(output) => &output.stdout,
+            Err(error) => &error.stdout,
+        };
+        if !stdout.is_empty() && !silent {
+            self.publish(
+                parent,
+                "stream",
+                json!({ "name": "stdout", "text": stdout }),
+            );
+        }
+
+        match result {
+            Ok(output) => {
+                if let Some(value) = output.value.filter(|_| !silent) {
+                    self.publish(
+                        parent,
+                        "execute_result",
+                        json!({
+                            "execution_count": count,
+                            "data": { "text/plain": value },
+                            "metadata": {},
+                        }),
+                    );
+                }
+                self.reply(
+                    request,
+                    "execute_reply",
+                    json!({
+                        "status": "ok",
+                        "execution_count": count,
+                        "user_expressions": {},
+                        "payload": [],
+                    }),
+                )
+            }
+            Err(error) => {
+                let content = json!({
+                    "ename": error.name,
+                    "evalue": error.message,
+                    "traceback": error.traceback,
+                });
+                self.publish(parent, "error", content.clone());
+                let mut reply = content;
+                reply["status"] = json!("error");
+                reply["execution_count"] = json!(count);
+                self.reply(request, "execute_reply", reply)
+            }
+        }
+    }
+
+    fn reply(&self, request: &Request, msg_type: &str, content: Value) -> io::Result<()> {
+        let parts = self.encode(
+            request.message.identities.clone(),
+            msg_type,
+            &request.message,
+            content,
+        );
+        request.reply.send(&parts)
+    }
+
+    fn publish(&self, parent: &Message, msg_type: &str, content: Value) {
+        let topic = format!("kernel.{}.{}", self.session_id, msg_type).into_bytes();
+        let parts = self.encode(vec![topic], msg_type, parent, content);
+        self.iopub
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(&parts).is_ok());
+    }
+
+    fn encode(
+        &self,
+        identities: Vec<Vec<u8>>,
+        msg_type: &str,
+        parent: &Message,
+        content: Value,
+    ) -> Vec<Vec<u8>> {
+        let header = json!({
+            "msg_id": new_id(),
+            "session": self.session_id,
+            "username": "rustic",
+            "date": timestamp(),
+            "msg_type": msg_type,
+            "version": PROTOCOL_VERSION,
+        });
+        let frames = [
+            header.to_string().into_bytes(),
+            parent.header.to_string().into_bytes(),
+            b"{}".to_vec(),
+            content.to_string().into_bytes(),
+        ];
+
+        let mut parts = identities;
+        parts.push(DELIMITER.to_vec());
+        parts.push(self.sign(&frames).into_bytes());
+        parts.extend(frames);
+        parts
+    }
+
+    fn decode(&self, mut parts: Vec<Vec<u8>>) -> Option<Message> {
+        let delimiter = parts.iter().position(|part| part == DELIMITER)?;
+        let mut rest = parts.split_off(delimiter + 1);
+        parts.pop();
+        if rest.len() < 5 {
+            return None;
+        }
+        let frames: Vec<Vec<u8>> = rest.drain(1..5).collect();
+        if !verify(&self.key, &frames, &rest[0]) {
+            eprintln!("rustic kernel: dropping a message with a bad signature");
+            return None;
+        }
+
+        Some(Message {
+            identities: parts,
+            header: serde_json::from_slice(&frames[0]).ok()?,
+            content: serde_json::from_slice(&frames[3]).unwrap_or(Value::Null),
+        })
+    }
+
+    fn sign(&self, frames: &[Vec<u8>]) -> String {
+        sign(&self.key, frames)
+    }
+}
+
+/// The signature of a message whose header, parent header, metadata and
+/// content are `frames`: their HMAC-SHA256 under `key` in hex, or nothing
+/// when the connection file gives no key.
+pub fn sign(key: &[u8], frames: &[Vec<u8>]) -> String {
+    if key.is_empty() {
+        return String::new();
+    }
+    let mut mac = HMAC::new(key);
+    for frame in frames {
+        mac.update(frame);
+    }
+    mac.finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Whether `signature` is the one `sign` gives `frames` under `key`. Any
+/// signature goes without a key.
+pub fn verify(key: &[u8], frames: &[Vec<u8>], signature: &[u8]) -> bool {
+    key.is_empty() || constant_time_eq(signature, sign(key, frames).as_bytes())
+}
+
+/// Whether `a` and `b` are equal, taking as long whichever byte they differ
+/// at, so comparing signatures does not tell a sender how much of one it
+/// got right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn new_id() -> String {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let mut hasher = DefaultHasher::new();
+    SystemTime::now().hash(&mut hasher);
+    std::process::id().hash(&mut hasher);
+    COUNTER
+        .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        .hash(&mut hasher);
+    let high = hasher.finish();
+    high.hash(&mut hasher);
+    format!("{:016x}{:016x}", high, hasher.finish())
+}
+
+/// Current UTC time in ISO 8601, as message headers require.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = now.as_secs();
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil-from-days (Howard Hinnant's algorithm).
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        now.subsec_micros()
+    )
+}
@@ -0,0 +1,552 @@
+//! Cell execution with persistent state.
+//!
+//! Cells that start with an item, such as `fn`, `enum`, `impl` or
+//! `@deprecated`, or with `import` add top-level items; any other cell is a
+//! list of statements appended to an implicit `main`. A trailing
+//! expression's value is shown as the cell result.
+//!
+//! The whole session is checked as one program, but only the new cell runs:
+//! a thread of the session's own keeps the interpreter's variables and
+//! globals from one cell to the next, so earlier cells are not run again. A
+//! cell that fails leaves the variables it declared out and keeps what it
+//! changed in the others.
+
+use crate::compiler::ast::{Item, Program, Statement, Type};
+use crate::compiler::builtins;
+use crate::compiler::interpreter::{self, Interpreter, State};
+use crate::compiler::lexer::{Lexer, TokenType, KEYWORDS};
+use crate::compiler::parser::{self, Parser};
+use crate::compiler::semantic::{SemanticAnalyzer, TypeTable};
+use crate::diagnostics::{DiagnosticEngine, Error, Span};
+use std::cell::{OnceCell, RefCell};
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const FILE_NAME: &str = "<cell>";
+const VALUE_MARKER: &str = "__rustic_kernel_value__";
+
+#[derive(Debug, Clone, Default)]
+pub struct CellOutput {
+    pub stdout: String,
+    /// Display form of the cell's trailing expression, if it has a value.
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CellError {
+    pub name: String,
+    pub message: String,
+    /// Display lines, with ANSI colors, pointing into the cell.
+    pub traceback: Vec<String>,
+    /// Output the cell printed before failing.
+    pub stdout: String,
+}
+
+#[derive(Default)]
+pub struct Session {
+    items: Vec<String>,
+    statements: Vec<String>,
+    /// How many statements of the implicit `main` the cells so far ran.
+    statement_count: usize,
+    /// Started by the first statement cell.
+    worker: Option<Worker>,
+}
+
+/// A statement cell being run. `Session::finish` waits for it to end.
+pub struct Running {
+    done: Receiver<Ran>,
+    ran: Option<Ran>,
+    interrupt: Arc<AtomicBool>,
+    /// The session's statement cells and how many statements of `main`
+    /// they make, once this one has run.
+    statements: Vec<String>,
+    statement_count: usize,
+}
+
+/// The thread statement cells run on, which keeps the interpreter's state.
+struct Worker {
+    jobs: mpsc::Sender<Job>,
+    interrupt: Arc<AtomicBool>,
+}
+
+/// A session program to run from statement `start` of `main`, the first
+/// of the new cell.
+struct Job {
+    checked: Checked,
+    start: usize,
+    done: mpsc::Sender<Ran>,
+}
+
+/// What a cell printed and how it ended.
+struct Ran {
+    stdout: String,
+    result: crate::diagnostics::Result<()>,
+}
+
+/// The programs the worker has run, oldest first. Each is kept while the
+/// session lasts, as the variables of later cells can hold its functions
+/// and lambdas.
+struct Programs {
+    checked: Checked,
+    next: OnceCell<Box<Programs>>,
+}
+
+/// Collects what the interpreter prints for the worker to take afterwards.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+/// A candidate session program and where the new cell sits in it.
+struct Assembled {
+    source: String,
+    cell_start: usize,
+    cell_end: usize,
+}
+
+struct Checked {
+    program: Program,
+    types: TypeTable,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `code` as the next cell and waits for it to finish.
+    pub fn execute(&mut self, code: &str) -> Result<CellOutput, CellError> {
+        match self.start(code)? {
+            Some(running) => self.finish(running),
+            None => Ok(CellOutput::default()),
+        }
+    }
+
+    /// Checks `code` as the next cell and starts running it. Cells with
+    /// nothing to run, such as those that only add items, are done at once
+    /// and give `None`.
+    pub fn start(&mut self, code: &str) -> Result<Option<Running>, CellError> {
+        if code.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let mut items = self.items.clone();
+        let mut statements = self.statements.clone();
+        let is_item_cell = starts_with_item(code);
+        if is_item_cell {
+            items.push(code.to_string());
+        } else {
+            statements.push(code.to_string());
+        }
+        let assembled = assemble(&items, &statements, is_item_cell);
+        let checked = check(&assembled)?;
+
+        if is_item_cell {
+            self.items = items;
+            return Ok(None);
+        }
+
+        let statement_count = main_statements(&checked.program);
+        let source = with_value_markers(&assembled, &checked);
+        let checked = check(&Assembled {
+            source,
+            ..assembled
+        })?;
+
+        let worker = self.worker.get_or_insert_with(Worker::start);
+        worker.interrupt.store(false, Ordering::Relaxed);
+        let (done, finished) = mpsc::channel();
+        let job = Job {
+            checked,
+            start: self.statement_count,
+            done,
+        };
+        // A worker that stopped is reported by `finish`.
+        let _ = worker.jobs.send(job);
+        Ok(Some(Running {
+            done: finished,
+            ran: None,
+            interrupt: Arc::clone(&worker.interrupt),
+            statements,
+            statement_count,
+        }))
+    }
+
+    /// Waits for `running` to end and gives what it printed and its value,
+    /// or why it failed.
+    pub fn finish(&mut self, running: Running) -> Result<CellOutput, CellError> {
+        let Running {
+            done,
+            ran,
+            interrupt,
+            statements,
+            statement_count,
+        } = running;
+        let Some(Ran { stdout, result }) = ran.or_else(|| done.recv().ok()) else {
+            // Its variables went with it.
+            self.worker = None;
+            self.statements.clear();
+            self.statement_count = 0;
+            let message =
+                "the interpreter stopped unexpectedly; the variables of earlier cells are gone";
+            return Err(CellError {
+                name: "InternalError".to_string(),
+                traceback: vec![format!("\x1b[31merror\x1b[0m: {}", message)],
+                message: message.to_string(),
+                stdout: String::new(),
+            });
+        };
+
+        let (stdout, value) = split_value(&stdout);
+        if let Err(error) = result {
+            let message = match error {
+                Error::RuntimeError(message) => message,
+                other => other.to_string(),
+            };
+            let name = match interrupt.load(Ordering::Relaxed) {
+                true => "Interrupted",
+                false => "RuntimeError",
+            };
+            return Err(CellError {
+                name: name.to_string(),
+                traceback: vec![format!("\x1b[31merror\x1b[0m: {}", message)],
+                message,
+                stdout,
+            });
+        }
+
+        self.statements = statements;
+        self.statement_count = statement_count;
+        Ok(CellOutput { stdout, value })
+    }
+
+    /// Keywords, builtin modules and names used so far that start with `prefix`.
+    pub fn completions(&self, prefix: &str) -> Vec<String> {
+        let mut names: BTreeSet<String> = KEYWORDS
+            .iter()
+            .chain(builtins::MODULES)
+            .map(|name| name.to_string())
+            .collect();
+
+        let source = self.items.iter().chain(&self.statements);
+        for cell in source {
+            if let Ok(tokens) = Lexer::new(cell, FILE_NAME).tokenize() {
+                names.extend(
+                    tokens
+                        .into_iter()
+                        .filter_map(|token| match token.token_type {
+                            TokenType::Identifier(name) => Some(name.to_string()),
+                            _ => None,
+                        }),
+                );
+            }
+        }
+
+        names
+            .into_iter()
+            .filter(|name| name.starts_with(prefix) && name != prefix)
+            .collect()
+    }
+}
+
+impl Running {
+    /// Waits up to `timeout` for the cell to end, returning whether it has.
+    pub fn wait(&mut self, timeout: Duration) -> bool {
+        if self.ran.is_some() {
+            return true;
+        }
+        match self.done.recv_timeout(timeout) {
+            Ok(ran) => {
+                self.ran = Some(ran);
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        }
+    }
+
+    /// Stops the cell at its next statement or loop iteration.
+    pub fn interrupt(&self) {
+        self.interrupt.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Worker {
+    fn start() -> Self {
+        let (jobs, incoming) = mpsc::channel();
+        let interrupt = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&interrupt);
+        thread::Builder::new()
+            .name("interpreter".to_string())
+            .stack_size(interpreter::STACK_SIZE)
+            .spawn(move || work(incoming, flag))
+            .expect("failed to start the interpreter thread");
+        Self { jobs, interrupt }
+    }
+}
+
+/// Runs each job's cell in the state the ones before it left, until the
+/// session is dropped.
+fn work(jobs: Receiver<Job>, interrupt: Arc<AtomicBool>) {
+    let programs: OnceCell<Box<Programs>> = OnceCell::new();
+    let mut last = &programs;
+    let mut state = State::default();
+    for job in jobs {
+        let Job {
+            checked,
+            start,
+            done,
+        } = job;
+        let link = last.get_or_init(|| {
+            Box::new(Programs {
+                checked,
+                next: OnceCell::new(),
+            })
+        });
+        last = &link.next;
+
+        let output = Output::default();
+        let mut interpreter = Interpreter::new(&link.checked.program, &link.checked.types);
+        interpreter.set_output(output.clone());
+        interpreter.set_input(io::empty());
+        interpreter.set_interrupt(Arc::clone(&interrupt));
+        let (result, next) = interpreter.resume(start, std::mem::take(&mut state));
+        state = next;
+        drop(interpreter);
+
+        let stdout = String::from_utf8_lossy(&output.0.take()).into_owned();
+        let _ = done.send(Ran { stdout, result });
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// How many statements the implicit `main` of `program` has.
+fn main_statements(program: &Program) -> usize {
+    program
+        .items
+        .iter()
+        .find_map(|item| match item {
+            Item::Function(function) if function.name == "main" => {
+                Some(function.body.statements.len())
+            }
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+/// Jupyter's `is_complete` status for `code`: unclosed brackets or strings
+/// mean the frontend should keep reading lines.
+pub fn is_complete(code: &str) -> &'static str {
+    let tokens = match Lexer::new(code, FILE_NAME).tokenize() {
+        Ok(tokens) => tokens,
+        Err(Error::LexError(message)) if message.starts_with("Unterminated") => {
+            return "incomplete"
+        }
+        Err(_) => return "invalid",
+    };
+
+    let mut depth = 0i64;
+    for token in &tokens {
+        match token.token_type {
+            TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => depth += 1,
+            TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket => depth -= 1,
+            _ => {}
+        }
+    }
+    match depth {
+        0 => "complete",
+        d if d > 0 => "incomplete",
+        _ => "invalid",
+    }
+}
+
+fn starts_with_item(code: &str) -> bool {
+    let Ok(tokens) = Lexer::new(code, FILE_NAME).tokenize() else {
+        return false;
+    };
+    tokens
+        .iter()
+        .find(|token| token.token_type != TokenType::Newline)
+        .is_some_and(|token| parser::starts_item(&token.token_type))
+}
+
+fn assemble(items: &[String], statements: &[String], is_item_cell: bool) -> Assembled {
+    let mut source = String::new();
+    let mut cell_start = 0;
+    let mut cell_end = 0;
+    let line = |source: &str| source.matches('\n').count() + 1;
+
+    for (index, item) in items.iter().enumerate() {
+        if is_item_cell && index + 1 == items.len() {
+            cell_start = line(&source);
+        }
+        source.push_str(item);
+        source.push('\n');
+        if is_item_cell && index + 1 == items.len() {
+            cell_end = line(&source) - 1;
+        }
+    }
+
+    source.push_str("fn main() {\n");
+    for (index, statement) in statements.iter().enumerate() {
+        if !is_item_cell && index + 1 == statements.len() {
+            cell_start = line(&source);
+        }
+        source.push_str(statement);
+        source.push('\n');
+        if !is_item_cell && index + 1 == statements.len() {
+            cell_end = line(&source) - 1;
+        }
+    }
+    source.push_str("}\n");
+
+    Assembled {
+        source,
+        cell_start,
+        cell_end,
+    }
+}
+
+fn check(assembled: &Assembled) -> Result<Checked, CellError> {
+    let mut diagnostics = DiagnosticEngine::new();
+    let tokens = Lexer::new(&assembled.source, FILE_NAME).tokenize_reporting(&mut diagnostics);
+    let result = Parser::new(tokens, &mut diagnostics)
+        .parse()
+        .and_then(|program| {
+            let types = SemanticAnalyzer::new(&mut diagnostics).analyze(&program)?;
+            Ok(Checked { program, types })
+        });
+
+    result.map_err(|error| {
+        let spans: Vec<(String, Span)> = diagnostics
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| (diagnostic.message.clone(), diagnostic.span.clone()))
+            .collect();
+        compile_error(&error, &spans, assembled)
+    })
+}
+
+fn compile_error(
+    error: &Error,
+    diagnostics: &[(String, Span)],
+    assembled: &Assembled,
+) -> CellError {
+    let lines: Vec<&str> = assembled.source.lines().collect();
+    let mut traceback = Vec::new();
+
+    for (message, span) in diagnostics {
+        traceback.push(format!("\x1b[1;31merror\x1b[0m: {}", message));
+        if (assembled.cell_start..=assembled.cell_end).contains(&span.start_line) {
+            let line = span.start_line - assembled.cell_start + 1;
+            let text = lines.get(span.start_line - 1).copied().unwrap_or_default();
+            let width = if span.end_line == span.start_line {
+                span.end_column.saturating_sub(span.start_column).max(1)
+            } else {
+                text.chars()
+                    .count()
+                    .saturating_sub(span.start_column - 1)
+                    .max(1)
+            };
+            traceback.push(format!(
+                "  \x1b[34m-->\x1b[0m line {}:{}",
+                line, span.start_column
+            ));
+            traceback.push(format!("\x1b[34m{:>4} |\x1b[0m {}", line, text));
+            traceback.push(format!(
+                "\x1b[34m     |\x1b[0m {}\x1b[1;31m{}\x1b[0m",
+                " ".repeat(span.start_column.saturating_sub(1)),
+                "^".repeat(width)
+            ));
+        }
+    }
+    if traceback.is_empty() {
+        traceback.push(format!("\x1b[1;31merror\x1b[0m: {}", error));
+    }
+
+    CellError {
+        name: "CompileError".to_string(),
+        message: diagnostics
+            .first()
+            .map(|(message, _)| message.clone())
+            .unwrap_or_else(|| error.to_string()),
+        traceback,
+        stdout: String::new(),
+    }
+}
+
+/// Rewrites a trailing value-producing expression in the new cell so the
+/// program prints it between marker lines.
+fn with_value_markers(assembled: &Assembled, checked: &Checked) -> String {
+    let main = checked
+        .program
+        .items
+        .iter()
+        .rev()
+        .find_map(|item| match item {
+            Item::Function(function) if function.name == "main" => Some(function),
+            _ => None,
+        });
+    let Some(Statement::Expression(expression)) = main.and_then(|main| main.body.statements.last())
+    else {
+        return assembled.source.clone();
+    };
+    let Some(span) = expression.span() else {
+        return assembled.source.clone();
+    };
+    if span.start_line < assembled.cell_start
+        || matches!(checked.types.get(span), None | Some(Type::Void))
+    {
+        return assembled.source.clone();
+    }
+
+    let source = &assembled.source;
+    let (before, value, after) = (
+        &source[..span.start_offset],
+        &source[span.range()],
+        &source[span.end_offset..],
+    );
+
+    let mut source = format!(
+        "{before}io.print(\"{VALUE_MARKER}\")\nio.print({value})\nio.print(\"{VALUE_MARKER}\"){after}"
+    );
+    if !checked
+        .program
+        .imports
+        .iter()
+        .any(|import| import.module_path == "io")
+    {
+        source.push_str("import io\n");
+    }
+    source
+}
+
+/// Separates the marked value from the program's own output.
+fn split_value(stdout: &str) -> (String, Option<String>) {
+    let marker = format!("{}\n", VALUE_MARKER);
+    let Some(start) = stdout.find(&marker) else {
+        return (stdout.to_string(), None);
+    };
+    let rest = &stdout[start + marker.len()..];
+    let Some(end) = rest.find(&marker) else {
+        return (stdout.to_string(), None);
+    };
+
+    let value = rest[..end].strip_suffix('\n').unwrap_or(&rest[..end]);
+    let output = format!("{}{}", &stdout[..start], &rest[end + marker.len()..]);
+    (output, Some(value.to_string()))
+}
@@ -0,0 +1,149 @@
+//! Just enough of ZMTP 3.0 (the ZeroMQ wire protocol, NULL security) over
+//! plain TCP to serve the sockets a Jupyter kernel binds: ROUTER for shell,
+//! control and stdin, PUB for iopub and REP for the heartbeat.
+//!
+//! Each accepted connection is its own peer, so ROUTER replies go back on the
+//! connection the request came from and no routing ids are needed.
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+
+const COMMAND: u8 = 0x04;
+const LONG: u8 = 0x02;
+const MORE: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketType {
+    Router,
+    Pub,
+    Rep,
+}
+
+impl SocketType {
+    fn name(self) -> &'static str {
+        match self {
+            SocketType::Router => "ROUTER",
+            SocketType::Pub => "PUB",
+            SocketType::Rep => "REP",
+        }
+    }
+}
+
+/// One handshaken peer connection.
+pub struct Connection {
+    reader: TcpStream,
+    writer: Arc<Mutex<TcpStream>>,
+}
+
+impl Connection {
+    /// Handshakes with the peer that connected on `stream`.
+    pub fn new(stream: TcpStream, socket_type: SocketType) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let mut connection = Self {
+            reader: stream.try_clone()?,
+            writer: Arc::new(Mutex::new(stream)),
+        };
+        connection.handshake(socket_type)?;
+        Ok(connection)
+    }
+
+    /// A handle that can send on this connection from another thread.
+    pub fn sender(&self) -> Sender {
+        Sender(self.writer.clone())
+    }
+
+    /// Reads the next multipart message, skipping commands such as PING or
+    /// SUBSCRIBE that need no reply here.
+    pub fn recv(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        let mut parts = Vec::new();
+        loop {
+            let (flags, body) = read_frame(&mut self.reader)?;
+            if flags & COMMAND != 0 {
+                continue;
+            }
+            parts.push(body);
+            if flags & MORE == 0 {
+                return Ok(parts);
+            }
+        }
+    }
+
+    fn handshake(&mut self, socket_type: SocketType) -> io::Result<()> {
+        let mut greeting = [0u8; 64];
+        greeting[0] = 0xff;
+        greeting[9] = 0x7f;
+        greeting[10] = 3;
+        greeting[12..16].copy_from_slice(b"NULL");
+        self.writer.lock().unwrap().write_all(&greeting)?;
+
+        let mut peer = [0u8; 64];
+        self.reader.read_exact(&mut peer)?;
+        if peer[0] != 0xff || peer[9] != 0x7f || peer[10] < 3 || &peer[12..16] != b"NULL" {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "peer does not speak ZMTP 3 with NULL security",
+            ));
+        }
+
+        let mut ready = Vec::new();
+        ready.push(5);
+        ready.extend_from_slice(b"READY");
+        let property = b"Socket-Type";
+        ready.push(property.len() as u8);
+        ready.extend_from_slice(property);
+        ready.extend_from_slice(&(socket_type.name().len() as u32).to_be_bytes());
+        ready.extend_from_slice(socket_type.name().as_bytes());
+        write_frame(&mut *self.writer.lock().unwrap(), COMMAND, &ready)?;
+
+        let (flags, body) = read_frame(&mut self.reader)?;
+        if flags & COMMAND == 0 || !body.starts_with(b"\x05READY") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "expected a READY command from the peer",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct Sender(Arc<Mutex<TcpStream>>);
+
+impl Sender {
+    pub fn send(&self, parts: &[Vec<u8>]) -> io::Result<()> {
+        let mut stream = self.0.lock().unwrap();
+        for (index, part) in parts.iter().enumerate() {
+            let flags = if index + 1 < parts.len() { MORE } else { 0 };
+            write_frame(&mut *stream, flags, part)?;
+        }
+        stream.flush()
+    }
+}
+
+fn read_frame(stream: &mut TcpStream) -> io::Result<(u8, Vec<u8>)> {
+    let mut flags = [0u8; 1];
+    stream.read_exact(&mut flags)?;
+    let size = if flags[0] & LONG != 0 {
+        let mut size = [0u8; 8];
+        stream.read_exact(&mut size)?;
+        u64::from_be_bytes(size) as usize
+    } else {
+        let mut size = [0u8; 1];
+        stream.read_exact(&mut size)?;
+        size[0] as usize
+    };
+    let mut body = vec![0u8; size];
+    stream.read_exact(&mut body)?;
+    Ok((flags[0], body))
+}
+
+fn write_frame(stream: &mut impl Write, flags: u8, body: &[u8]) -> io::Result<()> {
+    if body.len() > u8::MAX as usize {
+        stream.write_all(&[flags | LONG])?;
+        stream.write_all(&(body.len() as u64).to_be_bytes())?;
+    } else {
+        stream.write_all(&[flags, body.len() as u8])?;
+    }
+    stream.write_all(body)
+}
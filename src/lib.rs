@@ -0,0 +1,25 @@
+pub mod api;
+pub mod compiler;
+pub mod diagnostics;
+pub mod fix;
+pub mod fuzz;
+pub mod grammar;
+pub mod highlight;
+pub mod ice;
+pub mod introspect;
+pub mod kernel;
+pub mod mutation;
+pub mod profile;
+pub mod render;
+pub mod script;
+pub mod utils;
+
+#[cfg(feature = "test-support")]
+pub mod test_support;
+
+pub use api::{parse, CompileError, CompileOptions, CompileOutput, Compiler};
+pub use compiler::callbacks::CompilerCallbacks;
+pub use diagnostics::{
+    Applicability, Category, Diagnostic, DiagnosticCounts, Error, Label, Level, Lint, LintLevel,
+    LintName, Result, Span, Suggestion, Suppression,
+};
@@ -1,19 +1,40 @@
-use assert_cmd::output;
-use clap::{Arg, Command};
-use std::path::Path;
+use clap::{Arg, ArgMatches, Command};
+use std::fmt;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-mod compiler;
-mod diagnostics;
-mod utils;
+use rustic::compiler::graph::ProjectGraph;
+use rustic::compiler::{Emit, FileOutcome, InputFormat, RusticCompiler};
+use rustic::diagnostics::{DiagnosticEngine, Error};
+use rustic::fix;
+use rustic::fuzz::{self, FuzzOptions};
+use rustic::grammar;
+use rustic::highlight::{self, Format};
+use rustic::ice;
+use rustic::introspect;
+use rustic::kernel::{self, ConnectionInfo};
+use rustic::mutation::{self, MutantStatus, MutationOptions, Oracle};
+use rustic::profile;
+use rustic::render::{self, SourceMap};
+use rustic::script;
+use rustic::{
+    Category, CompileError, CompileOptions, CompileOutput, Compiler, Diagnostic, DiagnosticCounts,
+    LintLevel, LintName,
+};
 
-use compiler::RusticCompiler;
-use diagnostics::DiagnosticEngine;
+/// Most rounds of fixing and recompiling `--fix` and `rustic fix` do.
+const MAX_FIX_PASSES: usize = 10;
 
 fn main() {
-    let _matches = Command::new("rustic")
+    ice::install_hook();
+
+    let matches = Command::new("rustic")
         .version("0.10")
         .about("Rustic programming language compiler")
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
         .arg(
             Arg::new("input")
                 .help("Input .rsc file or dir")
@@ -42,15 +63,337 @@ fn main() {
                 .help("Enable verbose output")
                 .action(clap::ArgAction::SetTrue)
         )
+        .arg(
+            Arg::new("emit")
+                .long("emit")
+                .help("What to write for each module (ast-json: the parsed program, for other tools)")
+                .value_name("KIND")
+                .value_parser(["rust", "ast-json"])
+                .default_value("rust")
+        )
+        .arg(
+            Arg::new("from")
+                .long("from")
+                .help("Input format (ast-json: <module>.ast.json documents written by --emit ast-json)")
+                .value_name("FORMAT")
+                .value_parser(["rustic", "ast-json"])
+                .default_value("rustic")
+        )
+        .arg(
+            Arg::new("report")
+                .long("report")
+                .help("Write a report to the output directory (graph: import and call graphs as DOT and JSON)")
+                .value_name("KIND")
+                .value_parser(["graph"])
+                .action(clap::ArgAction::Append)
+        )
+        .arg(
+            Arg::new("fix")
+                .long("fix")
+                .help("Apply machine-applicable suggestions to the source files")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("watch")
+                .long("watch")
+                .help("Compile again whenever a source file changes, until interrupted")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("fix")
+        )
+        .arg(
+            Arg::new("rebuild")
+                .long("rebuild")
+                .help("Compile every module, even those unchanged since the last build")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("no-format")
+                .long("no-format")
+                .help("Leave the generated Rust unformatted, which compiles faster")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("release-opt")
+                .long("release-opt")
+                .help("Leave out the functions, methods, structs, enums and constants that main never reaches")
+                .action(clap::ArgAction::SetTrue)
+        )
+        .arg(
+            Arg::new("run")
+                .long("run")
+                .help("With --watch, run the program after each successful build")
+                .action(clap::ArgAction::SetTrue)
+                .requires("watch")
+                .requires("compile")
+        )
+        .arg(
+            Arg::new("self-profile")
+                .long("self-profile")
+                .help("Write a Chrome trace of where compilation time went (default: rustic-profile.json)")
+                .value_name("FILE")
+                .num_args(0..=1)
+                .require_equals(true)
+                .default_missing_value("rustic-profile.json")
+        )
+        .args(diagnostic_args())
+        .subcommand(
+            Command::new("check")
+                .about("Check source files for errors without generating code or writing any files")
+                .arg(
+                    Arg::new("input")
+                        .help("Input .rsc file or dir")
+                        .required(true)
+                        .index(1)
+                )
+                .args(diagnostic_args()),
+        )
+        .subcommand(
+            Command::new("fix")
+                .about("Apply machine-applicable suggestions to source files in place, then report what is left")
+                .arg(
+                    Arg::new("input")
+                        .help("Input .rsc file or dir")
+                        .required(true)
+                        .index(1)
+                )
+                .args(diagnostic_args()),
+        )
+        .subcommand(
+            Command::new("fuzz")
+                .about("Fuzz the lexer and parser with random and grammar-derived input (developer tool)")
+                .arg(
+                    Arg::new("iterations")
+                        .short('n')
+                        .long("iterations")
+                        .help("Number of inputs to try")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("10000")
+                )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .help("Random seed, for replaying a run (defaults to the current time)")
+                        .value_parser(clap::value_parser!(u64))
+                )
+                .arg(
+                    Arg::new("max-len")
+                        .long("max-len")
+                        .help("Maximum input length in bytes")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("4096")
+                )
+                .arg(
+                    Arg::new("corpus")
+                        .long("corpus")
+                        .help("Directory of .rsc files to mutate")
+                        .value_name("DIR")
+                )
+                .arg(
+                    Arg::new("artifacts")
+                        .long("artifacts")
+                        .help("Directory for minimized crashing inputs")
+                        .value_name("DIR")
+                        .default_value("target/fuzz-artifacts")
+                )
+                .arg(
+                    Arg::new("minimize")
+                        .long("minimize")
+                        .help("Minimize an existing crashing input instead of fuzzing")
+                        .value_name("FILE")
+                ),
+        )
+        .subcommand(
+            Command::new("mutate")
+                .about("Run mutation testing: mutate the program and report mutants the tests miss")
+                .arg(
+                    Arg::new("input")
+                        .help("Input .rsc file")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("test-cmd")
+                        .long("test-cmd")
+                        .help("Shell command run after each mutant is written; failure kills the mutant (default: compare the program's output)")
+                        .value_name("CMD")
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Directory the mutated Rust code is written to")
+                        .value_name("DIR")
+                        .default_value("target/mutants")
+                )
+                .arg(
+                    Arg::new("timeout")
+                        .long("timeout")
+                        .help("Seconds before a mutant's run is stopped and counted as detected")
+                        .value_parser(clap::value_parser!(u64))
+                        .default_value("10")
+                ),
+        )
+        .subcommand(
+            Command::new("xref")
+                .about("Write a JSON cross-reference index of definitions, references, types and spans")
+                .arg(
+                    Arg::new("input")
+                        .help("Input .rsc file or dir")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("File to write the index to (default: stdout)")
+                        .value_name("FILE")
+                ),
+        )
+        .subcommand(
+            Command::new("highlight")
+                .about("Render a source file with syntax highlighting as standalone HTML or ANSI text")
+                .arg(
+                    Arg::new("input")
+                        .help("Input .rsc file")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("Output format")
+                        .value_parser(["html", "ansi"])
+                        .default_value("html")
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("File to write to (default: stdout)")
+                        .value_name("FILE")
+                ),
+        )
+        .subcommand(
+            Command::new("grammar")
+                .about("Generate a TextMate grammar for editor syntax highlighting from the lexer's tables")
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("File to write the grammar to (default: stdout)")
+                        .value_name("FILE")
+                ),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Run a single .rsc file, built natively or with the interpreter")
+                .arg(
+                    Arg::new("input")
+                        .help("Input .rsc file")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .help("Build a cached native binary, or interpret without building")
+                        .value_parser(["native", "interpret"])
+                        .default_value("native")
+                )
+                .arg(diagnostic_format_arg())
+                .arg(
+                    Arg::new("args")
+                        .help("Arguments passed to the program (after `--`)")
+                        .index(2)
+                        .num_args(0..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true)
+                ),
+        )
+        .subcommand(
+            Command::new("script")
+                .about("Run a single .rsc file, caching the compiled binary until the file changes")
+                .arg(
+                    Arg::new("input")
+                        .help("Input .rsc file")
+                        .required(true)
+                        .index(1)
+                )
+                .arg(
+                    Arg::new("rebuild")
+                        .long("rebuild")
+                        .help("Rebuild even if a cached binary exists")
+                        .action(clap::ArgAction::SetTrue)
+                )
+                .arg(diagnostic_format_arg())
+                .arg(
+                    Arg::new("args")
+                        .help("Arguments passed to the script (after `--`)")
+                        .index(2)
+                        .num_args(0..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true)
+                ),
+        )
+        .subcommand(
+            Command::new("kernel")
+                .about("Run as a Jupyter kernel, or install its kernelspec")
+                .subcommand_negates_reqs(true)
+                .args_conflicts_with_subcommands(true)
+                .arg(
+                    Arg::new("connection-file")
+                        .long("connection-file")
+                        .help("Connection file written by Jupyter")
+                        .value_name("FILE")
+                        .required(true)
+                )
+                .subcommand(
+                    Command::new("install")
+                        .about("Register the Rustic kernel with Jupyter")
+                        .arg(
+                            Arg::new("kernels-dir")
+                                .long("kernels-dir")
+                                .help("Directory holding kernelspecs (default: the per-user Jupyter data dir)")
+                                .value_name("DIR")
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("introspect")
+                .about("Print JSON describing the supported keywords, types, builtin modules and diagnostics")
+        )
         .get_matches();
 
-    let input_path = _matches.get_one::<String>("input").unwrap();
-    let output_dir = _matches.get_one::<String>("output").unwrap();
-    let should_compile = _matches.get_flag("compile");
-    let verbose = _matches.get_flag("verbose");
+    match matches.subcommand() {
+        Some(("check", check_matches)) => run_check(check_matches),
+        Some(("fix", fix_matches)) => run_fix(fix_matches),
+        Some(("fuzz", fuzz_matches)) => run_fuzz(fuzz_matches),
+        Some(("mutate", mutate_matches)) => run_mutate(mutate_matches),
+        Some(("xref", xref_matches)) => run_xref(xref_matches),
+        Some(("highlight", highlight_matches)) => run_highlight(highlight_matches),
+        Some(("grammar", grammar_matches)) => run_grammar(grammar_matches),
+        Some(("run", run_matches)) => run_program(run_matches),
+        Some(("script", script_matches)) => run_script(script_matches),
+        Some(("kernel", kernel_matches)) => run_kernel(kernel_matches),
+        Some(("introspect", _)) => {
+            let json = serde_json::to_string_pretty(&introspect::describe())
+                .expect("language description serializes");
+            println!("{}", json);
+        }
+        _ => compile(&matches),
+    }
+}
 
-    let mut diagnostic_engine = DiagnosticEngine::new();
-    let mut compiler = RusticCompiler::new(&mut diagnostic_engine);
+fn compile(matches: &ArgMatches) {
+    let input_path = matches.get_one::<String>("input").unwrap();
+    let output_dir = matches.get_one::<String>("output").unwrap();
+    let should_compile = matches.get_flag("compile");
+    let verbose = matches.get_flag("verbose");
+    let reports: Vec<&String> = matches
+        .get_many::<String>("report")
+        .map(|values| values.collect())
+        .unwrap_or_default();
 
     if verbose {
         println!("Rustic Compiler v0.1.0");
@@ -58,39 +401,616 @@ fn main() {
         println!("Output: {}", output_dir);
     }
 
-    let result = if Path::new(input_path).is_file() {
-        compiler.compile_file(input_path, output_dir)
-    } else {
-        compiler.compile_directory(input_path, output_dir)
+    let emit = match matches.get_one::<String>("emit").map(String::as_str) {
+        Some("ast-json") => Emit::AstJson,
+        _ => Emit::Rust,
     };
+    let input_format = match matches.get_one::<String>("from").map(String::as_str) {
+        Some("ast-json") => InputFormat::AstJson,
+        _ => InputFormat::Rustic,
+    };
+
+    let mut options = CompileOptions::new()
+        .output_dir(output_dir)
+        .native(should_compile)
+        .emit(emit)
+        .input_format(input_format)
+        // A report covers every module, so none can be skipped.
+        .incremental(!matches.get_flag("rebuild") && reports.is_empty())
+        .eliminate_dead_code(matches.get_flag("release-opt"))
+        .format(!matches.get_flag("no-format"));
+    for (name, level) in lint_levels(matches) {
+        options = options.lint_level(name, level);
+    }
+
+    let profile_path = matches.get_one::<String>("self-profile");
+    if profile_path.is_some() {
+        profile::enable();
+    }
 
+    let compiler = Compiler::new(options);
+    if matches.get_flag("watch") {
+        watch(matches, &compiler, input_path);
+        return;
+    }
+    let mut result = compiler.compile(input_path);
+    if matches.get_flag("fix") {
+        result = fix_all(&compiler, input_path, result);
+    }
+    if let Some(path) = profile_path {
+        match profile::write_trace(Path::new(path)) {
+            Ok(spans) => println!("Wrote {} profile spans to {}", spans, path),
+            Err(e) => eprintln!("Error: failed to write {}: {}", path, e),
+        }
+    }
     match result {
-        Ok(generated_files) => {
+        Ok(output) => {
+            report(matches, &output.diagnostics);
+            print_summary(&output.outcomes);
+            // Denied warnings fail the build even when they did not stop it.
+            if output.counts().errors > 0 {
+                process::exit(1);
+            }
+
             if verbose {
-                println!("Generated {} Rust files:", generated_files.len());
-                for file in &generated_files {
-                    println!("  {}", file);
+                println!("Generated {} files:", output.files.len());
+                for file in &output.files {
+                    println!("  {}", file.display());
                 }
             }
 
-            if should_compile {
-                match compiler.compile_to_native(&generated_files, output_dir) {
-                    Ok(binary_path) => {
-                        println!("Successfully compiled to: {}", binary_path);
-                    }
-                    Err(e) => {
-                        eprint!("Compilation failed: {}", e);
-                        process::exit(1);
-                    }
+            for report in &reports {
+                if let Err(e) = write_report(&output.graph, report, output_dir) {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
                 }
             }
 
+            if let Some(binary_path) = &output.binary {
+                println!("Successfully compiled to: {}", binary_path.display());
+            }
+
             println!("Compilation successful!");
+        }
+        Err(e) => {
+            report(matches, &e.diagnostics);
+            print_summary(&e.outcomes);
+            report_failure(matches, &e.diagnostics, &e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Compiles `input_path` and again whenever its sources change, reporting
+/// each build and, with `--run`, restarting the program after it.
+fn watch(matches: &ArgMatches, compiler: &Compiler, input_path: &str) {
+    let mut running: Option<process::Child> = None;
+    println!("Watching {} for changes (press Ctrl-C to stop)", input_path);
+    let result = compiler.watch(input_path, |result| {
+        if let Some(mut child) = running.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        match result {
+            Ok(output) => {
+                report(matches, &output.diagnostics);
+                print_summary(&output.outcomes);
+                if output.counts().errors > 0 {
+                    return ControlFlow::Continue(());
+                }
+                println!("Compilation successful!");
+                // A library has nothing to run.
+                let program = output.binary.filter(|binary| {
+                    binary.extension().is_none_or(|extension| extension != "rlib")
+                });
+                if let (true, Some(binary)) = (matches.get_flag("run"), program) {
+                    match process::Command::new(&binary).spawn() {
+                        Ok(child) => running = Some(child),
+                        Err(e) => eprintln!("Error: failed to run {}: {}", binary.display(), e),
+                    }
+                }
+            }
+            Err(e) => {
+                report(matches, &e.diagnostics);
+                print_summary(&e.outcomes);
+                report_failure(matches, &e.diagnostics, &e);
+            }
+        }
+        println!("Waiting for changes...");
+        ControlFlow::Continue(())
+    });
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Runs the lexer, parser and semantic analysis over the input, stopping
+/// before code generation, so nothing is written.
+fn run_check(matches: &ArgMatches) {
+    let input_path = matches.get_one::<String>("input").unwrap();
+    let mut options = CompileOptions::new().emit(Emit::Check);
+    for (name, level) in lint_levels(matches) {
+        options = options.lint_level(name, level);
+    }
+
+    match Compiler::new(options).compile(input_path) {
+        Ok(output) => {
+            report(matches, &output.diagnostics);
+            print_summary(&output.outcomes);
+            if output.counts().errors > 0 {
+                process::exit(1);
+            }
+            println!("Check successful!");
+        }
+        Err(e) => {
+            report(matches, &e.diagnostics);
+            print_summary(&e.outcomes);
+            report_failure(matches, &e.diagnostics, &e);
+            process::exit(1);
+        }
     }
-    Err(e) => {
-        eprint!("Error: {}", e);
+}
+
+fn run_fix(matches: &ArgMatches) {
+    let input_path = matches.get_one::<String>("input").unwrap();
+    let mut options = CompileOptions::new().emit(Emit::Check);
+    for (name, level) in lint_levels(matches) {
+        options = options.lint_level(name, level);
+    }
+
+    let compiler = Compiler::new(options);
+    let result = fix_all(&compiler, input_path, compiler.compile(input_path));
+    let (diagnostics, outcomes) = match &result {
+        Ok(output) => (&output.diagnostics, &output.outcomes),
+        Err(e) => (&e.diagnostics, &e.outcomes),
+    };
+    report(matches, diagnostics);
+    print_summary(outcomes);
+    match result {
+        Ok(output) if output.counts().errors == 0 => {}
+        Ok(_) => process::exit(1),
+        Err(e) => {
+            report_failure(matches, &e.diagnostics, &e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Applies the machine-applicable suggestions of a compilation that ended
+/// with `result` and compiles `input_path` again, returning the last result.
+/// A syntax error hides the ones after it in the same item, so fixing it can
+/// reveal more; this goes on until nothing is left to fix.
+fn fix_all(
+    compiler: &Compiler,
+    input_path: &str,
+    mut result: Result<CompileOutput, CompileError>,
+) -> Result<CompileOutput, CompileError> {
+    for _ in 0..MAX_FIX_PASSES {
+        let diagnostics = match &result {
+            Ok(output) => &output.diagnostics,
+            Err(e) => &e.diagnostics,
+        };
+        let fixed = match fix::fix_files(diagnostics) {
+            Ok(fixed) => fixed,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        };
+        if fixed.is_empty() {
+            break;
+        }
+        for file in &fixed {
+            println!("Fixed {} problem(s) in {}", file.fixes, file.file);
+        }
+        result = compiler.compile(input_path);
+    }
+    result
+}
+
+/// Prints `diagnostics` in the format asked for by `--diagnostic-format`.
+fn report(matches: &ArgMatches, diagnostics: &[Diagnostic]) {
+    if json_diagnostics(matches) {
+        render::emit_json(diagnostics);
+    } else {
+        render::emit(diagnostics, &mut SourceMap::new());
+    }
+}
+
+/// Prints what `diagnostic_engine` collected like `report`, with snippets of
+/// the sources it was given.
+fn report_engine(matches: &ArgMatches, diagnostic_engine: &DiagnosticEngine) {
+    if json_diagnostics(matches) {
+        diagnostic_engine.emit_all_json();
+    } else {
         diagnostic_engine.emit_all();
+    }
+}
+
+/// Says that the command failed with `error` after reporting `diagnostics`:
+/// as a line of text, or with JSON diagnostics as a summary object, so that
+/// every line stays JSON. An error that only counts the errors reported is
+/// left to the `aborting due to ...` line that already ended them.
+fn report_failure(matches: &ArgMatches, diagnostics: &[Diagnostic], error: &dyn fmt::Display) {
+    let counts = DiagnosticCounts::of(diagnostics);
+    if json_diagnostics(matches) {
+        render::emit_json_failure(&counts, &error.to_string());
+    } else if counts.errors == 0 {
+        eprintln!("Error: {}", error);
+    }
+}
+
+fn json_diagnostics(matches: &ArgMatches) -> bool {
+    matches.get_one::<String>("diagnostic-format").map(String::as_str) == Some("json")
+}
+
+/// One line per file of a directory build: whether it compiled and how many
+/// problems it had.
+fn print_summary(outcomes: &[FileOutcome]) {
+    if outcomes.is_empty() {
+        return;
+    }
+    let plural = |count: usize, noun: &str| {
+        format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+    };
+    println!("Summary:");
+    for outcome in outcomes {
+        let status = if outcome.result.is_ok() { "ok" } else { "FAILED" };
+        let detail = match (&outcome.result, outcome.errors, outcome.warnings) {
+            // Failed before reporting anything, e.g. unreadable.
+            (Err(e), 0, 0) => e.to_string(),
+            (_, 0, 0) if outcome.cached => "up to date".to_string(),
+            (_, 0, 0) => "no problems".to_string(),
+            (_, errors, 0) => plural(errors, "error"),
+            (_, 0, warnings) => plural(warnings, "warning"),
+            (_, errors, warnings) => {
+                format!("{}, {}", plural(errors, "error"), plural(warnings, "warning"))
+            }
+        };
+        println!("  {:<6} {} ({})", status, outcome.file, detail);
+    }
+}
+
+/// How diagnostics are printed and which are reported, shared by building,
+/// `rustic check` and `rustic fix`.
+fn diagnostic_args() -> [Arg; 5] {
+    [
+        diagnostic_format_arg(),
+        Arg::new("warn")
+            .short('W')
+            .long("warn")
+            .help("Report warnings of a category or lint")
+            .value_name("LINT")
+            .value_parser(lint_names())
+            .action(clap::ArgAction::Append),
+        Arg::new("allow")
+            .short('A')
+            .long("allow")
+            .help("Silence warnings of a category or lint")
+            .value_name("LINT")
+            .value_parser(lint_names())
+            .action(clap::ArgAction::Append),
+        Arg::new("deny")
+            .short('D')
+            .long("deny")
+            .help("Report warnings of a category or lint as errors")
+            .value_name("LINT")
+            .value_parser(lint_names())
+            .action(clap::ArgAction::Append),
+        Arg::new("deny-warnings")
+            .long("deny-warnings")
+            .help("Report warnings of every category as errors, like -D for each")
+            .action(clap::ArgAction::SetTrue),
+    ]
+}
+
+/// `--diagnostic-format`, also accepted as cargo's `--message-format`.
+fn diagnostic_format_arg() -> Arg {
+    Arg::new("diagnostic-format")
+        .long("diagnostic-format")
+        .visible_alias("message-format")
+        .help("How to print diagnostics (json: one JSON object per line, for tools)")
+        .value_name("FORMAT")
+        .value_parser(["human", "json"])
+        .default_value("human")
+}
+
+/// What `-W`, `-A` and `-D` take: the name of a category or of a lint.
+fn lint_names() -> Vec<&'static str> {
+    LintName::names().collect()
+}
+
+/// The `-W`, `-A`, `-D` and `--deny-warnings` flags in command-line order, so
+/// a later flag for a category or lint overrides an earlier one. A flag for a
+/// lint overrides any for its category.
+fn lint_levels(matches: &ArgMatches) -> Vec<(LintName, LintLevel)> {
+    let mut levels = Vec::new();
+    if matches.get_flag("deny-warnings") {
+        let index = matches.index_of("deny-warnings").expect("the flag was given");
+        levels.extend(Category::ALL.map(|category| (index, category.into(), LintLevel::Deny)));
+    }
+    for (id, level) in [("warn", LintLevel::Warn), ("allow", LintLevel::Allow), ("deny", LintLevel::Deny)] {
+        let (Some(indices), Some(names)) = (matches.indices_of(id), matches.get_many::<String>(id)) else {
+            continue;
+        };
+        for (index, name) in indices.zip(names) {
+            let name = LintName::from_name(name).expect("clap only accepts lint names");
+            levels.push((index, name, level));
+        }
+    }
+    levels.sort_by_key(|&(index, _, _)| index);
+    levels.into_iter().map(|(_, name, level)| (name, level)).collect()
+}
+
+fn write_report(graph: &ProjectGraph, kind: &str, output_dir: &str) -> std::io::Result<()> {
+    match kind {
+        "graph" => {
+            std::fs::create_dir_all(output_dir)?;
+            for (file, contents) in [("graph.dot", graph.to_dot()), ("graph.json", graph.to_json())] {
+                let path = Path::new(output_dir).join(file);
+                std::fs::write(&path, contents)?;
+                println!("Wrote {}", path.display());
+            }
+            for cycle in graph.import_cycles() {
+                eprintln!("warning: modules import each other: {}", cycle.join(", "));
+            }
+        }
+        _ => unreachable!("clap restricts report kinds"),
+    }
+    Ok(())
+}
+
+fn run_fuzz(matches: &ArgMatches) {
+    if let Some(path) = matches.get_one::<String>("minimize") {
+        match fuzz::minimize_file(Path::new(path)) {
+            Ok(Some(minimized)) => {
+                let output = format!("{}.min", path);
+                if let Err(e) = std::fs::write(&output, &minimized) {
+                    eprintln!("Error: failed to write {}: {}", output, e);
+                    process::exit(1);
+                }
+                println!("Minimized to {} bytes: {}", minimized.len(), output);
+            }
+            Ok(None) => println!("{} does not reproduce a failure", path),
+            Err(e) => {
+                eprintln!("Error: failed to read {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let seed = matches.get_one::<u64>("seed").copied().unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(1)
+    });
+    let options = FuzzOptions {
+        iterations: *matches.get_one::<u64>("iterations").unwrap(),
+        seed,
+        max_len: *matches.get_one::<usize>("max-len").unwrap(),
+        corpus: matches.get_one::<String>("corpus").map(PathBuf::from),
+        artifacts: PathBuf::from(matches.get_one::<String>("artifacts").unwrap()),
+    };
+
+    println!("Fuzzing frontend with seed {}", options.seed);
+    let report = fuzz::run(&options);
+    println!(
+        "Ran {} inputs in {:.2?}; {} unique failure(s)",
+        report.executed,
+        report.elapsed,
+        report.crashes.len()
+    );
+
+    for crash in &report.crashes {
+        match &crash.path {
+            Some(path) => eprintln!("  {} ({} bytes) -> {}", crash.message, crash.input.len(), path.display()),
+            None => eprintln!("  {} ({} bytes)", crash.message, crash.input.len()),
+        }
+    }
+
+    if !report.crashes.is_empty() {
         process::exit(1);
+    }
+}
+
+fn run_mutate(matches: &ArgMatches) {
+    let input_path = matches.get_one::<String>("input").unwrap();
+    let options = MutationOptions {
+        oracle: match matches.get_one::<String>("test-cmd") {
+            Some(command) => Oracle::Command(command.clone()),
+            None => Oracle::CompareOutput,
+        },
+        output_dir: PathBuf::from(matches.get_one::<String>("output").unwrap()),
+        timeout: Duration::from_secs(*matches.get_one::<u64>("timeout").unwrap()),
+    };
+
+    let report = match mutation::run(Path::new(input_path), &options) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    for result in &report.results {
+        if result.status == MutantStatus::Survived {
+            println!("survived: {} {}", result.span, result.description);
         }
     }
-}
\ No newline at end of file
+
+    println!(
+        "{} mutants: {} killed, {} timed out, {} survived, {} unviable; mutation score {:.1}%",
+        report.results.len(),
+        report.count(MutantStatus::Killed),
+        report.count(MutantStatus::TimedOut),
+        report.count(MutantStatus::Survived),
+        report.count(MutantStatus::Unviable),
+        report.score()
+    );
+}
+
+fn run_xref(matches: &ArgMatches) {
+    let input_path = matches.get_one::<String>("input").unwrap();
+
+    let mut diagnostic_engine = DiagnosticEngine::new();
+    let index = RusticCompiler::new(&mut diagnostic_engine).index(input_path);
+
+    let json = match index {
+        Ok(index) => index.to_json(),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            diagnostic_engine.emit_all();
+            process::exit(1);
+        }
+    };
+
+    match matches.get_one::<String>("output") {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, json) {
+                eprintln!("Error: failed to write {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+        None => println!("{}", json),
+    }
+}
+
+fn run_highlight(matches: &ArgMatches) {
+    let input_path = matches.get_one::<String>("input").unwrap();
+    let format = match matches.get_one::<String>("format").unwrap().as_str() {
+        "ansi" => Format::Ansi,
+        _ => Format::Html,
+    };
+
+    let source = match std::fs::read_to_string(input_path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error: failed to read {}: {}", input_path, e);
+            process::exit(1);
+        }
+    };
+
+    let rendered = highlight::highlight(&source, input_path, format);
+
+    match matches.get_one::<String>("output") {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, rendered) {
+                eprintln!("Error: failed to write {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+        None => print!("{}", rendered),
+    }
+}
+
+fn run_grammar(matches: &ArgMatches) {
+    let json = serde_json::to_string_pretty(&grammar::textmate()).expect("grammar serializes");
+
+    match matches.get_one::<String>("output") {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, json) {
+                eprintln!("Error: failed to write {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+        None => println!("{}", json),
+    }
+}
+
+fn run_program(matches: &ArgMatches) {
+    let input_path = Path::new(matches.get_one::<String>("input").unwrap());
+    let args: Vec<String> = matches
+        .get_many::<String>("args")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+
+    let mut diagnostic_engine = DiagnosticEngine::new();
+    let result = match matches.get_one::<String>("backend").map(String::as_str) {
+        Some("interpret") => script::interpret(input_path, args, &mut diagnostic_engine),
+        _ => script::prepare(input_path, false, &mut diagnostic_engine)
+            .map(|binary| exec(&binary, &args)),
+    };
+    if let Err(e @ Error::RuntimeError(_)) = &result {
+        // Reported as a native build's binary reports it.
+        eprintln!("{}", e);
+        process::exit(101);
+    }
+    if let Err(e) = result {
+        report_engine(matches, &diagnostic_engine);
+        report_failure(matches, diagnostic_engine.diagnostics(), &e);
+        process::exit(1);
+    }
+}
+
+fn run_script(matches: &ArgMatches) {
+    let input_path = matches.get_one::<String>("input").unwrap();
+    let args: Vec<String> = matches
+        .get_many::<String>("args")
+        .map(|values| values.cloned().collect())
+        .unwrap_or_default();
+
+    let mut diagnostic_engine = DiagnosticEngine::new();
+    let binary = match script::prepare(
+        Path::new(input_path),
+        matches.get_flag("rebuild"),
+        &mut diagnostic_engine,
+    ) {
+        Ok(binary) => binary,
+        Err(e) => {
+            report_engine(matches, &diagnostic_engine);
+            report_failure(matches, diagnostic_engine.diagnostics(), &e);
+            process::exit(1);
+        }
+    };
+    exec(&binary, &args);
+}
+
+/// Runs `binary` in place of this process, exiting with its status.
+fn exec(binary: &Path, args: &[String]) {
+    let mut command = process::Command::new(binary);
+    command.args(args);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        let e = command.exec();
+        eprintln!("Error: failed to run {}: {}", binary.display(), e);
+        process::exit(1);
+    }
+
+    #[cfg(not(unix))]
+    match command.status() {
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("Error: failed to run {}: {}", binary.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_kernel(matches: &ArgMatches) {
+    if let Some(("install", install_matches)) = matches.subcommand() {
+        let kernels_dir = install_matches
+            .get_one::<String>("kernels-dir")
+            .map(PathBuf::from)
+            .unwrap_or_else(kernel::default_kernels_dir);
+        match kernel::install(&kernels_dir) {
+            Ok(dir) => println!("Installed the Rustic kernelspec in {}", dir.display()),
+            Err(e) => {
+                eprintln!("Error: failed to install the kernelspec: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let path = matches.get_one::<String>("connection-file").unwrap();
+    let result = ConnectionInfo::load(Path::new(path)).and_then(|info| kernel::run(&info));
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
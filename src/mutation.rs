@@ -0,0 +1,479 @@
+//! Mutation testing: systematically alter a checked program (flip comparisons,
+//! nudge integer constants, drop statements) and rerun a test oracle against each
+//! mutant. Mutants the oracle does not notice "survive" and point at untested logic.
+
+use crate::compiler::ast::*;
+use crate::compiler::codegen::CodeGenerator;
+use crate::compiler::lexer::Lexer;
+use crate::compiler::parser::Parser;
+use crate::compiler::semantic::SemanticAnalyzer;
+use crate::diagnostics::{DiagnosticEngine, Error, Result, Span};
+use crate::utils;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Environment variable holding the path of the mutated Rust file for `Oracle::Command`.
+pub const MUTANT_FILE_ENV: &str = "RUSTIC_MUTANT_FILE";
+
+#[derive(Debug, Clone)]
+pub struct Mutant {
+    pub description: String,
+    pub span: Span,
+    pub program: Program,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutantStatus {
+    Killed,
+    Survived,
+    /// The mutant no longer type-checks or compiles, so it says nothing about the tests.
+    Unviable,
+    TimedOut,
+}
+
+#[derive(Debug, Clone)]
+pub struct MutantResult {
+    pub description: String,
+    pub span: Span,
+    pub status: MutantStatus,
+}
+
+#[derive(Debug, Clone)]
+pub enum Oracle {
+    /// Build each mutant with rustc and compare its exit status and stdout with the original's.
+    CompareOutput,
+    /// Write the mutant's Rust into the output directory and run a shell command
+    /// (with [`MUTANT_FILE_ENV`] naming the file); a non-zero exit status kills the mutant.
+    Command(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct MutationOptions {
+    pub oracle: Oracle,
+    pub output_dir: PathBuf,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct MutationReport {
+    pub results: Vec<MutantResult>,
+}
+
+impl MutationReport {
+    pub fn count(&self, status: MutantStatus) -> usize {
+        self.results.iter().filter(|r| r.status == status).count()
+    }
+
+    /// Percentage of viable mutants that were killed or timed out.
+    pub fn score(&self) -> f64 {
+        let viable = self.results.len() - self.count(MutantStatus::Unviable);
+        if viable == 0 {
+            return 100.0;
+        }
+        let detected = self.count(MutantStatus::Killed) + self.count(MutantStatus::TimedOut);
+        detected as f64 * 100.0 / viable as f64
+    }
+}
+
+/// Every single-site mutation of `program`, parsed from `source`, in source
+/// order.
+pub fn mutants(program: &Program, source: &str) -> Vec<Mutant> {
+    let mut counter = Mutator::new(usize::MAX, source);
+    counter.visit_program_mut(&mut program.clone());
+
+    (0..counter.seen)
+        .filter_map(|target| {
+            let mut mutant = program.clone();
+            let mut mutator = Mutator::new(target, source);
+            mutator.visit_program_mut(&mut mutant);
+            mutator.applied.map(|(description, span)| Mutant {
+                description,
+                span,
+                program: mutant,
+            })
+        })
+        .collect()
+}
+
+pub fn run(input_path: &Path, options: &MutationOptions) -> Result<MutationReport> {
+    let source = fs::read_to_string(input_path).map_err(|e| {
+        Error::IoError(format!(
+            "Failed to read file {}: {}",
+            input_path.display(),
+            e
+        ))
+    })?;
+    let file_name = input_path.to_string_lossy();
+    let module_name = utils::module_name(input_path, "main");
+
+    let mut diagnostics = DiagnosticEngine::new();
+    let tokens = Lexer::new(&source, &file_name).tokenize()?;
+    let program = Parser::new(tokens, &mut diagnostics).parse()?;
+    let original = generate(&program, &module_name)?.ok_or_else(|| {
+        Error::SemanticError("the original program does not type-check".to_string())
+    })?;
+
+    fs::create_dir_all(&options.output_dir)
+        .map_err(|e| Error::IoError(format!("Failed to create output directory: {}", e)))?;
+
+    let baseline = match &options.oracle {
+        Oracle::CompareOutput => {
+            if !program
+                .items
+                .iter()
+                .any(|item| matches!(item, Item::Function(f) if f.name == "main"))
+            {
+                return Err(Error::CompilationError(
+                    "comparing program output needs a `main` function; pass --test-cmd instead"
+                        .to_string(),
+                ));
+            }
+            let binary = build(&original, &options.output_dir, "original")?.ok_or_else(|| {
+                Error::CompilationError(
+                    "the original program failed to build with rustc".to_string(),
+                )
+            })?;
+            let output = execute(Command::new(&binary), options.timeout)?.ok_or_else(|| {
+                Error::CompilationError("the original program timed out".to_string())
+            })?;
+            Some(output)
+        }
+        Oracle::Command(_) => None,
+    };
+
+    let mut report = MutationReport::default();
+    for (index, mutant) in mutants(&program, &source).into_iter().enumerate() {
+        let status = match generate(&mutant.program, &module_name)? {
+            None => MutantStatus::Unviable,
+            Some(code) => match &options.oracle {
+                Oracle::CompareOutput => {
+                    let baseline = baseline
+                        .as_ref()
+                        .expect("baseline is built for output comparison");
+                    compare_output(&code, baseline, options, index)?
+                }
+                Oracle::Command(command) => run_command(&code, command, &module_name, options)?,
+            },
+        };
+
+        report.results.push(MutantResult {
+            description: mutant.description,
+            span: mutant.span,
+            status,
+        });
+    }
+
+    if let Oracle::Command(_) = options.oracle {
+        write_module(&options.output_dir, &module_name, &original)?;
+    }
+    Ok(report)
+}
+
+fn generate(program: &Program, module_name: &str) -> Result<Option<String>> {
+    let mut diagnostics = DiagnosticEngine::new();
+    let mut analyzer = SemanticAnalyzer::new(&mut diagnostics);
+    let types = match analyzer.analyze(program) {
+        Ok(types) => types,
+        Err(_) => return Ok(None),
+    };
+    let folded = analyzer
+        .take_folded()
+        .expect("analyze folds the program it checks");
+    CodeGenerator::new(&types)
+        .generate(&folded, module_name)
+        .map(Some)
+}
+
+fn compare_output(
+    code: &str,
+    baseline: &Output,
+    options: &MutationOptions,
+    index: usize,
+) -> Result<MutantStatus> {
+    let Some(binary) = build(code, &options.output_dir, &format!("mutant_{}", index))? else {
+        return Ok(MutantStatus::Unviable);
+    };
+    let output = execute(Command::new(&binary), options.timeout)?;
+    let _ = fs::remove_file(&binary);
+
+    Ok(match output {
+        None => MutantStatus::TimedOut,
+        Some(output)
+            if output.status.code() == baseline.status.code()
+                && output.stdout == baseline.stdout =>
+        {
+            MutantStatus::Survived
+        }
+        Some(_) => MutantStatus::Killed,
+    })
+}
+
+fn run_command(
+    code: &str,
+    command: &str,
+    module_name: &str,
+    options: &MutationOptions,
+) -> Result<MutantStatus> {
+    let path = write_module(&options.output_dir, module_name, code)?;
+
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command).env(MUTANT_FILE_ENV, path);
+    Ok(match execute(shell, options.timeout)? {
+        None => MutantStatus::TimedOut,
+        Some(output) if output.status.success() => MutantStatus::Survived,
+        Some(_) => MutantStatus::Killed,
+    })
+}
+
+fn write_module(output_dir: &Path, module_name: &str, code: &str) -> Result<PathBuf> {
+    let path = output_dir.join(format!("{}.rs", module_name));
+    fs::write(&path, code)
+        .map_err(|e| Error::IoError(format!("Failed to write {}: {}", path.display(), e)))?;
+    Ok(path)
+}
+
+/// Compiles a standalone binary with rustc, returning `None` when rustc rejects it.
+fn build(code: &str, output_dir: &Path, name: &str) -> Result<Option<PathBuf>> {
+    let source = output_dir.join(format!("{}.rs", name));
+    let binary = output_dir.join(name);
+    fs::write(&source, code)
+        .map_err(|e| Error::IoError(format!("Failed to write {}: {}", source.display(), e)))?;
+
+    let output = Command::new("rustc")
+        .args(["--edition", "2021", "-C", "debuginfo=0", "-o"])
+        .arg(&binary)
+        .arg(&source)
+        .output()
+        .map_err(|e| Error::CompilationError(format!("Failed to run rustc: {}", e)))?;
+    let _ = fs::remove_file(&source);
+
+    Ok(output.status.success().then_some(binary))
+}
+
+/// Runs `command` to completion, or returns `None` after killing it at `timeout`.
+fn execute(mut command: Command, timeout: Duration) -> Result<Option<Output>> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::CompilationError(format!("Failed to run test oracle: {}", e)))?;
+
+    let start = Instant::now();
+    loop {
+        let finished = child.try_wait().map_err(|e| {
+            Error::CompilationError(format!("Failed to wait for test oracle: {}", e))
+        })?;
+        if finished.is_some() {
+            return child.wait_with_output().map(Some).map_err(|e| {
+                Error::CompilationError(format!("Failed to collect oracle output: {}", e))
+            });
+        }
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Walks the program counting mutation sites and applies the one numbered `target`.
+struct Mutator<'s> {
+    target: usize,
+    seen: usize,
+    applied: Option<(String, Span)>,
+    span: Span,
+    /// The text the program was parsed from, to find literals in, which
+    /// have no span of their own.
+    source: &'s str,
+    /// Where the last literal found ends; literals are visited in source
+    /// order, so the next one is after it.
+    literal_end: usize,
+}
+
+impl<'s> Mutator<'s> {
+    fn new(target: usize, source: &'s str) -> Self {
+        Self {
+            target,
+            seen: 0,
+            applied: None,
+            span: Span::default(),
+            source,
+            literal_end: 0,
+        }
+    }
+
+    /// Where the literal written `word` is in the statement being visited,
+    /// or the whole statement if it is written otherwise, such as `0x10`.
+    fn literal_span(&mut self, word: &str) -> Span {
+        let mut within = self.span.clone();
+        while let Some(found) = within.find_word(self.source, word) {
+            if found.start_offset >= self.literal_end {
+                self.literal_end = found.end_offset;
+                return found;
+            }
+            within = Span {
+                start_line: found.end_line,
+                start_column: found.end_column,
+                start_offset: found.end_offset,
+                ..within
+            };
+        }
+        self.span.clone()
+    }
+
+    /// Registers one mutation site; returns true when it is the one to apply.
+    fn site(&mut self, description: impl FnOnce() -> String, span: &Span) -> bool {
+        let hit = self.seen == self.target;
+        self.seen += 1;
+        if hit {
+            self.applied = Some((description(), span.clone()));
+        }
+        hit
+    }
+}
+
+impl VisitorMut for Mutator<'_> {
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        self.span = function.span.clone();
+        walk_function_mut(self, function);
+    }
+
+    fn visit_variable_mut(&mut self, variable: &mut Variable) {
+        self.span = variable.span.clone();
+        walk_variable_mut(self, variable);
+    }
+
+    fn visit_constant_mut(&mut self, constant: &mut Constant) {
+        self.span = constant.span.clone();
+        walk_constant_mut(self, constant);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        let mut index = 0;
+        while index < block.statements.len() {
+            let span = statement_span(&block.statements[index])
+                .unwrap_or(&block.span)
+                .clone();
+            let droppable = !matches!(block.statements[index], Statement::Variable(_));
+            if droppable && self.site(|| "removed statement".to_string(), &span) {
+                block.statements.remove(index);
+                continue;
+            }
+            self.visit_statement_mut(&mut block.statements[index]);
+            index += 1;
+        }
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        if let Some(span) = statement_span(statement) {
+            self.span = span.clone();
+        }
+        walk_statement_mut(self, statement);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        if let Expression::Unary(unary) = expression {
+            if unary.operator == UnaryOperator::Not
+                && self.site(|| "removed `!`".to_string(), &unary.span)
+            {
+                let operand = std::mem::replace(
+                    &mut *unary.operand,
+                    Expression::Literal(Literal::Boolean(false)),
+                );
+                *expression = operand;
+                return;
+            }
+        }
+        walk_expression_mut(self, expression);
+    }
+
+    fn visit_literal_mut(&mut self, literal: &mut Literal) {
+        match literal {
+            Literal::Integer(value) => {
+                let original = *value;
+                let span = self.literal_span(&original.to_string());
+                for replacement in [original.wrapping_add(1), original.wrapping_sub(1)] {
+                    if self.site(
+                        || format!("replaced `{}` with `{}`", original, replacement),
+                        &span,
+                    ) {
+                        *value = replacement;
+                    }
+                }
+            }
+            Literal::Boolean(value) => {
+                let original = *value;
+                let span = self.literal_span(&original.to_string());
+                if self.site(
+                    || format!("replaced `{}` with `{}`", original, !original),
+                    &span,
+                ) {
+                    *value = !original;
+                }
+            }
+            Literal::Float(_) | Literal::String(_) | Literal::None => {}
+        }
+    }
+
+    fn visit_binary_mut(&mut self, binary: &mut BinaryOp) {
+        let original = binary.operator.clone();
+        for replacement in operator_replacements(&original) {
+            if self.site(
+                || format!("replaced `{}` with `{}`", original, replacement),
+                &binary.span,
+            ) {
+                binary.operator = replacement;
+            }
+        }
+        walk_binary_mut(self, binary);
+    }
+}
+
+fn operator_replacements(operator: &BinaryOperator) -> Vec<BinaryOperator> {
+    use BinaryOperator::*;
+
+    match operator {
+        Lt => vec![Le, Ge],
+        Le => vec![Lt, Gt],
+        Gt => vec![Ge, Le],
+        Ge => vec![Gt, Lt],
+        Eq => vec![Ne],
+        Ne => vec![Eq],
+        Add => vec![Sub],
+        Sub => vec![Add],
+        Mul => vec![Div],
+        Div => vec![Mul],
+        Mod => vec![Mul],
+        And => vec![Or],
+        Or => vec![And],
+        Coalesce => vec![],
+        BitAnd => vec![BitOr],
+        BitOr => vec![BitAnd],
+        BitXor => vec![BitOr],
+        Shl => vec![Shr],
+        Shr => vec![Shl],
+        Pow => vec![Mul],
+    }
+}
+
+fn statement_span(statement: &Statement) -> Option<&Span> {
+    match statement {
+        Statement::Expression(expression) => expression.span(),
+        Statement::Variable(variable) => Some(&variable.span),
+        Statement::Destructure(destructure) => Some(&destructure.span),
+        Statement::Assignment(assignment) => Some(&assignment.span),
+        Statement::If(if_statement) => Some(&if_statement.span),
+        Statement::For(for_loop) => Some(&for_loop.span),
+        Statement::While(while_loop) => Some(&while_loop.span),
+        Statement::Try(try_statement) => Some(&try_statement.span),
+        Statement::Throw(throw_statement) => Some(&throw_statement.span),
+        Statement::Return(return_statement) => Some(&return_statement.span),
+        Statement::Break(break_statement) => Some(&break_statement.span),
+        Statement::Continue(continue_statement) => Some(&continue_statement.span),
+    }
+}
@@ -0,0 +1,155 @@
+//! Self-profiling for `rustic --self-profile`. While enabled, the compiler
+//! records how long each build, file, phase and query took; `write_trace`
+//! saves the spans in the Chrome trace event format, which `chrome://tracing`,
+//! Perfetto and speedscope open directly.
+//!
+//! Recording is off by default, and `start` costs an atomic load until it is
+//! turned on, so the pipeline times itself unconditionally.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::cell::Cell;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// One finished span, as a Chrome trace "complete" event.
+#[derive(Debug, Clone, Serialize)]
+struct Event {
+    name: String,
+    cat: &'static str,
+    ph: &'static str,
+    /// Start, in microseconds since recording was enabled.
+    ts: u64,
+    dur: u64,
+    pid: u32,
+    tid: u64,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    args: Map<String, Value>,
+}
+
+struct Profiler {
+    epoch: Instant,
+    events: Vec<Event>,
+}
+
+fn profiler() -> &'static Mutex<Profiler> {
+    static PROFILER: OnceLock<Mutex<Profiler>> = OnceLock::new();
+    PROFILER.get_or_init(|| {
+        Mutex::new(Profiler {
+            epoch: Instant::now(),
+            events: Vec::new(),
+        })
+    })
+}
+
+/// Small per-thread numbers, so traces show one row per thread.
+fn thread_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static ID: Cell<u64> = const { Cell::new(0) };
+    }
+    ID.with(|id| {
+        if id.get() == 0 {
+            id.set(NEXT.fetch_add(1, Ordering::Relaxed));
+        }
+        id.get()
+    })
+}
+
+/// Starts recording spans, measuring time from now.
+pub fn enable() {
+    profiler().lock().unwrap_or_else(|e| e.into_inner()).epoch = Instant::now();
+    ENABLED.store(true, Ordering::Release);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Acquire)
+}
+
+/// Times the activity `name` of kind `category` until the returned guard is
+/// dropped. Does nothing while recording is off.
+pub fn start(category: &'static str, name: &str) -> TimingGuard {
+    let timing = is_enabled().then(|| Timing {
+        name: name.to_string(),
+        category,
+        start: Instant::now(),
+        args: Map::new(),
+    });
+    TimingGuard { timing }
+}
+
+struct Timing {
+    name: String,
+    category: &'static str,
+    start: Instant,
+    args: Map<String, Value>,
+}
+
+/// Records its span when dropped.
+#[must_use = "the span ends when the guard is dropped"]
+pub struct TimingGuard {
+    timing: Option<Timing>,
+}
+
+impl TimingGuard {
+    /// Attaches `key: value` to the span, shown when it is selected.
+    pub fn arg(mut self, key: &str, value: impl Into<Value>) -> Self {
+        if let Some(timing) = &mut self.timing {
+            timing.args.insert(key.to_string(), value.into());
+        }
+        self
+    }
+}
+
+impl Drop for TimingGuard {
+    fn drop(&mut self) {
+        let Some(timing) = self.timing.take() else {
+            return;
+        };
+        let end = Instant::now();
+        let mut profiler = profiler().lock().unwrap_or_else(|e| e.into_inner());
+        let micros = |instant: Instant| {
+            let elapsed = instant.saturating_duration_since(profiler.epoch);
+            u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX)
+        };
+        let event = Event {
+            name: timing.name,
+            cat: timing.category,
+            ph: "X",
+            ts: micros(timing.start),
+            dur: micros(end) - micros(timing.start),
+            pid: std::process::id(),
+            tid: thread_id(),
+            args: timing.args,
+        };
+        profiler.events.push(event);
+    }
+}
+
+/// The spans recorded so far as a Chrome trace document, leaving them
+/// recorded.
+pub fn trace() -> Value {
+    let profiler = profiler().lock().unwrap_or_else(|e| e.into_inner());
+    let mut events = profiler.events.clone();
+    // Parents end after their children, but viewers expect them first.
+    events.sort_by_key(|event| (event.tid, event.ts, std::cmp::Reverse(event.dur)));
+    serde_json::json!({
+        "traceEvents": events,
+        "displayTimeUnit": "ms",
+        "otherData": { "version": format!("rustic {}", env!("CARGO_PKG_VERSION")) },
+    })
+}
+
+/// Writes `trace` to `path` and returns the number of spans written.
+pub fn write_trace(path: &Path) -> io::Result<usize> {
+    let trace = trace();
+    let count = trace["traceEvents"].as_array().map_or(0, Vec::len);
+    fs::write(path, serde_json::to_string(&trace)?)?;
+    Ok(count)
+}
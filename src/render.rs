@@ -0,0 +1,271 @@
+//! Terminal rendering of diagnostics: a `file:line:col` header followed by the
+//! offending source lines, with `^` under the primary span and `-` under each
+//! label. Colors follow the severity and are used only when stderr is a
+//! terminal and `NO_COLOR` is unset.
+
+use crate::diagnostics::{sort_diagnostics, Diagnostic, DiagnosticCounts, Level, Span};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io::IsTerminal;
+use std::sync::Arc;
+use std::{env, fs};
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// Source text of the files diagnostics point into. Files that were not added
+/// are read from disk the first time they are needed.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: HashMap<String, Option<Arc<str>>>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, file: &str, text: impl Into<Arc<str>>) {
+        self.files.insert(file.to_string(), Some(text.into()));
+    }
+
+    /// Adds every file of `other`.
+    pub fn extend(&mut self, other: SourceMap) {
+        self.files.extend(other.files);
+    }
+
+    /// The text of `file`, or `None` if it was not added and cannot be read.
+    pub fn load(&mut self, file: &str) -> Option<Arc<str>> {
+        self.files
+            .entry(file.to_string())
+            .or_insert_with(|| fs::read_to_string(file).ok().map(Arc::from))
+            .clone()
+    }
+}
+
+/// Whether diagnostics written to stderr should be colored.
+pub fn color_enabled() -> bool {
+    std::io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+/// Writes the diagnostics to stderr as `report` renders them, colored if
+/// `color_enabled`.
+pub fn emit(diagnostics: &[Diagnostic], sources: &mut SourceMap) {
+    eprint!("{}", report(diagnostics, sources, color_enabled()));
+}
+
+/// The diagnostics in reading order, followed by the end-of-run line, such
+/// as `error: aborting due to 2 previous errors; 1 warning emitted (1
+/// unused)`, when there were errors or warnings. When they span several
+/// files, each file's diagnostics come under a header naming it.
+pub fn report(diagnostics: &[Diagnostic], sources: &mut SourceMap, color: bool) -> String {
+    let style = Style { color };
+    let mut diagnostics = diagnostics.to_vec();
+    sort_diagnostics(&mut diagnostics);
+
+    let grouped = diagnostics
+        .windows(2)
+        .any(|pair| pair[0].span.file != pair[1].span.file);
+    let mut output = String::new();
+    let mut file = None;
+    for diagnostic in &diagnostics {
+        if grouped && file != Some(&diagnostic.span.file) {
+            file = Some(&diagnostic.span.file);
+            let name = match diagnostic.span.file.as_str() {
+                "" => "(no file)",
+                name => name,
+            };
+            let header = format!("── {} ──", name);
+            let _ = writeln!(output, "{}", style.paint(BOLD, &header));
+        }
+        let _ = writeln!(output, "{}", render(diagnostic, sources, color));
+    }
+
+    let counts = DiagnosticCounts::of(&diagnostics);
+    if let (Some(level), Some(summary)) = (counts.summary_level(), counts.summary()) {
+        let level_color = if level == Level::Error { RED } else { YELLOW };
+        let _ = writeln!(
+            output,
+            "{}{}",
+            style.paint(level_color, &level.to_string()),
+            style.paint(BOLD, &format!(": {}", summary))
+        );
+    }
+    output
+}
+
+/// Writes the diagnostics to stderr in reading order as JSON, one object per
+/// line, for editors and other tools.
+pub fn emit_json(diagnostics: &[Diagnostic]) {
+    let mut diagnostics = diagnostics.to_vec();
+    sort_diagnostics(&mut diagnostics);
+    for diagnostic in &diagnostics {
+        let json = serde_json::to_string(diagnostic).expect("diagnostics serialize");
+        eprintln!("{}", json);
+    }
+}
+
+/// Writes a JSON object to stderr saying that the run failed with `message`
+/// after reporting what `counts` counts, as the last line after `emit_json`.
+pub fn emit_json_failure(counts: &DiagnosticCounts, message: &str) {
+    let json = serde_json::json!({ "summary": counts, "message": message });
+    eprintln!("{}", json);
+}
+
+/// Renders `diagnostic` with its source lines. Spans whose file cannot be
+/// loaded, or that point outside it, get the header without a snippet.
+pub fn render(diagnostic: &Diagnostic, sources: &mut SourceMap, color: bool) -> String {
+    let style = Style { color };
+    let level_color = match diagnostic.level {
+        Level::Error => RED,
+        Level::Warning => YELLOW,
+        Level::Note => GREEN,
+    };
+
+    let mut output = String::new();
+    let mut title = diagnostic.level.to_string();
+    if let Some(code) = &diagnostic.code {
+        let _ = write!(title, "[{}]", code);
+    }
+    output.push_str(&style.paint(level_color, &title));
+    output.push_str(&style.paint(BOLD, &format!(": {}", diagnostic.message)));
+
+    // Room for the widest line number shown, so every gutter lines up.
+    let width = std::iter::once(&diagnostic.span)
+        .chain(diagnostic.labels.iter().map(|label| &label.span))
+        .map(|span| span.start_line.to_string().len())
+        .max()
+        .unwrap_or(1);
+    let gutter = " ".repeat(width);
+
+    // Errors found outside any source, such as bad arguments, have no span.
+    if diagnostic.span != Span::default() {
+        let _ = write!(
+            output,
+            "\n{}{} {}",
+            gutter,
+            style.paint(BLUE, "-->"),
+            diagnostic.span
+        );
+    }
+    let mut snippets = vec![(&diagnostic.span, '^', level_color, "")];
+    snippets.extend(
+        diagnostic
+            .labels
+            .iter()
+            .map(|label| (&label.span, '-', BLUE, label.message.as_str())),
+    );
+
+    let mut file = &diagnostic.span.file;
+    for (span, marker, marker_color, message) in snippets {
+        let Some(line) = source_line(sources, span) else {
+            if !message.is_empty() {
+                let _ = write!(
+                    output,
+                    "\n{} {} {}: {}",
+                    gutter,
+                    style.paint(BLUE, "="),
+                    span,
+                    message
+                );
+            }
+            continue;
+        };
+        if &span.file != file {
+            let _ = write!(output, "\n{}{} {}", gutter, style.paint(BLUE, ":::"), span);
+            file = &span.file;
+        }
+
+        let bar = style.paint(BLUE, "|");
+        let number = style.paint(BLUE, &format!("{:>width$}", span.start_line));
+        let (padding, length) = underline(&line, span);
+        let mut markers = style.paint(marker_color, &marker.to_string().repeat(length));
+        if !message.is_empty() {
+            markers.push(' ');
+            markers.push_str(&style.paint(marker_color, message));
+        }
+        let _ = write!(
+            output,
+            "\n{gutter} {bar}\n{number} {bar} {line}\n{gutter} {bar} {padding}{markers}"
+        );
+    }
+
+    for note in diagnostic
+        .notes
+        .iter()
+        .cloned()
+        .chain(diagnostic.category_note())
+    {
+        let _ = write!(
+            output,
+            "\n{} {} {}",
+            gutter,
+            style.paint(BLUE, "= note:"),
+            note
+        );
+    }
+    for help in &diagnostic.help {
+        let _ = write!(
+            output,
+            "\n{} {} {}",
+            gutter,
+            style.paint(BLUE, "= help:"),
+            help
+        );
+    }
+    for suggestion in &diagnostic.suggestions {
+        let _ = write!(
+            output,
+            "\n{} {} {}",
+            gutter,
+            style.paint(BLUE, "= help:"),
+            suggestion.help()
+        );
+    }
+    output
+}
+
+/// The line `span` starts on, without its line ending.
+fn source_line(sources: &mut SourceMap, span: &Span) -> Option<String> {
+    let text = sources.load(&span.file)?;
+    let index = span.start_line.checked_sub(1)?;
+    let line = text.split('\n').nth(index)?;
+    Some(line.strip_suffix('\r').unwrap_or(line).to_string())
+}
+
+/// Whitespace up to the span's start column and the number of markers under
+/// it. The whitespace copies tabs from the line so markers stay aligned; a
+/// span running past the line is underlined to its end.
+fn underline(line: &str, span: &Span) -> (String, usize) {
+    let start = span.start_column.saturating_sub(1);
+    let padding = line
+        .chars()
+        .take(start)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let line_length = line.chars().count();
+    let end = if span.end_line == span.start_line {
+        span.end_column.saturating_sub(1).min(line_length)
+    } else {
+        line_length
+    };
+    (padding, end.saturating_sub(start).max(1))
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
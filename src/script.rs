@@ -0,0 +1,166 @@
+//! Script mode: `rustic script file.rsc -- args` compiles a single file to a
+//! native binary on first run, caches it per user keyed by a hash of the
+//! source, and reuses it on later runs. A leading `#!` line is ignored so
+//! scripts can be made executable. `interpret` runs a script with the
+//! interpreter instead, which skips rustc at the cost of running slower.
+
+use crate::compiler::ast::{Item, Program};
+use crate::compiler::cache::StableHasher;
+use crate::compiler::codegen::CodeGenerator;
+use crate::compiler::interpreter::{self, Interpreter};
+use crate::compiler::lexer::Lexer;
+use crate::compiler::parser::Parser;
+use crate::compiler::rustc;
+use crate::compiler::semantic::{SemanticAnalyzer, TypeTable};
+use crate::diagnostics::{DiagnosticEngine, Error, Result};
+use crate::utils;
+use std::env;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Overrides the directory cached script binaries are kept in.
+pub const CACHE_DIR_ENV: &str = "RUSTIC_CACHE_DIR";
+
+/// `$RUSTIC_CACHE_DIR`, else the platform's per-user cache directory.
+pub fn cache_dir() -> PathBuf {
+    if let Some(dir) = env::var_os(CACHE_DIR_ENV) {
+        return PathBuf::from(dir);
+    }
+    let base = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Caches"))
+    } else {
+        env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    };
+    base.unwrap_or_else(env::temp_dir)
+        .join("rustic")
+        .join("scripts")
+}
+
+/// Returns the cached binary for `path`, building it first when the source
+/// changed since the last build or `rebuild` is set.
+pub fn prepare(path: &Path, rebuild: bool, diagnostics: &mut DiagnosticEngine) -> Result<PathBuf> {
+    let source = read(path)?;
+
+    let module_name = utils::module_name(path, "script");
+    let mut hasher = StableHasher::default();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    source.hash(&mut hasher);
+    let binary_name = format!(
+        "{}-{:016x}{}",
+        module_name,
+        hasher.finish(),
+        env::consts::EXE_SUFFIX
+    );
+
+    let dir = cache_dir();
+    let binary = dir.join(binary_name);
+    if binary.is_file() && !rebuild {
+        return Ok(binary);
+    }
+
+    let (program, types) = check(path, &source, diagnostics)?;
+    let code = CodeGenerator::new(&types).generate(&program, &module_name)?;
+
+    fs::create_dir_all(&dir)
+        .map_err(|e| Error::IoError(format!("Failed to create {}: {}", dir.display(), e)))?;
+
+    // Build under a process-unique name and rename, so concurrent runs of the
+    // same script never execute a half-written binary.
+    let staging = dir.join(format!("{}_{}.tmp", module_name, std::process::id()));
+    let rust_file = staging.with_extension("rs");
+    fs::write(&rust_file, code)
+        .map_err(|e| Error::IoError(format!("Failed to write {}: {}", rust_file.display(), e)))?;
+
+    let output = Command::new("rustc")
+        .args(["--edition", "2021", "--crate-name", "script", "-O"])
+        .arg("--error-format=json")
+        .arg("-o")
+        .arg(&staging)
+        .arg(&rust_file)
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            let _ = fs::remove_file(&rust_file);
+            return Err(Error::CompilationError(format!("Failed to run rustc: {}", e)));
+        }
+    };
+
+    if !output.status.success() {
+        // Errors are mapped through the generated file, so it goes after.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let details = rustc::report(&dir, rustc::rustc_errors(&stderr), &stderr, diagnostics);
+        let _ = fs::remove_file(&rust_file);
+        let _ = fs::remove_file(&staging);
+        return Err(Error::CompilationError(format!("rustc failed:\n{}", details)));
+    }
+    let _ = fs::remove_file(&rust_file);
+
+    fs::rename(&staging, &binary)
+        .map_err(|e| Error::IoError(format!("Failed to write {}: {}", binary.display(), e)))?;
+    Ok(binary)
+}
+
+/// Checks the script at `path` and runs it with the interpreter, passing it
+/// `args`. Nothing is built or cached.
+pub fn interpret(path: &Path, args: Vec<String>, diagnostics: &mut DiagnosticEngine) -> Result<()> {
+    let source = read(path)?;
+    let (program, types) = check(path, &source, diagnostics)?;
+    interpreter::with_stack(|| {
+        let mut interpreter = Interpreter::new(&program, &types);
+        interpreter.set_args(args);
+        interpreter.run()
+    })
+}
+
+fn read(path: &Path) -> Result<String> {
+    let source = fs::read_to_string(path)
+        .map_err(|e| Error::IoError(format!("Failed to read file {}: {}", path.display(), e)))?;
+    Ok(strip_shebang(&source))
+}
+
+/// Parses and checks the script `source`, which must define `main`, and
+/// returns it with its constants folded.
+fn check(
+    path: &Path,
+    source: &str,
+    diagnostics: &mut DiagnosticEngine,
+) -> Result<(Program, TypeTable)> {
+    let file_name = path.to_string_lossy();
+    diagnostics.add_source(&file_name, source);
+    let tokens = Lexer::new(source, &file_name).tokenize_reporting(diagnostics);
+    let program = Parser::new(tokens, diagnostics).parse()?;
+    if !program
+        .items
+        .iter()
+        .any(|item| matches!(item, Item::Function(f) if f.name == "main"))
+    {
+        return Err(Error::CompilationError(format!(
+            "{} has no `main` function to run",
+            path.display()
+        )));
+    }
+    let mut analyzer = SemanticAnalyzer::new(diagnostics);
+    let types = analyzer.analyze(&program)?;
+    let folded = analyzer
+        .take_folded()
+        .expect("analyze folds the program it checks");
+    Ok((folded, types))
+}
+
+/// Blanks a leading `#!` line, keeping the newline so line numbers still match.
+fn strip_shebang(source: &str) -> String {
+    match source.strip_prefix("#!") {
+        Some(rest) => rest
+            .find('\n')
+            .map(|index| rest[index..].to_string())
+            .unwrap_or_default(),
+        None => source.to_string(),
+    }
+}
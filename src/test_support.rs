@@ -0,0 +1,490 @@
+//! Golden-file helpers for the compiler stages, enabled by the `test-support` feature.
+//!
+//! Each stage renders a `.rsc` source to stable text (tokens, AST JSON, diagnostics,
+//! diagnostics as the compiler prints them, generated Rust or what the interpreted program
+//! prints) that can be compared against a checked-in snapshot. Setting
+//! `RUSTIC_UPDATE_SNAPSHOTS=1` rewrites snapshots instead of comparing them.
+
+use crate::api::{CompileOptions, Compiler};
+use crate::compiler::ast::Program;
+use crate::compiler::ast_json;
+use crate::compiler::codegen::{self, CodeGenerator};
+use crate::compiler::interpreter::{self, Interpreter};
+use crate::compiler::lexer::{Lexer, Token};
+use crate::compiler::parser::Parser;
+use crate::compiler::printer::to_source;
+use crate::compiler::semantic::{SemanticAnalyzer, TypeTable};
+use crate::compiler::Emit;
+use crate::diagnostics::{Category, Diagnostic, DiagnosticEngine, LintLevel, Result};
+use crate::render::{self, SourceMap};
+use crate::utils;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+pub const UPDATE_ENV: &str = "RUSTIC_UPDATE_SNAPSHOTS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Tokens,
+    Ast,
+    Diagnostics,
+    Rendered,
+    Rust,
+    Output,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::Tokens,
+        Stage::Ast,
+        Stage::Diagnostics,
+        Stage::Rendered,
+        Stage::Rust,
+        Stage::Output,
+    ];
+
+    /// Extension of the snapshot file written next to the source, e.g. `hello.generated.rs`.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Stage::Tokens => "tokens",
+            Stage::Ast => "ast.json",
+            Stage::Diagnostics => "diagnostics",
+            Stage::Rendered => "rendered",
+            Stage::Rust => "generated.rs",
+            Stage::Output => "output",
+        }
+    }
+}
+
+/// Other modules a source may import, by name.
+pub type Modules = [(String, Arc<Program>)];
+
+pub fn render(stage: Stage, source: &str, file_name: &str, modules: &Modules) -> String {
+    match stage {
+        Stage::Tokens => render_tokens(source, file_name),
+        Stage::Ast => render_ast(source, file_name),
+        Stage::Diagnostics => render_diagnostics(source, file_name, modules),
+        Stage::Rendered => render_snippets(source, file_name, modules),
+        Stage::Rust => render_rust(source, file_name, modules),
+        Stage::Output => render_output(source, file_name, modules),
+    }
+}
+
+/// One token per line: `line:column-line:column Kind`.
+pub fn render_tokens(source: &str, file_name: &str) -> String {
+    match Lexer::new(source, file_name).tokenize() {
+        Ok(tokens) => tokens.iter().fold(String::new(), |mut output, token| {
+            let span = &token.span;
+            let _ = writeln!(
+                output,
+                "{}:{}-{}:{} {:?}",
+                span.start_line,
+                span.start_column,
+                span.end_line,
+                span.end_column,
+                token.token_type
+            );
+            output
+        }),
+        Err(error) => format!("{}\n", error),
+    }
+}
+
+/// Pretty-printed AST JSON with object keys sorted, so struct initializers are stable.
+pub fn render_ast(source: &str, file_name: &str) -> String {
+    let mut diagnostics = DiagnosticEngine::new();
+    match parse(source, file_name, &mut diagnostics) {
+        Ok(program) => {
+            let value = serde_json::to_value(&program).expect("AST is always serializable");
+            let mut json =
+                serde_json::to_string_pretty(&value).expect("AST is always serializable");
+            json.push('\n');
+            json
+        }
+        Err(error) => format!(
+            "{}\n{}",
+            error,
+            format_diagnostics(diagnostics.diagnostics())
+        ),
+    }
+}
+
+/// Every diagnostic the full pipeline reports, followed by the final error if any.
+pub fn render_diagnostics(source: &str, file_name: &str, modules: &Modules) -> String {
+    let mut diagnostics = DiagnosticEngine::new();
+    let result = generate(source, file_name, modules, &mut diagnostics);
+
+    let mut output = format_diagnostics(diagnostics.diagnostics());
+    if let Err(error) = result {
+        let _ = writeln!(output, "{}", error);
+    }
+    output
+}
+
+/// Every diagnostic from checking the modules under `dir` together as a project, the way
+/// `rustic check <dir>` does, followed by the final error if any.
+pub fn render_project_diagnostics(dir: impl AsRef<Path>) -> String {
+    let options = CompileOptions::new()
+        .emit(Emit::Check)
+        .output_dir(std::env::temp_dir().join("rustic-project-diagnostics"));
+    match Compiler::new(options).compile(dir) {
+        Ok(output) => format_diagnostics(&output.diagnostics),
+        Err(error) => {
+            let mut output = format_diagnostics(&error.diagnostics);
+            let _ = writeln!(output, "{}", error);
+            output
+        }
+    }
+}
+
+/// Every diagnostic the full pipeline reports as `rustic` prints it, with annotated source
+/// snippets but no color, followed by the final error if any.
+pub fn render_snippets(source: &str, file_name: &str, modules: &Modules) -> String {
+    let mut diagnostics = DiagnosticEngine::new();
+    let result = generate(source, file_name, modules, &mut diagnostics);
+
+    let mut sources = SourceMap::new();
+    sources.add(file_name, source);
+    let mut output = render::report(diagnostics.diagnostics(), &mut sources, false);
+    if let Err(error) = result {
+        let _ = writeln!(output, "{}", error);
+    }
+    output
+}
+
+/// The generated Rust module, formatted as `rustic` writes it, or the error
+/// that stopped the pipeline.
+pub fn render_rust(source: &str, file_name: &str, modules: &Modules) -> String {
+    let mut diagnostics = DiagnosticEngine::new();
+    match generate(source, file_name, modules, &mut diagnostics) {
+        Ok(code) => codegen::format(&code),
+        Err(error) => format!("{}\n", error),
+    }
+}
+
+/// What the program prints when interpreted, followed by the error that
+/// stopped it if any. Input is empty and there are no arguments.
+pub fn render_output(source: &str, file_name: &str, modules: &Modules) -> String {
+    let mut diagnostics = DiagnosticEngine::new();
+    let (program, types) = match analyze(source, file_name, modules, &mut diagnostics) {
+        Ok(checked) => checked,
+        Err(error) => return format!("{}\n", error),
+    };
+    let mut output = Vec::new();
+    let result = interpreter::with_stack(|| {
+        let mut interpreter = Interpreter::new(&program, &types);
+        interpreter.set_output(&mut output);
+        interpreter.set_input(std::io::empty());
+        interpreter.run()
+    });
+
+    let mut output = String::from_utf8_lossy(&output).into_owned();
+    if let Err(error) = result {
+        let _ = writeln!(output, "{}", error);
+    }
+    output
+}
+
+fn parse(source: &str, file_name: &str, diagnostics: &mut DiagnosticEngine) -> Result<Program> {
+    let tokens: Vec<Token> = Lexer::new(source, file_name).tokenize_reporting(diagnostics);
+    Parser::new(tokens, diagnostics).parse()
+}
+
+fn generate(
+    source: &str,
+    file_name: &str,
+    modules: &Modules,
+    diagnostics: &mut DiagnosticEngine,
+) -> Result<String> {
+    let (program, types) = analyze(source, file_name, modules, diagnostics)?;
+    let module_name = module_name(file_name);
+    let mut codegen = CodeGenerator::new(&types);
+    for (name, module) in modules.iter().filter(|(name, _)| *name != module_name) {
+        codegen.add_module(name, Arc::clone(module));
+    }
+    codegen.generate(&program, &module_name)
+}
+
+fn analyze(
+    source: &str,
+    file_name: &str,
+    modules: &Modules,
+    diagnostics: &mut DiagnosticEngine,
+) -> Result<(Program, TypeTable)> {
+    diagnostics.add_source(file_name, source);
+    let program = parse(source, file_name, diagnostics)?;
+    let module_name = module_name(file_name);
+    let mut analyzer = SemanticAnalyzer::new(diagnostics);
+    for (name, module) in modules.iter().filter(|(name, _)| *name != module_name) {
+        analyzer.add_module(name, Arc::clone(module));
+    }
+    let types = analyzer.analyze(&program)?;
+    let folded = analyzer
+        .take_folded()
+        .expect("analyze folds the program it checks");
+    Ok((folded, types))
+}
+
+/// The module in `file_name`, a path relative to the golden directory whose
+/// directories nest it as in `import net.http`.
+fn module_name(file_name: &str) -> String {
+    let path = Path::new(file_name);
+    let directories = path.parent().into_iter().flat_map(Path::components);
+    directories
+        .filter_map(|component| component.as_os_str().to_str())
+        .chain([utils::module_name(path, "main").as_str()])
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn format_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .fold(String::new(), |mut output, diagnostic| {
+            let span = &diagnostic.span;
+            let code = match &diagnostic.code {
+                Some(code) => format!("[{}]", code),
+                None => String::new(),
+            };
+            let _ = writeln!(
+                output,
+                "{}{} {}:{}-{}:{} {}",
+                diagnostic.level,
+                code,
+                span.start_line,
+                span.start_column,
+                span.end_line,
+                span.end_column,
+                diagnostic.message
+            );
+            output
+        })
+}
+
+pub fn update_requested() -> bool {
+    std::env::var(UPDATE_ENV).is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Compares `actual` against the snapshot at `path`, or rewrites it when updating.
+pub fn check_snapshot(path: &Path, actual: &str) -> std::result::Result<(), String> {
+    if update_requested() {
+        return fs::write(path, actual)
+            .map_err(|e| format!("{}: failed to write snapshot: {}", path.display(), e));
+    }
+
+    let expected = fs::read_to_string(path).map_err(|_| {
+        format!(
+            "{}: snapshot missing; rerun with {}=1 to create it",
+            path.display(),
+            UPDATE_ENV
+        )
+    })?;
+    if expected == actual {
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "{}: snapshot mismatch; rerun with {}=1 to accept\n",
+        path.display(),
+        UPDATE_ENV
+    );
+    for (index, (expected, actual)) in expected.lines().zip(actual.lines()).enumerate() {
+        if expected != actual {
+            let _ = write!(
+                message,
+                "  first difference at line {}:\n  - {}\n  + {}",
+                index + 1,
+                expected,
+                actual
+            );
+            return Err(message);
+        }
+    }
+    let _ = write!(
+        message,
+        "  expected {} lines, found {}",
+        expected.lines().count(),
+        actual.lines().count()
+    );
+    Err(message)
+}
+
+pub fn assert_snapshot(path: impl AsRef<Path>, actual: &str) {
+    if let Err(message) = check_snapshot(path.as_ref(), actual) {
+        panic!("{}", message);
+    }
+}
+
+/// Renders every `.rsc` file under `dir` through `stages` and checks each
+/// against `<stem>.<stage extension>` beside it, reporting all mismatches at
+/// once. The files are modules of one program, so each can import the
+/// others, those in subdirectories as in `import net.http`.
+pub fn check_golden_dir(dir: impl AsRef<Path>, stages: &[Stage]) {
+    let dir = dir.as_ref();
+    let sources = golden_sources(dir);
+    let file_name = |path: &Path| {
+        let relative = path.strip_prefix(dir).unwrap_or(path);
+        relative.to_string_lossy().replace('\\', "/")
+    };
+    let modules: Vec<(String, Arc<Program>)> = sources
+        .iter()
+        .filter_map(|(path, source)| {
+            let file_name = file_name(path);
+            let program = parse(source, &file_name, &mut DiagnosticEngine::new()).ok()?;
+            Some((module_name(&file_name), Arc::new(program)))
+        })
+        .collect();
+
+    let mut failures = Vec::new();
+    for (source_path, source) in sources {
+        let file_name = file_name(&source_path);
+        for &stage in stages {
+            let snapshot = source_path.with_extension(stage.extension());
+            let actual = render(stage, &source, &file_name, &modules);
+            if let Err(message) = check_snapshot(&snapshot, &actual) {
+                failures.push(message);
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!(
+            "{} golden snapshot(s) failed:\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+/// Compiles the modules under `dir` together as a release build, which leaves
+/// out what `main` never reaches, and checks the Rust generated for each
+/// against `<stem>.generated.rs` beside its source.
+pub fn check_pruned_dir(dir: impl AsRef<Path>) {
+    let dir = dir.as_ref();
+    let output_dir = std::env::temp_dir().join(format!("rustic-pruned-{}", std::process::id()));
+    let options = CompileOptions::new()
+        .output_dir(&output_dir)
+        .eliminate_dead_code(true)
+        .lint_level(Category::Unused, LintLevel::Allow);
+    let output = Compiler::new(options)
+        .compile(dir)
+        .unwrap_or_else(|e| panic!("{}: cannot compile: {}", dir.display(), e));
+
+    let source_dir = format!("{}/", dir.display());
+    let mut failures = Vec::new();
+    for file in output
+        .files
+        .iter()
+        .filter(|file| file.extension() == Some("rs".as_ref()))
+    {
+        let generated = fs::read_to_string(file)
+            .unwrap_or_else(|e| panic!("{}: cannot read generated code: {}", file.display(), e));
+        let relative = file.strip_prefix(&output_dir).unwrap_or(file);
+        let snapshot = dir.join(relative).with_extension(Stage::Rust.extension());
+        if let Err(message) = check_snapshot(&snapshot, &generated.replace(&source_dir, "")) {
+            failures.push(message);
+        }
+    }
+    let _ = fs::remove_dir_all(&output_dir);
+
+    if !failures.is_empty() {
+        panic!(
+            "{} pruned snapshot(s) failed:\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+/// Checks that printing each parseable `.rsc` file in `dir` with `to_source`
+/// and parsing the result gives back the same AST.
+pub fn check_round_trip_dir(dir: impl AsRef<Path>) {
+    let failures: Vec<String> = golden_sources(dir.as_ref())
+        .into_iter()
+        .filter_map(|(path, source)| check_round_trip(&source, &path.display().to_string()).err())
+        .collect();
+
+    if !failures.is_empty() {
+        panic!(
+            "{} source(s) failed to round-trip:\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+/// Prints `source`'s AST and reparses it, comparing the two ASTs without spans,
+/// and checks the AST survives AST JSON unchanged. Sources that do not parse
+/// have nothing to round-trip and pass.
+pub fn check_round_trip(source: &str, file_name: &str) -> std::result::Result<(), String> {
+    let mut diagnostics = DiagnosticEngine::new();
+    let Ok(program) = parse(source, file_name, &mut diagnostics) else {
+        return Ok(());
+    };
+
+    let json = ast_json::to_json(&program);
+    match ast_json::from_json(&json, file_name) {
+        Ok(read) if read == program => {}
+        Ok(_) => return Err(format!("{}: AST JSON reads back differently", file_name)),
+        Err(error) => return Err(format!("{}: AST JSON rejected: {}", file_name, error)),
+    }
+
+    let printed = to_source(&program);
+    let reparsed = parse(&printed, file_name, &mut diagnostics).map_err(|error| {
+        format!(
+            "{}: printed source does not parse: {}\n{}",
+            file_name, error, printed
+        )
+    })?;
+
+    if without_spans(&program) != without_spans(&reparsed) {
+        return Err(format!(
+            "{}: printed source parses to a different AST:\n{}",
+            file_name, printed
+        ));
+    }
+    Ok(())
+}
+
+fn without_spans(program: &Program) -> serde_json::Value {
+    fn strip(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                map.retain(|key, _| key != "span" && !key.ends_with("_span"));
+                map.values_mut().for_each(strip);
+            }
+            serde_json::Value::Array(values) => values.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+
+    let mut value = serde_json::to_value(program).expect("AST is always serializable");
+    strip(&mut value);
+    value
+}
+
+fn golden_sources(dir: &Path) -> Vec<(PathBuf, String)> {
+    let mut paths: Vec<PathBuf> = WalkDir::new(dir)
+        .into_iter()
+        .map(|entry| {
+            entry.unwrap_or_else(|e| {
+                panic!("{}: cannot read golden directory: {}", dir.display(), e)
+            })
+        })
+        .map(|entry| entry.into_path())
+        .filter(|path| utils::is_rustic_source(path))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let source = fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("{}: cannot read source: {}", path.display(), e));
+            (path, source)
+        })
+        .collect()
+}
@@ -0,0 +1,24 @@
+use std::path::Path;
+
+pub const SOURCE_EXTENSION: &str = "rsc";
+
+/// File name suffix of AST JSON documents, as in `main.ast.json`.
+pub const AST_JSON_SUFFIX: &str = ".ast.json";
+
+pub fn is_rustic_source(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some(SOURCE_EXTENSION)
+}
+
+pub fn is_ast_json(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|s| s.to_str())
+        .is_some_and(|name| name.ends_with(AST_JSON_SUFFIX))
+}
+
+/// Derives a module name from a source file's stem, falling back to `default`.
+pub fn module_name(path: &Path, default: &str) -> String {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(default)
+        .to_string()
+}
@@ -0,0 +1,482 @@
+use rustic::compiler::ast::{
+    walk_call, walk_expression_mut, BinaryOp, BinaryOperator, Expression, FunctionCall, Identifier,
+    Item, Program, Visitor, VisitorMut, AST_SCHEMA_VERSION,
+};
+use rustic::compiler::lexer::Token;
+use rustic::compiler::printer;
+use rustic::compiler::semantic::TypeTable;
+use rustic::compiler::{Emit, InputFormat, RusticCompiler};
+use rustic::diagnostics::DiagnosticEngine;
+use rustic::{Category, CompileOptions, Compiler, CompilerCallbacks, Diagnostic, Error, Level};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A temporary project holding `files`, by name.
+fn project(files: &[(&str, &str)]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, source) in files {
+        fs::write(dir.path().join(name), source).unwrap();
+    }
+    dir
+}
+
+fn compiler(output: &Path) -> Compiler {
+    Compiler::new(CompileOptions::new().output_dir(output))
+}
+
+#[test]
+fn the_library_compiles_a_project_like_the_binary() {
+    let project = project(&[
+        (
+            "main.rsc",
+            "import util\n\nfn main() {\n  let unused = util.double(2)\n}\n",
+        ),
+        (
+            "util.rsc",
+            "pub fn double(x: int) -> int {\n  return x * 2\n}\n",
+        ),
+    ]);
+    let output_dir = project.path().join("out");
+
+    let output = compiler(&output_dir).compile(project.path()).unwrap();
+
+    let mut files = output.files.clone();
+    files.sort();
+    assert_eq!(
+        files,
+        [output_dir.join("main.rs"), output_dir.join("util.rs")]
+    );
+    assert!(fs::read_to_string(output_dir.join("util.rs"))
+        .unwrap()
+        .contains("pub fn double"));
+    let util = output.module("util").unwrap();
+    assert!(matches!(&util.items[0], Item::Function(function) if function.name == "double"));
+    assert_eq!(output.counts().warnings, 1);
+    assert_eq!(output.diagnostics[0].message, "Unused variable `unused`");
+}
+
+#[test]
+fn a_failed_compilation_keeps_its_diagnostics() {
+    let project = project(&[("main.rsc", "fn main() {\n  let x: int = \"a\"\n}\n")]);
+
+    let error = compiler(&project.path().join("out"))
+        .compile(project.path().join("main.rsc"))
+        .unwrap_err();
+
+    assert_eq!(error.counts().errors, 1);
+    let diagnostic = &error.diagnostics[0];
+    assert_eq!(diagnostic.level, Level::Error);
+    assert_eq!(diagnostic.code.as_deref(), Some("E0300"));
+    assert_eq!(
+        (diagnostic.span.start_line, diagnostic.span.start_column),
+        (2, 3)
+    );
+}
+
+#[test]
+fn compile_str_returns_rust_without_touching_the_disk() {
+    let mut diagnostics = DiagnosticEngine::new();
+    let code = RusticCompiler::new(&mut diagnostics)
+        .compile_str(
+            "pub fn square(x: int) -> int {\n  return x * x\n}\n",
+            "shapes",
+        )
+        .unwrap();
+    assert!(code.contains("pub fn square(x: i64) -> i64"), "{}", code);
+    assert!(diagnostics.diagnostics().is_empty());
+    assert!(!Path::new("shapes.rs").exists());
+
+    let mut diagnostics = DiagnosticEngine::new();
+    let result =
+        RusticCompiler::new(&mut diagnostics).compile_str("fn f() -> int {\n}\n", "broken");
+    assert!(result.is_err());
+    let span = &diagnostics.diagnostics()[0].span;
+    assert_eq!((span.file.as_str(), span.start_line), ("broken", 2));
+}
+
+/// Collects the names of called functions and of every identifier used.
+#[derive(Default)]
+struct Names {
+    calls: Vec<String>,
+    identifiers: Vec<String>,
+}
+
+impl Visitor for Names {
+    fn visit_call(&mut self, call: &FunctionCall) {
+        if let Expression::Identifier(callee) = call.function.as_ref() {
+            self.calls.push(callee.name.to_string());
+        }
+        walk_call(self, call);
+    }
+
+    fn visit_identifier(&mut self, identifier: &Identifier) {
+        self.identifiers.push(identifier.name.to_string());
+    }
+}
+
+/// Rewrites calls of `add(a, b)` into `a + b`, innermost first.
+struct InlineAdd;
+
+impl VisitorMut for InlineAdd {
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression);
+        let Expression::Call(call) = expression else {
+            return;
+        };
+        let is_add = matches!(
+            call.function.as_ref(),
+            Expression::Identifier(callee) if callee.name == "add"
+        );
+        if let (true, [left, right]) = (is_add, call.arguments.as_slice()) {
+            *expression = Expression::Binary(BinaryOp {
+                left: Box::new(left.clone()),
+                operator: BinaryOperator::Add,
+                right: Box::new(right.clone()),
+                span: call.span.clone(),
+            });
+        }
+    }
+}
+
+const VISITED: &str =
+    "fn main() {\n    let total = add(1, 2)\n    print_it(add(total, add(total, 3)))\n}\n";
+
+#[test]
+fn visitors_reach_nested_expressions() {
+    let (program, diagnostics) = rustic::parse(VISITED, "visited.rsc");
+    assert!(diagnostics.is_empty());
+
+    let mut names = Names::default();
+    names.visit_program(&program.unwrap());
+    assert_eq!(names.calls, ["add", "print_it", "add", "add"]);
+    assert_eq!(
+        names.identifiers,
+        ["add", "print_it", "add", "total", "add", "total"]
+    );
+}
+
+#[test]
+fn mutable_visitors_rewrite_the_tree() {
+    let mut program = rustic::parse(VISITED, "visited.rsc").0.unwrap();
+    InlineAdd.visit_program_mut(&mut program);
+    assert_eq!(
+        printer::to_source(&program),
+        "fn main() {\n    let total = 1 + 2\n    print_it(total + (total + 3))\n}\n"
+    );
+}
+
+#[test]
+fn parse_returns_the_ast_without_checking_it() {
+    let (program, diagnostics) =
+        rustic::parse("fn main() {\n  let x: int = \"a\"\n}\n", "unchecked.rsc");
+    assert!(diagnostics.is_empty());
+    let program = program.unwrap();
+    assert!(matches!(&program.items[..], [Item::Function(main)] if main.name == "main"));
+
+    let (program, diagnostics) = rustic::parse("fn main( {\n}\n", "broken.rsc");
+    assert!(program.is_none());
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].span.file, "broken.rsc");
+}
+
+#[test]
+fn ast_json_documents_compile_like_their_sources() {
+    let project = project(&[
+        (
+            "main.rsc",
+            "import util\n\nfn main() {\n  util.show(util.double(2))\n}\n",
+        ),
+        (
+            "util.rsc",
+            "import io\n\npub fn double(x: int) -> int {\n  return x * 2\n}\n\npub fn show(x: int) {\n  io.print(x)\n}\n",
+        ),
+    ]);
+    let from_source = project.path().join("from-source");
+    let documents = project.path().join("documents");
+    let from_documents = project.path().join("from-documents");
+
+    compiler(&from_source).compile(project.path()).unwrap();
+    Compiler::new(
+        CompileOptions::new()
+            .output_dir(&documents)
+            .emit(Emit::AstJson),
+    )
+    .compile(project.path())
+    .unwrap();
+    Compiler::new(
+        CompileOptions::new()
+            .output_dir(&from_documents)
+            .input_format(InputFormat::AstJson),
+    )
+    .compile(&documents)
+    .unwrap();
+
+    for file in ["main.rs", "util.rs"] {
+        assert_eq!(
+            fs::read_to_string(from_documents.join(file)).unwrap(),
+            fs::read_to_string(from_source.join(file)).unwrap(),
+            "{}",
+            file
+        );
+    }
+}
+
+#[test]
+fn ast_json_documents_of_another_schema_are_rejected() {
+    let project = project(&[(
+        "main.ast.json",
+        r#"{"schema_version": 0, "program": {"items": []}}"#,
+    )]);
+
+    let error = Compiler::new(
+        CompileOptions::new()
+            .output_dir(project.path().join("out"))
+            .input_format(InputFormat::AstJson),
+    )
+    .compile(project.path().join("main.ast.json"))
+    .unwrap_err();
+
+    let expected = format!(
+        "AST schema version 0 is not supported (expected {})",
+        AST_SCHEMA_VERSION
+    );
+    assert!(error.to_string().ends_with(&expected), "{}", error);
+}
+
+#[test]
+fn diagnostics_are_structured_values_that_round_trip_through_json() {
+    let mut diagnostics = DiagnosticEngine::new();
+    let result = RusticCompiler::new(&mut diagnostics).compile_str(
+        "fn twice(x: int) -> int {\n  return x * 2\n}\n\nfn main() {\n  let y: int = twice(\"a\")\n}\n",
+        "typed",
+    );
+    assert!(result.is_err());
+    let diagnostics = diagnostics.take();
+    let [error, warning] = diagnostics.as_slice() else {
+        panic!("expected an error and a warning, got {:?}", diagnostics);
+    };
+
+    assert_eq!(error.level, Level::Error);
+    assert_eq!(error.code.as_deref(), Some("E0300"));
+    assert_eq!((error.span.start_line, error.span.start_column), (6, 16));
+    assert_eq!(
+        error.labels[0].message,
+        "expected `int` because of this parameter declaration"
+    );
+    assert_eq!(error.labels[0].span.start_line, 1);
+
+    assert_eq!(warning.level, Level::Warning);
+    assert_eq!(warning.category, Some(Category::Unused));
+    assert_eq!(warning.suggestions[0].replacement, "_y");
+
+    for diagnostic in &diagnostics {
+        let json = serde_json::to_string(diagnostic).unwrap();
+        assert_eq!(
+            &serde_json::from_str::<Diagnostic>(&json).unwrap(),
+            diagnostic
+        );
+    }
+    assert_eq!(
+        error.to_string(),
+        "error[E0300]: Argument 1 of `twice` expects `int`, found `str`\n  \
+         --> typed:6:16\n  \
+         --> typed:1:10: expected `int` because of this parameter declaration"
+    );
+}
+
+#[test]
+fn the_parser_reports_every_broken_item_and_statement() {
+    let (program, diagnostics) = rustic::parse(
+        "fn first() {\n  let = 1\n  let z = (2\n}\n\nfn second( {\n}\n\nstruct Point { x: int y: int }\n\nfn fine() -> int {\n  return 1\n}\n",
+        "recovery.rsc",
+    );
+
+    assert!(program.is_none());
+    let errors: Vec<(usize, usize, &str)> = diagnostics
+        .iter()
+        .map(|d| (d.span.start_line, d.span.start_column, d.message.as_str()))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            (2, 7, "Expected identifier, found `=`"),
+            (4, 1, "Expected `)`, found `}`"),
+            (6, 12, "Expected identifier, found `{`"),
+            (9, 23, "Expected `,` or newline after struct field"),
+        ]
+    );
+}
+
+/// Records the phases each module goes through, marks the generated code
+/// and rejects functions called `forbidden`.
+#[derive(Default)]
+struct Recorder {
+    phases: Vec<String>,
+    diagnostics: Vec<String>,
+}
+
+impl CompilerCallbacks for Recorder {
+    fn on_tokens(&mut self, module: &str, _tokens: &mut Vec<Token>) {
+        self.phases.push(format!("{} tokens", module));
+    }
+
+    fn on_ast(
+        &mut self,
+        module: &str,
+        _program: &mut Program,
+        _diagnostics: &mut DiagnosticEngine,
+    ) {
+        self.phases.push(format!("{} ast", module));
+    }
+
+    fn on_typed_ast(
+        &mut self,
+        module: &str,
+        program: &Program,
+        _types: &TypeTable,
+        diagnostics: &mut DiagnosticEngine,
+    ) {
+        self.phases.push(format!("{} typed", module));
+        for item in &program.items {
+            if let Item::Function(function) = item {
+                if function.name == "forbidden" {
+                    diagnostics.error("`forbidden` is not allowed here", function.span.clone());
+                }
+            }
+        }
+    }
+
+    fn on_generated_code(&mut self, module: &str, code: &mut String) {
+        self.phases.push(format!("{} code", module));
+        code.insert_str(0, "// checked by Recorder\n");
+    }
+
+    fn on_diagnostic(&mut self, diagnostic: &Diagnostic) {
+        self.diagnostics.push(diagnostic.message.clone());
+    }
+}
+
+#[test]
+fn callbacks_see_every_phase_and_can_stop_a_module() {
+    let project = project(&[
+        (
+            "main.rsc",
+            "import util\n\nfn main() {\n  util.double(2)\n}\n",
+        ),
+        (
+            "util.rsc",
+            "pub fn double(x: int) -> int {\n  return x * 2\n}\n",
+        ),
+    ]);
+    let output_dir = project.path().join("out");
+
+    let mut recorder = Recorder::default();
+    compiler(&output_dir)
+        .compile_with(project.path(), &mut recorder)
+        .unwrap();
+    recorder.phases.sort();
+    assert_eq!(
+        recorder.phases,
+        [
+            "main ast",
+            "main code",
+            "main tokens",
+            "main typed",
+            "util ast",
+            "util code",
+            "util tokens",
+            "util typed",
+        ]
+    );
+    assert!(fs::read_to_string(output_dir.join("util.rs"))
+        .unwrap()
+        .starts_with("// checked by Recorder\n"));
+
+    fs::write(project.path().join("util.rsc"), "pub fn forbidden() {\n}\n").unwrap();
+    fs::write(project.path().join("main.rsc"), "fn main() {\n}\n").unwrap();
+    let mut recorder = Recorder::default();
+    let error = compiler(&project.path().join("again"))
+        .compile_with(project.path(), &mut recorder)
+        .unwrap_err();
+    assert_eq!(error.counts().errors, 1);
+    assert_eq!(recorder.diagnostics, ["`forbidden` is not allowed here"]);
+    assert!(!recorder.phases.contains(&"util code".to_string()));
+    assert!(recorder.phases.contains(&"main code".to_string()));
+}
+
+#[test]
+fn a_broken_file_does_not_stop_the_others() {
+    let project = project(&[
+        ("good.rsc", "pub fn one() -> int {\n  return 1\n}\n"),
+        ("broken.rsc", "pub fn two( -> int {\n  return 2\n}\n"),
+        ("typo.rsc", "pub fn four() -> int {\n  return \"4\"\n}\n"),
+    ]);
+    let output_dir = project.path().join("out");
+
+    let error = compiler(&output_dir).compile(project.path()).unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Compilation error: 2 of 3 files failed to compile"
+    );
+    let outcomes: Vec<_> = error
+        .outcomes
+        .iter()
+        .map(|outcome| {
+            let name = Path::new(&outcome.file).file_name().unwrap();
+            (
+                name.to_str().unwrap(),
+                outcome.result.is_ok(),
+                outcome.errors,
+            )
+        })
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            ("broken.rsc", false, 1),
+            ("good.rsc", true, 0),
+            ("typo.rsc", false, 1)
+        ]
+    );
+    assert!(matches!(
+        &error.outcomes[0].result,
+        Err(Error::ParseError(message)) if message.starts_with("Expected identifier, found `->`")
+    ));
+    assert_eq!(error.diagnostics.len(), 2);
+    assert!(output_dir.join("good.rs").exists());
+    assert!(!output_dir.join("broken.rs").exists());
+    assert!(!output_dir.join("typo.rs").exists());
+}
+
+#[test]
+fn compiled_modules_are_shared_rather_than_copied() {
+    let project = project(&[
+        ("main.rsc", "import util\n\nfn main() {\n  util.show()\n}\n"),
+        (
+            "util.rsc",
+            "import io\n\npub fn show() {\n  io.print(\"hi\")\n}\n",
+        ),
+    ]);
+    let input = project.path().to_str().unwrap();
+    let output = project.path().join("out");
+    let mut diagnostics = DiagnosticEngine::new();
+    let mut compiler = RusticCompiler::new(&mut diagnostics);
+    compiler
+        .compile_directory(input, output.to_str().unwrap())
+        .unwrap();
+
+    let mut names: Vec<&str> = compiler.modules().map(|(name, _)| name).collect();
+    names.sort();
+    assert_eq!(names, ["main", "util"]);
+    let util = compiler.module("util").unwrap();
+    assert!(Arc::ptr_eq(&util, &compiler.module("util").unwrap()));
+    let (_, listed) = compiler
+        .modules()
+        .find(|(name, _)| *name == "util")
+        .unwrap();
+    assert!(Arc::ptr_eq(&util, listed));
+    assert!(matches!(&util.items[0], Item::Function(function) if function.name == "show"));
+    assert!(compiler.module("io").is_none());
+}
@@ -0,0 +1,389 @@
+use assert_cmd::Command;
+use rustic::test_support::assert_snapshot;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn json_diagnostics_leave_nothing_but_json_on_stderr() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("broken.rsc");
+    fs::write(
+        &file,
+        "import io\n\nfn main() {\n  let x: int = \"a\"\n  io.print(x)\n}\n",
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("rustic")
+        .unwrap()
+        .args(["run", "--backend", "interpret", "--message-format", "json"])
+        .arg(&file)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<Value> = stderr
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|_| panic!("not JSON: {}", line)))
+        .collect();
+    let [diagnostic, summary] = lines.as_slice() else {
+        panic!("expected a diagnostic and a summary, got {:?}", lines);
+    };
+    assert_eq!(diagnostic["level"], "error");
+    assert_eq!(diagnostic["code"], "E0300");
+    assert_eq!(summary["summary"]["errors"], 1);
+}
+
+/// The lints `rustic check` reports for a function nothing calls with a
+/// variable nothing reads, given the lint flags `flags`.
+fn lints_reported(flags: &[&str]) -> Vec<(String, String)> {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("lints.rsc");
+    fs::write(&file, "fn helper() -> void {\n  let x: int = 1\n}\n").unwrap();
+
+    let output = Command::cargo_bin("rustic")
+        .unwrap()
+        .args(["check", "--message-format", "json"])
+        .args(flags)
+        .arg(&file)
+        .output()
+        .unwrap();
+
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter_map(|diagnostic| {
+            let lint = diagnostic["lint"].as_str()?.to_string();
+            Some((lint, diagnostic["level"].as_str()?.to_string()))
+        })
+        .collect()
+}
+
+#[test]
+fn a_lint_level_overrides_its_category() {
+    let lint = |name: &str, level: &str| (name.to_string(), level.to_string());
+    assert_eq!(
+        lints_reported(&["-A", "unused-variable"]),
+        [lint("unused-function", "warning")]
+    );
+    assert_eq!(
+        lints_reported(&["-W", "unused-variable", "-A", "unused"]),
+        [lint("unused-variable", "warning")]
+    );
+    assert_eq!(
+        lints_reported(&["--deny-warnings", "-W", "unused-function"]),
+        [
+            lint("unused-function", "warning"),
+            lint("unused-variable", "error")
+        ]
+    );
+}
+
+#[test]
+fn a_failed_build_ends_with_one_summary_line() {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, source) in [
+        ("types.rsc", "fn main() {\n  let _x: int = \"a\"\n}\n"),
+        ("lexing.rsc", "fn main() {\n  let _x = $\n}\n"),
+    ] {
+        let file = dir.path().join(name);
+        fs::write(&file, source).unwrap();
+        let output = Command::cargo_bin("rustic")
+            .unwrap()
+            .arg(&file)
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(
+            stderr.lines().last(),
+            Some("error: aborting due to 1 previous error"),
+            "{}",
+            stderr
+        );
+        assert!(!stderr.contains("Error:"), "{}", stderr);
+    }
+}
+
+/// Copies the `tests/fix/sloppy.rsc` fixture into a temporary directory,
+/// runs `rustic` on the copy with `args` and checks that it was fixed and
+/// that nothing is left to report once it is checked again.
+fn fixes_the_fixture(args: &[&str]) {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("sloppy.rsc");
+    fs::copy(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fix/sloppy.rsc"),
+        &file,
+    )
+    .unwrap();
+
+    Command::cargo_bin("rustic")
+        .unwrap()
+        .current_dir(dir.path())
+        .args(args)
+        .arg(&file)
+        .assert()
+        .success();
+    assert_snapshot(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fix/sloppy.fixed"),
+        &fs::read_to_string(&file).unwrap(),
+    );
+
+    let output = Command::cargo_bin("rustic")
+        .unwrap()
+        .arg("check")
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn fix_applies_every_suggestion() {
+    fixes_the_fixture(&["fix"]);
+}
+
+#[test]
+fn the_fix_flag_applies_every_suggestion() {
+    fixes_the_fixture(&["--fix", "--output", "generated"]);
+}
+
+#[test]
+fn rustc_errors_in_a_script_point_at_its_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("recursive.rsc");
+    fs::write(
+        &file,
+        "import io\n\nstruct Node {\n  value: int,\n  next: Node\n}\n\nfn main() {\n  io.print(\"hi\")\n}\n",
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("rustic")
+        .unwrap()
+        .env("RUSTIC_CACHE_DIR", dir.path().join("cache"))
+        .arg("script")
+        .arg(&file)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("error[E0500]: the generated Rust does not compile"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains(&format!("{}:3:1", file.display())),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("rustc error[E0072]"), "{}", stderr);
+}
+
+#[test]
+fn mutation_testing_reports_the_mutants_the_output_misses() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("abs.rsc");
+    fs::write(
+        &file,
+        "import io\n\nfn abs(x: int) -> int {\n  if x < 0 {\n    return -x\n  }\n  return x\n}\n\nfn main() {\n  io.print(abs(-3))\n}\n",
+    )
+    .unwrap();
+
+    let output = Command::cargo_bin("rustic")
+        .unwrap()
+        .arg("mutate")
+        .arg(&file)
+        .arg("--output")
+        .arg(dir.path().join("mutants"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let file = file.display();
+    assert_eq!(
+        stdout,
+        format!(
+            "survived: {file}:4:6 replaced `<` with `<=`\n\
+             survived: {file}:4:10 replaced `0` with `1`\n\
+             survived: {file}:4:10 replaced `0` with `-1`\n\
+             10 mutants: 6 killed, 0 timed out, 3 survived, 1 unviable; mutation score 66.7%\n"
+        )
+    );
+}
+
+#[test]
+fn the_graph_report_names_imported_functions_by_their_module() {
+    let output = tempfile::tempdir().unwrap();
+    Command::cargo_bin("rustic")
+        .unwrap()
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/graph"))
+        .args(["--report", "graph", "--output"])
+        .arg(output.path())
+        .assert()
+        .success();
+
+    for file in ["graph.dot", "graph.json"] {
+        assert_snapshot(
+            format!("{}/tests/graph/{}", env!("CARGO_MANIFEST_DIR"), file),
+            &fs::read_to_string(output.path().join(file)).unwrap(),
+        );
+    }
+}
+
+#[test]
+fn the_xref_index_links_uses_to_definitions_across_modules() {
+    let output = Command::cargo_bin("rustic")
+        .unwrap()
+        .current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests"))
+        .args(["xref", "graph"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let index: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let definitions = index["definitions"].as_array().unwrap();
+    let mut references: Vec<String> = index["references"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|reference| {
+            let definition = &definitions[reference["definition"].as_u64().unwrap() as usize];
+            let span = &reference["span"];
+            format!(
+                "{} {}:{}:{} -> {}:{}",
+                definition["name"].as_str().unwrap(),
+                span["file"].as_str().unwrap(),
+                span["start_line"],
+                span["start_column"],
+                definition["span"]["file"].as_str().unwrap(),
+                definition["span"]["start_line"],
+            )
+        })
+        .collect();
+    references.sort();
+    assert_eq!(
+        references,
+        [
+            "area graph/main.rsc:9:21 -> graph/shapes.rsc:1",
+            "double graph/main.rsc:5:10 -> graph/util.rsc:1",
+            "side graph/shapes.rsc:2:10 -> graph/shapes.rsc:1",
+            "side graph/shapes.rsc:2:17 -> graph/shapes.rsc:1",
+            "twice graph/main.rsc:9:15 -> graph/main.rsc:4",
+            "x graph/main.rsc:5:22 -> graph/main.rsc:4",
+            "x graph/util.rsc:2:10 -> graph/util.rsc:1",
+        ]
+    );
+}
+
+#[test]
+fn scripts_reuse_their_cached_binary_until_they_change() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("echo.rsc");
+    let cache = dir.path().join("cache");
+    let script = |args: &[&str]| {
+        let output = Command::cargo_bin("rustic")
+            .unwrap()
+            .env("RUSTIC_CACHE_DIR", &cache)
+            .arg("script")
+            .arg(&file)
+            .arg("--")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let binaries = || {
+        let mut binaries: Vec<_> = fs::read_dir(&cache)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                (
+                    entry.file_name(),
+                    entry.metadata().unwrap().modified().unwrap(),
+                )
+            })
+            .collect();
+        binaries.sort();
+        binaries
+    };
+
+    fs::write(
+        &file,
+        "#!/usr/bin/env rustic script\nimport io\n\nfn main() {\n  io.print(io.args())\n}\n",
+    )
+    .unwrap();
+    assert_eq!(script(&["one", "--two"]), "[\"one\", \"--two\"]\n");
+    let built = binaries();
+    assert_eq!(built.len(), 1);
+
+    assert_eq!(script(&[]), "[]\n");
+    assert_eq!(binaries(), built);
+
+    fs::write(
+        &file,
+        "import io\n\nfn main() {\n  io.print(\"changed\")\n}\n",
+    )
+    .unwrap();
+    assert_eq!(script(&[]), "changed\n");
+    assert_eq!(binaries().len(), 2);
+}
+
+#[test]
+fn self_profiling_traces_every_phase_of_every_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = Command::cargo_bin("rustic")
+        .unwrap()
+        .current_dir(dir.path())
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/graph"))
+        .args(["--output", "out", "--self-profile"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("profile spans to rustic-profile.json")
+    );
+
+    let trace: Value =
+        serde_json::from_str(&fs::read_to_string(dir.path().join("rustic-profile.json")).unwrap())
+            .unwrap();
+    let events = trace["traceEvents"].as_array().unwrap();
+    assert!(events.iter().all(|event| event["ph"] == "X"));
+    let spans = |category: &str| -> Vec<&Value> {
+        events
+            .iter()
+            .filter(|event| event["cat"] == category)
+            .collect()
+    };
+    let [compile] = spans("driver")[..] else {
+        panic!("expected one compile span in {:?}", events);
+    };
+    assert_eq!(compile["name"], "compile");
+    let end = |event: &Value| event["ts"].as_u64().unwrap() + event["dur"].as_u64().unwrap();
+
+    let mut phases: Vec<String> = Vec::new();
+    for phase in spans("phase") {
+        let file = phase["args"]["file"].as_str().unwrap();
+        let name = Path::new(file).file_name().unwrap().to_str().unwrap();
+        phases.push(format!("{} {}", name, phase["name"].as_str().unwrap()));
+        assert!(spans("file").iter().any(|parent| parent["name"] == file
+            && parent["tid"] == phase["tid"]
+            && parent["ts"].as_u64() <= phase["ts"].as_u64()
+            && end(phase) <= end(parent)));
+        assert!(compile["ts"].as_u64() <= phase["ts"].as_u64() && end(phase) <= end(compile));
+    }
+    phases.sort();
+    let mut expected = Vec::new();
+    for file in ["main.rsc", "shapes.rsc", "util.rsc"] {
+        for phase in ["code generation", "lexing", "parsing", "semantic analysis"] {
+            expected.push(format!("{} {}", file, phase));
+        }
+    }
+    assert_eq!(phases, expected);
+}
@@ -0,0 +1,40 @@
+use rustic::fuzz::{self, FuzzOptions};
+use std::path::Path;
+
+#[test]
+fn fuzzing_the_golden_corpus_finds_no_failures() {
+    let artifacts = tempfile::tempdir().unwrap();
+    let options = FuzzOptions {
+        iterations: 500,
+        seed: 7,
+        max_len: 512,
+        corpus: Some(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")),
+        artifacts: artifacts.path().to_path_buf(),
+    };
+
+    let report = fuzz::run(&options);
+    assert_eq!(report.executed, 500);
+    let messages: Vec<_> = report.crashes.iter().map(|crash| &crash.message).collect();
+    assert!(messages.is_empty(), "{:?}", messages);
+    assert!(artifacts.path().read_dir().unwrap().next().is_none());
+}
+
+#[test]
+fn broken_input_is_rejected_with_diagnostics() {
+    for input in [
+        &b"fn main( {"[..],
+        b"let x = \"unterminated",
+        b"struct { x: int",
+        b"\xff\xfe\x00 match",
+        b"((((((((((",
+    ] {
+        assert_eq!(fuzz::check_input(input), None, "{:?}", input);
+    }
+}
+
+#[test]
+fn minimizing_keeps_only_what_the_failure_needs() {
+    let input = b"fn main() {\n  let x = 1 ! 2\n}\n";
+    let minimized = fuzz::minimize(input, |candidate| candidate.contains(&b'!'));
+    assert_eq!(minimized, b"!");
+}
@@ -0,0 +1,151 @@
+use rustic::compiler::lexer::KEYWORDS;
+use rustic::compiler::RusticCompiler;
+use rustic::diagnostics::DiagnosticEngine;
+use rustic::grammar;
+use rustic::highlight::{self, Format};
+use rustic::introspect;
+use rustic::test_support::{
+    assert_snapshot, check_golden_dir, check_pruned_dir, check_round_trip_dir,
+    render_project_diagnostics, Stage,
+};
+
+#[test]
+fn golden_snapshots() {
+    check_golden_dir(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden"),
+        &[Stage::Diagnostics, Stage::Rendered, Stage::Rust],
+    );
+}
+
+#[test]
+fn interpreted_output_snapshots() {
+    check_golden_dir(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/run"),
+        &[Stage::Output],
+    );
+}
+
+#[test]
+fn token_and_ast_snapshots() {
+    check_golden_dir(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syntax"),
+        &[Stage::Tokens, Stage::Ast],
+    );
+}
+
+#[test]
+fn introspection_snapshot() {
+    let json = serde_json::to_string_pretty(&introspect::describe()).unwrap();
+    assert_snapshot(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/introspect.json"),
+        &format!("{}\n", json),
+    );
+}
+
+#[test]
+fn highlighting_snapshots() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/highlight/sample.rsc");
+    let source = std::fs::read_to_string(path).unwrap();
+    let segments = highlight::segments(&source, "sample.rsc");
+    let text: String = segments.into_iter().map(|(_, text)| text).collect();
+    assert_eq!(text, source);
+
+    for (format, extension) in [(Format::Html, "html"), (Format::Ansi, "ansi")] {
+        assert_snapshot(
+            path.replace(".rsc", &format!(".{}", extension)),
+            &highlight::highlight(&source, "sample.rsc", format),
+        );
+    }
+}
+
+#[test]
+fn grammar_snapshot() {
+    let grammar = grammar::textmate();
+    let words: Vec<&str> = grammar["repository"]["keywords"]["patterns"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|pattern| {
+            let words = pattern["match"].as_str().unwrap();
+            words
+                .trim_start_matches("\\b(?:")
+                .trim_end_matches(")\\b")
+                .split('|')
+        })
+        .collect();
+    for keyword in KEYWORDS {
+        assert!(words.contains(keyword), "`{}` is not highlighted", keyword);
+    }
+
+    assert_snapshot(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/rustic.tmLanguage.json"),
+        &format!("{}\n", serde_json::to_string_pretty(&grammar).unwrap()),
+    );
+}
+
+#[test]
+fn introspected_functions_type_check_as_described() {
+    let description = introspect::describe();
+    let mut calls = String::new();
+    for module in description["modules"].as_array().unwrap() {
+        let module_name = module["name"].as_str().unwrap();
+        calls.push_str(&format!("import {}\n", module_name));
+        for function in module["functions"].as_array().unwrap() {
+            let arguments: Vec<&str> = function["params"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|param| match param.as_str().unwrap() {
+                    "float" => "1.5",
+                    "str" => "\"text\"",
+                    "list[str]" => "[\"text\"]",
+                    "any" => "1",
+                    other => panic!("no sample argument of type {}", other),
+                })
+                .collect();
+            let call = format!(
+                "{}.{}({})",
+                module_name,
+                function["name"].as_str().unwrap(),
+                arguments.join(", ")
+            );
+            match function["return_type"].as_str().unwrap() {
+                "void" => calls.push_str(&format!("fn _{}() {{\n  {}\n}}\n", calls.len(), call)),
+                ty => calls.push_str(&format!(
+                    "fn _{}() -> {} {{\n  return {}\n}}\n",
+                    calls.len(),
+                    ty,
+                    call
+                )),
+            }
+        }
+    }
+
+    let mut diagnostics = DiagnosticEngine::new();
+    let result = RusticCompiler::new(&mut diagnostics).compile_str(&calls, "calls");
+    let messages: Vec<String> = diagnostics.take().iter().map(|d| d.to_string()).collect();
+    assert!(
+        result.is_ok() && messages.is_empty(),
+        "{}\n{:?}",
+        calls,
+        messages
+    );
+}
+
+#[test]
+fn printed_source_round_trips() {
+    check_round_trip_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden"));
+}
+
+#[test]
+fn import_cycle_snapshot() {
+    assert_snapshot(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cycle.diagnostics"),
+        &render_project_diagnostics(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/cycle")),
+    );
+}
+
+#[test]
+fn pruned_release_snapshots() {
+    check_pruned_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/prune"));
+}
@@ -0,0 +1,17 @@
+// Generated by rustic from `aliases.rsc`. Do not edit.
+#![allow(unused, arithmetic_overflow, unconditional_panic)]
+
+use crate::tour as t;
+use crate::tour::{Point, OutOfStock};
+
+// rsc: aliases.rsc:6
+pub fn report(corner: Point) {
+    // rsc: aliases.rsc:7
+    let origin: Point = Point { x: 0.0f64, y: 0.0f64 };
+    // rsc: aliases.rsc:8
+    println!("{}", t::distance(corner.clone(), origin.clone()));
+    // rsc: aliases.rsc:9
+    println!("{}", t::label(String::from("figs"), 2i64, t::rustic_default_label_2()));
+    // rsc: aliases.rsc:10
+    println!("{}", f64::sqrt(std::f64::consts::PI));
+}
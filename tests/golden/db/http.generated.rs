@@ -0,0 +1,8 @@
+// Generated by rustic from `db/http.rsc`. Do not edit.
+#![allow(unused, arithmetic_overflow, unconditional_panic)]
+
+// rsc: db/http.rsc:2
+pub fn get(key: String) -> String {
+    // rsc: db/http.rsc:3
+    return key.clone();
+}
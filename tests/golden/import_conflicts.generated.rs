@@ -0,0 +1 @@
+Semantic error: found 2 errors
@@ -0,0 +1,118 @@
+// Generated by rustic from `imports.rsc`. Do not edit.
+#![allow(unused, arithmetic_overflow, unconditional_panic)]
+
+use crate::tour::*;
+
+// rsc: imports.rsc:5
+pub fn main() {
+    rustic_main(|| {
+        // rsc: imports.rsc:6
+        let corner: Point = Point { x: 3.0f64, y: 4.0f64 };
+        // rsc: imports.rsc:7
+        println!("{}", corner.norm());
+        // rsc: imports.rsc:8
+        println!(
+            "{}", label(String::from("pears"), rustic_default_label_1(),
+            String::from("crate"))
+        );
+        // rsc: imports.rsc:9
+        let UNIT: String = String::from("crate");
+        // rsc: imports.rsc:10
+        println!(
+            "{}", label(String::from("pears"), rustic_default_label_1(),
+            rustic_default_label_2())
+        );
+        // rsc: imports.rsc:11
+        println!("{}", label(String::from("kiwis"), 3i64, rustic_default_label_2()));
+        // rsc: imports.rsc:12
+        let caught = std::panic::catch_unwind(
+            std::panic::AssertUnwindSafe(|| {
+                // rsc: imports.rsc:13
+                take(String::from("plums"), 9i64);
+            }),
+        );
+        if let Err(payload) = caught {
+            match RusticError::from_panic(payload) {
+                RusticError::OutOfStock(error) => {
+                    // rsc: imports.rsc:15
+                    println!(
+                        "{}", format!("{}{}", String::from("no "), error.item.clone())
+                    );
+                }
+                error => std::panic::resume_unwind(error.into_panic()),
+            }
+        }
+    });
+}
+
+#[derive(Debug)]
+enum RusticError {
+    OutOfStock(OutOfStock),
+    Runtime(String),
+}
+
+impl RusticError {
+    fn exception(payload: RusticPayload) -> Result<Self, RusticPayload> {
+        let payload = match payload.downcast::<OutOfStock>() {
+            Ok(error) => return Ok(RusticError::OutOfStock(*error)),
+            Err(payload) => payload,
+        };
+        Err(payload)
+    }
+    fn is_error(payload: &(dyn std::any::Any + Send)) -> bool {
+        payload.is::<&str>() || payload.is::<String>() || payload.is::<OutOfStock>()
+    }
+    fn into_panic(self) -> RusticPayload {
+        match self {
+            RusticError::OutOfStock(error) => Box::new(error),
+            RusticError::Runtime(message) => Box::new(message),
+        }
+    }
+    fn message(&self) -> String {
+        match self {
+            RusticError::OutOfStock(error) => format!("{:?}", error),
+            RusticError::Runtime(message) => message.clone(),
+        }
+    }
+}
+
+type RusticPayload = Box<dyn std::any::Any + Send>;
+
+impl RusticError {
+    fn from_panic(payload: RusticPayload) -> Self {
+        match RusticError::exception(payload) {
+            Ok(error) => error,
+            Err(payload) => {
+                RusticError::Runtime(
+                    payload
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown error".to_string()),
+                )
+            }
+        }
+    }
+}
+
+fn rustic_main(main: impl FnOnce()) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(
+        Box::new(move |info| {
+            if !RusticError::is_error(info.payload()) {
+                default_hook(info);
+            }
+        }),
+    );
+    let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(main)) else {
+        return;
+    };
+    if !RusticError::is_error(&*payload) {
+        std::panic::resume_unwind(payload);
+    }
+    match RusticError::from_panic(payload) {
+        RusticError::Runtime(message) => eprintln!("Runtime error: {}", message),
+        error => eprintln!("Runtime error: uncaught exception {}", error.message()),
+    }
+    std::process::exit(101);
+}
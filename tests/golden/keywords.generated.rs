@@ -0,0 +1,125 @@
+// Generated by rustic from `keywords.rsc`. Do not edit.
+#![allow(unused, arithmetic_overflow, unconditional_panic)]
+
+// rsc: keywords.rsc:4
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    r#type: String,
+    r#ref: i64,
+}
+
+// rsc: keywords.rsc:9
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Move(i64),
+    Yield,
+}
+
+// rsc: keywords.rsc:14
+fn r#loop(r#where: i64) -> i64 {
+    // rsc: keywords.rsc:15
+    let mut r#mut: i64 = i64::wrapping_mul(r#where, 2i64);
+    // rsc: keywords.rsc:16
+    for r#use in (0i64..r#where) {
+        // rsc: keywords.rsc:17
+        r#mut = i64::wrapping_add(r#mut, r#use);
+    }
+    // rsc: keywords.rsc:19
+    return r#mut;
+}
+
+// rsc: keywords.rsc:22
+pub fn main() {
+    rustic_main(|| {
+        // rsc: keywords.rsc:23
+        let token: Token = Token {
+            r#type: String::from("word"),
+            r#ref: r#loop(3i64),
+        };
+        // rsc: keywords.rsc:24
+        println!("{}", token.r#type.clone());
+        // rsc: keywords.rsc:25
+        let steps: Vec<Step> = vec![Step::Move(token.r#ref), Step::Yield];
+        // rsc: keywords.rsc:26
+        for r#move in steps.clone() {
+            // rsc: keywords.rsc:27
+            let size: i64 = match r#move.clone() {
+                Step::Move(r#box) => r#box,
+                Step::Yield => 0i64,
+            };
+            // rsc: keywords.rsc:31
+            println!("{}", size);
+        }
+        // rsc: keywords.rsc:33
+        let r#static: std::rc::Rc<dyn Fn(i64) -> i64> = std::rc::Rc::new(move |
+            r#dyn: i64,
+        | -> i64 { i64::wrapping_add(r#dyn, 1i64) }) as std::rc::Rc<dyn Fn(i64) -> i64>;
+        // rsc: keywords.rsc:34
+        println!("{}", r#static(1i64));
+    });
+}
+
+#[derive(Debug)]
+enum RusticError {
+    Runtime(String),
+}
+
+impl RusticError {
+    fn exception(payload: RusticPayload) -> Result<Self, RusticPayload> {
+        Err(payload)
+    }
+    fn is_error(payload: &(dyn std::any::Any + Send)) -> bool {
+        payload.is::<&str>() || payload.is::<String>()
+    }
+    fn into_panic(self) -> RusticPayload {
+        match self {
+            RusticError::Runtime(message) => Box::new(message),
+        }
+    }
+    fn message(&self) -> String {
+        match self {
+            RusticError::Runtime(message) => message.clone(),
+        }
+    }
+}
+
+type RusticPayload = Box<dyn std::any::Any + Send>;
+
+impl RusticError {
+    fn from_panic(payload: RusticPayload) -> Self {
+        match RusticError::exception(payload) {
+            Ok(error) => error,
+            Err(payload) => {
+                RusticError::Runtime(
+                    payload
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown error".to_string()),
+                )
+            }
+        }
+    }
+}
+
+fn rustic_main(main: impl FnOnce()) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(
+        Box::new(move |info| {
+            if !RusticError::is_error(info.payload()) {
+                default_hook(info);
+            }
+        }),
+    );
+    let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(main)) else {
+        return;
+    };
+    if !RusticError::is_error(&*payload) {
+        std::panic::resume_unwind(payload);
+    }
+    match RusticError::from_panic(payload) {
+        RusticError::Runtime(message) => eprintln!("Runtime error: {}", message),
+        error => eprintln!("Runtime error: uncaught exception {}", error.message()),
+    }
+    std::process::exit(101);
+}
@@ -0,0 +1,8 @@
+// Generated by rustic from `net/ftp.rsc`. Do not edit.
+#![allow(unused, arithmetic_overflow, unconditional_panic)]
+
+// rsc: net/ftp.rsc:2
+pub fn get(path: String) -> String {
+    // rsc: net/ftp.rsc:3
+    return format!("{}{}", String::from("RETR "), path.clone());
+}
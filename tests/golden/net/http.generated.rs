@@ -0,0 +1,14 @@
+// Generated by rustic from `net/http.rsc`. Do not edit.
+#![allow(unused, arithmetic_overflow, unconditional_panic)]
+
+// rsc: net/http.rsc:2
+pub fn get(path: String) -> String {
+    // rsc: net/http.rsc:3
+    return format!("{}{}", String::from("GET "), path.clone());
+}
+
+// rsc: net/http.rsc:6
+pub fn port() -> i64 {
+    // rsc: net/http.rsc:7
+    return 80i64;
+}
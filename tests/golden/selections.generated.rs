@@ -0,0 +1,102 @@
+// Generated by rustic from `selections.rsc`. Do not edit.
+#![allow(unused, arithmetic_overflow, unconditional_panic)]
+
+use crate::tour::{
+    Point, distance, OutOfStock, label as describe,
+    rustic_default_label_1 as rustic_default_describe_1,
+    rustic_default_label_2 as rustic_default_describe_2,
+};
+
+// rsc: selections.rsc:5
+#[derive(Debug, Clone, PartialEq)]
+pub enum Size {
+    Small,
+    Large,
+}
+
+// rsc: selections.rsc:10
+pub fn main() {
+    rustic_main(|| {
+        // rsc: selections.rsc:11
+        let corner: Point = Point { x: 6.0f64, y: 8.0f64 };
+        // rsc: selections.rsc:12
+        println!("{}", distance(corner.clone(), corner.clone()));
+        // rsc: selections.rsc:13
+        println!(
+            "{}", describe(String::from("limes"), rustic_default_describe_1(),
+            String::from("bag"))
+        );
+    });
+}
+
+#[derive(Debug)]
+enum RusticError {
+    OutOfStock(OutOfStock),
+    Runtime(String),
+}
+
+impl RusticError {
+    fn exception(payload: RusticPayload) -> Result<Self, RusticPayload> {
+        let payload = match payload.downcast::<OutOfStock>() {
+            Ok(error) => return Ok(RusticError::OutOfStock(*error)),
+            Err(payload) => payload,
+        };
+        Err(payload)
+    }
+    fn is_error(payload: &(dyn std::any::Any + Send)) -> bool {
+        payload.is::<&str>() || payload.is::<String>() || payload.is::<OutOfStock>()
+    }
+    fn into_panic(self) -> RusticPayload {
+        match self {
+            RusticError::OutOfStock(error) => Box::new(error),
+            RusticError::Runtime(message) => Box::new(message),
+        }
+    }
+    fn message(&self) -> String {
+        match self {
+            RusticError::OutOfStock(error) => format!("{:?}", error),
+            RusticError::Runtime(message) => message.clone(),
+        }
+    }
+}
+
+type RusticPayload = Box<dyn std::any::Any + Send>;
+
+impl RusticError {
+    fn from_panic(payload: RusticPayload) -> Self {
+        match RusticError::exception(payload) {
+            Ok(error) => error,
+            Err(payload) => {
+                RusticError::Runtime(
+                    payload
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown error".to_string()),
+                )
+            }
+        }
+    }
+}
+
+fn rustic_main(main: impl FnOnce()) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(
+        Box::new(move |info| {
+            if !RusticError::is_error(info.payload()) {
+                default_hook(info);
+            }
+        }),
+    );
+    let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(main)) else {
+        return;
+    };
+    if !RusticError::is_error(&*payload) {
+        std::panic::resume_unwind(payload);
+    }
+    match RusticError::from_panic(payload) {
+        RusticError::Runtime(message) => eprintln!("Runtime error: {}", message),
+        error => eprintln!("Runtime error: uncaught exception {}", error.message()),
+    }
+    std::process::exit(101);
+}
@@ -0,0 +1,91 @@
+// Generated by rustic from `suppressions.rsc`. Do not edit.
+#![allow(unused, arithmetic_overflow, unconditional_panic)]
+
+// rsc: suppressions.rsc:4
+fn Scale(value: i64, factor: i64) -> i64 {
+    // rsc: suppressions.rsc:5
+    let scratch: i64 = 0i64;
+    // rsc: suppressions.rsc:7
+    let result: i64 = i64::wrapping_mul(value, factor);
+    // rsc: suppressions.rsc:9
+    return result;
+}
+
+// rsc: suppressions.rsc:12
+pub fn main() {
+    rustic_main(|| {
+        // rsc: suppressions.rsc:13
+        let scaled: i64 = Scale(2i64, 3i64);
+        // rsc: suppressions.rsc:14
+        let spare: i64 = 1i64;
+        // rsc: suppressions.rsc:16
+        let Loud: i64 = 2i64;
+        // rsc: suppressions.rsc:17
+        println!("{}", scaled);
+    });
+}
+
+#[derive(Debug)]
+enum RusticError {
+    Runtime(String),
+}
+
+impl RusticError {
+    fn exception(payload: RusticPayload) -> Result<Self, RusticPayload> {
+        Err(payload)
+    }
+    fn is_error(payload: &(dyn std::any::Any + Send)) -> bool {
+        payload.is::<&str>() || payload.is::<String>()
+    }
+    fn into_panic(self) -> RusticPayload {
+        match self {
+            RusticError::Runtime(message) => Box::new(message),
+        }
+    }
+    fn message(&self) -> String {
+        match self {
+            RusticError::Runtime(message) => message.clone(),
+        }
+    }
+}
+
+type RusticPayload = Box<dyn std::any::Any + Send>;
+
+impl RusticError {
+    fn from_panic(payload: RusticPayload) -> Self {
+        match RusticError::exception(payload) {
+            Ok(error) => error,
+            Err(payload) => {
+                RusticError::Runtime(
+                    payload
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown error".to_string()),
+                )
+            }
+        }
+    }
+}
+
+fn rustic_main(main: impl FnOnce()) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(
+        Box::new(move |info| {
+            if !RusticError::is_error(info.payload()) {
+                default_hook(info);
+            }
+        }),
+    );
+    let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(main)) else {
+        return;
+    };
+    if !RusticError::is_error(&*payload) {
+        std::panic::resume_unwind(payload);
+    }
+    match RusticError::from_panic(payload) {
+        RusticError::Runtime(message) => eprintln!("Runtime error: {}", message),
+        error => eprintln!("Runtime error: uncaught exception {}", error.message()),
+    }
+    std::process::exit(101);
+}
@@ -0,0 +1 @@
+Parse error: found 8 syntax errors
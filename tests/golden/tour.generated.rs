@@ -0,0 +1,728 @@
+// Generated by rustic from `tour.rsc`. Do not edit.
+#![allow(unused, arithmetic_overflow, unconditional_panic)]
+
+// rsc: tour.rsc:7
+/// A point in the plane.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+// rsc: tour.rsc:12
+static ORIGIN_NAME: std::sync::LazyLock<String> = std::sync::LazyLock::new(|| String::from(
+    "origin",
+));
+
+// rsc: tour.rsc:17
+/// The straight-line distance between `p1` and `p2`.
+///
+/// Always at least `0.0`.
+pub fn distance(p1: Point, p2: Point) -> f64 {
+    // rsc: tour.rsc:18
+    let dx: f64 = (p1.x - p2.x);
+    // rsc: tour.rsc:19
+    let dy: f64 = (p1.y - p2.y);
+    // rsc: tour.rsc:20
+    return f64::sqrt(((dx * dx) + (dy * dy)));
+}
+
+// rsc: tour.rsc:23
+impl Point {
+    // rsc: tour.rsc:24
+    pub fn norm(&self) -> f64 {
+        // rsc: tour.rsc:25
+        return distance(self.clone(), Point { x: 0.0f64, y: 0.0f64 });
+    }
+    // rsc: tour.rsc:29
+    /// Moves the point away from the origin by `factor`.
+    fn scale(&mut self, factor: f64) {
+        // rsc: tour.rsc:30
+        self.x = (self.x * factor);
+        // rsc: tour.rsc:31
+        self.y = (self.y * factor);
+    }
+}
+
+// rsc: tour.rsc:35
+#[derive(Debug, Clone, PartialEq)]
+enum Shape {
+    Circle(f64),
+    Rect(f64, f64),
+    Empty,
+}
+
+// rsc: tour.rsc:41
+fn area(shape: Shape) -> f64 {
+    // rsc: tour.rsc:42
+    return match shape.clone() {
+        Shape::Circle(r) => ((3.5f64 * r) * r),
+        Shape::Rect(w, h) => (w * h),
+        Shape::Empty => 0.0f64,
+    };
+}
+
+// rsc: tour.rsc:49
+#[derive(Debug, Clone, PartialEq)]
+struct Pair<A, B> {
+    first: A,
+    second: B,
+}
+
+// rsc: tour.rsc:54
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutOfStock {
+    pub item: String,
+    pub wanted: i64,
+}
+
+// rsc: tour.rsc:59
+pub fn take(item: String, wanted: i64) -> i64 {
+    // rsc: tour.rsc:60
+    if (wanted > 3i64) {
+        // rsc: tour.rsc:61
+        std::panic::panic_any::<
+            OutOfStock,
+        >(OutOfStock {
+            item: item.clone(),
+            wanted: wanted,
+        });
+    }
+    // rsc: tour.rsc:63
+    return wanted;
+}
+
+// rsc: tour.rsc:66
+static UNIT: std::sync::LazyLock<String> = std::sync::LazyLock::new(|| String::from(
+    "box",
+));
+
+// rsc: tour.rsc:68
+pub fn label(item: String, amount: i64, unit: String) -> String {
+    // rsc: tour.rsc:69
+    return format!(
+        "{}{}", format!("{}{}", format!("{}{}", format!("{}{}", amount,
+        String::from(" ")), unit.clone()), String::from(" of ")), item.clone()
+    );
+}
+
+pub fn rustic_default_label_1() -> i64 {
+    1i64
+}
+
+pub fn rustic_default_label_2() -> String {
+    String::from("box")
+}
+
+// rsc: tour.rsc:72
+fn restock(item: String) -> i64 {
+    // rsc: tour.rsc:73
+    let caught = std::panic::catch_unwind(
+        std::panic::AssertUnwindSafe(|| {
+            // rsc: tour.rsc:74
+            return Some(take(item.clone(), 5i64));
+            None
+        }),
+    );
+    let handled = std::panic::catch_unwind(
+        std::panic::AssertUnwindSafe(|| {
+            match caught {
+                Ok(returned) => returned,
+                Err(payload) => {
+                    match RusticError::from_panic(payload) {
+                        RusticError::OutOfStock(_) => {
+                            // rsc: tour.rsc:76
+                            return Some(3i64);
+                        }
+                        error => std::panic::resume_unwind(error.into_panic()),
+                    }
+                    None
+                }
+            }
+        }),
+    );
+    // rsc: tour.rsc:78
+    println!("{}", format!("{}{}", String::from("checked "), item.clone()));
+    match handled {
+        Ok(Some(value)) => return value,
+        Ok(None) => {}
+        Err(payload) => std::panic::resume_unwind(payload),
+    }
+    // rsc: tour.rsc:80
+    return 0i64;
+}
+
+// rsc: tour.rsc:83
+impl<
+    A: Clone + PartialEq + std::fmt::Debug,
+    B: Clone + PartialEq + std::fmt::Debug,
+> Pair<A, B> {
+    // rsc: tour.rsc:84
+    fn swap(&self) -> Pair<B, A> {
+        // rsc: tour.rsc:85
+        return Pair {
+            first: self.second.clone(),
+            second: self.first.clone(),
+        };
+    }
+}
+
+// rsc: tour.rsc:89
+fn first<T: Clone + PartialEq + std::fmt::Debug>(xs: Vec<T>, fallback: T) -> T {
+    // rsc: tour.rsc:90
+    for x in xs.clone() {
+        // rsc: tour.rsc:91
+        return x.clone();
+    }
+    // rsc: tour.rsc:93
+    return fallback.clone();
+}
+
+// rsc: tour.rsc:96
+fn apply_twice(f: std::rc::Rc<dyn Fn(i64) -> i64>, x: i64) -> i64 {
+    // rsc: tour.rsc:97
+    return f(f(x));
+}
+
+// rsc: tour.rsc:100
+fn increment(x: i64) -> i64 {
+    // rsc: tour.rsc:101
+    return i64::wrapping_add(x, 1i64);
+}
+
+// rsc: tour.rsc:104
+fn adder(n: i64) -> std::rc::Rc<dyn Fn(i64) -> i64> {
+    // rsc: tour.rsc:105
+    return std::rc::Rc::new(move |x: i64| -> i64 { i64::wrapping_add(x, n) })
+        as std::rc::Rc<dyn Fn(i64) -> i64>;
+}
+
+// rsc: tour.rsc:110
+fn sum_and_count(xs: Vec<i64>) -> (i64, i64) {
+    // rsc: tour.rsc:111
+    let mut sum: i64 = 0i64;
+    // rsc: tour.rsc:112
+    for x in xs.clone() {
+        // rsc: tour.rsc:113
+        sum = i64::wrapping_add(sum, x);
+    }
+    // rsc: tour.rsc:115
+    return (sum, (xs.len() as i64));
+}
+
+// rsc: tour.rsc:118
+fn find(xs: Vec<i64>, target: i64) -> Option<i64> {
+    // rsc: tour.rsc:119
+    for x in xs.clone() {
+        // rsc: tour.rsc:120
+        if (x == target) {
+            // rsc: tour.rsc:121
+            return Some(x);
+        }
+    }
+    // rsc: tour.rsc:124
+    return None;
+}
+
+// rsc: tour.rsc:128
+pub fn main() {
+    rustic_main(|| {
+        // rsc: tour.rsc:129
+        let a: Point = Point { x: 0.0f64, y: 0.0f64 };
+        // rsc: tour.rsc:130
+        let b: Point = Point { x: 3.0f64, y: 4.0f64 };
+        // rsc: tour.rsc:132
+        let d: f64 = distance(a.clone(), b.clone());
+        // rsc: tour.rsc:133
+        let mut c: Point = b.clone();
+        // rsc: tour.rsc:134
+        c.scale(2.0f64);
+        // rsc: tour.rsc:135
+        println!("{}", format!("{}{}", String::from("Norm: "), c.norm()));
+        // rsc: tour.rsc:136
+        println!("{}", format!("{}{}", String::from("Distance: "), d));
+        // rsc: tour.rsc:137
+        let mut xs: Vec<i64> = Vec::new();
+        // rsc: tour.rsc:138
+        xs.push(3i64);
+        // rsc: tour.rsc:139
+        xs.push(4i64);
+        // rsc: tour.rsc:140
+        let mut total: i64 = 0i64;
+        // rsc: tour.rsc:141
+        for x in xs.clone() {
+            // rsc: tour.rsc:142
+            if (x == 4i64) {
+                // rsc: tour.rsc:143
+                continue;
+            }
+            // rsc: tour.rsc:145
+            total = i64::wrapping_add(total, x);
+        }
+        // rsc: tour.rsc:147
+        for i in (0i64..(xs.len() as i64)) {
+            // rsc: tour.rsc:148
+            total = i64::wrapping_add(total, i);
+        }
+        // rsc: tour.rsc:150
+        let mut countdown: i64 = 3i64;
+        // rsc: tour.rsc:151
+        while (countdown > 0i64) {
+            // rsc: tour.rsc:152
+            countdown = i64::wrapping_sub(countdown, 1i64);
+            // rsc: tour.rsc:153
+            if (countdown == 1i64) {
+                // rsc: tour.rsc:154
+                break;
+            }
+        }
+        // rsc: tour.rsc:157
+        for i in (1i64..=i64::wrapping_mul(total, 2i64)) {
+            // rsc: tour.rsc:158
+            countdown = i64::wrapping_add(countdown, rustic_rem(i, 2i64));
+        }
+        // rsc: tour.rsc:160
+        if (total > 5i64) {
+            // rsc: tour.rsc:161
+            println!("{}", format!("{:?}", xs.clone()));
+        } else if (total == 0i64) {
+            // rsc: tour.rsc:163
+            println!("{}", String::from("zero"));
+        } else {
+            // rsc: tour.rsc:165
+            println!("{}", format!("{:?}", a.clone()));
+        }
+        // rsc: tour.rsc:167
+        let mut parity: String;
+        // rsc: tour.rsc:168
+        if (rustic_rem(total, 2i64) == 0i64) {
+            // rsc: tour.rsc:169
+            parity = String::from("even");
+        } else {
+            // rsc: tour.rsc:171
+            parity = String::from("odd");
+        }
+        // rsc: tour.rsc:173
+        println!("{}", parity.clone());
+        // rsc: tour.rsc:174
+        if std::panic::catch_unwind(
+                std::panic::AssertUnwindSafe(|| {
+                    // rsc: tour.rsc:175
+                    let z: i64 = rustic_div(total, i64::wrapping_sub(total, total));
+                }),
+            )
+            .is_err()
+        {
+            // rsc: tour.rsc:177
+            println!("{}", String::from("caught origin"));
+        }
+        // rsc: tour.rsc:179
+        let caught = std::panic::catch_unwind(
+            std::panic::AssertUnwindSafe(|| {
+                // rsc: tour.rsc:180
+                println!(
+                    "{}", i64::wrapping_add(take(String::from("pears"), 2i64),
+                    take(String::from("apples"), 5i64))
+                );
+            }),
+        );
+        if let Err(payload) = caught {
+            match RusticError::from_panic(payload) {
+                RusticError::OutOfStock(shortage) => {
+                    // rsc: tour.rsc:182
+                    println!(
+                        "{}", format!("{}{}", format!("{}{}", format!("{}{}",
+                        String::from("only 3 "), shortage.item.clone()),
+                        String::from(", not ")), shortage.wanted)
+                    );
+                }
+                message => {
+                    let message = message.message();
+                    // rsc: tour.rsc:184
+                    println!("{}", message.clone());
+                }
+            }
+        }
+        // rsc: tour.rsc:186
+        println!("{}", restock(String::from("plums")));
+        // rsc: tour.rsc:187
+        println!("{}", take(String::from("figs"), 1i64));
+        // rsc: tour.rsc:188
+        println!(
+            "{}", format!("{}{}", format!("{}{}", format!("{}{}", format!("{}{}",
+            label(String::from("figs"), rustic_default_label_1(),
+            rustic_default_label_2()), String::from(", ")), label(String::from("nuts"),
+            rustic_default_label_1(), String::from("bag"))), String::from(", ")),
+            label(String::from("tea"), 2i64, rustic_default_label_2()))
+        );
+        // rsc: tour.rsc:189
+        println!(
+            "{}", format!("{}{}", format!("{}{}", format!("{}{}", String::from("len "),
+            (xs.len() as i64)), String::from(" ")), String::from("Hi").to_uppercase())
+        );
+        // rsc: tour.rsc:190
+        let size: String = match total {
+            0i64 => String::from("none"),
+            1i64 => String::from("one"),
+            _ => String::from("many"),
+        };
+        // rsc: tour.rsc:195
+        let greeting: String = match size.clone().as_str() {
+            "none" => String::from("nothing here"),
+            other => {
+                let other = other.to_string();
+                format!("{}{}", String::from("found "), other.clone())
+            }
+        };
+        // rsc: tour.rsc:199
+        println!(
+            "{}", match (total > 2i64) { true => greeting.clone(), false => size.clone()
+            }
+        );
+        // rsc: tour.rsc:200
+        println!("{}", (area(Shape::Rect(2.0f64, 3.0f64)) + area(Shape::Empty)));
+        // rsc: tour.rsc:201
+        let mut ages: std::collections::BTreeMap<String, i64> = std::collections::BTreeMap::from([
+            (String::from("ada"), 36i64),
+            (String::from("alan"), 41i64),
+        ]);
+        // rsc: tour.rsc:202
+        ages.insert(
+            String::from("grace"),
+            i64::wrapping_add(ages[RusticKey(&String::from("ada"))], 1i64),
+        );
+        // rsc: tour.rsc:203
+        ages.insert(
+            String::from("grace"),
+            i64::wrapping_mul(ages[RusticKey(&String::from("grace"))], 2i64),
+        );
+        // rsc: tour.rsc:204
+        if ages.contains_key(&String::from("alan")) {
+            // rsc: tour.rsc:205
+            println!(
+                "{}", format!("{}{}", format!("{}{}", format!("{}{}",
+                String::from("ages "), (ages.len() as i64)), String::from(" ")),
+                ages[RusticKey(& String::from("grace"))])
+            );
+        }
+        // rsc: tour.rsc:207
+        let pair: Pair<String, i64> = Pair {
+            first: String::from("one"),
+            second: 1i64,
+        };
+        // rsc: tour.rsc:208
+        let swapped: Pair<i64, String> = pair.swap();
+        // rsc: tour.rsc:209
+        println!(
+            "{}", i64::wrapping_add(i64::wrapping_add(swapped.first, first(xs.clone(),
+            0i64)), (first(vec![String::from("a")], String::from("b")).chars().count() as
+            i64))
+        );
+        // rsc: tour.rsc:210
+        let found: Option<i64> = find(xs.clone(), 4i64);
+        // rsc: tour.rsc:211
+        if let Some(found) = found {
+            // rsc: tour.rsc:212
+            println!(
+                "{}", format!("{}{}", String::from("found "), i64::wrapping_mul(found,
+                2i64))
+            );
+        }
+        // rsc: tour.rsc:214
+        let mut label: Option<String> = None;
+        // rsc: tour.rsc:215
+        let mut greeting_line: String = String::from("hello");
+        // rsc: tour.rsc:216
+        greeting_line = format!(
+            "{}{}", greeting_line.clone(), format!("{}{}", String::from(" world "),
+            total)
+        );
+        // rsc: tour.rsc:217
+        println!("{}", greeting_line.clone());
+        // rsc: tour.rsc:218
+        if (found == Some(4i64)) {
+            // rsc: tour.rsc:219
+            label = Some(String::from("four"));
+        }
+        // rsc: tour.rsc:221
+        println!("{}", i64::wrapping_add(found.unwrap_or(0i64), 1i64));
+        // rsc: tour.rsc:222
+        println!("{}", label.clone().unwrap_or(String::from("unlabeled")));
+        // rsc: tour.rsc:223
+        println!(
+            "{}", format!("{} has {} items in {{braces}}, and {:?}", swapped.second
+            .clone(), i64::wrapping_mul((xs.len() as i64), 2i64), ages.clone())
+        );
+        // rsc: tour.rsc:224
+        let double: std::rc::Rc<dyn Fn(i64) -> i64> = std::rc::Rc::new(move |
+            x: i64,
+        | -> i64 { i64::wrapping_mul(x, 2i64) }) as std::rc::Rc<dyn Fn(i64) -> i64>;
+        // rsc: tour.rsc:225
+        let add_total: std::rc::Rc<dyn Fn(i64) -> i64> = adder(total);
+        // rsc: tour.rsc:226
+        println!(
+            "{}", i64::wrapping_add(i64::wrapping_add(apply_twice(double.clone(), 3i64),
+            add_total(1i64)), apply_twice(std::rc::Rc::new(move | x : i64 | -> i64 {
+            i64::wrapping_sub(x, 1i64) }) as std::rc::Rc < dyn Fn(i64) -> i64 >, 10i64))
+        );
+        // rsc: tour.rsc:227
+        let describe: std::rc::Rc<dyn Fn(String) -> String> = {
+            let xs = xs.clone();
+            std::rc::Rc::new(move |name: String| -> String {
+                // rsc: tour.rsc:228
+                return format!(
+                    "{}{}", format!("{}{}", name.clone(), String::from(": ")), (xs.len()
+                    as i64)
+                );
+            }) as std::rc::Rc<dyn Fn(String) -> String>
+        };
+        // rsc: tour.rsc:230
+        println!("{}", describe(String::from("items")));
+        // rsc: tour.rsc:231
+        let measure: std::rc::Rc<dyn Fn(Shape) -> f64> = std::rc::Rc::new(area)
+            as std::rc::Rc<dyn Fn(Shape) -> f64>;
+        // rsc: tour.rsc:232
+        println!("{}", measure(Shape::Circle(1.0f64)));
+        // rsc: tour.rsc:233
+        println!(
+            "{}", apply_twice(std::rc::Rc::new(increment) as std::rc::Rc < dyn Fn(i64) ->
+            i64 >, 0i64)
+        );
+        // rsc: tour.rsc:234
+        let (sum, count) = sum_and_count(xs.clone());
+        // rsc: tour.rsc:235
+        let (mut name, _) = (String::from("tuple"), 2i64);
+        // rsc: tour.rsc:236
+        name = format!(
+            "{}{}", name.clone(), format!("{}{}", String::from(" "), rustic_div(sum,
+            count))
+        );
+        // rsc: tour.rsc:237
+        let named: (String, f64) = (name.clone(), measure(Shape::Empty));
+        // rsc: tour.rsc:238
+        println!("{}", format!("{:?}", named.clone()));
+        // rsc: tour.rsc:239
+        println!("{}", 1000280i64);
+        // rsc: tour.rsc:240
+        println!("{}", 1500.002f64);
+        // rsc: tour.rsc:241
+        let average: f64 = ((sum as f64) / (count as f64));
+        // rsc: tour.rsc:242
+        println!(
+            "{}", format!("{}{}", format!("{}{}", ((- average) as i64).to_string(),
+            String::from(" on average, ")), ((i64::wrapping_add(total, 1i64) as f64) *
+            0.5f64))
+        );
+        // rsc: tour.rsc:243
+        let flags: i64 = 31i64;
+        // rsc: tour.rsc:244
+        println!("{}", flags);
+        // rsc: tour.rsc:245
+        println!("{}", 516i64);
+        // rsc: tour.rsc:246
+        println!("{}", f64::powf(f64::powf((2i64 as f64), 0.5f64), 2.0f64));
+        // rsc: tour.rsc:247
+        let magnitude: String = if (total > 100i64) {
+            String::from("large")
+        } else if (total > 0i64) {
+            String::from("small")
+        } else {
+            String::from("empty")
+        };
+        // rsc: tour.rsc:248
+        let maybe_count: Option<i64> = if (count > 0i64) { Some(count) } else { None };
+        // rsc: tour.rsc:249
+        let mut grid: Vec<Vec<i64>> = vec![vec![1i64, 2i64], vec![3i64, 4i64]];
+        // rsc: tour.rsc:250
+        grid[RusticIndex(1i64)][RusticIndex(0i64)] = i64::wrapping_mul(
+            xs[RusticIndex(0i64)],
+            10i64,
+        );
+        // rsc: tour.rsc:251
+        grid[RusticIndex(0i64)][RusticIndex(1i64)] = i64::wrapping_add(
+            grid[RusticIndex(0i64)][RusticIndex(1i64)],
+            grid[RusticIndex(1i64)][RusticIndex(1i64)],
+        );
+        // rsc: tour.rsc:252
+        println!(
+            "{}", format!("{:?}", grid[RusticIndex(i64::wrapping_sub(count, 1i64))]
+            .clone())
+        );
+        // rsc: tour.rsc:253
+        println!(
+            "{}", i64::wrapping_add(grid[RusticIndex(0i64)]
+            [RusticIndex(i64::wrapping_sub(count, 1i64))],
+            grid[RusticIndex(i64::wrapping_neg(1i64))]
+            [RusticIndex(i64::wrapping_neg(2i64))])
+        );
+        // rsc: tour.rsc:254
+        println!("{}", format!("{:?}", rustic_slice(& xs, 1i64, i64::MAX)));
+        // rsc: tour.rsc:255
+        println!(
+            "{}", format!("{:?}", rustic_slice(& grid, 0i64, i64::wrapping_neg(1i64)))
+        );
+        // rsc: tour.rsc:256
+        println!(
+            "{}", (rustic_slice(& xs, i64::wrapping_neg(count), count).len() as i64)
+        );
+        // rsc: tour.rsc:257
+        let evens: Vec<i64> = (0i64..10i64)
+            .into_iter()
+            .filter(|&x| (rustic_rem(x, 2i64) == 0i64))
+            .map(|x| i64::wrapping_mul(x, x))
+            .collect::<Vec<_>>();
+        // rsc: tour.rsc:258
+        println!("{}", format!("{:?}", evens.clone()));
+        // rsc: tour.rsc:259
+        println!(
+            "{}", format!("{:?}", vec![String::from("ada"), String::from("alan")]
+            .into_iter().filter(| who | (who.clone() != String::from("alan"))).map(| who
+            | format!("{}{}", who.clone(), String::from("!"))).collect::< Vec < _ >> ())
+        );
+        // rsc: tour.rsc:260
+        println!(
+            "{}", format!("{}{}", format!("{}{}", format!("{}{}", format!("{}{}",
+            magnitude.clone(), String::from(" ")), if let Some(found) = found {
+            i64::wrapping_mul(found, 2i64) } else { 0i64 }), String::from(" ")),
+            maybe_count.unwrap_or(0i64))
+        );
+    });
+}
+
+struct RusticIndex(i64);
+
+impl RusticIndex {
+    fn position(self, len: usize) -> usize {
+        let position = if self.0 < 0 { self.0 + len as i64 } else { self.0 };
+        if position < 0 || position >= len as i64 {
+            panic!("index {} is out of bounds for a list of length {}", self.0, len);
+        }
+        position as usize
+    }
+}
+
+impl<T> std::ops::Index<RusticIndex> for Vec<T> {
+    type Output = T;
+    fn index(&self, index: RusticIndex) -> &T {
+        &self[index.position(self.len())]
+    }
+}
+
+impl<T> std::ops::IndexMut<RusticIndex> for Vec<T> {
+    fn index_mut(&mut self, index: RusticIndex) -> &mut T {
+        let position = index.position(self.len());
+        &mut self[position]
+    }
+}
+
+struct RusticKey<'k, K>(&'k K);
+
+impl<K: Ord, V> std::ops::Index<RusticKey<'_, K>> for std::collections::BTreeMap<K, V> {
+    type Output = V;
+    fn index(&self, key: RusticKey<'_, K>) -> &V {
+        self.get(key.0).unwrap_or_else(|| panic!("key not found"))
+    }
+}
+
+impl<K: Ord, V> std::ops::IndexMut<RusticKey<'_, K>>
+for std::collections::BTreeMap<K, V> {
+    fn index_mut(&mut self, key: RusticKey<'_, K>) -> &mut V {
+        self.get_mut(key.0).unwrap_or_else(|| panic!("key not found"))
+    }
+}
+
+fn rustic_slice<T: Clone>(list: &[T], start: i64, end: i64) -> Vec<T> {
+    let len = list.len() as i64;
+    let bound = |index: i64| {
+        (if index < 0 { index + len } else { index }).clamp(0, len) as usize
+    };
+    let (start, end) = (bound(start), bound(end));
+    list[start..end.max(start)].to_vec()
+}
+
+fn rustic_div(left: i64, right: i64) -> i64 {
+    if right == 0 {
+        panic!("attempt to divide by zero");
+    }
+    left.checked_div(right).unwrap_or_else(|| panic!("attempt to divide with overflow"))
+}
+
+fn rustic_rem(left: i64, right: i64) -> i64 {
+    if right == 0 {
+        panic!("attempt to calculate the remainder with a divisor of zero");
+    }
+    left.checked_rem(right)
+        .unwrap_or_else(|| panic!("attempt to calculate the remainder with overflow"))
+}
+
+#[derive(Debug)]
+enum RusticError {
+    OutOfStock(OutOfStock),
+    Runtime(String),
+}
+
+impl RusticError {
+    fn exception(payload: RusticPayload) -> Result<Self, RusticPayload> {
+        let payload = match payload.downcast::<OutOfStock>() {
+            Ok(error) => return Ok(RusticError::OutOfStock(*error)),
+            Err(payload) => payload,
+        };
+        Err(payload)
+    }
+    fn is_error(payload: &(dyn std::any::Any + Send)) -> bool {
+        payload.is::<&str>() || payload.is::<String>() || payload.is::<OutOfStock>()
+    }
+    fn into_panic(self) -> RusticPayload {
+        match self {
+            RusticError::OutOfStock(error) => Box::new(error),
+            RusticError::Runtime(message) => Box::new(message),
+        }
+    }
+    fn message(&self) -> String {
+        match self {
+            RusticError::OutOfStock(error) => format!("{:?}", error),
+            RusticError::Runtime(message) => message.clone(),
+        }
+    }
+}
+
+type RusticPayload = Box<dyn std::any::Any + Send>;
+
+impl RusticError {
+    fn from_panic(payload: RusticPayload) -> Self {
+        match RusticError::exception(payload) {
+            Ok(error) => error,
+            Err(payload) => {
+                RusticError::Runtime(
+                    payload
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown error".to_string()),
+                )
+            }
+        }
+    }
+}
+
+fn rustic_main(main: impl FnOnce()) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(
+        Box::new(move |info| {
+            if !RusticError::is_error(info.payload()) {
+                default_hook(info);
+            }
+        }),
+    );
+    let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(main)) else {
+        return;
+    };
+    if !RusticError::is_error(&*payload) {
+        std::panic::resume_unwind(payload);
+    }
+    match RusticError::from_panic(payload) {
+        RusticError::Runtime(message) => eprintln!("Runtime error: {}", message),
+        error => eprintln!("Runtime error: uncaught exception {}", error.message()),
+    }
+    std::process::exit(101);
+}
@@ -0,0 +1 @@
+Semantic error: found 129 errors
@@ -0,0 +1 @@
+Semantic error: found 1 error
@@ -0,0 +1,49 @@
+use rustic::ice::{self, Phase};
+use rustic::Span;
+use std::fs;
+use std::panic;
+
+#[test]
+fn panics_write_a_bug_report_naming_the_phase_file_and_span() {
+    let temp = tempfile::tempdir().unwrap();
+    std::env::set_var("TMPDIR", temp.path());
+    ice::install_hook();
+
+    let result = panic::catch_unwind(|| {
+        let _phase = ice::enter(Phase::SemanticAnalysis, "main.rsc");
+        ice::set_span(&Span {
+            file: "main.rsc".to_string(),
+            start_line: 3,
+            start_column: 5,
+            end_line: 3,
+            end_column: 10,
+            ..Span::default()
+        });
+        panic!("no type for `total`");
+    });
+    let _ = panic::take_hook();
+
+    assert!(result.is_err());
+    let reports: Vec<_> = fs::read_dir(temp.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(reports.len(), 1);
+    let name = reports[0].file_name().unwrap().to_str().unwrap();
+    assert!(name.starts_with("rustic-ice-") && name.ends_with(".md"));
+    let report = fs::read_to_string(&reports[0]).unwrap();
+    assert!(
+        report.starts_with("## Internal compiler error\n\n**Message:** no type for `total`\n\n")
+    );
+    for row in [
+        format!("| Version | rustic {} |", env!("CARGO_PKG_VERSION")),
+        "| Phase | semantic analysis |".to_string(),
+        "| File | main.rsc |".to_string(),
+        "| Span | main.rsc:3:5 |".to_string(),
+    ] {
+        assert!(report.contains(&row), "{} is missing from\n{}", row, report);
+    }
+    assert!(report.contains("| Panicked at | tests/ice.rs:"));
+    assert!(report.contains("## Steps to reproduce"));
+    assert!(report.contains("## Backtrace\n\n```\n"));
+}
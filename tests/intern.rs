@@ -0,0 +1,26 @@
+//! The interner is shared by the whole process, so this is the only test in
+//! its binary: nothing else interns names while it counts them.
+
+use rustic::compiler::intern::Symbol;
+use rustic::{CompileOptions, Compiler};
+use std::path::Path;
+
+#[test]
+fn rebuilding_interns_no_new_names() {
+    let output = tempfile::tempdir().unwrap();
+    let compiler = Compiler::new(CompileOptions::new().output_dir(output.path()));
+    let project = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/prune");
+
+    compiler.compile(&project).unwrap();
+    let interned = Symbol::count();
+    assert!(interned > 0);
+    for _ in 0..3 {
+        compiler.compile(&project).unwrap();
+        assert_eq!(Symbol::count(), interned);
+    }
+
+    assert_eq!(Symbol::intern("main").as_str(), "main");
+    assert_eq!(Symbol::count(), interned);
+    Symbol::intern("a_name_no_build_has_seen");
+    assert_eq!(Symbol::count(), interned + 1);
+}
@@ -0,0 +1,118 @@
+use rustic::kernel::{is_complete, sign, verify, CellOutput, Session};
+use std::time::Duration;
+
+fn execute(session: &mut Session, code: &str) -> CellOutput {
+    session
+        .execute(code)
+        .unwrap_or_else(|error| panic!("{:?} failed: {}", code, error.message))
+}
+
+fn value(session: &mut Session, code: &str) -> Option<String> {
+    execute(session, code).value
+}
+
+#[test]
+fn cells_keep_the_variables_of_earlier_ones_without_running_them_again() {
+    let mut session = Session::new();
+    execute(&mut session, "import io");
+    let output = execute(&mut session, "var total = 1\nio.print(\"counted\")");
+    assert_eq!(output.stdout, "counted\n");
+
+    let output = execute(&mut session, "total = total + 1\ntotal");
+    assert_eq!(output.stdout, "");
+    assert_eq!(output.value.as_deref(), Some("2"));
+    assert_eq!(value(&mut session, "total * 10").as_deref(), Some("20"));
+}
+
+#[test]
+fn cells_call_the_functions_and_lambdas_of_earlier_ones() {
+    let mut session = Session::new();
+    execute(
+        &mut session,
+        "fn square(x: int) -> int {\n  return x * x\n}",
+    );
+    execute(&mut session, "let double: fn(int) -> int = |x| x * 2");
+    assert_eq!(
+        value(&mut session, "double(square(3))").as_deref(),
+        Some("18")
+    );
+}
+
+#[test]
+fn a_failed_cell_drops_its_variables_and_keeps_its_changes() {
+    let mut session = Session::new();
+    execute(&mut session, "var count = 1");
+
+    let error = session
+        .execute("count = 5\nlet broken = [1, 2][count]")
+        .unwrap_err();
+    assert_eq!(error.name, "RuntimeError");
+    assert_eq!(
+        error.message,
+        "index 5 is out of bounds for a list of length 2"
+    );
+
+    assert_eq!(value(&mut session, "count").as_deref(), Some("5"));
+    let error = session.execute("broken").unwrap_err();
+    assert_eq!(error.name, "CompileError");
+}
+
+#[test]
+fn an_interrupted_cell_stops_and_the_session_goes_on() {
+    let mut session = Session::new();
+    execute(&mut session, "let kept = 3");
+
+    let mut running = session
+        .start("var spins = 0\nwhile true {\n  spins = spins + 1\n}")
+        .unwrap()
+        .unwrap();
+    assert!(!running.wait(Duration::from_millis(50)));
+    running.interrupt();
+    let error = session.finish(running).unwrap_err();
+    assert_eq!(error.name, "Interrupted");
+
+    assert_eq!(value(&mut session, "kept + 1").as_deref(), Some("4"));
+}
+
+#[test]
+fn item_cells_run_nothing() {
+    let mut session = Session::new();
+    assert!(session.start("fn unused() {}").unwrap().is_none());
+    assert!(session.start("   ").unwrap().is_none());
+}
+
+#[test]
+fn completeness_follows_brackets_and_strings() {
+    assert_eq!(is_complete("let x = 1"), "complete");
+    assert_eq!(is_complete("fn f() {\n  let x = (1 +"), "incomplete");
+    assert_eq!(is_complete("let s = \"open"), "incomplete");
+    assert_eq!(is_complete("let x = 1)"), "invalid");
+}
+
+fn frames(code: &str) -> Vec<Vec<u8>> {
+    vec![
+        br#"{"msg_type":"execute_request"}"#.to_vec(),
+        b"{}".to_vec(),
+        b"{}".to_vec(),
+        format!(r#"{{"code":"{}"}}"#, code).into_bytes(),
+    ]
+}
+
+#[test]
+fn messages_are_signed_with_hmac_sha256() {
+    assert_eq!(
+        sign(b"secret", &frames("1")),
+        "dd0d53212e51bca7ca20364fe8cec8bfa4dc10f818ea74cf6997c5e465e44d33"
+    );
+    assert_eq!(sign(b"", &frames("1")), "");
+}
+
+#[test]
+fn verification_rejects_changed_messages_and_other_keys() {
+    let signature = sign(b"secret", &frames("1"));
+    assert!(verify(b"secret", &frames("1"), signature.as_bytes()));
+    assert!(!verify(b"secret", &frames("2"), signature.as_bytes()));
+    assert!(!verify(b"other", &frames("1"), signature.as_bytes()));
+    assert!(!verify(b"secret", &frames("1"), b""));
+    assert!(verify(b"", &frames("1"), b"anything"));
+}
@@ -0,0 +1,124 @@
+use rustic::compiler::lexer::{Lexer, TokenType};
+use rustic::diagnostics::DiagnosticEngine;
+
+#[test]
+fn tokens_are_produced_lazily_through_eof() {
+    let mut lexer = Lexer::new("let x = 1 $", "lazy.rsc");
+    let first: Vec<TokenType> = lexer
+        .by_ref()
+        .take(4)
+        .map(|token| token.unwrap().token_type)
+        .collect();
+    let x = TokenType::Identifier("x".into());
+    assert_eq!(
+        first,
+        [TokenType::Let, x, TokenType::Assign, TokenType::Integer(1)]
+    );
+    assert!(lexer.next().unwrap().is_err());
+    assert!(lexer.next().is_none());
+
+    let types: Vec<TokenType> = Lexer::new("f()", "lazy.rsc")
+        .map(|token| token.unwrap().token_type)
+        .collect();
+    assert_eq!(types.last(), Some(&TokenType::Eof));
+    assert_eq!(types.len(), 4);
+}
+
+#[test]
+fn spans_hold_byte_offsets_and_character_columns() {
+    let source = "let s = \"héllo🙂\" + t\nlet u = s";
+    let tokens: Vec<_> = Lexer::new(source, "utf8.rsc")
+        .map(|token| token.unwrap())
+        .collect();
+
+    let string = &tokens[3];
+    assert_eq!(string.token_type, TokenType::String("héllo🙂".to_string()));
+    assert_eq!((string.span.start_offset, string.span.end_offset), (8, 20));
+    assert_eq!((string.span.start_column, string.span.end_column), (9, 17));
+
+    let plus = &tokens[4];
+    assert_eq!(plus.token_type, TokenType::Plus);
+    assert_eq!((plus.span.start_line, plus.span.start_column), (1, 18));
+    assert_eq!(plus.span.slice(source), Some("+"));
+
+    let last = &tokens[tokens.len() - 2];
+    assert_eq!((last.span.start_line, last.span.start_column), (2, 9));
+    assert_eq!(last.span.slice(source), Some("s"));
+}
+
+#[test]
+fn lexing_reports_each_bad_character_and_carries_on() {
+    let mut diagnostics = DiagnosticEngine::new();
+    let tokens = Lexer::new("let a = $\nlet b = `\nlet c = 3", "bad.rsc")
+        .tokenize_reporting(&mut diagnostics);
+
+    let reported: Vec<(usize, &str)> = diagnostics
+        .iter()
+        .map(|d| (d.span.start_line, d.message.as_str()))
+        .collect();
+    assert_eq!(
+        reported,
+        [
+            (1, "Unexpected character: $"),
+            (2, "Unexpected character: `"),
+        ]
+    );
+    let errors = tokens
+        .iter()
+        .filter(|token| matches!(token.token_type, TokenType::Error(_)))
+        .count();
+    assert_eq!(errors, 2);
+    let last: Vec<&TokenType> = tokens[tokens.len() - 3..]
+        .iter()
+        .map(|token| &token.token_type)
+        .collect();
+    assert_eq!(
+        last,
+        [&TokenType::Assign, &TokenType::Integer(3), &TokenType::Eof]
+    );
+}
+
+#[test]
+fn multibyte_characters_keep_offsets_and_columns_apart() {
+    let source = "// ünïcödé → ok\nlet π = \"ß\" ¬ é\n";
+    let mut diagnostics = DiagnosticEngine::new();
+    let tokens = Lexer::new(source, "wide.rsc").tokenize_reporting(&mut diagnostics);
+    let spans: Vec<(&str, usize, usize, usize, usize)> = tokens
+        .iter()
+        .map(|token| {
+            let span = &token.span;
+            let text = span.slice(source).unwrap();
+            (
+                text,
+                span.start_offset,
+                span.end_offset,
+                span.start_line,
+                span.start_column,
+            )
+        })
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            ("\n", 21, 22, 1, 16),
+            ("let", 22, 25, 2, 1),
+            ("π", 26, 28, 2, 5),
+            ("=", 29, 30, 2, 7),
+            ("\"ß\"", 31, 35, 2, 9),
+            ("¬", 36, 38, 2, 13),
+            ("é", 39, 41, 2, 15),
+            ("\n", 41, 42, 2, 16),
+            ("", 42, 42, 3, 1),
+        ]
+    );
+    assert_eq!(tokens[4].token_type, TokenType::String("ß".to_string()));
+    let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Unexpected character: π",
+            "Unexpected character: ¬",
+            "Unexpected character: é",
+        ]
+    );
+}
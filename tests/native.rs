@@ -0,0 +1,191 @@
+use assert_cmd::Command;
+use std::fs;
+use std::path::Path;
+
+/// Builds `tests/run/{name}.rsc` natively and runs it, checking that it
+/// prints what the interpreter printed into its snapshot, and that only an
+/// uncaught error reaches stderr.
+fn runs_like_the_interpreter(name: &str) {
+    let run = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/run");
+    let expected = fs::read_to_string(run.join(format!("{}.output", name))).unwrap();
+    let (stdout, stderr) = match expected.rfind("Runtime error: ") {
+        Some(start) => expected.split_at(start),
+        None => (expected.as_str(), ""),
+    };
+
+    let cache = tempfile::tempdir().unwrap();
+    let output = Command::cargo_bin("rustic")
+        .unwrap()
+        .arg("run")
+        .arg(run.join(format!("{}.rsc", name)))
+        .env("XDG_CACHE_HOME", cache.path())
+        .output()
+        .unwrap();
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout), stdout);
+    assert_eq!(String::from_utf8_lossy(&output.stderr), stderr);
+}
+
+#[test]
+fn caught_errors_are_not_reported() {
+    runs_like_the_interpreter("errors");
+}
+
+#[test]
+fn runtime_errors_are_reported_once() {
+    runs_like_the_interpreter("powers");
+}
+
+#[test]
+fn defaults_are_evaluated_where_they_are_declared() {
+    runs_like_the_interpreter("defaults");
+}
+
+#[test]
+fn native_builds_write_each_module_once() {
+    let input = tempfile::tempdir().unwrap();
+    fs::write(
+        input.path().join("util.rsc"),
+        "pub fn two() -> int {\n  return 2\n}\n",
+    )
+    .unwrap();
+    let main = "import util\nimport io\n\nfn main() {\n  io.print(util.two())\n}\n";
+    fs::write(input.path().join("main.rsc"), main).unwrap();
+
+    let output = tempfile::tempdir().unwrap();
+    Command::cargo_bin("rustic")
+        .unwrap()
+        .arg(input.path())
+        .arg("--output")
+        .arg(output.path())
+        .arg("--compile")
+        .assert()
+        .success();
+
+    let src = output.path().join("src");
+    for file in ["lib.rs", "main.rs", "main_module.rs", "util.rs"] {
+        assert!(src.join(file).exists(), "src/{} was not written", file);
+    }
+    for file in ["main.rs", "util.rs"] {
+        assert!(
+            !output.path().join(file).exists(),
+            "{} was written outside src/",
+            file
+        );
+    }
+    let binary = output.path().join("target/release/rustic-generated");
+    let printed = std::process::Command::new(binary).output().unwrap().stdout;
+    assert_eq!(String::from_utf8_lossy(&printed), "2\n");
+}
+
+/// Runs `body` as the body of `main` with both backends and checks they
+/// print the same, returning what the interpreter printed to stdout and
+/// stderr.
+fn backends_agree(body: &str) -> (String, String) {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("arithmetic.rsc");
+    fs::write(&source, format!("import io\n\nfn main() {{\n{}}}\n", body)).unwrap();
+
+    let cache = tempfile::tempdir().unwrap();
+    let run = |backend: &str| {
+        let output = Command::cargo_bin("rustic")
+            .unwrap()
+            .args(["run", "--backend", backend])
+            .arg(&source)
+            .env("XDG_CACHE_HOME", cache.path())
+            .output()
+            .unwrap();
+        (
+            String::from_utf8_lossy(&output.stdout).into_owned(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+    };
+    let interpreted = run("interpret");
+    assert_eq!(run("native"), interpreted);
+    interpreted
+}
+
+#[test]
+fn addition_wraps_on_overflow() {
+    let (stdout, _) = backends_agree(
+        "  let x: int = 9223372036854775807\n  io.print(x + 1)\n  var y = x\n  y += 2\n  io.print(y)\n",
+    );
+    assert_eq!(stdout, "-9223372036854775808\n-9223372036854775807\n");
+}
+
+#[test]
+fn subtraction_wraps_on_overflow() {
+    let (stdout, _) = backends_agree(
+        "  let x: int = -9223372036854775807\n  io.print(x - 2)\n  var y = x\n  y -= 1\n  io.print(-y)\n",
+    );
+    assert_eq!(stdout, "9223372036854775807\n-9223372036854775808\n");
+}
+
+#[test]
+fn multiplication_wraps_on_overflow() {
+    let (stdout, _) = backends_agree(
+        "  let x: int = 4611686018427387904\n  io.print(x * 2)\n  var y = x\n  y *= 4\n  io.print(y)\n",
+    );
+    assert_eq!(stdout, "-9223372036854775808\n0\n");
+}
+
+#[test]
+fn division_by_zero_is_a_runtime_error() {
+    let (stdout, stderr) =
+        backends_agree("  let a = 10\n  let b = 0\n  io.print(a / 5)\n  io.print(a / b)\n");
+    assert_eq!(stdout, "2\n");
+    assert_eq!(stderr, "Runtime error: attempt to divide by zero\n");
+}
+
+#[test]
+fn remainder_by_zero_is_a_runtime_error() {
+    let (stdout, stderr) =
+        backends_agree("  let a = 10\n  var b = 0\n  io.print(a % 4)\n  b %= b\n");
+    assert_eq!(stdout, "2\n");
+    assert_eq!(
+        stderr,
+        "Runtime error: attempt to calculate the remainder with a divisor of zero\n"
+    );
+}
+
+#[test]
+fn shifting_left_too_far_is_a_runtime_error() {
+    let (stdout, stderr) =
+        backends_agree("  let n: int = 64\n  io.print(1 << 63)\n  io.print(1 << n)\n");
+    assert_eq!(stdout, "-9223372036854775808\n");
+    assert_eq!(
+        stderr,
+        "Runtime error: attempt to shift left with overflow\n"
+    );
+}
+
+#[test]
+fn shifting_right_by_a_negative_number_is_a_runtime_error() {
+    let (stdout, stderr) =
+        backends_agree("  let n = -1\n  io.print(-8 >> 2)\n  io.print(8 >> n)\n");
+    assert_eq!(stdout, "-2\n");
+    assert_eq!(
+        stderr,
+        "Runtime error: attempt to shift right with overflow\n"
+    );
+}
+
+#[test]
+fn a_constant_index_out_of_bounds_is_reported_like_the_interpreter() {
+    let (stdout, stderr) =
+        backends_agree("  let xs = [1, 2, 3]\n  io.print(xs[2])\n  io.print(xs[5])\n");
+    assert_eq!(stdout, "3\n");
+    assert_eq!(
+        stderr,
+        "Runtime error: index 5 is out of bounds for a list of length 3\n"
+    );
+}
+
+#[test]
+fn a_missing_key_is_reported_like_the_interpreter() {
+    let (stdout, stderr) = backends_agree(
+        "  let ages = {\"ada\": 36}\n  io.print(ages[\"ada\"])\n  io.print(ages[\"grace\"])\n",
+    );
+    assert_eq!(stdout, "36\n");
+    assert_eq!(stderr, "Runtime error: key not found\n");
+}
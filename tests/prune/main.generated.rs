@@ -0,0 +1,113 @@
+// Generated by rustic from `main.rsc`. Do not edit.
+#![allow(unused, arithmetic_overflow, unconditional_panic)]
+
+use crate::shapes::{Square, area};
+
+// rsc: main.rsc:7
+#[derive(Debug, Clone, PartialEq)]
+enum Color {
+    Red,
+    Green,
+}
+
+// rsc: main.rsc:16
+#[derive(Debug, Clone, PartialEq)]
+struct Counter {
+    count: i64,
+}
+
+// rsc: main.rsc:20
+impl Counter {
+    // rsc: main.rsc:21
+    fn next(&mut self) -> i64 {
+        // rsc: main.rsc:22
+        self.count = i64::wrapping_add(self.count, 1i64);
+        // rsc: main.rsc:23
+        return self.count;
+    }
+}
+
+// rsc: main.rsc:31
+fn helper() -> i64 {
+    // rsc: main.rsc:32
+    return 2i64;
+}
+
+// rsc: main.rsc:39
+pub fn main() {
+    rustic_main(|| {
+        // rsc: main.rsc:40
+        let mut counter: Counter = Counter { count: 0i64 };
+        // rsc: main.rsc:41
+        let color: Color = Color::Red;
+        // rsc: main.rsc:42
+        println!("{}", i64::wrapping_add(counter.next(), helper()));
+        // rsc: main.rsc:43
+        println!("{}", area(Square { side : 3i64 }));
+    });
+}
+
+#[derive(Debug)]
+enum RusticError {
+    Runtime(String),
+}
+
+impl RusticError {
+    fn exception(payload: RusticPayload) -> Result<Self, RusticPayload> {
+        Err(payload)
+    }
+    fn is_error(payload: &(dyn std::any::Any + Send)) -> bool {
+        payload.is::<&str>() || payload.is::<String>()
+    }
+    fn into_panic(self) -> RusticPayload {
+        match self {
+            RusticError::Runtime(message) => Box::new(message),
+        }
+    }
+    fn message(&self) -> String {
+        match self {
+            RusticError::Runtime(message) => message.clone(),
+        }
+    }
+}
+
+type RusticPayload = Box<dyn std::any::Any + Send>;
+
+impl RusticError {
+    fn from_panic(payload: RusticPayload) -> Self {
+        match RusticError::exception(payload) {
+            Ok(error) => error,
+            Err(payload) => {
+                RusticError::Runtime(
+                    payload
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "unknown error".to_string()),
+                )
+            }
+        }
+    }
+}
+
+fn rustic_main(main: impl FnOnce()) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(
+        Box::new(move |info| {
+            if !RusticError::is_error(info.payload()) {
+                default_hook(info);
+            }
+        }),
+    );
+    let Err(payload) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(main)) else {
+        return;
+    };
+    if !RusticError::is_error(&*payload) {
+        std::panic::resume_unwind(payload);
+    }
+    match RusticError::from_panic(payload) {
+        RusticError::Runtime(message) => eprintln!("Runtime error: {}", message),
+        error => eprintln!("Runtime error: uncaught exception {}", error.message()),
+    }
+    std::process::exit(101);
+}
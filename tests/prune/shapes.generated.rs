@@ -0,0 +1,14 @@
+// Generated by rustic from `shapes.rsc`. Do not edit.
+#![allow(unused, arithmetic_overflow, unconditional_panic)]
+
+// rsc: shapes.rsc:1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Square {
+    pub side: i64,
+}
+
+// rsc: shapes.rsc:9
+pub fn area(square: Square) -> i64 {
+    // rsc: shapes.rsc:10
+    return i64::wrapping_mul(square.side, square.side);
+}
@@ -0,0 +1,141 @@
+use rustic::compiler::cache::{StableHasher, CACHE_FILE};
+use rustic::compiler::query::Database;
+use rustic::{CompileOptions, CompileOutput, Compiler};
+use std::collections::BTreeSet;
+use std::fs;
+use std::hash::Hasher;
+use std::path::Path;
+
+/// A database of modules with sources hashing to 1, each importing the
+/// modules listed with it, all compiled.
+fn compiled(modules: &[(&str, &[&str])]) -> Database {
+    let mut database = Database::new();
+    for (name, imports) in modules {
+        database.set_source(name, 1);
+        database.set_imports(name, imports.iter().copied());
+    }
+    for (name, _) in modules {
+        database.compiled(name);
+    }
+    database
+}
+
+fn names(names: &[&str]) -> BTreeSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn new_modules_are_outdated() {
+    let mut database = Database::new();
+    database.set_source("main", 1);
+    assert_eq!(database.outdated(), names(&["main"]));
+    database.compiled("main");
+    assert!(database.outdated().is_empty());
+}
+
+#[test]
+fn a_change_outdates_the_modules_importing_it() {
+    let mut database = compiled(&[
+        ("main", &["app"]),
+        ("app", &["util"]),
+        ("util", &[]),
+        ("other", &["io"]),
+    ]);
+    database.set_source("util", 1);
+    assert!(database.outdated().is_empty());
+    database.set_source("util", 2);
+    assert_eq!(database.outdated(), names(&["app", "main", "util"]));
+}
+
+#[test]
+fn removing_a_module_outdates_its_importers_and_parent() {
+    let mut database = compiled(&[("main", &["net.http"]), ("net", &[]), ("net.http", &[])]);
+    database.remove("net.http");
+    assert_eq!(database.outdated(), names(&["main", "net"]));
+}
+
+#[test]
+fn failed_modules_stay_outdated_until_they_compile() {
+    let mut database = compiled(&[("main", &["util"]), ("util", &[])]);
+    database.invalidate("util");
+    assert_eq!(database.outdated(), names(&["util"]));
+    database.compiled("util");
+    assert!(database.outdated().is_empty());
+}
+
+#[test]
+fn cache_hashes_are_fixed() {
+    // The first eight bytes of SHA-256("abc"), little-endian.
+    let mut hasher = StableHasher::default();
+    hasher.write(b"abc");
+    assert_eq!(hasher.finish(), 0xeacf_018f_bf16_78ba);
+}
+
+/// A project where `main` imports `util` and `other` imports nothing, in a
+/// temporary directory with its output directory beside it.
+fn project() -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let sources = dir.path().join("src");
+    fs::create_dir(&sources).unwrap();
+    let files = [
+        (
+            "main.rsc",
+            "import io\nimport util\n\nfn main() {\n  io.print(util.twice(2))\n}\n",
+        ),
+        (
+            "util.rsc",
+            "pub fn twice(x: int) -> int {\n  return 2 * x\n}\n",
+        ),
+        ("other.rsc", "pub fn unused() {\n  let x = 1\n}\n"),
+    ];
+    for (name, source) in files {
+        fs::write(sources.join(name), source).unwrap();
+    }
+    dir
+}
+
+fn build(project: &Path) -> CompileOutput {
+    let options = CompileOptions::new()
+        .output_dir(project.join("out"))
+        .incremental(true);
+    Compiler::new(options).compile(project.join("src")).unwrap()
+}
+
+fn compiled_modules(output: &CompileOutput) -> BTreeSet<String> {
+    output.modules.keys().cloned().collect()
+}
+
+#[test]
+fn editing_a_module_recompiles_only_it_and_its_importers() {
+    let project = project();
+    let first = build(project.path());
+    assert_eq!(compiled_modules(&first), names(&["main", "other", "util"]));
+
+    fs::write(
+        project.path().join("src/util.rsc"),
+        "pub fn twice(x: int) -> int {\n  return x + x\n}\n",
+    )
+    .unwrap();
+    let second = build(project.path());
+    assert_eq!(compiled_modules(&second), names(&["main", "util"]));
+    let cached: Vec<bool> = second
+        .outcomes
+        .iter()
+        .map(|outcome| outcome.cached)
+        .collect();
+    assert_eq!(cached.iter().filter(|cached| **cached).count(), 1);
+}
+
+#[test]
+fn a_later_build_reloads_the_cache_file() {
+    let project = project();
+    let first = build(project.path());
+    assert!(project.path().join("out").join(CACHE_FILE).is_file());
+
+    let second = build(project.path());
+    assert!(compiled_modules(&second).is_empty());
+    assert!(second.outcomes.iter().all(|outcome| outcome.cached));
+    // The warnings of the modules skipped are reported again.
+    assert_eq!(second.counts().warnings, first.counts().warnings);
+    assert!(second.counts().warnings > 0);
+}
@@ -0,0 +1,157 @@
+use rustic::compiler::RusticCompiler;
+use rustic::diagnostics::{sort_diagnostics, DiagnosticEngine};
+use rustic::render::{self, SourceMap};
+use rustic::{Diagnostic, Span};
+
+fn span(file: &str, line: usize, start_column: usize, end_column: usize) -> Span {
+    Span {
+        file: file.to_string(),
+        start_line: line,
+        start_column,
+        end_line: line,
+        end_column,
+        ..Span::default()
+    }
+}
+
+fn sources() -> SourceMap {
+    let mut sources = SourceMap::new();
+    sources.add(
+        "main.rsc",
+        "fn main() {\n\tlet x: int = shapes.area(\"a\")\n}\n",
+    );
+    sources.add(
+        "shapes.rsc",
+        "pub fn area(side: int) -> int {\n  return side * side\n}\n",
+    );
+    sources
+}
+
+/// `text` without its ANSI escape sequences.
+fn strip_colors(text: &str) -> String {
+    let mut plain = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('\x1b') {
+        plain.push_str(&rest[..start]);
+        let end = rest[start..].find('m').unwrap();
+        rest = &rest[start + end + 1..];
+    }
+    plain.push_str(rest);
+    plain
+}
+
+#[test]
+fn snippets_underline_spans_and_labels_in_other_files() {
+    let diagnostic = Diagnostic::error(
+        "Argument 1 of `area` expects `int`, found `str`",
+        span("main.rsc", 2, 26, 29),
+    )
+    .with_code("E0300")
+    .with_label(span("shapes.rsc", 1, 13, 22), "parameter declared here");
+
+    let plain = render::render(&diagnostic, &mut sources(), false);
+    assert_eq!(
+        plain,
+        "error[E0300]: Argument 1 of `area` expects `int`, found `str`
+ --> main.rsc:2:26
+  |
+2 | \tlet x: int = shapes.area(\"a\")
+  | \t                        ^^^
+ ::: shapes.rsc:1:13
+  |
+1 | pub fn area(side: int) -> int {
+  |             --------- parameter declared here"
+    );
+
+    let colored = render::render(&diagnostic, &mut sources(), true);
+    assert_eq!(strip_colors(&colored), plain);
+    assert!(colored.contains("\x1b[1;31m^^^\x1b[0m"));
+    assert!(colored.contains("\x1b[1;34m---------\x1b[0m"));
+}
+
+#[test]
+fn checked_code_reports_labels_notes_and_help() {
+    let source = "fn area() -> int {\n  return 1\n}\n\nfn area() -> int {\n  return 2\n}\n\nfn main() {\n  let _count = 1\n  if true {\n    let _count = 2\n  }\n}\n";
+    let mut diagnostics = DiagnosticEngine::new();
+    let result = RusticCompiler::new(&mut diagnostics).compile_str(source, "dupes.rsc");
+    assert!(result.is_err());
+    let mut sources = SourceMap::new();
+    sources.add("dupes.rsc", source);
+    let mut diagnostics = diagnostics.take();
+    sort_diagnostics(&mut diagnostics);
+    let rendered: Vec<String> = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.span.start_line != 5 || diagnostic.lint.is_none())
+        .map(|diagnostic| render::render(diagnostic, &mut sources, false))
+        .collect();
+
+    assert_eq!(
+        rendered,
+        [
+            "warning: Unused function `area`
+ --> dupes.rsc:1:4
+  |
+1 | fn area() -> int {
+  |    ^^^^
+  = note: `unused` warnings are on; `-A unused` turns them off
+  = help: remove it, or prefix its name with an underscore (`_area`) if this is intentional",
+            "error[E0203]: Function `area` is defined more than once
+ --> dupes.rsc:5:1
+  |
+5 | fn area() -> int {
+  | ^^^^^^^^^^^^^^^^^^
+  |
+1 | fn area() -> int {
+  | ------------------ first defined here",
+            "warning: `_count` shadows a variable from an enclosing scope
+  --> dupes.rsc:12:5
+   |
+12 |     let _count = 2
+   |     ^^^^^^^^^^^^^^
+   |
+10 |   let _count = 1
+   |   -------------- shadowed declaration here
+   = note: `shadowing` warnings are on; `-A shadowing` turns them off",
+        ]
+    );
+}
+
+#[test]
+fn reports_drop_repeats_and_follow_reading_order() {
+    let late = Diagnostic::warning("late", span("main.rsc", 3, 1, 2));
+    let first = Diagnostic::error("first", span("main.rsc", 1, 1, 3));
+    let same_place = Diagnostic::error("same place, reported later", span("main.rsc", 1, 1, 3));
+    let repeat = first.clone().with_note("found again by another pass");
+    let other_file = Diagnostic::error("in shapes", span("shapes.rsc", 2, 3, 9));
+
+    let mut diagnostics = vec![other_file, late, first, repeat, same_place];
+    let report = render::report(&diagnostics, &mut SourceMap::new(), false);
+    assert_eq!(
+        report,
+        "── main.rsc ──
+error: first
+ --> main.rsc:1:1
+error: same place, reported later
+ --> main.rsc:1:1
+warning: late
+ --> main.rsc:3:1
+── shapes.rsc ──
+error: in shapes
+ --> shapes.rsc:2:3
+error: aborting due to 3 previous errors; 1 warning emitted
+"
+    );
+
+    sort_diagnostics(&mut diagnostics);
+    let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        ["first", "same place, reported later", "late", "in shapes"]
+    );
+    assert!(diagnostics[0].notes.is_empty());
+
+    let mut engine = DiagnosticEngine::new();
+    engine.emit(diagnostics[0].clone());
+    engine.emit(diagnostics[0].clone().with_help("reported twice"));
+    assert_eq!(engine.diagnostics(), &diagnostics[..1]);
+}
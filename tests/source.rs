@@ -0,0 +1,32 @@
+use rustic::compiler::source::{SourceFile, MMAP_THRESHOLD};
+use std::fs;
+
+/// A temporary source of `MMAP_THRESHOLD` bytes, large enough to be mapped.
+fn large_source() -> (tempfile::TempDir, std::path::PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("large.rsc");
+    let line = "// padding\n";
+    let lines = MMAP_THRESHOLD as usize / line.len() + 1;
+    fs::write(&path, line.repeat(lines)).unwrap();
+    (dir, path)
+}
+
+#[test]
+fn only_mapping_maps_large_sources() {
+    let (_dir, path) = large_source();
+    let mapped = SourceFile::map(&path).unwrap();
+    let read = SourceFile::read(&path).unwrap();
+    assert!(mapped.is_mapped());
+    assert!(!read.is_mapped());
+    assert_eq!(&*mapped, &*read);
+}
+
+#[test]
+fn mapped_sources_must_be_utf8() {
+    let (_dir, path) = large_source();
+    let mut bytes = fs::read(&path).unwrap();
+    bytes[0] = 0xff;
+    fs::write(&path, bytes).unwrap();
+    let error = SourceFile::map(&path).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}